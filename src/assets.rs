@@ -1,25 +1,36 @@
 use std::path::{PathBuf, Path};
+#[cfg(feature = "render")]
 use std::fs;
-use std::lazy::SyncLazy;
-use rustc_hash::FxHashMap;
+use once_cell::sync::Lazy;
 use parking_lot::RwLock;
-use std::sync::Arc;
 
 use anyhow::Result;
 use image::DynamicImage;
 
-use crate::render::RenderContext;
-use crate::render::model::Model;
+// models are uploaded to the gpu when they are loaded, so they can only be loaded with the renderer
+#[cfg(feature = "render")]
+use std::sync::Arc;
+#[cfg(feature = "render")]
+use rustc_hash::FxHashMap;
+#[cfg(feature = "render")]
+use crate::render::{RenderContext, model::Model};
+
+// texture packs are folders in here, laid out like the resource folder
+const PACKS_FOLDER: &str = "packs";
 
-static LOADER: SyncLazy<AssetLoader> = SyncLazy::new(|| AssetLoader::from_path(PathBuf::from("res/")));
+static LOADER: Lazy<AssetLoader> = Lazy::new(|| AssetLoader::from_path(PathBuf::from("res/")));
 
 pub fn loader() -> &'static AssetLoader {
 	&LOADER
 }
 
 // this is realy basic for now, may be improved in future
+// resources are looked up in the active pack first, then in the resource folder
 pub struct AssetLoader {
 	resource_folder: PathBuf,
+	// name of the folder in the packs folder which is checked before the resource folder
+	active_pack: RwLock<Option<String>>,
+	#[cfg(feature = "render")]
 	cached_models: RwLock<FxHashMap<PathBuf, Arc<Model>>>,
 }
 
@@ -27,16 +38,66 @@ impl AssetLoader {
 	fn from_path(resource_folder: PathBuf) -> Self {
 		Self {
 			resource_folder,
+			active_pack: RwLock::new(None),
+			#[cfg(feature = "render")]
 			cached_models: RwLock::new(FxHashMap::default()),
 		}
 	}
 
+	fn packs_folder(&self) -> PathBuf {
+		self.resource_folder.join(PACKS_FOLDER)
+	}
+
+	// the resource in the active pack if the pack has it, otherwise the resource in the resource folder
 	fn path_of<T: AsRef<Path>>(&self, resource: T) -> PathBuf {
+		if let Some(pack) = &*self.active_pack.read() {
+			let mut path = self.packs_folder();
+			path.push(pack);
+			path.push(&resource);
+			if path.exists() {
+				return path;
+			}
+		}
+
 		let mut path = self.resource_folder.clone();
 		path.push(resource);
 		path
 	}
 
+	// packs are only picked in the settings, so they can only be changed with the renderer
+	#[cfg(feature = "render")]
+	pub fn active_pack(&self) -> Option<String> {
+		self.active_pack.read().clone()
+	}
+
+	// cached models are dropped, since they could come from the old pack
+	// anything else already loaded must be reloaded by whatever loaded it
+	#[cfg(feature = "render")]
+	pub fn set_active_pack(&self, pack: Option<String>) {
+		*self.active_pack.write() = pack;
+		self.cached_models.write().clear();
+	}
+
+	// names of the folders in the packs folder, sorted by name
+	#[cfg(feature = "render")]
+	pub fn available_packs(&self) -> Vec<String> {
+		let entries = match fs::read_dir(self.packs_folder()) {
+			Ok(entries) => entries,
+			// there are no packs if the folder doesn't exist
+			Err(_) => return Vec::new(),
+		};
+
+		let mut packs = entries
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| entry.path().is_dir())
+			.filter_map(|entry| entry.file_name().into_string().ok())
+			.collect::<Vec<_>>();
+		packs.sort();
+		packs
+	}
+
+	// only the renderer loads raw files, for its shaders
+	#[cfg(feature = "render")]
 	pub fn load_bytes<T: AsRef<Path>>(&self, file: T) -> Result<Vec<u8>> {
 		Ok(fs::read(&self.path_of(file))?)
 	}
@@ -45,13 +106,24 @@ impl AssetLoader {
 		Ok(image::open(&self.path_of(file))?)
 	}
 
-	/*pub fn load_obj<T: AsRef<Path>>(&self, file: T) -> Result<(Vec<tobj::Model>, Vec<tobj::Material>)> {
+	// meshes are triangulated and have a single index, so they can be uploaded to the gpu directly
+	// the material library is loaded relative to the obj file
+	#[cfg(feature = "render")]
+	pub fn load_obj<T: AsRef<Path>>(&self, file: T) -> Result<(Vec<tobj::Model>, Vec<tobj::Material>)> {
 		let (obj_meshes, obj_materials) = tobj::load_obj(&self.path_of(file), &tobj::GPU_LOAD_OPTIONS)?;
 		let obj_materials = obj_materials?;
 		Ok((obj_meshes, obj_materials))
-	}*/
+	}
 
-	/*pub fn load_model_cached<T: AsRef<Path>>(&self, file: T, context: RenderContext) -> Result<Arc<Model>> {
+	// external buffers and images are loaded relative to the gltf file
+	#[cfg(feature = "render")]
+	pub fn load_gltf<T: AsRef<Path>>(&self, file: T) -> Result<(gltf::Document, Vec<gltf::buffer::Data>, Vec<gltf::image::Data>)> {
+		Ok(gltf::import(self.path_of(file))?)
+	}
+
+	// gltf and glb files are loaded with Model::load_gltf, anything else is loaded as an obj
+	#[cfg(feature = "render")]
+	pub fn load_model_cached<T: AsRef<Path>>(&self, file: T, context: RenderContext) -> Result<Arc<Model>> {
 		let file = file.as_ref();
 
 		// don't use an upgradeable read lock guard,
@@ -66,17 +138,56 @@ impl AssetLoader {
 
 			// we will probably need to insert a PathBuf after this, so do the expensive allocation while no lock is held
 			let file_owned = file.to_owned();
-			let path = self.path_of(file);
 
 			let mut write_lock = self.cached_models.write();
 
 			if let Some(model) = write_lock.get(file) {
 				Ok(model.clone())
 			} else {
-				let model = Arc::new(Model::load_from_file(path, context)?);
+				let is_gltf = matches!(
+					file.extension().and_then(|extension| extension.to_str()),
+					Some("gltf" | "glb"),
+				);
+				let model = if is_gltf {
+					Model::load_gltf(file, context)?
+				} else {
+					Model::load_from_file(file, context)?
+				};
+				let model = Arc::new(model);
 				write_lock.insert(file_owned, model.clone());
 				Ok(model)
 			}
 		}
-	}*/
+	}
+}
+
+#[cfg(all(test, feature = "render"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn active_pack_overrides_resources() {
+		let resource_folder = std::env::temp_dir().join(format!("minecone-pack-test-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&resource_folder);
+		fs::create_dir_all(resource_folder.join("textures")).unwrap();
+		fs::create_dir_all(resource_folder.join("packs/partial/textures")).unwrap();
+		fs::create_dir_all(resource_folder.join("packs/empty")).unwrap();
+		fs::write(resource_folder.join("textures/dirt.png"), "base dirt").unwrap();
+		fs::write(resource_folder.join("textures/stone.png"), "base stone").unwrap();
+		fs::write(resource_folder.join("packs/partial/textures/dirt.png"), "pack dirt").unwrap();
+
+		let loader = AssetLoader::from_path(resource_folder.clone());
+		assert_eq!(loader.available_packs(), vec![String::from("empty"), String::from("partial")]);
+		assert_eq!(loader.load_bytes("textures/dirt.png").unwrap(), b"base dirt");
+
+		loader.set_active_pack(Some(String::from("partial")));
+		assert_eq!(loader.load_bytes("textures/dirt.png").unwrap(), b"pack dirt");
+		// resources missing from the pack come from the resource folder
+		assert_eq!(loader.load_bytes("textures/stone.png").unwrap(), b"base stone");
+
+		loader.set_active_pack(None);
+		assert_eq!(loader.load_bytes("textures/dirt.png").unwrap(), b"base dirt");
+
+		fs::remove_dir_all(&resource_folder).unwrap();
+	}
 }
@@ -17,4 +17,14 @@ impl BlockTrait for Air {
 	fn is_translucent(&self) -> bool {
 		true
 	}
+
+	// air is never shown on maps, since the highest block that isn't air is used
+	fn map_color(&self) -> [u8; 3] {
+		[0, 0, 0]
+	}
+
+	fn hardness(&self) -> f32 {
+		// air is never targeted, so it is never broken
+		0.0
+	}
 }
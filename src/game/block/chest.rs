@@ -0,0 +1,62 @@
+use image::Rgba;
+
+use super::*;
+use super::texmanip::TEXTURE_SIZE;
+use crate::game::block_entity::{BlockEntity, Container};
+
+pub const CHEST_SLOTS: usize = 27;
+
+// width of the dark frame around the edge of each side, in pixels
+const FRAME_WIDTH: u32 = 2;
+// pixel rows between planks
+const PLANK_HEIGHT: u32 = 6;
+
+#[derive(Debug, Clone)]
+pub struct Chest {}
+
+impl Chest {
+	pub fn new() -> Chest {
+		Chest {}
+	}
+
+	// wooden planks with a dark frame and a latch in the middle
+	pub fn get_texture() -> Result<DynamicImage> {
+		let side = texmanip::side_from_fn(|x, y| {
+			let frame = x < FRAME_WIDTH || y < FRAME_WIDTH || x >= TEXTURE_SIZE - FRAME_WIDTH || y >= TEXTURE_SIZE - FRAME_WIDTH;
+			let latch = (14..18).contains(&x) && (12..18).contains(&y);
+
+			if latch {
+				Rgba([200, 200, 190, 255])
+			} else if frame {
+				Rgba([70, 45, 20, 255])
+			} else if y % PLANK_HEIGHT == 0 {
+				Rgba([110, 72, 35, 255])
+			} else {
+				Rgba([150, 100, 50, 255])
+			}
+		});
+		Ok(texmanip::tile_from_side(&side))
+	}
+}
+
+impl BlockTrait for Chest {
+	fn name(&self) -> &str {
+		"chest"
+	}
+
+	fn is_translucent(&self) -> bool {
+		false
+	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[150, 100, 50]
+	}
+
+	fn hardness(&self) -> f32 {
+		2.5
+	}
+
+	fn new_block_entity(&self) -> Option<BlockEntity> {
+		Some(BlockEntity::Container(Container::new(CHEST_SLOTS)))
+	}
+}
@@ -21,4 +21,12 @@ impl BlockTrait for Dirt {
 	fn is_translucent(&self) -> bool {
 		false
 	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[134, 96, 67]
+	}
+
+	fn hardness(&self) -> f32 {
+		0.5
+	}
 }
@@ -0,0 +1,59 @@
+use image::Rgba;
+
+use super::*;
+use super::texmanip::TEXTURE_SIZE;
+use crate::game::block_entity::{BlockEntity, Furnace as FurnaceEntity};
+
+// pixels between rows of bricks, every other row is offset by half a brick
+const BRICK_HEIGHT: u32 = 8;
+const BRICK_WIDTH: u32 = 16;
+
+#[derive(Debug, Clone)]
+pub struct Furnace {}
+
+impl Furnace {
+	pub fn new() -> Furnace {
+		Furnace {}
+	}
+
+	// stone bricks with a dark opening in the middle of each side
+	pub fn get_texture() -> Result<DynamicImage> {
+		let side = texmanip::side_from_fn(|x, y| {
+			let opening = (10..22).contains(&x) && (14..24).contains(&y);
+			let row = y / BRICK_HEIGHT;
+			let brick_x = (x + row % 2 * BRICK_WIDTH / 2) % BRICK_WIDTH;
+			let mortar = y % BRICK_HEIGHT == 0 || brick_x == 0 || x == TEXTURE_SIZE - 1 || y == TEXTURE_SIZE - 1;
+
+			if opening {
+				Rgba([30, 25, 25, 255])
+			} else if mortar {
+				Rgba([80, 80, 80, 255])
+			} else {
+				Rgba([120, 120, 120, 255])
+			}
+		});
+		Ok(texmanip::tile_from_side(&side))
+	}
+}
+
+impl BlockTrait for Furnace {
+	fn name(&self) -> &str {
+		"furnace"
+	}
+
+	fn is_translucent(&self) -> bool {
+		false
+	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[110, 110, 110]
+	}
+
+	fn hardness(&self) -> f32 {
+		3.5
+	}
+
+	fn new_block_entity(&self) -> Option<BlockEntity> {
+		Some(BlockEntity::Furnace(FurnaceEntity::new()))
+	}
+}
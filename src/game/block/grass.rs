@@ -37,4 +37,12 @@ impl BlockTrait for Grass {
 	fn is_translucent(&self) -> bool {
 		false
 	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[95, 159, 53]
+	}
+
+	fn hardness(&self) -> f32 {
+		0.6
+	}
 }
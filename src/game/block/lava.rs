@@ -0,0 +1,69 @@
+use super::*;
+use liquid::*;
+
+// ticks between lava changing and it flowing, much slower than water
+const LAVA_FLOW_DELAY: u64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct Lava {
+	level: u8,
+}
+
+impl Lava {
+	// creates a source block
+	pub fn new() -> Lava {
+		Lava {
+			level: SOURCE_LIQUID_LEVEL,
+		}
+	}
+
+	pub fn with_level(level: u8) -> Lava {
+		assert!((1..=SOURCE_LIQUID_LEVEL).contains(&level), "invalid lava level");
+		Lava {
+			level,
+		}
+	}
+
+	pub fn get_texture() -> Result<DynamicImage> {
+		Ok(texmanip::tile_from_side(&loader().load_image("textures/lava.png")?))
+	}
+}
+
+impl BlockTrait for Lava {
+	fn name(&self) -> &str {
+		"lava"
+	}
+
+	fn is_translucent(&self) -> bool {
+		true
+	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[220, 90, 20]
+	}
+
+	fn hardness(&self) -> f32 {
+		// liquids can't be broken in survival
+		f32::INFINITY
+	}
+
+	fn liquid_level(&self) -> Option<u8> {
+		Some(self.level)
+	}
+
+	fn model_height(&self) -> f32 {
+		liquid_height(self.level)
+	}
+
+	fn on_place(&self, world: &World, block: BlockPos) {
+		world.schedule_update(block, LAVA_FLOW_DELAY);
+	}
+
+	fn on_neighbor_changed(&self, world: &World, block: BlockPos, _changed: BlockPos) {
+		world.schedule_update(block, LAVA_FLOW_DELAY);
+	}
+
+	fn scheduled_update(&self, world: &World, block: BlockPos) {
+		flow(world, block, self.level, BlockType::Lava, |level| Lava::with_level(level).into());
+	}
+}
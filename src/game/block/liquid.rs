@@ -0,0 +1,67 @@
+use super::*;
+
+// level of a source block, liquid flowing out of it loses 1 level for each block it spreads and disappears at 0
+pub const SOURCE_LIQUID_LEVEL: u8 = 8;
+// level of liquid falling down from the liquid above it
+const FALLING_LIQUID_LEVEL: u8 = SOURCE_LIQUID_LEVEL - 1;
+
+const HORIZONTAL_FACES: [BlockFace; 4] = [BlockFace::XPos, BlockFace::XNeg, BlockFace::ZPos, BlockFace::ZNeg];
+
+// height of the top surface of a liquid at the given level
+pub fn liquid_height(level: u8) -> f32 {
+	level as f32 / SOURCE_LIQUID_LEVEL as f32
+}
+
+// runs a scheduled update of a liquid block, shared by all liquids
+// the liquid first updates its own level to match the liquid flowing into it,
+// then flows down if it can, or spreads out to the sides otherwise
+// changing any block schedules updates for the liquids around it, which is how the flow continues
+pub(super) fn flow(world: &World, block: BlockPos, level: u8, block_type: BlockType, new_liquid: fn(u8) -> Block) {
+	// None if the block is not this liquid
+	let level_at = |position: BlockPos| {
+		world.block_at(position)
+			.filter(|block| block.block_type() == block_type)
+			.and_then(|block| block.liquid_level())
+	};
+	// unloaded blocks are not air, so liquids don't flow into unloaded chunks
+	let is_air = |position: BlockPos| world.block_at(position).is_some_and(|block| block.is_air());
+	// only blocks that were just checked are set, so their chunks are loaded
+	let set_block = |position: BlockPos, new_block: Block| {
+		let _ = world.set_block(position, new_block);
+	};
+
+	let above = block + BlockFace::YPos.block_pos_offset();
+	let below = block + BlockFace::YNeg.block_pos_offset();
+
+	if level != SOURCE_LIQUID_LEVEL {
+		let falling_level = level_at(above).map(|_| FALLING_LIQUID_LEVEL);
+		let side_level = HORIZONTAL_FACES.iter()
+			.filter_map(|face| level_at(block + face.block_pos_offset()))
+			.max()
+			.map(|level| level.saturating_sub(1));
+
+		// with no source feeding it, flowing liquid dries up
+		let fed_level = falling_level.max(side_level).unwrap_or(0);
+		if fed_level != level {
+			if fed_level == 0 {
+				set_block(block, Air::new().into());
+			} else {
+				set_block(block, new_liquid(fed_level));
+			}
+
+			// setting the block scheduled another update, which will spread it with the new level
+			return;
+		}
+	}
+
+	if is_air(below) {
+		set_block(below, new_liquid(FALLING_LIQUID_LEVEL));
+	} else if level > 1 && level_at(below).is_none() {
+		for face in HORIZONTAL_FACES {
+			let side = block + face.block_pos_offset();
+			if is_air(side) {
+				set_block(side, new_liquid(level - 1));
+			}
+		}
+	}
+}
@@ -1,13 +1,18 @@
-use std::{iter::FusedIterator, mem};
+use std::iter::FusedIterator;
 
 use image::DynamicImage;
 use glam::Vec3;
 use enum_dispatch::enum_dispatch;
+use smallvec::{SmallVec, smallvec};
 use anyhow::Result;
 
-pub use crate::render::model::{Vertex, Model};
+#[cfg(feature = "render")]
+use crate::render::model::Vertex;
+use crate::math::Aabb;
 use crate::prelude::*;
 use crate::assets::loader;
+use super::world::World;
+use super::block_entity::BlockEntity;
 
 mod texmanip;
 mod air;
@@ -22,9 +27,24 @@ mod rocky_dirt;
 pub use rocky_dirt::*;
 mod test_block;
 pub use test_block::*;
+mod liquid;
+pub use liquid::{SOURCE_LIQUID_LEVEL, liquid_height};
+mod water;
+pub use water::*;
+mod lava;
+pub use lava::*;
+mod sand;
+pub use sand::*;
+mod chest;
+pub use chest::*;
+mod stone_slab;
+pub use stone_slab::*;
+mod furnace;
+pub use furnace::*;
 
 // the amount of overlap between block verticies to stop rendering artifacts from occuring
-//const BLOCK_MODEL_OVERLAP: f64 = 0.00001;
+// this has to stay representable as an f32 offset at the edge of the world, where f32 precision is about 0.002
+const BLOCK_MODEL_OVERLAP: f32 = 0.002;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BlockFace {
@@ -69,6 +89,18 @@ impl BlockFace {
 	pub fn is_negative_face(&self) -> bool {
 		matches!(self, Self::XNeg | Self::YNeg | Self::ZNeg)
 	}
+
+	// the face pointing the other way on the same axis
+	pub fn opposite(&self) -> Self {
+		match self {
+			Self::XPos => Self::XNeg,
+			Self::XNeg => Self::XPos,
+			Self::YPos => Self::YNeg,
+			Self::YNeg => Self::YPos,
+			Self::ZPos => Self::ZNeg,
+			Self::ZNeg => Self::ZPos,
+		}
+	}
 }
 
 impl From<BlockFace> for usize {
@@ -117,28 +149,33 @@ impl FusedIterator for BlockFaceIter {}
 
 pub type TextureIndex = i32;
 
+// ambient occlusion of a vertex from 0 (unoccluded) to 3 (fully occluded)
+// it is only fractional when smooth lighting is on, the shader maps it to a brightness
+pub type OcclusionLevel = f32;
+pub const MAX_OCCLUSION_LEVEL: OcclusionLevel = 3.0;
+
 #[derive(Debug, Clone, Copy)]
 pub struct OcclusionCorners {
-	pub tl: u8,
-	pub tr: u8,
-	pub bl: u8,
-	pub br: u8,
+	pub tl: OcclusionLevel,
+	pub tr: OcclusionLevel,
+	pub bl: OcclusionLevel,
+	pub br: OcclusionLevel,
 }
 
 impl OcclusionCorners {
-	pub fn pos_corner(&self) -> u8 {
+	pub fn pos_corner(&self) -> OcclusionLevel {
 		self.tr
 	}
 
-	pub fn neg_corner(&self) -> u8 {
+	pub fn neg_corner(&self) -> OcclusionLevel {
 		self.bl
 	}
 
-	pub fn xpos_yneg_corner(&self) -> u8 {
+	pub fn xpos_yneg_corner(&self) -> OcclusionLevel {
 		self.br
 	}
 
-	pub fn xneg_ypos_corner(&self) -> u8 {
+	pub fn xneg_ypos_corner(&self) -> OcclusionLevel {
 		self.tl
 	}
 }
@@ -148,37 +185,46 @@ impl OcclusionCorners {
 pub struct BlockVertex {
 	position: [f32; 3],
 	normal: [f32; 3],
-	// texture color will be mutiplied by this color
-	color: [f32; 3],
+	// the shader darkens the vertex based on this and the ambient occlusion strength
+	occlusion_level: OcclusionLevel,
 	// the wgpu sample function takes in a signed integer so we use it here
 	texture_index: TextureIndex,
 }
 
 impl BlockVertex {
 	// panics on invalid occlusion level
-	pub fn new(position: Position, normal: Vec3, texture_index: TextureIndex, occlusion_level: u8) -> Self {
+	pub fn new(position: Position, normal: Vec3, texture_index: TextureIndex, occlusion_level: OcclusionLevel) -> Self {
+		assert!(
+			(0.0..=MAX_OCCLUSION_LEVEL).contains(&occlusion_level),
+			"invalid occlusion level passed to BlockVertex::new()",
+		);
+
 		Self {
 			position: [position.x as f32, position.y as f32, position.z as f32],
 			normal: [normal.x, normal.y, normal.z],
-			color: match occlusion_level {
-				0 => [1.0, 1.0, 1.0],
-				1 => [0.8, 0.8, 0.8],
-				2 => [0.6, 0.6, 0.6],
-				3 => [0.4, 0.4, 0.4],
-				_ => panic!("invalid occlusion level passed to BlockVertex::new()"),
-			},
+			occlusion_level,
 			texture_index,
 		}
 	}
 
+	pub fn position(&self) -> Vec3 {
+		Vec3::from(self.position)
+	}
+
+	pub fn texture_index(&self) -> TextureIndex {
+		self.texture_index
+	}
+
+	#[cfg(feature = "render")]
 	const ATTRIBS: [wgpu::VertexAttribute; 4] =
-		wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3, 3 => Sint32];
+		wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32, 3 => Sint32];
 }
 
+#[cfg(feature = "render")]
 impl Vertex for BlockVertex {
 	fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
 		wgpu::VertexBufferLayout {
-			array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+			array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
 			step_mode: wgpu::VertexStepMode::Vertex,
 			attributes: &Self::ATTRIBS,
 		}
@@ -191,7 +237,6 @@ impl Vertex for BlockVertex {
 pub struct BlockFaceMesh(pub [BlockVertex; 4]);
 
 impl BlockFaceMesh {
-	// TODO: add small overlap on edges to stop rendering artifacts
 	// occlusion levels in the array are: [tl, bl, br, tr]
 	pub fn from_corners(face: BlockFace, texture_index: TextureIndex, tl_corner_block: BlockPos, br_corner_block: BlockPos, occlusion_data: OcclusionCorners) -> Self {
 		let tl_corner_pos = tl_corner_block.as_position();
@@ -248,11 +293,21 @@ impl BlockFaceMesh {
 			BlockFace::ZNeg => Vec3::new(0.0, 0.0, -1.0),
 		};
 
+		// grow the quad slightly so floating point differences when rasterizing shared edges don't leave gaps
+		// it is only moved along the plane of the face, moving it along the normal would cause z fighting
+		// texture coordinates are calculated from world position in the shader, so they stay lined up with the quad
+		let center = 0.5 * (tl_corner + br_corner);
+		let in_plane = Vec3::ONE - normal.abs();
+		let extrude = |corner: Position| {
+			let direction = (corner - center).0.signum() * in_plane;
+			corner + Position(BLOCK_MODEL_OVERLAP * direction)
+		};
+
 		Self([
-			 BlockVertex::new(tl_corner, normal, texture_index, occlusion_data.tl),
-			 BlockVertex::new(bl_corner, normal, texture_index, occlusion_data.bl),
-			 BlockVertex::new(br_corner, normal, texture_index, occlusion_data.br),
-			 BlockVertex::new(tr_corner, normal, texture_index, occlusion_data.tr),
+			 BlockVertex::new(extrude(tl_corner), normal, texture_index, occlusion_data.tl),
+			 BlockVertex::new(extrude(bl_corner), normal, texture_index, occlusion_data.bl),
+			 BlockVertex::new(extrude(br_corner), normal, texture_index, occlusion_data.br),
+			 BlockVertex::new(extrude(tr_corner), normal, texture_index, occlusion_data.tr),
 		])
 	}
 
@@ -324,6 +379,16 @@ impl BlockFaceMesh {
 		Self::from_corners(face, texture_index, tl_corner, br_corner, occlusion_data)
 	}
 
+	// moves the vertices on the top of the block down, for blocks shorter than a full block
+	pub fn lower_top(&mut self, block_top: f32, amount: f32) {
+		for vertex in self.0.iter_mut() {
+			// vertices are only ever on the top or bottom of the block, plus the overlap
+			if vertex.position[1] > block_top - 0.5 {
+				vertex.position[1] -= amount;
+			}
+		}
+	}
+
 	// returns the indicies of the block model to be used for the index buffer
 	pub const fn indicies() -> &'static [u32] {
 		&[0, 2, 1, 2, 0, 3]
@@ -334,6 +399,47 @@ impl BlockFaceMesh {
 pub trait BlockTrait: Send + Sync {
 	fn name(&self) -> &str;
 	fn is_translucent(&self) -> bool;
+	// color of the block when it is the top of a column on a map
+	fn map_color(&self) -> [u8; 3];
+	// seconds it takes to break the block in survival, infinite for blocks which can't be broken
+	fn hardness(&self) -> f32;
+
+	// level of liquid in the block, None for blocks which are not liquids
+	fn liquid_level(&self) -> Option<u8> {
+		None
+	}
+
+	// liquids change the fog when the camera is inside of them
+	fn is_liquid(&self) -> bool {
+		self.liquid_level().is_some()
+	}
+
+	// height of the top of the block's model, blocks shorter than 1 have their top lowered when meshed
+	fn model_height(&self) -> f32 {
+		1.0
+	}
+
+	// boxes the player can target the block by looking at, relative to the block's negative corner
+	fn selection_aabbs(&self) -> SmallVec<[Aabb; 2]> {
+		smallvec![Aabb::new(Vec3::ZERO, Vec3::ONE)]
+	}
+
+	// data the block needs on top of its type, created by the world when the block is placed
+	fn new_block_entity(&self) -> Option<BlockEntity> {
+		None
+	}
+
+	// called after this block is placed in the world
+	fn on_place(&self, _world: &World, _block: BlockPos) {}
+
+	// called after this block is replaced by another block
+	fn on_break(&self, _world: &World, _block: BlockPos) {}
+
+	// called on the tick after one of the 6 adjacent blocks changes, changed is the position of that block
+	fn on_neighbor_changed(&self, _world: &World, _block: BlockPos, _changed: BlockPos) {}
+
+	// called when an update scheduled with World::schedule_update runs
+	fn scheduled_update(&self, _world: &World, _block: BlockPos) {}
 }
 
 macro_rules! blocks {
@@ -392,6 +498,54 @@ macro_rules! blocks {
 			}
 		}
 
+		impl $block_type {
+			// every block type, in the order they are declared
+			pub const ALL: &'static [Self] = &[
+				$(
+					Self::$blocks,
+				)*
+				$(
+					Self::$ublocks,
+				)*
+			];
+
+			// creates a new block of this type
+			pub fn new_block(self) -> $block {
+				match self {
+					$(
+						Self::$ublocks => $ublocks::new().into(),
+					)*
+					$(
+						Self::$blocks => $blocks::new().into(),
+					)*
+				}
+			}
+
+			// name the block type is saved with
+			pub fn name(self) -> &'static str {
+				match self {
+					$(
+						Self::$ublocks => stringify!($ublocks),
+					)*
+					$(
+						Self::$blocks => stringify!($blocks),
+					)*
+				}
+			}
+
+			pub fn from_name(name: &str) -> Option<Self> {
+				match name {
+					$(
+						stringify!($ublocks) => Some(Self::$ublocks),
+					)*
+					$(
+						stringify!($blocks) => Some(Self::$blocks),
+					)*
+					_ => None,
+				}
+			}
+		}
+
 		pub fn generate_texture_array() -> Result<Vec<DynamicImage>> {
 			Ok(vec![
 				$(
@@ -417,9 +571,35 @@ blocks! {
 		Grass,
 		Stone,
 		RockyDirt,
+		Water,
+		Lava,
+		Sand,
+		Chest,
+		StoneSlab,
+		Furnace,
 	},
 }
 
+// number of textures in the array made by generate_texture_array, every texture index is less than this
+pub fn num_textures() -> TextureIndex {
+	MaxTextureIndex::Max as TextureIndex
+}
+
+impl BlockType {
+	// name shown to the player, which is the block's name with each word capitalized
+	// name is what the block type is saved with, so this can change without breaking saves
+	pub fn display_name(self) -> String {
+		self.new_block().name()
+			.split(' ')
+			.map(|word| {
+				let mut chars = word.chars();
+				chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+			})
+			.collect::<Vec<_>>()
+			.join(" ")
+	}
+}
+
 impl Block {
 	pub fn is_air(&self) -> bool {
 		matches!(self, Self::Air(_))
@@ -435,6 +615,58 @@ impl Block {
 	}
 }
 
-pub fn num_textures() -> u32 {
-	MaxTextureIndex::Max as u32
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::world::WORLD_MAX_SIZE;
+
+	const NO_OCCLUSION: OcclusionCorners = OcclusionCorners {
+		tl: 0.0,
+		tr: 0.0,
+		bl: 0.0,
+		br: 0.0,
+	};
+
+	// meshes a flat 32x32 plane of mixed block types with one face per block,
+	// and checks each face covers its whole block and a bit past it without leaving the plane
+	fn check_plane_overlap(origin: BlockPos) {
+		for x in 0..32 {
+			for z in 0..32 {
+				let block = origin + BlockPos::new(x, 0, z);
+				let texture_index = (x + z) % 3;
+				let face_mesh = BlockFaceMesh::from_corners(BlockFace::YPos, texture_index, block, block, NO_OCCLUSION);
+
+				let positions = face_mesh.0.map(|vertex| Vec3::from(vertex.position));
+				let min = positions.iter().fold(Vec3::splat(f32::INFINITY), |a, b| a.min(*b));
+				let max = positions.iter().fold(Vec3::splat(f32::NEG_INFINITY), |a, b| a.max(*b));
+
+				let block_min = block.as_position().0;
+				let block_max = block_min + Vec3::ONE;
+
+				assert!(min.x < block_min.x && min.z < block_min.z, "face of {:?} does not overlap its neighbors", block);
+				assert!(max.x > block_max.x && max.z > block_max.z, "face of {:?} does not overlap its neighbors", block);
+				assert!(positions.iter().all(|position| position.y == block_max.y), "face of {:?} left its plane", block);
+			}
+		}
+	}
+
+	#[test]
+	fn faces_overlap_neighbors() {
+		check_plane_overlap(BlockPos::new(0, 0, 0));
+	}
+
+	// f32 precision is lowest far from the origin, the overlap must still be there
+	#[test]
+	fn faces_overlap_neighbors_far_from_origin() {
+		let world_edge = WORLD_MAX_SIZE.x as i32 * CHUNK_SIZE as i32;
+		check_plane_overlap(BlockPos::new(world_edge - 32, 0, -world_edge));
+	}
+
+	#[test]
+	fn display_names_are_capitalized() {
+		assert_eq!(BlockType::Stone.display_name(), "Stone");
+		assert_eq!(BlockType::StoneSlab.display_name(), "Stone Slab");
+		assert_eq!(BlockType::RockyDirt.display_name(), "Rocky Dirt");
+		assert!(BlockType::ALL.iter().all(|block_type| !block_type.display_name().is_empty()));
+	}
 }
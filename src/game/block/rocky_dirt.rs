@@ -21,4 +21,12 @@ impl BlockTrait for RockyDirt {
 	fn is_translucent(&self) -> bool {
 		false
 	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[112, 98, 86]
+	}
+
+	fn hardness(&self) -> f32 {
+		1.0
+	}
 }
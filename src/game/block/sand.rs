@@ -0,0 +1,65 @@
+use super::*;
+
+// ticks between sand losing the block under it and falling
+const SAND_FALL_DELAY: u64 = 2;
+
+#[derive(Debug, Clone)]
+pub struct Sand {}
+
+impl Sand {
+	pub fn new() -> Sand {
+		Sand {}
+	}
+
+	pub fn get_texture() -> Result<DynamicImage> {
+		Ok(texmanip::tile_from_side(&loader().load_image("textures/sand.png")?))
+	}
+}
+
+// sand falls through air and liquids, it doesn't fall into unloaded chunks
+fn can_fall_into(world: &World, block: BlockPos) -> bool {
+	world.block_at(block).is_some_and(|block| block.is_air() || block.is_liquid())
+}
+
+fn below(block: BlockPos) -> BlockPos {
+	block + BlockFace::YNeg.block_pos_offset()
+}
+
+impl BlockTrait for Sand {
+	fn name(&self) -> &str {
+		"sand"
+	}
+
+	fn is_translucent(&self) -> bool {
+		false
+	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[219, 203, 148]
+	}
+
+	fn hardness(&self) -> f32 {
+		0.5
+	}
+
+	fn on_place(&self, world: &World, block: BlockPos) {
+		if can_fall_into(world, below(block)) {
+			world.schedule_update(block, SAND_FALL_DELAY);
+		}
+	}
+
+	fn on_neighbor_changed(&self, world: &World, block: BlockPos, changed: BlockPos) {
+		if changed == below(block) && can_fall_into(world, changed) {
+			world.schedule_update(block, SAND_FALL_DELAY);
+		}
+	}
+
+	// falls 1 block, it keeps falling from on_place until it lands
+	fn scheduled_update(&self, world: &World, block: BlockPos) {
+		// can_fall_into is only true for loaded blocks, so both chunks are loaded
+		if can_fall_into(world, below(block)) {
+			let _ = world.set_block(block, Air::new().into());
+			let _ = world.set_block(below(block), Sand::new().into());
+		}
+	}
+}
@@ -21,4 +21,12 @@ impl BlockTrait for Stone {
 	fn is_translucent(&self) -> bool {
 		false
 	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[125, 125, 125]
+	}
+
+	fn hardness(&self) -> f32 {
+		1.5
+	}
 }
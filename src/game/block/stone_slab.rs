@@ -0,0 +1,42 @@
+use super::*;
+
+// half of a block of stone, sitting on the bottom of its block
+#[derive(Debug, Clone)]
+pub struct StoneSlab {}
+
+impl StoneSlab {
+	pub fn new() -> StoneSlab {
+		StoneSlab {}
+	}
+
+	pub fn get_texture() -> Result<DynamicImage> {
+		Ok(texmanip::tile_from_side(&loader().load_image("textures/stone.png")?))
+	}
+}
+
+impl BlockTrait for StoneSlab {
+	fn name(&self) -> &str {
+		"stone slab"
+	}
+
+	fn is_translucent(&self) -> bool {
+		// the top half is empty, so the faces of blocks next to it have to be shown
+		true
+	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[125, 125, 125]
+	}
+
+	fn hardness(&self) -> f32 {
+		0.75
+	}
+
+	fn model_height(&self) -> f32 {
+		0.5
+	}
+
+	fn selection_aabbs(&self) -> SmallVec<[Aabb; 2]> {
+		smallvec![Aabb::new(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0))]
+	}
+}
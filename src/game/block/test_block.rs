@@ -22,4 +22,12 @@ impl BlockTrait for TestBlock {
 		// it is not translucent, but we want to be able to see the test block everywhere it is for testing purposes
 		true
 	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[255, 0, 255]
+	}
+
+	fn hardness(&self) -> f32 {
+		0.0
+	}
 }
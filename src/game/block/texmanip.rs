@@ -1,11 +1,11 @@
 // This module contains functions for manipulating the textures
 
-use image::{DynamicImage, RgbaImage, imageops::tile, GenericImage};
+use image::{DynamicImage, Rgba, RgbaImage, imageops::tile, GenericImage};
 pub use image::imageops::{overlay, rotate90, rotate180, rotate270};
 use anyhow::Result;
 
 // length of textures in pixels
-const TEXTURE_SIZE: u32 = 32;
+pub const TEXTURE_SIZE: u32 = 32;
 
 pub fn tile_from_side(side: &DynamicImage) -> DynamicImage {
 	let mut out = RgbaImage::new(4 * TEXTURE_SIZE, 3 * TEXTURE_SIZE);
@@ -13,6 +13,11 @@ pub fn tile_from_side(side: &DynamicImage) -> DynamicImage {
 	DynamicImage::ImageRgba8(out)
 }
 
+// a side texture drawn from the color of each pixel, for blocks which don't have a texture file
+pub fn side_from_fn<F: FnMut(u32, u32) -> Rgba<u8>>(f: F) -> DynamicImage {
+	DynamicImage::ImageRgba8(RgbaImage::from_fn(TEXTURE_SIZE, TEXTURE_SIZE, f))
+}
+
 // describes the orientation of a texture face
 pub enum TextureFace<'a> {
 	// the texture face will be aligned with the direction of the stiched texture
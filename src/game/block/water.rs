@@ -0,0 +1,69 @@
+use super::*;
+use liquid::*;
+
+// ticks between water changing and it flowing
+const WATER_FLOW_DELAY: u64 = 5;
+
+#[derive(Debug, Clone)]
+pub struct Water {
+	level: u8,
+}
+
+impl Water {
+	// creates a source block
+	pub fn new() -> Water {
+		Water {
+			level: SOURCE_LIQUID_LEVEL,
+		}
+	}
+
+	pub fn with_level(level: u8) -> Water {
+		assert!((1..=SOURCE_LIQUID_LEVEL).contains(&level), "invalid water level");
+		Water {
+			level,
+		}
+	}
+
+	pub fn get_texture() -> Result<DynamicImage> {
+		Ok(texmanip::tile_from_side(&loader().load_image("textures/water.png")?))
+	}
+}
+
+impl BlockTrait for Water {
+	fn name(&self) -> &str {
+		"water"
+	}
+
+	fn is_translucent(&self) -> bool {
+		true
+	}
+
+	fn map_color(&self) -> [u8; 3] {
+		[50, 90, 200]
+	}
+
+	fn hardness(&self) -> f32 {
+		// liquids can't be broken in survival
+		f32::INFINITY
+	}
+
+	fn liquid_level(&self) -> Option<u8> {
+		Some(self.level)
+	}
+
+	fn model_height(&self) -> f32 {
+		liquid_height(self.level)
+	}
+
+	fn on_place(&self, world: &World, block: BlockPos) {
+		world.schedule_update(block, WATER_FLOW_DELAY);
+	}
+
+	fn on_neighbor_changed(&self, world: &World, block: BlockPos, _changed: BlockPos) {
+		world.schedule_update(block, WATER_FLOW_DELAY);
+	}
+
+	fn scheduled_update(&self, world: &World, block: BlockPos) {
+		flow(world, block, self.level, BlockType::Water, |level| Water::with_level(level).into());
+	}
+}
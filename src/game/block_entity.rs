@@ -0,0 +1,445 @@
+use anyhow::anyhow;
+
+use super::block::BlockType;
+use super::save_format::{Record, Tag};
+use super::world::World;
+use crate::prelude::*;
+
+// fields of a saved block entity
+const TAG_KIND: Tag = 0;
+const TAG_SIZE: Tag = 1;
+const TAG_SLOT: Tag = 2;
+// fields of a saved furnace, its slots are saved like a container's
+const TAG_BURN_TICKS: Tag = 3;
+const TAG_BURN_TOTAL: Tag = 4;
+const TAG_SMELT_TICKS: Tag = 5;
+// fields of each non empty slot of a saved container
+const TAG_SLOT_INDEX: Tag = 0;
+const TAG_SLOT_TYPE: Tag = 1;
+const TAG_SLOT_COUNT: Tag = 2;
+
+// ticks it takes a furnace to smelt 1 block
+pub const SMELT_TICKS: u32 = 200;
+// how bright a burning furnace is, out of 15
+pub const FURNACE_LIGHT_LEVEL: u8 = 13;
+
+// data attached to a block position, for blocks which need more than their block type
+// it is created by BlockTrait::new_block_entity when the block is placed, and removed when the block is replaced
+// block entities are stored in their chunk, so they stay with it when the chunk is meshed, unloaded or cached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockEntity {
+	Container(Container),
+	Furnace(Furnace),
+}
+
+impl BlockEntity {
+	// none for furnaces, since each of their slots only holds some block types, use insert instead
+	pub fn container_mut(&mut self) -> Option<&mut Container> {
+		match self {
+			Self::Container(container) => Some(container),
+			Self::Furnace(_) => None,
+		}
+	}
+
+	pub fn furnace(&self) -> Option<&Furnace> {
+		match self {
+			Self::Furnace(furnace) => Some(furnace),
+			Self::Container(_) => None,
+		}
+	}
+
+	// the slots shown when the block is used
+	pub fn slots(&self) -> &[Option<(BlockType, u32)>] {
+		match self {
+			Self::Container(container) => container.slots(),
+			Self::Furnace(furnace) => furnace.slots.slots(),
+		}
+	}
+
+	// empties the slot, returning what was in it
+	pub fn take(&mut self, slot: usize) -> Option<(BlockType, u32)> {
+		match self {
+			Self::Container(container) => container.take(slot),
+			Self::Furnace(furnace) => furnace.slots.take(slot),
+		}
+	}
+
+	// returns false if the blocks can't go in the block entity
+	pub fn insert(&mut self, block_type: BlockType, count: u32) -> bool {
+		match self {
+			Self::Container(container) => container.insert(block_type, count),
+			Self::Furnace(furnace) => furnace.insert(block_type, count),
+		}
+	}
+
+	// what is dropped in the world when the block is broken
+	pub fn drops(&self) -> Vec<(BlockType, u32)> {
+		self.slots().iter().flatten().copied().collect()
+	}
+
+	// block entities which are ticked are tracked by their chunk, so the world only ticks chunks which have them
+	pub fn is_ticking(&self) -> bool {
+		matches!(self, Self::Furnace(_))
+	}
+
+	// called once per world tick while the block entity's chunk is loaded
+	// the block entities of the chunk are locked, so this must not use any other block entities in the same chunk
+	pub fn tick(&mut self, _world: &World, _block: BlockPos) {
+		match self {
+			Self::Container(_) => (),
+			Self::Furnace(furnace) => furnace.tick(),
+		}
+	}
+
+	// light given off by the block entity, out of 15
+	// block light isn't meshed yet, so this is only for things which ask how bright a block is
+	pub fn light_level(&self) -> u8 {
+		match self {
+			Self::Furnace(furnace) if furnace.is_burning() => FURNACE_LIGHT_LEVEL,
+			_ => 0,
+		}
+	}
+
+	pub fn save(&self) -> Record {
+		let mut record = Record::new();
+		match self {
+			Self::Container(container) => {
+				record.push_str(TAG_KIND, "container");
+				container.save(&mut record);
+			},
+			Self::Furnace(furnace) => {
+				record.push_str(TAG_KIND, "furnace");
+				record.push_u32(TAG_BURN_TICKS, furnace.burn_ticks);
+				record.push_u32(TAG_BURN_TOTAL, furnace.burn_total);
+				record.push_u32(TAG_SMELT_TICKS, furnace.smelt_ticks);
+				furnace.slots.save(&mut record);
+			},
+		}
+		record
+	}
+
+	// none if the block entity is of a kind this version doesn't have
+	pub fn load(mut record: Record) -> Result<Option<Self>> {
+		let kind = record.take_string(TAG_KIND)?.ok_or_else(|| anyhow!("block entity has no kind"))?;
+		match kind.as_str() {
+			"container" => Ok(Some(Self::Container(Container::load(record)?))),
+			"furnace" => {
+				let burn_ticks = record.take_u32(TAG_BURN_TICKS)?.unwrap_or(0);
+				let burn_total = record.take_u32(TAG_BURN_TOTAL)?.unwrap_or(burn_ticks);
+				let smelt_ticks = record.take_u32(TAG_SMELT_TICKS)?.unwrap_or(0);
+				let slots = Container::load(record)?;
+				if slots.slots.len() != FURNACE_SLOTS {
+					return Err(anyhow!("furnace has {} slots instead of {}", slots.slots.len(), FURNACE_SLOTS));
+				}
+
+				Ok(Some(Self::Furnace(Furnace {
+					slots,
+					burn_ticks,
+					burn_total,
+					smelt_ticks,
+				})))
+			},
+			_ => Ok(None),
+		}
+	}
+}
+
+// slots holding any number of 1 block type each, like a chest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+	slots: Vec<Option<(BlockType, u32)>>,
+	// fields from the container's save that this version doesn't know, they are saved again unchanged
+	unknown_fields: Record,
+}
+
+impl Container {
+	pub fn new(size: usize) -> Self {
+		Container {
+			slots: vec![None; size],
+			unknown_fields: Record::new(),
+		}
+	}
+
+	pub fn slots(&self) -> &[Option<(BlockType, u32)>] {
+		&self.slots
+	}
+
+	// the size and each non empty slot, and the fields this version doesn't know
+	fn save(&self, record: &mut Record) {
+		record.push_u32(TAG_SIZE, self.slots.len() as u32);
+		for (index, (block_type, count)) in self.slots.iter().enumerate().filter_map(|(index, slot)| Some((index, (*slot)?))) {
+			let mut slot = Record::new();
+			slot.push_u32(TAG_SLOT_INDEX, index as u32);
+			slot.push_str(TAG_SLOT_TYPE, block_type.name());
+			slot.push_u32(TAG_SLOT_COUNT, count);
+			record.push_record(TAG_SLOT, &slot);
+		}
+		record.extend(self.unknown_fields.clone());
+	}
+
+	// the record's other fields must already be taken, whatever is left is kept as unknown fields
+	fn load(mut record: Record) -> Result<Self> {
+		let size = record.take_u32(TAG_SIZE)?.ok_or_else(|| anyhow!("container has no size"))?;
+		let mut container = Container::new(size as usize);
+		for slot in record.take_all(TAG_SLOT) {
+			let mut slot = Record::decode(&slot)?;
+			let index = slot.take_u32(TAG_SLOT_INDEX)?.ok_or_else(|| anyhow!("container slot has no index"))? as usize;
+			let type_name = slot.take_string(TAG_SLOT_TYPE)?.ok_or_else(|| anyhow!("container slot has no block type"))?;
+			let block_type = BlockType::from_name(&type_name).ok_or_else(|| anyhow!("unknown block type {}", type_name))?;
+			let count = slot.take_u32(TAG_SLOT_COUNT)?.unwrap_or(1);
+
+			*container.slots.get_mut(index).ok_or_else(|| anyhow!("container slot {} is past its size {}", index, size))? = Some((block_type, count));
+		}
+		container.unknown_fields = record;
+		Ok(container)
+	}
+
+	// empties the slot, returning what was in it
+	// slots past the end of the container are always empty
+	pub fn take(&mut self, slot: usize) -> Option<(BlockType, u32)> {
+		self.slots.get_mut(slot)?.take()
+	}
+
+	// adds blocks to the slot already holding their type, or to the first empty slot
+	// returns false if there is no room for them
+	pub fn insert(&mut self, block_type: BlockType, count: u32) -> bool {
+		if count == 0 {
+			return true;
+		}
+
+		let slot = self.slots.iter().position(|slot| matches!(slot, Some((slot_type, _)) if *slot_type == block_type))
+			.or_else(|| self.slots.iter().position(Option::is_none));
+
+		match slot {
+			Some(slot) => {
+				let (_, slot_count) = self.slots[slot].get_or_insert((block_type, 0));
+				*slot_count += count;
+				true
+			},
+			None => false,
+		}
+	}
+}
+
+// the slots of a furnace, in the order they are shown
+pub const FURNACE_INPUT_SLOT: usize = 0;
+pub const FURNACE_FUEL_SLOT: usize = 1;
+pub const FURNACE_OUTPUT_SLOT: usize = 2;
+const FURNACE_SLOTS: usize = 3;
+
+// what the block type turns into when it is smelted
+// recipes are hardcoded until there are more blocks to smelt
+pub fn smelting_output(input: BlockType) -> Option<BlockType> {
+	match input {
+		BlockType::RockyDirt => Some(BlockType::Stone),
+		BlockType::Grass => Some(BlockType::Dirt),
+		_ => None,
+	}
+}
+
+// how many ticks 1 of the block type burns for
+pub fn fuel_ticks(fuel: BlockType) -> Option<u32> {
+	match fuel {
+		BlockType::Lava => Some(4000),
+		BlockType::Chest => Some(300),
+		_ => None,
+	}
+}
+
+// smelts the block in its input slot into its output slot, burning 1 fuel at a time while there is something to smelt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Furnace {
+	slots: Container,
+	// ticks left until the fuel being burned runs out
+	burn_ticks: u32,
+	// how long the fuel being burned lasts in total
+	burn_total: u32,
+	// how far the block being smelted is, it is done at SMELT_TICKS
+	smelt_ticks: u32,
+}
+
+impl Furnace {
+	pub fn new() -> Self {
+		Furnace {
+			slots: Container::new(FURNACE_SLOTS),
+			burn_ticks: 0,
+			burn_total: 0,
+			smelt_ticks: 0,
+		}
+	}
+
+	pub fn is_burning(&self) -> bool {
+		self.burn_ticks > 0
+	}
+
+	// fraction of the fuel being burned that is left
+	pub fn burn_progress(&self) -> f32 {
+		if self.burn_total == 0 {
+			0.0
+		} else {
+			self.burn_ticks as f32 / self.burn_total as f32
+		}
+	}
+
+	// fraction of the way the block being smelted is to being done
+	pub fn smelt_progress(&self) -> f32 {
+		self.smelt_ticks as f32 / SMELT_TICKS as f32
+	}
+
+	// blocks which can be smelted go in the input slot, and fuel goes in the fuel slot
+	// nothing can be put in the output slot
+	pub fn insert(&mut self, block_type: BlockType, count: u32) -> bool {
+		let slot = if smelting_output(block_type).is_some() {
+			FURNACE_INPUT_SLOT
+		} else if fuel_ticks(block_type).is_some() {
+			FURNACE_FUEL_SLOT
+		} else {
+			return false;
+		};
+
+		match &mut self.slots.slots[slot] {
+			Some((slot_type, slot_count)) if *slot_type == block_type => *slot_count += count,
+			Some(_) => return false,
+			slot @ None => *slot = Some((block_type, count)),
+		}
+		true
+	}
+
+	// what the input would be smelted into, none if there is no input or the output slot holds something else
+	fn output(&self) -> Option<BlockType> {
+		let (input, _) = self.slots.slots[FURNACE_INPUT_SLOT]?;
+		let output = smelting_output(input)?;
+		match self.slots.slots[FURNACE_OUTPUT_SLOT] {
+			Some((output_type, _)) if output_type != output => None,
+			_ => Some(output),
+		}
+	}
+
+	// takes 1 from the slot, emptying it if it was the last one
+	fn take_one(&mut self, slot: usize) -> Option<BlockType> {
+		let (block_type, count) = self.slots.slots[slot].as_mut()?;
+		let block_type = *block_type;
+		*count -= 1;
+		if *count == 0 {
+			self.slots.slots[slot] = None;
+		}
+		Some(block_type)
+	}
+
+	fn tick(&mut self) {
+		let output = self.output();
+
+		// new fuel is only burned when there is something to smelt
+		if self.burn_ticks == 0 && output.is_some() {
+			if let Some(fuel) = self.take_one(FURNACE_FUEL_SLOT) {
+				self.burn_total = fuel_ticks(fuel).unwrap_or(0);
+				self.burn_ticks = self.burn_total;
+			}
+		}
+
+		if self.burn_ticks == 0 {
+			self.smelt_ticks = 0;
+			return;
+		}
+		self.burn_ticks -= 1;
+
+		match output {
+			Some(output) => {
+				self.smelt_ticks += 1;
+				if self.smelt_ticks >= SMELT_TICKS {
+					self.smelt_ticks = 0;
+					self.take_one(FURNACE_INPUT_SLOT);
+					let (_, count) = self.slots.slots[FURNACE_OUTPUT_SLOT].get_or_insert((output, 0));
+					*count += 1;
+				}
+			},
+			None => self.smelt_ticks = 0,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn container_stacks_block_types() {
+		let mut container = Container::new(2);
+
+		assert!(container.insert(BlockType::Stone, 3));
+		assert!(container.insert(BlockType::Dirt, 1));
+		assert!(container.insert(BlockType::Stone, 2));
+		assert!(!container.insert(BlockType::Sand, 1));
+		assert_eq!(container.slots(), [Some((BlockType::Stone, 5)), Some((BlockType::Dirt, 1))]);
+
+		assert_eq!(container.take(0), Some((BlockType::Stone, 5)));
+		assert_eq!(container.take(0), None);
+		assert_eq!(container.take(2), None);
+		assert!(container.insert(BlockType::Sand, 1));
+		assert_eq!(BlockEntity::Container(container).drops(), [(BlockType::Sand, 1), (BlockType::Dirt, 1)]);
+	}
+
+	#[test]
+	fn containers_are_saved_with_their_slots() {
+		let mut container = Container::new(4);
+		container.insert(BlockType::Stone, 3);
+		container.insert(BlockType::Chest, 1);
+		container.take(0);
+		container.insert(BlockType::Sand, 64);
+		let block_entity = BlockEntity::Container(container);
+
+		let saved = Record::decode(&block_entity.save().encode()).unwrap();
+		assert_eq!(BlockEntity::load(saved).unwrap(), Some(block_entity.clone()));
+
+		// fields from a newer version are saved again
+		let mut newer = block_entity.save();
+		newer.push_u32(40, 9);
+		let mut resaved = BlockEntity::load(newer).unwrap().unwrap().save();
+		assert_eq!(resaved.take_u32(40).unwrap(), Some(9));
+
+		let mut future = Record::new();
+		future.push_str(TAG_KIND, "hopper");
+		assert_eq!(BlockEntity::load(future).unwrap(), None);
+	}
+
+	#[test]
+	fn furnace_smelts_while_fuel_lasts() {
+		let mut furnace = Furnace::new();
+		assert!(!furnace.insert(BlockType::Stone, 1));
+		assert!(furnace.insert(BlockType::RockyDirt, 3));
+		// nothing is burned until there is fuel
+		furnace.tick();
+		assert!(!furnace.is_burning());
+		assert!(furnace.insert(BlockType::Chest, 1));
+
+		for _ in 0..SMELT_TICKS {
+			furnace.tick();
+		}
+		assert!(furnace.is_burning());
+		assert_eq!(furnace.slots.slots(), [Some((BlockType::RockyDirt, 2)), None, Some((BlockType::Stone, 1))]);
+		assert_eq!(BlockEntity::Furnace(furnace.clone()).light_level(), FURNACE_LIGHT_LEVEL);
+
+		// the chest burns for 300 ticks, which isn't enough for a second block
+		for _ in 0..SMELT_TICKS {
+			furnace.tick();
+		}
+		assert!(!furnace.is_burning());
+		assert_eq!(furnace.smelt_progress(), 0.0);
+		assert_eq!(furnace.slots.slots(), [Some((BlockType::RockyDirt, 2)), None, Some((BlockType::Stone, 1))]);
+		assert_eq!(BlockEntity::Furnace(furnace).light_level(), 0);
+	}
+
+	#[test]
+	fn furnaces_are_saved_while_burning() {
+		let mut furnace = Furnace::new();
+		furnace.insert(BlockType::Grass, 5);
+		furnace.insert(BlockType::Lava, 2);
+		for _ in 0..50 {
+			furnace.tick();
+		}
+		let block_entity = BlockEntity::Furnace(furnace);
+
+		let saved = Record::decode(&block_entity.save().encode()).unwrap();
+		assert_eq!(BlockEntity::load(saved).unwrap(), Some(block_entity));
+	}
+}
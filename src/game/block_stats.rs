@@ -0,0 +1,122 @@
+use crate::prelude::*;
+use super::block::BlockType;
+use super::chunk::Chunk;
+
+// number of blocks of each type in some of the loaded chunks, counted by Task::Analyze for /stats blocks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockStats {
+	// indexed by block type
+	counts: Vec<u64>,
+	pub chunks_scanned: usize,
+	// chunks which were being changed when they were reached, they are skipped instead of waited on
+	pub skipped_chunks: Vec<ChunkPos>,
+}
+
+impl Default for BlockStats {
+	fn default() -> Self {
+		BlockStats {
+			counts: vec![0; BlockType::ALL.len()],
+			chunks_scanned: 0,
+			skipped_chunks: Vec::new(),
+		}
+	}
+}
+
+impl BlockStats {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// only locks the chunk's blocks if they aren't locked for writing already
+	pub fn add_chunk(&mut self, chunk: &Chunk) {
+		if chunk.try_count_block_types(&mut self.counts) {
+			self.chunks_scanned += 1;
+		} else {
+			self.skipped_chunks.push(chunk.chunk_position());
+		}
+	}
+
+	pub fn count(&self, block_type: BlockType) -> u64 {
+		self.counts[block_type as usize]
+	}
+
+	pub fn total_blocks(&self) -> u64 {
+		self.counts.iter().sum()
+	}
+
+	// the block types which were found, most common first
+	pub fn sorted_counts(&self) -> Vec<(BlockType, u64)> {
+		let mut counts = BlockType::ALL.iter()
+			.map(|block_type| (*block_type, self.count(*block_type)))
+			.filter(|(_, count)| *count > 0)
+			.collect::<Vec<_>>();
+		counts.sort_by(|(a_type, a_count), (b_type, b_count)| b_count.cmp(a_count).then(a_type.name().cmp(b_type.name())));
+		counts
+	}
+
+	// the lines printed in the console, a table of block types and a summary
+	pub fn table(&self) -> Vec<String> {
+		let total = self.total_blocks();
+		let mut lines = vec![format!("scanned {} chunks, {} blocks", self.chunks_scanned, total)];
+
+		for (block_type, count) in self.sorted_counts() {
+			let percent = 100.0 * count as f64 / total as f64;
+			lines.push(format!("{:<12} {:>12} {:>6.2}%", block_type.display_name(), count, percent));
+		}
+
+		if !self.skipped_chunks.is_empty() {
+			let skipped = self.skipped_chunks.iter()
+				.map(|chunk| format!("{:?}", chunk.0))
+				.collect::<Vec<_>>()
+				.join(", ");
+			lines.push(format!("skipped {} chunks which were locked: {}", self.skipped_chunks.len(), skipped));
+		}
+
+		lines
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::world::World;
+	use crate::game::block::{Air, Stone};
+
+	#[test]
+	fn block_stats_table() {
+		let world = World::new_test().unwrap();
+		let stone = Chunk::filled(world.clone(), ChunkPos::new(0, -1, 0), Stone::new().into());
+		let half_air = Chunk::new(world.clone(), ChunkPos::new(0, 0, 0), |block| if block.y < 8 {
+			Stone::new().into()
+		} else {
+			Air::new().into()
+		});
+		let chunk_blocks = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as u64;
+
+		let mut stats = BlockStats::new();
+		stats.add_chunk(&stone);
+		stats.add_chunk(&half_air);
+		assert_eq!(stats.chunks_scanned, 2);
+		assert_eq!(stats.total_blocks(), 2 * chunk_blocks);
+
+		let stone_count = chunk_blocks + 8 * (CHUNK_SIZE * CHUNK_SIZE) as u64;
+		assert_eq!(stats.sorted_counts(), vec![
+			(BlockType::Stone, stone_count),
+			(BlockType::Air, 2 * chunk_blocks - stone_count),
+		]);
+
+		let table = stats.table();
+		assert_eq!(table.len(), 3);
+		assert!(table[0].contains("2 chunks"));
+		assert!(table[1].starts_with("Stone") && table[1].contains(&stone_count.to_string()));
+
+		// a chunk being changed is skipped instead of waited on
+		let changing = stone.get_block_mut(BlockPos::new(0, 0, 0));
+		stats.add_chunk(&stone);
+		drop(changing);
+		assert_eq!(stats.chunks_scanned, 2);
+		assert_eq!(stats.skipped_chunks, vec![ChunkPos::new(0, -1, 0)]);
+		assert!(stats.table().last().unwrap().starts_with("skipped 1 chunks"));
+		world.close();
+	}
+}
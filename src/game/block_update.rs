@@ -0,0 +1,205 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use rustc_hash::FxHashSet;
+
+use crate::prelude::*;
+
+// a change made by a neighbor changed hook notifies its neighbors too, with 1 more depth than the update that ran the hook
+// updates deeper than this are dropped, so hooks which keep changing each other's blocks can't loop forever
+pub const MAX_NEIGHBOR_UPDATE_DEPTH: u32 = 32;
+
+// block updates scheduled to run on a later tick, such as liquids flowing
+#[derive(Debug, Default)]
+pub struct BlockUpdateQueue {
+	// ordered by tick, then block coordinates so updates on the same tick always run in the same order
+	queue: BinaryHeap<Reverse<(u64, [i32; 3])>>,
+	// blocks which already have an update queued, a block only has 1 update queued at a time
+	pending: FxHashSet<BlockPos>,
+}
+
+impl BlockUpdateQueue {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// does nothing if the block already has an update scheduled
+	pub fn schedule(&mut self, block: BlockPos, tick: u64) {
+		if self.pending.insert(block) {
+			self.queue.push(Reverse((tick, block.to_array())));
+		}
+	}
+
+	// removes up to max_updates updates which are due on or before the tick
+	// updates past the limit stay queued and run on the next tick
+	pub fn pop_due(&mut self, tick: u64, max_updates: usize) -> Vec<BlockPos> {
+		let mut out = Vec::new();
+
+		while out.len() < max_updates {
+			match self.queue.peek() {
+				Some(Reverse((update_tick, _))) if *update_tick <= tick => {
+					let Reverse((_, [x, y, z])) = self.queue.pop().unwrap();
+					let block = BlockPos::new(x, y, z);
+					self.pending.remove(&block);
+					out.push(block);
+				},
+				_ => break,
+			}
+		}
+
+		out
+	}
+
+	// drops the updates of every block in the chunk, for when its blocks are replaced
+	pub fn remove_chunk(&mut self, chunk: ChunkPos) {
+		self.queue.retain(|Reverse((_, [x, y, z]))| BlockPos::new(*x, *y, *z).as_chunk_pos() != chunk);
+		self.pending.retain(|block| block.as_chunk_pos() != chunk);
+	}
+
+	pub fn len(&self) -> usize {
+		self.queue.len()
+	}
+
+	#[cfg(test)]
+	pub fn is_empty(&self) -> bool {
+		self.queue.is_empty()
+	}
+}
+
+// tells a block that one of its neighbors changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeighborUpdate {
+	pub block: BlockPos,
+	pub changed: BlockPos,
+	// 1 for changes not made by a neighbor changed hook
+	pub depth: u32,
+}
+
+// neighbor updates are queued and run on the world tick, instead of recursively from set_block
+#[derive(Debug, Default)]
+pub struct NeighborUpdateQueue {
+	queue: VecDeque<NeighborUpdate>,
+	// a block is only told about the same neighbor once until the update runs
+	pending: FxHashSet<(BlockPos, BlockPos)>,
+	// number of updates dropped for being too deep
+	dropped: u64,
+}
+
+impl NeighborUpdateQueue {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// returns false if the update was dropped for being deeper than MAX_NEIGHBOR_UPDATE_DEPTH
+	pub fn push(&mut self, update: NeighborUpdate) -> bool {
+		if update.depth > MAX_NEIGHBOR_UPDATE_DEPTH {
+			self.dropped += 1;
+			return false;
+		}
+
+		if self.pending.insert((update.block, update.changed)) {
+			self.queue.push_back(update);
+		}
+		true
+	}
+
+	pub fn pop(&mut self) -> Option<NeighborUpdate> {
+		let update = self.queue.pop_front()?;
+		self.pending.remove(&(update.block, update.changed));
+		Some(update)
+	}
+
+	pub fn len(&self) -> usize {
+		self.queue.len()
+	}
+
+	pub fn dropped_count(&self) -> u64 {
+		self.dropped
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn updates_run_in_tick_order_with_limit() {
+		let mut queue = BlockUpdateQueue::new();
+		queue.schedule(BlockPos::new(0, 0, 0), 5);
+		queue.schedule(BlockPos::new(1, 0, 0), 2);
+		queue.schedule(BlockPos::new(2, 0, 0), 2);
+		// already scheduled, so this is ignored
+		queue.schedule(BlockPos::new(1, 0, 0), 1);
+
+		assert!(queue.pop_due(1, 10).is_empty());
+		assert_eq!(queue.pop_due(2, 1), vec![BlockPos::new(1, 0, 0)]);
+		// the update past the limit runs on the next tick
+		assert_eq!(queue.pop_due(3, 10), vec![BlockPos::new(2, 0, 0)]);
+		assert_eq!(queue.pop_due(10, 10), vec![BlockPos::new(0, 0, 0)]);
+		assert!(queue.is_empty());
+	}
+
+	#[test]
+	fn removing_a_chunk_keeps_other_updates() {
+		let mut queue = BlockUpdateQueue::new();
+		queue.schedule(BlockPos::new(3, 4, 5), 2);
+		queue.schedule(BlockPos::new(40, 4, 5), 2);
+		queue.remove_chunk(ChunkPos::new(0, 0, 0));
+
+		assert_eq!(queue.pop_due(2, 10), vec![BlockPos::new(40, 4, 5)]);
+		// the removed block can be scheduled again
+		queue.schedule(BlockPos::new(3, 4, 5), 3);
+		assert_eq!(queue.pop_due(3, 10), vec![BlockPos::new(3, 4, 5)]);
+	}
+
+	// a chain of updates where each one causes the next is cut off at the max depth
+	#[test]
+	fn neighbor_update_cascade_is_limited() {
+		let mut queue = NeighborUpdateQueue::new();
+		let mut depth = 1;
+		assert!(queue.push(NeighborUpdate {
+			block: BlockPos::new(1, 0, 0),
+			changed: BlockPos::new(0, 0, 0),
+			depth,
+		}));
+
+		let mut updates_run = 0;
+		while let Some(update) = queue.pop() {
+			updates_run += 1;
+			depth = update.depth + 1;
+			queue.push(NeighborUpdate {
+				block: update.block + BlockPos::new(1, 0, 0),
+				changed: update.block,
+				depth,
+			});
+		}
+
+		assert_eq!(updates_run, MAX_NEIGHBOR_UPDATE_DEPTH);
+		assert_eq!(depth, MAX_NEIGHBOR_UPDATE_DEPTH + 1);
+		assert_eq!(queue.dropped_count(), 1);
+	}
+
+	#[test]
+	fn duplicate_neighbor_updates_run_once() {
+		let mut queue = NeighborUpdateQueue::new();
+		let update = NeighborUpdate {
+			block: BlockPos::new(0, 1, 0),
+			changed: BlockPos::new(0, 0, 0),
+			depth: 1,
+		};
+		queue.push(update);
+		queue.push(update);
+		// a different neighbor changing is a different update
+		queue.push(NeighborUpdate {
+			changed: BlockPos::new(0, 2, 0),
+			..update
+		});
+
+		assert_eq!(queue.len(), 2);
+		assert_eq!(queue.pop(), Some(update));
+		queue.pop();
+		// it can be queued again once it has run
+		queue.push(update);
+		assert_eq!(queue.len(), 1);
+	}
+}
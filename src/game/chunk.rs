@@ -1,16 +1,26 @@
+use std::mem;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
 
+use anyhow::{anyhow, bail};
 use array_init::array_init;
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use rustc_hash::{FxHashMap, FxHashSet};
 
-use super::block::{Block, BlockTrait, BlockFaceMesh, BlockFace, OcclusionCorners};
-use super::entity::Entity;
-use super::world::World;
+use super::block::{Block, BlockType, BlockTrait, BlockFaceMesh, BlockFace, OcclusionCorners, OcclusionLevel, Water, Lava, SOURCE_LIQUID_LEVEL};
+use super::block_entity::BlockEntity;
+use super::entity::{Entity, EntityId, SavedEntity};
+use super::save_format::{Record, Tag};
+use super::world::{World, SavedChunk};
 use crate::prelude::*;
 
 pub const CHUNK_SIZE: usize = 32;
+// size of the block storage of a chunk, which is the same for every chunk
+pub const CHUNK_BLOCK_BYTES: u64 = mem::size_of::<[[[Block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>() as u64;
+// block entities in a chunk which is loaded again are ticked for the time it was unloaded, up to this many ticks,
+// so loading a chunk which was unloaded for a long time doesn't stall the tick
+pub const MAX_BLOCK_ENTITY_CATCH_UP_TICKS: u64 = 1200;
 
 // says all blocks that have been visited for the greedy meshing algorithm in a given layer
 pub struct VisitedBlockMap {
@@ -61,8 +71,77 @@ impl VisitedBlockMap {
 
 type BlockArray = Box<[[[Block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>;
 
+// blocks which hide every face next to them, no face between 2 of these is ever meshed
+fn is_opaque_cube(block: &Block) -> bool {
+	!block.is_translucent() && block.model_height() >= 1.0
+}
+
+// index of the axis a face is on, for OpacityCounts
+fn face_axis(face: BlockFace) -> usize {
+	match face {
+		BlockFace::XPos | BlockFace::XNeg => 0,
+		BlockFace::YPos | BlockFace::YNeg => 1,
+		BlockFace::ZPos | BlockFace::ZNeg => 2,
+	}
+}
+
+// the layer of a face's blocks which is next to the chunk the faces point towards
+pub fn border_layer(face: BlockFace) -> usize {
+	if face.is_positive_face() {
+		CHUNK_SIZE - 1
+	} else {
+		0
+	}
+}
+
+// number of opaque cubes in each layer of the chunk along each axis, indexed by axis then layer
+type OpacityCounts = [[u16; CHUNK_SIZE]; 3];
+
+// the opacity counts are kept under the same lock as the blocks, so they are always up to date with them
+struct ChunkBlocks {
+	blocks: BlockArray,
+	opacity_counts: OpacityCounts,
+}
+
+impl ChunkBlocks {
+	fn new(blocks: BlockArray) -> Self {
+		let mut opacity_counts = [[0; CHUNK_SIZE]; 3];
+		for x in 0..CHUNK_SIZE {
+			for y in 0..CHUNK_SIZE {
+				for z in 0..CHUNK_SIZE {
+					if is_opaque_cube(&blocks[x][y][z]) {
+						opacity_counts[0][x] += 1;
+						opacity_counts[1][y] += 1;
+						opacity_counts[2][z] += 1;
+					}
+				}
+			}
+		}
+
+		ChunkBlocks {
+			blocks,
+			opacity_counts,
+		}
+	}
+
+	// called after the block at the indicies changed from opaque to not opaque, or the other way
+	fn update_opacity_counts(&mut self, (x, y, z): (usize, usize, usize), opaque: bool) {
+		for (axis, layer) in [x, y, z].into_iter().enumerate() {
+			if opaque {
+				self.opacity_counts[axis][layer] += 1;
+			} else {
+				self.opacity_counts[axis][layer] -= 1;
+			}
+		}
+	}
+
+	fn layer_is_opaque(&self, axis: usize, layer: usize) -> bool {
+		self.opacity_counts[axis][layer] as usize == CHUNK_SIZE * CHUNK_SIZE
+	}
+}
+
 pub struct ChunkBlockRef<'a> {
-	_block_lock: RwLockReadGuard<'a, BlockArray>,
+	_block_lock: RwLockReadGuard<'a, ChunkBlocks>,
 	block: *const Block,
 }
 
@@ -75,9 +154,12 @@ impl Deref for ChunkBlockRef<'_> {
 	}
 }
 
+// the block may be changed in any way through this, so the opacity counts are updated when it is dropped
 pub struct ChunkBlockRefMut<'a> {
-	_block_lock: RwLockWriteGuard<'a, BlockArray>,
+	block_lock: RwLockWriteGuard<'a, ChunkBlocks>,
 	block: *mut Block,
+	indicies: (usize, usize, usize),
+	was_opaque: bool,
 }
 
 impl Deref for ChunkBlockRefMut<'_> {
@@ -96,6 +178,15 @@ impl DerefMut for ChunkBlockRefMut<'_> {
 	}
 }
 
+impl Drop for ChunkBlockRefMut<'_> {
+	fn drop(&mut self) {
+		let opaque = is_opaque_cube(self);
+		if opaque != self.was_opaque {
+			self.block_lock.update_opacity_counts(self.indicies, opaque);
+		}
+	}
+}
+
 pub struct Chunk {
 	world: Arc<World>,
 	// position of back bottom left corner of chunk in block coordinates
@@ -106,9 +197,22 @@ pub struct Chunk {
 	// coordinates of bottom left back block in world space
 	block_position: BlockPos,
 	// store them on heap to avoid stack overflow
-	blocks: RwLock<BlockArray>,
+	blocks: RwLock<ChunkBlocks>,
+	// keyed by chunk local position, see BlockEntity
+	block_entities: RwLock<FxHashMap<BlockPos, BlockEntity>>,
+	// chunk local positions of the block entities which are ticked, see BlockEntity::is_ticking
+	ticking_block_entities: RwLock<FxHashSet<BlockPos>>,
+	// the world tick the block entities were last ticked on, none if they haven't been ticked yet
+	last_block_entity_tick: Mutex<Option<u64>>,
 	//chunk_mesh: HashMap<BlockPos, Vec<BlockFaceMesh>>,
 	chunk_mesh: RwLock<Box<[[Vec<BlockFaceMesh>; CHUNK_SIZE]; 6]>>,
+	// one bit for each face whose border layer was last meshed while the chunk next to it wasn't loaded,
+	// those faces were left out, so the border has to be meshed again once that chunk is loaded
+	unmeshed_borders: AtomicU8,
+	// set when the blocks or block entities change, and cleared when the chunk is saved
+	modified: AtomicBool,
+	// fields from the chunk's save that this version doesn't know, they are saved again unchanged
+	unknown_fields: Record,
 }
 
 impl Chunk {
@@ -133,11 +237,33 @@ impl Chunk {
 			position: Position::new(x, y, z),
 			chunk_position: position,
 			block_position,
-			blocks: RwLock::new(blocks),
+			blocks: RwLock::new(ChunkBlocks::new(blocks)),
+			block_entities: RwLock::new(FxHashMap::default()),
+			ticking_block_entities: RwLock::new(FxHashSet::default()),
+			last_block_entity_tick: Mutex::new(None),
 			chunk_mesh: RwLock::new(Box::new(array_init(|_| array_init(|_| Vec::new())))),
+			unmeshed_borders: AtomicU8::new(0),
+			modified: AtomicBool::new(false),
+			unknown_fields: Record::new(),
 		}
 	}
 
+	// creates a chunk where every block is the same
+	pub fn filled(world: Arc<World>, position: ChunkPos, block: Block) -> Self {
+		Self::new(world, position, |_| block.clone())
+	}
+
+	// bytes allocated for the chunk's mesh, including unused capacity in each layer
+	pub fn mesh_bytes(&self) -> u64 {
+		let chunk_mesh = self.chunk_mesh.read();
+		let face_count = chunk_mesh.iter()
+			.flatten()
+			.map(|layer| layer.capacity())
+			.sum::<usize>();
+
+		(mem::size_of::<[[Vec<BlockFaceMesh>; CHUNK_SIZE]; 6]>() + face_count * mem::size_of::<BlockFaceMesh>()) as u64
+	}
+
 	// calls the function on the given block position
 	// the block may be from another chunk
 	#[inline]
@@ -177,7 +303,7 @@ impl Chunk {
 		let (x, y, z) = block.as_indicies().unwrap();
 
 		let block_lock = self.blocks.read();
-		let block = &block_lock[x][y][z] as *const Block;
+		let block = &block_lock.blocks[x][y][z] as *const Block;
 		ChunkBlockRef {
 			_block_lock: block_lock,
 			block,
@@ -188,12 +314,16 @@ impl Chunk {
 	pub fn get_block_mut(&self, block: BlockPos) -> ChunkBlockRefMut {
 		assert!(block.is_chunk_local());
 		let (x, y, z) = block.as_indicies().unwrap();
+		self.mark_modified();
 
 		let mut block_lock = self.blocks.write();
-		let block = &mut block_lock[x][y][z] as *mut Block;
+		let block = &mut block_lock.blocks[x][y][z] as *mut Block;
+		let was_opaque = is_opaque_cube(&block_lock.blocks[x][y][z]);
 		ChunkBlockRefMut {
-			_block_lock: block_lock,
+			block_lock,
 			block,
+			indicies: (x, y, z),
+			was_opaque,
 		}
 	}
 
@@ -202,11 +332,163 @@ impl Chunk {
 		assert!(block_pos.is_chunk_local());
 		let (x, y, z) = block_pos.as_indicies().unwrap();
 
-		self.blocks.write()[x][y][z] = block;
+		self.mark_modified();
+		let mut blocks = self.blocks.write();
+		let opaque = is_opaque_cube(&block);
+		if opaque != is_opaque_cube(&blocks.blocks[x][y][z]) {
+			blocks.update_opacity_counts((x, y, z), opaque);
+		}
+		blocks.blocks[x][y][z] = block;
+	}
+
+	// adds the number of blocks of each type to counts, which is indexed by block type
+	// returns false without counting anything if the blocks are locked for writing, so nothing waits on a chunk being changed
+	pub fn try_count_block_types(&self, counts: &mut [u64]) -> bool {
+		let blocks = match self.blocks.try_read() {
+			Some(blocks) => blocks,
+			None => return false,
+		};
+
+		for block in blocks.blocks.iter().flatten().flatten() {
+			counts[block.block_type() as usize] += 1;
+		}
+		true
+	}
+
+	pub(super) fn mark_modified(&self) {
+		self.modified.store(true, Ordering::Release);
+	}
+
+	// true if the blocks or block entities changed since the last call, or since the chunk was generated or loaded
+	pub fn take_modified(&self) -> bool {
+		self.modified.swap(false, Ordering::AcqRel)
+	}
+
+	pub fn has_block_entities(&self) -> bool {
+		!self.block_entities.read().is_empty()
+	}
+
+	pub fn block_entity_count(&self) -> usize {
+		self.block_entities.read().len()
+	}
+
+	// calls the function on the block entity at the chunk local position, none if there isn't one
+	pub fn with_block_entity_mut<T, F>(&self, block_pos: BlockPos, f: F) -> Option<T>
+		where F: FnOnce(&mut BlockEntity) -> T {
+		assert!(block_pos.is_chunk_local());
+		let mut block_entities = self.block_entities.write();
+		let block_entity = block_entities.get_mut(&block_pos)?;
+		self.mark_modified();
+		Some(f(block_entity))
+	}
+
+	// replaces any block entity already at the chunk local position
+	pub fn set_block_entity(&self, block_pos: BlockPos, block_entity: BlockEntity) {
+		assert!(block_pos.is_chunk_local());
+		self.mark_modified();
+		let mut block_entities = self.block_entities.write();
+		let mut ticking_block_entities = self.ticking_block_entities.write();
+
+		if block_entity.is_ticking() {
+			// a chunk which had nothing to tick has nothing to catch up on
+			if ticking_block_entities.is_empty() {
+				*self.last_block_entity_tick.lock() = None;
+			}
+			ticking_block_entities.insert(block_pos);
+		} else {
+			ticking_block_entities.remove(&block_pos);
+		}
+		block_entities.insert(block_pos, block_entity);
+	}
+
+	pub fn remove_block_entity(&self, block_pos: BlockPos) -> Option<BlockEntity> {
+		assert!(block_pos.is_chunk_local());
+		let mut block_entities = self.block_entities.write();
+		self.ticking_block_entities.write().remove(&block_pos);
+		let removed = block_entities.remove(&block_pos);
+		if removed.is_some() {
+			self.mark_modified();
+		}
+		removed
+	}
+
+	pub fn has_ticking_block_entities(&self) -> bool {
+		!self.ticking_block_entities.read().is_empty()
+	}
+
+	// ticks every ticking block entity once for each world tick since they were last ticked,
+	// so ticks missed while the chunk was unloaded are caught up, up to MAX_BLOCK_ENTITY_CATCH_UP_TICKS
+	pub fn tick_block_entities(&self, tick_number: u64) {
+		let ticks = {
+			let mut last_tick = self.last_block_entity_tick.lock();
+			let ticks = last_tick.map_or(1, |last_tick| tick_number.saturating_sub(last_tick)).min(MAX_BLOCK_ENTITY_CATCH_UP_TICKS);
+			*last_tick = Some(tick_number);
+			ticks
+		};
+
+		let mut block_entities = self.block_entities.write();
+		for local in self.ticking_block_entities.read().iter() {
+			if let Some(block_entity) = block_entities.get_mut(local) {
+				self.mark_modified();
+				let block = self.block_position + *local;
+				for _ in 0..ticks {
+					block_entity.tick(&self.world, block);
+				}
+			}
+		}
+	}
+
+	// true if no face in the layer can be visible, because the layer and the layer the faces point towards are both fully opaque
+	// the layer the faces point towards may be in another chunk, then this is always false
+	fn layer_is_hidden(&self, face: BlockFace, index: usize) -> bool {
+		let axis = face_axis(face);
+		let neighbor = index as i32 + face.block_pos_offset().get_face_component(face);
+		if neighbor < 0 || neighbor >= CHUNK_SIZE as i32 {
+			return false;
+		}
+
+		let blocks = self.blocks.read();
+		blocks.layer_is_opaque(axis, index) && blocks.layer_is_opaque(axis, neighbor as usize)
+	}
+
+	// if the face of the block at the chunk local block_pos is drawn, none if the block it faces isn't loaded
+	// liquid faces next to the same liquid are hidden, and the lowered top of short blocks is always visible
+	#[inline]
+	pub fn face_visibility(&self, block: &Block, block_pos: BlockPos, face: BlockFace) -> Option<bool> {
+		self.with_block(block_pos + face.block_pos_offset(), |neighbor| {
+			if block.is_liquid() && neighbor.block_type() == block.block_type() {
+				false
+			} else {
+				neighbor.is_translucent() || (face == BlockFace::YPos && block.model_height() < 1.0)
+			}
+		})
 	}
 
 	// the visit map is passed in seperately to avoid having to reallocat the memory for the visit map every time	
 	pub fn mesh_update_inner(&self, face: BlockFace, index: usize, visit_map: &mut VisitedBlockMap) {
+		// the neighbor is checked before meshing, so if it is loaded in the meantime,
+		// either this still sees the border as unmeshed, or the meshing sees the neighbor
+		if index == border_layer(face) {
+			let neighbor = self.chunk_position + ChunkPos(face.block_pos_offset().0);
+			let bit = 1 << face as u8;
+			if self.world.chunks.contains_key(&neighbor) {
+				self.unmeshed_borders.fetch_and(!bit, Ordering::AcqRel);
+			} else {
+				self.unmeshed_borders.fetch_or(bit, Ordering::AcqRel);
+			}
+		}
+
+		// most layers underground are hidden, so this skips most of the work of meshing them
+		if self.layer_is_hidden(face, index) {
+			self.chunk_mesh.write()[Into::<usize>::into(face)][index].clear();
+			return;
+		}
+
+		self.mesh_layer(face, index, visit_map);
+	}
+
+	// meshes every block in the layer, even if the layer is hidden
+	fn mesh_layer(&self, face: BlockFace, index: usize, visit_map: &mut VisitedBlockMap) {
 		visit_map.set_face_coord(face, index as i32);
 		let mut chunk_mesh = self.chunk_mesh.write();
 		chunk_mesh[Into::<usize>::into(face)][index].clear();
@@ -226,7 +508,7 @@ impl Chunk {
 		};
 
 		// FIXME: inconsistance arguments
-		let vertex_occlusion_level = |x: i32, y: i32| {
+		let hard_occlusion_level = |x: i32, y: i32| -> u8 {
 			let tl_occludes = is_occluded_by(visit_map.get_block_pos(x - 1, y - 1));
 			let tr_occludes = is_occluded_by(visit_map.get_block_pos(x, y - 1));
 			let bl_occludes = is_occluded_by(visit_map.get_block_pos(x - 1, y));
@@ -241,6 +523,36 @@ impl Chunk {
 			return occlusion_level;
 		};
 
+		let face_visible = |block: &Block, block_pos: BlockPos| self.face_visibility(block, block_pos, face).unwrap_or(false);
+
+		// liquid with the same liquid on top fills its whole block, so falling liquid looks connected
+		let up_offset = BlockFace::YPos.block_pos_offset();
+		let block_height = |block: &Block, block_pos: BlockPos| {
+			let covered = block.is_liquid()
+				&& self.with_block(block_pos + up_offset, |above| above.block_type() == block.block_type()).unwrap_or(false);
+
+			if covered {
+				1.0
+			} else {
+				block.model_height()
+			}
+		};
+
+		let smooth_lighting = self.world.smooth_lighting();
+		let vertex_occlusion_level = |x: i32, y: i32| -> OcclusionLevel {
+			if smooth_lighting {
+				// averaging with the adjacent vertices on the face smooths out the steps between levels
+				let level_sum = hard_occlusion_level(x, y)
+					+ hard_occlusion_level(x - 1, y)
+					+ hard_occlusion_level(x + 1, y)
+					+ hard_occlusion_level(x, y - 1)
+					+ hard_occlusion_level(x, y + 1);
+				level_sum as OcclusionLevel / 5.0
+			} else {
+				hard_occlusion_level(x, y) as OcclusionLevel
+			}
+		};
+
 		let face_occlusion_data = |block_pos: BlockPos| {
 			let (x, y) = visit_map.get_index(block_pos);
 			let x = x as i32;
@@ -259,20 +571,13 @@ impl Chunk {
 				let block_pos = visit_map.get_block_pos(x, y);
 
 				let block = self.get_block(block_pos);
-				if block.is_air() {
-					y += 1;
-					continue;
-				} else if let Some(is_translucent) = self.with_block(block_pos + face_offset, |block| block.is_translucent()) {
-					if !is_translucent {
-						y += 1;
-						continue;
-					}
-				} else {
+				if block.is_air() || !face_visible(&block, block_pos) {
 					y += 1;
 					continue;
 				}
 
 				let block_type = block.block_type();
+				let height = block_height(&block, block_pos);
 
 				// width and height of the greedy mesh region
 				let mut width = 1;
@@ -280,7 +585,10 @@ impl Chunk {
 				let occlusion_corners = face_occlusion_data(block_pos);
 
 				// to be growable, the ambient occlusion level of the vertext on each respective x level has to be the same
-				let growable = occlusion_corners.tl == occlusion_corners.bl && occlusion_corners.tr == occlusion_corners.br;
+				// short blocks are not merged, since their lowered top would be stretched over the blocks they are merged with
+				let growable = occlusion_corners.tl == occlusion_corners.bl
+					&& occlusion_corners.tr == occlusion_corners.br
+					&& height == 1.0;
 
 				if growable {
 					loop {
@@ -290,15 +598,14 @@ impl Chunk {
 							break;
 						}
 
-						if let Some(is_translucent) = self.with_block(block_pos + face_offset, |block| block.is_translucent()) {
-							if is_translucent && self.get_block(current_block_pos).block_type() == block_type {
-								// TODO: don't need to calculate all occlusion corners, only 2
-								let occlusion_corners_new = face_occlusion_data(current_block_pos);
-								if occlusion_corners_new.tl == occlusion_corners.tl && occlusion_corners_new.tr == occlusion_corners.tr {
-									width += 1;
-								} else {
-									break;
-								}
+						let current_block = self.get_block(current_block_pos);
+						if current_block.block_type() == block_type
+							&& face_visible(&current_block, current_block_pos)
+							&& block_height(&current_block, current_block_pos) == 1.0 {
+							// TODO: don't need to calculate all occlusion corners, only 2
+							let occlusion_corners_new = face_occlusion_data(current_block_pos);
+							if occlusion_corners_new.tl == occlusion_corners.tl && occlusion_corners_new.tr == occlusion_corners.tr {
+								width += 1;
 							} else {
 								break;
 							}
@@ -308,13 +615,18 @@ impl Chunk {
 					}
 				}
 
-				let block_face_mesh = BlockFaceMesh::from_cube_corners(
+				let mut block_face_mesh = BlockFaceMesh::from_cube_corners(
 					face,
 					block.texture_index().unwrap(),
 					block_pos + self.block_position,
 					visit_map.get_block_pos_offset(block_pos, 0, width - 1) + self.block_position,
 					occlusion_corners,
 				);
+
+				if height < 1.0 {
+					let block_top = (block_pos.y + self.block_position.y + 1) as f32;
+					block_face_mesh.lower_top(block_top, 1.0 - height);
+				}
 	
 				chunk_mesh[Into::<usize>::into(face)][index].push(block_face_mesh);
 
@@ -324,6 +636,7 @@ impl Chunk {
 	}
 
 	// updates the mesh for the entire chunk
+	#[instrument(level = "debug", skip(self), fields(chunk = ?self.chunk_position))]
 	pub fn chunk_mesh_update(&self) {
 		let mut visit_map = VisitedBlockMap::new();
 
@@ -334,6 +647,26 @@ impl Chunk {
 		}
 	}
 
+	// true if the border layer of the face was meshed while the chunk next to it wasn't loaded
+	pub fn has_unmeshed_border(&self, face: BlockFace) -> bool {
+		self.unmeshed_borders.load(Ordering::Acquire) & (1 << face as u8) != 0
+	}
+
+	pub fn chunk_position(&self) -> ChunkPos {
+		self.chunk_position
+	}
+
+	// coordinates of the bottom left back block in world space
+	pub fn block_position(&self) -> BlockPos {
+		self.block_position
+	}
+
+	// calls f with the faces in each layer of the mesh, indexed by face and then by the layer's coordinate along the face's axis
+	pub fn with_mesh<T, F>(&self, f: F) -> T
+		where F: FnOnce(&[[Vec<BlockFaceMesh>; CHUNK_SIZE]; 6]) -> T {
+		f(&self.chunk_mesh.read())
+	}
+
 	// returns None if the mesh is currently locked, which means it is being generated,
 	// so we wouldn't have to display it anyways
 	pub fn get_chunk_mesh(&self) -> Option<Vec<BlockFaceMesh>> {
@@ -372,9 +705,372 @@ impl LoadedChunk {
 	}
 }
 
-// the entire saved state of the chunk, which is all blocks and entities
+// the entire saved state of the chunk, which is all blocks, block entities and entities
 // TODO: maybe save chunk mesh to load faster
 pub struct ChunkData {
 	chunk: Chunk,
-	entities: Vec<Box<dyn Entity>>,
+	// the entities which were in the chunk when it was unloaded, they go back in the world with the same ids when it is loaded
+	entities: Vec<(EntityId, Box<dyn Entity>)>,
+}
+
+impl ChunkData {
+	pub fn new(chunk: Chunk, entities: Vec<(EntityId, Box<dyn Entity>)>) -> Self {
+		ChunkData {
+			chunk,
+			entities,
+		}
+	}
+
+	// the chunk is ready to be put back in the world, its entities still have to be restored
+	pub fn into_parts(self) -> SavedChunk {
+		(LoadedChunk::new(self.chunk), self.entities)
+	}
+
+	pub fn entity_count(&self) -> usize {
+		self.entities.len()
+	}
+
+	// entities are not counted
+	pub fn memory_bytes(&self) -> u64 {
+		CHUNK_BLOCK_BYTES + self.chunk.mesh_bytes()
+	}
+}
+
+impl ChunkData {
+	// only increased for changes older versions can't read, new fields can be added without changing it
+	pub const FORMAT_VERSION: u32 = 1;
+
+	// fields of a saved chunk
+	const TAG_VERSION: Tag = 0;
+	const TAG_PALETTE: Tag = 1;
+	const TAG_BLOCKS: Tag = 2;
+	const TAG_BLOCK_ENTITY: Tag = 3;
+	const TAG_ENTITY: Tag = 4;
+	const TAG_LAST_BLOCK_ENTITY_TICK: Tag = 5;
+	// fields of each palette entry
+	const TAG_PALETTE_TYPE: Tag = 0;
+	const TAG_PALETTE_LEVEL: Tag = 1;
+	// fields of each saved block entity
+	const TAG_BLOCK_ENTITY_POSITION: Tag = 0;
+	const TAG_BLOCK_ENTITY_DATA: Tag = 1;
+
+	// the chunk is saved as a record with a palette of the different blocks in it, and an index into the palette for each block
+	// block entities and entities are each saved as their own record, entities which aren't saved are left out
+	pub fn encode(&self) -> Vec<u8> {
+		Self::encode_parts(&self.chunk, self.entities.iter().map(|(_, entity)| &**entity))
+	}
+
+	// encodes a chunk which is still loaded, with the entities in it
+	pub fn encode_parts<'a>(chunk: &Chunk, entities: impl Iterator<Item = &'a dyn Entity>) -> Vec<u8> {
+		let mut record = Record::new();
+		record.push_u32(Self::TAG_VERSION, Self::FORMAT_VERSION);
+
+		let mut palette: Vec<(BlockType, Option<u8>)> = Vec::new();
+		let mut indicies = Vec::with_capacity(2 * CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+		{
+			let blocks = chunk.blocks.read();
+			for block in blocks.blocks.iter().flatten().flatten() {
+				let entry = (block.block_type(), block.liquid_level());
+				let index = palette.iter().position(|palette_entry| *palette_entry == entry).unwrap_or_else(|| {
+					palette.push(entry);
+					palette.len() - 1
+				});
+				indicies.extend((index as u16).to_le_bytes());
+			}
+		}
+
+		for (block_type, level) in palette {
+			let mut entry = Record::new();
+			entry.push_str(Self::TAG_PALETTE_TYPE, block_type.name());
+			if let Some(level) = level {
+				entry.push_u8(Self::TAG_PALETTE_LEVEL, level);
+			}
+			record.push_record(Self::TAG_PALETTE, &entry);
+		}
+		record.push(Self::TAG_BLOCKS, indicies);
+
+		// sorted so the same chunk is always saved the same way
+		let block_entities = chunk.block_entities.read();
+		let mut positions: Vec<BlockPos> = block_entities.keys().copied().collect();
+		positions.sort_by_key(|position| position.to_array());
+		for position in positions {
+			let mut entry = Record::new();
+			entry.push_ivec3(Self::TAG_BLOCK_ENTITY_POSITION, *position);
+			entry.push_record(Self::TAG_BLOCK_ENTITY_DATA, &block_entities[&position].save());
+			record.push_record(Self::TAG_BLOCK_ENTITY, &entry);
+		}
+		// saved so the time the chunk is unloaded for is caught up when it is loaded
+		if let Some(last_tick) = *chunk.last_block_entity_tick.lock() {
+			record.push_u64(Self::TAG_LAST_BLOCK_ENTITY_TICK, last_tick);
+		}
+
+		for entity in entities {
+			if let Some(saved) = entity.save() {
+				record.push_record(Self::TAG_ENTITY, &saved.encode());
+			}
+		}
+
+		record.extend(chunk.unknown_fields.clone());
+		record.encode()
+	}
+
+	// the entities get new ids, since ids aren't kept between runs
+	// unknown fields, block entities and entities are kept, so saving the chunk again doesn't lose them
+	pub fn decode(world: Arc<World>, position: ChunkPos, bytes: &[u8]) -> Result<Self> {
+		let mut record = Record::decode(bytes)?;
+
+		let version = record.take_u32(Self::TAG_VERSION)?.ok_or_else(|| anyhow!("chunk has no format version"))?;
+		if version > Self::FORMAT_VERSION {
+			bail!("chunk is saved with format version {}, only versions up to {} can be read", version, Self::FORMAT_VERSION);
+		}
+
+		let palette = record.take_all(Self::TAG_PALETTE).into_iter()
+			.map(|entry| Self::palette_block(Record::decode(&entry)?))
+			.collect::<Result<Vec<Block>>>()?;
+
+		let indicies = record.take(Self::TAG_BLOCKS).ok_or_else(|| anyhow!("chunk has no blocks"))?;
+		if indicies.len() != 2 * CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE {
+			bail!("chunk has {} bytes of blocks, expected {}", indicies.len(), 2 * CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+		}
+		if let Some(index) = indicies.chunks(2).map(|index| u16::from_le_bytes([index[0], index[1]]) as usize).find(|index| *index >= palette.len()) {
+			bail!("block palette index {} is past the end of the palette, which has {} blocks", index, palette.len());
+		}
+
+		let block_position = position.as_block_pos();
+		let mut chunk = Chunk::new(world, position, |block| {
+			let local = block - block_position;
+			let offset = 2 * ((local.x as usize * CHUNK_SIZE + local.y as usize) * CHUNK_SIZE + local.z as usize);
+			palette[u16::from_le_bytes([indicies[offset], indicies[offset + 1]]) as usize].clone()
+		});
+
+		for data in record.take_all(Self::TAG_BLOCK_ENTITY) {
+			let mut entry = Record::decode(&data)?;
+			let local = entry.take_ivec3(Self::TAG_BLOCK_ENTITY_POSITION)?.ok_or_else(|| anyhow!("block entity has no position"))?;
+			let saved = entry.take_record(Self::TAG_BLOCK_ENTITY_DATA)?.ok_or_else(|| anyhow!("block entity has no data"))?;
+			if !BlockPos(local).is_chunk_local() {
+				bail!("block entity position {} is outside of the chunk", local);
+			}
+
+			match BlockEntity::load(saved)? {
+				Some(block_entity) => chunk.set_block_entity(BlockPos(local), block_entity),
+				None => chunk.unknown_fields.push(Self::TAG_BLOCK_ENTITY, data),
+			}
+		}
+
+		// set after the block entities, since setting a ticking block entity clears it
+		*chunk.last_block_entity_tick.lock() = record.take_u64(Self::TAG_LAST_BLOCK_ENTITY_TICK)?;
+
+		let entities = record.take_all(Self::TAG_ENTITY).into_iter()
+			.map(|data| Ok((EntityId::new(), SavedEntity::decode(Record::decode(&data)?)?.load())))
+			.collect::<Result<Vec<_>>>()?;
+
+		chunk.unknown_fields.extend(record);
+		// the chunk is the same as its save
+		chunk.take_modified();
+
+		Ok(ChunkData::new(chunk, entities))
+	}
+
+	// the block a palette entry is for, liquids are saved with their level
+	fn palette_block(mut entry: Record) -> Result<Block> {
+		let type_name = entry.take_string(Self::TAG_PALETTE_TYPE)?.ok_or_else(|| anyhow!("palette entry has no block type"))?;
+		let block_type = BlockType::from_name(&type_name).ok_or_else(|| anyhow!("unknown block type {}", type_name))?;
+
+		match (block_type, entry.take_u8(Self::TAG_PALETTE_LEVEL)?) {
+			(_, Some(level)) if !(1..=SOURCE_LIQUID_LEVEL).contains(&level) => bail!("invalid liquid level {}", level),
+			(BlockType::Water, Some(level)) => Ok(Water::with_level(level).into()),
+			(BlockType::Lava, Some(level)) => Ok(Lava::with_level(level).into()),
+			(_, Some(_)) => bail!("{} has no liquid level", type_name),
+			(_, None) => Ok(block_type.new_block()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::block::{Stone, StoneSlab, Dirt, Air, Water, Chest, BlockVertex};
+	use crate::game::block_entity::Container;
+	use crate::game::entity::Item;
+	use crate::game::worldgen::DeterministicRng;
+
+	// the mesh's vertices as bytes, so meshes can be compared
+	fn mesh_data(chunk: &Chunk) -> Vec<u8> {
+		let vertices: Vec<BlockVertex> = chunk.get_chunk_mesh().unwrap().iter().flat_map(|face| face.0).collect();
+		bytemuck::cast_slice(&vertices).to_vec()
+	}
+
+	// every layer meshed without skipping hidden layers
+	fn brute_force_mesh(chunk: &Chunk) -> Vec<u8> {
+		let mut visit_map = VisitedBlockMap::new();
+		for face in BlockFace::iter() {
+			for i in 0..CHUNK_SIZE {
+				chunk.mesh_layer(face, i, &mut visit_map);
+			}
+		}
+		mesh_data(chunk)
+	}
+
+	#[test]
+	fn skipping_hidden_layers_matches_brute_force() {
+		let world = World::new_test().unwrap();
+
+		let mut rng = DeterministicRng::for_test(24680);
+		let mut next = move |max: u32| rng.range(0..max as i32) as u32;
+
+		// mostly solid underground, with a few caves, and mixed blocks near the top
+		let chunk = Chunk::new(world, ChunkPos::new(0, 0, 0), |block| {
+			if block.y < 24 {
+				match next(200) {
+					0 => Air::new().into(),
+					1 => Water::new().into(),
+					2..=99 => Dirt::new().into(),
+					_ => Stone::new().into(),
+				}
+			} else {
+				match next(5) {
+					0 => StoneSlab::new().into(),
+					1 | 2 => Stone::new().into(),
+					_ => Air::new().into(),
+				}
+			}
+		});
+
+		// fill in the caves in some layers, so whole layers are opaque
+		for x in 0..CHUNK_SIZE as i32 {
+			for y in 0..16 {
+				for z in 0..CHUNK_SIZE as i32 {
+					chunk.set_block(BlockPos::new(x, y, z), Stone::new().into());
+				}
+			}
+		}
+		// and change some blocks above them through a mutable reference, which also has to keep the counts up to date
+		for _ in 0..40 {
+			let block = BlockPos::new(next(32) as i32, 16 + next(8) as i32, next(32) as i32);
+			*chunk.get_block_mut(block) = if next(2) == 0 { Air::new().into() } else { StoneSlab::new().into() };
+		}
+
+		let recounted = ChunkBlocks::new(chunk.blocks.read().blocks.clone());
+		assert_eq!(chunk.blocks.read().opacity_counts, recounted.opacity_counts);
+		assert!((0..CHUNK_SIZE).any(|layer| chunk.blocks.read().layer_is_opaque(1, layer)));
+
+		let expected = brute_force_mesh(&chunk);
+		chunk.chunk_mesh_update();
+		assert!(mesh_data(&chunk) == expected);
+	}
+
+	// a few different blocks, water which isn't a source, a chest with blocks in it, and an item
+	fn saved_chunk(world: Arc<World>) -> ChunkData {
+		let chunk = Chunk::new(world, ChunkPos::new(1, -1, 2), |block| {
+			if block.y < -20 {
+				Stone::new().into()
+			} else if block.y == -20 {
+				Dirt::new().into()
+			} else {
+				Air::new().into()
+			}
+		});
+		chunk.set_block(BlockPos::new(3, 13, 4), Water::with_level(5).into());
+		chunk.set_block(BlockPos::new(7, 13, 7), Chest::new().into());
+
+		let mut container = Container::new(27);
+		container.insert(BlockType::Sand, 12);
+		container.insert(BlockType::Stone, 3);
+		chunk.set_block_entity(BlockPos::new(7, 13, 7), BlockEntity::Container(container));
+
+		let item = Box::new(Item::new(Position::new(40.5, -19.0, 70.5), BlockType::Dirt, 4));
+		ChunkData::new(chunk, vec![(EntityId::new(), item)])
+	}
+
+	fn block_states(chunk: &Chunk) -> Vec<(BlockType, Option<u8>)> {
+		chunk.blocks.read().blocks.iter().flatten().flatten()
+			.map(|block| (block.block_type(), block.liquid_level()))
+			.collect()
+	}
+
+	#[test]
+	fn chunks_round_trip() {
+		let world = World::new_test().unwrap();
+		let data = saved_chunk(world.clone());
+		let bytes = data.encode();
+
+		let loaded = ChunkData::decode(world.clone(), ChunkPos::new(1, -1, 2), &bytes).unwrap();
+		assert_eq!(block_states(&loaded.chunk), block_states(&data.chunk));
+		assert_eq!(loaded.chunk.get_block(BlockPos::new(3, 13, 4)).liquid_level(), Some(5));
+		assert_eq!(*loaded.chunk.block_entities.read(), *data.chunk.block_entities.read());
+		assert_eq!(loaded.entity_count(), 1);
+		assert_eq!(loaded.entities[0].1.save(), data.entities[0].1.save());
+		assert_eq!(loaded.encode(), bytes);
+
+		assert!(ChunkData::decode(world.clone(), ChunkPos::new(1, -1, 2), &bytes[..bytes.len() - 3]).is_err());
+
+		let mut future = Record::decode(&bytes).unwrap();
+		future.take_u32(ChunkData::TAG_VERSION).unwrap();
+		future.push_u32(ChunkData::TAG_VERSION, ChunkData::FORMAT_VERSION + 1);
+		assert!(ChunkData::decode(world.clone(), ChunkPos::new(1, -1, 2), &future.encode()).is_err());
+
+		drop((data, loaded));
+		world.close();
+	}
+
+	#[test]
+	fn unknown_data_survives_loading_and_saving() {
+		let world = World::new_test().unwrap();
+		let mut record = Record::decode(&saved_chunk(world.clone()).encode()).unwrap();
+
+		// what a newer version might save: a new chunk field, an entity kind and a block entity kind this version doesn't have,
+		// and new fields on an item, both in its fields and next to its kind and position
+		record.push(900, vec![9, 8, 7, 6]);
+
+		let mut minecart_fields = Record::new();
+		minecart_fields.push_f32(0, 3.5);
+		let minecart = SavedEntity {
+			kind: String::from("minecart"),
+			position: Position::new(33.0, -20.0, 65.0),
+			fields: minecart_fields,
+			unknown_fields: Record::new(),
+		};
+		record.push_record(ChunkData::TAG_ENTITY, &minecart.encode());
+
+		let mut hopper = Record::new();
+		hopper.push_str(0, "hopper");
+		hopper.push_u32(7, 200);
+		let mut hopper_entry = Record::new();
+		hopper_entry.push_ivec3(ChunkData::TAG_BLOCK_ENTITY_POSITION, *BlockPos::new(8, 13, 7));
+		hopper_entry.push_record(ChunkData::TAG_BLOCK_ENTITY_DATA, &hopper);
+		record.push_record(ChunkData::TAG_BLOCK_ENTITY, &hopper_entry);
+
+		let mut item_fields = Record::new();
+		item_fields.push_str(0, "Sand");
+		item_fields.push(50, vec![1]);
+		let mut item_entity_fields = Record::new();
+		item_entity_fields.push(60, vec![2, 3]);
+		let item = SavedEntity {
+			kind: String::from("item"),
+			position: Position::new(34.0, -19.0, 66.0),
+			fields: item_fields,
+			unknown_fields: item_entity_fields,
+		};
+		record.push_record(ChunkData::TAG_ENTITY, &item.encode());
+
+		let loaded = ChunkData::decode(world.clone(), ChunkPos::new(1, -1, 2), &record.encode()).unwrap();
+		assert_eq!(loaded.entity_count(), 3);
+		assert_eq!(loaded.entities[1].1.save(), Some(minecart.clone()));
+		assert_eq!(loaded.chunk.block_entities.read().len(), 1);
+
+		let mut saved = Record::decode(&loaded.encode()).unwrap();
+		assert_eq!(saved.take(900), Some(vec![9, 8, 7, 6]));
+		assert!(saved.take_all(ChunkData::TAG_BLOCK_ENTITY).contains(&hopper_entry.encode()));
+		let entities = saved.take_all(ChunkData::TAG_ENTITY).into_iter()
+			.map(|entity| SavedEntity::decode(Record::decode(&entity).unwrap()).unwrap())
+			.collect::<Vec<_>>();
+		assert!(entities.contains(&minecart));
+		// the item is loaded, and keeps its new fields
+		let mut saved_item = entities.into_iter().find(|entity| entity.kind == "item" && entity.position == item.position).unwrap();
+		assert_eq!(saved_item.fields.take(50), Some(vec![1]));
+		assert_eq!(saved_item.unknown_fields.take(60), Some(vec![2, 3]));
+
+		drop(loaded);
+		world.close();
+	}
 }
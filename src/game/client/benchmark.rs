@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::prelude::*;
+use crate::game::parallel::{TaskLatency, TaskLatencyStats, LatencyPercentiles};
+
+// measurements taken during 1 physics update of a benchmark
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkSample {
+	// time since the previous physics update
+	pub frame_time: Duration,
+	pub triangle_count: u64,
+	// time the gpu spent on the main pass of a recent frame, none if the gpu can't measure it
+	pub gpu_time: Option<Duration>,
+	// number of chunk load jobs which finished during this update
+	pub chunk_loads_finished: usize,
+	// longest time between one of those jobs being requested and finishing
+	pub max_chunk_load_latency: Duration,
+	// rolling task latency percentiles, which are only updated about once a second
+	pub task_latency: TaskLatencyStats,
+}
+
+const CSV_HEADER: &str = "update,frame_time_ms,triangle_count,gpu_time_ms,chunk_loads_finished,max_chunk_load_latency_ms,\
+	generate_p50_ms,generate_p95_ms,generate_max_ms,generate_exec_p50_ms,generate_exec_p95_ms,generate_exec_max_ms,\
+	mesh_p50_ms,mesh_p95_ms,mesh_max_ms,mesh_exec_p50_ms,mesh_exec_p95_ms,mesh_exec_max_ms\n";
+
+fn milliseconds(duration: Duration) -> String {
+	format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+// queued to done percentiles, then running percentiles
+fn latency_columns(latency: &TaskLatency) -> String {
+	let percentiles = |percentiles: &LatencyPercentiles| [percentiles.p50, percentiles.p95, percentiles.max].map(milliseconds).join(",");
+	format!("{},{}", percentiles(&latency.total), percentiles(&latency.execution))
+}
+
+// collects a sample each physics update while a camera path is played back, and writes them to a csv file
+#[derive(Debug)]
+pub struct Benchmark {
+	output: PathBuf,
+	samples: Vec<BenchmarkSample>,
+}
+
+impl Benchmark {
+	pub fn new(output: PathBuf) -> Self {
+		Benchmark {
+			output,
+			samples: Vec::new(),
+		}
+	}
+
+	pub fn add_sample(
+		&mut self,
+		frame_time: Duration,
+		triangle_count: u64,
+		gpu_time: Option<Duration>,
+		chunk_load_latencies: &[Duration],
+		task_latency: TaskLatencyStats,
+	) {
+		self.samples.push(BenchmarkSample {
+			frame_time,
+			triangle_count,
+			gpu_time,
+			chunk_loads_finished: chunk_load_latencies.len(),
+			max_chunk_load_latency: chunk_load_latencies.iter().max().copied().unwrap_or_default(),
+			task_latency,
+		});
+	}
+
+	pub fn to_csv(&self) -> String {
+		let mut out = String::from(CSV_HEADER);
+		for (i, sample) in self.samples.iter().enumerate() {
+			// left empty if the gpu can't measure it
+			let gpu_time = sample.gpu_time
+				.map(milliseconds)
+				.unwrap_or_default();
+
+			out.push_str(&format!(
+				"{},{},{},{},{},{},{},{}\n",
+				i,
+				milliseconds(sample.frame_time),
+				sample.triangle_count,
+				gpu_time,
+				sample.chunk_loads_finished,
+				milliseconds(sample.max_chunk_load_latency),
+				latency_columns(&sample.task_latency.generate_chunk),
+				latency_columns(&sample.task_latency.chunk_mesh),
+			));
+		}
+		out
+	}
+
+	pub fn write(&self) -> Result<()> {
+		fs::write(&self.output, self.to_csv())?;
+		info!("wrote {} benchmark samples to {}", self.samples.len(), self.output.display());
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn benchmark_csv() {
+		let mut benchmark = Benchmark::new(PathBuf::new());
+		benchmark.add_sample(Duration::from_micros(16_667), 1200, None, &[], TaskLatencyStats::default());
+		let generate_chunk = TaskLatency {
+			count: 12,
+			total: LatencyPercentiles {
+				p50: Duration::from_millis(20),
+				p95: Duration::from_millis(80),
+				max: Duration::from_micros(120_500),
+			},
+			execution: LatencyPercentiles {
+				p50: Duration::from_millis(3),
+				p95: Duration::from_millis(6),
+				max: Duration::from_millis(9),
+			},
+		};
+		benchmark.add_sample(
+			Duration::from_micros(17_000),
+			3400,
+			Some(Duration::from_micros(4_250)),
+			&[Duration::from_millis(250), Duration::from_micros(1_500_250)],
+			TaskLatencyStats {
+				generate_chunk,
+				..TaskLatencyStats::default()
+			},
+		);
+
+		let no_latency = "0.000,0.000,0.000,0.000,0.000,0.000";
+		assert_eq!(benchmark.to_csv(), format!(
+			"{}0,16.667,1200,,0,0.000,{},{}\n1,17.000,3400,4.250,2,1500.250,20.000,80.000,120.500,3.000,6.000,9.000,{}\n",
+			CSV_HEADER,
+			no_latency,
+			no_latency,
+			no_latency,
+		));
+	}
+}
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use glam::{Vec3, Vec4};
+
+use crate::prelude::*;
+use crate::render::particle::Particle;
+use crate::game::block::{BlockType, BlockTrait};
+use crate::game::worldgen::DeterministicRng;
+use super::interaction::InteractionEvent;
+
+// each crack while a block is being broken is a small puff, and breaking it is a burst
+const CRACK_PARTICLES: usize = 4;
+const BURST_PARTICLES: usize = 24;
+// blocks per second that debris is thrown out at
+const CRACK_SPEED: f32 = 1.0;
+const BURST_SPEED: f32 = 3.0;
+// blocks per second squared
+const GRAVITY: f32 = 12.0;
+const PARTICLE_SIZE: f32 = 0.08;
+// debris fades out over this long
+const LIFETIME: Duration = Duration::from_millis(600);
+// only reached if a lot of blocks are broken at once
+const MAX_PARTICLES: usize = 512;
+// debris doesn't have to be deterministic, this just keeps its random numbers from matching a worldgen feature's
+const DEBRIS_SALT: u64 = 0x6465_6272;
+
+#[derive(Debug, Clone, Copy)]
+struct Debris {
+	position: Vec3,
+	velocity: Vec3,
+	color: Vec4,
+	age: Duration,
+}
+
+// bits of blocks thrown out as they crack and break, colored like the block on the map
+#[derive(Debug)]
+pub struct BreakParticles {
+	debris: Vec<Debris>,
+	rng: DeterministicRng,
+}
+
+impl BreakParticles {
+	pub fn new() -> Self {
+		BreakParticles {
+			debris: Vec::new(),
+			rng: DeterministicRng::for_column(0, DEBRIS_SALT, 0, 0),
+		}
+	}
+
+	pub fn handle_event(&mut self, event: InteractionEvent) {
+		match event {
+			InteractionEvent::BreakProgressed { block, block_type, .. } => self.spawn(block, block_type, CRACK_PARTICLES, CRACK_SPEED),
+			InteractionEvent::BreakCompleted { block, block_type } => self.spawn(block, block_type, BURST_PARTICLES, BURST_SPEED),
+			_ => (),
+		}
+	}
+
+	// debris starts just outside the block's surface, and is thrown outwards and a bit up
+	fn spawn(&mut self, block: BlockPos, block_type: BlockType, count: usize, speed: f32) {
+		let [red, green, blue] = block_type.new_block().map_color();
+		let color = Vec4::new(red as f32 / 255.0, green as f32 / 255.0, blue as f32 / 255.0, 1.0);
+		let center = block.as_position().0 + Vec3::splat(0.5);
+
+		for _ in 0..count.min(MAX_PARTICLES - self.debris.len()) {
+			let direction = Vec3::new(self.random_signed(), self.random_signed(), self.random_signed());
+			let extent = direction.abs().max_element();
+			if extent == 0.0 {
+				continue;
+			}
+
+			self.debris.push(Debris {
+				position: center + 0.55 * direction / extent,
+				velocity: speed * (direction.normalize() + Vec3::Y),
+				color,
+				age: Duration::ZERO,
+			});
+		}
+	}
+
+	// from -1 to 1
+	fn random_signed(&mut self) -> f32 {
+		2.0 * self.rng.next_f64() as f32 - 1.0
+	}
+
+	pub fn update(&mut self, delta: Duration) {
+		let seconds = delta.as_secs_f32();
+		self.debris.retain_mut(|debris| {
+			debris.age += delta;
+			debris.velocity.y -= GRAVITY * seconds;
+			debris.position += seconds * debris.velocity;
+			debris.age < LIFETIME
+		});
+	}
+
+	pub fn particles(&self) -> impl Iterator<Item = Particle> + '_ {
+		self.debris.iter().map(|debris| {
+			let fade = 1.0 - debris.age.as_secs_f32() / LIFETIME.as_secs_f32();
+			let color = debris.color * Vec4::new(1.0, 1.0, 1.0, fade);
+			Particle::new(debris.position, PARTICLE_SIZE, PARTICLE_SIZE, color)
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn breaking_bursts_more_than_cracking_and_fades() {
+		let block = BlockPos::new(3, 4, 5);
+		let mut particles = BreakParticles::new();
+
+		particles.handle_event(InteractionEvent::BreakStarted(block));
+		assert_eq!(particles.particles().count(), 0);
+
+		particles.handle_event(InteractionEvent::BreakProgressed {
+			block,
+			block_type: BlockType::Stone,
+			stage: 1,
+		});
+		let cracked = particles.particles().count();
+		assert!(cracked > 0);
+
+		particles.handle_event(InteractionEvent::BreakCompleted {
+			block,
+			block_type: BlockType::Stone,
+		});
+		assert!(particles.particles().count() - cracked > cracked);
+		// every piece starts outside of the block
+		let center = block.as_position().0 + Vec3::splat(0.5);
+		assert!(particles.debris.iter().all(|debris| (debris.position - center).abs().max_element() > 0.5));
+
+		particles.update(LIFETIME / 2);
+		assert!(particles.particles().count() > cracked);
+		particles.update(LIFETIME / 2);
+		assert_eq!(particles.particles().count(), 0);
+	}
+}
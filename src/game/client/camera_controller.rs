@@ -1,10 +1,41 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use winit::event::*;
 //use nalgebra::{Unit, Matrix, Vector4};
-use glam::{Mat4, Vec4, Vec4Swizzles};
+use glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
 
+use crate::prelude::*;
 use crate::render::camera::Camera;
+use crate::game::settings::SprintSettings;
+use super::vertical_motion::{VerticalMotion, VerticalInput, WATER_SPEED_MULTIPLIER};
+
+// maximum time between 2 presses of the forward or jump key for them to count as a double tap
+const DOUBLE_TAP_TIME: Duration = Duration::from_millis(300);
+
+// fraction of the normal speed while sneaking
+const SNEAK_SPEED_MULTIPLIER: f32 = 0.3;
+// how far the camera moves down while sneaking
+const SNEAK_CAMERA_DROP: f32 = 0.15;
+
+// distance from the player's feet to the camera, when not sneaking
+const PLAYER_EYE_HEIGHT: f32 = 1.62;
+// half the width of the player's footprint on the x and z axis
+const PLAYER_HALF_WIDTH: f32 = 0.3;
+const PLAYER_HEIGHT: f32 = 1.8;
+// time taken to smoothly move up a 1 block step
+const STEP_UP_TIME: Duration = Duration::from_millis(100);
+// movement is shortened by this much at a time until the player stays on a block while sneaking
+const SNEAK_CLAMP_STEP: f32 = 0.05;
+
+// analog input from a gamepad, added on top of keyboard input
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnalogInput {
+	// x is right, y is up and z is forward, each in the range -1 to 1
+	pub movement: Vec3,
+	// x is rotating right and y is rotating up, each in the range -1 to 1
+	pub look: Vec2,
+	pub sprint: bool,
+}
 
 #[derive(Debug)]
 pub struct CameraController {
@@ -24,6 +55,27 @@ pub struct CameraController {
 	rotate_left_pressed: bool,
 	rotate_right_pressed: bool,
 	sprint_pressed: bool,
+	sneak_pressed: bool,
+	// the camera's current offset from sneaking, so it can be moved back up when sneaking stops
+	sneak_camera_offset: f32,
+	// the step the player is currently moving up
+	step_up: Option<StepUp>,
+	vertical_motion: VerticalMotion,
+	sprint_settings: SprintSettings,
+	// time forward was last pressed, used to detect double taps
+	last_forward_press: Option<Instant>,
+	// sprinting because of a double tap, lasts until forward is released
+	double_tap_sprinting: bool,
+	// sprint state when toggle sprint is on
+	sprint_toggled: bool,
+	// time jump was last pressed, double tapping jump toggles flying
+	last_up_press: Option<Instant>,
+	flying: bool,
+	// depends on the game mode, flying stops when it isn't allowed
+	fly_allowed: bool,
+	analog_input: AnalogInput,
+	// set while the ui is taking keyboard input, keys and analog input are ignored until it is cleared
+	input_captured: bool,
 }
 
 impl CameraController {
@@ -43,10 +95,136 @@ impl CameraController {
 			rotate_left_pressed: false,
 			rotate_right_pressed: false,
 			sprint_pressed: false,
+			sneak_pressed: false,
+			sneak_camera_offset: 0.0,
+			step_up: None,
+			vertical_motion: VerticalMotion::new(),
+			sprint_settings: SprintSettings::default(),
+			last_forward_press: None,
+			double_tap_sprinting: false,
+			sprint_toggled: false,
+			last_up_press: None,
+			flying: true,
+			fly_allowed: true,
+			analog_input: AnalogInput::default(),
+			input_captured: false,
+		}
+	}
+
+	// releases every key, for when key releases will be missed because the window lost focus
+	// toggled sprint is kept, since it isn't a key being held
+	pub fn reset(&mut self) {
+		self.forward_pressed = false;
+		self.backward_pressed = false;
+		self.left_pressed = false;
+		self.right_pressed = false;
+		self.up_pressed = false;
+		self.down_pressed = false;
+		self.rotate_up_pressed = false;
+		self.rotate_down_pressed = false;
+		self.rotate_left_pressed = false;
+		self.rotate_right_pressed = false;
+		self.sprint_pressed = false;
+		self.sneak_pressed = false;
+		self.last_forward_press = None;
+		self.double_tap_sprinting = false;
+		self.last_up_press = None;
+		self.analog_input = AnalogInput::default();
+	}
+
+	pub fn set_analog_input(&mut self, analog_input: AnalogInput) {
+		if !self.input_captured {
+			self.analog_input = analog_input;
+		}
+	}
+
+	// keys held when the ui takes input are released, since their releases will go to the ui
+	pub fn set_input_captured(&mut self, input_captured: bool) {
+		if input_captured && !self.input_captured {
+			self.reset();
+		}
+		self.input_captured = input_captured;
+	}
+
+	pub fn set_sprint_settings(&mut self, sprint_settings: SprintSettings) {
+		// the release of the old key won't be seen once it is rebound
+		if sprint_settings.sprint_key != self.sprint_settings.sprint_key {
+			self.sprint_pressed = false;
 		}
+		self.sprint_settings = sprint_settings;
+
+		if !sprint_settings.double_tap_sprint {
+			self.double_tap_sprinting = false;
+		}
+		if !sprint_settings.toggle_sprint {
+			self.sprint_toggled = false;
+		}
+	}
+
+	pub fn is_flying(&self) -> bool {
+		self.flying
+	}
+
+	// players who are allowed to fly start flying, so switching to creative doesn't drop them
+	pub fn set_fly_allowed(&mut self, fly_allowed: bool) {
+		if fly_allowed != self.fly_allowed {
+			self.set_flying(fly_allowed);
+		}
+		self.fly_allowed = fly_allowed;
 	}
 
-	pub fn process_event(&mut self, event: &WindowEvent) -> bool {
+	fn set_flying(&mut self, flying: bool) {
+		self.flying = flying;
+		// falling speed shouldn't carry over into flying, or from a previous fall
+		self.vertical_motion = VerticalMotion::new();
+	}
+
+	// see VerticalMotion::take_landing_speed, landings while flying aren't counted
+	pub fn take_landing_speed(&mut self) -> Option<f32> {
+		self.vertical_motion.take_landing_speed()
+	}
+
+	pub fn is_sneaking(&self) -> bool {
+		self.sneak_pressed
+	}
+
+	pub fn is_sprinting(&self) -> bool {
+		let sprint_key = if self.sprint_settings.toggle_sprint {
+			self.sprint_toggled
+		} else {
+			self.sprint_pressed
+		};
+
+		sprint_key || self.double_tap_sprinting || self.analog_input.sprint
+	}
+
+	// combined keyboard and analog movement, in the same space as AnalogInput::movement
+	fn movement_input(&self) -> Vec3 {
+		let keyboard = Vec3::new(
+			key_axis(self.right_pressed, self.left_pressed),
+			key_axis(self.up_pressed, self.down_pressed),
+			key_axis(self.forward_pressed, self.backward_pressed),
+		);
+
+		(keyboard + self.analog_input.movement).clamp(Vec3::splat(-1.0), Vec3::ONE)
+	}
+
+	// combined keyboard and analog rotation, in the same space as AnalogInput::look
+	fn look_input(&self) -> Vec2 {
+		let keyboard = Vec2::new(
+			key_axis(self.rotate_right_pressed, self.rotate_left_pressed),
+			key_axis(self.rotate_up_pressed, self.rotate_down_pressed),
+		);
+
+		(keyboard + self.analog_input.look).clamp(Vec2::splat(-1.0), Vec2::ONE)
+	}
+
+	// now is the time the event occured, used for double tap detection
+	pub fn process_event(&mut self, event: &WindowEvent, now: Instant) -> bool {
+		if self.input_captured {
+			return false;
+		}
+
 		match event {
 			WindowEvent::KeyboardInput {
 				input: KeyboardInput {
@@ -56,60 +234,120 @@ impl CameraController {
 				},
 				..
 			} => {
-				let is_pressed = *state == ElementState::Pressed;
-				match keycode {
-					VirtualKeyCode::W => {
-						self.forward_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::S => {
-						self.backward_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::A => {
-						self.left_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::D => {
-						self.right_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::Space => {
-						self.up_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => {
-						self.down_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::Up => {
-						self.rotate_up_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::Down => {
-						self.rotate_down_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::Left => {
-						self.rotate_left_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::Right => {
-						self.rotate_right_pressed = is_pressed;
-						true
-					},
-					VirtualKeyCode::LShift | VirtualKeyCode::RShift => {
-						self.sprint_pressed = is_pressed;
-						true
-					},
-					_ => false,
-				}
+				self.process_key(*keycode, *state == ElementState::Pressed, now)
 			}
 			_ => false,
 		}
 	}
 
-	pub fn update_camera(&self, camera: &mut Camera, time_delta: Duration) {
+	fn process_key(&mut self, keycode: VirtualKeyCode, is_pressed: bool, now: Instant) -> bool {
+		match keycode {
+			// the sprint key is bound in the settings, so it is checked before any other key
+			key if key == self.sprint_settings.sprint_key => {
+				// ignore key repeats when toggling, and presses while toggling is off so turning it on doesn't start sprinting
+				if self.sprint_settings.toggle_sprint && is_pressed && !self.sprint_pressed {
+					self.sprint_toggled = !self.sprint_toggled;
+				}
+				self.sprint_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::W => {
+				self.process_forward_key(is_pressed, now);
+				true
+			},
+			VirtualKeyCode::S => {
+				self.backward_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::A => {
+				self.left_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::D => {
+				self.right_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::Space => {
+				self.process_up_key(is_pressed, now);
+				true
+			},
+			VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => {
+				self.down_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::Up => {
+				self.rotate_up_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::Down => {
+				self.rotate_down_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::Left => {
+				self.rotate_left_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::Right => {
+				self.rotate_right_pressed = is_pressed;
+				true
+			},
+			VirtualKeyCode::LShift | VirtualKeyCode::RShift => {
+				self.sneak_pressed = is_pressed;
+				true
+			},
+			_ => false,
+		}
+	}
+
+	fn process_up_key(&mut self, is_pressed: bool, now: Instant) {
+		// holding a key sends repeated press events, these are not taps
+		if !is_pressed || self.up_pressed {
+			self.up_pressed = is_pressed;
+			return;
+		}
+		self.up_pressed = true;
+
+		let double_tapped = self.last_up_press
+			.is_some_and(|last_press| now.saturating_duration_since(last_press) <= DOUBLE_TAP_TIME);
+
+		if self.fly_allowed && double_tapped {
+			self.set_flying(!self.flying);
+			// a third tap should not toggle flying again
+			self.last_up_press = None;
+		} else {
+			self.last_up_press = Some(now);
+		}
+	}
+
+	fn process_forward_key(&mut self, is_pressed: bool, now: Instant) {
+		if !is_pressed {
+			self.forward_pressed = false;
+			self.double_tap_sprinting = false;
+			return;
+		}
+
+		// holding a key sends repeated press events, these are not taps
+		if self.forward_pressed {
+			return;
+		}
+		self.forward_pressed = true;
+
+		let double_tapped = self.last_forward_press
+			.is_some_and(|last_press| now.saturating_duration_since(last_press) <= DOUBLE_TAP_TIME);
+
+		if self.sprint_settings.double_tap_sprint && double_tapped {
+			self.double_tap_sprinting = true;
+			// a third tap should not count as another double tap
+			self.last_forward_press = None;
+		} else {
+			self.last_forward_press = Some(now);
+		}
+	}
+
+	// is_solid says if the player can stand on a block, and liquid_height is the height of the liquid in a block, or 0 if it has none
+	// these are used for movement while not flying
+	pub fn update_camera<F, L>(&mut self, camera: &mut Camera, time_delta: Duration, is_solid: F, liquid_height: L)
+		where F: Fn(BlockPos) -> bool, L: Fn(BlockPos) -> f32 {
 		let forward = camera.forward();
 		let up = camera.up;
 		// sideways is pointing right
@@ -122,58 +360,94 @@ impl CameraController {
 		let camera_up_norm = camera_up.normalize();
 
 
-		let distance_moved = time_delta.as_millis() as f32 * 
-			if self.sprint_pressed {
-				self.fast_speed
-			} else {
-				self.speed
-			} / 1000.0;
+		let speed = if self.is_sneaking() {
+			self.speed * SNEAK_SPEED_MULTIPLIER
+		} else if self.is_sprinting() {
+			self.fast_speed
+		} else {
+			self.speed
+		};
+		let distance_moved = time_delta.as_secs_f32() * speed;
 
-		if self.forward_pressed {
-			camera.position += forward_norm * distance_moved;
-		}
-		if self.backward_pressed {
-			camera.position -= forward_norm * distance_moved;
-		}
-		if self.left_pressed {
-			camera.position -= right_norm * distance_moved;
-		}
-		if self.right_pressed {
-			camera.position += right_norm * distance_moved;
-		}
-		if self.up_pressed {
-			camera.position += camera_up_norm * distance_moved;
+		let movement_input = self.movement_input();
+		let mut movement = (right_norm * movement_input.x + camera_up_norm * movement_input.y + forward_norm * movement_input.z) * distance_moved;
+
+		// there is nothing to fall off of, step up on, or swim in while flying
+		if !self.is_flying() {
+			let feet = camera.position - Vec3::Y * (PLAYER_EYE_HEIGHT + self.sneak_camera_offset);
+			let grounded = is_supported(feet, &is_solid);
+			let submersion = submersion(feet, &liquid_height);
+
+			// vertical movement comes from gravity, jumping and swimming instead
+			movement.y = 0.0;
+			if submersion > 0.0 {
+				movement.x *= WATER_SPEED_MULTIPLIER;
+				movement.z *= WATER_SPEED_MULTIPLIER;
+			}
+
+			if self.is_sneaking() {
+				movement = clamp_sneak_movement(feet, movement, &is_solid);
+			}
+
+			if self.step_up.is_none() {
+				self.step_up = step_up_height(feet, movement, grounded, self.is_sneaking(), &is_solid)
+					.map(StepUp::new);
+			}
+
+			let against_ledge = submersion > 0.0 && movement != Vec3::ZERO && collides(feet + movement, &is_solid);
+			// movement into a ledge being stepped up is blocked until the step has raised the feet above it
+			movement = clamp_wall_movement(feet, movement, &is_solid);
+
+			movement.y = self.vertical_motion.update(VerticalInput {
+				// the step carries the player up, so they don't fall while it is raising them
+				grounded: grounded || self.step_up.is_some(),
+				submersion,
+				jump: movement_input.y > 0.0,
+				against_ledge,
+			}, time_delta);
+
+			// falling stops on top of the block landed on instead of partway into it,
+			// which would leave the player inside of the blocks they walk into next
+			if movement.y < 0.0 && !collides(feet, &is_solid) && collides(feet + movement, &is_solid) {
+				movement.y = ((feet.y + movement.y).floor() + 1.0 - feet.y).min(0.0);
+			}
 		}
-		if self.down_pressed {
-			camera.position -= camera_up_norm * distance_moved;
+		camera.position += movement;
+
+		if let Some(step_up) = &mut self.step_up {
+			camera.position.y += step_up.advance(time_delta);
+			if step_up.is_done() {
+				self.step_up = None;
+			}
 		}
 
+		let sneak_camera_offset = if self.is_sneaking() {
+			-SNEAK_CAMERA_DROP
+		} else {
+			0.0
+		};
+		camera.position.y += sneak_camera_offset - self.sneak_camera_offset;
+		self.sneak_camera_offset = sneak_camera_offset;
+
 
-		let angle_rotated = time_delta.as_millis() as f32 * self.rotation_speed / 1000.0;
+		let angle_rotated = time_delta.as_secs_f32() * self.rotation_speed;
 
 		let mut forward4 = Vec4::new(forward.x, forward.y, forward.z, 0.0);
 
-		if self.rotate_up_pressed {
-			let verticle_rotation = Mat4::from_axis_angle(right_norm, angle_rotated);
-			let forward_temp = verticle_rotation * forward4;
-			if forward_temp.xyz().normalize().dot(up) < 0.98 {
-				forward4 = forward_temp;
-			}
-		}
-		if self.rotate_down_pressed {
-			let verticle_rotation = Mat4::from_axis_angle(right_norm, -angle_rotated);
+		let look = self.look_input();
+
+		if look.y != 0.0 {
+			let verticle_rotation = Mat4::from_axis_angle(right_norm, angle_rotated * look.y);
 			let forward_temp = verticle_rotation * forward4;
-			if forward_temp.xyz().normalize().dot(up) > -0.98 {
+			let vertical_dot = forward_temp.xyz().normalize().dot(up);
+			// don't allow looking straight up or down
+			if (look.y > 0.0 && vertical_dot < 0.98) || (look.y < 0.0 && vertical_dot > -0.98) {
 				forward4 = forward_temp;
 			}
 		}
 
-		if self.rotate_left_pressed {
-			let horizantal_rotation = Mat4::from_axis_angle(up, angle_rotated);
-			forward4 = horizantal_rotation * forward4;
-		}
-		if self.rotate_right_pressed {
-			let horizantal_rotation = Mat4::from_axis_angle(up, -angle_rotated);
+		if look.x != 0.0 {
+			let horizantal_rotation = Mat4::from_axis_angle(up, -angle_rotated * look.x);
 			forward4 = horizantal_rotation * forward4;
 		}
 
@@ -183,3 +457,611 @@ impl CameraController {
 		camera.generate_frustum();
 	}
 }
+
+// if any block under the player's footprint is solid
+fn is_supported<F: Fn(BlockPos) -> bool>(feet: Vec3, is_solid: &F) -> bool {
+	// the block just under the feet, so standing exactly on top of a block counts
+	let y = (feet.y - 0.01).floor() as i32;
+	// the footprint is shrunk slightly so standing exactly on a block edge doesn't count the block past it
+	let min = (feet - Vec3::splat(PLAYER_HALF_WIDTH - 0.001)).floor().as_ivec3();
+	let max = (feet + Vec3::splat(PLAYER_HALF_WIDTH - 0.001)).floor().as_ivec3();
+
+	(min.x..=max.x).any(|x| (min.z..=max.z).any(|z| is_solid(BlockPos::new(x, y, z))))
+}
+
+fn step_towards_zero(n: f32) -> f32 {
+	if n.abs() <= SNEAK_CLAMP_STEP {
+		0.0
+	} else {
+		n - SNEAK_CLAMP_STEP * n.signum()
+	}
+}
+
+// shortens horizontal movement so the player's footprint stays over a solid block
+// each axis is clamped on its own first, so the player can still slide along edges
+fn clamp_sneak_movement<F: Fn(BlockPos) -> bool>(feet: Vec3, movement: Vec3, is_solid: F) -> Vec3 {
+	// the player is already falling, so there is no edge to stop at
+	if !is_supported(feet, &is_solid) {
+		return movement;
+	}
+
+	let mut x = movement.x;
+	while x != 0.0 && !is_supported(feet + Vec3::new(x, 0.0, 0.0), &is_solid) {
+		x = step_towards_zero(x);
+	}
+
+	let mut z = movement.z;
+	while z != 0.0 && !is_supported(feet + Vec3::new(0.0, 0.0, z), &is_solid) {
+		z = step_towards_zero(z);
+	}
+
+	// moving on both axis can still go off of a corner
+	while x != 0.0 && z != 0.0 && !is_supported(feet + Vec3::new(x, 0.0, z), &is_solid) {
+		x = step_towards_zero(x);
+		z = step_towards_zero(z);
+	}
+
+	Vec3::new(x, movement.y, z)
+}
+
+// fraction of the player's height which is in liquid, only the column of blocks at the center of the player is checked
+fn submersion<L: Fn(BlockPos) -> f32>(feet: Vec3, liquid_height: &L) -> f32 {
+	let x = feet.x.floor() as i32;
+	let z = feet.z.floor() as i32;
+	let head = feet.y + PLAYER_HEIGHT;
+
+	let submerged: f32 = ((feet.y.floor() as i32)..=(head.floor() as i32))
+		.map(|y| {
+			let liquid_top = y as f32 + liquid_height(BlockPos::new(x, y, z));
+			// the part of this block's liquid which overlaps the player
+			(liquid_top.min(head) - (y as f32).max(feet.y)).max(0.0)
+		})
+		.sum();
+
+	submerged / PLAYER_HEIGHT
+}
+
+// if the player's box would overlap any solid block with its feet at this position
+fn collides<F: Fn(BlockPos) -> bool>(feet: Vec3, is_solid: &F) -> bool {
+	let min = (feet - Vec3::new(PLAYER_HALF_WIDTH, 0.0, PLAYER_HALF_WIDTH) + Vec3::splat(0.001)).floor().as_ivec3();
+	let max = (feet + Vec3::new(PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_HALF_WIDTH) - Vec3::splat(0.001)).floor().as_ivec3();
+
+	(min.x..=max.x).any(|x| {
+		(min.y..=max.y).any(|y| {
+			(min.z..=max.z).any(|z| is_solid(BlockPos::new(x, y, z)))
+		})
+	})
+}
+
+// shortens horizontal movement so the player's box stops against solid blocks instead of moving into them
+// each axis is clamped on its own, so walking into a wall at an angle slides along it
+fn clamp_wall_movement<F: Fn(BlockPos) -> bool>(feet: Vec3, movement: Vec3, is_solid: &F) -> Vec3 {
+	// a player already inside of a block, like one placed where they are standing, can still walk out of it
+	if collides(feet, is_solid) {
+		return movement;
+	}
+
+	let x = clamp_axis_movement(feet, Vec3::X, movement.x, is_solid);
+	let z = clamp_axis_movement(feet + Vec3::new(x, 0.0, 0.0), Vec3::Z, movement.z, is_solid);
+	Vec3::new(x, movement.y, z)
+}
+
+// how far the player can move along the axis, stopping at the edge of the first block in the way
+fn clamp_axis_movement<F: Fn(BlockPos) -> bool>(feet: Vec3, axis: Vec3, distance: f32, is_solid: &F) -> f32 {
+	if distance == 0.0 || !collides(feet + axis * distance, is_solid) {
+		return distance;
+	}
+
+	// the side of the player's box facing the movement, and the edge of the block it runs into
+	let side = feet.dot(axis) + PLAYER_HALF_WIDTH * distance.signum();
+	let block_edge = if distance > 0.0 {
+		(side + distance).floor()
+	} else {
+		(side + distance).floor() + 1.0
+	};
+	let allowed = block_edge - side;
+
+	// movement longer than a block can hit another block before that edge
+	if collides(feet + axis * allowed, is_solid) {
+		0.0
+	} else {
+		allowed
+	}
+}
+
+// if the horizontal movement runs into a ledge the player can walk up, returns the height of the ledge above the feet
+// the ledge must be at most 1 block high, and the player has to fit above it and on the way up to it
+fn step_up_height<F: Fn(BlockPos) -> bool>(feet: Vec3, movement: Vec3, grounded: bool, sneaking: bool, is_solid: &F) -> Option<f32> {
+	// sneaking players should not be moved onto ledges they are trying to stay away from
+	if sneaking || !grounded {
+		return None;
+	}
+
+	let horizontal = Vec3::new(movement.x, 0.0, movement.z);
+	if horizontal == Vec3::ZERO || !collides(feet + horizontal, is_solid) {
+		return None;
+	}
+
+	// the top of the block the feet are in
+	let height = (feet.y + 0.001).floor() + 1.0 - feet.y;
+	let raised_feet = feet + Vec3::Y * height;
+
+	// a ceiling above the player, or a wall more than 1 block high
+	if collides(raised_feet, is_solid) || collides(raised_feet + horizontal, is_solid) {
+		return None;
+	}
+
+	Some(height)
+}
+
+// moves the player up a step over STEP_UP_TIME, so stepping up isn't a sudden jump
+#[derive(Debug, Clone, Copy)]
+struct StepUp {
+	height: f32,
+	elapsed: Duration,
+}
+
+impl StepUp {
+	fn new(height: f32) -> Self {
+		StepUp {
+			height,
+			elapsed: Duration::ZERO,
+		}
+	}
+
+	// returns how far to move up this frame
+	fn advance(&mut self, time_delta: Duration) -> f32 {
+		let old_progress = self.progress();
+		self.elapsed = (self.elapsed + time_delta).min(STEP_UP_TIME);
+		(self.progress() - old_progress) * self.height
+	}
+
+	fn progress(&self) -> f32 {
+		self.elapsed.as_secs_f32() / STEP_UP_TIME.as_secs_f32()
+	}
+
+	fn is_done(&self) -> bool {
+		self.elapsed >= STEP_UP_TIME
+	}
+}
+
+// 1 if only positive is pressed, -1 if only negative is pressed, and 0 otherwise
+fn key_axis(positive: bool, negative: bool) -> f32 {
+	positive as i32 as f32 - negative as i32 as f32
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn controller() -> CameraController {
+		let mut controller = CameraController::new(7.0, 20.0, 2.0);
+		controller.set_sprint_settings(SprintSettings {
+			double_tap_sprint: true,
+			..Default::default()
+		});
+		controller
+	}
+
+	fn ms(millis: u64) -> Duration {
+		Duration::from_millis(millis)
+	}
+
+	#[test]
+	fn double_tap_starts_sprint() {
+		let start = Instant::now();
+		let mut controller = controller();
+
+		controller.process_key(VirtualKeyCode::W, true, start);
+		controller.process_key(VirtualKeyCode::W, false, start + ms(100));
+		assert!(!controller.is_sprinting());
+		controller.process_key(VirtualKeyCode::W, true, start + ms(250));
+		assert!(controller.is_sprinting());
+	}
+
+	#[test]
+	fn double_tap_too_slow() {
+		let start = Instant::now();
+		let mut controller = controller();
+
+		controller.process_key(VirtualKeyCode::W, true, start);
+		controller.process_key(VirtualKeyCode::W, false, start + ms(100));
+		controller.process_key(VirtualKeyCode::W, true, start + ms(301));
+		assert!(!controller.is_sprinting());
+
+		// the slow tap can still be the start of a new double tap
+		controller.process_key(VirtualKeyCode::W, false, start + ms(350));
+		controller.process_key(VirtualKeyCode::W, true, start + ms(450));
+		assert!(controller.is_sprinting());
+	}
+
+	#[test]
+	fn forward_held_across_window() {
+		let start = Instant::now();
+		let mut controller = controller();
+
+		controller.process_key(VirtualKeyCode::W, true, start);
+		// key repeat events while held
+		controller.process_key(VirtualKeyCode::W, true, start + ms(150));
+		controller.process_key(VirtualKeyCode::W, true, start + ms(280));
+		assert!(!controller.is_sprinting());
+
+		controller.process_key(VirtualKeyCode::W, false, start + ms(400));
+		controller.process_key(VirtualKeyCode::W, true, start + ms(450));
+		assert!(!controller.is_sprinting());
+	}
+
+	#[test]
+	fn releasing_forward_cancels_sprint() {
+		let start = Instant::now();
+		let mut controller = controller();
+
+		controller.process_key(VirtualKeyCode::W, true, start);
+		controller.process_key(VirtualKeyCode::W, false, start + ms(50));
+		controller.process_key(VirtualKeyCode::W, true, start + ms(100));
+		assert!(controller.is_sprinting());
+
+		controller.process_key(VirtualKeyCode::W, false, start + ms(2000));
+		assert!(!controller.is_sprinting());
+
+		// pressing again shortly after is not a double tap, since the previous press started the sprint
+		controller.process_key(VirtualKeyCode::W, true, start + ms(2100));
+		assert!(!controller.is_sprinting());
+	}
+
+	#[test]
+	fn double_tap_disabled() {
+		let start = Instant::now();
+		let mut controller = CameraController::new(7.0, 20.0, 2.0);
+
+		controller.process_key(VirtualKeyCode::W, true, start);
+		controller.process_key(VirtualKeyCode::W, false, start + ms(50));
+		controller.process_key(VirtualKeyCode::W, true, start + ms(100));
+		assert!(!controller.is_sprinting());
+	}
+
+	#[test]
+	fn toggle_sprint() {
+		let start = Instant::now();
+		let mut controller = CameraController::new(7.0, 20.0, 2.0);
+		controller.set_sprint_settings(SprintSettings {
+			toggle_sprint: true,
+			..Default::default()
+		});
+
+		controller.process_key(VirtualKeyCode::LControl, true, start);
+		controller.process_key(VirtualKeyCode::LControl, true, start + ms(500));
+		controller.process_key(VirtualKeyCode::LControl, false, start + ms(600));
+		assert!(controller.is_sprinting());
+
+		controller.process_key(VirtualKeyCode::LControl, true, start + ms(1000));
+		assert!(!controller.is_sprinting());
+	}
+
+	#[test]
+	fn enabling_toggle_sprint_starts_untoggled() {
+		let start = Instant::now();
+		let mut controller = CameraController::new(7.0, 20.0, 2.0);
+
+		// an odd number of presses while holding to sprint
+		controller.process_key(VirtualKeyCode::LControl, true, start);
+		controller.process_key(VirtualKeyCode::LControl, false, start + ms(100));
+		assert!(!controller.is_sprinting());
+
+		controller.set_sprint_settings(SprintSettings {
+			toggle_sprint: true,
+			..Default::default()
+		});
+		assert!(!controller.is_sprinting());
+		controller.process_key(VirtualKeyCode::LControl, true, start + ms(500));
+		assert!(controller.is_sprinting());
+	}
+
+	#[test]
+	fn sprint_key_can_be_rebound() {
+		let start = Instant::now();
+		let mut controller = CameraController::new(7.0, 20.0, 2.0);
+		controller.set_sprint_settings(SprintSettings {
+			sprint_key: VirtualKeyCode::Tab,
+			..Default::default()
+		});
+
+		controller.process_key(VirtualKeyCode::LControl, true, start);
+		assert!(!controller.is_sprinting());
+		controller.process_key(VirtualKeyCode::Tab, true, start);
+		assert!(controller.is_sprinting());
+
+		// rebinding while the old key is held stops sprinting
+		controller.set_sprint_settings(SprintSettings::default());
+		assert!(!controller.is_sprinting());
+	}
+
+	#[test]
+	fn reset_releases_keys() {
+		let start = Instant::now();
+		let mut controller = controller();
+		let position = Vec3::new(0.5, 80.0, 0.5);
+		let mut camera = Camera::new(position, position + Vec3::X, 16.0 / 9.0);
+
+		controller.process_key(VirtualKeyCode::W, true, start);
+		controller.process_key(VirtualKeyCode::Left, true, start);
+		controller.reset();
+		controller.update_camera(&mut camera, ms(100), |_| false, |_| 0.0);
+
+		assert_eq!(camera.position, position);
+		assert_eq!(camera.forward(), Vec3::X);
+	}
+
+	#[test]
+	fn double_tap_jump_toggles_flying() {
+		let start = Instant::now();
+		let mut controller = controller();
+		assert!(controller.is_flying());
+
+		controller.process_key(VirtualKeyCode::Space, true, start);
+		controller.process_key(VirtualKeyCode::Space, false, start + ms(100));
+		controller.process_key(VirtualKeyCode::Space, true, start + ms(200));
+		assert!(!controller.is_flying());
+
+		// key repeats while holding jump aren't taps
+		controller.process_key(VirtualKeyCode::Space, true, start + ms(250));
+		assert!(!controller.is_flying());
+		controller.process_key(VirtualKeyCode::Space, false, start + ms(300));
+		// the tap that toggled flying doesn't count as the first tap of the next double tap
+		controller.process_key(VirtualKeyCode::Space, true, start + ms(400));
+		controller.process_key(VirtualKeyCode::Space, false, start + ms(450));
+		assert!(!controller.is_flying());
+		controller.process_key(VirtualKeyCode::Space, true, start + ms(500));
+		assert!(controller.is_flying());
+	}
+
+	#[test]
+	fn flying_needs_to_be_allowed() {
+		let start = Instant::now();
+		let mut controller = controller();
+
+		controller.set_fly_allowed(false);
+		assert!(!controller.is_flying());
+		controller.process_key(VirtualKeyCode::Space, true, start);
+		controller.process_key(VirtualKeyCode::Space, false, start + ms(100));
+		controller.process_key(VirtualKeyCode::Space, true, start + ms(200));
+		assert!(!controller.is_flying());
+
+		// without flying, the player falls
+		let position = Vec3::new(0.5, 80.0, 0.5);
+		let mut camera = Camera::new(position, position + Vec3::X, 16.0 / 9.0);
+		controller.process_key(VirtualKeyCode::Space, false, start + ms(300));
+		controller.update_camera(&mut camera, ms(100), |_| false, |_| 0.0);
+		assert!(camera.position.y < position.y);
+
+		controller.set_fly_allowed(true);
+		assert!(controller.is_flying());
+	}
+
+	#[test]
+	fn captured_input_stops_walking() {
+		let start = Instant::now();
+		let mut controller = controller();
+		#[allow(deprecated)]
+		let press = |keycode| WindowEvent::KeyboardInput {
+			device_id: unsafe { DeviceId::dummy() },
+			input: KeyboardInput {
+				scancode: 0,
+				state: ElementState::Pressed,
+				virtual_keycode: Some(keycode),
+				modifiers: Default::default(),
+			},
+			is_synthetic: false,
+		};
+		let mut camera = Camera::new(Vec3::ZERO, Vec3::Z, 1.0);
+
+		// a text field is focused while walking forward
+		assert!(controller.process_event(&press(VirtualKeyCode::W), start));
+		controller.set_input_captured(true);
+		controller.update_camera(&mut camera, ms(100), |_| false, |_| 0.0);
+		assert_eq!(camera.position, Vec3::ZERO);
+
+		// typing in it doesn't move the player, and its release goes to the text field
+		assert!(!controller.process_event(&press(VirtualKeyCode::D), start + ms(50)));
+		controller.set_analog_input(AnalogInput {
+			movement: Vec3::Z,
+			..Default::default()
+		});
+		controller.update_camera(&mut camera, ms(100), |_| false, |_| 0.0);
+		assert_eq!(camera.position, Vec3::ZERO);
+
+		// once the text field loses focus, keys work again
+		controller.set_input_captured(false);
+		assert!(controller.process_event(&press(VirtualKeyCode::W), start + ms(500)));
+		controller.update_camera(&mut camera, ms(100), |_| false, |_| 0.0);
+		assert!(camera.position.z > 0.0);
+	}
+
+	// moves forward for 1 second, split into updates of the given length
+	fn position_after_one_second(update_time: Duration) -> Vec3 {
+		let start = Instant::now();
+		let mut controller = controller();
+		controller.process_key(VirtualKeyCode::W, true, start);
+
+		let mut camera = Camera::new(Vec3::ZERO, Vec3::Z, 1.0);
+		let updates = Duration::from_secs(1).as_nanos() / update_time.as_nanos();
+		for _ in 0..updates {
+			controller.update_camera(&mut camera, update_time, |_| false, |_| 0.0);
+		}
+		camera.position
+	}
+
+	#[test]
+	fn movement_is_frame_rate_independent() {
+		let one_update = position_after_one_second(Duration::from_secs(1));
+		assert!((one_update.length() - 7.0).abs() < 0.001);
+
+		assert!(position_after_one_second(ms(1)).distance(one_update) < 0.001);
+		// updates shorter than a millisecond still move
+		assert!(position_after_one_second(Duration::from_micros(250)).distance(one_update) < 0.001);
+	}
+
+	// standing in the middle of the top of the block at 0, 0, 0
+	const FEET: Vec3 = Vec3::new(0.5, 1.0, 0.5);
+
+	fn floor(blocks: &'static [(i32, i32)]) -> impl Fn(BlockPos) -> bool {
+		|block| block.y == 0 && blocks.contains(&(block.x, block.z))
+	}
+
+	#[test]
+	fn sneak_stops_at_convex_corner() {
+		let is_solid = floor(&[(0, 0)]);
+
+		// walking diagonally off of a single block stops with the footprint just over the corner
+		let movement = clamp_sneak_movement(FEET, Vec3::new(1.0, 0.0, 1.0), &is_solid);
+		let feet = FEET + movement;
+		assert!(is_supported(feet, &is_solid));
+		assert!(feet.x - PLAYER_HALF_WIDTH > 1.0 - SNEAK_CLAMP_STEP - 0.001);
+		assert!(feet.z - PLAYER_HALF_WIDTH > 1.0 - SNEAK_CLAMP_STEP - 0.001);
+
+		// walking along an edge of a row of blocks is not stopped, only movement off of the edge is
+		let is_solid = floor(&[(0, 0), (1, 0), (2, 0)]);
+		let movement = clamp_sneak_movement(FEET, Vec3::new(1.0, 0.0, 1.0), &is_solid);
+		assert_eq!(movement.x, 1.0);
+		assert!(FEET.z + movement.z - PLAYER_HALF_WIDTH < 1.0);
+		assert!(is_supported(FEET + movement, &is_solid));
+	}
+
+	#[test]
+	fn sneak_stops_at_concave_corner() {
+		// an L shape of blocks, with the block at 1, 1 missing
+		let is_solid = floor(&[(0, 0), (1, 0), (0, 1)]);
+
+		// moving along either arm of the L is fine
+		assert_eq!(clamp_sneak_movement(FEET, Vec3::new(1.0, 0.0, 0.0), &is_solid), Vec3::new(1.0, 0.0, 0.0));
+		assert_eq!(clamp_sneak_movement(FEET, Vec3::new(0.0, 0.0, 1.0), &is_solid), Vec3::new(0.0, 0.0, 1.0));
+
+		// moving diagonally into the gap stops while the footprint still overlaps the arms
+		let movement = clamp_sneak_movement(FEET, Vec3::new(1.0, 0.0, 1.0), &is_solid);
+		let feet = FEET + movement;
+		assert!(is_supported(feet, &is_solid));
+		assert!(!is_supported(feet + Vec3::new(SNEAK_CLAMP_STEP, 0.0, SNEAK_CLAMP_STEP), &is_solid));
+		assert!(movement.x > 0.5 && movement.z > 0.5);
+	}
+
+	#[test]
+	fn not_clamped_when_already_falling() {
+		let movement = Vec3::new(1.0, -0.5, 1.0);
+		assert_eq!(clamp_sneak_movement(FEET, movement, floor(&[])), movement);
+	}
+
+	// a row of blocks at y 0 to stand on, with extra blocks on top of it
+	fn terrain(extra_blocks: &'static [(i32, i32, i32)]) -> impl Fn(BlockPos) -> bool {
+		|block| (block.y == 0 && block.z == 0) || extra_blocks.contains(&(block.x, block.y, block.z))
+	}
+
+	// walking in the x direction towards the block at x = 1
+	const WALK: Vec3 = Vec3::new(0.3, 0.0, 0.0);
+
+	#[test]
+	fn steps_up_one_block_ledge() {
+		let is_solid = terrain(&[(1, 1, 0)]);
+		assert_eq!(step_up_height(FEET, WALK, true, false, &is_solid), Some(1.0));
+
+		// nothing in the way
+		assert_eq!(step_up_height(FEET, WALK, true, false, &terrain(&[])), None);
+	}
+
+	#[test]
+	fn no_step_up_on_two_block_wall() {
+		let is_solid = terrain(&[(1, 1, 0), (1, 2, 0)]);
+		assert_eq!(step_up_height(FEET, WALK, true, false, &is_solid), None);
+	}
+
+	#[test]
+	fn no_step_up_while_airborne_or_sneaking() {
+		let is_solid = terrain(&[(1, 1, 0)]);
+		assert_eq!(step_up_height(FEET, WALK, false, false, &is_solid), None);
+		assert_eq!(step_up_height(FEET, WALK, true, true, &is_solid), None);
+	}
+
+	#[test]
+	fn no_step_up_into_gap_too_small() {
+		// a ceiling above the ledge leaves a 1 block gap, the player is 2 blocks tall
+		let is_solid = terrain(&[(1, 1, 0), (1, 3, 0)]);
+		assert_eq!(step_up_height(FEET, WALK, true, false, &is_solid), None);
+
+		// a ceiling right above the player stops them from moving up at all
+		let is_solid = terrain(&[(1, 1, 0), (0, 3, 0)]);
+		assert_eq!(step_up_height(FEET, WALK, true, false, &is_solid), None);
+	}
+
+	// a player standing at FEET facing in the x direction, who isn't flying
+	fn walking_player() -> (CameraController, Camera) {
+		let mut controller = controller();
+		controller.set_fly_allowed(false);
+		let eye = FEET + Vec3::Y * PLAYER_EYE_HEIGHT;
+		(controller, Camera::new(eye, eye + Vec3::X, 1.0))
+	}
+
+	fn feet(camera: &Camera) -> Vec3 {
+		camera.position - Vec3::Y * PLAYER_EYE_HEIGHT
+	}
+
+	#[test]
+	fn walls_stop_walking() {
+		let is_solid = terrain(&[(1, 1, 0), (1, 2, 0)]);
+		let (mut controller, mut camera) = walking_player();
+		controller.process_key(VirtualKeyCode::W, true, Instant::now());
+
+		for _ in 0..30 {
+			controller.update_camera(&mut camera, ms(16), &is_solid, |_| 0.0);
+			assert!(!collides(feet(&camera), &is_solid), "walked into the wall at {}", feet(&camera));
+		}
+		// stopped right against the wall, not short of it
+		assert!((feet(&camera).x + PLAYER_HALF_WIDTH - 1.0).abs() < 0.001);
+
+		// walking into it at an angle slides along it
+		let movement = clamp_wall_movement(FEET, Vec3::new(0.5, 0.0, 0.2), &is_solid);
+		assert!((movement.x - 0.2).abs() < 0.001);
+		assert_eq!(movement.z, 0.2);
+	}
+
+	#[test]
+	fn stepping_up_never_moves_into_the_ledge() {
+		let is_solid = terrain(&[(1, 1, 0)]);
+		let (mut controller, mut camera) = walking_player();
+		controller.process_key(VirtualKeyCode::W, true, Instant::now());
+
+		for _ in 0..15 {
+			controller.update_camera(&mut camera, ms(16), &is_solid, |_| 0.0);
+			assert!(!collides(feet(&camera), &is_solid), "moved into the ledge at {}", feet(&camera));
+		}
+		// on top of the ledge, and still walking
+		assert!(feet(&camera).x > 1.0);
+		assert!((feet(&camera).y - 2.0).abs() < 0.001);
+	}
+
+	#[test]
+	fn step_up_is_smooth() {
+		let mut step_up = StepUp::new(1.0);
+
+		let first = step_up.advance(STEP_UP_TIME / 4);
+		assert!((first - 0.25).abs() < 0.001);
+		assert!(!step_up.is_done());
+
+		// a long frame only finishes the step, it doesn't go past it
+		let rest = step_up.advance(STEP_UP_TIME * 2);
+		assert!((first + rest - 1.0).abs() < 0.001);
+		assert!(step_up.is_done());
+	}
+
+	#[test]
+	fn submersion_of_player() {
+		// water up to y = 2, with the top block only half full
+		let liquid_height = |block: BlockPos| match block.y {
+			0 => 1.0,
+			1 => 0.5,
+			_ => 0.0,
+		};
+
+		assert!((submersion(Vec3::new(0.5, 0.0, 0.5), &liquid_height) - 1.5 / PLAYER_HEIGHT).abs() < 0.001);
+		assert!((submersion(Vec3::new(0.5, 1.25, 0.5), &liquid_height) - 0.25 / PLAYER_HEIGHT).abs() < 0.001);
+		assert_eq!(submersion(Vec3::new(0.5, 2.0, 0.5), &liquid_height), 0.0);
+	}
+}
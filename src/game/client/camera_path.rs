@@ -0,0 +1,231 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use glam::Vec3;
+
+use crate::prelude::*;
+use crate::render::camera::Camera;
+
+// where the camera was and which way it was looking during one physics update
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+	pub position: Vec3,
+	pub forward: Vec3,
+}
+
+impl CameraKeyframe {
+	pub fn from_camera(camera: &Camera) -> Self {
+		CameraKeyframe {
+			position: camera.position,
+			forward: camera.forward(),
+		}
+	}
+
+	pub fn apply(&self, camera: &mut Camera) {
+		camera.position = self.position;
+		camera.look_at = self.position + self.forward;
+		camera.generate_frustum();
+	}
+}
+
+// a recorded camera path, with 1 keyframe per physics update
+// stored as text, with the position and forward vector of a keyframe on each line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CameraPath {
+	keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+	pub fn load<T: AsRef<Path>>(file: T) -> Result<Self> {
+		let file = file.as_ref();
+		let text = fs::read_to_string(file)
+			.map_err(|error| anyhow!("could not read camera path {}: {}", file.display(), error))?;
+		Self::parse(&text)
+	}
+
+	pub fn save<T: AsRef<Path>>(&self, file: T) -> Result<()> {
+		Ok(fs::write(file, self.to_text())?)
+	}
+
+	pub fn parse(text: &str) -> Result<Self> {
+		let mut keyframes = Vec::new();
+		for (i, line) in text.lines().enumerate() {
+			if line.trim().is_empty() {
+				continue;
+			}
+
+			let values = line.split_whitespace()
+				.map(|value| value.parse::<f32>())
+				.collect::<Result<Vec<_>, _>>()
+				.map_err(|error| anyhow!("invalid number on line {} of camera path: {}", i + 1, error))?;
+			if values.len() != 6 {
+				bail!("line {} of camera path has {} values, expected 6", i + 1, values.len());
+			}
+
+			keyframes.push(CameraKeyframe {
+				position: Vec3::new(values[0], values[1], values[2]),
+				forward: Vec3::new(values[3], values[4], values[5]),
+			});
+		}
+
+		Ok(CameraPath {
+			keyframes,
+		})
+	}
+
+	pub fn to_text(&self) -> String {
+		self.keyframes.iter()
+			.map(|keyframe| format!(
+				"{} {} {} {} {} {}\n",
+				keyframe.position.x,
+				keyframe.position.y,
+				keyframe.position.z,
+				keyframe.forward.x,
+				keyframe.forward.y,
+				keyframe.forward.z,
+			))
+			.collect()
+	}
+
+	pub fn push(&mut self, keyframe: CameraKeyframe) {
+		self.keyframes.push(keyframe);
+	}
+
+	#[allow(clippy::len_without_is_empty)]
+	pub fn len(&self) -> usize {
+		self.keyframes.len()
+	}
+}
+
+#[derive(Debug)]
+enum RecorderState {
+	Idle,
+	Recording(CameraPath),
+	Playing {
+		path: CameraPath,
+		next_keyframe: usize,
+	},
+}
+
+// records the camera's path to a file, and plays it back by driving the camera directly
+#[derive(Debug)]
+pub struct CameraPathRecorder {
+	state: RecorderState,
+	file: PathBuf,
+}
+
+impl CameraPathRecorder {
+	// paths are recorded to and played back from the file
+	pub fn new(file: PathBuf) -> Self {
+		CameraPathRecorder {
+			state: RecorderState::Idle,
+			file,
+		}
+	}
+
+	// starts recording, or stops recording and saves the path
+	// stops playback if it is playing
+	pub fn toggle_recording(&mut self) -> Result<()> {
+		match std::mem::replace(&mut self.state, RecorderState::Idle) {
+			RecorderState::Recording(path) => {
+				path.save(&self.file)?;
+				info!("saved camera path with {} keyframes to {}", path.len(), self.file.display());
+			},
+			_ => self.state = RecorderState::Recording(CameraPath::default()),
+		}
+		Ok(())
+	}
+
+	// plays the saved path, or stops playing it
+	// stops recording without saving if it is recording
+	pub fn toggle_playback(&mut self) -> Result<()> {
+		match std::mem::replace(&mut self.state, RecorderState::Idle) {
+			RecorderState::Playing { .. } => (),
+			_ => self.play(CameraPath::load(&self.file)?),
+		}
+		Ok(())
+	}
+
+	pub fn play(&mut self, path: CameraPath) {
+		self.state = RecorderState::Playing {
+			path,
+			next_keyframe: 0,
+		};
+	}
+
+	// adds the camera's current position to the path if recording
+	pub fn record(&mut self, camera: &Camera) {
+		if let RecorderState::Recording(path) = &mut self.state {
+			path.push(CameraKeyframe::from_camera(camera));
+		}
+	}
+
+	// the keyframe for this physics update if playing, playback stops after the last keyframe
+	pub fn next_keyframe(&mut self) -> Option<CameraKeyframe> {
+		if let RecorderState::Playing { path, next_keyframe } = &mut self.state {
+			match path.keyframes.get(*next_keyframe) {
+				Some(keyframe) => {
+					*next_keyframe += 1;
+					return Some(*keyframe);
+				},
+				None => self.state = RecorderState::Idle,
+			}
+		}
+		None
+	}
+
+	pub fn is_playing(&self) -> bool {
+		matches!(self.state, RecorderState::Playing { .. })
+	}
+
+	// shown in the debug window
+	pub fn status(&self) -> String {
+		match &self.state {
+			RecorderState::Idle => String::from("idle"),
+			RecorderState::Recording(path) => format!("recording, {} keyframes", path.len()),
+			RecorderState::Playing { path, next_keyframe } => format!("playing, keyframe {} of {}", next_keyframe, path.len()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn keyframe(x: f32) -> CameraKeyframe {
+		CameraKeyframe {
+			position: Vec3::new(x, 64.5, -3.25),
+			forward: Vec3::new(0.0, -0.5, 1.0),
+		}
+	}
+
+	#[test]
+	fn camera_path_text_round_trip() {
+		let mut path = CameraPath::default();
+		for i in 0..3 {
+			path.push(keyframe(i as f32 * 0.1));
+		}
+
+		assert_eq!(CameraPath::parse(&path.to_text()).unwrap(), path);
+		assert!(CameraPath::parse("1 2 3 4 5\n").is_err());
+		assert!(CameraPath::parse("1 2 3 4 5 six\n").is_err());
+	}
+
+	#[test]
+	fn playback_returns_each_keyframe_once() {
+		let mut path = CameraPath::default();
+		path.push(keyframe(1.0));
+		path.push(keyframe(2.0));
+
+		let mut recorder = CameraPathRecorder::new(PathBuf::new());
+		assert_eq!(recorder.next_keyframe(), None);
+
+		recorder.play(path);
+		assert!(recorder.is_playing());
+		assert_eq!(recorder.next_keyframe(), Some(keyframe(1.0)));
+		assert_eq!(recorder.next_keyframe(), Some(keyframe(2.0)));
+		assert_eq!(recorder.next_keyframe(), None);
+		assert!(!recorder.is_playing());
+	}
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use glam::{Quat, Vec3};
+
+use crate::render::camera::Camera;
+
+// where the camera should be, set by whatever is moving the player
+// the camera is smoothed towards it every frame, so positions which only change now and then don't look jumpy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraTarget {
+	pub position: Vec3,
+	pub forward: Vec3,
+	// the camera jumps straight to the target on the next frame, instead of being smoothed
+	snap: bool,
+}
+
+impl CameraTarget {
+	// the camera is already at the target, so nothing is smoothed until it is moved
+	pub fn from_camera(camera: &Camera) -> Self {
+		CameraTarget {
+			position: camera.position,
+			forward: camera.forward(),
+			snap: false,
+		}
+	}
+
+	// the camera is smoothed towards the new target
+	pub fn set(&mut self, position: Vec3, forward: Vec3) {
+		self.position = position;
+		self.forward = forward;
+	}
+
+	// for teleports, the camera jumps to the new target on the next frame
+	pub fn snap(&mut self, position: Vec3, forward: Vec3) {
+		self.set(position, forward);
+		self.snap = true;
+	}
+
+	// moves the camera towards the target, the distance left shrinks by a factor of e every 1 / rate seconds
+	// this is the same no matter how the delta is split up, so the framerate doesn't change how it looks
+	pub fn apply(&mut self, camera: &mut Camera, delta: Duration, rate: f32) {
+		let forward = camera.forward();
+		if camera.position == self.position && forward == self.forward {
+			self.snap = false;
+			return;
+		}
+
+		let (position, forward) = if self.snap {
+			self.snap = false;
+			(self.position, self.forward)
+		} else {
+			let t = 1.0 - (-rate * delta.as_secs_f32()).exp();
+			(camera.position.lerp(self.position, t), turn_towards(forward, self.forward, t))
+		};
+
+		camera.position = position;
+		camera.look_at = position + forward;
+		camera.generate_frustum();
+	}
+}
+
+// rotates forward by the fraction t of the angle between it and target, so turning is smoothed like moving is
+fn turn_towards(forward: Vec3, target: Vec3, t: f32) -> Vec3 {
+	let forward = forward.normalize_or_zero();
+	let target = target.normalize_or_zero();
+	match forward.cross(target).try_normalize() {
+		Some(axis) => Quat::from_axis_angle(axis, t * forward.angle_between(target)) * forward,
+		// already facing the target, or facing directly away from it so there is no single axis to turn around
+		None => forward.lerp(target, t).try_normalize().unwrap_or(target),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn smoothing_is_framerate_independent() {
+		let mut one_frame = Camera::new(Vec3::ZERO, Vec3::Z, 1.0);
+		let mut many_frames = Camera::new(Vec3::ZERO, Vec3::Z, 1.0);
+		let mut target = CameraTarget::from_camera(&one_frame);
+		target.set(Vec3::new(10.0, 0.0, 0.0), Vec3::X);
+		let mut many_target = target;
+
+		target.apply(&mut one_frame, Duration::from_millis(100), 10.0);
+		for _ in 0..10 {
+			many_target.apply(&mut many_frames, Duration::from_millis(10), 10.0);
+		}
+
+		// 1 / rate seconds gets within 1 / e of the target
+		assert!((one_frame.position.x - 10.0 * (1.0 - (-1.0f32).exp())).abs() < 0.001);
+		assert!(one_frame.position.distance(many_frames.position) < 0.001);
+		assert!((one_frame.forward() - many_frames.forward()).length() < 0.001);
+	}
+
+	#[test]
+	fn snapping_skips_smoothing() {
+		let mut camera = Camera::new(Vec3::ZERO, Vec3::Z, 1.0);
+		let mut target = CameraTarget::from_camera(&camera);
+
+		target.snap(Vec3::new(0.0, 50.0, 0.0), Vec3::X);
+		target.apply(&mut camera, Duration::from_millis(1), 1.0);
+		assert_eq!(camera.position, Vec3::new(0.0, 50.0, 0.0));
+		assert!((camera.forward() - Vec3::X).length() < 0.0001);
+
+		// only the next frame snaps
+		target.set(Vec3::ZERO, Vec3::X);
+		target.apply(&mut camera, Duration::from_millis(1), 1.0);
+		assert!(camera.position.y > 49.0);
+	}
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use std::f32::consts::TAU;
+
+use glam::{Quat, Vec3, Vec4};
+
+use crate::prelude::*;
+use crate::render::Fog;
+use crate::render::shadow_map::DEFAULT_SUN_DIRECTION;
+use crate::game::world_state::WorldState;
+
+// the environment gets 95% of the way to a new biome's, or to being underwater, in this time
+const TRANSITION_TIME: Duration = Duration::from_secs(1);
+
+// fog ends a bit before the edge of the render distance, so chunks don't visibly appear
+const SURFACE_FOG_START: f32 = 160.0;
+const SURFACE_FOG_END: f32 = 300.0;
+
+const UNDERWATER_FOG: Fog = Fog {
+	color: Vec3::new(0.05, 0.2, 0.45),
+	start: 0.0,
+	end: 24.0,
+};
+// drawn over the whole screen while underwater
+const UNDERWATER_TINT: Vec4 = Vec4::new(0.1, 0.3, 0.8, 0.3);
+
+// full rain fades the fog, and the sky with it, towards a dark grey and brings it closer
+const RAIN_FOG_COLOR: Vec3 = Vec3::new(0.32, 0.34, 0.38);
+const RAIN_FOG_AMOUNT: f32 = 0.75;
+const RAIN_FOG_SCALE: f32 = 0.6;
+
+// water further below the camera than this isn't reflected
+const MAX_REFLECTION_DEPTH: i32 = 48;
+
+// fog and screen tint around the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Environment {
+	pub fog: Fog,
+	// rgba color drawn over the screen, nothing is drawn with 0 alpha
+	pub screen_tint: Vec4,
+}
+
+impl Environment {
+	// fog_color is in the 0 to 255 range used by biomes
+	pub fn surface(fog_color: [u8; 3]) -> Self {
+		Environment {
+			fog: Fog {
+				color: Vec3::from(fog_color.map(|component| component as f32 / 255.0)),
+				start: SURFACE_FOG_START,
+				end: SURFACE_FOG_END,
+			},
+			// same color as underwater so only the alpha changes when surfacing
+			screen_tint: UNDERWATER_TINT.truncate().extend(0.0),
+		}
+	}
+
+	// rain_intensity goes from 0 when clear to 1 at full rain
+	pub fn with_rain(self, rain_intensity: f32) -> Self {
+		let scale = 1.0 + (RAIN_FOG_SCALE - 1.0) * rain_intensity;
+		Environment {
+			fog: Fog {
+				color: self.fog.color.lerp(RAIN_FOG_COLOR, RAIN_FOG_AMOUNT * rain_intensity),
+				start: self.fog.start * scale,
+				end: self.fog.end * scale,
+			},
+			..self
+		}
+	}
+
+	pub fn underwater() -> Self {
+		Environment {
+			fog: UNDERWATER_FOG,
+			screen_tint: UNDERWATER_TINT,
+		}
+	}
+
+	fn lerp(&self, other: &Self, t: f32) -> Self {
+		Environment {
+			fog: Fog {
+				color: self.fog.color.lerp(other.fog.color, t),
+				start: self.fog.start + (other.fog.start - self.fog.start) * t,
+				end: self.fog.end + (other.fog.end - self.fog.end) * t,
+			},
+			screen_tint: self.screen_tint.lerp(other.screen_tint, t),
+		}
+	}
+
+	// moves towards the target environment, so crossing biome borders or surfacing doesn't pop
+	pub fn transition_to(&mut self, target: Environment, delta: Duration) {
+		// exponential so it doesn't depend on how often this is called, e^-3 is about 5% remaining
+		let t = 1.0 - (-3.0 * delta.as_secs_f32() / TRANSITION_TIME.as_secs_f32()).exp();
+		*self = self.lerp(&target, t);
+	}
+}
+
+// turns the sky a full circle each day, around the horizontal axis which is perpendicular to the default sun direction,
+// so the sun is highest at the start of the day and below the horizon at night
+pub fn sky_rotation(state: &WorldState) -> Quat {
+	let axis = DEFAULT_SUN_DIRECTION.cross(Vec3::Y).normalize();
+	Quat::from_axis_angle(axis, TAU * state.day_fraction())
+}
+
+// height of the surface of the water under the camera, which water reflections are mirrored about
+// none if there is ground first, or the water is too far down
+pub fn water_surface_below(
+	camera_position: Position,
+	is_solid_at: impl Fn(BlockPos) -> bool,
+	liquid_height_at: impl Fn(BlockPos) -> f32,
+) -> Option<f32> {
+	let camera_block = camera_position.as_block_pos();
+	for depth in 0..MAX_REFLECTION_DEPTH {
+		let block = BlockPos::new(camera_block.x, camera_block.y - depth, camera_block.z);
+		let liquid_height = liquid_height_at(block);
+		if liquid_height > 0.0 {
+			return Some(block.y as f32 + liquid_height);
+		}
+		if is_solid_at(block) {
+			return None;
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::world_state::NIGHT_TIME;
+
+	#[test]
+	fn transition_is_gradual() {
+		let mut environment = Environment::surface([170, 205, 235]);
+		let target = Environment::underwater();
+		let frame_time = Duration::from_micros(16_667);
+
+		environment.transition_to(target, frame_time);
+		// a single frame should only move a small part of the way
+		assert!(environment.screen_tint.w < 0.1 * UNDERWATER_TINT.w);
+
+		for _ in 0..60 {
+			environment.transition_to(target, frame_time);
+		}
+		// after about 1 second it is almost completely underwater
+		assert!(environment.screen_tint.w > 0.9 * UNDERWATER_TINT.w);
+		assert!(environment.fog.end < 0.1 * SURFACE_FOG_END + UNDERWATER_FOG.end);
+	}
+
+	#[test]
+	fn sun_sets_at_night() {
+		let mut state = WorldState::default();
+		let sun = |state: &WorldState| sky_rotation(state) * DEFAULT_SUN_DIRECTION.normalize();
+		assert!(sun(&state).abs_diff_eq(DEFAULT_SUN_DIRECTION.normalize(), 0.0001));
+
+		state.set_time_of_day(NIGHT_TIME);
+		assert!(sun(&state).y < -0.5);
+	}
+
+	#[test]
+	fn water_surface_is_found_below_the_camera() {
+		// a pool with its surface 0.9 up the block at y 10, on ground at y 5
+		let is_solid_at = |block: BlockPos| block.y <= 5;
+		let liquid_height_at = |block: BlockPos| if block.y > 5 && block.y <= 10 { 0.9 } else { 0.0 };
+
+		let surface = water_surface_below(Position::new(0.5, 20.5, 0.5), is_solid_at, liquid_height_at);
+		assert_eq!(surface, Some(10.9));
+
+		// no water over dry ground
+		assert_eq!(water_surface_below(Position::new(0.5, 20.5, 0.5), is_solid_at, |_| 0.0), None);
+		// water too far down isn't reflected
+		let deep = Position::new(0.5, 10.0 + MAX_REFLECTION_DEPTH as f32 + 1.0, 0.5);
+		assert_eq!(water_surface_below(deep, is_solid_at, liquid_height_at), None);
+	}
+}
@@ -0,0 +1,131 @@
+use gilrs::{Gilrs, Gamepad, EventType, Button, Axis};
+use glam::{Vec2, Vec3};
+
+use super::camera_controller::AnalogInput;
+
+// stick values with a magnitude below this are ignored
+const STICK_DEADZONE: f32 = 0.15;
+// look stick magnitude is raised to this power, so small movements allow fine aiming
+const LOOK_RESPONSE_EXPONENT: f32 = 2.0;
+
+const JUMP_BUTTON: Button = Button::South;
+const DESCEND_BUTTON: Button = Button::East;
+const SPRINT_BUTTON: Button = Button::LeftThumb;
+const BREAK_BUTTON: Button = Button::RightTrigger2;
+const PLACE_BUTTON: Button = Button::LeftTrigger2;
+
+// actions triggered by gamepad buttons since the last poll
+#[derive(Debug, Default)]
+pub struct GamepadActions {
+	pub break_block: bool,
+	pub place_block: bool,
+}
+
+// reads input from all connected gamepads
+// gamepads can be connected or disconnected at any time
+pub struct GamepadInput {
+	// none if gamepad support could not be initialized on this platform
+	gilrs: Option<Gilrs>,
+}
+
+impl GamepadInput {
+	pub fn new() -> Self {
+		let gilrs = match Gilrs::new() {
+			Ok(gilrs) => {
+				for (_, gamepad) in gilrs.gamepads() {
+					info!("gamepad connected: {}", gamepad.name());
+				}
+				Some(gilrs)
+			},
+			Err(err) => {
+				warn!("gamepad support unavailable: {}", err);
+				None
+			},
+		};
+
+		GamepadInput {
+			gilrs,
+		}
+	}
+
+	// processes all pending gamepad events, and returns the analog input from the current gamepad state
+	pub fn poll(&mut self) -> (AnalogInput, GamepadActions) {
+		let mut actions = GamepadActions::default();
+
+		let gilrs = match &mut self.gilrs {
+			Some(gilrs) => gilrs,
+			None => return (AnalogInput::default(), actions),
+		};
+
+		while let Some(event) = gilrs.next_event() {
+			match event.event {
+				EventType::Connected => info!("gamepad connected: {}", gilrs.gamepad(event.id).name()),
+				EventType::Disconnected => info!("gamepad disconnected: {}", gilrs.gamepad(event.id).name()),
+				EventType::ButtonPressed(BREAK_BUTTON, _) => actions.break_block = true,
+				EventType::ButtonPressed(PLACE_BUTTON, _) => actions.place_block = true,
+				_ => (),
+			}
+		}
+
+		let mut analog_input = AnalogInput::default();
+		for (_, gamepad) in gilrs.gamepads() {
+			let input = gamepad_analog_input(&gamepad);
+			analog_input.movement += input.movement;
+			analog_input.look += input.look;
+			analog_input.sprint |= input.sprint;
+		}
+
+		(analog_input, actions)
+	}
+}
+
+fn gamepad_analog_input(gamepad: &Gamepad) -> AnalogInput {
+	let stick = |x_axis, y_axis| apply_deadzone(Vec2::new(gamepad.value(x_axis), gamepad.value(y_axis)));
+	let button = |button| gamepad.is_pressed(button) as i32 as f32;
+
+	let movement_stick = stick(Axis::LeftStickX, Axis::LeftStickY);
+	let look_stick = response_curve(stick(Axis::RightStickX, Axis::RightStickY));
+
+	AnalogInput {
+		movement: Vec3::new(movement_stick.x, button(JUMP_BUTTON) - button(DESCEND_BUTTON), movement_stick.y),
+		look: look_stick,
+		sprint: gamepad.is_pressed(SPRINT_BUTTON),
+	}
+}
+
+// ignores small stick movements, and rescales the rest so the output still covers the range 0 to 1
+fn apply_deadzone(stick: Vec2) -> Vec2 {
+	let magnitude = stick.length();
+	if magnitude < STICK_DEADZONE {
+		Vec2::ZERO
+	} else {
+		let scaled_magnitude = ((magnitude - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0);
+		stick / magnitude * scaled_magnitude
+	}
+}
+
+fn response_curve(stick: Vec2) -> Vec2 {
+	stick.normalize_or_zero() * stick.length().powf(LOOK_RESPONSE_EXPONENT)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn deadzone() {
+		assert_eq!(apply_deadzone(Vec2::new(0.1, -0.05)), Vec2::ZERO);
+		assert!((apply_deadzone(Vec2::new(1.0, 0.0)) - Vec2::new(1.0, 0.0)).length() < 0.0001);
+
+		// just outside the deadzone should be close to 0, not jump to the deadzone value
+		let small = apply_deadzone(Vec2::new(0.0, STICK_DEADZONE + 0.01));
+		assert!(small.y > 0.0 && small.y < 0.05);
+	}
+
+	#[test]
+	fn look_response_curve() {
+		assert_eq!(response_curve(Vec2::ZERO), Vec2::ZERO);
+		assert!((response_curve(Vec2::new(0.5, 0.0)) - Vec2::new(0.25, 0.0)).length() < 0.0001);
+		assert!((response_curve(Vec2::new(0.0, -1.0)) - Vec2::new(0.0, -1.0)).length() < 0.0001);
+	}
+}
@@ -0,0 +1,209 @@
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use glam::{Mat3, Mat4, Quat, Vec3};
+
+use crate::prelude::*;
+use crate::render::camera::Camera;
+use crate::game::block::{BlockFace, BlockFaceMesh, BlockType, BlockVertex, OcclusionCorners};
+
+// where the held block is relative to the camera, along the camera's right, up, and forward directions
+const HELD_BLOCK_OFFSET: Vec3 = Vec3::new(0.55, -0.45, 0.9);
+const HELD_BLOCK_SIZE: f32 = 0.35;
+// the block is turned so the top and 2 of its sides can be seen
+const HELD_BLOCK_YAW: f32 = 0.6;
+const HELD_BLOCK_PITCH: f32 = 0.25;
+
+const SWING_DURATION: Duration = Duration::from_millis(250);
+// how far the block moves down and towards the center of the view, and tips forward, at the peak of a swing
+const SWING_DROP: f32 = 0.15;
+const SWING_INWARD: f32 = 0.1;
+const SWING_ANGLE: f32 = 0.6;
+
+// the block bobs through a full cycle every BOB_STRIDE blocks walked
+const BOB_STRIDE: f32 = 2.5;
+const BOB_HEIGHT: f32 = 0.03;
+const BOB_SWAY: f32 = 0.02;
+// bobbing fades in and out over this many physics updates when walking starts and stops
+const BOB_FADE_UPDATES: f32 = 8.0;
+// moving further than this in one physics update is a teleport, not walking
+const MAX_WALK_STEP: f32 = 2.0;
+
+const NO_OCCLUSION: OcclusionCorners = OcclusionCorners {
+	tl: 0.0,
+	tr: 0.0,
+	bl: 0.0,
+	br: 0.0,
+};
+
+// the selected block, floating in the lower right of the view
+#[derive(Debug)]
+pub struct HeldBlock {
+	block: Option<BlockType>,
+	// time since the current swing started, none when not swinging
+	swing_time: Option<Duration>,
+	// fraction of the way through a bob cycle
+	bob_phase: f32,
+	// 0 when standing still, up to 1 when walking
+	bob_amount: f32,
+}
+
+impl HeldBlock {
+	pub fn new() -> Self {
+		HeldBlock {
+			block: None,
+			swing_time: None,
+			bob_phase: 0.0,
+			bob_amount: 0.0,
+		}
+	}
+
+	// returns true if the held block changed, so its mesh has to be made again
+	pub fn set_block(&mut self, block: Option<BlockType>) -> bool {
+		let changed = block != self.block;
+		self.block = block;
+		changed
+	}
+
+	// starts the swing over if one is already going
+	pub fn swing(&mut self) {
+		self.swing_time = Some(Duration::ZERO);
+	}
+
+	// called each physics update with the horizontal distance walked on the ground during it
+	pub fn walk(&mut self, distance: f32) {
+		let walking = distance > 0.0 && distance <= MAX_WALK_STEP;
+		if walking {
+			self.bob_phase = (self.bob_phase + distance / BOB_STRIDE).fract();
+		}
+
+		let fade_step = 1.0 / BOB_FADE_UPDATES;
+		self.bob_amount = if walking {
+			(self.bob_amount + fade_step).min(1.0)
+		} else {
+			(self.bob_amount - fade_step).max(0.0)
+		};
+	}
+
+	// called each frame with the time since the last frame
+	pub fn advance(&mut self, delta: Duration) {
+		self.swing_time = self.swing_time
+			.map(|swing_time| swing_time + delta)
+			.filter(|swing_time| *swing_time < SWING_DURATION);
+	}
+
+	// goes from 0 up to 1 half way through a swing, then back down to 0
+	fn swing_amount(&self) -> f32 {
+		self.swing_time.map_or(0.0, |swing_time| (PI * swing_time.as_secs_f32() / SWING_DURATION.as_secs_f32()).sin())
+	}
+
+	// position of the center of the block relative to the camera, along the camera's right, up, and forward directions
+	fn camera_offset(&self) -> Vec3 {
+		let swing = self.swing_amount();
+		let bob_angle = 2.0 * PI * self.bob_phase;
+		let bob = self.bob_amount * Vec3::new(BOB_SWAY * bob_angle.sin(), -BOB_HEIGHT * bob_angle.sin().abs(), 0.0);
+
+		HELD_BLOCK_OFFSET + bob + Vec3::new(-SWING_INWARD * swing, -SWING_DROP * swing, 0.0)
+	}
+
+	// moves the held block's mesh into place in front of the camera, in world space
+	pub fn transform(&self, camera: &Camera) -> Mat4 {
+		let forward = camera.forward().normalize();
+		let right = camera.sideways();
+		let up = right.cross(forward);
+
+		let offset = self.camera_offset();
+		let center = camera.position + offset.x * right + offset.y * up + offset.z * forward;
+
+		// the camera looks down its negative z axis
+		let camera_rotation = Quat::from_mat3(&Mat3::from_cols(right, up, -forward));
+		let pitch = HELD_BLOCK_PITCH - SWING_ANGLE * self.swing_amount();
+		let rotation = camera_rotation * Quat::from_rotation_x(pitch) * Quat::from_rotation_y(HELD_BLOCK_YAW);
+
+		// the mesh goes from 0 to 1, so it is centered before being turned
+		Mat4::from_scale_rotation_translation(Vec3::splat(HELD_BLOCK_SIZE), rotation, center)
+			* Mat4::from_translation(Vec3::splat(-0.5))
+	}
+}
+
+// a cube from 0 to 1 on each axis with the block's textures, none for blocks without a texture
+pub fn held_block_mesh(block: BlockType) -> Option<(Vec<BlockVertex>, Vec<u32>)> {
+	let texture_index = block.new_block().texture_index()?;
+
+	let mut vertices = Vec::new();
+	let mut indices = Vec::new();
+	for face in BlockFace::iter() {
+		let face_mesh = BlockFaceMesh::from_cube_corners(face, texture_index, BlockPos::new(0, 0, 0), BlockPos::new(0, 0, 0), NO_OCCLUSION);
+		let first_index = vertices.len() as u32;
+		vertices.extend(face_mesh.0);
+		indices.extend(BlockFaceMesh::indicies().iter().map(|index| index + first_index));
+	}
+	Some((vertices, indices))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn camera() -> Camera {
+		Camera::new(Vec3::new(3.0, 40.0, -2.0), Vec3::new(3.0, 40.0, 10.0), 1.0)
+	}
+
+	#[test]
+	fn held_block_mesh_is_a_cube() {
+		let (vertices, indices) = held_block_mesh(BlockType::Stone).unwrap();
+		assert_eq!(vertices.len(), 24);
+		assert_eq!(indices.len(), 36);
+		assert!(indices.iter().all(|index| (*index as usize) < vertices.len()));
+
+		assert!(held_block_mesh(BlockType::Air).is_none());
+	}
+
+	#[test]
+	fn held_block_is_in_the_lower_right_of_the_view() {
+		let camera = camera();
+		let held_block = HeldBlock::new();
+		let center = held_block.transform(&camera).transform_point3(Vec3::splat(0.5));
+
+		let relative = center - camera.position;
+		assert!(relative.dot(camera.forward().normalize()) > 0.0);
+		assert!(relative.dot(camera.sideways()) > 0.0);
+		assert!(relative.y < 0.0);
+	}
+
+	#[test]
+	fn swing_returns_to_rest() {
+		let camera = camera();
+		let mut held_block = HeldBlock::new();
+		let rest = held_block.transform(&camera);
+
+		held_block.swing();
+		held_block.advance(SWING_DURATION / 2);
+		assert!(!held_block.transform(&camera).abs_diff_eq(rest, 0.01));
+
+		held_block.advance(SWING_DURATION / 2);
+		assert!(held_block.transform(&camera).abs_diff_eq(rest, 0.0001));
+	}
+
+	#[test]
+	fn only_walking_bobs() {
+		let camera = camera();
+		let mut held_block = HeldBlock::new();
+		let rest = held_block.transform(&camera);
+
+		// a teleport
+		held_block.walk(50.0);
+		assert!(held_block.transform(&camera).abs_diff_eq(rest, 0.0001));
+
+		for _ in 0..10 {
+			held_block.walk(0.1);
+		}
+		assert!(!held_block.transform(&camera).abs_diff_eq(rest, 0.001));
+
+		// settles back down after stopping
+		for _ in 0..BOB_FADE_UPDATES as usize {
+			held_block.walk(0.0);
+		}
+		assert!(held_block.transform(&camera).abs_diff_eq(rest, 0.0001));
+	}
+}
@@ -0,0 +1,614 @@
+use std::mem;
+use std::time::{Duration, Instant};
+
+use glam::Vec3;
+use winit::event::VirtualKeyCode;
+
+use crate::prelude::*;
+use crate::game::world::{World, RaycastHit};
+use crate::game::block::{BlockType, BlockTrait, Air};
+use crate::game::player::GameMode;
+use super::sound::Sound;
+
+pub const HOTBAR_SIZE: usize = 9;
+// while the break button is held, a block is broken this often
+const BREAK_REPEAT_DELAY: Duration = Duration::from_millis(250);
+// a block being broken in survival cracks this many times on the way to breaking, evenly spaced over its break progress
+// so harder blocks crack less often
+pub const BREAK_STAGES: u32 = 4;
+
+// the block types the player can switch between to place, one of the slots is selected
+// in creative the slots are a palette, in survival they also hold a number of blocks, and slots with none left are empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotbar {
+	slots: [Option<BlockType>; HOTBAR_SIZE],
+	// number of blocks in each slot, only used in survival
+	counts: [u32; HOTBAR_SIZE],
+	selected_slot: usize,
+}
+
+impl Hotbar {
+	pub fn new() -> Self {
+		let mut slots = [None; HOTBAR_SIZE];
+		for (slot, block_type) in slots.iter_mut().zip([
+			BlockType::Stone,
+			BlockType::Dirt,
+			BlockType::Grass,
+			BlockType::RockyDirt,
+			BlockType::Water,
+			BlockType::Lava,
+			BlockType::Sand,
+		]) {
+			*slot = Some(block_type);
+		}
+
+		Hotbar {
+			slots,
+			counts: [0; HOTBAR_SIZE],
+			selected_slot: 0,
+		}
+	}
+
+	// a saved hotbar, slots past the end of the hotbar are dropped, and missing slots are empty
+	pub fn from_slots(saved_slots: &[Option<BlockType>], saved_counts: &[u32], selected_slot: usize) -> Self {
+		let mut slots = [None; HOTBAR_SIZE];
+		for (slot, saved_slot) in slots.iter_mut().zip(saved_slots) {
+			*slot = *saved_slot;
+		}
+
+		let mut counts = [0; HOTBAR_SIZE];
+		for (count, saved_count) in counts.iter_mut().zip(saved_counts) {
+			*count = *saved_count;
+		}
+
+		Hotbar {
+			slots,
+			counts,
+			selected_slot: selected_slot.min(HOTBAR_SIZE - 1),
+		}
+	}
+
+	pub fn slots(&self) -> &[Option<BlockType>] {
+		&self.slots
+	}
+
+	pub fn counts(&self) -> &[u32] {
+		&self.counts
+	}
+
+	pub fn selected_count(&self) -> u32 {
+		self.counts[self.selected_slot]
+	}
+
+	pub fn selected_slot(&self) -> usize {
+		self.selected_slot
+	}
+
+	// none if the selected slot is empty
+	pub fn selected_block(&self) -> Option<BlockType> {
+		self.slots[self.selected_slot]
+	}
+
+	// slots past the end of the hotbar are ignored
+	pub fn select_slot(&mut self, slot: usize) {
+		if slot < HOTBAR_SIZE {
+			self.selected_slot = slot;
+		}
+	}
+
+	// selects the slot holding the block type if there is one, otherwise puts it in the selected slot
+	pub fn pick(&mut self, block_type: BlockType) {
+		match self.slots.iter().position(|slot| *slot == Some(block_type)) {
+			Some(slot) => self.selected_slot = slot,
+			None => self.slots[self.selected_slot] = Some(block_type),
+		}
+	}
+
+	// the block type to place, none if there is nothing to place
+	// outside of creative one block is used up from the selected slot
+	pub fn take_selected(&mut self, game_mode: GameMode) -> Option<BlockType> {
+		let block_type = self.selected_block()?;
+		if game_mode.infinite_blocks() {
+			return Some(block_type);
+		}
+
+		let count = &mut self.counts[self.selected_slot];
+		if *count == 0 {
+			return None;
+		}
+		*count -= 1;
+		Some(block_type)
+	}
+
+	// takes every block out of the slot, the slot keeps its block type
+	// none if the slot has no blocks in it
+	pub fn take_slot(&mut self, slot: usize) -> Option<(BlockType, u32)> {
+		let block_type = (*self.slots.get(slot)?)?;
+		match std::mem::take(&mut self.counts[slot]) {
+			0 => None,
+			count => Some((block_type, count)),
+		}
+	}
+
+	// adds blocks to the slot already holding their type, or to the first empty slot
+	// returns false if there is no room for them
+	pub fn collect(&mut self, block_type: BlockType, count: u32) -> bool {
+		match self.collect_slot(block_type) {
+			Some(slot) => {
+				self.slots[slot] = Some(block_type);
+				self.counts[slot] += count;
+				true
+			},
+			None => false,
+		}
+	}
+
+	pub fn has_room(&self, block_type: BlockType) -> bool {
+		self.collect_slot(block_type).is_some()
+	}
+
+	// the slot blocks of this type are collected into
+	fn collect_slot(&self, block_type: BlockType) -> Option<usize> {
+		self.slots.iter().position(|slot| *slot == Some(block_type))
+			.or_else(|| (0..HOTBAR_SIZE).find(|slot| self.slots[*slot].is_none() || self.counts[*slot] == 0))
+	}
+}
+
+// the number keys select hotbar slots, 1 is the first slot
+pub fn hotbar_slot_of_key(key: VirtualKeyCode) -> Option<usize> {
+	let slot = match key {
+		VirtualKeyCode::Key1 => 0,
+		VirtualKeyCode::Key2 => 1,
+		VirtualKeyCode::Key3 => 2,
+		VirtualKeyCode::Key4 => 3,
+		VirtualKeyCode::Key5 => 4,
+		VirtualKeyCode::Key6 => 5,
+		VirtualKeyCode::Key7 => 6,
+		VirtualKeyCode::Key8 => 7,
+		VirtualKeyCode::Key9 => 8,
+		_ => return None,
+	};
+	Some(slot)
+}
+
+// something the player did to a block, consumers like sounds and particles react to these instead of polling the interaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionEvent {
+	// the break button started being held on the block in survival
+	BreakStarted(BlockPos),
+	// the break progress passed another stage, which is from 1 up to BREAK_STAGES - 1
+	BreakProgressed {
+		block: BlockPos,
+		block_type: BlockType,
+		stage: u32,
+	},
+	// the block was broken, in any game mode
+	BreakCompleted {
+		block: BlockPos,
+		block_type: BlockType,
+	},
+	// breaking stopped or moved to another block before the block broke
+	BreakCancelled(BlockPos),
+	BlockPlaced {
+		block: BlockPos,
+		block_type: BlockType,
+	},
+}
+
+impl InteractionEvent {
+	pub fn block(&self) -> BlockPos {
+		match *self {
+			InteractionEvent::BreakStarted(block)
+				| InteractionEvent::BreakProgressed { block, .. }
+				| InteractionEvent::BreakCompleted { block, .. }
+				| InteractionEvent::BreakCancelled(block)
+				| InteractionEvent::BlockPlaced { block, .. } => block,
+		}
+	}
+
+	// cracking makes a quiet sound, starting and cancelling are silent
+	pub fn sound(&self) -> Option<Sound> {
+		match *self {
+			InteractionEvent::BreakProgressed { block_type, .. } => Some(Sound::Crack(block_type)),
+			InteractionEvent::BreakCompleted { block_type, .. } => Some(Sound::BlockBreak(block_type)),
+			InteractionEvent::BlockPlaced { block_type, .. } => Some(Sound::BlockPlace(block_type)),
+			InteractionEvent::BreakStarted(_) | InteractionEvent::BreakCancelled(_) => None,
+		}
+	}
+}
+
+// the stage of breaking a block with this hardness has reached after being held for held
+fn break_stage(held: Duration, hardness: f32) -> u32 {
+	((held.as_secs_f32() / hardness).min(1.0) * BREAK_STAGES as f32) as u32
+}
+
+// the block the player is looking at, and the actions to do to it
+// the target is found with a single raycast each physics update, which everything that needs the target uses
+#[derive(Debug)]
+pub struct Interaction {
+	target: Option<RaycastHit>,
+	// the selected slot is the type of block that is placed, picking a block puts it in the hotbar
+	hotbar: Hotbar,
+	// actions requested by input since the last update
+	break_requested: bool,
+	place_requested: bool,
+	pick_requested: bool,
+	// set when the place button is used on a block with a container, until it is taken
+	opened_container: Option<BlockPos>,
+	// set when the player breaks, places, or tries to break a block, until it is taken
+	swung: bool,
+	// what was done to blocks since the events were last taken
+	events: Vec<InteractionEvent>,
+	// the break button is held down, so blocks keep being broken
+	break_held: bool,
+	last_break: Option<Instant>,
+	// the block being broken in survival, and how long it has been held for
+	break_progress: Option<(BlockPos, Duration)>,
+}
+
+impl Interaction {
+	pub fn new(hotbar: Hotbar) -> Self {
+		Interaction {
+			target: None,
+			hotbar,
+			break_requested: false,
+			place_requested: false,
+			pick_requested: false,
+			opened_container: None,
+			swung: false,
+			events: Vec::new(),
+			break_held: false,
+			last_break: None,
+			break_progress: None,
+		}
+	}
+
+	// the block the player was looking at during the last update
+	pub fn target(&self) -> Option<RaycastHit> {
+		self.target
+	}
+
+	pub fn hotbar(&self) -> &Hotbar {
+		&self.hotbar
+	}
+
+	// for moving blocks between the hotbar and the inventory
+	pub fn hotbar_mut(&mut self) -> &mut Hotbar {
+		&mut self.hotbar
+	}
+
+	// fraction of the way the targeted block is to being broken in survival, none if it isn't being broken
+	pub fn break_progress(&self, world: &World) -> Option<f32> {
+		let (block, held) = self.break_progress?;
+		Some((held.as_secs_f32() / world.block_at(block)?.hardness()).min(1.0))
+	}
+
+	// the block whose container the player opened since the last call, if they opened one
+	pub fn take_opened_container(&mut self) -> Option<BlockPos> {
+		self.opened_container.take()
+	}
+
+	// true if the held block should swing because of something done since the last call
+	pub fn take_swing(&mut self) -> bool {
+		mem::take(&mut self.swung)
+	}
+
+	pub fn take_events(&mut self) -> Vec<InteractionEvent> {
+		mem::take(&mut self.events)
+	}
+
+	pub fn select_hotbar_slot(&mut self, slot: usize) {
+		self.hotbar.select_slot(slot);
+	}
+
+	pub fn request_break(&mut self) {
+		self.break_requested = true;
+	}
+
+	// breaks the target, then keeps breaking whatever is targeted until stop_breaking is called
+	pub fn start_breaking(&mut self) {
+		self.break_requested = true;
+		self.break_held = true;
+	}
+
+	pub fn stop_breaking(&mut self) {
+		self.break_held = false;
+	}
+
+	pub fn request_place(&mut self) {
+		self.place_requested = true;
+	}
+
+	pub fn request_pick(&mut self) {
+		self.pick_requested = true;
+	}
+
+	// stops breaking the block whose break progress is being kept, if there is one
+	fn cancel_breaking(&mut self) {
+		if let Some((block, _)) = self.break_progress.take() {
+			self.events.push(InteractionEvent::BreakCancelled(block));
+		}
+	}
+
+	// adds the time since the last update to how long the block has been held for, and returns true once it breaks
+	// the progress is reset when breaking stops or moves to another block
+	fn hold_to_break(&mut self, world: &World, block: BlockPos, delta: Duration) -> bool {
+		if !self.break_held && !self.break_requested {
+			self.cancel_breaking();
+			return false;
+		}
+
+		let (previous_held, held) = match self.break_progress {
+			Some((progress_block, held)) if progress_block == block => (held, held + delta),
+			_ => {
+				self.cancel_breaking();
+				self.events.push(InteractionEvent::BreakStarted(block));
+				(Duration::ZERO, delta)
+			},
+		};
+		self.break_progress = Some((block, held));
+
+		let (block_type, hardness) = match world.block_at(block) {
+			Some(block) => (block.block_type(), block.hardness()),
+			None => return false,
+		};
+		if held.as_secs_f32() >= hardness {
+			return true;
+		}
+
+		for stage in (break_stage(previous_held, hardness) + 1)..=break_stage(held, hardness) {
+			self.events.push(InteractionEvent::BreakProgressed {
+				block,
+				block_type,
+				stage,
+			});
+		}
+		false
+	}
+
+	// finds the new target and does any requested actions to it
+	// delta is the time since the last update, which is how long the break button was held for
+	pub fn update(&mut self, world: &World, ray_start: Position, ray: Vec3, reach: f32, game_mode: GameMode, delta: Duration) {
+		self.target = world.block_raycast(ray_start, ray, reach);
+
+		match self.target {
+			Some(target) => {
+				if self.pick_requested {
+					if let Some(block_type) = world.block_type_at(target.block) {
+						self.hotbar.pick(block_type);
+					}
+				}
+
+				let broken = if game_mode.instant_break() {
+					let repeat_break = self.break_held
+						&& self.last_break.is_none_or(|last_break| last_break.elapsed() >= BREAK_REPEAT_DELAY);
+					self.break_requested || repeat_break
+				} else {
+					self.hold_to_break(world, target.block, delta)
+				};
+
+				if broken {
+					// nothing is collected if the block couldn't be broken
+					let block_type = world.block_type_at(target.block);
+					let broken_type = world.set_block(target.block, Air::new().into()).ok().and(block_type);
+					match broken_type {
+						Some(block_type) => {
+							self.events.push(InteractionEvent::BreakCompleted {
+								block: target.block,
+								block_type,
+							});
+							if !game_mode.infinite_blocks() {
+								self.hotbar.collect(block_type, 1);
+							}
+							self.break_progress = None;
+						},
+						None => self.cancel_breaking(),
+					}
+
+					self.last_break = Some(Instant::now());
+					self.swung = true;
+					// the target is gone, the next update will find what is behind it
+					self.target = None;
+				} else if self.place_requested && world.with_block_entity_mut(target.block, |entity| !entity.slots().is_empty()) == Some(true) {
+					// using a container opens it instead of placing a block against it
+					self.opened_container = Some(target.block);
+				} else if self.place_requested {
+					let block = target.adjacent_block();
+					// don't place blocks inside of the player or on top of other blocks
+					if block != ray_start.as_block_pos() && world.block_type_at(block) == Some(BlockType::Air) {
+						// nothing is placed from an empty slot
+						if let Some(block_type) = self.hotbar.take_selected(game_mode) {
+							if world.set_block(block, block_type.new_block()).is_ok() {
+								self.events.push(InteractionEvent::BlockPlaced {
+									block,
+									block_type,
+								});
+							}
+							self.target = None;
+							self.swung = true;
+						}
+					}
+				}
+			},
+			None => self.cancel_breaking(),
+		}
+
+		// clicking swings even if there is nothing to break
+		self.swung |= self.break_requested;
+		self.break_requested = false;
+		self.place_requested = false;
+		self.pick_requested = false;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use super::*;
+	use crate::game::block::Stone;
+	use crate::game::chunk::{Chunk, LoadedChunk};
+
+	#[test]
+	fn picking_blocks_into_the_hotbar() {
+		let mut hotbar = Hotbar::new();
+		hotbar.select_slot(7);
+		assert_eq!(hotbar.selected_block(), None);
+
+		// a block type already in the hotbar selects its slot
+		hotbar.pick(BlockType::Grass);
+		assert_eq!(hotbar.selected_slot(), 2);
+
+		// a new block type replaces the selected slot
+		hotbar.pick(BlockType::TestBlock);
+		assert_eq!(hotbar.selected_slot(), 2);
+		assert_eq!(hotbar.selected_block(), Some(BlockType::TestBlock));
+
+		hotbar.select_slot(HOTBAR_SIZE);
+		assert_eq!(hotbar.selected_slot(), 2);
+	}
+
+	#[test]
+	fn survival_hotbar_uses_up_blocks() {
+		let mut hotbar = Hotbar::new();
+
+		// the palette is only free in creative
+		assert_eq!(hotbar.take_selected(GameMode::Creative), Some(BlockType::Stone));
+		assert_eq!(hotbar.take_selected(GameMode::Survival), None);
+
+		assert!(hotbar.collect(BlockType::Stone, 1));
+		assert!(hotbar.collect(BlockType::Stone, 1));
+		assert_eq!(hotbar.selected_count(), 2);
+		assert_eq!(hotbar.take_selected(GameMode::Survival), Some(BlockType::Stone));
+		assert_eq!(hotbar.take_selected(GameMode::Survival), Some(BlockType::Stone));
+		assert_eq!(hotbar.take_selected(GameMode::Survival), None);
+
+		// a new block type goes in the first slot without any blocks, replacing what was there in the palette
+		assert!(hotbar.collect(BlockType::TestBlock, 1));
+		assert_eq!(hotbar.slots()[0], Some(BlockType::TestBlock));
+		assert_eq!(hotbar.counts()[0], 1);
+	}
+
+	// a stone chunk below an air chunk at the origin, and the start of a ray pointing down at the stone's top layer
+	fn stone_floor() -> (Arc<World>, Position) {
+		let world = World::new_test().unwrap();
+		for (chunk, block) in [(ChunkPos::new(0, -1, 0), Stone::new().into()), (ChunkPos::new(0, 0, 0), Air::new().into())] {
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, block)));
+		}
+		(world, Position::new(0.5, 2.5, 0.5))
+	}
+
+	#[test]
+	fn survival_breaking_takes_hardness() {
+		let (world, ray_start) = stone_floor();
+		let top = BlockPos::new(0, -1, 0);
+		let hardness = Stone::new().hardness();
+		let half = Duration::from_secs_f32(0.5 * hardness);
+
+		let mut interaction = Interaction::new(Hotbar::new());
+		interaction.start_breaking();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, half);
+		assert_eq!(world.block_type_at(top), Some(BlockType::Stone));
+		assert!((interaction.break_progress(&world).unwrap() - 0.5).abs() < 0.01);
+
+		// letting go resets the progress
+		interaction.stop_breaking();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, half);
+		assert_eq!(interaction.break_progress(&world), None);
+
+		interaction.start_breaking();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, half);
+		assert_eq!(world.block_type_at(top), Some(BlockType::Stone));
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, Duration::from_secs_f32(hardness));
+		assert_eq!(world.block_type_at(top), Some(BlockType::Air));
+		// the broken block is collected into the hotbar, and can be placed back once
+		assert_eq!(interaction.hotbar().selected_count(), 1);
+		interaction.stop_breaking();
+
+		interaction.request_place();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, half);
+		assert_eq!(world.block_type_at(top), Some(BlockType::Stone));
+		assert_eq!(interaction.hotbar().selected_count(), 0);
+
+		interaction.request_place();
+		interaction.update(&world, ray_start + Position::new(0.0, 1.0, 0.0), Vec3::NEG_Y, 5.0, GameMode::Survival, half);
+		assert_eq!(world.block_type_at(top + BlockPos::new(0, 1, 0)), Some(BlockType::Air));
+	}
+
+	#[test]
+	fn breaking_events_for_a_full_break() {
+		let (world, ray_start) = stone_floor();
+		let top = BlockPos::new(0, -1, 0);
+		let step = Duration::from_secs_f32(0.3 * Stone::new().hardness());
+		let progressed = |stage| InteractionEvent::BreakProgressed {
+			block: top,
+			block_type: BlockType::Stone,
+			stage,
+		};
+
+		let mut interaction = Interaction::new(Hotbar::new());
+		interaction.start_breaking();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, step);
+		assert_eq!(interaction.take_events(), vec![InteractionEvent::BreakStarted(top), progressed(1)]);
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, step);
+		assert_eq!(interaction.take_events(), vec![progressed(2)]);
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, step);
+		assert_eq!(interaction.take_events(), vec![progressed(3)]);
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, step);
+		assert_eq!(interaction.take_events(), vec![InteractionEvent::BreakCompleted {
+			block: top,
+			block_type: BlockType::Stone,
+		}]);
+
+		// nothing else starts being broken once the button is let go
+		interaction.stop_breaking();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, step);
+		assert_eq!(interaction.take_events(), vec![]);
+	}
+
+	#[test]
+	fn breaking_events_when_cancelled() {
+		let (world, ray_start) = stone_floor();
+		let top = BlockPos::new(0, -1, 0);
+		let step = Duration::from_secs_f32(0.3 * Stone::new().hardness());
+
+		let mut interaction = Interaction::new(Hotbar::new());
+		interaction.start_breaking();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, step);
+		assert_eq!(interaction.take_events().len(), 2);
+
+		interaction.stop_breaking();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, step);
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, step);
+		assert_eq!(interaction.take_events(), vec![InteractionEvent::BreakCancelled(top)]);
+		assert_eq!(world.block_type_at(top), Some(BlockType::Stone));
+
+		// moving to another block cancels the first one before starting the next
+		let next = BlockPos::new(1, -1, 0);
+		interaction.start_breaking();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Survival, Duration::ZERO);
+		interaction.update(&world, ray_start + Position::new(1.0, 0.0, 0.0), Vec3::NEG_Y, 5.0, GameMode::Survival, Duration::ZERO);
+		assert_eq!(interaction.take_events(), vec![
+			InteractionEvent::BreakStarted(top),
+			InteractionEvent::BreakCancelled(top),
+			InteractionEvent::BreakStarted(next),
+		]);
+	}
+
+	#[test]
+	fn creative_breaking_is_instant() {
+		let (world, ray_start) = stone_floor();
+		let top = BlockPos::new(0, -1, 0);
+
+		let mut interaction = Interaction::new(Hotbar::new());
+		interaction.request_break();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Creative, Duration::ZERO);
+		assert_eq!(world.block_type_at(top), Some(BlockType::Air));
+		// nothing is collected, and placing doesn't use anything up
+		assert_eq!(interaction.hotbar().selected_count(), 0);
+
+		interaction.request_place();
+		interaction.update(&world, ray_start, Vec3::NEG_Y, 5.0, GameMode::Creative, Duration::ZERO);
+		assert_eq!(world.block_type_at(top), Some(BlockType::Stone));
+	}
+}
@@ -1,6 +1,7 @@
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::cell::RefCell;
+use std::path::PathBuf;
 
 use winit::{
 	window::Window,
@@ -8,92 +9,751 @@ use winit::{
 	dpi::PhysicalSize
 };
 use rustc_hash::FxHashMap;
+use glam::{IVec2, Vec3};
+use array_init::array_init;
 
 use crate::prelude::*;
-use crate::render::{Renderer, Aabb};
-use crate::render::model::{Mesh, Material};
+use crate::render::Renderer;
+use crate::render::model::{Material, Mesh};
+use crate::render::texture::TextureFilter;
+use crate::render::terrain_buffer::TerrainMesh;
+use crate::render::render_queue::{RenderQueue, DrawItem, DetailLevel};
+use crate::render::blob_shadow::{BlobShadow, MAX_SHADOW_HEIGHT};
+use crate::render::Aabb;
 use camera_controller::CameraController;
-use super::player::PlayerId;
-use super::world::World;
-use super::block::{generate_texture_array, BlockFaceMesh, Air};
-use super::render_zone::{UpdatedRenderZones, render_zone_aabb};
-use super::ui::MineConeUi;
+use camera_path::{CameraPath, CameraPathRecorder};
+use camera_target::CameraTarget;
+use held_block::{HeldBlock, held_block_mesh};
+use benchmark::Benchmark;
+use zone_stats::{MeshStats, heaviest_zones, format_zone_list};
+use environment::{Environment, sky_rotation, water_surface_below};
+use rain::Rain;
+use break_particles::BreakParticles;
+use sound::SoundManager;
+use interaction::{Interaction, InteractionEvent, Hotbar, hotbar_slot_of_key};
+use gamepad::GamepadInput;
+use super::player::{PlayerId, GameMode, DamageOutcome, generate_player_name};
+use super::world::{World, ChunkRepair};
+use super::world_state::Weather;
+use super::block::{generate_texture_array, BlockFaceMesh, BlockTrait, BlockType};
+use super::render_zone::{UpdatedChunks, render_zone_of_chunk, render_zone_aabb, chunk_aabb};
+use super::parallel::{self, TaskLatency, TaskLatencyStats, LATENCY_WINDOW_TIME};
+use super::config::{Config, CONFIG_FILE};
+use super::commands::{Command, parse_command};
+use super::map_export::MapExport;
+use super::crafting::{CraftingGrid, RecipeBook, CRAFTING_GRID_SIZE};
+use crate::assets::loader;
+use super::ui::{MineConeUi, MinimapData, BiomeOverlayData, HudData, LoadingProgress, InventoryData, InventoryAction, ContainerData, ContainerAction, FurnaceProgress, MAX_LABEL_DISTANCE, UI_SCALE_KEY};
 
 mod camera_controller;
+mod camera_path;
+mod camera_target;
+mod held_block;
+mod benchmark;
+mod zone_stats;
+mod environment;
+mod rain;
+mod break_particles;
+mod interaction;
+mod vertical_motion;
+mod gamepad;
+mod sound;
+
+// radius in chunks of the area shown on the minimap
+const MINIMAP_RADIUS: i32 = 16;
+// number of columns sampled in each direction from the player for the biome overlay,
+// and the distance in blocks between them
+const BIOME_OVERLAY_RADIUS: i32 = 32;
+const BIOME_OVERLAY_STEP: i32 = 8;
+// camera paths are recorded to and played back from this file, relative to the working directory
+const CAMERA_PATH_FILE: &str = "camera-path.txt";
+// config key of the name of the active texture pack
+const TEXTURE_PACK_KEY: &str = "texture_pack";
+// config key of the name the player's state is saved under in each world
+const PLAYER_NAME_KEY: &str = "player_name";
+// number of render zones with the most triangles shown in the debug window
+const HOT_ZONE_COUNT: usize = 10;
+// entities further than this from the camera on any axis have no shadow
+const SHADOW_DISTANCE: f32 = 32.0;
+// labels are drawn this far above their entity's position
+const ENTITY_LABEL_HEIGHT: f32 = 2.0;
+// time it takes a newly appeared render zone to fade in
+const ZONE_FADE_TIME: Duration = Duration::from_millis(300);
+// memory use is estimated by going through every chunk, so it isn't done every physics update
+const MEMORY_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+// task latency percentiles sort every sample in the window, so they are only updated this often
+const LATENCY_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+// measured movement speed is averaged over this long, single physics updates are too noisy
+const SPEED_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+// meshes of every chunk in a render zone
+struct ZoneMesh {
+	// when the zone first appeared, so it can be faded in
+	spawn_time: Instant,
+	chunk_meshes: FxHashMap<ChunkPos, TerrainMesh>,
+	// total size of the chunk meshes
+	stats: MeshStats,
+}
+
+impl ZoneMesh {
+	fn new() -> Self {
+		ZoneMesh {
+			spawn_time: Instant::now(),
+			chunk_meshes: FxHashMap::default(),
+			stats: MeshStats::default(),
+		}
+	}
+
+	// the chunk's old mesh must be removed first, so it can be freed
+	fn insert_mesh(&mut self, chunk: ChunkPos, mesh: TerrainMesh) {
+		self.stats += MeshStats::of_mesh(&mesh);
+		let old_mesh = self.chunk_meshes.insert(chunk, mesh);
+		assert!(old_mesh.is_none(), "old mesh of chunk {:?} was not removed", chunk);
+	}
+
+	fn remove_mesh(&mut self, chunk: ChunkPos) -> Option<TerrainMesh> {
+		let mesh = self.chunk_meshes.remove(&chunk)?;
+		self.stats -= MeshStats::of_mesh(&mesh);
+		Some(mesh)
+	}
+
+	fn alpha(&self) -> f32 {
+		(self.spawn_time.elapsed().as_secs_f32() / ZONE_FADE_TIME.as_secs_f32()).min(1.0)
+	}
+}
 
 pub struct Client {
 	world: Arc<World>,
-	world_mesh: RefCell<FxHashMap<ChunkPos, Mesh>>,
+	// meshes of each chunk, grouped by render zone
+	world_mesh: RefCell<FxHashMap<ChunkPos, ZoneMesh>>,
 	block_textures: Material,
 	player_id: PlayerId,
 	camera_controller: CameraController,
+	// the camera is smoothed towards this every frame, the local controller moves both together so it isn't smoothed
+	camera_target: CameraTarget,
+	held_block: HeldBlock,
+	gamepad: GamepadInput,
 	ui: MineConeUi,
+	// fog and tint around the camera, moves towards the environment at the camera's position each physics update
+	environment: Environment,
+	// falls around the camera while it is raining
+	rain: Rain,
+	break_particles: BreakParticles,
+	sounds: SoundManager,
 	renderer: Renderer,
 	window: Window,
-	// destroy block on the next physics frame
-	destroy_block: bool,
-	// this is a set of all the render zones that need their frame updated
-	updated_render_zones: UpdatedRenderZones,
+	interaction: Interaction,
+	// what the player did to blocks during this physics update, drained by the sounds and particles
+	interaction_events: Vec<InteractionEvent>,
+	game_mode: GameMode,
+	// ingredients put in the crafting grid in the inventory, they go back to the hotbar when it is closed
+	crafting_grid: CraftingGrid,
+	recipes: RecipeBook,
+	// the block whose container is open, it is closed when the block is broken or the player moves away from it
+	open_container: Option<BlockPos>,
+	// set while the world loads where the player is teleporting to, the camera is moved there once the world moves the player
+	teleporting: bool,
+	// shadows of the entities around the camera, found each physics update
+	blob_shadows: Vec<BlobShadow>,
+	// world raycast count at the end of the last physics update
+	last_raycast_count: u64,
+	// time between the last 2 rendered frames, which isn't the time between physics updates
+	last_frame_time: Duration,
+	// number of terrain triangles in the last rendered frame
+	last_triangle_count: u64,
+	// gpu time of the main pass of a recent frame, none if the gpu can't measure it
+	last_gpu_time: Option<Duration>,
+	last_memory_report: Instant,
+	// task latency percentiles from the last latency report, also recorded by the benchmark
+	task_latency: TaskLatencyStats,
+	last_latency_report: Instant,
+	// camera position and time when the measured movement speed was last updated
+	speed_sample: (Vec3, Instant),
+	camera_path: CameraPathRecorder,
+	// the map being exported with /exportmap, only one can be exported at a time
+	map_export: Option<MapExport>,
+	// set when the game was started with --benchmark, it is written out when the game exits
+	benchmark: Option<Benchmark>,
+	config: Config,
+	// this is a set of all the chunks that need their mesh updated, some are carried over to later physics updates
+	updated_chunks: UpdatedChunks,
+	// chunk of the oldest block edit whose mesh isn't uploaded yet, and when the edit was made
+	pending_edit: Option<(ChunkPos, Instant)>,
+	// time of the edit whose mesh was uploaded since the last frame, the next frame shows it
+	uploaded_edit: Option<Instant>,
 }
 
 impl Client {
-	pub fn new(window: Window, world: Arc<World>) -> Self {
-		let renderer = pollster::block_on(Renderer::new(&window));
+	// the renderer and ui are created before the world, for the main menu
+	// if a benchmark camera path is given, it is played back immediately,
+	// and stats are collected into a csv file next to it with the same name
+	pub fn new(window: Window, mut renderer: Renderer, mut ui: MineConeUi, world: Arc<World>, benchmark_path: Option<PathBuf>) -> Self {
+		let mut camera_path = CameraPathRecorder::new(PathBuf::from(CAMERA_PATH_FILE));
+		let benchmark = benchmark_path.map(|benchmark_path| {
+			let path = CameraPath::load(&benchmark_path).expect("could not load benchmark camera path");
+			camera_path.play(path);
+			Benchmark::new(benchmark_path.with_extension("csv"))
+		});
+
+		let mut config = Config::load(PathBuf::from(CONFIG_FILE));
+		let mut texture_pack = config.get(TEXTURE_PACK_KEY).map(String::from);
+		loader().set_active_pack(texture_pack.clone());
 
-		let texture_array = generate_texture_array().expect("could not load texture map");
-		let block_textures = Material::array_from_images(&texture_array, String::from("texture map"), renderer.context());
+		let create_block_textures = || generate_texture_array().and_then(|texture_array| Material::array_from_images(
+			&texture_array,
+			String::from("texture map"),
+			renderer.context().sampler(TextureFilter::Nearest),
+			renderer.context(),
+		));
+		// a broken texture pack shouldn't stop the game from starting
+		let block_textures = create_block_textures().or_else(|error| match texture_pack.take() {
+			Some(pack) => {
+				warn!("could not use texture pack {}, using default textures: {:#}", pack, error);
+				loader().set_active_pack(None);
+				create_block_textures()
+			},
+			None => Err(error),
+		}).expect("could not create texture map");
+
+		let player_name = match config.get(PLAYER_NAME_KEY) {
+			Some(name) => name.to_owned(),
+			None => {
+				let name = generate_player_name();
+				config.set(PLAYER_NAME_KEY, Some(&name));
+				if let Err(error) = config.save() {
+					error!("could not save config: {:#}", error);
+				}
+				name
+			},
+		};
 
-		let player_id = world.connect();
+		let (player_id, player_state) = world.connect(&player_name);
+
+		// the renderer may have been used for another world, so the camera is moved to where the player was
+		let camera = renderer.get_camera_mut();
+		camera.position = player_state.position.0;
+		camera.look_at = player_state.position.0 + player_state.forward();
+		camera.generate_frustum();
+		let camera_target = CameraTarget::from_camera(camera);
+
+		let environment = Environment::surface(world.surface_biome_at(player_state.position.as_block_pos()).fog_color);
+		// new players don't have a saved hotbar
+		let hotbar = if player_state.hotbar.is_empty() {
+			Hotbar::new()
+		} else {
+			Hotbar::from_slots(&player_state.hotbar, &player_state.hotbar_counts, player_state.selected_slot)
+		};
+		let mut camera_controller = CameraController::new(7.0, 20.0, 2.0);
+		camera_controller.set_fly_allowed(player_state.game_mode.can_fly());
+
+		ui.set_texture_pack(texture_pack);
+		// the tops of water blocks show the reflection
+		renderer.set_water_texture_index(BlockType::Water.new_block().texture_index().unwrap_or(-1));
 
 		Self {
 			world,
 			world_mesh: RefCell::new(FxHashMap::default()),
 			block_textures,
 			player_id,
-			camera_controller: CameraController::new(7.0, 20.0, 2.0),
-			ui: MineConeUi::new(&window, &renderer),
+			camera_controller,
+			camera_target,
+			held_block: HeldBlock::new(),
+			gamepad: GamepadInput::new(),
+			ui,
+			environment,
+			rain: Rain::new(),
+			break_particles: BreakParticles::new(),
+			sounds: SoundManager::new(),
 			renderer,
 			window,
-			destroy_block: false,
-			updated_render_zones: UpdatedRenderZones::new(),
+			interaction: Interaction::new(hotbar),
+			interaction_events: Vec::new(),
+			game_mode: player_state.game_mode,
+			crafting_grid: CraftingGrid::new(),
+			recipes: RecipeBook::builtin(),
+			open_container: None,
+			teleporting: false,
+			blob_shadows: Vec::new(),
+			last_raycast_count: 0,
+			last_frame_time: Duration::ZERO,
+			last_triangle_count: 0,
+			last_gpu_time: None,
+			last_memory_report: Instant::now(),
+			task_latency: TaskLatencyStats::default(),
+			last_latency_report: Instant::now(),
+			speed_sample: (player_state.position.0, Instant::now()),
+			camera_path,
+			map_export: None,
+			benchmark,
+			config,
+			updated_chunks: UpdatedChunks::new(),
+			pending_edit: None,
+			uploaded_edit: None,
 		}
 	}
 
-	fn generate_mesh(&self, render_zone: ChunkPos) {
+	fn generate_mesh(&mut self, chunk: ChunkPos) {
 		let mut vertexes = Vec::new();
 		let mut indexes = Vec::new();
 
 		let mut current_index = 0;
-		for block_face in self.world.render_zone_mesh(render_zone) {
+		for block_face in self.world.chunk_mesh(chunk) {
 			vertexes.extend(block_face.0);
 			indexes.extend(BlockFaceMesh::indicies().iter().map(|elem| elem + current_index));
 			current_index += 4;
 		}
 
-		// TODO: write to the underlying buffer
-		self.world_mesh.borrow_mut().insert(render_zone, Mesh::new(
-			"world mesh",
-			&vertexes,
-			&indexes,
-			0,
-			Some(render_zone_aabb(render_zone)),
-			self.renderer.context(),
+		let mut world_mesh = self.world_mesh.borrow_mut();
+		let render_zone = render_zone_of_chunk(chunk);
+		// zones that are already present are not faded in again when one of their chunks is updated
+		let zone_mesh = world_mesh.entry(render_zone).or_insert_with(ZoneMesh::new);
+
+		if let Some(old_mesh) = zone_mesh.remove_mesh(chunk) {
+			self.renderer.free_terrain_mesh(old_mesh);
+		}
+
+		// chunk is unloaded or has no visible faces
+		if vertexes.is_empty() {
+			if zone_mesh.chunk_meshes.is_empty() {
+				world_mesh.remove(&render_zone);
+			}
+			return;
+		}
+
+		let mesh = self.renderer.alloc_terrain_mesh(&vertexes, &indexes, chunk_aabb(chunk));
+		zone_mesh.insert_mesh(chunk, mesh);
+	}
+
+	// rebuilds the block textures from the pack, and saves it as the active pack
+	// if the pack's textures can't be used, the old pack is kept
+	fn set_texture_pack(&mut self, pack: Option<String>) {
+		let old_pack = loader().active_pack();
+		loader().set_active_pack(pack.clone());
+
+		let result = generate_texture_array()
+			.and_then(|texture_array| self.block_textures.replace_images(&texture_array, self.renderer.context()));
+		if let Err(error) = result {
+			debug_string("Texture Pack Error", format!("{:#}", error));
+			loader().set_active_pack(old_pack.clone());
+			self.ui.set_texture_pack(old_pack);
+			return;
+		}
+		debug_string("Texture Pack Error", String::from("none"));
+
+		self.config.set(TEXTURE_PACK_KEY, pack.as_deref());
+		if let Err(error) = self.config.save() {
+			error!("could not save config: {:#}", error);
+		}
+	}
+
+	// each shadow is put on top of the first block below its entity
+	fn update_blob_shadows(&mut self, camera_position: Position, tick_alpha: f32) {
+		let area = Aabb::new(camera_position.0 - Vec3::splat(SHADOW_DISTANCE), Vec3::splat(2.0 * SHADOW_DISTANCE));
+		for entity in self.world.entities_in_aabb(area) {
+			let position = match self.world.entity_render_position(entity, tick_alpha) {
+				Some(position) => position,
+				None => continue,
+			};
+
+			if let Some(ground) = self.world.block_raycast(position, Vec3::NEG_Y, MAX_SHADOW_HEIGHT) {
+				let ground_height = (ground.block.y + 1) as f32;
+				self.blob_shadows.extend(BlobShadow::new(position.0, ground_height));
+			}
+		}
+	}
+
+	// gives the world what it saves about the player, other than their position which it already knows
+	fn save_player_state(&self) {
+		let forward = self.renderer.camera().forward();
+		let hotbar = self.interaction.hotbar();
+
+		self.world.update_player_state(self.player_id, |state| {
+			state.set_forward(forward);
+			state.game_mode = self.game_mode;
+			state.hotbar = hotbar.slots().to_vec();
+			state.hotbar_counts = hotbar.counts().to_vec();
+			state.selected_slot = hotbar.selected_slot();
+		});
+	}
+
+	// the world has already applied the damage, this shows it, and moves the camera to the spawn point if the player died
+	fn show_damage(&mut self, outcome: DamageOutcome) {
+		self.ui.flash_damage();
+
+		if let DamageOutcome::Died { respawn_point } = outcome {
+			self.move_camera_to(respawn_point);
+			self.ui.console_print(String::from("you died"));
+		}
+	}
+
+	// jumps the camera to the position without smoothing, facing the same way
+	fn move_camera_to(&mut self, position: Position) {
+		let camera = self.renderer.get_camera_mut();
+		let forward = camera.forward();
+		camera.position = position.0;
+		camera.look_at = position.0 + forward;
+		camera.generate_frustum();
+		self.camera_target.snap(position.0, forward);
+	}
+
+	// shows how much of the teleport destination is loaded, and moves the camera to the player once the world has moved them
+	// this has to run before the camera's position is given to the world, or the player would be moved back
+	fn update_teleport(&mut self) {
+		let progress = self.world.teleport_progress(self.player_id);
+		self.ui.set_teleport_progress(progress);
+
+		if self.teleporting && progress.is_none() {
+			if let Some(position) = self.world.player_position(self.player_id) {
+				self.move_camera_to(position);
+			}
+		}
+		self.teleporting = progress.is_some();
+	}
+
+	// applies what was done in the inventory window, and gives it the current hotbar and crafting grid
+	fn update_inventory(&mut self) {
+		if !self.ui.inventory_open() {
+			self.return_crafting_grid();
+			return;
+		}
+
+		let hotbar = self.interaction.hotbar_mut();
+		match self.ui.take_inventory_action() {
+			Some(InventoryAction::SelectSlot(slot)) => hotbar.select_slot(slot),
+			Some(InventoryAction::AddToCell { row, column }) => {
+				// a block is only taken from the hotbar if the cell can hold it
+				let fits = match (hotbar.selected_block(), self.crafting_grid.cell(row, column)) {
+					(Some(block_type), Some((cell_type, _))) => block_type == cell_type,
+					(selected, None) => selected.is_some(),
+					(None, _) => false,
+				};
+				if fits {
+					if let Some(block_type) = hotbar.take_selected(self.game_mode) {
+						self.crafting_grid.add(row, column, block_type);
+					}
+				}
+			},
+			Some(InventoryAction::EmptyCell { row, column }) => {
+				if let Some((block_type, _)) = self.crafting_grid.cell(row, column) {
+					if hotbar.has_room(block_type) {
+						let (block_type, count) = self.crafting_grid.take(row, column).unwrap();
+						hotbar.collect(block_type, count);
+					}
+				}
+			},
+			Some(InventoryAction::Craft) => {
+				// crafted blocks go in the hotbar, so nothing is crafted when there is no room for them
+				if let Some((block_type, _)) = self.crafting_grid.output(&self.recipes) {
+					if hotbar.has_room(block_type) {
+						let (block_type, count) = self.crafting_grid.craft(&self.recipes).unwrap();
+						hotbar.collect(block_type, count);
+					}
+				}
+			},
+			None => (),
+		}
+
+		let hotbar = self.interaction.hotbar();
+		let infinite_blocks = self.game_mode.infinite_blocks();
+		self.ui.set_inventory_data(InventoryData {
+			slots: hotbar.slots().iter()
+				.zip(hotbar.counts())
+				.map(|(slot, count)| (*slot, (!infinite_blocks).then_some(*count)))
+				.collect(),
+			selected_slot: hotbar.selected_slot(),
+			crafting_grid: array_init(|row| array_init(|column| self.crafting_grid.cell(row, column))),
+			output: self.crafting_grid.output(&self.recipes),
+		});
+	}
+
+	// applies what was done in the container window, and gives it the current contents of the container and hotbar
+	fn update_container(&mut self, camera_position: Position, reach: f32) {
+		let block = match self.open_container {
+			Some(block) => block,
+			None => return,
+		};
+
+		let world = &self.world;
+		let container_slots = || world.with_block_entity_mut(block, |entity| entity.slots().to_vec());
+		// a bit of leeway, so the container doesn't close as soon as the player steps back
+		let in_reach = (block.as_position() + Position::splat(0.5)).distance(camera_position.0) <= reach + 1.0;
+		let slots = match container_slots() {
+			Some(slots) if in_reach && self.ui.container_open() => slots,
+			_ => {
+				self.open_container = None;
+				self.ui.close_container();
+				return;
+			},
+		};
+
+		let hotbar = self.interaction.hotbar_mut();
+		match self.ui.take_container_action() {
+			Some(ContainerAction::TakeSlot(slot)) => {
+				// blocks are only taken when there is room for them in the hotbar
+				if let Some((block_type, _)) = slots.get(slot).copied().flatten() {
+					if hotbar.has_room(block_type) {
+						if let Some((block_type, count)) = world.with_block_entity_mut(block, |entity| entity.take(slot)).flatten() {
+							hotbar.collect(block_type, count);
+						}
+					}
+				}
+			},
+			Some(ContainerAction::StoreSlot(slot)) => {
+				if let Some((block_type, count)) = hotbar.take_slot(slot) {
+					let stored = world.with_block_entity_mut(block, |entity| entity.insert(block_type, count));
+					// the blocks go back if the container is full, or they can't go in it
+					if stored != Some(true) {
+						hotbar.collect(block_type, count);
+					}
+				}
+			},
+			None => (),
+		}
+
+		let hotbar = self.interaction.hotbar();
+		self.ui.set_container_data(ContainerData {
+			title: world.block_type_at(block).map_or("Container", BlockType::name).to_owned(),
+			slots: container_slots().unwrap_or_default(),
+			hotbar: hotbar.slots().iter()
+				.zip(hotbar.counts())
+				.map(|(slot, count)| slot.filter(|_| *count > 0).map(|block_type| (block_type, *count)))
+				.collect(),
+			furnace: world.with_block_entity_mut(block, |entity| entity.furnace().map(|furnace| FurnaceProgress {
+				burn: furnace.burn_progress(),
+				smelt: furnace.smelt_progress(),
+			})).flatten(),
+		});
+	}
+
+	// blocks which there isn't room for in the hotbar stay in the grid until there is
+	fn return_crafting_grid(&mut self) {
+		let hotbar = self.interaction.hotbar_mut();
+		for row in 0..CRAFTING_GRID_SIZE {
+			for column in 0..CRAFTING_GRID_SIZE {
+				if let Some((block_type, _)) = self.crafting_grid.cell(row, column) {
+					if hotbar.has_room(block_type) {
+						let (block_type, count) = self.crafting_grid.take(row, column).unwrap();
+						hotbar.collect(block_type, count);
+					}
+				}
+			}
+		}
+	}
+
+	// remakes the held block's mesh when another block is selected
+	fn update_held_block(&mut self) {
+		let selected_block = self.interaction.hotbar().selected_block();
+		if self.held_block.set_block(selected_block) {
+			let mesh = selected_block
+				.and_then(held_block_mesh)
+				.map(|(vertices, indices)| Mesh::new("held block", &vertices, &indices, 0, None, self.renderer.context()));
+			self.renderer.set_held_block(mesh);
+		}
+	}
+
+	// labels over the entities around the camera, and over the targeted block if that is turned on in the debug window
+	fn update_world_labels(&mut self, camera_position: Position, tick_alpha: f32) {
+		let area = Aabb::new(camera_position.0 - Vec3::splat(MAX_LABEL_DISTANCE), Vec3::splat(2.0 * MAX_LABEL_DISTANCE));
+		let mut labels = self.world.entities_in_aabb(area).into_iter()
+			.filter_map(|entity| self.world.entity_label(entity, tick_alpha))
+			.map(|(position, label)| (position + Position::new(0.0, ENTITY_LABEL_HEIGHT, 0.0), label))
+			.collect::<Vec<_>>();
+
+		if self.ui.target_label_enabled() {
+			let target = self.interaction.target()
+				.and_then(|target| Some((target.selection, self.world.block_at(target.block)?)));
+			if let Some((selection, block)) = target {
+				// the label sits on top of the box that was targeted, so it isn't floating over short blocks
+				let top = selection.position + selection.size * Vec3::new(0.5, 1.0, 0.5);
+				labels.push((Position(top), block.name().to_owned()));
+			}
+		}
+
+		self.ui.draw_world_labels(self.renderer.camera(), &labels);
+	}
+
+	fn update_biome_overlay(&mut self, player_position: Position) {
+		if !self.ui.biome_overlay_has_diagram() {
+			self.ui.set_biome_diagram(&self.world.biome_diagram_colors());
+		}
+
+		let player_block = player_position.as_block_pos();
+
+		let mut world_biomes = Vec::new();
+		for z in -BIOME_OVERLAY_RADIUS..=BIOME_OVERLAY_RADIUS {
+			for x in -BIOME_OVERLAY_RADIUS..=BIOME_OVERLAY_RADIUS {
+				let block = player_block + BlockPos::new(x, 0, z) * BIOME_OVERLAY_STEP;
+				world_biomes.push(self.world.surface_biome_at(block).map_color);
+			}
+		}
+
+		self.ui.set_biome_overlay_data(BiomeOverlayData {
+			player_noise: self.world.biome_noise_at(player_block),
+			player_biome: self.world.surface_biome_at(player_block).name.clone(),
+			player_underground_biome: self.world.underground_biome_at(player_block).name().to_owned(),
+			world_biomes,
+			world_biomes_size: (2 * BIOME_OVERLAY_RADIUS + 1) as usize,
+			world_biomes_step: BIOME_OVERLAY_STEP,
+		});
+	}
+
+	fn display_task_problems(&self) {
+		let failed_tasks = parallel::failed_tasks();
+		let failed_tasks_string = failed_tasks.iter()
+			.map(|task| format!("{:?}", task))
+			.collect::<Vec<_>>()
+			.join("\n");
+		debug_display("Failed Tasks", &failed_tasks.len());
+		if !failed_tasks.is_empty() {
+			debug_string("Failed Task List", failed_tasks_string);
+		}
+
+		let worker_problems = parallel::worker_problems();
+		let worker_problems_string = worker_problems.iter()
+			.map(|problem| format!("{:?}", problem))
+			.collect::<Vec<_>>()
+			.join("\n");
+		debug_string("Worker Problems", worker_problems_string);
+		debug_display("Task Workers", &parallel::worker_count());
+
+		let stale_tasks = parallel::stale_task_counts();
+		debug_string("Skipped Stale Tasks", format!(
+			"{} chunk mesh, {} chunk mesh face",
+			stale_tasks.chunk_mesh,
+			stale_tasks.chunk_mesh_face,
+		));
+	}
+
+	// shows how fast the camera actually moved, so speed regressions are visible
+	fn measure_speed(&mut self, camera_position: Vec3) {
+		let (sample_position, sample_time) = self.speed_sample;
+		let elapsed = sample_time.elapsed();
+		if elapsed >= SPEED_SAMPLE_INTERVAL {
+			let speed = camera_position.distance(sample_position) / elapsed.as_secs_f32();
+			debug_string("Movement Speed", format!("{:.2} blocks/s", speed));
+			self.speed_sample = (camera_position, Instant::now());
+		}
+	}
+
+	fn report_memory(&self) {
+		let world_stats = self.world.memory_stats();
+		let mut mesh_stats = MeshStats::default();
+		for zone_mesh in self.world_mesh.borrow().values() {
+			mesh_stats += zone_mesh.stats;
+		}
+
+		debug_section_string("Memory", "Loaded Chunks", world_stats.loaded_chunks.to_string());
+		debug_section_string("Memory", "Chunk Blocks", format_bytes(world_stats.block_bytes));
+		debug_section_string("Memory", "Chunk Meshes (CPU)", format_bytes(world_stats.mesh_bytes));
+		debug_section_string("Memory", "Cached Chunks", format!("{}, {}", world_stats.cached_chunks, format_bytes(world_stats.cached_bytes)));
+		debug_section_string("Memory", "Chunk Meshes (GPU)", format!(
+			"{} used, {} allocated",
+			format_bytes(mesh_stats.bytes),
+			format_bytes(self.renderer.terrain_memory_size()),
 		));
+		debug_section_string("Memory", "Queued Tasks", parallel::queued_task_count().to_string());
+	}
+
+	fn report_task_latency(&mut self) {
+		self.task_latency = parallel::task_latency_stats();
+
+		let format_latency = |latency: TaskLatency| format!(
+			"{} in the last {}s\nqueued to done: {}\nrunning: {}",
+			latency.count,
+			LATENCY_WINDOW_TIME.as_secs(),
+			latency.total,
+			latency.execution,
+		);
+		debug_section_string("Task Latency", "Chunk Generation", format_latency(self.task_latency.generate_chunk));
+		debug_section_string("Task Latency", "Chunk Meshing", format_latency(self.task_latency.chunk_mesh));
 	}
 
-	fn render(&mut self) {
+	// delta is the time since the last frame was rendered
+	fn render(&mut self, delta: Duration) {
+		self.last_frame_time = delta;
+		let smoothing_rate = self.ui.settings().graphics.camera_smoothing_rate;
+		self.camera_target.apply(self.renderer.get_camera_mut(), delta, smoothing_rate);
+		self.held_block.advance(delta);
+		let held_block_transform = self.held_block.transform(self.renderer.camera());
+		self.renderer.set_held_block_transform(held_block_transform);
+
 		let world_mesh = self.world_mesh.borrow();
-		let models = world_mesh.values().map(|mesh| (mesh, &self.block_textures)).collect::<Vec<_>>();
+		let camera_position = self.renderer.camera().position;
+		let full_detail_distance = self.ui.settings().graphics.full_detail_distance;
+
+		let mut render_queue = RenderQueue::new();
+		let mut total_stats = MeshStats::default();
+		for (render_zone, zone_mesh) in world_mesh.iter() {
+			total_stats += zone_mesh.stats;
 
-		let mut tri_count = 0;
-		for (mesh, _) in models.iter() {
-			tri_count += mesh.triangle_count() as i64;
+			let bounding_box = render_zone_aabb(*render_zone);
+			let alpha = zone_mesh.alpha();
+			// zones which are fading in are sorted by the distance to their center, so the whole zone is blended together
+			let sort_key = (bounding_box.position + 0.5 * bounding_box.size).distance_squared(camera_position);
+			let detail = zone_detail_level(bounding_box, camera_position, full_detail_distance);
+
+			render_queue.push_group(bounding_box, zone_mesh.chunk_meshes.values().map(|mesh| {
+				if alpha < 1.0 {
+					DrawItem::TerrainTranslucent {
+						mesh,
+						alpha,
+						sort_key,
+						detail,
+					}
+				} else {
+					DrawItem::TerrainOpaque(mesh, detail)
+				}
+			}));
+		}
+		for shadow in self.blob_shadows.iter() {
+			render_queue.push(DrawItem::BlobShadow(*shadow));
 		}
-		debug_display("Triangle Count", &tri_count);
+		for particle in self.rain.particles().chain(self.break_particles.particles()) {
+			render_queue.push(DrawItem::Particle(particle));
+		}
+
+		debug_display("Triangle Count", &total_stats.triangles);
+		debug_display("Vertex Count", &total_stats.vertices);
+		self.last_triangle_count = total_stats.triangles;
+
+		let hot_zones = heaviest_zones(world_mesh.iter().map(|(zone, zone_mesh)| (*zone, zone_mesh.stats)), HOT_ZONE_COUNT);
+		debug_string("Heaviest Render Zones", format_zone_list(&hot_zones));
 
 		self.renderer.start_render_pass();		
 
-		self.renderer.render(&models);
+		let render_start = Instant::now();
+		let render_stats = self.renderer.render(&render_queue, &self.block_textures);
+		debug_display("Render CPU Time (us)", &render_start.elapsed().as_micros());
+		if let Some(edit_time) = self.uploaded_edit.take() {
+			debug_string("Last Edit Latency (ms)", format!("{:.2}", edit_time.elapsed().as_secs_f64() * 1000.0));
+		}
+		match render_stats.gpu_time {
+			Some(gpu_time) => debug_display("Render GPU Time (us)", &gpu_time.as_micros()),
+			None => debug_string("Render GPU Time (us)", String::from("unsupported")),
+		}
+		self.last_gpu_time = render_stats.gpu_time;
+		debug_display("Draw Calls", &render_stats.draw_calls);
+		debug_display("Visible Chunk Meshes", &render_stats.visible_meshes);
+		debug_string("Render Zones Drawn / Culled", format!("{} / {}", render_stats.visible_groups, render_stats.culled_groups));
+		debug_string("Terrain VRAM", format!(
+			"{:.1} MiB allocated, {:.1} MiB used by meshes",
+			self.renderer.terrain_memory_size() as f64 / (1024.0 * 1024.0),
+			total_stats.bytes as f64 / (1024.0 * 1024.0),
+		));
+
+		let (vertex_pool, index_pool) = self.renderer.terrain_pool_stats();
+		debug_string("Terrain Vertex Pool", format!(
+			"{} pages, {:.1}% used, {:.1}% fragmented",
+			vertex_pool.pages,
+			100.0 * vertex_pool.used_fraction,
+			100.0 * vertex_pool.fragmentation,
+		));
+		debug_string("Terrain Index Pool", format!(
+			"{} pages, {:.1}% used, {:.1}% fragmented",
+			index_pool.pages,
+			100.0 * index_pool.used_fraction,
+			100.0 * index_pool.fragmentation,
+		));
 		self.ui.frame_update(&self.window, &self.renderer);
 
 		self.renderer.finish_render_pass();
@@ -104,9 +764,20 @@ impl Client {
 		self.ui.handle_event(event);
 	}
 
+	// copies the targeted block into the hotbar, only allowed in creative
+	fn pick_block(&mut self) {
+		if self.game_mode == GameMode::Creative {
+			self.interaction.request_pick();
+		}
+	}
+
 	pub fn input(&mut self, event: &WindowEvent) {
 		self.ui.input(event);
-		self.camera_controller.process_event(event);
+		// typing in the console or a text field shouldn't move the player or do anything else in the game
+		if self.update_input_capture() {
+			return;
+		}
+		self.camera_controller.process_event(event, Instant::now());
 
 		if let WindowEvent::KeyboardInput {
 			input: KeyboardInput {
@@ -116,41 +787,556 @@ impl Client {
 			},
 			..
 		} = event {
-			self.destroy_block = true;
+			self.interaction.request_break();
+		}
+
+		match event {
+			// key and button releases aren't sent to an unfocused window, so they would stay held
+			WindowEvent::Focused(false) => {
+				self.camera_controller.reset();
+				self.interaction.stop_breaking();
+			},
+			WindowEvent::CursorLeft { .. } => self.interaction.stop_breaking(),
+			_ => (),
+		}
+
+		if let WindowEvent::MouseInput {
+			state,
+			button,
+			..
+		} = event {
+			match (state, button) {
+				// clicks on ui windows shouldn't also affect the block behind them
+				(ElementState::Pressed, _) if self.ui.wants_pointer_input() => (),
+				(ElementState::Pressed, MouseButton::Left) => self.interaction.start_breaking(),
+				(ElementState::Pressed, MouseButton::Right) => self.interaction.request_place(),
+				(ElementState::Pressed, MouseButton::Middle) => self.pick_block(),
+				(ElementState::Released, MouseButton::Left) => self.interaction.stop_breaking(),
+				_ => (),
+			}
+		}
+
+		if let WindowEvent::KeyboardInput {
+			input: KeyboardInput {
+				state: ElementState::Pressed,
+				virtual_keycode: Some(keycode),
+				..
+			},
+			..
+		} = event {
+			if Some(*keycode) == self.ui.settings().interaction.pick_block_key {
+				self.pick_block();
+			}
+
+			if let Some(slot) = hotbar_slot_of_key(*keycode) {
+				self.interaction.select_hotbar_slot(slot);
+			}
+		}
+
+		if let WindowEvent::KeyboardInput {
+			input: KeyboardInput {
+				state: ElementState::Pressed,
+				virtual_keycode: Some(keycode @ (VirtualKeyCode::F9 | VirtualKeyCode::F10)),
+				..
+			},
+			..
+		} = event {
+			let result = if *keycode == VirtualKeyCode::F9 {
+				self.camera_path.toggle_recording()
+			} else {
+				self.camera_path.toggle_playback()
+			};
+
+			match result {
+				Ok(()) => debug_string("Camera Path Error", String::from("none")),
+				Err(error) => debug_string("Camera Path Error", format!("{:#}", error)),
+			}
+		}
+
+		#[cfg(debug_assertions)]
+		if let WindowEvent::KeyboardInput {
+			input: KeyboardInput {
+				state: ElementState::Pressed,
+				virtual_keycode: Some(VirtualKeyCode::F8),
+				..
+			},
+			..
+		} = event {
+			match self.renderer.reload_shader() {
+				Ok(()) => debug_string("Shader Error", String::from("none")),
+				Err(error) => debug_string("Shader Error", format!("{:#}", error)),
+			}
+		}
+	}
+
+	// escape closes the console instead of leaving the world while it is open
+	pub fn console_open(&self) -> bool {
+		self.ui.console_open()
+	}
+
+	pub fn close_console(&mut self) {
+		self.ui.close_console();
+	}
+
+	fn run_console_command(&mut self, player_block: BlockPos) {
+		let text = match self.ui.take_console_command() {
+			Some(text) => text,
+			None => return,
+		};
+
+		match parse_command(&text) {
+			Ok(Command::BlockStats { radius }) => {
+				let player_chunk = player_block.as_chunk_pos();
+				let area = radius.map(|radius| ChunkBox::new(player_chunk - ChunkPos::splat(radius), player_chunk + ChunkPos::splat(radius + 1)));
+				self.world.analyze_blocks(area);
+				match radius {
+					Some(radius) => self.ui.console_print(format!("counting blocks in loaded chunks within {} chunks", radius)),
+					None => self.ui.console_print(String::from("counting blocks in every loaded chunk")),
+				}
+			},
+			Ok(Command::ExportMap { radius }) => self.start_map_export(player_block, radius),
+			Ok(Command::GameMode(game_mode)) => self.set_game_mode(game_mode),
+			Ok(Command::MeshCheck(validation)) => {
+				self.world.set_mesh_validation(validation);
+				self.ui.console_print(format!("mesh check set to {}, violations are logged", validation.name()));
+			},
+			Ok(Command::Pregen { radius }) => match self.world.start_pregen(radius) {
+				Some(total) => self.ui.console_print(format!("pregenerating {} chunks around spawn, progress is shown in the debug window", total)),
+				None => self.ui.console_print(String::from("chunks are already being pregenerated")),
+			},
+			Ok(Command::Regenerate([x, y, z])) => {
+				let chunk = BlockPos::new(x.resolve(player_block.x), y.resolve(player_block.y), z.resolve(player_block.z)).as_chunk_pos();
+				match self.world.regenerate_chunk(chunk) {
+					Ok(()) => self.ui.console_print(format!("regenerating chunk {:?}", chunk.0)),
+					Err(error) => self.ui.console_print(format!("could not regenerate: {}", error)),
+				}
+			},
+			Ok(Command::Remesh([x, y, z])) => {
+				let chunk = BlockPos::new(x.resolve(player_block.x), y.resolve(player_block.y), z.resolve(player_block.z)).as_chunk_pos();
+				match self.world.remesh_chunk(chunk) {
+					Ok(()) => self.ui.console_print(format!("remeshing chunk {:?}", chunk.0)),
+					Err(error) => self.ui.console_print(format!("could not remesh: {}", error)),
+				}
+			},
+			Ok(Command::SetTime(time_of_day)) => {
+				self.world.set_time_of_day(time_of_day);
+				self.ui.console_print(format!("time set to {}", self.world.world_state().time_of_day()));
+			},
+			Ok(Command::SetWeather(weather)) => {
+				self.world.set_weather(weather);
+				self.ui.console_print(format!("weather set to {}", weather.name()));
+			},
+			Ok(Command::SetBlock { position: [x, y, z], block }) => {
+				let block_pos = BlockPos::new(x.resolve(player_block.x), y.resolve(player_block.y), z.resolve(player_block.z));
+				match self.world.set_block(block_pos, block.new_block()) {
+					Ok(()) => self.ui.console_print(format!("set {:?} to {}", block_pos.0, block.name())),
+					Err(error) => self.ui.console_print(format!("could not set {:?}: {}", block_pos.0, error)),
+				}
+			},
+			Ok(Command::Teleport([x, y, z])) => {
+				let block_pos = BlockPos::new(x.resolve(player_block.x), y.resolve(player_block.y), z.resolve(player_block.z));
+				// the middle of the block, so the player isn't put on its edge
+				let destination = block_pos.as_position() + Position::new(0.5, 0.0, 0.5);
+				match self.world.teleport_player(self.player_id, destination) {
+					Ok(()) => self.ui.console_print(format!("teleporting to {:?}", block_pos.0)),
+					Err(error) => self.ui.console_print(format!("could not teleport: {}", error)),
+				}
+			},
+			Err(error) => self.ui.console_print(format!("{:#}", error)),
+		}
+	}
+
+	fn set_game_mode(&mut self, game_mode: GameMode) {
+		self.game_mode = game_mode;
+		self.camera_controller.set_fly_allowed(game_mode.can_fly());
+		self.ui.console_print(format!("game mode set to {}", game_mode.name()));
+	}
+
+	fn start_map_export(&mut self, center: BlockPos, radius: i32) {
+		if self.map_export.is_some() {
+			self.ui.console_print(String::from("a map is already being exported"));
+			return;
+		}
+
+		match MapExport::start(self.world.new_generator(), center.x, center.z, radius) {
+			Ok(map_export) => {
+				self.ui.console_print(format!("exporting map of radius {} around {}, {}", radius, center.x, center.z));
+				self.map_export = Some(map_export);
+			},
+			Err(error) => self.ui.console_print(format!("could not export map: {:#}", error)),
+		}
+	}
+
+	fn update_map_export(&mut self) {
+		let map_export = match &self.map_export {
+			Some(map_export) => map_export,
+			None => return,
+		};
+
+		let (rows_done, total_rows) = map_export.progress();
+		debug_string("Map Export", format!("{} / {} rows", rows_done, total_rows));
+
+		if map_export.is_finished() {
+			let message = match self.map_export.take().unwrap().finish() {
+				Ok(path) => format!("saved map to {}", path.display()),
+				Err(error) => format!("could not export map: {:#}", error),
+			};
+			debug_string("Map Export", message.clone());
+			self.ui.console_print(message);
+		}
+	}
+
+	// shows how many chunks around the player are loaded, returns true once all of them are
+	pub fn update_loading(&mut self, world_name: &str) -> bool {
+		let (loaded_chunks, total_chunks) = self.world.player_load_progress(self.player_id).unwrap_or((0, 0));
+		let finished = loaded_chunks >= total_chunks;
+
+		self.ui.set_loading_progress((!finished).then(|| LoadingProgress {
+			world_name: world_name.to_owned(),
+			loaded_chunks,
+			total_chunks,
+		}));
+		finished
+	}
+
+	// true once the benchmark camera path has finished playing
+	pub fn benchmark_finished(&self) -> bool {
+		self.benchmark.is_some() && !self.camera_path.is_playing()
+	}
+
+	// writes out the benchmark results, may be called more than once
+	pub fn exit(&mut self) {
+		if let Some(benchmark) = self.benchmark.take() {
+			if let Err(error) = benchmark.write() {
+				error!("could not write benchmark results: {:#}", error);
+			}
 		}
 	}
 
-	pub fn frame_update(&mut self, new_window_size: Option<PhysicalSize<u32>>) {
-		if let Some(new_window_size) = new_window_size {
-			self.renderer.resize(new_window_size);
+	// frees everything used for the world, and gives back what is needed to show the main menu
+	pub fn close(mut self) -> (Window, Renderer, MineConeUi) {
+		self.exit();
+
+		self.save_player_state();
+		if let Err(error) = self.world.disconnect(self.player_id) {
+			error!("could not save player: {:#}", error);
 		}
-		self.render();
+
+		for (_, mut zone_mesh) in self.world_mesh.take() {
+			for (_, mesh) in zone_mesh.chunk_meshes.drain() {
+				self.renderer.free_terrain_mesh(mesh);
+			}
+		}
+
+		(self.window, self.renderer, self.ui)
+	}
+
+	// text fields are focused while the ui is drawn, so this is also checked each physics update,
+	// otherwise a key held when one is clicked would keep moving the player until the next window event
+	// returns true if the ui is taking input
+	fn update_input_capture(&mut self) -> bool {
+		let captured = self.ui.wants_input();
+		self.camera_controller.set_input_captured(captured);
+		if captured {
+			self.interaction.stop_breaking();
+		}
+		captured
+	}
+
+	// should be called every tick, regardless of which window events occured
+	pub fn gamepad_update(&mut self) {
+		let (analog_input, actions) = self.gamepad.poll();
+		self.camera_controller.set_analog_input(analog_input);
+
+		if actions.break_block {
+			self.interaction.request_break();
+		}
+		if actions.place_block {
+			self.interaction.request_place();
+		}
+	}
+
+	pub fn resize(&mut self, new_window_size: PhysicalSize<u32>) {
+		self.renderer.resize(new_window_size);
+	}
+
+	pub fn request_redraw(&self) {
+		self.window.request_redraw();
 	}
 
-	pub fn physics_update(&mut self, delta: Duration) {
+	// delta is the time since the last frame
+	pub fn frame_update(&mut self, delta: Duration) {
+		self.render(delta);
+	}
+
+	// tick_alpha is the fraction of the way to the next world tick, entities are drawn that far between their last 2 positions
+	pub fn physics_update(&mut self, delta: Duration, tick_alpha: f32) {
+		let settings = *self.ui.settings();
+		self.update_input_capture();
+		if let Some(texture_pack) = self.ui.take_texture_pack_change() {
+			self.set_texture_pack(texture_pack);
+		}
+		if let Some(ui_scale) = self.ui.take_ui_scale_change() {
+			self.config.set(UI_SCALE_KEY, Some(&ui_scale.to_string()));
+			if let Err(error) = self.config.save() {
+				error!("could not save config: {:#}", error);
+			}
+		}
+		self.camera_controller.set_sprint_settings(settings.sprint);
+		self.renderer.set_ao_strength(settings.graphics.ao_strength);
+		self.renderer.set_face_shading(settings.graphics.face_shading);
+		self.renderer.set_shadows(settings.graphics.sun_shadows, settings.graphics.shadow_resolution);
+		self.renderer.set_shadow_bias(self.ui.shadow_bias());
+		self.renderer.set_water_reflections(settings.graphics.water_reflections);
+		self.renderer.set_vsync(settings.graphics.vsync);
+		self.world.set_smooth_lighting(settings.graphics.smooth_lighting);
+		self.update_teleport();
+
 		let camera = self.renderer.get_camera_mut();
-		self.camera_controller.update_camera(camera, delta);
+		let old_camera_position = camera.position;
+		let world = &self.world;
+		match self.camera_path.next_keyframe() {
+			// playback drives the camera directly, the rest of the update runs the same as in live play
+			Some(keyframe) => keyframe.apply(camera),
+			None => self.camera_controller.update_camera(
+				camera,
+				delta,
+				|block| world.is_solid_at(block),
+				|block| world.liquid_height_at(block),
+			),
+		}
+		self.camera_path.record(camera);
+		self.camera_target = CameraTarget::from_camera(camera);
+		let walked = if self.camera_controller.is_flying() {
+			0.0
+		} else {
+			let moved = camera.position - old_camera_position;
+			moved.x.hypot(moved.z)
+		};
+		self.held_block.walk(walked);
+		debug_string("Camera Path", self.camera_path.status());
+
+		if let Some(impact_speed) = self.camera_controller.take_landing_speed() {
+			if let Some(outcome) = self.world.player_landed(self.player_id, impact_speed) {
+				self.show_damage(outcome);
+			}
+		}
+
+		let camera = self.renderer.camera();
 		let camera_position = camera.get_position();
+		let camera_forward = camera.forward();
+		let camera_right = camera.sideways();
+		self.measure_speed(camera_position.0);
 
-		if self.destroy_block {
-			if let Some(block) = self.world.block_raycast(camera_position, camera.forward(), 15.0) {
-				self.world.set_block(block, Air::new().into());
-				self.world.mesh_update_adjacent(block, &mut self.updated_render_zones);
+		let reach = settings.interaction.reach(self.camera_controller.is_flying());
+		self.interaction.update(&self.world, camera_position, camera_forward, reach, self.game_mode, delta);
+		match self.interaction.target() {
+			Some(target) => debug_display("Target Block", &format!("{:?} {:?} face", *target.block, target.face)),
+			None => debug_string("Target Block", String::from("none")),
+		}
+		if self.interaction.take_swing() {
+			self.held_block.swing();
+		}
+		self.sounds.set_listener(camera_position.0, camera_right);
+		self.interaction_events.extend(self.interaction.take_events());
+		for event in self.interaction_events.drain(..) {
+			if let Some(sound) = event.sound() {
+				self.sounds.emit_at(&self.world, sound, event.block().as_position() + Position::splat(0.5));
 			}
+			self.break_particles.handle_event(event);
+		}
+		self.break_particles.update(delta);
+		self.sounds.update(Instant::now());
+		debug_display("Sound Voices", &self.sounds.voices().len());
+		self.update_held_block();
+		if let Some(block) = self.interaction.take_opened_container() {
+			self.open_container = Some(block);
+			self.ui.open_container();
+		}
+		self.update_container(camera_position, reach);
+		self.update_inventory();
+		let hotbar = self.interaction.hotbar();
+		let selected_block = if self.game_mode.infinite_blocks() {
+			format!("{:?} (slot {})", hotbar.selected_block(), hotbar.selected_slot() + 1)
+		} else {
+			format!("{:?} x{} (slot {})", hotbar.selected_block(), hotbar.selected_count(), hotbar.selected_slot() + 1)
+		};
+		debug_string("Selected Block", selected_block);
+		debug_display("Game Mode", &self.game_mode.name());
+		match self.interaction.break_progress(&self.world) {
+			Some(progress) => debug_string("Break Progress", format!("{:.0}%", 100.0 * progress)),
+			None => debug_string("Break Progress", String::from("none")),
+		}
+
+		if let Err(error) = self.world.update_player_position(self.player_id, camera_position) {
+			debug_string("Player Position Error", error.to_string());
+		}
+		self.save_player_state();
+
+		self.run_console_command(camera_position.as_block_pos());
+		self.update_map_export();
+
+		parallel::set_activity_tracking(self.ui.task_activity_open());
+		if self.ui.minimap_open() {
+			let center = camera_position.as_chunk_pos();
+			let activity = if self.ui.task_activity_open() {
+				parallel::chunk_activity().into_iter()
+					.filter(|(chunk, _)| (chunk.x - center.x).abs() <= MINIMAP_RADIUS && (chunk.z - center.z).abs() <= MINIMAP_RADIUS)
+					.map(|(chunk, activity)| (IVec2::new(chunk.x, chunk.z), activity))
+					.collect()
+			} else {
+				Vec::new()
+			};
+
+			self.ui.set_minimap_data(MinimapData {
+				center,
+				radius: MINIMAP_RADIUS,
+				facing: camera_forward,
+				chunks: self.world.chunk_states_around(center, MINIMAP_RADIUS),
+				activity,
+			});
+		}
+
+		if self.ui.biome_overlay_open() {
+			self.update_biome_overlay(camera_position);
+		}
+
+		self.blob_shadows.clear();
+		if settings.graphics.blob_shadows {
+			self.update_blob_shadows(camera_position, tick_alpha);
+		}
 
-			self.destroy_block = false;
+		if self.ui.hud_open() {
+			self.update_world_labels(camera_position, tick_alpha);
 		}
 
-		self.world.set_player_position(self.player_id, camera_position);
+		let camera_block = camera_position.as_block_pos();
+		if self.ui.hud_open() {
+			self.ui.set_hud_data(HudData {
+				block: camera_block,
+				facing: camera_forward,
+				biome: self.world.surface_biome_at(camera_block).name.clone(),
+			});
+		}
+		// there is no health bar in creative
+		let health = if self.game_mode.takes_damage() {
+			self.world.player_health(self.player_id)
+		} else {
+			None
+		};
+		self.ui.set_health(health);
+
+		// time and weather come from the world, which advances them each tick
+		let world_state = self.world.world_state();
+		self.renderer.set_sky_rotation(sky_rotation(&world_state));
+		debug_string("World Time", format!("{} ({:?})", world_state.time_of_day(), world_state.weather));
+		let world = &self.world;
+		self.rain.update(delta, camera_position.0, world_state.weather == Weather::Rain, |x, z| world.surface_height(x, z));
+
+		let reflection_plane = settings.graphics.water_reflections.then(|| water_surface_below(
+			camera_position,
+			|block| world.is_solid_at(block),
+			|block| world.liquid_height_at(block),
+		)).flatten();
+		self.renderer.set_reflection_plane(reflection_plane);
+
+		let target_environment = if self.world.is_liquid_at(camera_block) {
+			Environment::underwater()
+		} else {
+			Environment::surface(self.world.surface_biome_at(camera_block).fog_color).with_rain(self.rain.intensity())
+		};
+		self.environment.transition_to(target_environment, delta);
+		self.renderer.set_fog(self.environment.fog);
+		self.ui.set_screen_tint(self.environment.screen_tint);
+
+		// chunks closest to the camera are uploaded first, the rest stay queued for later physics updates
+		self.world.poll_completed_tasks(&mut self.updated_chunks);
+		if let Some((block, edit_time)) = self.world.take_pending_edit() {
+			self.pending_edit.get_or_insert((block.as_chunk_pos(), edit_time));
+		}
+		for (chunk, repair) in self.world.take_finished_repairs() {
+			let repaired = match repair {
+				ChunkRepair::Remesh => "remeshed",
+				ChunkRepair::Regenerate => "regenerated",
+			};
+			self.ui.console_print(format!("{} chunk {:?}", repaired, chunk.0));
+		}
+		for stats in self.world.take_finished_analyses() {
+			match stats {
+				Some(stats) => for line in stats.table() {
+					self.ui.console_print(line);
+				},
+				None => self.ui.console_print(String::from("could not count blocks, the task failed")),
+			}
+		}
+		let upload_budget = self.ui.settings().graphics.mesh_uploads_per_update;
+		let updated_chunks = self.updated_chunks.take_nearest(camera_block.as_chunk_pos(), upload_budget);
+		let mesh_update_start = Instant::now();
+		for chunk in updated_chunks.iter() {
+			self.generate_mesh(*chunk);
+		}
+		if !updated_chunks.is_empty() {
+			debug_display("Last Mesh Update (us)", &mesh_update_start.elapsed().as_micros());
+			debug_display("Last Mesh Update Chunks", &updated_chunks.len());
+		}
+		debug_display("Queued Mesh Uploads", &self.updated_chunks.len());
+		// the edit is dropped without a measurement if its chunk wasn't remeshed
+		if let Some((chunk, edit_time)) = self.pending_edit {
+			if updated_chunks.contains(&chunk) {
+				self.uploaded_edit = Some(edit_time);
+				self.pending_edit = None;
+			} else if self.updated_chunks.is_empty() {
+				self.pending_edit = None;
+			}
+		}
 
-		self.world.poll_completed_tasks(&mut self.updated_render_zones);
-		for render_zone in self.updated_render_zones.iter() {
-			self.generate_mesh(*render_zone);
+		let chunk_load_latencies = self.world.take_chunk_load_latencies();
+		if let Some(latency) = chunk_load_latencies.iter().max() {
+			debug_display("Last Chunk Load Latency (ms)", &latency.as_millis());
+		}
+		if let Some(benchmark) = &mut self.benchmark {
+			if self.camera_path.is_playing() {
+				benchmark.add_sample(self.last_frame_time, self.last_triangle_count, self.last_gpu_time, &chunk_load_latencies, self.task_latency);
+			}
 		}
-		self.updated_render_zones.clear();
 
 		debug_display("Physics Updates per Second", &((1.0 / delta.as_secs_f64()) as i64));
+		self.display_task_problems();
+
+		if self.last_latency_report.elapsed() >= LATENCY_REPORT_INTERVAL {
+			self.report_task_latency();
+			self.last_latency_report = Instant::now();
+		}
+		if self.last_memory_report.elapsed() >= MEMORY_REPORT_INTERVAL {
+			self.report_memory();
+			self.last_memory_report = Instant::now();
+		}
+
+		debug_string("Mesh Violations", format!("{} ({} check)", self.world.mesh_violation_count(), self.world.mesh_validation().name()));
+		let raycast_count = self.world.raycast_count();
+		debug_display("Raycasts per Physics Update", &(raycast_count - self.last_raycast_count));
+		self.last_raycast_count = raycast_count;
+
+		let entity_count = self.world.entity_count();
+		debug_string("Entities", format!(
+			"{} active, {} frozen, {} pending save",
+			entity_count.active,
+			entity_count.frozen,
+			entity_count.pending_save,
+		));
+	}
+}
+
+fn format_bytes(bytes: u64) -> String {
+	format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
 
-		self.render();
+// zones with any part closer than full_detail_distance to the camera are drawn in full detail
+fn zone_detail_level(zone_aabb: Aabb, camera_position: Vec3, full_detail_distance: f32) -> DetailLevel {
+	let closest_point = camera_position.clamp(zone_aabb.position, zone_aabb.end());
+	if closest_point.distance_squared(camera_position) <= full_detail_distance * full_detail_distance {
+		DetailLevel::Full
+	} else {
+		DetailLevel::Reduced
 	}
 }
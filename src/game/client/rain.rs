@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use glam::{Vec3, Vec4};
+
+use crate::render::particle::Particle;
+use crate::game::worldgen::DeterministicRng;
+
+// drops spawn in a cylinder around the camera with this radius, between these heights above the camera
+const SPAWN_RADIUS: f32 = 24.0;
+const SPAWN_MIN_HEIGHT: f32 = 4.0;
+const SPAWN_MAX_HEIGHT: f32 = 20.0;
+// drops this far from the camera horizontally are removed, so moving quickly doesn't leave rain behind
+const DESPAWN_RADIUS: f32 = 1.5 * SPAWN_RADIUS;
+// number of drops at full intensity
+const MAX_DROPS: usize = 2000;
+// spawning is spread over a few updates, so rain starts as a drizzle instead of all at once
+const MAX_SPAWNS_PER_UPDATE: usize = 200;
+// blocks per second
+const FALL_SPEED: f32 = 20.0;
+const STREAK_WIDTH: f32 = 0.03;
+const STREAK_LENGTH: f32 = 0.6;
+const STREAK_COLOR: Vec4 = Vec4::new(0.7, 0.75, 0.85, 0.5);
+// time to go from clear to full rain, or back
+const FADE_TIME: Duration = Duration::from_secs(3);
+// rain doesn't have to be deterministic, this just keeps its random numbers from matching a worldgen feature's
+const RAIN_SALT: u64 = 0x7261_696e;
+
+#[derive(Debug, Clone, Copy)]
+struct RainDrop {
+	position: Vec3,
+	// top of the ground the drop falls to
+	ground_height: f32,
+}
+
+// streaks of rain falling around the camera, only on columns where the camera is above the surface
+#[derive(Debug)]
+pub struct Rain {
+	drops: Vec<RainDrop>,
+	// 0 when clear, up to 1 at full rain
+	intensity: f32,
+	rng: DeterministicRng,
+}
+
+impl Rain {
+	pub fn new() -> Self {
+		Rain {
+			drops: Vec::new(),
+			intensity: 0.0,
+			rng: DeterministicRng::for_column(0, RAIN_SALT, 0, 0),
+		}
+	}
+
+	pub fn intensity(&self) -> f32 {
+		self.intensity
+	}
+
+	// surface_height is the y coordinate of the top solid block in a column
+	pub fn update<F: Fn(i32, i32) -> i32>(&mut self, delta: Duration, camera_position: Vec3, raining: bool, surface_height: F) {
+		let fade_step = delta.as_secs_f32() / FADE_TIME.as_secs_f32();
+		self.intensity = if raining {
+			(self.intensity + fade_step).min(1.0)
+		} else {
+			(self.intensity - fade_step).max(0.0)
+		};
+
+		let fall = FALL_SPEED * delta.as_secs_f32();
+		self.drops.retain_mut(|drop| {
+			drop.position.y -= fall;
+			let horizontal_distance = (drop.position.x - camera_position.x).hypot(drop.position.z - camera_position.z);
+			drop.position.y > drop.ground_height && horizontal_distance <= DESPAWN_RADIUS
+		});
+
+		let target_drops = (MAX_DROPS as f32 * self.intensity) as usize;
+		let spawn_attempts = target_drops.saturating_sub(self.drops.len()).min(MAX_SPAWNS_PER_UPDATE);
+		for _ in 0..spawn_attempts {
+			// uniform over the area of the circle
+			let distance = SPAWN_RADIUS * (self.rng.next_f64() as f32).sqrt();
+			let angle = std::f32::consts::TAU * self.rng.next_f64() as f32;
+			let height = SPAWN_MIN_HEIGHT + (SPAWN_MAX_HEIGHT - SPAWN_MIN_HEIGHT) * self.rng.next_f64() as f32;
+			let position = camera_position + Vec3::new(distance * angle.cos(), height, distance * angle.sin());
+
+			// no rain falls in columns where the spawn point is underground
+			let ground_height = surface_height(position.x.floor() as i32, position.z.floor() as i32) as f32 + 1.0;
+			if position.y > ground_height {
+				self.drops.push(RainDrop {
+					position,
+					ground_height,
+				});
+			}
+		}
+	}
+
+	// streaks are fainter while the rain fades in and out
+	pub fn particles(&self) -> impl Iterator<Item = Particle> + '_ {
+		let color = STREAK_COLOR * Vec4::new(1.0, 1.0, 1.0, self.intensity);
+		self.drops.iter().map(move |drop| Particle::new(drop.position, STREAK_WIDTH, STREAK_LENGTH, color))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const UPDATE_TIME: Duration = Duration::from_millis(50);
+
+	#[test]
+	fn rain_fades_in_and_out() {
+		let mut rain = Rain::new();
+		rain.update(UPDATE_TIME, Vec3::ZERO, true, |_, _| -10);
+		assert!(rain.intensity() > 0.0 && rain.intensity() < 0.1);
+		assert!(rain.particles().count() > 0);
+
+		for _ in 0..(FADE_TIME.as_millis() / UPDATE_TIME.as_millis()) {
+			rain.update(UPDATE_TIME, Vec3::ZERO, true, |_, _| -10);
+		}
+		assert_eq!(rain.intensity(), 1.0);
+
+		for _ in 0..(FADE_TIME.as_millis() / UPDATE_TIME.as_millis()) + 1 {
+			rain.update(UPDATE_TIME, Vec3::ZERO, false, |_, _| -10);
+		}
+		assert_eq!(rain.intensity(), 0.0);
+	}
+
+	#[test]
+	fn rain_stops_at_the_surface() {
+		let mut rain = Rain::new();
+		// the camera is underground, every spawn point is below the surface
+		for _ in 0..20 {
+			rain.update(UPDATE_TIME, Vec3::ZERO, true, |_, _| 100);
+		}
+		assert_eq!(rain.particles().count(), 0);
+
+		// the ground is a bit below the camera
+		for _ in 0..20 {
+			rain.update(UPDATE_TIME, Vec3::ZERO, true, |_, _| -3);
+		}
+		assert!(rain.particles().count() > 0);
+		assert!(rain.drops.iter().all(|drop| drop.position.y > -2.0));
+	}
+}
@@ -0,0 +1,211 @@
+use std::time::{Duration, Instant};
+
+use glam::Vec3;
+
+use crate::prelude::*;
+use crate::game::block::BlockType;
+use crate::game::world::World;
+
+// sounds closer than this to the listener are at full volume
+const REFERENCE_DISTANCE: f32 = 2.0;
+// sounds this far from the listener can't be heard, and aren't started
+const MAX_SOUND_DISTANCE: f32 = 48.0;
+// volume multiplier of sounds with blocks between them and the listener, they should also be low pass filtered
+const OCCLUDED_GAIN: f32 = 0.4;
+// once this many sounds are playing, the furthest one is stopped for a closer one
+const MAX_VOICES: usize = 16;
+// there are no sound files to get the length from yet, so every voice lasts this long
+const SOUND_LENGTH: Duration = Duration::from_millis(400);
+// volume of a block cracking while it is broken, which happens a few times for each block
+const CRACK_VOLUME: f32 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+	BlockBreak(BlockType),
+	// the block cracked while being broken
+	Crack(BlockType),
+	BlockPlace(BlockType),
+}
+
+impl Sound {
+	// volume from 0 to 1 of the sound at the reference distance
+	pub fn volume(&self) -> f32 {
+		match self {
+			Sound::Crack(_) => CRACK_VOLUME,
+			Sound::BlockBreak(_) | Sound::BlockPlace(_) => 1.0,
+		}
+	}
+}
+
+// volume from 0 to 1 of a sound the distance away from the listener
+// it falls off with the inverse of the distance, and fades out completely at the max distance
+pub fn attenuation(distance: f32) -> f32 {
+	let falloff = REFERENCE_DISTANCE / distance.max(REFERENCE_DISTANCE);
+	let fade = (1.0 - distance / MAX_SOUND_DISTANCE).clamp(0.0, 1.0);
+	falloff * fade
+}
+
+// -1 is fully in the left ear, 1 is fully in the right ear, and 0 is in front, behind, or at the listener
+// right is the direction to the listener's right, it must be normalized
+pub fn pan(listener_position: Vec3, right: Vec3, sound_position: Vec3) -> f32 {
+	let direction = (sound_position - listener_position).normalize_or_zero();
+	direction.dot(right).clamp(-1.0, 1.0)
+}
+
+// a sound that is playing
+#[derive(Debug, Clone, Copy)]
+pub struct Voice {
+	pub sound: Sound,
+	pub position: Vec3,
+	// there were blocks between the sound and the listener when it started
+	pub occluded: bool,
+	// these are updated each frame for where the listener is
+	pub gain: f32,
+	pub pan: f32,
+	started: Instant,
+}
+
+impl Voice {
+	fn spatialize(&mut self, listener_position: Vec3, listener_right: Vec3) {
+		let occlusion = if self.occluded { OCCLUDED_GAIN } else { 1.0 };
+		self.gain = self.sound.volume() * attenuation(self.position.distance(listener_position)) * occlusion;
+		self.pan = pan(listener_position, listener_right, self.position);
+	}
+}
+
+// keeps track of positional sounds around the listener, which is the camera
+// nothing is played yet, the voices are what an audio backend would mix
+pub struct SoundManager {
+	listener_position: Vec3,
+	listener_right: Vec3,
+	voices: Vec<Voice>,
+}
+
+impl SoundManager {
+	pub fn new() -> Self {
+		SoundManager {
+			listener_position: Vec3::ZERO,
+			listener_right: Vec3::X,
+			voices: Vec::new(),
+		}
+	}
+
+	pub fn voices(&self) -> &[Voice] {
+		&self.voices
+	}
+
+	// called each frame with the camera's position and sideways direction
+	pub fn set_listener(&mut self, position: Vec3, right: Vec3) {
+		self.listener_position = position;
+		self.listener_right = right.normalize_or_zero();
+	}
+
+	fn listener_distance(&self, position: Vec3) -> f32 {
+		position.distance(self.listener_position)
+	}
+
+	// starts the sound at the position, unless it is too far away to hear
+	// it is occluded if a ray from the listener hits any block before the one the sound is in
+	pub fn emit_at(&mut self, world: &World, sound: Sound, position: Position) {
+		let distance = self.listener_distance(position.0);
+		if distance >= MAX_SOUND_DISTANCE {
+			return;
+		}
+
+		let occluded = distance > 0.0 && world.block_raycast(Position(self.listener_position), position.0 - self.listener_position, distance)
+			.is_some_and(|hit| hit.block != position.as_block_pos());
+		self.add_voice(sound, position.0, occluded, Instant::now());
+	}
+
+	fn add_voice(&mut self, sound: Sound, position: Vec3, occluded: bool, now: Instant) {
+		if self.voices.len() >= MAX_VOICES {
+			let furthest = self.voices.iter()
+				.enumerate()
+				.map(|(index, voice)| (index, self.listener_distance(voice.position)))
+				.max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+			match furthest {
+				Some((index, furthest_distance)) if furthest_distance > self.listener_distance(position) => {
+					self.voices.swap_remove(index);
+				},
+				_ => return,
+			}
+		}
+
+		let mut voice = Voice {
+			sound,
+			position,
+			occluded,
+			gain: 0.0,
+			pan: 0.0,
+			started: now,
+		};
+		voice.spatialize(self.listener_position, self.listener_right);
+		self.voices.push(voice);
+	}
+
+	// drops finished voices, and updates the rest for where the listener is now
+	pub fn update(&mut self, now: Instant) {
+		self.voices.retain(|voice| now.saturating_duration_since(voice.started) < SOUND_LENGTH);
+
+		for voice in self.voices.iter_mut() {
+			voice.spatialize(self.listener_position, self.listener_right);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sounds_get_quieter_with_distance() {
+		assert_eq!(attenuation(0.0), 1.0);
+		assert!((attenuation(1.0) - 1.0).abs() < 0.05);
+		assert!(attenuation(4.0) < attenuation(2.0));
+		assert!(attenuation(16.0) < attenuation(8.0));
+		assert_eq!(attenuation(MAX_SOUND_DISTANCE), 0.0);
+		assert_eq!(attenuation(2.0 * MAX_SOUND_DISTANCE), 0.0);
+	}
+
+	#[test]
+	fn sounds_are_panned_to_the_side_they_are_on() {
+		let listener = Vec3::new(10.0, 5.0, 10.0);
+		let right = Vec3::X;
+		assert_eq!(pan(listener, right, listener + Vec3::new(3.0, 0.0, 0.0)), 1.0);
+		assert_eq!(pan(listener, right, listener - Vec3::new(3.0, 0.0, 0.0)), -1.0);
+		assert_eq!(pan(listener, right, listener + Vec3::new(0.0, 0.0, 4.0)), 0.0);
+		assert_eq!(pan(listener, right, listener), 0.0);
+
+		let diagonal = pan(listener, right, listener + Vec3::new(1.0, 0.0, 1.0));
+		assert!(diagonal > 0.0 && diagonal < 1.0);
+	}
+
+	#[test]
+	fn furthest_voices_are_dropped_first() {
+		let now = Instant::now();
+		let sound = Sound::BlockBreak(BlockType::Stone);
+		let mut sounds = SoundManager::new();
+
+		for i in 0..MAX_VOICES {
+			sounds.add_voice(sound, Vec3::new(i as f32 + 1.0, 0.0, 0.0), false, now);
+		}
+		// further than every playing voice, so it isn't started
+		sounds.add_voice(sound, Vec3::new(40.0, 0.0, 0.0), false, now);
+		assert!(sounds.voices().iter().all(|voice| voice.position.x < 40.0));
+
+		// closer than the furthest, which is stopped for it
+		sounds.add_voice(sound, Vec3::new(0.5, 0.0, 0.0), true, now);
+		assert_eq!(sounds.voices().len(), MAX_VOICES);
+		assert!(sounds.voices().iter().all(|voice| voice.position.x < MAX_VOICES as f32));
+		let occluded = sounds.voices().iter().find(|voice| voice.occluded).unwrap();
+		assert_eq!(occluded.gain, attenuation(0.5) * OCCLUDED_GAIN);
+
+		// moving the listener updates the voices, and finished voices are dropped
+		sounds.set_listener(Vec3::new(40.0, 0.0, 0.0), Vec3::Z);
+		sounds.update(now);
+		assert!(sounds.voices().iter().all(|voice| voice.gain < 0.1 && voice.pan == 0.0));
+		sounds.update(now + SOUND_LENGTH);
+		assert!(sounds.voices().is_empty());
+	}
+}
@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+// all speeds are in blocks / second, and accelerations in blocks / second^2
+const GRAVITY: f32 = 28.0;
+const TERMINAL_SPEED: f32 = 60.0;
+const JUMP_SPEED: f32 = 9.0;
+
+// gravity is reduced in water, and buoyancy pushes up in proportion to how much of the player is underwater
+// fully submerged players still sink slowly, since gravity is a bit stronger than buoyancy
+const WATER_GRAVITY: f32 = 6.0;
+const WATER_BUOYANCY: f32 = 4.5;
+// fraction of velocity lost each second in water
+const WATER_DRAG: f32 = 3.0;
+// swimming up is also proportional to how much of the player is underwater,
+// so swimming at the surface settles where it balances gravity instead of leaving the water
+const SWIM_ACCELERATION: f32 = 16.0;
+// upward speed when swimming into a ledge, enough to get on top of a 1 block ledge
+const WATER_EXIT_SPEED: f32 = 7.0;
+
+// horizontal movement is multiplied by this while in water
+pub const WATER_SPEED_MULTIPLIER: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VerticalInput {
+	// standing on a solid block, liquids are never ground
+	pub grounded: bool,
+	// fraction of the player's height which is underwater, from 0 to 1
+	pub submersion: f32,
+	// jumping on land, swimming up in water
+	pub jump: bool,
+	// the player is in water and moving into a block they could climb out onto
+	pub against_ledge: bool,
+}
+
+// the player's vertical velocity while not flying
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VerticalMotion {
+	// positive is up
+	velocity: f32,
+	// speed the player last hit the ground at, until it is taken
+	landing_speed: Option<f32>,
+}
+
+impl VerticalMotion {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	#[cfg(test)]
+	pub fn velocity(&self) -> f32 {
+		self.velocity
+	}
+
+	// how fast the player was falling when they last landed on the ground, if they landed since the last call
+	// landing in water doesn't count, since the water breaks the fall
+	pub fn take_landing_speed(&mut self) -> Option<f32> {
+		self.landing_speed.take()
+	}
+
+	// updates the velocity, then returns how far the player moves up in this time
+	pub fn update(&mut self, input: VerticalInput, time_delta: Duration) -> f32 {
+		let delta = time_delta.as_secs_f32();
+
+		if input.submersion > 0.0 {
+			let mut acceleration = -WATER_GRAVITY + WATER_BUOYANCY * input.submersion;
+			if input.jump {
+				acceleration += SWIM_ACCELERATION * input.submersion;
+			}
+
+			self.velocity += acceleration * delta;
+			self.velocity *= (-WATER_DRAG * delta).exp();
+
+			if input.jump && input.against_ledge {
+				self.velocity = self.velocity.max(WATER_EXIT_SPEED);
+			}
+		} else if input.grounded {
+			if self.velocity < 0.0 {
+				self.landing_speed = Some(-self.velocity);
+			}
+
+			self.velocity = if input.jump {
+				JUMP_SPEED
+			} else {
+				0.0
+			};
+		} else {
+			self.velocity = (self.velocity - GRAVITY * delta).max(-TERMINAL_SPEED);
+		}
+
+		// the ground under water stops sinking too
+		if input.grounded && self.velocity < 0.0 {
+			self.velocity = 0.0;
+		}
+
+		self.velocity * delta
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const FRAME: Duration = Duration::from_micros(16_667);
+	const PLAYER_HEIGHT: f32 = 1.8;
+
+	// water fills everything below y = 0, the player's feet are at y
+	fn submersion(y: f32) -> f32 {
+		(-y / PLAYER_HEIGHT).clamp(0.0, 1.0)
+	}
+
+	// runs frames for the given number of seconds, returning the feet height after each frame
+	fn simulate(motion: &mut VerticalMotion, mut y: f32, seconds: f32, jump: bool) -> Vec<f32> {
+		let frames = (seconds / FRAME.as_secs_f32()) as usize;
+		(0..frames).map(|_| {
+			y += motion.update(VerticalInput {
+				grounded: false,
+				submersion: submersion(y),
+				jump,
+				against_ledge: false,
+			}, FRAME);
+			y
+		}).collect()
+	}
+
+	#[test]
+	fn falls_with_gravity_in_air() {
+		let mut motion = VerticalMotion::new();
+		let heights = simulate(&mut motion, 100.0, 1.0, false);
+
+		// about 1/2 * g * t^2
+		let fallen = 100.0 - heights.last().unwrap();
+		assert!((fallen - 0.5 * GRAVITY).abs() < 0.5, "fell {}", fallen);
+		assert!((motion.velocity() + GRAVITY).abs() < 0.5);
+	}
+
+	#[test]
+	fn sinks_slowly_in_water() {
+		let mut motion = VerticalMotion::new();
+		simulate(&mut motion, -10.0, 5.0, false);
+
+		// drag limits the sinking speed to a small fraction of falling through air
+		let sink_speed = -motion.velocity();
+		assert!(sink_speed > 0.0 && sink_speed < 1.0, "sink speed {}", sink_speed);
+	}
+
+	#[test]
+	fn entering_water_slows_fall() {
+		let mut motion = VerticalMotion::new();
+		let heights = simulate(&mut motion, 5.0, 3.0, false);
+
+		// the player hits the water at about 18 blocks / second, drag stops them before they go very deep
+		assert!(heights.last().unwrap() > &-15.0);
+		assert!(motion.velocity() > -1.0);
+	}
+
+	#[test]
+	fn swimming_up_bobs_at_surface() {
+		let mut motion = VerticalMotion::new();
+		let heights = simulate(&mut motion, -5.0, 10.0, true);
+
+		// rises to the surface, then stays partly submerged instead of leaving the water
+		let settled = &heights[heights.len() / 2..];
+		for y in settled {
+			assert!(submersion(*y) > 0.0 && submersion(*y) < 0.5, "feet at {}", y);
+		}
+
+		let min = settled.iter().cloned().fold(f32::INFINITY, f32::min);
+		let max = settled.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+		assert!(max - min < 0.2, "bobbing between {} and {}", min, max);
+	}
+
+	#[test]
+	fn swimming_into_ledge_boosts_out() {
+		let mut motion = VerticalMotion::new();
+		motion.update(VerticalInput {
+			grounded: false,
+			submersion: 0.3,
+			jump: true,
+			against_ledge: true,
+		}, FRAME);
+		assert_eq!(motion.velocity(), WATER_EXIT_SPEED);
+
+		// without jumping there is no boost
+		let mut motion = VerticalMotion::new();
+		motion.update(VerticalInput {
+			grounded: false,
+			submersion: 0.3,
+			jump: false,
+			against_ledge: true,
+		}, FRAME);
+		assert!(motion.velocity() < 0.0);
+	}
+
+	#[test]
+	fn jumps_only_when_grounded() {
+		let mut motion = VerticalMotion::new();
+		let input = VerticalInput {
+			grounded: true,
+			submersion: 0.0,
+			jump: true,
+			against_ledge: false,
+		};
+		motion.update(input, FRAME);
+		assert_eq!(motion.velocity(), JUMP_SPEED);
+
+		// pressing jump in the air does nothing
+		motion.update(VerticalInput { grounded: false, ..input }, FRAME);
+		assert!(motion.velocity() < JUMP_SPEED);
+	}
+
+	#[test]
+	fn landing_speed_is_falling_speed() {
+		let mut motion = VerticalMotion::new();
+		simulate(&mut motion, 100.0, 1.0, false);
+		let falling_speed = -motion.velocity();
+		assert_eq!(motion.take_landing_speed(), None);
+
+		let ground = VerticalInput {
+			grounded: true,
+			..VerticalInput::default()
+		};
+		motion.update(ground, FRAME);
+		assert_eq!(motion.take_landing_speed(), Some(falling_speed));
+		// standing on the ground isn't landing again
+		motion.update(ground, FRAME);
+		assert_eq!(motion.take_landing_speed(), None);
+	}
+}
@@ -0,0 +1,99 @@
+use std::ops::{AddAssign, SubAssign};
+
+use crate::prelude::*;
+use crate::render::terrain_buffer::TerrainMesh;
+
+// size of the meshes in a render zone, kept up to date as its chunk meshes are rebuilt
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MeshStats {
+	pub triangles: u64,
+	pub vertices: u64,
+	// size of the mesh's vertex and index data on the gpu
+	pub bytes: u64,
+}
+
+impl MeshStats {
+	pub fn of_mesh(mesh: &TerrainMesh) -> Self {
+		MeshStats {
+			triangles: mesh.triangle_count() as u64,
+			vertices: mesh.vertex_count() as u64,
+			bytes: mesh.byte_size(),
+		}
+	}
+}
+
+impl AddAssign for MeshStats {
+	fn add_assign(&mut self, other: Self) {
+		self.triangles += other.triangles;
+		self.vertices += other.vertices;
+		self.bytes += other.bytes;
+	}
+}
+
+impl SubAssign for MeshStats {
+	fn sub_assign(&mut self, other: Self) {
+		self.triangles -= other.triangles;
+		self.vertices -= other.vertices;
+		self.bytes -= other.bytes;
+	}
+}
+
+// the zones with the most triangles, most triangles first
+pub fn heaviest_zones<I: IntoIterator<Item = (ChunkPos, MeshStats)>>(zones: I, count: usize) -> Vec<(ChunkPos, MeshStats)> {
+	let mut zones = zones.into_iter().collect::<Vec<_>>();
+	// ties are broken by position so the list doesn't flicker between frames
+	zones.sort_by_key(|(zone, stats)| (std::cmp::Reverse(stats.triangles), zone.x, zone.y, zone.z));
+	zones.truncate(count);
+	zones
+}
+
+// one line per zone, for the debug window
+pub fn format_zone_list(zones: &[(ChunkPos, MeshStats)]) -> String {
+	zones.iter()
+		.map(|(zone, stats)| format!(
+			"({}, {}, {}): {} triangles, {} vertices, {:.1} KiB",
+			zone.x,
+			zone.y,
+			zone.z,
+			stats.triangles,
+			stats.vertices,
+			stats.bytes as f64 / 1024.0,
+		))
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stats(triangles: u64) -> MeshStats {
+		MeshStats {
+			triangles,
+			vertices: triangles * 2,
+			bytes: triangles * 100,
+		}
+	}
+
+	#[test]
+	fn heaviest_zones_are_sorted_and_limited() {
+		let zones = (0..20).rev().map(|i| (ChunkPos::new(i, 0, 0), stats(i as u64 % 7)));
+		let heaviest = heaviest_zones(zones, 3);
+
+		// zones with the same number of triangles are ordered by position
+		assert_eq!(heaviest, vec![
+			(ChunkPos::new(6, 0, 0), stats(6)),
+			(ChunkPos::new(13, 0, 0), stats(6)),
+			(ChunkPos::new(5, 0, 0), stats(5)),
+		]);
+	}
+
+	#[test]
+	fn mesh_stats_add_and_remove() {
+		let mut total = MeshStats::default();
+		total += stats(5);
+		total += stats(3);
+		total -= stats(5);
+		assert_eq!(total, stats(3));
+	}
+}
@@ -0,0 +1,371 @@
+use anyhow::{anyhow, Context};
+
+use crate::prelude::*;
+use super::block::BlockType;
+use super::mesh_validation::MeshValidation;
+use super::player::GameMode;
+use super::world::MAX_PREGEN_RADIUS;
+use super::world_state::{Weather, DAY_TIME, NIGHT_TIME};
+
+// what each argument of a command can be, used for tab completion
+#[derive(Debug, Clone, Copy)]
+enum ArgumentKind {
+	// one of these words
+	Keyword(&'static [&'static str]),
+	// a block coordinate, ~ is the player's coordinate
+	Coordinate,
+	Block,
+	// anything else, which isn't completed
+	Number,
+}
+
+// every command with the kind of each of its arguments, sorted by name
+const COMMANDS: &[(&str, &[ArgumentKind])] = &[
+	("exportmap", &[ArgumentKind::Number]),
+	("gamemode", &[ArgumentKind::Keyword(&["creative", "survival"])]),
+	("meshcheck", &[ArgumentKind::Keyword(&["full", "off", "quads"])]),
+	("pregen", &[ArgumentKind::Number]),
+	("regen", &[ArgumentKind::Keyword(&["here"]), ArgumentKind::Keyword(&["confirm"])]),
+	("remesh", &[ArgumentKind::Keyword(&["here"])]),
+	("setblock", &[ArgumentKind::Coordinate, ArgumentKind::Coordinate, ArgumentKind::Coordinate, ArgumentKind::Block]),
+	("stats", &[ArgumentKind::Keyword(&["blocks"]), ArgumentKind::Number]),
+	("time", &[ArgumentKind::Keyword(&["set"]), ArgumentKind::Keyword(&["day", "night"])]),
+	("tp", &[ArgumentKind::Coordinate, ArgumentKind::Coordinate, ArgumentKind::Coordinate]),
+	("weather", &[ArgumentKind::Keyword(&["clear", "rain"])]),
+];
+
+// a block coordinate in a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coordinate {
+	Absolute(i32),
+	// ~ or ~<offset>, relative to the player's block
+	Relative(i32),
+}
+
+impl Coordinate {
+	fn parse(text: &str) -> Result<Self> {
+		let invalid = || format!("invalid coordinate {}", text);
+		match text.strip_prefix('~') {
+			Some("") => Ok(Coordinate::Relative(0)),
+			Some(offset) => Ok(Coordinate::Relative(offset.parse().with_context(invalid)?)),
+			None => Ok(Coordinate::Absolute(text.parse().with_context(invalid)?)),
+		}
+	}
+
+	// player is the player's coordinate on the same axis
+	pub fn resolve(self, player: i32) -> i32 {
+		match self {
+			Coordinate::Absolute(coordinate) => coordinate,
+			Coordinate::Relative(offset) => player + offset,
+		}
+	}
+}
+
+const REGEN_USAGE: &str = "/regen [here|<x> <y> <z>] confirm";
+const REMESH_USAGE: &str = "/remesh [here|<x> <y> <z>]";
+
+// the block a chunk command is used on, no coordinates or here is the player's block
+fn parse_target(arguments: &[&str], usage: &str) -> Result<[Coordinate; 3]> {
+	match *arguments {
+		[] | ["here"] => Ok([Coordinate::Relative(0); 3]),
+		[x, y, z] => Ok([Coordinate::parse(x)?, Coordinate::parse(y)?, Coordinate::parse(z)?]),
+		_ => Err(anyhow!("usage: {}", usage)),
+	}
+}
+
+// block names are matched ignoring case, since they are saved in camel case
+fn parse_block(name: &str) -> Result<BlockType> {
+	BlockType::ALL.iter()
+		.copied()
+		.find(|block_type| block_type.name().eq_ignore_ascii_case(name))
+		.ok_or_else(|| anyhow!("unknown block {}", name))
+}
+
+// something typed into the console
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+	// counts the blocks of each type in the loaded chunks within radius chunks of the player, or every loaded chunk
+	BlockStats {
+		radius: Option<i32>,
+	},
+	// saves a map of the surface around the player, see map_export
+	ExportMap {
+		radius: i32,
+	},
+	GameMode(GameMode),
+	// checks run on chunk meshes after they are updated, see mesh_validation
+	MeshCheck(MeshValidation),
+	// generates and saves the chunks within radius of spawn in the background, see World::start_pregen
+	Pregen {
+		radius: i32,
+	},
+	// generates the chunk containing the block again, throwing away every change made to it, see World::regenerate_chunk
+	Regenerate([Coordinate; 3]),
+	// remeshes the chunk containing the block, see World::remesh_chunk
+	Remesh([Coordinate; 3]),
+	// ticks since the start of the day
+	SetTime(u64),
+	SetWeather(Weather),
+	SetBlock {
+		position: [Coordinate; 3],
+		block: BlockType,
+	},
+	// moves the player to the block once the chunks around it are generated
+	Teleport([Coordinate; 3]),
+}
+
+// the leading slash is optional
+pub fn parse_command(text: &str) -> Result<Command> {
+	let text = text.trim();
+	let text = text.strip_prefix('/').unwrap_or(text);
+	let mut words = text.split_whitespace();
+	let name = words.next().ok_or_else(|| anyhow!("no command entered"))?;
+	let arguments = words.collect::<Vec<_>>();
+
+	match name {
+		"exportmap" => match arguments[..] {
+			[radius] => Ok(Command::ExportMap {
+				radius: radius.parse().with_context(|| format!("invalid radius {}", radius))?,
+			}),
+			_ => Err(anyhow!("usage: /exportmap <radius>")),
+		},
+		"gamemode" => match arguments[..] {
+			[mode] => GameMode::from_name(mode)
+				.map(Command::GameMode)
+				.ok_or_else(|| anyhow!("unknown game mode {}, must be creative or survival", mode)),
+			_ => Err(anyhow!("usage: /gamemode creative|survival")),
+		},
+		"meshcheck" => match arguments[..] {
+			[validation] => MeshValidation::from_name(validation)
+				.map(Command::MeshCheck)
+				.ok_or_else(|| anyhow!("unknown mesh check {}, must be off, quads or full", validation)),
+			_ => Err(anyhow!("usage: /meshcheck off|quads|full")),
+		},
+		"pregen" => match arguments[..] {
+			[radius] => match radius.parse() {
+				Ok(radius) if (1..=MAX_PREGEN_RADIUS).contains(&radius) => Ok(Command::Pregen {
+					radius,
+				}),
+				_ => Err(anyhow!("invalid radius {}, must be from 1 to {}", radius, MAX_PREGEN_RADIUS)),
+			},
+			_ => Err(anyhow!("usage: /pregen <radius>")),
+		},
+		// the chunk's contents can't be gotten back, so it has to be confirmed
+		"regen" => match arguments.split_last() {
+			Some((&"confirm", target)) => Ok(Command::Regenerate(parse_target(target, REGEN_USAGE)?)),
+			_ => {
+				parse_target(&arguments, REGEN_USAGE)?;
+				Err(anyhow!("regenerating throws away every change made to the chunk, add confirm to the end to do it"))
+			},
+		},
+		"remesh" => Ok(Command::Remesh(parse_target(&arguments, REMESH_USAGE)?)),
+		"setblock" => match arguments[..] {
+			[x, y, z, block] => Ok(Command::SetBlock {
+				position: [Coordinate::parse(x)?, Coordinate::parse(y)?, Coordinate::parse(z)?],
+				block: parse_block(block)?,
+			}),
+			_ => Err(anyhow!("usage: /setblock <x> <y> <z> <block>")),
+		},
+		"stats" => match arguments[..] {
+			["blocks"] => Ok(Command::BlockStats {
+				radius: None,
+			}),
+			["blocks", radius] => match radius.parse() {
+				Ok(radius) if radius >= 0 => Ok(Command::BlockStats {
+					radius: Some(radius),
+				}),
+				_ => Err(anyhow!("invalid radius {}, must be 0 or more", radius)),
+			},
+			_ => Err(anyhow!("usage: /stats blocks [radius]")),
+		},
+		"time" => match arguments[..] {
+			["set", "day"] => Ok(Command::SetTime(DAY_TIME)),
+			["set", "night"] => Ok(Command::SetTime(NIGHT_TIME)),
+			["set", ticks] => Ok(Command::SetTime(ticks.parse().with_context(|| format!("invalid time {}", ticks))?)),
+			_ => Err(anyhow!("usage: /time set <ticks>|day|night")),
+		},
+		"tp" => match arguments[..] {
+			[x, y, z] => Ok(Command::Teleport([Coordinate::parse(x)?, Coordinate::parse(y)?, Coordinate::parse(z)?])),
+			_ => Err(anyhow!("usage: /tp <x> <y> <z>")),
+		},
+		"weather" => match arguments[..] {
+			[weather] => Weather::from_name(weather)
+				.map(Command::SetWeather)
+				.ok_or_else(|| anyhow!("unknown weather {}, must be clear or rain", weather)),
+			_ => Err(anyhow!("usage: /weather clear|rain")),
+		},
+		_ => Err(anyhow!("unknown command {}", name)),
+	}
+}
+
+// the words the word being typed at cursor could be completed to, sorted
+// cursor is a byte index into input, only the text before it is looked at
+// command names keep the slash if it was typed
+pub fn complete(input: &str, cursor: usize) -> Vec<String> {
+	let before_cursor = &input[..cursor];
+	let mut words = before_cursor.split_whitespace().collect::<Vec<_>>();
+	// a word is only being typed if the cursor is right after it, otherwise a new one is being started
+	let word = if before_cursor.ends_with(|c: char| !c.is_whitespace()) {
+		words.pop().unwrap_or("")
+	} else {
+		""
+	};
+
+	let starts_with = |candidate: &str, prefix: &str| {
+		candidate.len() >= prefix.len() && candidate[..prefix.len()].eq_ignore_ascii_case(prefix)
+	};
+
+	let command = match words.first() {
+		Some(command) => command.strip_prefix('/').unwrap_or(command),
+		None => {
+			let (slash, name) = match word.strip_prefix('/') {
+				Some(name) => ("/", name),
+				None => ("", word),
+			};
+			return COMMANDS.iter()
+				.filter(|(command, _)| starts_with(command, name))
+				.map(|(command, _)| format!("{}{}", slash, command))
+				.collect();
+		},
+	};
+
+	let argument_kinds = match COMMANDS.iter().find(|(name, _)| *name == command) {
+		Some((_, argument_kinds)) => argument_kinds,
+		None => return Vec::new(),
+	};
+
+	let mut completions = match argument_kinds.get(words.len() - 1) {
+		Some(ArgumentKind::Keyword(keywords)) => keywords.iter()
+			.filter(|keyword| starts_with(keyword, word))
+			.map(|keyword| keyword.to_string())
+			.collect(),
+		Some(ArgumentKind::Coordinate) if word.is_empty() => vec![String::from("~")],
+		Some(ArgumentKind::Block) => BlockType::ALL.iter()
+			.map(|block_type| block_type.name())
+			.filter(|name| starts_with(name, word))
+			.map(String::from)
+			.collect(),
+		_ => Vec::new(),
+	};
+	completions.sort();
+	completions
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_commands() {
+		assert_eq!(parse_command("/exportmap 512").unwrap(), Command::ExportMap { radius: 512 });
+		assert_eq!(parse_command("  exportmap   64 ").unwrap(), Command::ExportMap { radius: 64 });
+
+		assert!(parse_command("/exportmap").is_err());
+		assert!(parse_command("/exportmap big").is_err());
+		assert!(parse_command("/exportmap 1 2").is_err());
+		assert_eq!(parse_command("/gamemode survival").unwrap(), Command::GameMode(GameMode::Survival));
+		assert_eq!(parse_command("gamemode creative").unwrap(), Command::GameMode(GameMode::Creative));
+		assert!(parse_command("/gamemode").is_err());
+		assert!(parse_command("/gamemode adventure").is_err());
+		assert_eq!(parse_command("/time set 6000").unwrap(), Command::SetTime(6000));
+		assert_eq!(parse_command("/time set day").unwrap(), Command::SetTime(DAY_TIME));
+		assert_eq!(parse_command("time set night").unwrap(), Command::SetTime(NIGHT_TIME));
+		assert!(parse_command("/time set").is_err());
+		assert!(parse_command("/time set -5").is_err());
+		assert!(parse_command("/time add 100").is_err());
+		assert_eq!(parse_command("/weather rain").unwrap(), Command::SetWeather(Weather::Rain));
+		assert_eq!(parse_command("/weather clear").unwrap(), Command::SetWeather(Weather::Clear));
+		assert!(parse_command("/weather").is_err());
+		assert!(parse_command("/weather snow").is_err());
+
+		assert_eq!(parse_command("/meshcheck full").unwrap(), Command::MeshCheck(MeshValidation::Full));
+		assert!(parse_command("/meshcheck slow").is_err());
+		assert_eq!(parse_command("/pregen 12").unwrap(), Command::Pregen { radius: 12 });
+		assert!(parse_command("/pregen 0").is_err());
+		assert!(parse_command("/pregen 100000").is_err());
+
+		let here = [Coordinate::Relative(0); 3];
+		assert_eq!(parse_command("/remesh").unwrap(), Command::Remesh(here));
+		assert_eq!(parse_command("/remesh here").unwrap(), Command::Remesh(here));
+		assert_eq!(parse_command("/remesh 40 ~ -3").unwrap(), Command::Remesh([
+			Coordinate::Absolute(40),
+			Coordinate::Relative(0),
+			Coordinate::Absolute(-3),
+		]));
+		assert!(parse_command("/remesh 40 2").is_err());
+		assert!(parse_command("/remesh there").is_err());
+		// regenerating has to be confirmed
+		assert_eq!(parse_command("/regen here confirm").unwrap(), Command::Regenerate(here));
+		assert_eq!(parse_command("/regen confirm").unwrap(), Command::Regenerate(here));
+		assert_eq!(parse_command("/regen 1 2 3 confirm").unwrap(), Command::Regenerate([
+			Coordinate::Absolute(1),
+			Coordinate::Absolute(2),
+			Coordinate::Absolute(3),
+		]));
+		assert!(parse_command("/regen").is_err());
+		assert!(parse_command("/regen 1 2 3").is_err());
+		assert!(parse_command("/regen confirm here").is_err());
+
+		assert_eq!(parse_command("/setblock 1 ~ ~-2 stoneslab").unwrap(), Command::SetBlock {
+			position: [Coordinate::Absolute(1), Coordinate::Relative(0), Coordinate::Relative(-2)],
+			block: BlockType::StoneSlab,
+		});
+		assert!(parse_command("/setblock 1 2 3").is_err());
+		assert!(parse_command("/setblock 1 2 ~a Stone").is_err());
+		assert!(parse_command("/setblock 1 2 3 Bedrock").is_err());
+
+		assert_eq!(parse_command("/stats blocks").unwrap(), Command::BlockStats { radius: None });
+		assert_eq!(parse_command("/stats blocks 4").unwrap(), Command::BlockStats { radius: Some(4) });
+		assert!(parse_command("/stats").is_err());
+		assert!(parse_command("/stats blocks -1").is_err());
+		assert!(parse_command("/stats entities").is_err());
+
+		assert_eq!(parse_command("/tp 100 ~5 -20").unwrap(), Command::Teleport([
+			Coordinate::Absolute(100),
+			Coordinate::Relative(5),
+			Coordinate::Absolute(-20),
+		]));
+		assert!(parse_command("/tp 100 64").is_err());
+		assert!(parse_command("/teleport 0 0 0").is_err());
+		assert!(parse_command("/").is_err());
+	}
+
+	#[test]
+	fn complete_command_names() {
+		assert_eq!(complete("/ga", 3), vec!["/gamemode"]);
+		assert_eq!(complete("we", 2), vec!["weather"]);
+		assert_eq!(complete("/T", 2), vec!["/time", "/tp"]);
+		assert!(complete("/x", 2).is_empty());
+		// the slash on its own could be any command
+		assert_eq!(complete("/", 1), vec![
+			"/exportmap", "/gamemode", "/meshcheck", "/pregen", "/regen", "/remesh", "/setblock", "/stats", "/time", "/tp", "/weather",
+		]);
+	}
+
+	#[test]
+	fn complete_arguments() {
+		assert_eq!(complete("/gamemode s", 11), vec!["survival"]);
+		assert_eq!(complete("/gamemode ", 10), vec!["creative", "survival"]);
+		assert_eq!(complete("/time set n", 11), vec!["night"]);
+		assert!(complete("/time set night ", 16).is_empty());
+		assert!(complete("/exportmap 5", 12).is_empty());
+		assert!(complete("/nothing ", 9).is_empty());
+		assert_eq!(complete("/regen here c", 13), vec!["confirm"]);
+		assert_eq!(complete("/stats ", 7), vec!["blocks"]);
+
+		// blocks are completed ignoring case, and an ambiguous prefix gives every block it could be
+		assert_eq!(complete("/setblock 0 0 0 sto", 19), vec!["Stone", "StoneSlab"]);
+		assert_eq!(complete("/setblock ~ ~ ~ Wa", 18), vec!["Water"]);
+		// coordinates can be relative to the player
+		assert_eq!(complete("/setblock 5 ", 12), vec!["~"]);
+		assert!(complete("/setblock 5", 11).is_empty());
+	}
+
+	#[test]
+	fn complete_in_the_middle_of_input() {
+		// only the word before the cursor is completed, text after it is ignored
+		let input = "/weather c 123";
+		assert_eq!(complete(input, 10), vec!["clear"]);
+		assert_eq!(complete(input, 4), vec!["/weather"]);
+	}
+}
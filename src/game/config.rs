@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::prelude::*;
+
+// settings which are kept between sessions, stored in the working directory
+pub const CONFIG_FILE: &str = "minecone.cfg";
+
+// string values saved to a file, with a `key = value` pair on each line
+#[derive(Debug)]
+pub struct Config {
+	file: PathBuf,
+	values: BTreeMap<String, String>,
+}
+
+impl Config {
+	// a missing file is an empty config, lines which can't be parsed are skipped
+	pub fn load(file: PathBuf) -> Self {
+		let values = match fs::read_to_string(&file) {
+			Ok(text) => Self::parse(&text),
+			Err(_) => BTreeMap::new(),
+		};
+
+		Config {
+			file,
+			values,
+		}
+	}
+
+	fn parse(text: &str) -> BTreeMap<String, String> {
+		let mut values = BTreeMap::new();
+		for line in text.lines() {
+			if line.trim().is_empty() {
+				continue;
+			}
+
+			match line.split_once('=') {
+				Some((key, value)) => {
+					values.insert(key.trim().to_owned(), value.trim().to_owned());
+				},
+				None => warn!("skipping invalid config line: {}", line),
+			}
+		}
+		values
+	}
+
+	fn to_text(&self) -> String {
+		self.values.iter()
+			.map(|(key, value)| format!("{} = {}\n", key, value))
+			.collect()
+	}
+
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.values.get(key).map(String::as_str)
+	}
+
+	// removes the key if the value is none, changes are only written when the config is saved
+	pub fn set(&mut self, key: &str, value: Option<&str>) {
+		match value {
+			Some(value) => self.values.insert(key.to_owned(), value.to_owned()),
+			None => self.values.remove(key),
+		};
+	}
+
+	pub fn save(&self) -> Result<()> {
+		Ok(fs::write(&self.file, self.to_text())?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn config_round_trip() {
+		let mut config = Config {
+			file: PathBuf::new(),
+			values: Config::parse("texture_pack = faithful\n\nnot a setting\nfov=70\n"),
+		};
+		assert_eq!(config.get("texture_pack"), Some("faithful"));
+		assert_eq!(config.get("fov"), Some("70"));
+
+		config.set("fov", None);
+		config.set("volume", Some("0.5"));
+		assert_eq!(config.to_text(), "texture_pack = faithful\nvolume = 0.5\n");
+		assert_eq!(Config::parse(&config.to_text()), config.values);
+	}
+}
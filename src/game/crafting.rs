@@ -0,0 +1,280 @@
+use anyhow::anyhow;
+
+use crate::prelude::*;
+use super::block::BlockType;
+
+// the crafting grid in the inventory is this many cells wide and tall
+pub const CRAFTING_GRID_SIZE: usize = 2;
+const CRAFTING_GRID_CELLS: usize = CRAFTING_GRID_SIZE * CRAFTING_GRID_SIZE;
+
+// what has to be in the crafting grid for a recipe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipePattern {
+	// the ingredients must be in this arrangement, anywhere in the grid, and may be mirrored left to right
+	// rows are all the same length, and none are longer than the grid
+	Shaped(Vec<Vec<Option<BlockType>>>),
+	// the ingredients can be anywhere in the grid, each block in the list takes up 1 cell
+	Shapeless(Vec<BlockType>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipe {
+	pub pattern: RecipePattern,
+	pub output: BlockType,
+	pub count: u32,
+}
+
+impl Recipe {
+	// rows are written like "SS", with each character looked up in key, and spaces as empty cells
+	pub fn shaped(rows: &[&str], key: &[(char, BlockType)], output: BlockType, count: u32) -> Result<Self> {
+		let width = rows.first().map_or(0, |row| row.chars().count());
+		if width == 0 || rows.iter().any(|row| row.chars().count() != width) {
+			return Err(anyhow!("rows of a shaped recipe must all be the same length, and not empty"));
+		}
+		if width > CRAFTING_GRID_SIZE || rows.len() > CRAFTING_GRID_SIZE {
+			return Err(anyhow!("shaped recipe is bigger than the {}x{} crafting grid", CRAFTING_GRID_SIZE, CRAFTING_GRID_SIZE));
+		}
+
+		let pattern = rows.iter()
+			.map(|row| row.chars().map(|c| match c {
+				' ' => Ok(None),
+				_ => key.iter()
+					.find(|(key_char, _)| *key_char == c)
+					.map(|(_, block_type)| Some(*block_type))
+					.ok_or_else(|| anyhow!("{} is not in the recipe's key", c)),
+			}).collect::<Result<Vec<_>>>())
+			.collect::<Result<Vec<_>>>()?;
+
+		Ok(Recipe {
+			pattern: RecipePattern::Shaped(pattern),
+			output,
+			count,
+		})
+	}
+
+	pub fn shapeless(ingredients: &[BlockType], output: BlockType, count: u32) -> Result<Self> {
+		if ingredients.is_empty() || ingredients.len() > CRAFTING_GRID_CELLS {
+			return Err(anyhow!("shapeless recipe must have between 1 and {} ingredients", CRAFTING_GRID_CELLS));
+		}
+
+		Ok(Recipe {
+			pattern: RecipePattern::Shapeless(ingredients.to_vec()),
+			output,
+			count,
+		})
+	}
+
+	pub fn matches(&self, grid: &CraftingGrid) -> bool {
+		match &self.pattern {
+			RecipePattern::Shaped(rows) => {
+				let trimmed = grid.trimmed();
+				let mirrored = trimmed.iter()
+					.map(|row| row.iter().rev().copied().collect::<Vec<_>>())
+					.collect::<Vec<_>>();
+				*rows == trimmed || *rows == mirrored
+			},
+			RecipePattern::Shapeless(ingredients) => {
+				let mut remaining = grid.cells.iter().flatten().flatten().copied().collect::<Vec<_>>();
+				for ingredient in ingredients.iter() {
+					match remaining.iter().position(|block_type| block_type == ingredient) {
+						Some(index) => remaining.swap_remove(index),
+						None => return false,
+					};
+				}
+				remaining.is_empty()
+			},
+		}
+	}
+}
+
+// every recipe that can be crafted, recipes earlier in the list are used first if more than 1 matches
+pub struct RecipeBook {
+	recipes: Vec<Recipe>,
+}
+
+impl RecipeBook {
+	pub fn new(recipes: Vec<Recipe>) -> Self {
+		RecipeBook {
+			recipes,
+		}
+	}
+
+	// placeholder recipes, until there are blocks worth crafting
+	pub fn builtin() -> Self {
+		use BlockType::*;
+
+		let recipes = vec![
+			Recipe::shaped(&["SS", "SS"], &[('S', Stone)], RockyDirt, 4),
+			Recipe::shaped(&["RR"], &[('R', RockyDirt)], Stone, 1),
+			Recipe::shaped(&["S ", "SS"], &[('S', Sand)], Stone, 3),
+			Recipe::shapeless(&[Dirt, Water], Grass, 1),
+			Recipe::shaped(&["SR", "RS"], &[('S', Stone), ('R', RockyDirt)], Chest, 1),
+			Recipe::shaped(&["SS", "LL"], &[('S', Stone), ('L', StoneSlab)], Furnace, 1),
+		];
+		Self::new(recipes.into_iter().collect::<Result<_>>().expect("invalid builtin recipe"))
+	}
+
+	pub fn find(&self, grid: &CraftingGrid) -> Option<&Recipe> {
+		if grid.is_empty() {
+			return None;
+		}
+		self.recipes.iter().find(|recipe| recipe.matches(grid))
+	}
+}
+
+// ingredients put in the crafting grid, each cell holds any number of 1 block type
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CraftingGrid {
+	// indexed by row, then column
+	cells: [[Option<BlockType>; CRAFTING_GRID_SIZE]; CRAFTING_GRID_SIZE],
+	counts: [[u32; CRAFTING_GRID_SIZE]; CRAFTING_GRID_SIZE],
+}
+
+impl CraftingGrid {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.cells.iter().flatten().all(Option::is_none)
+	}
+
+	pub fn cell(&self, row: usize, column: usize) -> Option<(BlockType, u32)> {
+		self.cells[row][column].map(|block_type| (block_type, self.counts[row][column]))
+	}
+
+	// returns false if the cell holds a different block type
+	pub fn add(&mut self, row: usize, column: usize, block_type: BlockType) -> bool {
+		let cell = &mut self.cells[row][column];
+		if cell.is_some_and(|cell_type| cell_type != block_type) {
+			return false;
+		}
+
+		*cell = Some(block_type);
+		self.counts[row][column] += 1;
+		true
+	}
+
+	// empties the cell, returning what was in it
+	pub fn take(&mut self, row: usize, column: usize) -> Option<(BlockType, u32)> {
+		let block_type = self.cells[row][column].take()?;
+		Some((block_type, std::mem::take(&mut self.counts[row][column])))
+	}
+
+	// the block type and count the grid would currently craft
+	pub fn output(&self, recipes: &RecipeBook) -> Option<(BlockType, u32)> {
+		recipes.find(self).map(|recipe| (recipe.output, recipe.count))
+	}
+
+	// crafts the output, using up 1 block from every cell
+	pub fn craft(&mut self, recipes: &RecipeBook) -> Option<(BlockType, u32)> {
+		let output = self.output(recipes)?;
+
+		for (cell, count) in self.cells.iter_mut().flatten().zip(self.counts.iter_mut().flatten()) {
+			if cell.is_some() {
+				*count -= 1;
+				if *count == 0 {
+					*cell = None;
+				}
+			}
+		}
+
+		Some(output)
+	}
+
+	// the smallest rectangle around every non empty cell, so shaped recipes match anywhere in the grid
+	fn trimmed(&self) -> Vec<Vec<Option<BlockType>>> {
+		let used_rows = (0..CRAFTING_GRID_SIZE)
+			.filter(|row| self.cells[*row].iter().any(Option::is_some))
+			.collect::<Vec<_>>();
+		let used_columns = (0..CRAFTING_GRID_SIZE)
+			.filter(|column| self.cells.iter().any(|row| row[*column].is_some()))
+			.collect::<Vec<_>>();
+
+		let (rows, columns) = match (used_rows.first().zip(used_rows.last()), used_columns.first().zip(used_columns.last())) {
+			(Some((first_row, last_row)), Some((first_column, last_column))) => (*first_row..=*last_row, *first_column..=*last_column),
+			_ => return Vec::new(),
+		};
+
+		rows.map(|row| self.cells[row][columns.clone()].to_vec()).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use BlockType::*;
+
+	fn grid(cells: [[Option<BlockType>; CRAFTING_GRID_SIZE]; CRAFTING_GRID_SIZE]) -> CraftingGrid {
+		let mut grid = CraftingGrid::new();
+		for (row, row_cells) in cells.iter().enumerate() {
+			for (column, cell) in row_cells.iter().enumerate() {
+				if let Some(block_type) = cell {
+					grid.add(row, column, *block_type);
+				}
+			}
+		}
+		grid
+	}
+
+	#[test]
+	fn shaped_recipes_match_at_any_offset() {
+		let recipe = Recipe::shaped(&["RR"], &[('R', RockyDirt)], Stone, 1).unwrap();
+
+		assert!(recipe.matches(&grid([[Some(RockyDirt), Some(RockyDirt)], [None, None]])));
+		assert!(recipe.matches(&grid([[None, None], [Some(RockyDirt), Some(RockyDirt)]])));
+		assert!(!recipe.matches(&grid([[Some(RockyDirt), None], [None, Some(RockyDirt)]])));
+		assert!(!recipe.matches(&grid([[Some(RockyDirt), None], [Some(RockyDirt), None]])));
+		// extra ingredients mean a different recipe
+		assert!(!recipe.matches(&grid([[Some(RockyDirt), Some(RockyDirt)], [Some(Dirt), None]])));
+	}
+
+	#[test]
+	fn shaped_recipes_match_mirrored() {
+		let recipe = Recipe::shaped(&["S ", "SS"], &[('S', Sand)], Stone, 3).unwrap();
+
+		assert!(recipe.matches(&grid([[Some(Sand), None], [Some(Sand), Some(Sand)]])));
+		assert!(recipe.matches(&grid([[None, Some(Sand)], [Some(Sand), Some(Sand)]])));
+		// upside down isn't a mirror image
+		assert!(!recipe.matches(&grid([[Some(Sand), Some(Sand)], [Some(Sand), None]])));
+	}
+
+	#[test]
+	fn shapeless_recipes_match_anywhere() {
+		let recipe = Recipe::shapeless(&[Dirt, Water], Grass, 1).unwrap();
+
+		assert!(recipe.matches(&grid([[Some(Dirt), Some(Water)], [None, None]])));
+		assert!(recipe.matches(&grid([[None, Some(Water)], [Some(Dirt), None]])));
+		assert!(!recipe.matches(&grid([[Some(Dirt), None], [None, None]])));
+		assert!(!recipe.matches(&grid([[Some(Dirt), Some(Water)], [Some(Dirt), None]])));
+	}
+
+	#[test]
+	fn invalid_recipes_are_rejected() {
+		assert!(Recipe::shaped(&["SSS"], &[('S', Stone)], Stone, 1).is_err());
+		assert!(Recipe::shaped(&["S", "SS"], &[('S', Stone)], Stone, 1).is_err());
+		assert!(Recipe::shaped(&["SX"], &[('S', Stone)], Stone, 1).is_err());
+		assert!(Recipe::shapeless(&[], Stone, 1).is_err());
+	}
+
+	#[test]
+	fn crafting_uses_up_ingredients() {
+		let recipes = RecipeBook::builtin();
+		let mut grid = CraftingGrid::new();
+		for (row, column) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+			grid.add(row, column, Stone);
+		}
+		grid.add(0, 0, Stone);
+		assert!(!grid.add(0, 0, Dirt));
+
+		assert_eq!(grid.output(&recipes), Some((RockyDirt, 4)));
+		assert_eq!(grid.craft(&recipes), Some((RockyDirt, 4)));
+		// only the cell with 2 blocks has any left, which isn't a recipe
+		assert_eq!(grid.cell(0, 0), Some((Stone, 1)));
+		assert_eq!(grid.cell(1, 1), None);
+		assert_eq!(grid.craft(&recipes), None);
+
+		assert_eq!(grid.take(0, 0), Some((Stone, 1)));
+		assert!(grid.is_empty());
+	}
+}
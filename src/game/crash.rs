@@ -0,0 +1,156 @@
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::{Arc, Weak, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::{Mutex, const_mutex};
+
+use crate::prelude::*;
+use super::world::World;
+
+const CRASH_REPORT_FOLDER: &str = "crash-reports";
+// how long the panicking thread waits for the world to save before it gives up and lets the process exit
+const CRASH_SAVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// world that is saved if the game panics
+static CRASH_WORLD: Mutex<Option<Weak<World>>> = const_mutex(None);
+// set while saving after a panic, so a panic while saving can't cause another save
+static SAVING: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+	// true while a panic on this thread will be caught and recovered from
+	static CATCHING_PANIC: Cell<bool> = const { Cell::new(false) };
+}
+
+// sets the world which will be saved if a panic occurs
+pub fn set_crash_world(world: Weak<World>) {
+	*CRASH_WORLD.lock() = Some(world);
+}
+
+pub fn install_panic_hook() {
+	let default_hook = panic::take_hook();
+
+	panic::set_hook(Box::new(move |info| {
+		let report = crash_report(info);
+
+		if CATCHING_PANIC.with(|catching| catching.get()) {
+			// the panic will be recovered from, so only log it
+			error!("{}", report);
+			return;
+		}
+
+		error!("{}", report);
+		match write_crash_report(&report) {
+			Ok(path) => error!("crash report written to {}", path.display()),
+			Err(err) => error!("could not write crash report: {}", err),
+		}
+
+		save_crash_world();
+
+		default_hook(info);
+	}));
+}
+
+// runs f, catching any panic that occurs and returning None if it does
+// this does not save the world or write a crash report, since the panic is expected to be recovered from
+pub fn catch_panic<T, F: FnOnce() -> T>(f: F) -> Option<T> {
+	CATCHING_PANIC.with(|catching| catching.set(true));
+	let result = panic::catch_unwind(AssertUnwindSafe(f));
+	CATCHING_PANIC.with(|catching| catching.set(false));
+
+	result.ok()
+}
+
+fn crash_report(info: &PanicHookInfo) -> String {
+	let thread = thread::current();
+	let thread_name = thread.name().unwrap_or("<unnamed>");
+
+	format!(
+		"thread '{}' {}\n\nbacktrace:\n{}",
+		thread_name,
+		info,
+		Backtrace::force_capture(),
+	)
+}
+
+fn write_crash_report(report: &str) -> Result<PathBuf> {
+	let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+	let mut path = PathBuf::from(CRASH_REPORT_FOLDER);
+	fs::create_dir_all(&path)?;
+	path.push(format!("{}.txt", timestamp));
+
+	fs::write(&path, report)?;
+	Ok(path)
+}
+
+// best effort attempt to save the world
+fn save_crash_world() {
+	if SAVING.swap(true, Ordering::AcqRel) {
+		error!("panicked while saving the world after a crash, world will not be saved");
+		return;
+	}
+
+	// the lock might be held by the panicking thread, so don't wait on it
+	let world = match CRASH_WORLD.try_lock() {
+		Some(world) => world.as_ref().and_then(Weak::upgrade),
+		None => None,
+	};
+
+	if let Some(world) = world {
+		if !save_world_with_timeout(world, CRASH_SAVE_TIMEOUT) {
+			error!("saving the world after a crash took too long, the panicking thread probably holds a lock saving needs");
+		}
+	}
+}
+
+// saves the world on another thread, since the panicking thread may hold locks the save waits on,
+// which would hang the process instead of letting it exit
+// returns false if the save didn't finish in time, then it is left running
+fn save_world_with_timeout(world: Arc<World>, timeout: Duration) -> bool {
+	let (sender, receiver) = mpsc::channel();
+	let spawned = thread::Builder::new()
+		.name(String::from("crash save"))
+		.spawn(move || {
+			match world.save_after_crash() {
+				Ok(()) => error!("world saved after crash"),
+				Err(err) => error!("could not save world after crash: {}", err),
+			}
+			let _ = sender.send(());
+		});
+
+	match spawned {
+		Ok(_) => receiver.recv_timeout(timeout).is_ok(),
+		Err(err) => {
+			error!("could not start thread to save world after crash: {}", err);
+			false
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::chunk::{Chunk, LoadedChunk};
+	use crate::game::block::Air;
+
+	#[test]
+	fn save_gives_up_on_held_locks() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+
+		// as if the panicking thread was changing the chunk
+		let guard = world.chunks.get_mut(&chunk).unwrap();
+		assert!(!save_world_with_timeout(world.clone(), Duration::from_millis(100)));
+		drop(guard);
+
+		assert!(save_world_with_timeout(world.clone(), Duration::from_secs(10)));
+		world.close();
+	}
+}
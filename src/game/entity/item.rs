@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use glam::Vec3;
+
+use super::{Entity, SavedEntity};
+use crate::prelude::*;
+use crate::math::Aabb;
+use crate::game::block::BlockType;
+use crate::game::world::World;
+use crate::game::save_format::{Record, Tag};
+
+// items despawn after they have been ticked for this long
+pub const ITEM_LIFETIME: Duration = Duration::from_secs(5 * 60);
+// all speeds are in blocks / second, and accelerations in blocks / second^2
+const ITEM_GRAVITY: f32 = 28.0;
+const ITEM_TERMINAL_SPEED: f32 = 60.0;
+// width and height of an item's bounding box, its position is the middle of the bottom
+const ITEM_SIZE: f32 = 0.25;
+
+// fields of a saved item
+const TAG_BLOCK_TYPE: Tag = 0;
+const TAG_COUNT: Tag = 1;
+const TAG_VERTICAL_SPEED: Tag = 2;
+// in milliseconds
+const TAG_AGE: Tag = 3;
+
+// blocks lying in the world, like the contents of a broken chest
+// they fall until they land on a solid block, and can't be picked up yet
+pub struct Item {
+	position: Position,
+	previous_position: Position,
+	// negative when falling
+	vertical_speed: f32,
+	block_type: BlockType,
+	count: u32,
+	// time spent in loaded chunks
+	age: Duration,
+	// fields from the item's save that this version doesn't know, they are saved again unchanged
+	unknown_fields: Record,
+	// the same for the fields next to the item's kind and position, see SavedEntity
+	unknown_entity_fields: Record,
+}
+
+impl Item {
+	pub fn new(position: Position, block_type: BlockType, count: u32) -> Self {
+		Item {
+			position,
+			previous_position: position,
+			vertical_speed: 0.0,
+			block_type,
+			count,
+			age: Duration::ZERO,
+			unknown_fields: Record::new(),
+			unknown_entity_fields: Record::new(),
+		}
+	}
+
+	// only the block type is needed, items saved without the other fields start out new
+	pub fn load(position: Position, mut fields: Record, unknown_entity_fields: Record) -> Result<Box<dyn Entity>> {
+		let type_name = fields.take_string(TAG_BLOCK_TYPE)?.ok_or_else(|| anyhow!("item has no block type"))?;
+		let block_type = BlockType::from_name(&type_name).ok_or_else(|| anyhow!("unknown block type {}", type_name))?;
+
+		let vertical_speed = fields.take_f32(TAG_VERTICAL_SPEED)?.unwrap_or(0.0);
+		let count = fields.take_u32(TAG_COUNT)?.unwrap_or(1);
+		let age = Duration::from_millis(fields.take_u64(TAG_AGE)?.unwrap_or(0));
+
+		Ok(Box::new(Item {
+			position,
+			previous_position: position,
+			vertical_speed,
+			block_type,
+			count,
+			age,
+			unknown_fields: fields,
+			unknown_entity_fields,
+		}))
+	}
+}
+
+// how far down something at position can fall towards target_y before it lands on a solid block
+// blocks which aren't loaded stop it as well, so it doesn't fall through terrain that isn't there
+fn fall_to(world: &World, position: Position, target_y: f32) -> f32 {
+	let block = position.as_block_pos();
+	// the blocks whose tops are between the position and the target, from the top down
+	for y in ((target_y.ceil() as i32 - 1)..=(position.y.floor() as i32 - 1)).rev() {
+		let below = BlockPos::new(block.x, y, block.z);
+		if world.block_type_at(below).is_none() || world.is_solid_at(below) {
+			return (y + 1) as f32;
+		}
+	}
+	target_y
+}
+
+impl Entity for Item {
+	fn position(&self) -> Position {
+		self.position
+	}
+
+	fn previous_position(&self) -> Position {
+		self.previous_position
+	}
+
+	fn label(&self) -> Option<String> {
+		Some(format!("{} x{}", self.block_type.name(), self.count))
+	}
+
+	fn tick(&mut self, world: &World, delta: Duration) {
+		self.age += delta;
+		self.previous_position = self.position;
+
+		let delta = delta.as_secs_f32();
+		self.vertical_speed = (self.vertical_speed - ITEM_GRAVITY * delta).max(-ITEM_TERMINAL_SPEED);
+		let target_y = self.position.y + self.vertical_speed * delta;
+		let y = fall_to(world, self.position, target_y);
+		if y > target_y {
+			self.vertical_speed = 0.0;
+		}
+		self.position.y = y;
+	}
+
+	fn bounding_box(&self) -> Option<Aabb> {
+		let corner = self.position.0 - Vec3::new(ITEM_SIZE / 2.0, 0.0, ITEM_SIZE / 2.0);
+		Some(Aabb::new(corner, Vec3::splat(ITEM_SIZE)))
+	}
+
+	fn pushable(&self) -> bool {
+		true
+	}
+
+	// items aren't pushed into walls, if they are pushed off of a ledge they fall on the next tick
+	fn push(&mut self, world: &World, offset: Vec3) {
+		let pushed = Position(self.position.0 + offset);
+		if !world.is_solid_at(pushed.as_block_pos()) {
+			self.position = pushed;
+		}
+	}
+
+	fn should_despawn(&self) -> bool {
+		self.age >= ITEM_LIFETIME
+	}
+
+	fn save(&self) -> Option<SavedEntity> {
+		let mut fields = Record::new();
+		fields.push_str(TAG_BLOCK_TYPE, self.block_type.name());
+		fields.push_u32(TAG_COUNT, self.count);
+		fields.push_f32(TAG_VERTICAL_SPEED, self.vertical_speed);
+		fields.push_u64(TAG_AGE, self.age.as_millis() as u64);
+		fields.extend(self.unknown_fields.clone());
+
+		Some(SavedEntity {
+			kind: String::from("item"),
+			position: self.position,
+			fields,
+			unknown_fields: self.unknown_entity_fields.clone(),
+		})
+	}
+}
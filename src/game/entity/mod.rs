@@ -1,4 +1,160 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use glam::Vec3;
+
+use crate::prelude::*;
+use crate::math::Aabb;
+use super::world::World;
+use super::save_format::{Record, Tag};
+
 mod player;
 pub use player::*;
+mod item;
+pub use item::*;
+mod unknown;
+pub use unknown::*;
+
+// fields of a saved entity, the rest of the fields are read by its kind
+const TAG_KIND: Tag = 0;
+const TAG_POSITION: Tag = 1;
+const TAG_FIELDS: Tag = 2;
+
+// the furthest an entity is pushed by other entities in one tick, so a stack of entities spreads out over several ticks
+pub const MAX_PUSH_PER_TICK: f32 = 0.05;
+// entities overlapping by less than this aren't pushed, so rounding errors don't keep nudging entities that have spread out
+const PUSH_TOLERANCE: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntityId(u64);
+
+static NEXT_ENTITY_ID: AtomicU64 = AtomicU64::new(0);
+
+impl EntityId {
+	// returns a unique entity id
+	pub fn new() -> EntityId {
+		EntityId(NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed))
+	}
+}
+
+pub trait Entity: Send + Sync {
+	fn position(&self) -> Position;
+
+	// text shown floating over the entity, none if it has no label
+	fn label(&self) -> Option<String> {
+		None
+	}
+
+	// where the entity was before the last tick, entities which move should return it so they are drawn smoothly
+	fn previous_position(&self) -> Position {
+		self.position()
+	}
+
+	// where the entity is drawn, alpha is the fraction of the way from the last tick to the next one
+	// ticks are less frequent than frames, so drawing the entity at its position would make it move jerkily
+	fn render_position(&self, alpha: f32) -> Position {
+		Position(self.previous_position().lerp(self.position().0, alpha.clamp(0.0, 1.0)))
+	}
+
+	// called once every world tick with the world's tick time, but not while the entity's chunk isn't loaded
+	// the world's entities are locked, so this can't look at other entities
+	fn tick(&mut self, _world: &World, _delta: Duration) {}
+
+	// the space the entity takes up, entities without one don't collide with other entities
+	fn bounding_box(&self) -> Option<Aabb> {
+		None
+	}
+
+	// true if overlapping entities push this one away, entities which aren't pushable still push the ones which are
+	fn pushable(&self) -> bool {
+		false
+	}
+
+	// called after the entity's tick if it was pushed by other entities, offset is horizontal
+	fn push(&mut self, _world: &World, _offset: Vec3) {}
+
+	// checked after every tick, the entity is removed from the world once this is true
+	fn should_despawn(&self) -> bool {
+		false
+	}
+
+	// what is saved with the entity's chunk, none if the entity isn't saved
+	fn save(&self) -> Option<SavedEntity> {
+		None
+	}
+}
+
+// how far second has to move horizontally to stop overlapping first, along the axis they overlap the least on
+// none if they don't overlap by more than PUSH_TOLERANCE
+// entities stacked in exactly the same place are split along an axis and direction picked from their ids,
+// so a stack spreads out in every direction instead of in a line
+pub fn push_out(first: Aabb, second: Aabb, ids: [EntityId; 2]) -> Option<Vec3> {
+	let overlap = first.end().min(second.end()) - first.position.max(second.position);
+	if overlap.cmple(Vec3::splat(PUSH_TOLERANCE)).any() {
+		return None;
+	}
+
+	let tie_break = ids[0].0 ^ ids[1].0;
+	let axis = if overlap.x < overlap.z || (overlap.x == overlap.z && tie_break & 1 == 0) {
+		Axis::X
+	} else {
+		Axis::Z
+	};
+
+	let center_distance = (second.position[axis] + second.size[axis] / 2.0) - (first.position[axis] + first.size[axis] / 2.0);
+	let direction = if center_distance > 0.0 || (center_distance == 0.0 && ids[0] < ids[1]) {
+		1.0
+	} else {
+		-1.0
+	};
+
+	Some(direction * overlap[axis] * Vec3::axis(axis))
+}
+
+// an entity as it is saved in its chunk, the kind says which entity type reads the fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedEntity {
+	pub kind: String,
+	pub position: Position,
+	pub fields: Record,
+	// fields next to the kind and position which this version doesn't know, they are saved again unchanged
+	pub unknown_fields: Record,
+}
+
+impl SavedEntity {
+	pub fn encode(&self) -> Record {
+		let mut record = Record::new();
+		record.push_str(TAG_KIND, &self.kind);
+		record.push_vec3(TAG_POSITION, self.position.0);
+		record.push_record(TAG_FIELDS, &self.fields);
+		record.extend(self.unknown_fields.clone());
+		record
+	}
+
+	pub fn decode(mut record: Record) -> Result<Self> {
+		Ok(SavedEntity {
+			kind: record.take_string(TAG_KIND)?.ok_or_else(|| anyhow!("entity has no kind"))?,
+			position: Position(record.take_vec3(TAG_POSITION)?.ok_or_else(|| anyhow!("entity has no position"))?),
+			fields: record.take_record(TAG_FIELDS)?.unwrap_or_default(),
+			unknown_fields: record,
+		})
+	}
+
+	// entities of kinds this version doesn't have, or which can't be read, are kept as they were saved
+	pub fn load(self) -> Box<dyn Entity> {
+		let loaded = match self.kind.as_str() {
+			"item" => Item::load(self.position, self.fields.clone(), self.unknown_fields.clone()).map(Some),
+			_ => Ok(None),
+		};
 
-pub trait Entity: Send + Sync {}
+		match loaded {
+			Ok(Some(entity)) => entity,
+			Ok(None) => Box::new(UnknownEntity::new(self)),
+			Err(error) => {
+				warn!("could not load {} entity, it is kept as it was saved: {:#}", self.kind, error);
+				Box::new(UnknownEntity::new(self))
+			},
+		}
+	}
+}
@@ -1,5 +1,8 @@
-use super::*;
+use glam::Vec3;
+
+use super::Entity;
 use crate::prelude::*;
+use crate::math::Aabb;
 
 pub struct Player {
 	position: Position,
@@ -14,4 +17,18 @@ impl Player {
 }
 
 impl Entity for Player {
+	fn position(&self) -> Position {
+		self.position
+	}
+
+	fn label(&self) -> Option<String> {
+		Some(String::from("Player"))
+	}
+
+	// players push other entities around, but they only move themselves
+	// the position is the middle of the bottom of the box
+	fn bounding_box(&self) -> Option<Aabb> {
+		let size = Vec3::new(0.6, 1.8, 0.6);
+		Some(Aabb::new(self.position.0 - Vec3::new(size.x / 2.0, 0.0, size.z / 2.0), size))
+	}
 }
@@ -0,0 +1,26 @@
+use super::{Entity, SavedEntity};
+use crate::prelude::*;
+
+// an entity loaded from a save that this version can't read, usually from a newer version
+// it does nothing, and is saved again exactly as it was loaded, so it isn't lost
+pub struct UnknownEntity {
+	saved: SavedEntity,
+}
+
+impl UnknownEntity {
+	pub fn new(saved: SavedEntity) -> Self {
+		UnknownEntity {
+			saved,
+		}
+	}
+}
+
+impl Entity for UnknownEntity {
+	fn position(&self) -> Position {
+		self.saved.position
+	}
+
+	fn save(&self) -> Option<SavedEntity> {
+		Some(self.saved.clone())
+	}
+}
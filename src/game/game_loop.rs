@@ -0,0 +1,360 @@
+use std::time::{Instant, Duration};
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::mem;
+
+use winit::window::WindowId;
+use winit::{
+	window::Window,
+	event::*,
+	event_loop::ControlFlow,
+	dpi::PhysicalSize,
+};
+
+use crate::prelude::*;
+use super::world::World;
+use super::client::Client;
+use super::tick::{TickClock, RateCounter};
+use super::menu::MenuScreen;
+use super::world_list::{self, WorldEntry, WORLDS_DIRECTORY};
+use super::worldgen::{GeneratorType, GeneratorOptions};
+use super::{crash, parallel};
+
+// the world used by --benchmark, it is created with a fixed seed so every run generates the same terrain
+const BENCHMARK_WORLD: &str = "benchmark";
+
+enum GameState {
+	// no world is loaded yet
+	Menu(MenuScreen),
+	// the world is open, and the chunks around the player are being generated
+	Loading {
+		client: Client,
+		world_name: String,
+	},
+	InGame(Client),
+	// only while moving from one state to the next
+	Switching,
+}
+
+// Game is in charge of calling frame_update and physics_update on the correct intervals
+// and dispatching input events
+// physics updates happen at a fixed rate when the event loop runs out of events,
+// and a frame is rendered once for each redraw, the event loop sleeps until the next update once it is rendered
+pub struct Game {
+	window_id: WindowId,
+	// time between physics updates
+	frame_time: Duration,
+	last_update_time: Instant,
+	// when the last frame was rendered, the client smooths the camera using the time between frames
+	last_frame_time: Instant,
+	// a redraw was requested and its frame hasn't been rendered yet, the event loop doesn't sleep until it is
+	redraw_pending: bool,
+	// shown in the debug window, so frame rate and physics rate can be compared
+	frames_rendered: RateCounter,
+	physics_steps: RateCounter,
+	tick_rate: u32,
+	// world ticks happen at a fixed rate, seperate from physics updates
+	tick_clock: TickClock,
+	// none until a world is chosen in the menu
+	world: Option<Arc<World>>,
+	state: GameState,
+	// the benchmark starts as soon as the game does, instead of showing the menu
+	benchmark_path: Option<PathBuf>,
+}
+
+impl Game {
+	// benchmark_path is a camera path to play back while collecting performance stats
+	// physics_rate is physics updates per second, frames aren't limited by it
+	pub fn new(physics_rate: u64, tick_rate: u32, window: Window, benchmark_path: Option<PathBuf>) -> Self {
+		let frame_time = Duration::from_micros(1_000_000 / physics_rate);
+		let window_id = window.id();
+
+		let mut game = Self {
+			window_id,
+			frame_time,
+			last_update_time: Instant::now() - frame_time,
+			last_frame_time: Instant::now(),
+			redraw_pending: false,
+			frames_rendered: RateCounter::new(Instant::now()),
+			physics_steps: RateCounter::new(Instant::now()),
+			tick_rate,
+			tick_clock: TickClock::new(tick_rate, Instant::now()),
+			world: None,
+			state: GameState::Menu(MenuScreen::new(window)),
+			benchmark_path,
+		};
+
+		if game.benchmark_path.is_some() {
+			let world = benchmark_world().expect("could not create benchmark world");
+			// pregenerating would compete with the benchmark for workers
+			game.start_world(world, 0);
+		}
+
+		game
+	}
+
+	// opens the world and starts loading it, the menu shows an error if it can't be opened
+	// the chunks within pregen_radius of spawn are generated in the background, behind the chunks around the player
+	fn start_world(&mut self, mut entry: WorldEntry, pregen_radius: i32) {
+		let world = match World::open(&entry.directory, entry.meta.seed, entry.meta.generator, entry.meta.generator_options) {
+			Ok(world) => world,
+			Err(error) => {
+				if let GameState::Menu(menu) = &mut self.state {
+					menu.set_error(format!("Could not open {}: {:#}", entry.meta.name, error));
+				}
+				return;
+			},
+		};
+		if let Err(error) = entry.mark_played() {
+			warn!("could not update last played time of {}: {:#}", entry.meta.name, error);
+		}
+
+		// ticks are counted from when the world starts, not from when the game did
+		self.tick_clock = TickClock::new(self.tick_rate, Instant::now());
+		world.set_tick_time(self.tick_clock.tick_time());
+		crash::set_crash_world(Arc::downgrade(&world));
+		parallel::init(world.clone(), num_cpus::get() - 1);
+
+		self.state = match mem::replace(&mut self.state, GameState::Switching) {
+			GameState::Menu(menu) => GameState::Loading {
+				client: menu.into_client(world.clone(), self.benchmark_path.take()),
+				world_name: entry.meta.name,
+			},
+			_ => panic!("worlds can only be started from the menu"),
+		};
+		if pregen_radius > 0 {
+			world.start_pregen(pregen_radius);
+		}
+		self.world = Some(world);
+	}
+
+	// saves the world and frees everything used by it, so another world can be opened
+	fn quit_to_menu(&mut self) {
+		let world = match self.world.take() {
+			Some(world) => world,
+			None => return,
+		};
+		if let Err(error) = world.save_all() {
+			error!("could not save world: {:#}", error);
+		}
+
+		// the workers and the client hold references to the world, and so do its chunks
+		parallel::shutdown();
+		self.state = match mem::replace(&mut self.state, GameState::Switching) {
+			GameState::Loading { client, .. } | GameState::InGame(client) => {
+				let (window, renderer, ui) = client.close();
+				GameState::Menu(MenuScreen::from_parts(window, renderer, ui))
+			},
+			state => state,
+		};
+		world.close();
+
+		let world_weak = Arc::downgrade(&world);
+		drop(world);
+		if world_weak.upgrade().is_some() {
+			warn!("world is still referenced after returning to the menu, it will not be freed");
+		}
+	}
+
+	pub fn input(&mut self, event: &WindowEvent) {
+		// nothing can be done in the world until it has loaded
+		if let GameState::InGame(client) = &mut self.state {
+			client.input(event);
+		}
+	}
+
+	// time since the last frame, and starts timing the next one
+	fn frame_delta(&mut self) -> Duration {
+		let current_time = Instant::now();
+		current_time - mem::replace(&mut self.last_frame_time, current_time)
+	}
+
+	// renders a frame, this must only be called for RedrawRequested
+	fn frame_update(&mut self) {
+		let delta = self.frame_delta();
+		self.frames_rendered.count(1, self.last_frame_time);
+		match &mut self.state {
+			GameState::Menu(menu) => menu.frame_update(),
+			GameState::Loading { client, .. } | GameState::InGame(client) => client.frame_update(delta),
+			GameState::Switching => (),
+		}
+	}
+
+	// the next frame is rendered once the event loop gets to it
+	fn request_redraw(&mut self) {
+		self.redraw_pending = true;
+		match &self.state {
+			GameState::Menu(menu) => menu.request_redraw(),
+			GameState::Loading { client, .. } | GameState::InGame(client) => client.request_redraw(),
+			GameState::Switching => (),
+		}
+	}
+
+	fn resize(&mut self, new_window_size: PhysicalSize<u32>) {
+		match &mut self.state {
+			GameState::Menu(menu) => menu.resize(new_window_size),
+			GameState::Loading { client, .. } | GameState::InGame(client) => client.resize(new_window_size),
+			GameState::Switching => (),
+		}
+	}
+
+	// runs the world ticks which are due, and a physics update if one is due
+	fn try_physics_update(&mut self) -> ControlFlow {
+		let current_time = Instant::now();
+		let time_delta = current_time - self.last_update_time;
+
+		if let Some(world) = &self.world {
+			for tick in self.tick_clock.update(current_time) {
+				world.tick(tick);
+			}
+			debug_display("Ticks per Second", &self.tick_clock.ticks_per_second());
+			debug_display("Current Tick", &world.current_tick());
+			debug_string("Block Updates", format!(
+				"{} scheduled, {} neighbor, {} dropped",
+				world.scheduled_update_count(),
+				world.neighbor_update_count(),
+				world.dropped_neighbor_update_count(),
+			));
+			if let Some(progress) = world.pregen_progress() {
+				let time_left = match progress.time_left {
+					Some(time_left) => format!("{}s left", time_left.as_secs()),
+					None => String::from("estimating time left"),
+				};
+				debug_string("Pregeneration", format!("{} / {} chunks, {}", progress.done, progress.total, time_left));
+			}
+		}
+
+		if let GameState::InGame(client) = &self.state {
+			if client.benchmark_finished() {
+				return ControlFlow::Exit;
+			}
+		}
+
+		let physics_due = time_delta > self.frame_time;
+		if physics_due {
+			self.physics_update(time_delta);
+			self.last_update_time = current_time;
+		}
+		self.physics_steps.count(physics_due as u32, current_time);
+		debug_string("Frames Rendered / Physics Steps", format!(
+			"{} / {} per second",
+			self.frames_rendered.per_second(),
+			self.physics_steps.per_second(),
+		));
+
+		self.control_flow()
+	}
+
+	// polls while a frame is waiting to be rendered, otherwise sleeps until the next physics update or world tick
+	fn control_flow(&self) -> ControlFlow {
+		if self.redraw_pending {
+			return ControlFlow::Poll;
+		}
+
+		let next_update_time = self.last_update_time + self.frame_time;
+		match self.world {
+			Some(_) => ControlFlow::WaitUntil(next_update_time.min(self.tick_clock.next_tick_time())),
+			None => ControlFlow::WaitUntil(next_update_time),
+		}
+	}
+
+	// nothing is rendered here, frames are only rendered when a redraw is requested
+	fn physics_update(&mut self, time_delta: Duration) {
+		let tick_alpha = self.tick_clock.alpha();
+		match &mut self.state {
+			GameState::Menu(menu) => {
+				if let Some((world, pregen_radius)) = menu.handle_action() {
+					self.start_world(world, pregen_radius);
+				}
+			},
+			GameState::Loading { client, world_name } => {
+				client.physics_update(time_delta, tick_alpha);
+				if client.update_loading(world_name) {
+					self.state = match mem::replace(&mut self.state, GameState::Switching) {
+						GameState::Loading { client, .. } => GameState::InGame(client),
+						_ => unreachable!(),
+					};
+				}
+			},
+			GameState::InGame(client) => client.physics_update(time_delta, tick_alpha),
+			GameState::Switching => (),
+		}
+	}
+
+	// must be called once the event loop is exiting
+	pub fn exit(&mut self) {
+		if let GameState::Loading { client, .. } | GameState::InGame(client) = &mut self.state {
+			client.exit();
+		}
+		if let Some(world) = &self.world {
+			if let Err(error) = world.save_all() {
+				error!("could not save world: {:#}", error);
+			}
+		}
+	}
+
+	pub fn event_update(&mut self, event: Event<()>) -> ControlFlow {
+		match &mut self.state {
+			GameState::Menu(menu) => menu.handle_event(&event),
+			GameState::Loading { client, .. } => client.handle_event(&event),
+			GameState::InGame(client) => {
+				client.gamepad_update();
+				client.handle_event(&event);
+			},
+			GameState::Switching => (),
+		}
+
+		match event {
+			// every batch of events ends with this, so it happens at least once per frame
+			// the next frame is requested right away, so there is a frame after every physics update and input event
+			Event::MainEventsCleared => match self.try_physics_update() {
+				ControlFlow::Exit => ControlFlow::Exit,
+				_ => {
+					self.request_redraw();
+					self.control_flow()
+				},
+			},
+			Event::RedrawRequested(window_id) if window_id == self.window_id => {
+				self.frame_update();
+				self.redraw_pending = false;
+				self.control_flow()
+			},
+			Event::WindowEvent {
+				ref event,
+				window_id,
+			} if window_id == self.window_id => {
+				match event {
+					WindowEvent::CloseRequested => return ControlFlow::Exit,
+					// escape closes the console, leaves the world, and closes the game from the menu
+					WindowEvent::KeyboardInput {
+						input:
+							KeyboardInput {
+								state: ElementState::Pressed,
+								virtual_keycode: Some(VirtualKeyCode::Escape),
+								..
+							},
+						..
+					} => match &mut self.state {
+						GameState::Menu(_) => return ControlFlow::Exit,
+						GameState::InGame(client) if client.console_open() => client.close_console(),
+						_ => self.quit_to_menu(),
+					},
+					WindowEvent::Resized(new_size) => self.resize(*new_size),
+					WindowEvent::ScaleFactorChanged { new_inner_size, .. } => self.resize(**new_inner_size),
+					_ => self.input(event),
+				}
+				self.control_flow()
+			},
+			_ => self.control_flow(),
+		}
+	}
+}
+
+// the existing benchmark world, or a new one if there isn't one
+fn benchmark_world() -> anyhow::Result<WorldEntry> {
+	let worlds_directory = Path::new(WORLDS_DIRECTORY);
+	match world_list::list_worlds(worlds_directory).into_iter().find(|world| world.meta.name == BENCHMARK_WORLD) {
+		Some(world) => Ok(world),
+		None => world_list::create_world(worlds_directory, BENCHMARK_WORLD, 0, GeneratorType::Normal, GeneratorOptions::default()),
+	}
+}
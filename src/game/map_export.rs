@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Context};
+use image::{Rgb, RgbImage};
+
+use crate::prelude::*;
+use super::worldgen::{WorldGenerator, SurfaceColumn};
+use super::block::BlockTrait;
+
+// exported maps are saved here, relative to the working directory
+pub const MAP_DIRECTORY: &str = "maps";
+// maps are 2 * radius + 1 pixels wide, so bigger ones would use too much memory
+pub const MAX_EXPORT_RADIUS: i32 = 2048;
+// change in brightness for each block a column is higher than the one north west of it
+const SLOPE_SHADING: f32 = 0.08;
+const MIN_SHADE: f32 = 0.6;
+const MAX_SHADE: f32 = 1.4;
+
+// color of the top block of the column, lit as if the sun was in the north west
+fn column_color(column: SurfaceColumn, north_west_height: i32) -> Rgb<u8> {
+	let color = column.biome.get_block_at_depth(0, column.layer_jitter).map_color();
+	let shade = (1.0 + (column.height - north_west_height) as f32 * SLOPE_SHADING).clamp(MIN_SHADE, MAX_SHADE);
+	Rgb(color.map(|channel| (channel as f32 * shade).min(255.0) as u8))
+}
+
+// how far along a surface map is, shared with the thread making it
+#[derive(Debug, Default)]
+pub struct MapProgress {
+	pub rows_done: AtomicUsize,
+	// when set, the map stops being made after the current row
+	pub cancelled: AtomicBool,
+}
+
+// a map of the naturally generated surface around the center, with one pixel per column
+// north (-z) is up, like on the minimap, and rows_done is incremented after each row of pixels
+// this only queries the generator, so no chunks are generated and changes to the world aren't shown
+// returns none if the map was cancelled before it was finished
+pub fn surface_map(generator: &WorldGenerator, center_x: i32, center_z: i32, radius: i32, progress: &MapProgress) -> Option<RgbImage> {
+	let size = 2 * radius + 1;
+	let min_x = center_x - radius;
+	let min_z = center_z - radius;
+
+	// each row has an extra column to the west, so every pixel has a north west neighbor
+	let row_columns = |z: i32| (min_x - 1..min_x + size)
+		.map(|x| generator.surface_column_uncached(x, z))
+		.collect::<Vec<_>>();
+
+	let mut image = RgbImage::new(size as u32, size as u32);
+	let mut north_row = row_columns(min_z - 1);
+	for pixel_z in 0..size {
+		if progress.cancelled.load(Ordering::Relaxed) {
+			return None;
+		}
+
+		let row = row_columns(min_z + pixel_z);
+		for pixel_x in 0..size {
+			let color = column_color(row[pixel_x as usize + 1], north_row[pixel_x as usize].height);
+			image.put_pixel(pixel_x as u32, pixel_z as u32, color);
+		}
+
+		north_row = row;
+		progress.rows_done.fetch_add(1, Ordering::Relaxed);
+	}
+
+	Some(image)
+}
+
+// a surface map being made and saved on its own thread, so it doesn't block the game
+pub struct MapExport {
+	path: PathBuf,
+	total_rows: usize,
+	progress: Arc<MapProgress>,
+	thread: JoinHandle<Result<()>>,
+}
+
+impl MapExport {
+	pub fn start(generator: WorldGenerator, center_x: i32, center_z: i32, radius: i32) -> Result<Self> {
+		if !(1..=MAX_EXPORT_RADIUS).contains(&radius) {
+			return Err(anyhow!("radius must be between 1 and {}", MAX_EXPORT_RADIUS));
+		}
+
+		let path = Path::new(MAP_DIRECTORY).join(format!("map_{}_{}_r{}.png", center_x, center_z, radius));
+		let progress = Arc::new(MapProgress::default());
+
+		let thread_path = path.clone();
+		let thread_progress = progress.clone();
+		let thread = thread::Builder::new()
+			.name(String::from("map export"))
+			.spawn(move || {
+				// exports are never cancelled
+				let image = surface_map(&generator, center_x, center_z, radius, &thread_progress)
+					.ok_or_else(|| anyhow!("map export was cancelled"))?;
+				fs::create_dir_all(MAP_DIRECTORY)?;
+				image.save(&thread_path).with_context(|| format!("could not save {}", thread_path.display()))
+			})?;
+
+		Ok(MapExport {
+			path,
+			total_rows: (2 * radius + 1) as usize,
+			progress,
+			thread,
+		})
+	}
+
+	// rows of pixels done, and the total number of rows
+	pub fn progress(&self) -> (usize, usize) {
+		(self.progress.rows_done.load(Ordering::Relaxed), self.total_rows)
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.thread.is_finished()
+	}
+
+	// waits for the export to finish, and returns where the map was saved
+	pub fn finish(self) -> Result<PathBuf> {
+		self.thread.join().map_err(|_| anyhow!("map export panicked"))??;
+		Ok(self.path)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::worldgen::{GeneratorType, GeneratorOptions};
+
+	#[test]
+	fn flat_map_has_unshaded_surface_colors() {
+		let generator = WorldGenerator::new(0, GeneratorType::Flat, GeneratorOptions::default());
+		let progress = MapProgress::default();
+		let image = surface_map(&generator, 100, -20, 8, &progress).unwrap();
+
+		assert_eq!(image.dimensions(), (17, 17));
+		assert_eq!(progress.rows_done.load(Ordering::Relaxed), 17);
+
+		// the top left pixel is the north west corner
+		let column = generator.surface_column(92, -28);
+		assert_eq!(*image.get_pixel(0, 0), Rgb(column.biome.get_block_at_depth(0, column.layer_jitter).map_color()));
+	}
+
+	#[test]
+	fn higher_columns_are_brighter() {
+		let generator = WorldGenerator::new(0, GeneratorType::Normal, GeneratorOptions::default());
+		let column = generator.surface_column(0, 0);
+		let base = column_color(column, column.height);
+		let lit = column_color(column, column.height - 2);
+		let shadowed = column_color(column, column.height + 2);
+
+		assert_eq!(base, Rgb(column.biome.get_block_at_depth(0, column.layer_jitter).map_color()));
+		assert!(lit.0.iter().zip(base.0).all(|(lit, base)| *lit >= base));
+		assert!(shadowed.0.iter().zip(base.0).all(|(shadowed, base)| *shadowed <= base));
+	}
+
+	#[test]
+	fn cancelled_maps_stop_early() {
+		let generator = WorldGenerator::new(0, GeneratorType::Flat, GeneratorOptions::default());
+		let progress = MapProgress::default();
+		progress.cancelled.store(true, Ordering::Relaxed);
+
+		assert!(surface_map(&generator, 0, 0, 8, &progress).is_none());
+		assert_eq!(progress.rows_done.load(Ordering::Relaxed), 0);
+	}
+}
@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use winit::{window::Window, event::Event, dpi::PhysicalSize};
+
+use crate::render::Renderer;
+use super::client::Client;
+use super::world::World;
+use super::world_list::{WorldEntry, list_worlds, create_world, delete_world, WORLDS_DIRECTORY};
+use super::ui::{MineConeUi, MainMenu, MenuAction};
+
+// the window, renderer, and ui before any world is loaded, they are given to the client once one is
+pub struct MenuScreen {
+	window: Window,
+	renderer: Renderer,
+	ui: MineConeUi,
+}
+
+impl MenuScreen {
+	pub fn new(window: Window) -> Self {
+		let renderer = pollster::block_on(Renderer::new(&window));
+		let ui = MineConeUi::new(&window, &renderer);
+		Self::from_parts(window, renderer, ui)
+	}
+
+	// used when returning to the menu from a world
+	pub fn from_parts(window: Window, renderer: Renderer, mut ui: MineConeUi) -> Self {
+		ui.open_main_menu(MainMenu::new(list_worlds(Path::new(WORLDS_DIRECTORY))));
+
+		MenuScreen {
+			window,
+			renderer,
+			ui,
+		}
+	}
+
+	pub fn handle_event(&mut self, event: &Event<()>) {
+		self.ui.handle_event(event);
+	}
+
+	pub fn resize(&mut self, new_window_size: PhysicalSize<u32>) {
+		self.renderer.resize(new_window_size);
+	}
+
+	pub fn request_redraw(&self) {
+		self.window.request_redraw();
+	}
+
+	pub fn frame_update(&mut self) {
+		// the menu covers the whole screen, so nothing else is drawn
+		self.renderer.start_render_pass();
+		self.ui.frame_update(&self.window, &self.renderer);
+		self.renderer.finish_render_pass();
+	}
+
+	// does what was chosen in the menu, and returns the world to play if one was chosen or created,
+	// along with the radius around spawn to pregenerate, which is 0 unless the world was just created
+	pub fn handle_action(&mut self) -> Option<(WorldEntry, i32)> {
+		let worlds_directory = Path::new(WORLDS_DIRECTORY);
+
+		match self.ui.take_menu_action()? {
+			MenuAction::Play(world) => Some((world, 0)),
+			MenuAction::Create { name, seed, generator, options, pregen_radius } => match create_world(worlds_directory, &name, seed, generator, options) {
+				Ok(world) => Some((world, pregen_radius)),
+				Err(error) => {
+					self.set_error(format!("Could not create world: {:#}", error));
+					None
+				},
+			},
+			MenuAction::Delete(world) => {
+				if let Err(error) = delete_world(&world) {
+					self.set_error(format!("{:#}", error));
+				}
+				if let Some(main_menu) = self.ui.main_menu_mut() {
+					main_menu.set_worlds(list_worlds(worlds_directory));
+				}
+				None
+			},
+		}
+	}
+
+	pub fn set_error(&mut self, error: String) {
+		if let Some(main_menu) = self.ui.main_menu_mut() {
+			main_menu.set_error(error);
+		}
+	}
+
+	pub fn into_client(mut self, world: Arc<World>, benchmark_path: Option<PathBuf>) -> Client {
+		self.ui.close_main_menu();
+		Client::new(self.window, self.renderer, self.ui, world, benchmark_path)
+	}
+}
@@ -0,0 +1,281 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use glam::{IVec3, Vec3};
+
+use crate::prelude::*;
+use super::block::{BlockFace, BlockFaceMesh, TextureIndex, num_textures};
+use super::chunk::{Chunk, CHUNK_SIZE};
+
+// quads further than this from a plane, or from the expanded chunk box, are counted as violations
+const EPSILON: f32 = 0.001;
+// how far into a block's face the point checked for coverage is, quads are only grown by much less than this
+// it is less than the height of the shortest block, so lowered tops still cover it
+const COVERAGE_INSET: f32 = 0.05;
+
+// which checks are run after each chunk mesh update, set with the /meshcheck command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshValidation {
+	Off,
+	// checks each quad on its own, and for duplicates
+	Quads,
+	// also checks every exposed face is covered exactly once, which is much slower
+	Full,
+}
+
+impl MeshValidation {
+	pub const ALL: [MeshValidation; 3] = [MeshValidation::Off, MeshValidation::Quads, MeshValidation::Full];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Off => "off",
+			Self::Quads => "quads",
+			Self::Full => "full",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|validation| validation.name() == name)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+	// another quad in the chunk has the same corners and face, they z fight
+	DuplicateQuad,
+	InvalidTexture(TextureIndex),
+	NotCoplanar,
+	// a vertex is more than a block outside the chunk
+	OutOfBounds,
+	// an exposed face of the block has no quad covering it, so there is a hole
+	MissingFace(BlockPos),
+	// the face of the block is covered by this many quads, but it should be covered by 1, or 0 if it isn't exposed
+	ExtraFace(BlockPos, u32),
+}
+
+// block positions in violations are in world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshViolation {
+	pub chunk: ChunkPos,
+	pub face: BlockFace,
+	pub violation: Violation,
+}
+
+// the axis along the face's normal, and the 2 axes in its plane
+fn face_axes(face: BlockFace) -> (usize, usize, usize) {
+	match face {
+		BlockFace::XPos | BlockFace::XNeg => (0, 1, 2),
+		BlockFace::YPos | BlockFace::YNeg => (1, 0, 2),
+		BlockFace::ZPos | BlockFace::ZNeg => (2, 0, 1),
+	}
+}
+
+fn quad_bounds(quad: &BlockFaceMesh) -> (Vec3, Vec3) {
+	let positions = quad.0.map(|vertex| vertex.position());
+	let min = positions.iter().fold(Vec3::splat(f32::INFINITY), |a, b| a.min(*b));
+	let max = positions.iter().fold(Vec3::splat(f32::NEG_INFINITY), |a, b| a.max(*b));
+	(min, max)
+}
+
+// checks each quad on its own, and that no 2 quads with the same face have the same corners
+// quads are the faces of the chunk at block_position facing the face
+pub fn check_quads<'a, I>(chunk: ChunkPos, block_position: BlockPos, face: BlockFace, quads: I, out: &mut Vec<MeshViolation>)
+	where I: IntoIterator<Item = &'a BlockFaceMesh> {
+	let mut violation = |violation| out.push(MeshViolation {
+		chunk,
+		face,
+		violation,
+	});
+
+	let chunk_min = (block_position - BlockPos::splat(1)).0.as_vec3() - Vec3::splat(EPSILON);
+	let chunk_max = (block_position + BlockPos::splat(CHUNK_SIZE as i32 + 1)).0.as_vec3() + Vec3::splat(EPSILON);
+	let mut corners = FxHashSet::default();
+
+	for quad in quads {
+		let positions = quad.0.map(|vertex| vertex.position());
+
+		if !corners.insert(positions.map(|position| position.to_array().map(f32::to_bits))) {
+			violation(Violation::DuplicateQuad);
+		}
+
+		if let Some(vertex) = quad.0.iter().find(|vertex| !(0..num_textures()).contains(&vertex.texture_index())) {
+			violation(Violation::InvalidTexture(vertex.texture_index()));
+		}
+
+		let normal = (positions[1] - positions[0]).cross(positions[2] - positions[0]).normalize_or_zero();
+		if normal == Vec3::ZERO || (positions[3] - positions[0]).dot(normal).abs() > EPSILON {
+			violation(Violation::NotCoplanar);
+		}
+
+		if positions.iter().any(|position| position.cmplt(chunk_min).any() || position.cmpgt(chunk_max).any()) {
+			violation(Violation::OutOfBounds);
+		}
+	}
+}
+
+// compares the quads of each layer with the faces which should be drawn, found by checking every block
+// faces of blocks next to chunks which aren't loaded are skipped, since the mesher doesn't know if they are exposed
+fn check_coverage(chunk: &Chunk, face: BlockFace, layers: &[Vec<BlockFaceMesh>; CHUNK_SIZE], out: &mut Vec<MeshViolation>) {
+	let (normal_axis, u_axis, v_axis) = face_axes(face);
+	let block_position = chunk.block_position();
+
+	// how many quads cover each block's face, keyed by the block's chunk local position
+	let mut coverage = FxHashMap::<BlockPos, u32>::default();
+	for (index, layer) in layers.iter().enumerate() {
+		for quad in layer {
+			let (min, max) = quad_bounds(quad);
+			let first = |axis: usize| (min[axis] + COVERAGE_INSET).floor() as i32;
+			let last = |axis: usize| (max[axis] - COVERAGE_INSET).ceil() as i32 - 1;
+
+			for u in first(u_axis)..=last(u_axis) {
+				for v in first(v_axis)..=last(v_axis) {
+					let mut block = IVec3::ZERO;
+					block[normal_axis] = index as i32;
+					block[u_axis] = u - block_position[u_axis];
+					block[v_axis] = v - block_position[v_axis];
+					*coverage.entry(BlockPos(block)).or_insert(0) += 1;
+				}
+			}
+		}
+	}
+
+	let mut violation = |violation| out.push(MeshViolation {
+		chunk: chunk.chunk_position(),
+		face,
+		violation,
+	});
+
+	for x in 0..CHUNK_SIZE as i32 {
+		for y in 0..CHUNK_SIZE as i32 {
+			for z in 0..CHUNK_SIZE as i32 {
+				let local = BlockPos::new(x, y, z);
+				let count = coverage.remove(&local).unwrap_or(0);
+				let block = chunk.get_block(local);
+				let exposed = if block.is_air() {
+					false
+				} else {
+					match chunk.face_visibility(&block, local, face) {
+						Some(exposed) => exposed,
+						None => continue,
+					}
+				};
+
+				match (exposed, count) {
+					(true, 0) => violation(Violation::MissingFace(local + block_position)),
+					(true, 1) | (false, 0) => (),
+					(_, count) => violation(Violation::ExtraFace(local + block_position, count)),
+				}
+			}
+		}
+	}
+
+	// quads covering faces outside of the chunk
+	for (local, count) in coverage {
+		violation(Violation::ExtraFace(local + block_position, count));
+	}
+}
+
+// runs the checks for the validation on the chunk's current mesh
+pub fn validate_chunk_mesh(chunk: &Chunk, validation: MeshValidation) -> Vec<MeshViolation> {
+	let mut out = Vec::new();
+	if validation == MeshValidation::Off {
+		return out;
+	}
+
+	chunk.with_mesh(|mesh| {
+		for face in BlockFace::iter() {
+			let layers = &mesh[Into::<usize>::into(face)];
+			check_quads(chunk.chunk_position(), chunk.block_position(), face, layers.iter().flatten(), &mut out);
+			if validation == MeshValidation::Full {
+				check_coverage(chunk, face, layers, &mut out);
+			}
+		}
+	});
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::block::{OcclusionCorners, Stone};
+	use crate::game::chunk::LoadedChunk;
+	use crate::game::world::World;
+	use crate::game::worldgen::{WorldGenerator, GeneratorType, GeneratorOptions};
+
+	const NO_OCCLUSION: OcclusionCorners = OcclusionCorners {
+		tl: 0.0,
+		tr: 0.0,
+		bl: 0.0,
+		br: 0.0,
+	};
+
+	#[test]
+	fn bad_quads_are_found() {
+		let chunk = ChunkPos::new(1, 0, -1);
+		let block_position = chunk.as_block_pos();
+		let quad = |texture_index, block: BlockPos| BlockFaceMesh::from_corners(BlockFace::YPos, texture_index, block, block, NO_OCCLUSION);
+
+		let mut bent = quad(0, block_position);
+		bent.0[3] = BlockFaceMesh::from_corners(BlockFace::YPos, 0, block_position + BlockPos::new(0, 1, 0), block_position, NO_OCCLUSION).0[3];
+		let quads = [
+			quad(0, block_position),
+			quad(1, block_position + BlockPos::new(1, 0, 0)),
+			// same corners as the first
+			quad(2, block_position),
+			quad(num_textures(), block_position + BlockPos::new(2, 0, 0)),
+			quad(0, block_position + BlockPos::new(40, 0, 0)),
+			bent,
+		];
+
+		let mut out = Vec::new();
+		check_quads(chunk, block_position, BlockFace::YPos, &quads, &mut out);
+		let violations = out.iter().map(|violation| violation.violation).collect::<Vec<_>>();
+		assert_eq!(violations, vec![
+			Violation::DuplicateQuad,
+			Violation::InvalidTexture(num_textures()),
+			Violation::OutOfBounds,
+			Violation::NotCoplanar,
+		]);
+		assert!(out.iter().all(|violation| violation.chunk == chunk && violation.face == BlockFace::YPos));
+	}
+
+	// every face of generated chunks, surrounded by loaded chunks, is meshed exactly once
+	#[test]
+	fn generated_chunks_have_valid_meshes() {
+		let world = World::new_test().unwrap();
+		let generator = WorldGenerator::new(1234, GeneratorType::Normal, GeneratorOptions::default());
+		let surface_chunk = BlockPos::new(0, generator.surface_column(0, 0).height, 0).as_chunk_pos();
+
+		let min_chunk = surface_chunk - ChunkPos::new(1, 2, 1);
+		let max_chunk = surface_chunk + ChunkPos::new(2, 2, 2);
+		for chunk in ChunkPos::iter_range(min_chunk, max_chunk) {
+			world.chunks.insert(chunk, generator.generate_chunk(world.clone(), chunk));
+		}
+
+		let mut checked_faces = 0;
+		for chunk in ChunkPos::iter_range(min_chunk + ChunkPos::splat(1), max_chunk - ChunkPos::splat(1)) {
+			let chunk = world.chunks.get(&chunk).unwrap();
+			chunk.chunk.chunk_mesh_update();
+			let violations = validate_chunk_mesh(&chunk.chunk, MeshValidation::Full);
+			assert!(violations.is_empty(), "violations in {:?}: {:?}", chunk.chunk.chunk_position(), &violations[..violations.len().min(10)]);
+			checked_faces += chunk.chunk.get_chunk_mesh().unwrap().len();
+		}
+		assert!(checked_faces > 0);
+
+		// a block added without remeshing has no faces
+		{
+			let chunk: &LoadedChunk = &world.chunks.get(&surface_chunk).unwrap();
+			let on_top = (0..CHUNK_SIZE as i32 - 1)
+				.flat_map(|y| (0..CHUNK_SIZE as i32).map(move |x| BlockPos::new(x, y, 8)))
+				.find(|local| {
+					let block = chunk.chunk.get_block(*local);
+					!block.is_air() && chunk.chunk.face_visibility(&block, *local, BlockFace::YPos) == Some(true)
+				})
+				.unwrap() + BlockPos::new(0, 1, 0);
+			chunk.chunk.set_block(on_top, Stone::new().into());
+			let violations = validate_chunk_mesh(&chunk.chunk, MeshValidation::Full);
+			let hole = Violation::MissingFace(on_top + chunk.chunk.block_position());
+			assert!(violations.iter().any(|violation| violation.face == BlockFace::YPos && violation.violation == hole), "{:?}", violations);
+		}
+
+		world.close();
+	}
+}
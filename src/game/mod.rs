@@ -1,112 +1,43 @@
-use std::time::{Instant, Duration};
-use std::sync::Arc;
-
-use winit::window::WindowId;
-use winit::{
-	window::Window,
-	event::*,
-	event_loop::ControlFlow,
-	dpi::PhysicalSize,
-};
-
-use world::World;
-use client::Client;
-
+pub mod crash;
+pub use crash::install_panic_hook;
+#[cfg(feature = "render")]
+mod game_loop;
+#[cfg(feature = "render")]
+pub use game_loop::Game;
+#[cfg(feature = "render")]
 mod client;
+#[cfg(feature = "render")]
+mod menu;
+#[cfg(feature = "render")]
 mod ui;
-pub use ui::{debug_string, debug_display};
-mod player;
-mod parallel;
-mod world;
-mod worldgen;
-mod chunk;
+#[cfg(feature = "render")]
+pub use ui::{debug_string, debug_display, debug_section_string};
+pub mod player;
+#[cfg(feature = "render")]
+mod settings;
+pub mod config;
+pub mod parallel;
+pub mod tick;
+pub use tick::DEFAULT_TICK_RATE;
+pub mod world;
+pub mod world_list;
+mod world_state;
+pub mod region;
+mod save_format;
+pub mod map_export;
+#[cfg(feature = "render")]
+mod world_preview;
+pub mod commands;
+pub mod block_stats;
+pub mod crafting;
+mod block_update;
+mod block_entity;
+pub mod worldgen;
+pub mod chunk;
+mod mesh_validation;
 pub use chunk::CHUNK_SIZE;
-mod render_zone;
+pub mod render_zone;
 mod entity;
-mod block;
-pub use block::{BlockFace, BlockVertex, num_textures};
+pub mod block;
+pub use block::{BlockFace, BlockVertex};
 pub mod types;
-
-// Game is in charge of calling frame_update and physics_update on the correct intervals
-// and dispatching input events
-pub struct Game {
-	window_id: WindowId,
-	frame_time: Duration,
-	last_update_time: Instant,
-	world: Arc<World>,
-	client: Client,
-}
-
-impl Game {
-	pub fn new(framerate: u64, window: Window) -> Self {
-		let frame_time = Duration::from_micros(1_000_000 / framerate);
-
-		let world = World::new_test().expect("could not load world");
-		parallel::init(world.clone(), num_cpus::get() - 1);
-
-		let window_id = window.id();
-
-		let client = Client::new(window, world.clone());
-
-		Self {
-			window_id,
-			frame_time,
-			last_update_time: Instant::now() - frame_time,
-			world,
-			client,
-		}
-	}
-
-	pub fn input(&mut self, event: &WindowEvent) {
-		self.client.input(event);
-	}
-
-	// TODO: implement correctly, with redrawing every so often
-	pub fn frame_update(&mut self, new_window_size: Option<PhysicalSize<u32>>) {
-		self.client.frame_update(new_window_size);
-	}
-
-	pub fn try_physics_update(&mut self) -> ControlFlow {
-		let current_time = Instant::now();
-		let time_delta = current_time - self.last_update_time;
-
-		if time_delta > self.frame_time {
-			self.client.physics_update(time_delta);
-			self.last_update_time = current_time;
-		}
-		ControlFlow::WaitUntil(self.last_update_time + self.frame_time)
-	}
-
-	pub fn event_update(&mut self, event: Event<()>) -> ControlFlow {
-		self.client.handle_event(&event);
-
-		match event {
-			Event::RedrawRequested(window_id) if window_id == self.window_id => {
-				self.frame_update(None);
-				self.try_physics_update()
-			},
-			Event::WindowEvent {
-				ref event,
-				window_id,
-			} if window_id == self.window_id => {
-				match event {
-					WindowEvent::CloseRequested
-					| WindowEvent::KeyboardInput {
-						input:
-							KeyboardInput {
-								state: ElementState::Pressed,
-								virtual_keycode: Some(VirtualKeyCode::Escape),
-								..
-							},
-						..
-					} => return ControlFlow::Exit,
-					WindowEvent::Resized(new_size) => self.frame_update(Some(*new_size)),
-					WindowEvent::ScaleFactorChanged { new_inner_size, .. } => self.frame_update(Some(**new_inner_size)),
-					_ => self.input(event),
-				}
-				self.try_physics_update()
-			},
-			_ => self.try_physics_update(),
-		}
-	}
-}
@@ -1,22 +1,60 @@
-use std::lazy::SyncLazy;
+use std::fmt;
+use std::mem;
 use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use crossbeam::{
 	deque::{Injector, Steal},
 	queue::SegQueue,
 };
+use tracing::Span;
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock, const_mutex, const_rwlock};
 
 use crate::prelude::*;
-use super::{world::World, BlockFace};
-use super::chunk::{VisitedBlockMap, CHUNK_SIZE};
+use super::{world::World, BlockFace, crash::catch_panic};
+use super::chunk::{VisitedBlockMap, border_layer};
+use super::block_stats::BlockStats;
+use super::entity::{Entity, EntityId};
 
-static TASK_QUEUE: SyncLazy<Injector<Task>> = SyncLazy::new(|| Injector::new());
+static TASK_QUEUE: Lazy<Injector<QueuedTask>> = Lazy::new(Injector::new);
+// tasks nobody is waiting on, workers only take these when TASK_QUEUE is empty
+static BACKGROUND_QUEUE: Lazy<Injector<QueuedTask>> = Lazy::new(Injector::new);
 static COMPLETED_TASKS: SegQueue<Task> = SegQueue::new();
+// tasks which panicked on every attempt, which the world has not been notified of yet
+static NEW_FAILED_TASKS: SegQueue<Task> = SegQueue::new();
+// every task that has failed this session
+static FAILED_TASKS: Mutex<Vec<Task>> = const_mutex(Vec::new());
+static WORKERS: RwLock<Vec<Arc<WorkerStatus>>> = const_rwlock(Vec::new());
+static WORKER_THREADS: Mutex<Vec<JoinHandle<()>>> = const_mutex(Vec::new());
+// tells the workers to exit once their current task is done
+static STOP_WORKERS: AtomicBool = AtomicBool::new(false);
+// heartbeat times are stored as milliseconds since this instant
+static START_TIME: Lazy<Instant> = Lazy::new(Instant::now);
+// activity is only recorded while the task activity view is open
+static TRACK_ACTIVITY: AtomicBool = AtomicBool::new(false);
+static CHUNK_ACTIVITY: Lazy<FxDashMap<ChunkPos, ChunkActivity>> = Lazy::new(FxDashMap::default);
+// latencies of recently completed tasks, the workers' buffers are merged into this when it is read
+static LATENCY_WINDOW: Mutex<LatencyWindow> = const_mutex(LatencyWindow::new());
+// mesh tasks skipped because no player could see their chunks by the time they ran
+static STALE_CHUNK_MESH_TASKS: AtomicU64 = AtomicU64::new(0);
+static STALE_MESH_FACE_TASKS: AtomicU64 = AtomicU64::new(0);
+
+// a task that panics is retried this many times before it is marked as failed
+const TASK_RETRIES: usize = 1;
+// a task running for longer than this is reported as stuck
+const STUCK_TASK_TIME: Duration = Duration::from_secs(10);
+// a worker that hasn't reported a heartbeat in this long while not running a task is reported as unresponsive
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(1);
+// completed activity is kept for this long so the task activity view can fade it out
+pub const COMPLETED_ACTIVITY_TIME: Duration = Duration::from_secs(1);
+// task latency percentiles are over the tasks completed in this long
+pub const LATENCY_WINDOW_TIME: Duration = Duration::from_secs(30);
 
 // TODO: allow easy way of chaining tasks
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Task {
 	// generate a mesh for the given chunk
 	ChunkMesh(ChunkPos),
@@ -27,92 +65,553 @@ pub enum Task {
 	},
 	// use world generate to generate chunk
 	GenerateChunk(ChunkPos),
+	// generate the chunk and save it to its region without loading it, see World::pregenerate_chunk
+	PregenerateChunk(ChunkPos),
 	UnloadChunks {
 		min_chunk: ChunkPos,
 		max_chunk: ChunkPos,
 	},
+	// counts the blocks of each type in the loaded chunks in the area, or every loaded chunk if there is no area
+	// the stats are none until the task has run, the completed task carries them back to the world
+	Analyze {
+		area: Option<ChunkBox>,
+		stats: Option<Box<BlockStats>>,
+	},
+	// always panics, used to test task supervision
+	#[cfg(test)]
+	TestPanic(ChunkPos),
+}
+
+impl Task {
+	// span covering the execution of this task, used to correlate logs from different worker threads
+	fn span(&self) -> Span {
+		match self {
+			Self::ChunkMesh(chunk) => debug_span!("task", kind = "chunk mesh", chunk = ?chunk),
+			Self::ChunkMeshFace { min_chunk, max_chunk, face } =>
+				debug_span!("task", kind = "chunk mesh face", ?min_chunk, ?max_chunk, ?face),
+			Self::GenerateChunk(chunk) => debug_span!("task", kind = "generate chunk", chunk = ?chunk),
+			Self::PregenerateChunk(chunk) => debug_span!("task", kind = "pregenerate chunk", chunk = ?chunk),
+			Self::UnloadChunks { min_chunk, max_chunk } =>
+				debug_span!("task", kind = "unload chunks", ?min_chunk, ?max_chunk),
+			Self::Analyze { area, .. } => debug_span!("task", kind = "analyze", ?area),
+			#[cfg(test)]
+			Self::TestPanic(chunk) => debug_span!("task", kind = "test panic", chunk = ?chunk),
+		}
+	}
+
+	// chunks between the min and max chunk (not including max) are what this task works on,
+	// and the activity is what it is doing to them while it runs
+	fn activity(&self) -> Option<(ChunkPos, ChunkPos, ChunkActivity)> {
+		match self {
+			Self::ChunkMesh(chunk) => Some((*chunk, *chunk + ChunkPos::splat(1), ChunkActivity::Meshing)),
+			Self::ChunkMeshFace { min_chunk, max_chunk, .. } => Some((*min_chunk, *max_chunk, ChunkActivity::Meshing)),
+			Self::GenerateChunk(chunk) | Self::PregenerateChunk(chunk) =>
+				Some((*chunk, *chunk + ChunkPos::splat(1), ChunkActivity::Generating)),
+			_ => None,
+		}
+	}
+
+	// the tasks whose latency is measured
+	fn latency_kind(&self) -> Option<LatencyKind> {
+		match self {
+			Self::GenerateChunk(_) => Some(LatencyKind::GenerateChunk),
+			Self::ChunkMesh(_) => Some(LatencyKind::ChunkMesh),
+			_ => None,
+		}
+	}
+}
+
+// a task waiting in a queue, and when it was put there
+#[derive(Debug)]
+struct QueuedTask {
+	task: Task,
+	enqueued: Instant,
+}
+
+impl QueuedTask {
+	fn new(task: Task) -> Self {
+		QueuedTask {
+			task,
+			enqueued: Instant::now(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LatencyKind {
+	GenerateChunk,
+	ChunkMesh,
+}
+
+// how long a task took, recorded by the worker that ran it
+#[derive(Debug, Clone, Copy)]
+struct LatencySample {
+	kind: LatencyKind,
+	completed: Instant,
+	// from being queued to being completed
+	total: Duration,
+	// only the time spent running it, including any retries
+	execution: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyPercentiles {
+	pub p50: Duration,
+	pub p95: Duration,
+	pub max: Duration,
+}
+
+impl LatencyPercentiles {
+	// nearest rank percentiles, all 0 if there are no durations
+	fn of(mut durations: Vec<Duration>) -> Self {
+		if durations.is_empty() {
+			return LatencyPercentiles::default();
+		}
+		durations.sort_unstable();
+
+		let percentile = |percent: usize| durations[(percent * durations.len()).div_ceil(100).max(1) - 1];
+		LatencyPercentiles {
+			p50: percentile(50),
+			p95: percentile(95),
+			max: durations[durations.len() - 1],
+		}
+	}
+}
+
+impl fmt::Display for LatencyPercentiles {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"p50 {:.1}ms, p95 {:.1}ms, max {:.1}ms",
+			self.p50.as_secs_f64() * 1000.0,
+			self.p95.as_secs_f64() * 1000.0,
+			self.max.as_secs_f64() * 1000.0,
+		)
+	}
+}
+
+// latency of one kind of task over the last LATENCY_WINDOW_TIME
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaskLatency {
+	pub count: usize,
+	// from being queued to being completed
+	pub total: LatencyPercentiles,
+	pub execution: LatencyPercentiles,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaskLatencyStats {
+	pub generate_chunk: TaskLatency,
+	pub chunk_mesh: TaskLatency,
+}
+
+// mesh tasks skipped since the game started because their chunks were no longer in any player's render zone
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StaleTaskCounts {
+	pub chunk_mesh: u64,
+	pub chunk_mesh_face: u64,
+}
+
+// samples from the last LATENCY_WINDOW_TIME, only the reader of the stats locks it
+#[derive(Debug)]
+struct LatencyWindow {
+	samples: Vec<LatencySample>,
+}
+
+impl LatencyWindow {
+	const fn new() -> Self {
+		LatencyWindow {
+			samples: Vec::new(),
+		}
+	}
+
+	// samples are dropped once they are older than the window, now is passed in so tests can control time
+	fn stats(&mut self, new_samples: impl IntoIterator<Item = LatencySample>, now: Instant) -> TaskLatencyStats {
+		self.samples.extend(new_samples);
+		self.samples.retain(|sample| now.saturating_duration_since(sample.completed) <= LATENCY_WINDOW_TIME);
+
+		let latency = |kind| {
+			let samples = self.samples.iter().filter(|sample| sample.kind == kind);
+			TaskLatency {
+				count: samples.clone().count(),
+				total: LatencyPercentiles::of(samples.clone().map(|sample| sample.total).collect()),
+				execution: LatencyPercentiles::of(samples.map(|sample| sample.execution).collect()),
+			}
+		};
+		TaskLatencyStats {
+			generate_chunk: latency(LatencyKind::GenerateChunk),
+			chunk_mesh: latency(LatencyKind::ChunkMesh),
+		}
+	}
+}
+
+// what the task system is doing to a chunk, shown by the task activity view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkActivity {
+	QueuedGeneration,
+	Generating,
+	Meshing,
+	// time the last task on the chunk finished
+	Completed(Instant),
+}
+
+// what a worker thread is currently doing, used to find workers which have died or are stuck
+#[derive(Debug, Default)]
+struct WorkerStatus {
+	// milliseconds since START_TIME
+	last_heartbeat: AtomicU64,
+	current_task: Mutex<Option<(Task, Instant)>>,
+	// latencies recorded since the stats were last read, each worker has its own so they don't contend with each other
+	latency_samples: Mutex<Vec<LatencySample>>,
+}
+
+impl WorkerStatus {
+	fn heartbeat(&self) {
+		let now = START_TIME.elapsed().as_millis() as u64;
+		self.last_heartbeat.store(now, Ordering::Release);
+	}
+
+	fn heartbeat_age(&self) -> Duration {
+		let last_heartbeat = Duration::from_millis(self.last_heartbeat.load(Ordering::Acquire));
+		START_TIME.elapsed().saturating_sub(last_heartbeat)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub enum WorkerProblem {
+	// the worker has not reported a heartbeat while idle
+	Unresponsive {
+		worker: usize,
+		heartbeat_age: Duration,
+	},
+	// the worker has been running the same task for a long time
+	StuckTask {
+		worker: usize,
+		task: Task,
+		running_time: Duration,
+	},
 }
 
 pub fn init(world: Arc<World>, num_tasks: usize) {
 	info!("runing with {} task processing threads", num_tasks);
-	for _ in 0..num_tasks {
+	STOP_WORKERS.store(false, Ordering::Release);
+
+	for i in 0..num_tasks {
 		let thread_world = world.clone();
-		thread::spawn(move || task_runner(thread_world));
+		let status = Arc::new(WorkerStatus::default());
+		status.heartbeat();
+		WORKERS.write().push(status.clone());
+
+		let thread = thread::Builder::new()
+			.name(format!("task runner {}", i))
+			.spawn(move || task_runner(thread_world, status))
+			.expect("could not spawn task runner thread");
+		WORKER_THREADS.lock().push(thread);
 	}
 }
 
+// waits for the workers to finish their current task and exit, which drops their references to the world,
+// then throws away all queued and completed tasks so none of them are run on the next world
+pub fn shutdown() {
+	STOP_WORKERS.store(true, Ordering::Release);
+	let threads = std::mem::take(&mut *WORKER_THREADS.lock());
+	info!("stopping {} task processing threads", threads.len());
+	for thread in threads {
+		if thread.join().is_err() {
+			error!("task runner thread panicked while shutting down");
+		}
+	}
+	WORKERS.write().clear();
+
+	for queue in [&*TASK_QUEUE, &*BACKGROUND_QUEUE] {
+		loop {
+			match queue.steal() {
+				Steal::Empty => break,
+				_ => continue,
+			}
+		}
+	}
+	while COMPLETED_TASKS.pop().is_some() {}
+	while NEW_FAILED_TASKS.pop().is_some() {}
+	FAILED_TASKS.lock().clear();
+	CHUNK_ACTIVITY.clear();
+	LATENCY_WINDOW.lock().samples.clear();
+}
+
+// number of tasks waiting for a worker
+pub fn queued_task_count() -> usize {
+	TASK_QUEUE.len() + BACKGROUND_QUEUE.len()
+}
+
+// number of worker threads currently running
+pub fn worker_count() -> usize {
+	WORKER_THREADS.lock().len()
+}
+
 // appends the given task to the task queue
 pub fn run_task(task: Task) {
-	TASK_QUEUE.push(task);
+	if matches!(task, Task::GenerateChunk(_)) {
+		record_activity(&task, ChunkActivity::QueuedGeneration);
+	}
+
+	TASK_QUEUE.push(QueuedTask::new(task));
+}
+
+// appends the given task to the background queue, it is only run once there are no tasks in the task queue
+pub fn run_background_task(task: Task) {
+	BACKGROUND_QUEUE.push(QueuedTask::new(task));
+}
+
+// sets the activity of every chunk the task works on
+fn record_activity(task: &Task, activity: ChunkActivity) {
+	if !TRACK_ACTIVITY.load(Ordering::Relaxed) {
+		return;
+	}
+
+	if let Some((min_chunk, max_chunk, _)) = task.activity() {
+		for chunk in ChunkPos::iter_range(min_chunk, max_chunk) {
+			CHUNK_ACTIVITY.insert(chunk, activity);
+		}
+	}
+}
+
+// starts or stops recording chunk activity, all recorded activity is cleared when it is stopped
+pub fn set_activity_tracking(enabled: bool) {
+	TRACK_ACTIVITY.store(enabled, Ordering::Relaxed);
+	if !enabled {
+		CHUNK_ACTIVITY.clear();
+	}
+}
+
+// returns the activity of every chunk that has any, completed activity is dropped after COMPLETED_ACTIVITY_TIME
+// this only briefly locks each part of the activity map, so workers are not blocked
+pub fn chunk_activity() -> Vec<(ChunkPos, ChunkActivity)> {
+	CHUNK_ACTIVITY.retain(|_, activity| {
+		!matches!(activity, ChunkActivity::Completed(time) if time.elapsed() > COMPLETED_ACTIVITY_TIME)
+	});
+
+	CHUNK_ACTIVITY.iter()
+		.map(|entry| (*entry.key(), *entry.value()))
+		.collect()
 }
 
 pub fn pull_completed_task() -> Option<Task> {
 	COMPLETED_TASKS.pop()
 }
 
+// returns tasks that have failed since they were last pulled
+// failed tasks have not done their work, but should still count as completed for any jobs waiting on them
+pub fn pull_failed_task() -> Option<Task> {
+	NEW_FAILED_TASKS.pop()
+}
+
+// latency percentiles of the tasks completed in the last LATENCY_WINDOW_TIME
+// each worker's buffer is only locked long enough to take its samples
+pub fn task_latency_stats() -> TaskLatencyStats {
+	let new_samples = WORKERS.read().iter()
+		.flat_map(|status| std::mem::take(&mut *status.latency_samples.lock()))
+		.collect::<Vec<_>>();
+	LATENCY_WINDOW.lock().stats(new_samples, Instant::now())
+}
+
+pub fn stale_task_counts() -> StaleTaskCounts {
+	StaleTaskCounts {
+		chunk_mesh: STALE_CHUNK_MESH_TASKS.load(Ordering::Relaxed),
+		chunk_mesh_face: STALE_MESH_FACE_TASKS.load(Ordering::Relaxed),
+	}
+}
+
+// returns all tasks that have failed this session
+pub fn failed_tasks() -> Vec<Task> {
+	FAILED_TASKS.lock().clone()
+}
+
+// returns any workers that appear to be dead or stuck
+pub fn worker_problems() -> Vec<WorkerProblem> {
+	let mut out = Vec::new();
+
+	for (worker, status) in WORKERS.read().iter().enumerate() {
+		if let Some((task, start_time)) = &*status.current_task.lock() {
+			let running_time = start_time.elapsed();
+			if running_time > STUCK_TASK_TIME {
+				out.push(WorkerProblem::StuckTask {
+					worker,
+					task: task.clone(),
+					running_time,
+				});
+			}
+		} else {
+			let heartbeat_age = status.heartbeat_age();
+			if heartbeat_age > HEARTBEAT_TIMEOUT {
+				out.push(WorkerProblem::Unresponsive {
+					worker,
+					heartbeat_age,
+				});
+			}
+		}
+	}
+
+	out
+}
+
 // waits for a task to apear, than runs it
-fn task_runner(world: Arc<World>) {
+fn task_runner(world: Arc<World>, status: Arc<WorkerStatus>) {
 	let sleep_duration = Duration::from_millis(2);
 
-	loop {
-		match TASK_QUEUE.steal() {
-			Steal::Success(task) => execute_task(&world, task),
+	while !STOP_WORKERS.load(Ordering::Acquire) {
+		status.heartbeat();
+
+		match steal_task(&[&TASK_QUEUE, &BACKGROUND_QUEUE]) {
+			Steal::Success(queued_task) => run_supervised(&world, &status, queued_task),
 			Steal::Empty => thread::sleep(sleep_duration),
 			Steal::Retry => continue,
 		}
 	}
 }
 
-// executes the given task
-fn execute_task(world: &Arc<World>, task: Task) {
+// takes a task from the first queue which has one, so tasks in later queues wait for every earlier queue to be empty
+fn steal_task(queues: &[&Injector<QueuedTask>]) -> Steal<QueuedTask> {
+	for queue in queues {
+		match queue.steal() {
+			Steal::Empty => continue,
+			steal => return steal,
+		}
+	}
+	Steal::Empty
+}
+
+// runs the task, retrying it if it panics, and records it as failed if it panics on every attempt
+// the latency of tasks that succeed and aren't stale is recorded in the worker's buffer
+fn run_supervised(world: &Arc<World>, status: &WorkerStatus, queued_task: QueuedTask) {
+	let QueuedTask { task, enqueued } = queued_task;
+	let start_time = Instant::now();
+	*status.current_task.lock() = Some((task.clone(), start_time));
+	if let Some((_, _, activity)) = task.activity() {
+		record_activity(&task, activity);
+	}
+
+	let mut succeeded = false;
+	let mut stale = false;
+	let mut progress = TaskProgress::default();
+	for attempt in 0..=TASK_RETRIES {
+		if let Some(ran) = catch_panic(|| execute_attempt(world, task.clone(), &mut progress)) {
+			succeeded = true;
+			stale = !ran;
+			break;
+		}
+
+		if attempt < TASK_RETRIES {
+			warn!("task {:?} panicked, retrying", task);
+		}
+	}
+
+	*status.current_task.lock() = None;
+	let completed = Instant::now();
+	record_activity(&task, ChunkActivity::Completed(completed));
+
+	// a stale task finishes almost immediately, which would make meshing look faster than it is
+	if let Some(kind) = task.latency_kind().filter(|_| succeeded && !stale) {
+		status.latency_samples.lock().push(LatencySample {
+			kind,
+			completed,
+			total: completed - enqueued,
+			execution: completed - start_time,
+		});
+	}
+
+	if !succeeded {
+		error!("task {:?} panicked on every attempt, it has been marked as failed", task);
+		FAILED_TASKS.lock().push(task.clone());
+		NEW_FAILED_TASKS.push(task);
+	}
+}
+
+// what earlier attempts at a task already did, so retrying it after a panic doesn't do it again
+#[derive(Default)]
+struct TaskProgress {
+	// the generated chunk's load count was incremented
+	load_counted: bool,
+	// entities taken out of the cache or the chunk's region, which haven't been given back to the world yet
+	unrestored_entities: Vec<(EntityId, Box<dyn Entity>)>,
+}
+
+// one attempt at executing the task, progress is kept between attempts at the same task
+// returns false if it was stale and skipped, stale tasks are still completed, so whatever is waiting on them isn't stuck
+fn execute_attempt(world: &Arc<World>, task: Task, progress: &mut TaskProgress) -> bool {
+	let _span = task.span().entered();
+
 	match task {
 		Task::ChunkMesh(chunk) => {
+			if !world.chunk_needed(chunk) {
+				STALE_CHUNK_MESH_TASKS.fetch_add(1, Ordering::Relaxed);
+				COMPLETED_TASKS.push(task);
+				return false;
+			}
+
 			world.chunks.get(&chunk).map(|chunk| chunk.value().chunk.chunk_mesh_update());
+			world.validate_chunk_mesh(chunk);
 			COMPLETED_TASKS.push(task);
 		},
 		Task::ChunkMeshFace { face, min_chunk, max_chunk } => {
 			let mut visit_map = VisitedBlockMap::new();
+			let mut meshed_any = false;
 
-			for x in min_chunk.x..max_chunk.x {
-				for y in min_chunk.y..max_chunk.y {
-					for z in min_chunk.z..max_chunk.z {
-						let chunk_pos = ChunkPos::new(x, y, z);
-						if let Some(chunk) = world.chunks.get(&chunk_pos) {
-							let index = if face.is_positive_face() {
-								CHUNK_SIZE - 1
-							} else {
-								0
-							};
-
-							chunk.chunk.mesh_update_inner(face, index, &mut visit_map);
-						}
-					}
+			for chunk_pos in ChunkPos::iter_range(min_chunk, max_chunk) {
+				if !world.chunk_needed(chunk_pos) {
+					continue;
+				}
+				meshed_any = true;
+
+				if let Some(chunk) = world.chunks.get(&chunk_pos) {
+					chunk.chunk.mesh_update_inner(face, border_layer(face), &mut visit_map);
 				}
+				world.validate_chunk_mesh(chunk_pos);
 			}
 
 			COMPLETED_TASKS.push(task);
+			if !meshed_any {
+				STALE_MESH_FACE_TASKS.fetch_add(1, Ordering::Relaxed);
+				return false;
+			}
 		},
 		Task::GenerateChunk(chunk) => {
-			let chunk = world.chunks.entry(chunk)
-				.or_insert_with(|| world.world_generator
-					.generate_chunk(world.clone(), chunk));
+			// a retry finds the chunk an earlier attempt inserted, and restores the entities that attempt took
+			let loaded_chunk = world.chunks.entry(chunk)
+				.or_insert_with(|| match world.take_cached_chunk(chunk).or_else(|| world.load_saved_chunk(chunk)) {
+					Some((stored_chunk, entities)) => {
+						progress.unrestored_entities = entities;
+						stored_chunk
+					},
+					None => world.world_generator.generate_chunk(world.clone(), chunk),
+				});
 
 			// when first inserting load count starts at 0
-			chunk.inc_load_count();
+			// it is only counted once per task, or the chunk would never be unloaded
+			if !progress.load_counted {
+				loaded_chunk.inc_load_count();
+				progress.load_counted = true;
+			}
+			let ticking = loaded_chunk.chunk.has_ticking_block_entities();
+			// the chunk map can't be locked while entities are
+			drop(loaded_chunk);
+			world.restore_entities(mem::take(&mut progress.unrestored_entities));
+			if ticking {
+				world.track_ticking_chunk(chunk);
+			}
 
 			COMPLETED_TASKS.push(task);
 		},
+		Task::PregenerateChunk(chunk) => {
+			world.pregenerate_chunk(chunk);
+			COMPLETED_TASKS.push(task);
+		},
 		Task::UnloadChunks { min_chunk, max_chunk } => {
-			for x in min_chunk.x..max_chunk.x {
-				for y in min_chunk.y..max_chunk.y {
-					for z in min_chunk.z..max_chunk.z {
-						let position = ChunkPos::new(x, y, z);
-
-						if let Some(loaded_chunk) = world.chunks.get(&position) {
-							if loaded_chunk.dec_load_count() == 0 {
-								drop(loaded_chunk);
-								world.chunks.remove(&position);
-							}
+			for position in ChunkPos::iter_range(min_chunk, max_chunk) {
+				if let Some(loaded_chunk) = world.chunks.get(&position) {
+					if loaded_chunk.dec_load_count() == 0 {
+						drop(loaded_chunk);
+						if let Some((_, loaded_chunk)) = world.chunks.remove(&position) {
+							world.save_unloaded_chunk(position, loaded_chunk.chunk);
 						}
 					}
 				}
@@ -120,5 +619,380 @@ fn execute_task(world: &Arc<World>, task: Task) {
 
 			COMPLETED_TASKS.push(task);
 		},
+		Task::Analyze { area, .. } => {
+			COMPLETED_TASKS.push(Task::Analyze {
+				area,
+				stats: Some(Box::new(analyze_blocks(world, area))),
+			});
+		},
+		#[cfg(test)]
+		Task::TestPanic(chunk) => panic!("test panic task for chunk {:?}", chunk),
+	}
+
+	true
+}
+
+// counts the blocks of each type in the loaded chunks in the area, or every loaded chunk if there is no area
+fn analyze_blocks(world: &World, area: Option<ChunkBox>) -> BlockStats {
+	// the chunk map isn't iterated while the chunks are locked, so nothing waits on the whole map for long
+	let chunks = world.chunks.iter()
+		.map(|loaded_chunk| *loaded_chunk.key())
+		.filter(|chunk| area.is_none_or(|area| area.contains(*chunk)))
+		.collect::<Vec<_>>();
+
+	let mut stats = BlockStats::new();
+	for chunk in chunks {
+		if let Some(loaded_chunk) = world.chunks.get(&chunk) {
+			stats.add_chunk(&loaded_chunk.chunk);
+		}
+	}
+	stats
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{IVec3, Vec3};
+
+	use super::*;
+	use crate::math::Aabb;
+	use crate::game::block::{BlockType, Air, Chest, Stone};
+	use crate::game::chunk::{Chunk, LoadedChunk};
+	use crate::game::entity::{Item, ITEM_LIFETIME};
+	use crate::game::world::EntityCount;
+	use crate::game::worldgen::{GeneratorType, GeneratorOptions};
+	use crate::game::mesh_validation::{MeshValidation, validate_chunk_mesh};
+
+	// executes the task in a single attempt, returns false if it was stale and skipped
+	fn execute_task(world: &Arc<World>, task: Task) -> bool {
+		execute_attempt(world, task, &mut TaskProgress::default())
+	}
+
+	#[test]
+	fn panicking_task_is_marked_failed() {
+		let world = World::new_test().unwrap();
+		let status = WorkerStatus::default();
+		let chunk = ChunkPos::new(7, -3, 12);
+
+		run_supervised(&world, &status, QueuedTask::new(Task::TestPanic(chunk)));
+
+		assert!(failed_tasks().iter().any(|task| matches!(task, Task::TestPanic(pos) if *pos == chunk)));
+		assert!(status.current_task.lock().is_none());
+	}
+
+	#[test]
+	fn retried_chunk_generation_counts_the_load_once() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(3, 0, 3);
+
+		// as if the first attempt panicked after inserting the chunk
+		let mut progress = TaskProgress::default();
+		execute_attempt(&world, Task::GenerateChunk(chunk), &mut progress);
+		execute_attempt(&world, Task::GenerateChunk(chunk), &mut progress);
+		assert_eq!(world.chunks.get(&chunk).unwrap().get_load_count(), 1);
+
+		// another task loading the same chunk still counts
+		execute_task(&world, Task::GenerateChunk(chunk));
+		assert_eq!(world.chunks.get(&chunk).unwrap().get_load_count(), 2);
+		world.close();
+	}
+
+	#[test]
+	fn chest_contents_survive_unloading() {
+		let directory = std::env::temp_dir().join(format!("minecone-chest-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&directory);
+		let world = World::open(&directory, 0, GeneratorType::Normal, GeneratorOptions::default()).unwrap();
+		let chunk = ChunkPos::new(30, -2, -30);
+		let chest = chunk.as_block_pos() + BlockPos::new(4, 5, 6);
+		let unload = Task::UnloadChunks {
+			min_chunk: chunk,
+			max_chunk: chunk + ChunkPos::splat(1),
+		};
+
+		execute_task(&world, Task::GenerateChunk(chunk));
+		world.set_block(chest, Chest::new().into()).unwrap();
+		let stored = world.with_block_entity_mut(chest, |entity| entity.container_mut().unwrap().insert(BlockType::Stone, 12));
+		assert_eq!(stored, Some(true));
+
+		execute_task(&world, unload.clone());
+		assert!(!world.chunks.contains_key(&chunk));
+		assert!(world.regions().contains_chunk(chunk).unwrap());
+		assert_eq!(world.memory_stats().cached_chunks, 0);
+		world.save_all().unwrap();
+		world.close();
+
+		// the chunk comes back from its region after the world is opened again, instead of being generated again
+		let world = World::open(&directory, 0, GeneratorType::Normal, GeneratorOptions::default()).unwrap();
+		execute_task(&world, Task::GenerateChunk(chunk));
+		assert_eq!(world.block_type_at(chest), Some(BlockType::Chest));
+		let contents = world.with_block_entity_mut(chest, |entity| entity.container_mut().unwrap().slots()[0]);
+		assert_eq!(contents, Some(Some((BlockType::Stone, 12))));
+
+		// breaking the chest drops what was in it
+		world.set_block(chest, Air::new().into()).unwrap();
+		assert_eq!(world.with_block_entity_mut(chest, |_| ()), None);
+		let around_chest = Aabb::new(chest.as_position().0 - Vec3::ONE, Vec3::splat(3.0));
+		let items = world.entities_in_aabb(around_chest);
+		assert_eq!(items.len(), 1);
+		assert_eq!(world.entity_label(items[0], 1.0).unwrap().1, "Stone x12");
+		world.remove_entity(items[0]);
+		execute_task(&world, unload);
+		assert_eq!(world.memory_stats().cached_chunks, 0);
+		world.close();
+
+		std::fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn block_edits_survive_unloading() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(-31, -2, 31);
+		let (edited, untouched) = (chunk.as_block_pos() + BlockPos::new(1, 2, 3), chunk + ChunkPos::new(1, 0, 0));
+		let unload = Task::UnloadChunks {
+			min_chunk: chunk,
+			max_chunk: untouched + ChunkPos::splat(1),
+		};
+
+		execute_task(&world, Task::GenerateChunk(chunk));
+		execute_task(&world, Task::GenerateChunk(untouched));
+		let (replaced, replaced_type) = if world.block_type_at(edited) == Some(BlockType::Stone) {
+			(Air::new().into(), BlockType::Air)
+		} else {
+			(Stone::new().into(), BlockType::Stone)
+		};
+		world.set_block(edited, replaced).unwrap();
+
+		// only the edited chunk has anything to save
+		execute_task(&world, unload);
+		assert!(world.regions().contains_chunk(chunk).unwrap());
+		assert!(!world.regions().contains_chunk(untouched).unwrap());
+
+		execute_task(&world, Task::GenerateChunk(chunk));
+		assert_eq!(world.block_type_at(edited), Some(replaced_type));
+	}
+
+	#[test]
+	fn entities_are_kept_with_unloaded_chunks() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(-30, 1, 30);
+		let position = (chunk.as_block_pos() + BlockPos::new(10, 3, 20)).as_position();
+		let unload = Task::UnloadChunks {
+			min_chunk: chunk,
+			max_chunk: chunk + ChunkPos::splat(1),
+		};
+
+		execute_task(&world, Task::GenerateChunk(chunk));
+		let item = world.add_entity(Box::new(Item::new(position, BlockType::Sand, 3)));
+		assert_eq!(world.entity_count(), EntityCount { active: 1, frozen: 0, pending_save: 0 });
+
+		execute_task(&world, unload);
+		assert_eq!(world.entity_position(item), None);
+		// the item is saved with the chunk, so nothing is waiting to be saved
+		assert_eq!(world.entity_count(), EntityCount::default());
+		assert!(world.regions().contains_chunk(chunk).unwrap());
+
+		// long enough for the item to despawn, if it was being ticked
+		world.set_tick_time(ITEM_LIFETIME / 50);
+		for tick in 0..100 {
+			world.tick(tick);
+		}
+
+		// entities read back from a region get new ids
+		execute_task(&world, Task::GenerateChunk(chunk));
+		let items = world.entities_in_aabb(Aabb::new(position.0 - Vec3::ONE, Vec3::splat(2.0)));
+		assert_eq!(items.len(), 1);
+		let item = items[0];
+		assert_eq!(world.entity_position(item), Some(position));
+		assert_eq!(world.entity_count(), EntityCount { active: 1, frozen: 0, pending_save: 0 });
+		assert_eq!(world.memory_stats().cached_chunks, 0);
+
+		// once it is loaded, the item ages and despawns
+		world.set_tick_time(ITEM_LIFETIME);
+		world.tick(100);
+		assert_eq!(world.entity_position(item), None);
+		assert_eq!(world.entity_count(), EntityCount::default());
+	}
+
+	// every other chunk is generated and meshed before any of its neighbors exist,
+	// so without remeshing their borders, every border between 2 chunks would have a hole
+	#[test]
+	fn chunks_meshed_before_their_neighbors_have_no_holes() {
+		let world = World::new_test().unwrap();
+		let surface_chunk = BlockPos::new(16, world.surface_height(16, 16), 16).as_chunk_pos();
+		let min_chunk = surface_chunk - ChunkPos::splat(1);
+		let max_chunk = surface_chunk + ChunkPos::splat(2);
+
+		let (first, second): (Vec<_>, Vec<_>) = ChunkPos::iter_range(min_chunk, max_chunk)
+			.partition(|chunk| (chunk.x + chunk.y + chunk.z).rem_euclid(2) == 0);
+
+		let mut border_tasks = 0;
+		for chunk in first.into_iter().chain(second) {
+			execute_task(&world, Task::GenerateChunk(chunk));
+			for task in world.unmeshed_border_tasks(chunk) {
+				execute_task(&world, task);
+				border_tasks += 1;
+			}
+			execute_task(&world, Task::ChunkMesh(chunk));
+		}
+		assert!(border_tasks > 0);
+
+		for chunk_pos in ChunkPos::iter_range(min_chunk, max_chunk) {
+			let chunk = world.chunks.get(&chunk_pos).unwrap();
+			let violations = validate_chunk_mesh(&chunk.chunk, MeshValidation::Full);
+			assert!(violations.is_empty(), "violations in {:?}: {:?}", chunk_pos, &violations[..violations.len().min(10)]);
+		}
+
+		world.close();
+	}
+
+	#[test]
+	fn background_tasks_wait_for_other_tasks() {
+		let queue = Injector::new();
+		let background_queue = Injector::new();
+		background_queue.push(QueuedTask::new(Task::PregenerateChunk(ChunkPos::new(0, 0, 0))));
+		queue.push(QueuedTask::new(Task::GenerateChunk(ChunkPos::new(1, 0, 0))));
+		queue.push(QueuedTask::new(Task::GenerateChunk(ChunkPos::new(2, 0, 0))));
+
+		let mut order = Vec::new();
+		while let Steal::Success(queued_task) = steal_task(&[&queue, &background_queue]) {
+			order.push(queued_task.task);
+		}
+		assert!(matches!(order[..], [
+			Task::GenerateChunk(ChunkPos(IVec3 { x: 1, .. })),
+			Task::GenerateChunk(ChunkPos(IVec3 { x: 2, .. })),
+			Task::PregenerateChunk(_),
+		]));
+	}
+
+	#[test]
+	fn chunk_activity_follows_task() {
+		let world = World::new_test().unwrap();
+		let status = WorkerStatus::default();
+		let chunk = ChunkPos::new(-40, 9, 3);
+		let activity_of = |chunk: ChunkPos| chunk_activity().into_iter()
+			.find(|(pos, _)| *pos == chunk)
+			.map(|(_, activity)| activity);
+
+		set_activity_tracking(true);
+
+		let task = Task::GenerateChunk(chunk);
+		record_activity(&task, ChunkActivity::QueuedGeneration);
+		assert_eq!(activity_of(chunk), Some(ChunkActivity::QueuedGeneration));
+
+		run_supervised(&world, &status, QueuedTask::new(task));
+		assert!(matches!(activity_of(chunk), Some(ChunkActivity::Completed(_))));
+
+		// the worker keeps the latency until the stats are read
+		let samples = status.latency_samples.lock();
+		assert_eq!(samples.len(), 1);
+		assert_eq!(samples[0].kind, LatencyKind::GenerateChunk);
+		assert!(samples[0].execution <= samples[0].total);
+	}
+
+	fn latency_sample(kind: LatencyKind, completed: Instant, total_ms: u64) -> LatencySample {
+		LatencySample {
+			kind,
+			completed,
+			total: Duration::from_millis(total_ms),
+			execution: Duration::from_millis(total_ms / 2),
+		}
+	}
+
+	#[test]
+	fn latency_percentiles() {
+		assert_eq!(LatencyPercentiles::of(Vec::new()), LatencyPercentiles::default());
+
+		let durations = (1..=100).rev().map(Duration::from_millis).collect();
+		assert_eq!(LatencyPercentiles::of(durations), LatencyPercentiles {
+			p50: Duration::from_millis(50),
+			p95: Duration::from_millis(95),
+			max: Duration::from_millis(100),
+		});
+
+		let single = LatencyPercentiles::of(vec![Duration::from_millis(7)]);
+		assert_eq!((single.p50, single.p95, single.max), (Duration::from_millis(7), Duration::from_millis(7), Duration::from_millis(7)));
+	}
+
+	#[test]
+	fn latency_window_drops_old_samples() {
+		let start = Instant::now();
+		let mut window = LatencyWindow::new();
+		let stats = window.stats([
+			latency_sample(LatencyKind::GenerateChunk, start, 40),
+			latency_sample(LatencyKind::GenerateChunk, start + Duration::from_secs(10), 10),
+			latency_sample(LatencyKind::ChunkMesh, start + Duration::from_secs(10), 4),
+		], start + Duration::from_secs(10));
+		assert_eq!(stats.generate_chunk.count, 2);
+		assert_eq!(stats.generate_chunk.total.max, Duration::from_millis(40));
+		assert_eq!(stats.generate_chunk.execution.max, Duration::from_millis(20));
+		assert_eq!(stats.chunk_mesh.count, 1);
+
+		// the first sample is now older than the window
+		let stats = window.stats([], start + LATENCY_WINDOW_TIME + Duration::from_secs(1));
+		assert_eq!(stats.generate_chunk.count, 1);
+		assert_eq!(stats.generate_chunk.total.max, Duration::from_millis(10));
+		assert_eq!(stats.chunk_mesh.count, 1);
+
+		let stats = window.stats([], start + 2 * LATENCY_WINDOW_TIME);
+		assert_eq!(stats, TaskLatencyStats::default());
+	}
+
+	// the surface chunk of the column the position is in, so it has blocks to mesh
+	fn surface_chunk(world: &World, position: Position) -> ChunkPos {
+		let block = position.as_block_pos();
+		BlockPos::new(block.x, world.surface_height(block.x, block.z), block.z).as_chunk_pos()
+	}
+
+	#[test]
+	fn chunks_left_behind_mid_load_are_never_meshed() {
+		let world = World::new_test().unwrap();
+		let (player_id, state) = world.connect("stale mesh tester");
+		let left_behind = surface_chunk(&world, state.position);
+		let stale_before = stale_task_counts();
+
+		// the player moves far away before any of the chunks they were loading have been meshed
+		let destination = Position::new(state.position.x + 20.0 * CHUNK_SIZE as f32, state.position.y, state.position.z);
+		world.update_player_position(player_id, destination).unwrap();
+		let destination_chunk = surface_chunk(&world, destination);
+
+		for chunk in [left_behind, destination_chunk] {
+			execute_task(&world, Task::GenerateChunk(chunk));
+		}
+		assert!(!execute_task(&world, Task::ChunkMesh(left_behind)));
+		assert!(!execute_task(&world, Task::ChunkMeshFace {
+			min_chunk: left_behind,
+			max_chunk: left_behind + ChunkPos::splat(1),
+			face: BlockFace::YPos,
+		}));
+		assert!(execute_task(&world, Task::ChunkMesh(destination_chunk)));
+
+		let mesh_len = |chunk| world.chunks.get(&chunk).unwrap().chunk.get_chunk_mesh().unwrap().len();
+		assert_eq!(mesh_len(left_behind), 0);
+		assert!(mesh_len(destination_chunk) > 0);
+
+		// other tests can skip stale tasks at the same time
+		let stale_after = stale_task_counts();
+		assert!(stale_after.chunk_mesh > stale_before.chunk_mesh);
+		assert!(stale_after.chunk_mesh_face > stale_before.chunk_mesh_face);
+
+		world.disconnect(player_id).unwrap();
+	}
+
+	#[test]
+	fn analyze_counts_loaded_chunks_in_area() {
+		let world = World::new_test().unwrap();
+		let area = ChunkBox::new(ChunkPos::new(-40, -1, 40), ChunkPos::new(-38, 1, 41));
+		for chunk in ChunkPos::iter_range(ChunkPos::new(-40, -1, 40), ChunkPos::new(-37, 1, 41)) {
+			let block = if chunk.y < 0 { Stone::new().into() } else { Air::new().into() };
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, block)));
+		}
+
+		let stats = analyze_blocks(&world, Some(area));
+		let chunk_blocks = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as u64;
+		// the third column of chunks is loaded, but outside of the area
+		assert_eq!(stats.chunks_scanned, 4);
+		assert_eq!(stats.count(BlockType::Stone), 2 * chunk_blocks);
+		assert_eq!(stats.count(BlockType::Air), 2 * chunk_blocks);
+		assert!(stats.skipped_chunks.is_empty());
+		world.close();
 	}
 }
@@ -1,6 +1,25 @@
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context};
+use glam::Vec3;
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use rustc_hash::FxHasher;
 
 use crate::prelude::*;
+use super::block::BlockType;
+use super::config::Config;
+
+// where new players start
+pub const DEFAULT_SPAWN_POINT: Position = Position(Vec3::ZERO);
+pub const MAX_HEALTH: f32 = 20.0;
+// landing slower than this doesn't hurt, it is about the speed after falling 3 blocks
+const SAFE_LANDING_SPEED: f32 = 13.0;
+// health lost for each block / second faster than the safe speed the player lands
+const FALL_DAMAGE_PER_SPEED: f32 = 0.75;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PlayerId(u64);
@@ -14,20 +33,323 @@ impl PlayerId {
 	}
 }
 
+// a name for a player who doesn't have one yet, which is very unlikely to be used by anyone else
+pub fn generate_player_name() -> String {
+	let mut hasher = FxHasher::default();
+	SystemTime::now().hash(&mut hasher);
+	std::process::id().hash(&mut hasher);
+	format!("player-{:016x}", hasher.finish())
+}
+
+// health lost by landing on the ground at impact_speed, in blocks / second
+pub fn fall_damage(impact_speed: f32) -> f32 {
+	((impact_speed - SAFE_LANDING_SPEED) * FALL_DAMAGE_PER_SPEED).max(0.0)
+}
+
+// what the player is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+	Creative,
+	Survival,
+}
+
+impl GameMode {
+	// blocks break as soon as they are clicked, instead of after being held for their hardness
+	pub fn instant_break(&self) -> bool {
+		*self == Self::Creative
+	}
+
+	// blocks are placed from the hotbar without using any up, and broken blocks aren't collected
+	pub fn infinite_blocks(&self) -> bool {
+		*self == Self::Creative
+	}
+
+	pub fn can_fly(&self) -> bool {
+		*self == Self::Creative
+	}
+
+	pub fn takes_damage(&self) -> bool {
+		*self == Self::Survival
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Creative => "creative",
+			Self::Survival => "survival",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"creative" => Some(Self::Creative),
+			"survival" => Some(Self::Survival),
+			_ => None,
+		}
+	}
+}
+
+// everything about a player which is saved with the world
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerState {
+	pub position: Position,
+	// facing direction in radians, yaw is around the y axis starting from +z, and pitch is up from the horizon
+	pub yaw: f32,
+	pub pitch: f32,
+	pub game_mode: GameMode,
+	// block type in each hotbar slot
+	pub hotbar: Vec<Option<BlockType>>,
+	// number of blocks in each hotbar slot, only used in survival
+	pub hotbar_counts: Vec<u32>,
+	pub selected_slot: usize,
+	pub spawn_point: Position,
+	pub health: f32,
+}
+
+impl Default for PlayerState {
+	fn default() -> Self {
+		PlayerState {
+			position: DEFAULT_SPAWN_POINT,
+			yaw: 0.0,
+			pitch: 0.0,
+			game_mode: GameMode::Creative,
+			hotbar: Vec::new(),
+			hotbar_counts: Vec::new(),
+			selected_slot: 0,
+			spawn_point: DEFAULT_SPAWN_POINT,
+			health: MAX_HEALTH,
+		}
+	}
+}
+
+impl PlayerState {
+	// unit vector the player is facing
+	pub fn forward(&self) -> Vec3 {
+		Vec3::new(
+			self.pitch.cos() * self.yaw.sin(),
+			self.pitch.sin(),
+			self.pitch.cos() * self.yaw.cos(),
+		)
+	}
+
+	// forward doesn't have to be normalized
+	pub fn set_forward(&mut self, forward: Vec3) {
+		let forward = forward.normalize_or_zero();
+		if forward == Vec3::ZERO {
+			return;
+		}
+
+		self.yaw = forward.x.atan2(forward.z);
+		self.pitch = forward.y.clamp(-1.0, 1.0).asin();
+	}
+
+	// none if the file doesn't exist, which means the player is new to the world
+	pub fn load(file: &Path) -> Result<Option<Self>> {
+		if !file.is_file() {
+			return Ok(None);
+		}
+		let config = Config::load(file.to_owned());
+
+		let get = |key: &str| config.get(key).ok_or_else(|| anyhow!("missing {}", key));
+		let parse_f32 = |key: &str| -> Result<f32> {
+			get(key)?.parse().with_context(|| format!("invalid {}", key))
+		};
+		let parse_position = |key: &str| -> Result<Position> {
+			let coordinates = get(key)?.split_whitespace()
+				.map(str::parse)
+				.collect::<Result<Vec<f32>, _>>()
+				.with_context(|| format!("invalid {}", key))?;
+			match coordinates[..] {
+				[x, y, z] => Ok(Position::new(x, y, z)),
+				_ => Err(anyhow!("{} must have 3 coordinates", key)),
+			}
+		};
+
+		// empty hotbar slots are saved as -
+		let hotbar = get("hotbar")?.split_whitespace()
+			.map(|name| match name {
+				"-" => Ok(None),
+				_ => BlockType::from_name(name).map(Some).ok_or_else(|| anyhow!("unknown block type {} in hotbar", name)),
+			})
+			.collect::<Result<Vec<_>>>()?;
+		// saves from before survival don't have counts
+		let hotbar_counts = config.get("hotbar_counts").unwrap_or("").split_whitespace()
+			.map(str::parse)
+			.collect::<Result<Vec<_>, _>>()
+			.context("invalid hotbar counts")?;
+		// saves from before health don't have it
+		let health = match config.get("health") {
+			Some(health) => health.parse().context("invalid health")?,
+			None => MAX_HEALTH,
+		};
+
+		Ok(Some(PlayerState {
+			position: parse_position("position")?,
+			yaw: parse_f32("yaw")?,
+			pitch: parse_f32("pitch")?,
+			game_mode: GameMode::from_name(get("game_mode")?).ok_or_else(|| anyhow!("invalid game mode"))?,
+			hotbar,
+			hotbar_counts,
+			selected_slot: get("selected_slot")?.parse().context("invalid selected slot")?,
+			spawn_point: parse_position("spawn_point")?,
+			health,
+		}))
+	}
+
+	// the file's directory is created if it doesn't exist
+	pub fn save(&self, file: &Path) -> Result<()> {
+		if let Some(directory) = file.parent() {
+			fs::create_dir_all(directory)?;
+		}
+
+		let format_position = |position: Position| format!("{} {} {}", position.x, position.y, position.z);
+		let hotbar = self.hotbar.iter()
+			.map(|slot| slot.map_or("-", BlockType::name))
+			.collect::<Vec<_>>()
+			.join(" ");
+		let hotbar_counts = self.hotbar_counts.iter()
+			.map(u32::to_string)
+			.collect::<Vec<_>>()
+			.join(" ");
+
+		let mut config = Config::load(file.to_owned());
+		config.set("position", Some(&format_position(self.position)));
+		config.set("yaw", Some(&self.yaw.to_string()));
+		config.set("pitch", Some(&self.pitch.to_string()));
+		config.set("game_mode", Some(self.game_mode.name()));
+		config.set("hotbar", Some(&hotbar));
+		config.set("hotbar_counts", Some(&hotbar_counts));
+		config.set("selected_slot", Some(&self.selected_slot.to_string()));
+		config.set("spawn_point", Some(&format_position(self.spawn_point)));
+		config.set("health", Some(&self.health.to_string()));
+		config.save()
+	}
+}
+
+// what happened to a player who was damaged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DamageOutcome {
+	// health is what the player has left
+	Hurt {
+		health: f32,
+	},
+	// the player is back at their spawn point with full health
+	Died {
+		respawn_point: Position,
+	},
+}
+
+// how many chunks are loaded around a player
+// there is usually only sky above the surface and stone below it, so fewer chunks are loaded vertically
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderDistance {
+	// in x and z, in each direction
+	pub horizontal: i32,
+	// like with ChunkBox::around, the player's chunk counts as one of the chunks above it
+	pub up: i32,
+	pub down: i32,
+	// if the surface is always loaded, even when it is further above or below the player than up or down
+	pub include_surface: bool,
+}
+
+impl Default for RenderDistance {
+	fn default() -> Self {
+		RenderDistance {
+			horizontal: 10,
+			up: 2,
+			down: 4,
+			include_surface: true,
+		}
+	}
+}
+
+impl RenderDistance {
+	// the chunks loaded for a player in center, surface_chunk_y is the height of the chunk the surface is in at the player's column
+	pub fn zone_around(&self, center: ChunkPos, surface_chunk_y: i32) -> ChunkBox {
+		let mut zone = ChunkBox::new(
+			ChunkPos::new(center.x - self.horizontal, center.y - self.down, center.z - self.horizontal),
+			ChunkPos::new(center.x + self.horizontal, center.y + self.up, center.z + self.horizontal),
+		);
+		if self.include_surface {
+			zone.min.y = zone.min.y.min(surface_chunk_y);
+			zone.max.y = zone.max.y.max(surface_chunk_y + 1);
+		}
+		zone
+	}
+}
+
+// the part of a player which changes every tick, the world keeps it seperately from the rest of the player
+// so it can be read and moved without locking the player map
+pub struct PlayerPosition {
+	// held while the player's chunks are being loaded, so concurrent moves of the same player are applied in order
+	position: Mutex<Position>,
+	// copied from the player, it doesn't change while they are in the world
+	render_distance: RenderDistance,
+	// the chunks loaded around the player, updated whenever they move to another chunk
+	// it isn't behind the position lock, so workers can check it without waiting on a move
+	render_box: RwLock<ChunkBox>,
+}
+
+impl PlayerPosition {
+	pub fn new(player: &Player, position: Position, render_box: ChunkBox) -> Self {
+		PlayerPosition {
+			position: Mutex::new(position),
+			render_distance: player.render_distance(),
+			render_box: RwLock::new(render_box),
+		}
+	}
+
+	pub fn get(&self) -> Position {
+		*self.position.lock()
+	}
+
+	pub fn lock(&self) -> MutexGuard<'_, Position> {
+		self.position.lock()
+	}
+
+	pub fn render_distance(&self) -> RenderDistance {
+		self.render_distance
+	}
+
+	pub fn render_box(&self) -> ChunkBox {
+		*self.render_box.read()
+	}
+
+	pub fn set_render_box(&self, render_box: ChunkBox) {
+		*self.render_box.write() = render_box;
+	}
+}
+
 pub struct Player {
 	id: PlayerId,
-	pub position: Position,
-	// render distance in x, y, and z direction
-	render_distance: ChunkPos,
+	// the player's state is saved under this name, since ids are different every session
+	name: String,
+	// the position in here is where the player was when they connected, the current one is in their PlayerPosition
+	state: Mutex<PlayerState>,
+	render_distance: RenderDistance,
 }
 
 impl Player {
+	// a player which is not saved
+	#[cfg(test)]
 	pub fn new() -> Player {
+		Self::with_state(String::new(), PlayerState::default())
+	}
+
+	// a player which is not saved, with chunks loaded around them differently than usual
+	#[cfg(test)]
+	pub fn with_render_distance(render_distance: RenderDistance) -> Player {
+		Player {
+			render_distance,
+			..Self::new()
+		}
+	}
+
+	pub fn with_state(name: String, state: PlayerState) -> Player {
 		Player {
 			id: PlayerId::new(),
-			position: Position::new(0.0, 0.0, 0.0),
-			render_distance: ChunkPos::new(10, 5, 10),
-			//render_distance: ChunkPos::new(20, 10, 20),
+			name,
+			state: Mutex::new(state),
+			render_distance: RenderDistance::default(),
 		}
 	}
 
@@ -35,11 +357,102 @@ impl Player {
 		self.id
 	}
 
-	pub fn chunk_position(&self) -> ChunkPos {
-		self.position.into()
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	// the position isn't up to date, use World::player_state to get the whole state
+	pub fn state(&self) -> PlayerState {
+		self.state.lock().clone()
+	}
+
+	// the position can't be changed here, it is changed by moving the player
+	pub fn update_state<F: FnOnce(&mut PlayerState)>(&self, f: F) {
+		f(&mut self.state.lock());
+	}
+
+	// none if no damage was taken, because the amount was 0 or the player can't take damage in their game mode
+	// players who die get full health here, but the world has to move them to their spawn point
+	pub fn damage(&self, amount: f32) -> Option<DamageOutcome> {
+		let mut state = self.state.lock();
+		if amount <= 0.0 || !state.game_mode.takes_damage() {
+			return None;
+		}
+
+		state.health -= amount;
+		if state.health > 0.0 {
+			Some(DamageOutcome::Hurt {
+				health: state.health,
+			})
+		} else {
+			state.health = MAX_HEALTH;
+			Some(DamageOutcome::Died {
+				respawn_point: state.spawn_point,
+			})
+		}
 	}
 
-	pub fn render_distance(&self) -> ChunkPos {
+	pub fn health(&self) -> f32 {
+		self.state.lock().health
+	}
+
+	pub fn render_distance(&self) -> RenderDistance {
 		self.render_distance
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn facing_round_trip() {
+		let mut state = PlayerState::default();
+		assert!((state.forward() - Vec3::Z).length() < 0.0001);
+
+		for forward in [Vec3::X, Vec3::new(-1.0, 0.5, -2.0), Vec3::new(0.3, -0.9, 0.1)] {
+			state.set_forward(forward);
+			assert!((state.forward() - forward.normalize()).length() < 0.0001, "forward {:?}", forward);
+		}
+	}
+
+	#[test]
+	fn fall_damage_curve() {
+		// jumping and short falls don't hurt
+		assert_eq!(fall_damage(0.0), 0.0);
+		assert_eq!(fall_damage(9.0), 0.0);
+		assert_eq!(fall_damage(SAFE_LANDING_SPEED), 0.0);
+
+		// faster landings always hurt more
+		let damages = [15.0, 20.0, 30.0, 45.0].map(fall_damage);
+		assert!(damages[0] > 0.0);
+		assert!(damages.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", damages);
+
+		// a fall from 10 blocks hurts without killing, falling at terminal speed kills
+		let ten_block_speed = (2.0f32 * 28.0 * 10.0).sqrt();
+		assert!(fall_damage(ten_block_speed) > 0.0 && fall_damage(ten_block_speed) < MAX_HEALTH);
+		assert!(fall_damage(60.0) >= MAX_HEALTH);
+	}
+
+	#[test]
+	fn render_zone_reaches_the_surface() {
+		let render_distance = RenderDistance::default();
+		let center = ChunkPos::new(3, 1, -2);
+
+		// the surface is in the zone, so it is only the usual size
+		let zone = render_distance.zone_around(center, 0);
+		assert_eq!(zone.min, ChunkPos::new(-7, -3, -12));
+		assert_eq!(zone.max, ChunkPos::new(13, 3, 8));
+		assert!(zone.volume() < ChunkBox::around(center, ChunkPos::new(10, 5, 10)).volume());
+
+		// flying high above the surface, and deep below it
+		assert_eq!(render_distance.zone_around(center, -20).min.y, -20);
+		assert_eq!(render_distance.zone_around(center, 20).max.y, 21);
+
+		let without_surface = RenderDistance {
+			include_surface: false,
+			..render_distance
+		};
+		assert_eq!(without_surface.zone_around(center, -20), zone);
+	}
+}
@@ -0,0 +1,693 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glam::IVec3;
+use parking_lot::Mutex;
+
+use crate::prelude::*;
+
+// number of chunks along each axis of a region
+// chunks are cubes and the world has no height limit, so regions are cubes of chunks too
+pub const REGION_SIZE: i32 = 16;
+const REGION_CHUNK_COUNT: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+// space in region files is allocated in sectors
+const SECTOR_SIZE: u64 = 4096;
+// each header entry is the first sector and the number of sectors of a chunk, both u32
+const HEADER_ENTRY_SIZE: usize = 8;
+const HEADER_SECTORS: u32 = ((REGION_CHUNK_COUNT * HEADER_ENTRY_SIZE) as u64 / SECTOR_SIZE) as u32;
+// each chunk's sectors start with the length of its payload
+const LENGTH_PREFIX_SIZE: u64 = 4;
+
+// number of region files kept open by a RegionCache
+pub const DEFAULT_OPEN_REGIONS: usize = 16;
+
+// position of a region in region coordinates
+pub fn region_pos(chunk: ChunkPos) -> IVec3 {
+	chunk.0.map(|elem| elem.div_euclid(REGION_SIZE))
+}
+
+fn chunk_index(chunk: ChunkPos) -> usize {
+	let local = chunk.0.map(|elem| elem.rem_euclid(REGION_SIZE));
+	(local.x + local.z * REGION_SIZE + local.y * REGION_SIZE * REGION_SIZE) as usize
+}
+
+// the chunk at the index in the region, the opposite of chunk_index
+fn chunk_at_index(region: IVec3, index: usize) -> ChunkPos {
+	let index = index as i32;
+	let local = IVec3::new(index % REGION_SIZE, index / (REGION_SIZE * REGION_SIZE), index / REGION_SIZE % REGION_SIZE);
+	ChunkPos(region * REGION_SIZE + local)
+}
+
+fn sectors_needed(payload_len: usize) -> u32 {
+	(payload_len as u64 + LENGTH_PREFIX_SIZE).div_ceil(SECTOR_SIZE) as u32
+}
+
+// where a chunk is stored in its region file, a sector count of 0 means the chunk is not saved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SectorRange {
+	start: u32,
+	count: u32,
+}
+
+impl SectorRange {
+	fn is_empty(&self) -> bool {
+		self.count == 0
+	}
+
+	fn end(&self) -> u32 {
+		self.start + self.count
+	}
+
+	fn offset(&self) -> u64 {
+		self.start as u64 * SECTOR_SIZE
+	}
+}
+
+#[derive(Debug)]
+struct RegionFileInner {
+	file: File,
+	header: Box<[SectorRange; REGION_CHUNK_COUNT]>,
+	// which sectors are used, including the header sectors
+	used_sectors: Vec<bool>,
+}
+
+impl RegionFileInner {
+	fn write_chunk(&mut self, index: usize, payload: &[u8]) -> Result<()> {
+		let count = sectors_needed(payload.len());
+		let old_range = self.header[index];
+
+		let range = if count <= old_range.count {
+			// shrink in place and free the sectors that are no longer needed
+			self.set_used(SectorRange {
+				start: old_range.start + count,
+				count: old_range.count - count,
+			}, false);
+			SectorRange {
+				start: old_range.start,
+				count,
+			}
+		} else {
+			self.set_used(old_range, false);
+			self.allocate(count)
+		};
+
+		let mut data = Vec::with_capacity((count as u64 * SECTOR_SIZE) as usize);
+		data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+		data.extend_from_slice(payload);
+		// pad to whole sectors so the file length is always a multiple of the sector size
+		data.resize((count as u64 * SECTOR_SIZE) as usize, 0);
+
+		// data is written before the header, so a crash in between leaves the old chunk readable
+		// unless it is overwritten in place
+		self.file.seek(SeekFrom::Start(range.offset()))?;
+		self.file.write_all(&data)?;
+
+		self.header[index] = range;
+		self.write_header_entry(index)?;
+		self.trim()
+	}
+
+	fn write_header_entry(&mut self, index: usize) -> Result<()> {
+		let range = self.header[index];
+		let mut bytes = [0; HEADER_ENTRY_SIZE];
+		bytes[..4].copy_from_slice(&range.start.to_le_bytes());
+		bytes[4..].copy_from_slice(&range.count.to_le_bytes());
+
+		self.file.seek(SeekFrom::Start((index * HEADER_ENTRY_SIZE) as u64))?;
+		self.file.write_all(&bytes)?;
+		Ok(())
+	}
+
+	fn set_used(&mut self, range: SectorRange, used: bool) {
+		if self.used_sectors.len() < range.end() as usize {
+			self.used_sectors.resize(range.end() as usize, false);
+		}
+		self.used_sectors[range.start as usize..range.end() as usize].fill(used);
+	}
+
+	// first run of free sectors that is long enough, or the end of the file
+	fn allocate(&mut self, count: u32) -> SectorRange {
+		let mut run_start = HEADER_SECTORS;
+		for (sector, used) in self.used_sectors.iter().enumerate().skip(HEADER_SECTORS as usize) {
+			if *used {
+				run_start = sector as u32 + 1;
+			} else if sector as u32 + 1 - run_start == count {
+				break;
+			}
+		}
+
+		let range = SectorRange {
+			start: run_start,
+			count,
+		};
+		self.set_used(range, true);
+		range
+	}
+
+	// removes free sectors from the end of the file
+	fn trim(&mut self) -> Result<()> {
+		let used_len = self.used_sectors.iter()
+			.rposition(|used| *used)
+			.map_or(HEADER_SECTORS as usize, |sector| sector + 1);
+
+		if used_len < self.used_sectors.len() {
+			self.used_sectors.truncate(used_len);
+			self.file.set_len(used_len as u64 * SECTOR_SIZE)?;
+		}
+		Ok(())
+	}
+
+	// clears the header entry of a chunk whose data can't be read, so it is regenerated
+	fn discard_chunk(&mut self, index: usize) -> Result<()> {
+		self.header[index] = SectorRange::default();
+		self.write_header_entry(index)
+	}
+}
+
+// a file storing a REGION_SIZE cube of chunks
+// the file starts with a header of where each chunk is stored, followed by the chunk data in sectors
+// rewriting a chunk reuses its sectors if it still fits, otherwise it is moved to the first free space
+#[derive(Debug)]
+pub struct RegionFile {
+	region: IVec3,
+	inner: Mutex<RegionFileInner>,
+}
+
+impl RegionFile {
+	// creates the file if it doesn't exist
+	// a header which can't be read is reset, so all chunks in the region are regenerated
+	pub fn open<T: AsRef<Path>>(file_name: T, region: IVec3) -> Result<Self> {
+		let file_name = file_name.as_ref();
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			// chunks already in the file are kept
+			.truncate(false)
+			.open(file_name)?;
+
+		let header_len = HEADER_SECTORS as u64 * SECTOR_SIZE;
+		let file_len = file.metadata()?.len();
+		let file_sectors = file_len.div_ceil(SECTOR_SIZE) as u32;
+
+		let mut header = Box::new([SectorRange::default(); REGION_CHUNK_COUNT]);
+		// sectors past the header are free until a chunk is found in them
+		let mut used_sectors = vec![false; file_sectors.max(HEADER_SECTORS) as usize];
+		used_sectors[..HEADER_SECTORS as usize].fill(true);
+
+		if file_len < header_len {
+			if file_len != 0 {
+				warn!("region file {} has a truncated header, regenerating its chunks", file_name.display());
+			}
+			file.set_len(0)?;
+			file.set_len(header_len)?;
+			used_sectors.truncate(HEADER_SECTORS as usize);
+		} else {
+			let mut bytes = vec![0; header_len as usize];
+			file.read_exact(&mut bytes)?;
+
+			let mut corrupt_entries = 0;
+			for (i, entry) in bytes.chunks_exact(HEADER_ENTRY_SIZE).enumerate() {
+				let range = SectorRange {
+					start: u32::from_le_bytes(entry[..4].try_into().unwrap()),
+					count: u32::from_le_bytes(entry[4..].try_into().unwrap()),
+				};
+				if range.is_empty() {
+					continue;
+				}
+
+				// entries pointing into the header, past the end of the file, or at another chunk's sectors are dropped
+				let in_bounds = range.start >= HEADER_SECTORS && range.start.checked_add(range.count).is_some_and(|end| end <= file_sectors);
+				if !in_bounds || used_sectors[range.start as usize..range.end() as usize].contains(&true) {
+					corrupt_entries += 1;
+					continue;
+				}
+
+				header[i] = range;
+				used_sectors[range.start as usize..range.end() as usize].fill(true);
+			}
+
+			if corrupt_entries != 0 {
+				warn!("region file {} has {} invalid chunk entries, those chunks will be regenerated", file_name.display(), corrupt_entries);
+			}
+		}
+
+		let mut inner = RegionFileInner {
+			file,
+			header,
+			used_sectors,
+		};
+		inner.trim()?;
+
+		Ok(RegionFile {
+			region,
+			inner: Mutex::new(inner),
+		})
+	}
+
+	pub fn region(&self) -> IVec3 {
+		self.region
+	}
+
+	pub fn contains_chunk(&self, chunk: ChunkPos) -> bool {
+		debug_assert_eq!(region_pos(chunk), self.region);
+		!self.inner.lock().header[chunk_index(chunk)].is_empty()
+	}
+
+	// every chunk saved in the region, corrupt chunks are included until they are read
+	pub fn saved_chunks(&self) -> Vec<ChunkPos> {
+		self.inner.lock().header.iter()
+			.enumerate()
+			.filter(|(_, range)| !range.is_empty())
+			.map(|(index, _)| chunk_at_index(self.region, index))
+			.collect()
+	}
+
+	// returns none if the chunk was never saved, or if its data is corrupt and it should be regenerated
+	pub fn read_chunk(&self, chunk: ChunkPos) -> Result<Option<Vec<u8>>> {
+		debug_assert_eq!(region_pos(chunk), self.region);
+		let index = chunk_index(chunk);
+
+		let mut inner = self.inner.lock();
+		let range = inner.header[index];
+		if range.is_empty() {
+			return Ok(None);
+		}
+
+		inner.file.seek(SeekFrom::Start(range.offset()))?;
+		let mut length = [0; LENGTH_PREFIX_SIZE as usize];
+		inner.file.read_exact(&mut length)?;
+		let length = u32::from_le_bytes(length) as usize;
+
+		if sectors_needed(length) > range.count {
+			warn!("chunk {:?} has an invalid length of {} bytes in its region file, regenerating it", chunk, length);
+			inner.set_used(range, false);
+			inner.discard_chunk(index)?;
+			inner.trim()?;
+			return Ok(None);
+		}
+
+		let mut payload = vec![0; length];
+		inner.file.read_exact(&mut payload)?;
+		Ok(Some(payload))
+	}
+
+	pub fn write_chunk(&self, chunk: ChunkPos, payload: &[u8]) -> Result<()> {
+		debug_assert_eq!(region_pos(chunk), self.region);
+		self.inner.lock().write_chunk(chunk_index(chunk), payload)
+	}
+
+	// writes the chunk only if it isn't saved yet, returns false if it already was
+	// the check and the write happen under the same lock, so a chunk saved in between is never overwritten
+	pub fn write_new_chunk(&self, chunk: ChunkPos, payload: &[u8]) -> Result<bool> {
+		debug_assert_eq!(region_pos(chunk), self.region);
+		let index = chunk_index(chunk);
+
+		let mut inner = self.inner.lock();
+		if !inner.header[index].is_empty() {
+			return Ok(false);
+		}
+		inner.write_chunk(index, payload)?;
+		Ok(true)
+	}
+
+	// frees the sectors used by the chunk
+	pub fn remove_chunk(&self, chunk: ChunkPos) -> Result<()> {
+		debug_assert_eq!(region_pos(chunk), self.region);
+		let index = chunk_index(chunk);
+
+		let mut inner = self.inner.lock();
+		let range = inner.header[index];
+		if range.is_empty() {
+			return Ok(());
+		}
+
+		inner.set_used(range, false);
+		inner.discard_chunk(index)?;
+		inner.trim()
+	}
+
+	pub fn sync(&self) -> Result<()> {
+		self.inner.lock().file.sync_all()?;
+		Ok(())
+	}
+
+	// size of the file in bytes
+	pub fn len(&self) -> u64 {
+		self.inner.lock().used_sectors.len() as u64 * SECTOR_SIZE
+	}
+
+	// true if no chunks are saved in the file, it still has a header
+	pub fn is_empty(&self) -> bool {
+		self.inner.lock().header.iter().all(SectorRange::is_empty)
+	}
+}
+
+// opens region files in the world's region directory as chunks in them are read and written
+// only the most recently used region files are kept open, along with any still being used
+#[derive(Debug)]
+pub struct RegionCache {
+	directory: PathBuf,
+	max_open: usize,
+	// most recently used last
+	open_regions: Mutex<Vec<Arc<RegionFile>>>,
+}
+
+impl RegionCache {
+	// regions are stored in `region` inside the world directory
+	pub fn new<T: AsRef<Path>>(world_directory: T, max_open: usize) -> Result<Self> {
+		let directory = world_directory.as_ref().join("region");
+		fs::create_dir_all(&directory)?;
+
+		Ok(RegionCache {
+			directory,
+			max_open: max_open.max(1),
+			open_regions: Mutex::new(Vec::new()),
+		})
+	}
+
+	fn region_file_name(&self, region: IVec3) -> PathBuf {
+		self.directory.join(format!("r.{}.{}.{}.bin", region.x, region.y, region.z))
+	}
+
+	// the opposite of region_file_name, none if the file isn't a region file
+	fn region_from_file_name(file_name: &str) -> Option<IVec3> {
+		let coordinates = file_name.strip_prefix("r.")?.strip_suffix(".bin")?
+			.split('.')
+			.map(|coordinate| coordinate.parse().ok())
+			.collect::<Option<Vec<i32>>>()?;
+
+		match coordinates[..] {
+			[x, y, z] => Some(IVec3::new(x, y, z)),
+			_ => None,
+		}
+	}
+
+	// every region with a file in the region directory, files which aren't region files are ignored
+	pub fn saved_regions(&self) -> Result<Vec<IVec3>> {
+		let mut regions = Vec::new();
+		for entry in fs::read_dir(&self.directory)? {
+			if let Some(region) = entry?.file_name().to_str().and_then(Self::region_from_file_name) {
+				regions.push(region);
+			}
+		}
+		Ok(regions)
+	}
+
+	// every chunk saved in every region, the region files are opened to read their headers
+	pub fn saved_chunks(&self) -> Result<Vec<ChunkPos>> {
+		let mut chunks = Vec::new();
+		for region in self.saved_regions()? {
+			chunks.extend(self.region_file(ChunkPos(region * REGION_SIZE))?.saved_chunks());
+		}
+		Ok(chunks)
+	}
+
+	// size of the region's file in bytes
+	pub fn region_len(&self, region: IVec3) -> Result<u64> {
+		Ok(self.region_file(ChunkPos(region * REGION_SIZE))?.len())
+	}
+
+	fn region_file(&self, chunk: ChunkPos) -> Result<Arc<RegionFile>> {
+		let region = region_pos(chunk);
+		let mut open_regions = self.open_regions.lock();
+
+		let file = match open_regions.iter().position(|file| file.region() == region) {
+			Some(i) => open_regions.remove(i),
+			// the file is opened with the lock held, and files still used by another thread are never evicted,
+			// so the same region is never open twice, with two headers handing out the same sectors
+			None => Arc::new(RegionFile::open(self.region_file_name(region), region)?),
+		};
+		open_regions.push(file.clone());
+
+		// only the cache holds a file if its count is 1, and it can't be taken without the lock
+		// if too many files are in use more than max_open are kept open, and they are evicted on a later lookup once they aren't
+		// the file being returned is held here, so it is never evicted
+		while open_regions.len() > self.max_open {
+			match open_regions.iter().position(|file| Arc::strong_count(file) == 1) {
+				Some(i) => {
+					open_regions.remove(i);
+				},
+				None => break,
+			}
+		}
+		Ok(file)
+	}
+
+	// fails if the region file can't be opened
+	pub fn contains_chunk(&self, chunk: ChunkPos) -> Result<bool> {
+		Ok(self.region_file(chunk)?.contains_chunk(chunk))
+	}
+
+	pub fn read_chunk(&self, chunk: ChunkPos) -> Result<Option<Vec<u8>>> {
+		self.region_file(chunk)?.read_chunk(chunk)
+	}
+
+	pub fn write_chunk(&self, chunk: ChunkPos, payload: &[u8]) -> Result<()> {
+		self.region_file(chunk)?.write_chunk(chunk, payload)
+	}
+
+	// see RegionFile::write_new_chunk
+	pub fn write_new_chunk(&self, chunk: ChunkPos, payload: &[u8]) -> Result<bool> {
+		self.region_file(chunk)?.write_new_chunk(chunk, payload)
+	}
+
+	pub fn remove_chunk(&self, chunk: ChunkPos) -> Result<()> {
+		self.region_file(chunk)?.remove_chunk(chunk)
+	}
+
+	pub fn open_region_count(&self) -> usize {
+		self.open_regions.lock().len()
+	}
+
+	pub fn sync_all(&self) -> Result<()> {
+		for file in self.open_regions.lock().iter() {
+			file.sync()?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn test_directory(name: &str) -> PathBuf {
+		let directory = std::env::temp_dir().join(format!("minecone-region-test-{}-{}", name, std::process::id()));
+		let _ = fs::remove_dir_all(&directory);
+		fs::create_dir_all(&directory).unwrap();
+		directory
+	}
+
+	fn payload(seed: u8, len: usize) -> Vec<u8> {
+		(0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect()
+	}
+
+	#[test]
+	fn rewriting_chunks_reuses_sectors() {
+		let directory = test_directory("rewrite");
+		let file_name = directory.join("r.0.0.0.bin");
+		let region = RegionFile::open(&file_name, IVec3::ZERO).unwrap();
+		let header_len = HEADER_SECTORS as u64 * SECTOR_SIZE;
+		assert_eq!(region.len(), header_len);
+		assert!(region.is_empty());
+
+		let a = ChunkPos::new(0, 0, 0);
+		let b = ChunkPos::new(3, 15, 7);
+		region.write_chunk(a, &payload(1, 5000)).unwrap();
+		region.write_chunk(b, &payload(2, 100)).unwrap();
+		assert_eq!(region.len(), header_len + 3 * SECTOR_SIZE);
+		assert!(!region.is_empty());
+
+		// a larger payload doesn't fit in a's 2 sectors, so it moves to the end
+		region.write_chunk(a, &payload(3, 9000)).unwrap();
+		assert_eq!(region.read_chunk(a).unwrap(), Some(payload(3, 9000)));
+		assert_eq!(region.read_chunk(b).unwrap(), Some(payload(2, 100)));
+		assert_eq!(region.len(), header_len + 6 * SECTOR_SIZE);
+
+		// the 2 sectors a left behind are reused
+		let c = ChunkPos::new(15, 0, 15);
+		region.write_chunk(c, &payload(4, 6000)).unwrap();
+		assert_eq!(region.len(), header_len + 6 * SECTOR_SIZE);
+
+		// a smaller payload stays in place, and the freed sectors at the end are trimmed
+		region.write_chunk(a, &payload(5, 10)).unwrap();
+		assert_eq!(region.read_chunk(a).unwrap(), Some(payload(5, 10)));
+		assert_eq!(region.len(), header_len + 4 * SECTOR_SIZE);
+
+		assert_eq!(region.read_chunk(ChunkPos::new(1, 1, 1)).unwrap(), None);
+		drop(region);
+
+		// everything is still there after reopening
+		let region = RegionFile::open(&file_name, IVec3::ZERO).unwrap();
+		assert_eq!(region.read_chunk(a).unwrap(), Some(payload(5, 10)));
+		assert_eq!(region.read_chunk(b).unwrap(), Some(payload(2, 100)));
+		assert_eq!(region.read_chunk(c).unwrap(), Some(payload(4, 6000)));
+
+		assert!(region.contains_chunk(a));
+		region.remove_chunk(a).unwrap();
+		assert_eq!(region.read_chunk(a).unwrap(), None);
+		assert!(!region.contains_chunk(a));
+		assert_eq!(region.len(), header_len + 3 * SECTOR_SIZE);
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn new_chunks_dont_replace_saved_chunks() {
+		let directory = test_directory("new");
+		let region = RegionFile::open(directory.join("r.0.0.0.bin"), IVec3::ZERO).unwrap();
+		let chunk = ChunkPos::new(1, 2, 3);
+
+		assert!(region.write_new_chunk(chunk, &payload(1, 100)).unwrap());
+		assert!(!region.write_new_chunk(chunk, &payload(2, 100)).unwrap());
+		assert_eq!(region.read_chunk(chunk).unwrap(), Some(payload(1, 100)));
+		drop(region);
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn corrupt_chunks_are_regenerated() {
+		let directory = test_directory("corrupt");
+		let file_name = directory.join("r.0.0.0.bin");
+		let region = RegionFile::open(&file_name, IVec3::ZERO).unwrap();
+		let good = ChunkPos::new(0, 0, 0);
+		let bad_header = ChunkPos::new(1, 0, 0);
+		let bad_length = ChunkPos::new(2, 0, 0);
+		region.write_chunk(good, &payload(1, 100)).unwrap();
+		region.write_chunk(bad_header, &payload(2, 100)).unwrap();
+		region.write_chunk(bad_length, &payload(3, 100)).unwrap();
+		drop(region);
+
+		let mut file = OpenOptions::new().write(true).open(&file_name).unwrap();
+		// point bad_header past the end of the file
+		file.seek(SeekFrom::Start((chunk_index(bad_header) * HEADER_ENTRY_SIZE) as u64)).unwrap();
+		file.write_all(&1000u32.to_le_bytes()).unwrap();
+		// give bad_length a payload longer than its sectors
+		file.seek(SeekFrom::Start((HEADER_SECTORS + 2) as u64 * SECTOR_SIZE)).unwrap();
+		file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+		drop(file);
+
+		let region = RegionFile::open(&file_name, IVec3::ZERO).unwrap();
+		assert_eq!(region.read_chunk(good).unwrap(), Some(payload(1, 100)));
+		assert_eq!(region.read_chunk(bad_header).unwrap(), None);
+		assert_eq!(region.read_chunk(bad_length).unwrap(), None);
+
+		// a truncated header resets the whole region
+		drop(region);
+		OpenOptions::new().write(true).open(&file_name).unwrap().set_len(100).unwrap();
+		let region = RegionFile::open(&file_name, IVec3::ZERO).unwrap();
+		assert_eq!(region.read_chunk(good).unwrap(), None);
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn concurrent_reads() {
+		let directory = test_directory("concurrent");
+		let cache = Arc::new(RegionCache::new(&directory, 2).unwrap());
+
+		// chunks spread over more regions than are kept open, with negative positions
+		let chunks: Vec<ChunkPos> = (-20..20)
+			.step_by(3)
+			.flat_map(|x| [ChunkPos::new(x, -x, 5), ChunkPos::new(x, 0, -17)])
+			.collect();
+		for (i, chunk) in chunks.iter().enumerate() {
+			cache.write_chunk(*chunk, &payload(i as u8, 1000 + i * 700)).unwrap();
+		}
+		assert_eq!(cache.open_region_count(), 2);
+
+		let threads: Vec<_> = (0..4).map(|thread| {
+			let cache = cache.clone();
+			let chunks = chunks.clone();
+			std::thread::spawn(move || {
+				for _ in 0..8 {
+					for (i, chunk) in chunks.iter().enumerate().skip(thread) {
+						assert_eq!(cache.read_chunk(*chunk).unwrap(), Some(payload(i as u8, 1000 + i * 700)));
+					}
+				}
+			})
+		}).collect();
+
+		for thread in threads {
+			thread.join().unwrap();
+		}
+
+		assert!(directory.join("region/r.-2.1.0.bin").exists());
+		assert!(directory.join("region/r.1.0.-2.bin").exists());
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn concurrent_writes_with_one_open_region() {
+		let directory = test_directory("concurrent-writes");
+		let cache = Arc::new(RegionCache::new(&directory, 1).unwrap());
+
+		// every thread writes to both regions, so files are evicted while other threads are writing to them
+		let thread_chunks = |thread: i32| [ChunkPos::new(thread, 0, 0), ChunkPos::new(REGION_SIZE + thread, 0, 0)];
+		let threads: Vec<_> = (0..4).map(|thread| {
+			let cache = cache.clone();
+			std::thread::spawn(move || {
+				for i in 0..50 {
+					for chunk in thread_chunks(thread) {
+						cache.write_chunk(chunk, &payload(i as u8, 1000 + i * 300)).unwrap();
+					}
+				}
+			})
+		}).collect();
+
+		for thread in threads {
+			thread.join().unwrap();
+		}
+
+		for thread in 0..4 {
+			for chunk in thread_chunks(thread) {
+				assert_eq!(cache.read_chunk(chunk).unwrap(), Some(payload(49, 1000 + 49 * 300)));
+			}
+		}
+		// files are only kept open past the limit while they are in use
+		assert_eq!(cache.open_region_count(), 1);
+		drop(cache);
+
+		// the headers written by every thread agree with each other once the regions are opened again
+		let cache = RegionCache::new(&directory, 1).unwrap();
+		for thread in 0..4 {
+			for chunk in thread_chunks(thread) {
+				assert_eq!(cache.read_chunk(chunk).unwrap(), Some(payload(49, 1000 + 49 * 300)));
+			}
+		}
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn saved_chunks_are_listed() {
+		let directory = test_directory("list");
+		let cache = RegionCache::new(&directory, 2).unwrap();
+		let mut chunks = vec![
+			ChunkPos::new(0, 0, 0),
+			ChunkPos::new(15, 3, 9),
+			ChunkPos::new(-1, -16, 40),
+			ChunkPos::new(-33, 7, -2),
+		];
+		for chunk in chunks.iter() {
+			cache.write_chunk(*chunk, &payload(1, 10)).unwrap();
+		}
+		// files which aren't regions are skipped
+		fs::write(directory.join("region/notes.txt"), "not a region").unwrap();
+
+		let mut saved = cache.saved_chunks().unwrap();
+		saved.sort_by_key(|chunk| chunk.to_array());
+		chunks.sort_by_key(|chunk| chunk.to_array());
+		assert_eq!(saved, chunks);
+		// the first 2 chunks share a region
+		assert_eq!(cache.saved_regions().unwrap().len(), 3);
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+}
@@ -1,62 +1,105 @@
-use std::collections::hash_set::Iter;
-
 use rustc_hash::FxHashSet;
 
-use crate::{prelude::*, render::Aabb};
+use crate::{prelude::*, math::Aabb};
 
 pub const RENDER_ZONE_SIZE: i32 = 4;
 
-pub struct UpdatedRenderZones(FxHashSet<ChunkPos>);
+// returns the position of the render zone containing the chunk
+pub fn render_zone_of_chunk(chunk: ChunkPos) -> ChunkPos {
+    ChunkPos(RENDER_ZONE_SIZE * chunk.map(|elem| {
+        if elem >= 0 {
+            elem / RENDER_ZONE_SIZE
+        } else {
+            (elem - RENDER_ZONE_SIZE + 1) / RENDER_ZONE_SIZE
+        }
+    }))
+}
+
+// set of all chunks whose mesh needs to be rebuilt
+// meshes are stored per chunk, so only the chunks that changed have to be uploaded again
+pub struct UpdatedChunks(FxHashSet<ChunkPos>);
 
-impl UpdatedRenderZones {
+impl UpdatedChunks {
     pub fn new() -> Self {
-        UpdatedRenderZones(FxHashSet::default())
+        UpdatedChunks(FxHashSet::default())
     }
 
-    fn get_render_zone_of_chunk(chunk: ChunkPos) -> ChunkPos {
-		ChunkPos(RENDER_ZONE_SIZE * chunk.map(|elem| {
-			if elem >= 0 {
-				elem / RENDER_ZONE_SIZE
-			} else {
-				(elem - RENDER_ZONE_SIZE + 1) / RENDER_ZONE_SIZE
-			}
-		}))
-	}
-
     pub fn mark_block(&mut self, block: BlockPos) {
         self.mark_chunk(block.into());
     }
 
     pub fn mark_chunk(&mut self, chunk: ChunkPos) {
-        self.0.insert(Self::get_render_zone_of_chunk(chunk));
+        self.0.insert(chunk);
     }
 
     pub fn mark_chunk_zone(&mut self, min_chunk: ChunkPos, max_chunk: ChunkPos) {
-        let min_render_zone = Self::get_render_zone_of_chunk(min_chunk);
-		let max_render_zone = Self::get_render_zone_of_chunk(max_chunk - ChunkPos::splat(1));
-
-		for x in (min_render_zone.x..=max_render_zone.x).step_by(RENDER_ZONE_SIZE as usize) {
-			for y in (min_render_zone.y..=max_render_zone.y).step_by(RENDER_ZONE_SIZE as usize) {
-				for z in (min_render_zone.z..=max_render_zone.z).step_by(RENDER_ZONE_SIZE as usize) {
-					self.0.insert(ChunkPos::new(x, y, z));
-				}
-			}
-		}
+        self.0.extend(ChunkPos::iter_range(min_chunk, max_chunk));
     }
 
     pub fn clear(&mut self) {
         self.0.clear();
     }
 
-    pub fn iter(&self) -> Iter<ChunkPos> {
-        self.0.iter()
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    // removes and returns up to count chunks, closest to the center chunk first
+    // the rest stay marked, so they can be taken later
+    pub fn take_nearest(&mut self, center: ChunkPos, count: usize) -> Vec<ChunkPos> {
+        let mut chunks = self.0.iter().copied().collect::<Vec<_>>();
+        chunks.sort_unstable_by_key(|chunk| {
+            let offset = chunk.0 - center.0;
+            (offset.dot(offset), chunk.to_array())
+        });
+        chunks.truncate(count);
+
+        for chunk in chunks.iter() {
+            self.0.remove(chunk);
+        }
+        chunks
     }
 }
 
-/// returns the axis aligned bounding box for the render zone
+// returns the axis aligned bounding box for the render zone
 pub fn render_zone_aabb(render_zone: ChunkPos) -> Aabb {
     Aabb::new(
         render_zone.as_position().0,
         ChunkPos::splat(RENDER_ZONE_SIZE).as_position().0,
     )
-}
\ No newline at end of file
+}
+
+// returns the axis aligned bounding box for the chunk
+pub fn chunk_aabb(chunk: ChunkPos) -> Aabb {
+    Aabb::new(
+        chunk.as_position().0,
+        ChunkPos::splat(1).as_position().0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_chunks_are_taken_first() {
+        let mut updated_chunks = UpdatedChunks::new();
+        updated_chunks.mark_chunk_zone(ChunkPos::new(-2, 0, 0), ChunkPos::new(3, 1, 1));
+        // marking a chunk again doesn't queue it twice
+        updated_chunks.mark_block(BlockPos::new(0, 5, 5));
+
+        let center = ChunkPos::new(1, 0, 0);
+        assert_eq!(updated_chunks.take_nearest(center, 3), vec![
+            ChunkPos::new(1, 0, 0),
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(2, 0, 0),
+        ]);
+        assert_eq!(updated_chunks.len(), 2);
+        assert_eq!(updated_chunks.take_nearest(center, 3), vec![ChunkPos::new(-1, 0, 0), ChunkPos::new(-2, 0, 0)]);
+        assert!(updated_chunks.is_empty());
+    }
+}
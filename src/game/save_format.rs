@@ -0,0 +1,198 @@
+use anyhow::{anyhow, bail};
+use glam::{IVec3, Vec3};
+
+use crate::prelude::*;
+
+// everything saved in region files is a record, which is a list of tagged fields
+// each field is a 2 byte tag, a 4 byte length, and then that many bytes, all numbers are little endian
+// a tag can be repeated for lists, and a field can hold another record
+// readers take the fields they know and keep the rest, so they can be written back unchanged,
+// this way fields can be added without breaking old saves, and old versions don't drop data from newer ones
+pub type Tag = u16;
+
+const FIELD_HEADER_SIZE: usize = 6;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+	fields: Vec<(Tag, Vec<u8>)>,
+}
+
+impl Record {
+	pub fn new() -> Self {
+		Record {
+			fields: Vec::new(),
+		}
+	}
+
+	pub fn decode(mut bytes: &[u8]) -> Result<Self> {
+		let mut fields = Vec::new();
+
+		while !bytes.is_empty() {
+			if bytes.len() < FIELD_HEADER_SIZE {
+				bail!("field header is truncated");
+			}
+			let tag = Tag::from_le_bytes([bytes[0], bytes[1]]);
+			let length = u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]) as usize;
+
+			let data = bytes.get(FIELD_HEADER_SIZE..FIELD_HEADER_SIZE + length)
+				.ok_or_else(|| anyhow!("field {} is truncated", tag))?;
+			fields.push((tag, data.to_vec()));
+			bytes = &bytes[FIELD_HEADER_SIZE + length..];
+		}
+
+		Ok(Record {
+			fields,
+		})
+	}
+
+	pub fn encode(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		for (tag, data) in self.fields.iter() {
+			out.extend(tag.to_le_bytes());
+			out.extend((data.len() as u32).to_le_bytes());
+			out.extend(data);
+		}
+		out
+	}
+
+	// fields with the same tag are kept in the order they are pushed
+	pub fn push(&mut self, tag: Tag, data: Vec<u8>) {
+		self.fields.push((tag, data));
+	}
+
+	// adds every field of the other record, after the fields already in this one
+	pub fn extend(&mut self, other: Record) {
+		self.fields.extend(other.fields);
+	}
+
+	pub fn push_u8(&mut self, tag: Tag, value: u8) {
+		self.push(tag, vec![value]);
+	}
+
+	pub fn push_u32(&mut self, tag: Tag, value: u32) {
+		self.push(tag, value.to_le_bytes().to_vec());
+	}
+
+	pub fn push_u64(&mut self, tag: Tag, value: u64) {
+		self.push(tag, value.to_le_bytes().to_vec());
+	}
+
+	pub fn push_f32(&mut self, tag: Tag, value: f32) {
+		self.push(tag, value.to_le_bytes().to_vec());
+	}
+
+	pub fn push_str(&mut self, tag: Tag, value: &str) {
+		self.push(tag, value.as_bytes().to_vec());
+	}
+
+	pub fn push_ivec3(&mut self, tag: Tag, value: IVec3) {
+		self.push(tag, value.to_array().into_iter().flat_map(i32::to_le_bytes).collect());
+	}
+
+	pub fn push_vec3(&mut self, tag: Tag, value: Vec3) {
+		self.push(tag, value.to_array().into_iter().flat_map(f32::to_le_bytes).collect());
+	}
+
+	pub fn push_record(&mut self, tag: Tag, record: &Record) {
+		self.push(tag, record.encode());
+	}
+
+	// removes and returns the first field with the tag, none if there isn't one
+	pub fn take(&mut self, tag: Tag) -> Option<Vec<u8>> {
+		let index = self.fields.iter().position(|(field_tag, _)| *field_tag == tag)?;
+		Some(self.fields.remove(index).1)
+	}
+
+	// removes and returns every field with the tag, in order
+	pub fn take_all(&mut self, tag: Tag) -> Vec<Vec<u8>> {
+		let (taken, kept) = std::mem::take(&mut self.fields).into_iter()
+			.partition(|(field_tag, _)| *field_tag == tag);
+		self.fields = kept;
+		taken.into_iter().map(|(_, data)| data).collect()
+	}
+
+	// the take functions for values return none if the field is missing, and fail if it has the wrong size
+	fn take_array<const N: usize>(&mut self, tag: Tag) -> Result<Option<[u8; N]>> {
+		self.take(tag)
+			.map(|data| data.try_into().map_err(|data: Vec<u8>| anyhow!("field {} is {} bytes, expected {}", tag, data.len(), N)))
+			.transpose()
+	}
+
+	pub fn take_u8(&mut self, tag: Tag) -> Result<Option<u8>> {
+		Ok(self.take_array::<1>(tag)?.map(|[value]| value))
+	}
+
+	pub fn take_u32(&mut self, tag: Tag) -> Result<Option<u32>> {
+		Ok(self.take_array(tag)?.map(u32::from_le_bytes))
+	}
+
+	pub fn take_u64(&mut self, tag: Tag) -> Result<Option<u64>> {
+		Ok(self.take_array(tag)?.map(u64::from_le_bytes))
+	}
+
+	pub fn take_f32(&mut self, tag: Tag) -> Result<Option<f32>> {
+		Ok(self.take_array(tag)?.map(f32::from_le_bytes))
+	}
+
+	pub fn take_string(&mut self, tag: Tag) -> Result<Option<String>> {
+		self.take(tag)
+			.map(|data| String::from_utf8(data).map_err(|_| anyhow!("field {} is not valid utf-8", tag)))
+			.transpose()
+	}
+
+	pub fn take_ivec3(&mut self, tag: Tag) -> Result<Option<IVec3>> {
+		Ok(self.take_array::<12>(tag)?.map(|data| {
+			IVec3::from_array([0, 1, 2].map(|i| i32::from_le_bytes(data[4 * i..4 * i + 4].try_into().unwrap())))
+		}))
+	}
+
+	pub fn take_vec3(&mut self, tag: Tag) -> Result<Option<Vec3>> {
+		Ok(self.take_array::<12>(tag)?.map(|data| {
+			Vec3::from_array([0, 1, 2].map(|i| f32::from_le_bytes(data[4 * i..4 * i + 4].try_into().unwrap())))
+		}))
+	}
+
+	pub fn take_record(&mut self, tag: Tag) -> Result<Option<Record>> {
+		self.take(tag).map(|data| Record::decode(&data)).transpose()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn records_round_trip() {
+		let mut inner = Record::new();
+		inner.push_str(0, "chest");
+		inner.push_u32(1, 27);
+
+		let mut record = Record::new();
+		record.push_u8(0, 3);
+		record.push_u64(1, u64::MAX - 5);
+		record.push_f32(2, -1.5);
+		record.push_ivec3(3, IVec3::new(-4, 100, 7));
+		record.push_vec3(4, Vec3::new(0.25, -8.0, 1e6));
+		record.push_record(5, &inner);
+		record.push_record(5, &Record::new());
+		// a field from a newer version
+		record.push(900, vec![1, 2, 3]);
+
+		let mut decoded = Record::decode(&record.encode()).unwrap();
+		assert_eq!(decoded, record);
+		assert_eq!(decoded.take_u8(0).unwrap(), Some(3));
+		assert_eq!(decoded.take_u64(1).unwrap(), Some(u64::MAX - 5));
+		assert_eq!(decoded.take_f32(2).unwrap(), Some(-1.5));
+		assert_eq!(decoded.take_ivec3(3).unwrap(), Some(IVec3::new(-4, 100, 7)));
+		assert_eq!(decoded.take_vec3(4).unwrap(), Some(Vec3::new(0.25, -8.0, 1e6)));
+		assert_eq!(decoded.take_all(5).len(), 2);
+		assert_eq!(decoded.take_u32(6).unwrap(), None);
+		// only the unknown field is left
+		assert_eq!(decoded.encode(), [132, 3, 3, 0, 0, 0, 1, 2, 3]);
+
+		let mut wrong_size = Record::new();
+		wrong_size.push_u8(0, 1);
+		assert!(wrong_size.take_u32(0).is_err());
+		assert!(Record::decode(&record.encode()[..20]).is_err());
+	}
+}
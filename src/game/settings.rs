@@ -0,0 +1,120 @@
+use winit::event::VirtualKeyCode;
+
+// range of the ui scale slider
+pub const MIN_UI_SCALE: f32 = 0.5;
+pub const MAX_UI_SCALE: f32 = 2.0;
+
+// user adjustable options, edited through the settings window
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Settings {
+	pub sprint: SprintSettings,
+	pub graphics: GraphicsSettings,
+	pub interaction: InteractionSettings,
+	pub interface: InterfaceSettings,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SprintSettings {
+	// double tapping forward starts sprinting until forward is released
+	pub double_tap_sprint: bool,
+	// pressing sprint toggles sprinting instead of having to hold it
+	pub toggle_sprint: bool,
+	pub sprint_key: VirtualKeyCode,
+}
+
+impl Default for SprintSettings {
+	fn default() -> Self {
+		SprintSettings {
+			double_tap_sprint: false,
+			toggle_sprint: false,
+			sprint_key: VirtualKeyCode::LControl,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GraphicsSettings {
+	// how dark ambient occlusion makes corners, 0 turns it off and 1 is full strength
+	pub ao_strength: f32,
+	// sides and bottoms of blocks are darker than their tops
+	pub face_shading: bool,
+	// averages ambient occlusion between adjacent vertices, changing this remeshes the world
+	pub smooth_lighting: bool,
+	// dark circles on the ground under entities
+	pub blob_shadows: bool,
+	// terrain shadows from the sun
+	pub sun_shadows: bool,
+	// width and height of each shadow cascade, in texels
+	pub shadow_resolution: u32,
+	// how quickly the camera catches up to where the player is, when it is moved by something other than the player
+	pub camera_smoothing_rate: f32,
+	// render zones further than this from the camera are drawn with less detail, in blocks
+	pub full_detail_distance: f32,
+	// water under the camera reflects the terrain, which draws the terrain a second time
+	pub water_reflections: bool,
+	// most chunk meshes uploaded each physics update, the rest wait for later updates, so large loads don't cause a hitch
+	pub mesh_uploads_per_update: usize,
+	// frames wait for the display to refresh before they are shown, without it frames are rendered as fast as possible
+	pub vsync: bool,
+}
+
+impl Default for GraphicsSettings {
+	fn default() -> Self {
+		GraphicsSettings {
+			ao_strength: 1.0,
+			face_shading: true,
+			smooth_lighting: false,
+			blob_shadows: true,
+			sun_shadows: true,
+			shadow_resolution: 2048,
+			camera_smoothing_rate: 15.0,
+			full_detail_distance: 128.0,
+			water_reflections: false,
+			mesh_uploads_per_update: 32,
+			vsync: true,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InteractionSettings {
+	// how far away blocks can be broken, placed, or picked, in blocks
+	pub survival_reach: f32,
+	pub fly_reach: f32,
+	// key which picks the targeted block, in addition to the middle mouse button
+	pub pick_block_key: Option<VirtualKeyCode>,
+}
+
+impl InteractionSettings {
+	pub fn reach(&self, flying: bool) -> f32 {
+		if flying {
+			self.fly_reach
+		} else {
+			self.survival_reach
+		}
+	}
+}
+
+impl Default for InteractionSettings {
+	fn default() -> Self {
+		InteractionSettings {
+			survival_reach: 5.0,
+			fly_reach: 15.0,
+			pick_block_key: None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InterfaceSettings {
+	// multiplies the os scale factor, so the ui can be made bigger or smaller than other programs on the same display
+	pub ui_scale: f32,
+}
+
+impl Default for InterfaceSettings {
+	fn default() -> Self {
+		InterfaceSettings {
+			ui_scale: 1.0,
+		}
+	}
+}
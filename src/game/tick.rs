@@ -0,0 +1,161 @@
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+// world ticks per second unless configured otherwise
+pub const DEFAULT_TICK_RATE: u32 = 20;
+// if the clock falls further behind than this many ticks, the extra ticks are skipped instead of all being run at once
+const MAX_CATCH_UP_TICKS: u64 = 10;
+const MEASURE_TIME: Duration = Duration::from_secs(1);
+
+// decides when world ticks happen, at a fixed rate independent of frames and physics updates
+#[derive(Debug)]
+pub struct TickClock {
+	tick_time: Duration,
+	next_tick: u64,
+	// time since the last tick which has not been ticked yet
+	accumulated: Duration,
+	last_update: Instant,
+	ticks_per_second: RateCounter,
+}
+
+impl TickClock {
+	pub fn new(tick_rate: u32, now: Instant) -> Self {
+		TickClock {
+			tick_time: Duration::from_secs(1) / tick_rate,
+			next_tick: 0,
+			accumulated: Duration::ZERO,
+			last_update: now,
+			ticks_per_second: RateCounter::new(now),
+		}
+	}
+
+	// returns the numbers of the ticks which are due, they should be run in order
+	pub fn update(&mut self, now: Instant) -> Range<u64> {
+		self.accumulated += now.saturating_duration_since(self.last_update);
+		self.last_update = now;
+
+		let due_ticks = (self.accumulated.as_nanos() / self.tick_time.as_nanos()) as u64;
+		self.accumulated -= self.tick_time * due_ticks as u32;
+		let due_ticks = due_ticks.min(MAX_CATCH_UP_TICKS);
+
+		let ticks = self.next_tick..self.next_tick + due_ticks;
+		self.next_tick += due_ticks;
+
+		self.ticks_per_second.count(due_ticks as u32, now);
+
+		ticks
+	}
+
+	pub fn tick_time(&self) -> Duration {
+		self.tick_time
+	}
+
+	// when update will next return a tick
+	pub fn next_tick_time(&self) -> Instant {
+		self.last_update + (self.tick_time - self.accumulated)
+	}
+
+	// fraction of the way from the last tick to the next one, as of the last update
+	// things which move on ticks are drawn this far between where they were and where they are
+	pub fn alpha(&self) -> f32 {
+		(self.accumulated.as_secs_f32() / self.tick_time.as_secs_f32()).min(1.0)
+	}
+
+	// number of ticks run in the last second
+	pub fn ticks_per_second(&self) -> u32 {
+		self.ticks_per_second.per_second()
+	}
+}
+
+// counts how many times something happened in the last second, like frames rendered or ticks run
+#[derive(Debug)]
+pub struct RateCounter {
+	// counted since measure_start
+	measure_start: Instant,
+	measure_count: u32,
+	per_second: u32,
+}
+
+impl RateCounter {
+	pub fn new(now: Instant) -> Self {
+		RateCounter {
+			measure_start: now,
+			measure_count: 0,
+			per_second: 0,
+		}
+	}
+
+	// count can be 0, so the rate is still updated when nothing happened
+	pub fn count(&mut self, count: u32, now: Instant) {
+		self.measure_count += count;
+		if now.saturating_duration_since(self.measure_start) >= MEASURE_TIME {
+			self.per_second = self.measure_count;
+			self.measure_count = 0;
+			self.measure_start = now;
+		}
+	}
+
+	// the count over the last full second
+	pub fn per_second(&self) -> u32 {
+		self.per_second
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ticks_at_fixed_rate() {
+		let start = Instant::now();
+		let mut clock = TickClock::new(20, start);
+
+		// frames at about 60 fps for 1 second
+		let mut ticks = Vec::new();
+		for frame in 1..=60 {
+			ticks.extend(clock.update(start + Duration::from_micros(frame * 16_667)));
+		}
+
+		assert_eq!(ticks, (0..20).collect::<Vec<_>>());
+		assert_eq!(clock.ticks_per_second(), 20);
+	}
+
+	#[test]
+	fn alpha_is_fraction_of_tick() {
+		let start = Instant::now();
+		let mut clock = TickClock::new(20, start);
+		assert_eq!(clock.alpha(), 0.0);
+
+		clock.update(start + Duration::from_millis(10));
+		assert!((clock.alpha() - 0.2).abs() < 0.001);
+		// the fraction starts over after each tick
+		assert_eq!(clock.update(start + Duration::from_millis(60)), 0..1);
+		assert!((clock.alpha() - 0.2).abs() < 0.001);
+	}
+
+	#[test]
+	fn long_stall_skips_ticks() {
+		let start = Instant::now();
+		let mut clock = TickClock::new(20, start);
+
+		assert_eq!(clock.update(start + Duration::from_secs(5)), 0..MAX_CATCH_UP_TICKS);
+		// the skipped ticks are not run later
+		assert_eq!(clock.update(start + Duration::from_secs(5) + clock.tick_time()), MAX_CATCH_UP_TICKS..MAX_CATCH_UP_TICKS + 1);
+	}
+
+	#[test]
+	fn rate_counts_over_each_second() {
+		let start = Instant::now();
+		let mut frames = RateCounter::new(start);
+
+		// 144 frames in the first second, then nothing for a second
+		for frame in 1..=144 {
+			frames.count(1, start + Duration::from_secs(1) * frame / 144);
+		}
+		assert_eq!(frames.per_second(), 144);
+		frames.count(0, start + Duration::from_millis(1500));
+		assert_eq!(frames.per_second(), 144);
+		frames.count(0, start + Duration::from_secs(2));
+		assert_eq!(frames.per_second(), 0);
+	}
+}
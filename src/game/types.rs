@@ -2,6 +2,7 @@ use std::ops::{Mul, Index, IndexMut};
 
 use glam::{Vec3, IVec3};
 use derive_more::{Deref, DerefMut, Add, Sub, Mul, Div};
+use smallvec::{SmallVec, smallvec};
 
 use super::{CHUNK_SIZE, BlockFace};
 
@@ -83,6 +84,27 @@ macro_rules! only_methods {
     };
 }
 
+// integer distances, which don't need a float conversion like length does
+macro_rules! distance_methods {
+    ($vec:ident) => {
+        impl $vec {
+            pub fn length_squared(&self) -> i32 {
+                self.0.dot(self.0)
+            }
+
+            /// sum of the distances along each axis
+            pub fn manhattan_distance(&self, other: Self) -> i32 {
+                (self.0 - other.0).abs().to_array().iter().sum()
+            }
+
+            /// largest of the distances along each axis
+            pub fn chebyshev_distance(&self, other: Self) -> i32 {
+                (self.0 - other.0).abs().max_element()
+            }
+        }
+    };
+}
+
 /// Position of a chunk in chunk coordinates
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deref, DerefMut, Add, Sub, Mul, Div)]
 pub struct ChunkPos(pub IVec3);
@@ -92,10 +114,20 @@ impl ChunkPos {
         ChunkPos(IVec3::new(x, y, z))
     }
 
+    /// chunk position with every coordinate set to n, often used as a size or radius
     pub fn splat(n: i32) -> Self {
         ChunkPos(IVec3::splat(n))
     }
 
+    /// every chunk from min to max, not including max, in x then y then z order
+    pub fn iter_range(min: ChunkPos, max: ChunkPos) -> impl Iterator<Item = ChunkPos> {
+        (min.x..max.x).flat_map(move |x| {
+            (min.y..max.y).flat_map(move |y| {
+                (min.z..max.z).map(move |z| ChunkPos::new(x, y, z))
+            })
+        })
+    }
+
     pub fn as_block_pos(self) -> BlockPos {
         self.into()
     }
@@ -110,6 +142,7 @@ impl ChunkPos {
 }
 
 only_methods!(ChunkPos, 0);
+distance_methods!(ChunkPos);
 
 impl Mul<ChunkPos> for i32 {
     type Output = ChunkPos;
@@ -138,6 +171,93 @@ impl From<Position> for ChunkPos {
     }
 }
 
+/// Box of chunks, which includes min but not max
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkBox {
+    pub min: ChunkPos,
+    pub max: ChunkPos,
+}
+
+impl ChunkBox {
+    pub fn new(min: ChunkPos, max: ChunkPos) -> Self {
+        ChunkBox {
+            min,
+            max,
+        }
+    }
+
+    /// the chunks within radius of center, like the zone loaded around a player with radius as the render distance
+    pub fn around(center: ChunkPos, radius: ChunkPos) -> Self {
+        ChunkBox::new(center - radius, center + radius)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.max[i] <= self.min[i])
+    }
+
+    /// number of chunks in the box
+    pub fn volume(&self) -> usize {
+        if self.is_empty() {
+            0
+        } else {
+            let size = self.max - self.min;
+            (size.x as i64 * size.y as i64 * size.z as i64) as usize
+        }
+    }
+
+    pub fn contains(&self, chunk: ChunkPos) -> bool {
+        (0..3).all(|i| chunk[i] >= self.min[i] && chunk[i] < self.max[i])
+    }
+
+    /// the chunks in both boxes, none if they don't overlap
+    pub fn intersect(&self, other: ChunkBox) -> Option<ChunkBox> {
+        let intersection = ChunkBox::new(ChunkPos(self.min.max(other.min.0)), ChunkPos(self.max.min(other.max.0)));
+
+        if intersection.is_empty() {
+            None
+        } else {
+            Some(intersection)
+        }
+    }
+
+    /// splits the chunks in this box which are not in other into up to 6 boxes, which don't overlap each other
+    pub fn difference(&self, other: ChunkBox) -> SmallVec<[ChunkBox; 6]> {
+        if self.is_empty() {
+            return SmallVec::new();
+        }
+
+        let overlap = match self.intersect(other) {
+            Some(overlap) => overlap,
+            None => return smallvec![*self],
+        };
+
+        let mut out = SmallVec::new();
+        // the remaining part of this box, cut down to the overlap one axis at a time
+        let mut remaining = *self;
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            if remaining.min[axis] < overlap.min[axis] {
+                let mut slab = remaining;
+                slab.max[axis] = overlap.min[axis];
+                out.push(slab);
+                remaining.min[axis] = overlap.min[axis];
+            }
+
+            if remaining.max[axis] > overlap.max[axis] {
+                let mut slab = remaining;
+                slab.min[axis] = overlap.max[axis];
+                out.push(slab);
+                remaining.max[axis] = overlap.max[axis];
+            }
+        }
+
+        out
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ChunkPos> {
+        ChunkPos::iter_range(self.min, self.max)
+    }
+}
+
 /// Position of a block in chunk space
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deref, DerefMut, Add, Sub, Mul, Div)]
 pub struct BlockPos(pub IVec3);
@@ -201,6 +321,7 @@ impl BlockPos {
 }
 
 only_methods!(BlockPos, 0);
+distance_methods!(BlockPos);
 
 impl Mul<BlockPos> for i32 {
     type Output = BlockPos;
@@ -264,4 +385,130 @@ impl From<BlockPos> for Position {
     fn from(block_pos: BlockPos) -> Self {
         Position(block_pos.0.as_vec3())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // checks every chunk around both boxes is in exactly one piece of the difference if it is only in a, and no piece otherwise
+    fn check_difference(a: ChunkBox, b: ChunkBox) {
+        let pieces = a.difference(b);
+        assert!(pieces.len() <= 6);
+        assert!(pieces.iter().all(|piece| !piece.is_empty()), "empty piece in {:?}", pieces);
+
+        let bounds = ChunkBox::new(
+            ChunkPos(a.min.min(b.min.0)) - ChunkPos::splat(1),
+            ChunkPos(a.max.max(b.max.0)) + ChunkPos::splat(1),
+        );
+        for chunk in bounds.iter() {
+            let count = pieces.iter().filter(|piece| piece.contains(chunk)).count();
+            let expected = if a.contains(chunk) && !b.contains(chunk) { 1 } else { 0 };
+            assert_eq!(count, expected, "chunk {:?} in {:?} - {:?}", chunk, a, b);
+        }
+
+        let volume: usize = pieces.iter().map(ChunkBox::volume).sum();
+        let overlap = a.intersect(b).map_or(0, |overlap| overlap.volume());
+        assert_eq!(volume, a.volume() - overlap);
+    }
+
+    #[test]
+    fn box_difference_covers_difference_once() {
+        let a = ChunkBox::new(ChunkPos::new(0, 0, 0), ChunkPos::new(10, 4, 10));
+
+        // moving by one chunk along each axis and diagonally
+        for offset in [
+            ChunkPos::new(1, 0, 0),
+            ChunkPos::new(-1, 0, 0),
+            ChunkPos::new(0, 1, 0),
+            ChunkPos::new(1, 0, 1),
+            ChunkPos::new(-1, -1, 1),
+            ChunkPos::new(3, 2, -4),
+        ] {
+            check_difference(a, ChunkBox::new(a.min + offset, a.max + offset));
+        }
+
+        // b inside of a leaves a hollow shell, which takes all 6 pieces
+        let inner = ChunkBox::new(ChunkPos::new(2, 1, 2), ChunkPos::new(8, 3, 8));
+        assert_eq!(a.difference(inner).len(), 6);
+        check_difference(a, inner);
+
+        // a inside of b leaves nothing
+        assert!(inner.difference(a).is_empty());
+        // same box leaves nothing
+        assert!(a.difference(a).is_empty());
+
+        // b sticking out of one side of a
+        check_difference(a, ChunkBox::new(ChunkPos::new(5, -2, -2), ChunkPos::new(20, 10, 20)));
+        // boxes with negative coordinates
+        check_difference(
+            ChunkBox::new(ChunkPos::new(-8, -8, -8), ChunkPos::new(-2, 0, 3)),
+            ChunkBox::new(ChunkPos::new(-5, -3, -10), ChunkPos::new(0, 5, 0)),
+        );
+    }
+
+    #[test]
+    fn box_difference_without_overlap() {
+        let a = ChunkBox::new(ChunkPos::new(0, 0, 0), ChunkPos::new(10, 4, 10));
+
+        // teleporting far away gives the whole box
+        let far = ChunkBox::new(ChunkPos::new(100, 0, 0), ChunkPos::new(110, 4, 10));
+        assert_eq!(a.difference(far).as_slice(), &[a]);
+
+        // boxes which only touch don't overlap, since max isn't included
+        let touching = ChunkBox::new(ChunkPos::new(10, 0, 0), ChunkPos::new(20, 4, 10));
+        assert_eq!(a.intersect(touching), None);
+        assert_eq!(a.difference(touching).as_slice(), &[a]);
+
+        // an empty box has no difference, and removing an empty box changes nothing
+        let empty = ChunkBox::new(ChunkPos::new(3, 3, 3), ChunkPos::new(3, 5, 5));
+        assert!(empty.is_empty());
+        assert!(empty.difference(a).is_empty());
+        assert_eq!(a.difference(empty).as_slice(), &[a]);
+    }
+
+    #[test]
+    fn box_intersect_and_contains() {
+        let a = ChunkBox::new(ChunkPos::new(0, 0, 0), ChunkPos::new(4, 4, 4));
+        let b = ChunkBox::new(ChunkPos::new(2, -1, 3), ChunkPos::new(6, 2, 8));
+
+        assert_eq!(a.intersect(b), Some(ChunkBox::new(ChunkPos::new(2, 0, 3), ChunkPos::new(4, 2, 4))));
+        assert_eq!(a.intersect(b), b.intersect(a));
+
+        assert!(a.contains(ChunkPos::new(0, 0, 0)));
+        assert!(a.contains(ChunkPos::new(3, 3, 3)));
+        assert!(!a.contains(ChunkPos::new(4, 0, 0)));
+        assert!(!a.contains(ChunkPos::new(0, -1, 0)));
+
+        assert_eq!(ChunkBox::around(ChunkPos::new(1, 2, 3), ChunkPos::splat(2)).volume(), 64);
+    }
+
+    #[test]
+    fn iter_range_visits_every_chunk_once() {
+        let chunks = ChunkPos::iter_range(ChunkPos::new(-1, 0, 2), ChunkPos::new(1, 3, 4)).collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 2 * 3 * 2);
+        assert_eq!(chunks[0], ChunkPos::new(-1, 0, 2));
+        assert_eq!(chunks[1], ChunkPos::new(-1, 0, 3));
+        assert_eq!(*chunks.last().unwrap(), ChunkPos::new(0, 2, 3));
+
+        let mut deduped = chunks.clone();
+        deduped.sort_by_key(|chunk| chunk.to_array());
+        deduped.dedup();
+        assert_eq!(deduped.len(), chunks.len());
+
+        // empty ranges have no chunks
+        assert_eq!(ChunkPos::iter_range(ChunkPos::new(0, 0, 0), ChunkPos::new(0, 5, 5)).count(), 0);
+        assert_eq!(ChunkPos::iter_range(ChunkPos::new(2, 0, 0), ChunkPos::new(0, 5, 5)).count(), 0);
+    }
+
+    #[test]
+    fn integer_distances() {
+        let a = BlockPos::new(1, -2, 3);
+        let b = BlockPos::new(-3, 4, 3);
+
+        assert_eq!(a.length_squared(), 14);
+        assert_eq!(a.manhattan_distance(b), 10);
+        assert_eq!(a.chebyshev_distance(b), 6);
+        assert_eq!(b.chebyshev_distance(a), 6);
+        assert_eq!(ChunkPos::new(2, 0, -5).chebyshev_distance(ChunkPos::splat(0)), 5);
+    }
+}
@@ -0,0 +1,124 @@
+use egui::{Window, Context, Color32, ColorImage, TextureHandle, Stroke, Vec2, Pos2};
+
+use crate::game::worldgen::{BiomeNoiseData, BIOME_MAP_SIZE};
+
+// size of the biome diagram in points
+const DIAGRAM_SIZE: f32 = 300.0;
+
+// biome information around the player, filled in by the client
+pub struct BiomeOverlayData {
+    pub player_noise: BiomeNoiseData,
+    pub player_biome: String,
+    pub player_underground_biome: String,
+    // colors of the surface biome of columns in a square around the player
+    // the player is in the center, rows go along z and columns along x
+    pub world_biomes: Vec<[u8; 3]>,
+    pub world_biomes_size: usize,
+    // distance in blocks between each sampled column
+    pub world_biomes_step: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BiomeOverlayTab {
+    Diagram,
+    World,
+}
+
+pub struct BiomeOverlay {
+    tab: BiomeOverlayTab,
+    diagram_texture: Option<TextureHandle>,
+    world_texture: Option<TextureHandle>,
+    data: Option<BiomeOverlayData>,
+}
+
+fn color_image(size: usize, colors: &[[u8; 3]]) -> ColorImage {
+    ColorImage {
+        size: [size, size],
+        pixels: colors.iter().map(|[r, g, b]| Color32::from_rgb(*r, *g, *b)).collect(),
+    }
+}
+
+impl BiomeOverlay {
+    pub fn new() -> Self {
+        BiomeOverlay {
+            tab: BiomeOverlayTab::Diagram,
+            diagram_texture: None,
+            world_texture: None,
+            data: None,
+        }
+    }
+
+    pub fn has_diagram(&self) -> bool {
+        self.diagram_texture.is_some()
+    }
+
+    // the biome diagram never changes, so this only has to be called once
+    pub fn set_diagram(&mut self, context: &Context, colors: &[[u8; 3]]) {
+        let image = color_image(BIOME_MAP_SIZE, colors);
+        self.diagram_texture = Some(context.load_texture("biome diagram", image));
+    }
+
+    pub fn set_data(&mut self, context: &Context, data: BiomeOverlayData) {
+        if self.tab == BiomeOverlayTab::World {
+            let image = color_image(data.world_biomes_size, &data.world_biomes);
+            match &mut self.world_texture {
+                Some(texture) => texture.set(image),
+                None => self.world_texture = Some(context.load_texture("world biomes", image)),
+            }
+        }
+
+        self.data = Some(data);
+    }
+
+    pub fn window(&mut self, context: &Context) {
+        let tab = &mut self.tab;
+        let diagram_texture = &self.diagram_texture;
+        let world_texture = &self.world_texture;
+        let data = &self.data;
+
+        Window::new("Biomes").resizable(false).show(context, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(tab, BiomeOverlayTab::Diagram, "Heat / Humidity");
+                ui.selectable_value(tab, BiomeOverlayTab::World, "World");
+            });
+
+            let data = match data {
+                Some(data) => data,
+                None => return,
+            };
+
+            ui.label(format!(
+                "{} (heat: {}, humidity: {})",
+                data.player_biome,
+                data.player_noise.heat,
+                data.player_noise.humidity,
+            ));
+            ui.label(format!("underground: {}", data.player_underground_biome));
+
+            let stroke = Stroke::new(2.0_f32, Color32::WHITE);
+
+            match tab {
+                BiomeOverlayTab::Diagram => if let Some(texture) = diagram_texture {
+                    let rect = ui.image(texture, Vec2::splat(DIAGRAM_SIZE)).rect;
+
+                    // heat goes down the rows, humidity across the columns
+                    let cell_size = DIAGRAM_SIZE / BIOME_MAP_SIZE as f32;
+                    let point = rect.min + Vec2::new(
+                        (data.player_noise.humidity as f32 + 0.5) * cell_size,
+                        (data.player_noise.heat as f32 + 0.5) * cell_size,
+                    );
+                    ui.painter().circle_stroke(point, cell_size, stroke);
+                    ui.label("colder ↑ hotter ↓, drier ← wetter →");
+                },
+                BiomeOverlayTab::World => if let Some(texture) = world_texture {
+                    let rect = ui.image(texture, Vec2::splat(DIAGRAM_SIZE)).rect;
+                    let center: Pos2 = rect.center();
+                    ui.painter().circle_stroke(center, 3.0, stroke);
+
+                    let radius = data.world_biomes_step * (data.world_biomes_size as i32 / 2);
+                    ui.label(format!("{} blocks in each direction, x →, z ↓", radius));
+                },
+            }
+        });
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use egui::{Align2, Context, Id, Key, Modifiers, ScrollArea, TextEdit, Window};
+use egui::text::{CCursor, CCursorRange};
+
+use crate::prelude::*;
+use crate::game::commands::complete;
+
+// older lines are dropped from the scrollback
+const MAX_LINES: usize = 200;
+// older commands are dropped from the history
+const MAX_HISTORY: usize = 100;
+// entered commands are saved here, next to the config file, so they are kept between sessions
+pub const HISTORY_FILE: &str = "console-history.txt";
+
+// commands entered before, which can be gone back through with the arrow keys
+pub struct CommandHistory {
+    // oldest first
+    commands: VecDeque<String>,
+    // index of the command being shown, none while typing a new command
+    position: Option<usize>,
+    // what was being typed before going back through the history, it is shown again after the newest command
+    draft: String,
+}
+
+impl CommandHistory {
+    pub fn new() -> Self {
+        CommandHistory {
+            commands: VecDeque::new(),
+            position: None,
+            draft: String::new(),
+        }
+    }
+
+    // one command on each line, a missing file is an empty history
+    pub fn load(file: &Path) -> Self {
+        let mut history = Self::new();
+        if let Ok(text) = fs::read_to_string(file) {
+            for command in text.lines().filter(|line| !line.trim().is_empty()) {
+                history.push(command.to_owned());
+            }
+        }
+        history
+    }
+
+    pub fn save(&self, file: &Path) -> Result<()> {
+        let mut text = String::new();
+        for command in self.commands.iter() {
+            text.push_str(command);
+            text.push('\n');
+        }
+        Ok(fs::write(file, text)?)
+    }
+
+    // going through the history starts over from the newest command
+    // a command entered twice in a row is only kept once
+    pub fn push(&mut self, command: String) {
+        self.position = None;
+        self.draft.clear();
+        if self.commands.back() == Some(&command) {
+            return;
+        }
+
+        if self.commands.len() >= MAX_HISTORY {
+            self.commands.pop_front();
+        }
+        self.commands.push_back(command);
+    }
+
+    // input is what is typed now, it is kept while going through older commands
+    // returns none if there are no older commands
+    pub fn previous(&mut self, input: &str) -> Option<&str> {
+        let position = match self.position {
+            Some(0) => return None,
+            Some(position) => position - 1,
+            None => {
+                let newest = self.commands.len().checked_sub(1)?;
+                self.draft = input.to_owned();
+                newest
+            },
+        };
+        self.position = Some(position);
+        Some(&self.commands[position])
+    }
+
+    // returns the draft after the newest command, and none if not going through the history
+    pub fn next(&mut self) -> Option<&str> {
+        let position = self.position?;
+        if position + 1 < self.commands.len() {
+            self.position = Some(position + 1);
+            Some(&self.commands[position + 1])
+        } else {
+            self.position = None;
+            Some(&self.draft)
+        }
+    }
+}
+
+// completes the word before the cursor, which is a char index into input
+// returns the new input and cursor, and every completion if there was more than one
+fn tab_complete(input: &str, cursor: usize) -> (String, usize, Vec<String>) {
+    let cursor_byte = input.char_indices().nth(cursor).map_or(input.len(), |(index, _)| index);
+    let completions = complete(input, cursor_byte);
+    if completions.is_empty() {
+        return (input.to_owned(), cursor, completions);
+    }
+
+    let word_start = input[..cursor_byte].rfind(char::is_whitespace).map_or(0, |index| index + 1);
+    let after_cursor = &input[cursor_byte..];
+    let replacement = if completions.len() == 1 {
+        // the next argument can be typed straight away
+        if after_cursor.starts_with(char::is_whitespace) {
+            completions[0].clone()
+        } else {
+            format!("{} ", completions[0])
+        }
+    } else {
+        // as much as all the completions have in common
+        let mut prefix = completions[0].clone();
+        for completion in completions.iter().skip(1) {
+            let common_length = prefix.chars()
+                .zip(completion.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a.len_utf8())
+                .sum();
+            prefix.truncate(common_length);
+        }
+        // nothing is removed from what was typed, even if it only matched ignoring case
+        if prefix.len() < cursor_byte - word_start {
+            input[word_start..cursor_byte].to_owned()
+        } else {
+            prefix
+        }
+    };
+
+    let before = format!("{}{}", &input[..word_start], replacement);
+    let new_cursor = before.chars().count();
+    let completions = if completions.len() > 1 { completions } else { Vec::new() };
+    (before + after_cursor, new_cursor, completions)
+}
+
+// where commands are typed, and where their output is shown
+// the scrollback is kept while the console is closed
+pub struct Console {
+    lines: VecDeque<String>,
+    input: String,
+    // set when the console is opened, so the input gets focus with the cursor after any text already in it
+    focus_input: bool,
+    history: CommandHistory,
+    // none if the history isn't saved
+    history_file: Option<PathBuf>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            lines: VecDeque::new(),
+            input: String::new(),
+            focus_input: false,
+            history: CommandHistory::new(),
+            history_file: None,
+        }
+    }
+
+    // the history is loaded from the file, and saved to it each time a command is entered
+    pub fn with_history_file(file: PathBuf) -> Self {
+        Console {
+            history: CommandHistory::load(&file),
+            history_file: Some(file),
+            ..Self::new()
+        }
+    }
+
+    // input is what the input line starts with
+    pub fn open(&mut self, input: &str) {
+        self.input = input.to_owned();
+        self.focus_input = true;
+    }
+
+    pub fn print(&mut self, line: String) {
+        if self.lines.len() >= MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    // returns the command if one was entered
+    pub fn show(&mut self, context: &Context) -> Option<String> {
+        let mut command = None;
+        let input_id = Id::new("console input");
+
+        Window::new("Console")
+            .collapsible(false)
+            .resizable(false)
+            .default_width(500.0)
+            .anchor(Align2::LEFT_BOTTOM, [8.0, -8.0])
+            .show(context, |ui| {
+                ScrollArea::vertical().max_height(200.0).stick_to_bottom().show(ui, |ui| {
+                    for line in self.lines.iter() {
+                        ui.monospace(line);
+                    }
+                });
+
+                // taken before the text field sees them, so tab doesn't move focus and the arrows don't move the cursor
+                let (tab, up, down) = if ui.memory().has_focus(input_id) {
+                    let mut input = ui.input_mut();
+                    (
+                        input.consume_key(Modifiers::NONE, Key::Tab),
+                        input.consume_key(Modifiers::NONE, Key::ArrowUp),
+                        input.consume_key(Modifiers::NONE, Key::ArrowDown),
+                    )
+                } else {
+                    (false, false, false)
+                };
+
+                let mut output = TextEdit::singleline(&mut self.input)
+                    .id(input_id)
+                    .desired_width(f32::INFINITY)
+                    .show(ui);
+
+                let mut new_cursor = None;
+                if up {
+                    if let Some(previous) = self.history.previous(&self.input) {
+                        self.input = previous.to_owned();
+                        new_cursor = Some(self.input.chars().count());
+                    }
+                } else if down {
+                    if let Some(next) = self.history.next() {
+                        self.input = next.to_owned();
+                        new_cursor = Some(self.input.chars().count());
+                    }
+                } else if tab {
+                    let cursor = output.cursor_range.map_or(self.input.chars().count(), |range| range.primary.ccursor.index);
+                    let (input, cursor, completions) = tab_complete(&self.input, cursor);
+                    self.input = input;
+                    new_cursor = Some(cursor);
+                    if !completions.is_empty() {
+                        self.print(completions.join("  "));
+                    }
+                }
+
+                if self.focus_input {
+                    output.response.request_focus();
+                    new_cursor = Some(self.input.chars().count());
+                    self.focus_input = false;
+                }
+                if let Some(cursor) = new_cursor {
+                    output.state.set_ccursor_range(Some(CCursorRange::one(CCursor::new(cursor))));
+                    output.state.store(ui.ctx(), output.response.id);
+                }
+
+                if output.response.lost_focus() && ui.input().key_pressed(Key::Enter) {
+                    let text = self.input.trim();
+                    if !text.is_empty() {
+                        command = Some(text.to_owned());
+                    }
+                    self.input.clear();
+                }
+
+                if ui.button("Copy").on_hover_text("Copy the scrollback to the clipboard").clicked() {
+                    ui.output().copied_text = self.lines.iter().cloned().collect::<Vec<_>>().join("\n");
+                }
+            });
+
+        if let Some(command) = &command {
+            self.print(format!("> {}", command));
+            self.history.push(command.clone());
+            if let Some(history_file) = &self.history_file {
+                if let Err(error) = self.history.save(history_file) {
+                    error!("could not save console history: {:#}", error);
+                }
+            }
+        }
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_goes_back_and_forth() {
+        let mut history = CommandHistory::new();
+        assert_eq!(history.previous("/ti"), None);
+
+        history.push(String::from("/time set day"));
+        history.push(String::from("/weather rain"));
+        history.push(String::from("/weather rain"));
+
+        assert_eq!(history.previous("/ga"), Some("/weather rain"));
+        assert_eq!(history.previous("/weather rain"), Some("/time set day"));
+        assert_eq!(history.previous("/time set day"), None);
+        assert_eq!(history.next(), Some("/weather rain"));
+        // what was being typed comes back after the newest command
+        assert_eq!(history.next(), Some("/ga"));
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn history_is_bounded_and_saved() {
+        let file = std::env::temp_dir().join(format!("minecone-console-history-test-{}", std::process::id()));
+
+        let mut history = CommandHistory::new();
+        for i in 0..MAX_HISTORY + 5 {
+            history.push(format!("/exportmap {}", i));
+        }
+        history.save(&file).unwrap();
+
+        let mut loaded = CommandHistory::load(&file);
+        assert_eq!(loaded.commands.len(), MAX_HISTORY);
+        assert_eq!(loaded.commands, history.commands);
+        assert_eq!(loaded.previous(""), Some(format!("/exportmap {}", MAX_HISTORY + 4).as_str()));
+
+        fs::remove_file(&file).unwrap();
+        assert!(CommandHistory::load(&file).commands.is_empty());
+    }
+
+    #[test]
+    fn tab_completes_the_word_at_the_cursor() {
+        assert_eq!(tab_complete("/wea", 4), (String::from("/weather "), 9, Vec::new()));
+        // only the common part of ambiguous completions is filled in, and the choices are returned
+        let (input, cursor, completions) = tab_complete("/setblock ~ ~ ~ sto", 19);
+        assert_eq!((input.as_str(), cursor), ("/setblock ~ ~ ~ Stone", 21));
+        assert_eq!(completions, vec!["Stone", "StoneSlab"]);
+        // text after the cursor is kept
+        assert_eq!(tab_complete("/gamemode c 5", 11), (String::from("/gamemode creative 5"), 18, Vec::new()));
+        // nothing to complete
+        assert_eq!(tab_complete("/exportmap 1", 12), (String::from("/exportmap 1"), 12, Vec::new()));
+    }
+}
@@ -0,0 +1,80 @@
+use egui::{Align2, Context, Grid, ProgressBar, Ui, Window};
+
+use crate::game::block::BlockType;
+use super::slot::slot_widget;
+
+// container slots are shown in rows this long, the same as the hotbar
+const SLOTS_PER_ROW: usize = 9;
+
+// snapshot of an open container and the player's hotbar, filled in by the client every physics update
+pub struct ContainerData {
+    pub title: String,
+    pub slots: Vec<Option<(BlockType, u32)>>,
+    // the block type and count in each hotbar slot, slots without any blocks are empty
+    pub hotbar: Vec<Option<(BlockType, u32)>>,
+    // set when the container is a furnace
+    pub furnace: Option<FurnaceProgress>,
+}
+
+// fractions from 0 to 1, shown as bars under the furnace's slots
+#[derive(Debug, Clone, Copy)]
+pub struct FurnaceProgress {
+    // how much of the fuel being burned is left
+    pub burn: f32,
+    // how far the block being smelted is
+    pub smelt: f32,
+}
+
+// something done in the container window, which the client applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerAction {
+    // moves everything in the container slot to the hotbar
+    TakeSlot(usize),
+    // moves everything in the hotbar slot to the container
+    StoreSlot(usize),
+}
+
+// returns true if the slot was clicked
+fn container_slot(ui: &mut Ui, contents: Option<(BlockType, u32)>) -> bool {
+    slot_widget(ui, contents.map(|(block_type, count)| (block_type, Some(count))), false).clicked()
+}
+
+// open is set to false when the window is closed
+pub fn container_window(context: &Context, data: &ContainerData, open: &mut bool) -> Option<ContainerAction> {
+    let mut action = None;
+
+    Window::new(&data.title)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .open(open)
+        .show(context, |ui| {
+            Grid::new("container slots").show(ui, |ui| {
+                for (slot, contents) in data.slots.iter().enumerate() {
+                    if container_slot(ui, *contents) {
+                        action = Some(ContainerAction::TakeSlot(slot));
+                    }
+                    if slot % SLOTS_PER_ROW == SLOTS_PER_ROW - 1 {
+                        ui.end_row();
+                    }
+                }
+            });
+
+            if let Some(furnace) = data.furnace {
+                ui.add(ProgressBar::new(furnace.smelt).text("Smelting"));
+                ui.add(ProgressBar::new(furnace.burn).text("Fuel"));
+            }
+
+            ui.separator();
+            ui.label("Hotbar");
+            ui.horizontal(|ui| {
+                for (slot, contents) in data.hotbar.iter().enumerate() {
+                    if container_slot(ui, *contents) {
+                        action = Some(ContainerAction::StoreSlot(slot));
+                    }
+                }
+            });
+        });
+
+    action
+}
@@ -1,12 +1,17 @@
-use std::{lazy::SyncLazy, collections::BTreeMap};
+use std::collections::BTreeMap;
 
-use egui::{Window, Context};
+use egui::{Window, Context, CollapsingHeader, Slider};
+use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 
-static debug_info: SyncLazy<Mutex<BTreeMap<String, String>>> = SyncLazy::new(|| Mutex::new(BTreeMap::new()));
+use crate::render::shadow_map::ShadowBias;
+
+static DEBUG_INFO: Lazy<Mutex<BTreeMap<String, String>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+// values grouped under a collapsible header for each section, shown after the other values
+static DEBUG_SECTIONS: Lazy<Mutex<BTreeMap<String, BTreeMap<String, String>>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
 
 pub fn debug_string(label: &str, data: String) {
-    let mut map = debug_info.lock();
+    let mut map = DEBUG_INFO.lock();
 
     map.insert(String::from(label), data);
 }
@@ -15,9 +20,16 @@ pub fn debug_display<T: ToString>(label: &str, data: &T) {
     debug_string(label, data.to_string());
 }
 
-pub fn debug_window(context: &Context) {
+pub fn debug_section_string(section: &str, label: &str, data: String) {
+    let mut sections = DEBUG_SECTIONS.lock();
+
+    sections.entry(String::from(section)).or_default().insert(String::from(label), data);
+}
+
+// the shadow bias can be tuned here, since the right values depend on the scene
+pub fn debug_window(context: &Context, shadow_bias: &mut ShadowBias, target_label: &mut bool) {
     Window::new("Debug Window").show(context, |ui| {
-        let map = debug_info.lock();
+        let map = DEBUG_INFO.lock();
 
         for (label, data) in map.iter() {
             ui.horizontal(|ui| {
@@ -25,5 +37,23 @@ pub fn debug_window(context: &Context) {
                 ui.label(data);
             });
         }
+
+        for (section, section_map) in DEBUG_SECTIONS.lock().iter() {
+            CollapsingHeader::new(section).default_open(true).show(ui, |ui| {
+                for (label, data) in section_map.iter() {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        ui.label(data);
+                    });
+                }
+            });
+        }
+
+        ui.checkbox(target_label, "Label targeted block");
+
+        CollapsingHeader::new("Shadow Bias").show(ui, |ui| {
+            ui.add(Slider::new(&mut shadow_bias.depth_bias, 0.0..=0.01).logarithmic(true).text("Depth bias"));
+            ui.add(Slider::new(&mut shadow_bias.normal_offset, 0.0..=0.5).text("Normal offset"));
+        });
     });
 }
\ No newline at end of file
@@ -0,0 +1,141 @@
+use egui::{Area, Align2, Color32, Context, FontId, Frame, LayerId, RichText, Rect, Stroke, Vec2};
+use glam::Vec3;
+
+use crate::prelude::*;
+
+// what the hud shows, filled in by the client every physics update
+pub struct HudData {
+    pub block: BlockPos,
+    // direction the camera is facing
+    pub facing: Vec3,
+    pub biome: String,
+}
+
+// north is -z and east is +x, the same as up and right on the minimap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardinalDirection {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl CardinalDirection {
+    // clockwise from north
+    const ALL: [CardinalDirection; 8] = [
+        CardinalDirection::North,
+        CardinalDirection::NorthEast,
+        CardinalDirection::East,
+        CardinalDirection::SouthEast,
+        CardinalDirection::South,
+        CardinalDirection::SouthWest,
+        CardinalDirection::West,
+        CardinalDirection::NorthWest,
+    ];
+
+    // the closest direction to the horizontal part of facing, none if facing is straight up or down
+    pub fn from_facing(facing: Vec3) -> Option<Self> {
+        if facing.x == 0.0 && facing.z == 0.0 {
+            return None;
+        }
+
+        // yaw is measured clockwise from north
+        let yaw = facing.x.atan2(-facing.z).to_degrees().rem_euclid(360.0);
+        let index = (yaw / 45.0).round() as usize % 8;
+        Some(Self::ALL[index])
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CardinalDirection::North => "N",
+            CardinalDirection::NorthEast => "NE",
+            CardinalDirection::East => "E",
+            CardinalDirection::SouthEast => "SE",
+            CardinalDirection::South => "S",
+            CardinalDirection::SouthWest => "SW",
+            CardinalDirection::West => "W",
+            CardinalDirection::NorthWest => "NW",
+        }
+    }
+}
+
+// small overlay in the top right corner, in a monospace font so the numbers don't move around as they change
+pub fn hud(context: &Context, data: &HudData) {
+    let direction = CardinalDirection::from_facing(data.facing).map_or("-", |direction| direction.name());
+    let text = format!(
+        "X {:>7}\nY {:>7}\nZ {:>7}\nFacing {:>2}\n{}",
+        data.block.x,
+        data.block.y,
+        data.block.z,
+        direction,
+        data.biome,
+    );
+
+    Area::new("hud")
+        .anchor(Align2::RIGHT_TOP, [-8.0, 8.0])
+        .interactable(false)
+        .show(context, |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(RichText::new(text).monospace());
+            });
+        });
+}
+
+const HEALTH_BAR_SIZE: Vec2 = Vec2::new(200.0, 12.0);
+// distance from the bottom of the screen
+const HEALTH_BAR_MARGIN: f32 = 64.0;
+
+// bar along the bottom of the screen, above where the hotbar would be
+pub fn health_bar(context: &Context, health: f32, max_health: f32) {
+    let fraction = (health / max_health).clamp(0.0, 1.0);
+    let screen = context.input().screen_rect();
+    let bar = Rect::from_center_size(
+        [screen.center().x, screen.bottom() - HEALTH_BAR_MARGIN].into(),
+        HEALTH_BAR_SIZE,
+    );
+    let filled = Rect::from_min_size(bar.min, Vec2::new(bar.width() * fraction, bar.height()));
+
+    let painter = context.layer_painter(LayerId::background());
+    painter.rect_filled(bar, 2.0, Color32::from_black_alpha(128));
+    painter.rect_filled(filled, 2.0, Color32::from_rgb(200, 30, 30));
+    painter.rect_stroke(bar, 2.0, Stroke::new(1.0_f32, Color32::from_white_alpha(160)));
+    painter.text(
+        bar.center(),
+        Align2::CENTER_CENTER,
+        format!("{:.0} / {:.0}", health.max(0.0).ceil(), max_health),
+        FontId::proportional(10.0),
+        Color32::WHITE,
+    );
+}
+
+// shown in the middle of the screen while the chunks around where the player is teleporting to are generated
+pub fn teleport_overlay(context: &Context, done: usize, total: usize) {
+    Area::new("teleport_overlay")
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .interactable(false)
+        .show(context, |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(RichText::new(format!("Teleporting... {} / {} chunks", done, total)).heading());
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cardinal_direction_from_facing() {
+        assert_eq!(CardinalDirection::from_facing(Vec3::new(0.0, 0.0, -1.0)), Some(CardinalDirection::North));
+        assert_eq!(CardinalDirection::from_facing(Vec3::new(1.0, 0.5, 0.0)), Some(CardinalDirection::East));
+        assert_eq!(CardinalDirection::from_facing(Vec3::new(0.1, -0.3, 1.0)), Some(CardinalDirection::South));
+        assert_eq!(CardinalDirection::from_facing(Vec3::new(-1.0, 0.0, -1.0)), Some(CardinalDirection::NorthWest));
+        // just short of halfway between north and north west
+        assert_eq!(CardinalDirection::from_facing(Vec3::new(-0.41, 0.0, -1.0)), Some(CardinalDirection::North));
+        assert_eq!(CardinalDirection::from_facing(Vec3::Y), None);
+    }
+}
@@ -0,0 +1,80 @@
+use egui::{Align2, Context, Grid, Window};
+
+use crate::game::block::BlockType;
+use crate::game::crafting::CRAFTING_GRID_SIZE;
+use super::slot::slot_widget;
+
+// snapshot of the hotbar and crafting grid, filled in by the client every physics update
+pub struct InventoryData {
+    // the block type and count in each hotbar slot, counts are none when blocks are infinite
+    pub slots: Vec<(Option<BlockType>, Option<u32>)>,
+    pub selected_slot: usize,
+    // indexed by row, then column
+    pub crafting_grid: [[Option<(BlockType, u32)>; CRAFTING_GRID_SIZE]; CRAFTING_GRID_SIZE],
+    // what the crafting grid would make
+    pub output: Option<(BlockType, u32)>,
+}
+
+// something done in the inventory window, which the client applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryAction {
+    SelectSlot(usize),
+    // puts 1 block from the selected slot in the crafting grid cell
+    AddToCell {
+        row: usize,
+        column: usize,
+    },
+    // moves everything in the cell back to the hotbar
+    EmptyCell {
+        row: usize,
+        column: usize,
+    },
+    Craft,
+}
+
+// left clicking a crafting cell adds the selected block to it, and right clicking empties it
+pub fn inventory_window(context: &Context, data: &InventoryData) -> Option<InventoryAction> {
+    let mut action = None;
+
+    Window::new("Inventory")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(context, |ui| {
+            ui.label("Crafting");
+            ui.horizontal(|ui| {
+                Grid::new("crafting grid").show(ui, |ui| {
+                    for (row, cells) in data.crafting_grid.iter().enumerate() {
+                        for (column, cell) in cells.iter().enumerate() {
+                            let response = slot_widget(ui, cell.map(|(block_type, count)| (block_type, Some(count))), false);
+                            if response.clicked() {
+                                action = Some(InventoryAction::AddToCell { row, column });
+                            } else if response.secondary_clicked() {
+                                action = Some(InventoryAction::EmptyCell { row, column });
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+
+                ui.label("=>");
+                let output = data.output.map(|(block_type, count)| (block_type, Some(count)));
+                if ui.add_enabled_ui(output.is_some(), |ui| slot_widget(ui, output, false)).inner.clicked() {
+                    action = Some(InventoryAction::Craft);
+                }
+            });
+
+            ui.separator();
+            ui.label("Hotbar");
+            ui.horizontal(|ui| {
+                for (slot, (block_type, count)) in data.slots.iter().enumerate() {
+                    let contents = block_type.map(|block_type| (block_type, *count));
+                    if slot_widget(ui, contents, slot == data.selected_slot).clicked() {
+                        action = Some(InventoryAction::SelectSlot(slot));
+                    }
+                }
+            });
+        });
+
+    action
+}
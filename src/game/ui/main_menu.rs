@@ -0,0 +1,327 @@
+use std::ops::RangeInclusive;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use egui::{Align2, Button, CentralPanel, CollapsingHeader, Color32, ColorImage, ComboBox, Context, DragValue, Grid, Image, ProgressBar, RichText, ScrollArea, Stroke, TextEdit, TextureHandle, Ui, Vec2, Window};
+use image::RgbImage;
+
+use crate::game::world_list::{WorldEntry, seed_from_text};
+use crate::game::world_preview::WorldPreview;
+use crate::game::world::{DEFAULT_PREGEN_RADIUS, MAX_PREGEN_RADIUS};
+use crate::game::worldgen::{GeneratorType, GeneratorOptions};
+
+// size of the world preview in points
+const PREVIEW_SIZE: f32 = 256.0;
+
+// what the player chose in the main menu, the game does it and updates the menu
+#[derive(Debug, Clone)]
+pub enum MenuAction {
+    Play(WorldEntry),
+    Create {
+        name: String,
+        seed: u32,
+        generator: GeneratorType,
+        options: GeneratorOptions,
+        // chunks within this many chunks of spawn are generated in the background once the world is opened
+        pregen_radius: i32,
+    },
+    Delete(WorldEntry),
+}
+
+// how far along loading the chunks around the player is, shown instead of the game until it is done
+#[derive(Debug, Clone)]
+pub struct LoadingProgress {
+    pub world_name: String,
+    pub loaded_chunks: usize,
+    pub total_chunks: usize,
+}
+
+struct CreateWorldDialog {
+    name: String,
+    // parsed with seed_from_text when the world is created
+    seed: String,
+    generator: GeneratorType,
+    options: GeneratorOptions,
+    // 0 doesn't pregenerate anything
+    pregen_radius: i32,
+    preview: WorldPreview,
+    // the last finished preview, it is shown faded while the next one is made
+    preview_texture: Option<TextureHandle>,
+}
+
+impl Default for CreateWorldDialog {
+    fn default() -> Self {
+        CreateWorldDialog {
+            name: String::from("New World"),
+            seed: String::new(),
+            generator: GeneratorType::Normal,
+            options: GeneratorOptions::default(),
+            pregen_radius: DEFAULT_PREGEN_RADIUS,
+            preview: WorldPreview::new(),
+            preview_texture: None,
+        }
+    }
+}
+
+// the generator options which can be changed, with the range each can be set to
+fn show_generator_options(ui: &mut Ui, options: &mut GeneratorOptions) {
+    Grid::new("generator options").num_columns(2).show(ui, |ui| {
+        let option = |ui: &mut Ui, label: &str, value: &mut f64, range: RangeInclusive<f64>| {
+            ui.label(label);
+            ui.add(DragValue::new(value).speed(0.0001).clamp_range(range).max_decimals(4));
+            ui.end_row();
+        };
+        option(ui, "Terrain scale", &mut options.terrain_scale, 0.005..=0.5);
+        option(ui, "Biome scale", &mut options.biome_scale, 0.0002..=0.02);
+        option(ui, "Cave scale", &mut options.cave_scale, 0.004..=0.4);
+        option(ui, "Cave threshold", &mut options.cave_threshold, 0.0..=1.0);
+        option(ui, "Underground biome scale", &mut options.underground_biome_scale, 0.0004..=0.04);
+    });
+    if ui.button("Reset").clicked() {
+        *options = GeneratorOptions::default();
+    }
+}
+
+fn preview_image(image: &RgbImage) -> ColorImage {
+    ColorImage {
+        size: [image.width() as usize, image.height() as usize],
+        pixels: image.pixels().map(|pixel| Color32::from_rgb(pixel[0], pixel[1], pixel[2])).collect(),
+    }
+}
+
+// the screen shown before a world is loaded
+pub struct MainMenu {
+    worlds: Vec<WorldEntry>,
+    selected: Option<usize>,
+    create_dialog: Option<CreateWorldDialog>,
+    // index of the world the player is being asked to confirm deleting
+    confirm_delete: Option<usize>,
+    // the last action that failed
+    error: Option<String>,
+}
+
+impl MainMenu {
+    pub fn new(worlds: Vec<WorldEntry>) -> Self {
+        MainMenu {
+            worlds,
+            selected: None,
+            create_dialog: None,
+            confirm_delete: None,
+            error: None,
+        }
+    }
+
+    // the selection is cleared, since the list may have been reordered
+    pub fn set_worlds(&mut self, worlds: Vec<WorldEntry>) {
+        self.worlds = worlds;
+        self.selected = None;
+        self.confirm_delete = None;
+    }
+
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn show(&mut self, context: &Context) -> Option<MenuAction> {
+        let mut action = None;
+        let dialog_open = self.create_dialog.is_some() || self.confirm_delete.is_some();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+
+        CentralPanel::default().show(context, |ui| {
+            ui.set_enabled(!dialog_open);
+            ui.vertical_centered(|ui| {
+                ui.heading(RichText::new("Minecone").size(40.0));
+                ui.add_space(16.0);
+            });
+
+            ScrollArea::vertical().max_height(ui.available_height() - 60.0).show(ui, |ui| {
+                if self.worlds.is_empty() {
+                    ui.label("No worlds yet");
+                }
+
+                for (i, world) in self.worlds.iter().enumerate() {
+                    let text = format!(
+                        "{}\nseed {}, {} terrain, {}",
+                        world.meta.name,
+                        world.meta.seed,
+                        world.meta.generator.name(),
+                        format_last_played(world.meta.last_played, now),
+                    );
+                    let response = ui.selectable_label(self.selected == Some(i), text);
+                    if response.clicked() {
+                        self.selected = Some(i);
+                    }
+                    if response.double_clicked() {
+                        action = Some(MenuAction::Play(world.clone()));
+                    }
+                }
+            });
+
+            if let Some(error) = &self.error {
+                ui.colored_label(Color32::RED, error);
+            }
+
+            ui.horizontal(|ui| {
+                let selected = self.selected.and_then(|i| self.worlds.get(i));
+                if ui.add_enabled(selected.is_some(), Button::new("Play")).clicked() {
+                    action = selected.cloned().map(MenuAction::Play);
+                }
+                if ui.button("Create World").clicked() {
+                    self.create_dialog = Some(CreateWorldDialog::default());
+                }
+                if ui.add_enabled(selected.is_some(), Button::new("Delete")).clicked() {
+                    self.confirm_delete = self.selected;
+                }
+            });
+        });
+
+        if let Some(dialog) = &mut self.create_dialog {
+            // a random seed is only chosen when the world is created, so there is nothing to preview
+            let seed = (!dialog.seed.trim().is_empty()).then(|| seed_from_text(&dialog.seed));
+            if let Some(image) = dialog.preview.update(seed, dialog.generator, dialog.options, Instant::now()) {
+                let image = preview_image(&image);
+                match &mut dialog.preview_texture {
+                    Some(texture) => texture.set(image),
+                    None => dialog.preview_texture = Some(context.load_texture("world preview", image)),
+                }
+            }
+
+            let mut close = false;
+            Window::new("Create World")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(context, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut dialog.name);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Seed");
+                        ui.add(TextEdit::singleline(&mut dialog.seed).hint_text("random"));
+                    });
+                    ComboBox::from_label("Terrain")
+                        .selected_text(dialog.generator.name())
+                        .show_ui(ui, |ui| {
+                            for generator in GeneratorType::ALL {
+                                ui.selectable_value(&mut dialog.generator, generator, generator.name());
+                            }
+                        });
+                    CollapsingHeader::new("Advanced").show(ui, |ui| {
+                        show_generator_options(ui, &mut dialog.options);
+                        ui.horizontal(|ui| {
+                            ui.label("Pregenerate radius");
+                            ui.add(DragValue::new(&mut dialog.pregen_radius).clamp_range(0..=MAX_PREGEN_RADIUS).suffix(" chunks"));
+                        });
+                    });
+
+                    match (&dialog.preview_texture, seed) {
+                        (Some(texture), Some(_)) => {
+                            let tint = if dialog.preview.is_current() { Color32::WHITE } else { Color32::GRAY };
+                            let rect = ui.add(Image::new(texture, Vec2::splat(PREVIEW_SIZE)).tint(tint)).rect;
+                            // the spawn point is in the center
+                            ui.painter().circle_stroke(rect.center(), 4.0, Stroke::new(2.0_f32, Color32::RED));
+                        },
+                        (None, Some(_)) => {
+                            ui.label("Making preview...");
+                        },
+                        (_, None) => {
+                            ui.label("Enter a seed to preview the world");
+                        },
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(!dialog.name.trim().is_empty(), Button::new("Create")).clicked() {
+                            action = Some(MenuAction::Create {
+                                name: dialog.name.trim().to_owned(),
+                                seed: seed_from_text(&dialog.seed),
+                                generator: dialog.generator,
+                                options: dialog.options,
+                                pregen_radius: dialog.pregen_radius,
+                            });
+                            close = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+
+            if close {
+                self.create_dialog = None;
+            }
+        }
+
+        if let Some(world) = self.confirm_delete.and_then(|i| self.worlds.get(i)) {
+            let mut close = false;
+            Window::new("Delete World")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(context, |ui| {
+                    ui.label(format!("Delete {}? This can't be undone.", world.meta.name));
+                    ui.horizontal(|ui| {
+                        if ui.button("Delete").clicked() {
+                            action = Some(MenuAction::Delete(world.clone()));
+                            close = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+
+            if close {
+                self.confirm_delete = None;
+            }
+        }
+
+        if action.is_some() {
+            self.error = None;
+        }
+        action
+    }
+}
+
+pub fn loading_screen(context: &Context, progress: &LoadingProgress) {
+    CentralPanel::default().show(context, |ui| {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 2.0 - 40.0);
+            ui.heading(format!("Loading {}", progress.world_name));
+            ui.add(ProgressBar::new(progress.loaded_chunks as f32 / progress.total_chunks.max(1) as f32)
+                .text(format!("{} / {} chunks", progress.loaded_chunks, progress.total_chunks)));
+        });
+    });
+}
+
+// last_played and now are seconds since the unix epoch
+fn format_last_played(last_played: u64, now: u64) -> String {
+    if last_played == 0 {
+        return String::from("never played");
+    }
+
+    let (amount, unit) = match now.saturating_sub(last_played) {
+        seconds if seconds < 60 => return String::from("played just now"),
+        seconds if seconds < 60 * 60 => (seconds / 60, "minute"),
+        seconds if seconds < 24 * 60 * 60 => (seconds / (60 * 60), "hour"),
+        seconds => (seconds / (24 * 60 * 60), "day"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    format!("played {} {}{} ago", amount, unit, plural)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_played_text() {
+        let now = 1_700_000_000;
+        assert_eq!(format_last_played(0, now), "never played");
+        assert_eq!(format_last_played(now - 30, now), "played just now");
+        assert_eq!(format_last_played(now - 60, now), "played 1 minute ago");
+        assert_eq!(format_last_played(now - 3 * 60 * 60 - 5, now), "played 3 hours ago");
+        assert_eq!(format_last_played(now - 2 * 24 * 60 * 60, now), "played 2 days ago");
+        // clocks can go backwards
+        assert_eq!(format_last_played(now + 100, now), "played just now");
+    }
+}
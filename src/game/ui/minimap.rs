@@ -0,0 +1,124 @@
+use egui::{Window, Context, Color32, Stroke, Sense, Rect, Vec2};
+use glam::{IVec2, Vec3};
+
+use crate::prelude::*;
+use crate::game::world::ChunkState;
+use crate::game::parallel::{ChunkActivity, COMPLETED_ACTIVITY_TIME};
+use crate::game::render_zone::RENDER_ZONE_SIZE;
+
+// size of each chunk cell in the minimap in points
+const CELL_SIZE: f32 = 8.0;
+
+// snapshot of the world around the player which the minimap draws
+// filled in by the client, so no world locks are held while egui is rendering
+pub struct MinimapData {
+    pub center: ChunkPos,
+    pub radius: i32,
+    // direction the player is facing
+    pub facing: Vec3,
+    pub chunks: Vec<(IVec2, ChunkState)>,
+    // task activity of chunks in any chunk of each column, empty if the task activity view is off
+    pub activity: Vec<(IVec2, ChunkActivity)>,
+}
+
+fn state_color(state: ChunkState) -> Color32 {
+    match state {
+        ChunkState::Loaded => Color32::from_rgb(60, 170, 60),
+        ChunkState::Queued => Color32::from_rgb(210, 190, 40),
+        ChunkState::Cached => Color32::from_rgb(60, 110, 200),
+        ChunkState::Unloaded => Color32::from_rgb(40, 40, 40),
+    }
+}
+
+// completed activity fades out, None once it has fully faded
+fn activity_color(activity: ChunkActivity) -> Option<Color32> {
+    match activity {
+        ChunkActivity::QueuedGeneration => Some(Color32::YELLOW),
+        ChunkActivity::Generating => Some(Color32::from_rgb(255, 140, 0)),
+        ChunkActivity::Meshing => Some(Color32::from_rgb(40, 120, 255)),
+        ChunkActivity::Completed(time) => {
+            let fade = 1.0 - time.elapsed().as_secs_f32() / COMPLETED_ACTIVITY_TIME.as_secs_f32();
+            (fade > 0.0).then(|| Color32::from_white_alpha((255.0 * fade) as u8))
+        },
+    }
+}
+
+// activity drawn later is drawn on top, so in progress work is not hidden by other chunks in the same column
+fn activity_draw_order(activity: ChunkActivity) -> u8 {
+    match activity {
+        ChunkActivity::Completed(_) => 0,
+        ChunkActivity::QueuedGeneration => 1,
+        ChunkActivity::Meshing => 2,
+        ChunkActivity::Generating => 3,
+    }
+}
+
+pub fn minimap_window(context: &Context, data: &MinimapData, show_activity: &mut bool) {
+    Window::new("Minimap").resizable(false).show(context, |ui| {
+        let cells = (2 * data.radius + 1) as f32;
+        let (response, painter) = ui.allocate_painter(Vec2::splat(cells * CELL_SIZE), Sense::hover());
+        let origin = response.rect.min;
+
+        // top left corner of the given chunk column
+        let cell_pos = |column: IVec2| {
+            let offset = column - IVec2::new(data.center.x - data.radius, data.center.z - data.radius);
+            origin + Vec2::new(offset.x as f32, offset.y as f32) * CELL_SIZE
+        };
+
+        for (column, state) in data.chunks.iter() {
+            let rect = Rect::from_min_size(cell_pos(*column), Vec2::splat(CELL_SIZE));
+            painter.rect_filled(rect.shrink(0.5), 0.0, state_color(*state));
+        }
+
+        let mut activity = data.activity.iter().collect::<Vec<_>>();
+        activity.sort_by_key(|(_, activity)| activity_draw_order(*activity));
+        for (column, activity) in activity {
+            if let Some(color) = activity_color(*activity) {
+                let rect = Rect::from_min_size(cell_pos(*column), Vec2::splat(CELL_SIZE));
+                painter.rect_stroke(rect.shrink(1.0), 0.0, Stroke::new(1.5_f32, color));
+            }
+        }
+
+        // render zone boundaries
+        let zone_stroke = Stroke::new(1.0_f32, Color32::from_gray(200));
+        for i in -data.radius..=data.radius + 1 {
+            let x = data.center.x + i;
+            if x.rem_euclid(RENDER_ZONE_SIZE) == 0 {
+                let top = cell_pos(IVec2::new(x, data.center.z - data.radius));
+                painter.line_segment([top, top + Vec2::new(0.0, cells * CELL_SIZE)], zone_stroke);
+            }
+
+            let z = data.center.z + i;
+            if z.rem_euclid(RENDER_ZONE_SIZE) == 0 {
+                let left = cell_pos(IVec2::new(data.center.x - data.radius, z));
+                painter.line_segment([left, left + Vec2::new(cells * CELL_SIZE, 0.0)], zone_stroke);
+            }
+        }
+
+        // player position and facing
+        let player_pos = cell_pos(IVec2::new(data.center.x, data.center.z)) + Vec2::splat(CELL_SIZE / 2.0);
+        let facing = Vec2::new(data.facing.x, data.facing.z);
+        if facing.length() > 0.0 {
+            painter.arrow(player_pos, facing.normalized() * 2.0 * CELL_SIZE, Stroke::new(2.0_f32, Color32::WHITE));
+        }
+        painter.circle_filled(player_pos, CELL_SIZE / 3.0, Color32::RED);
+
+        ui.horizontal(|ui| {
+            for state in [ChunkState::Loaded, ChunkState::Queued, ChunkState::Cached, ChunkState::Unloaded] {
+                ui.colored_label(state_color(state), format!("{:?}", state));
+            }
+        });
+        ui.label(format!("chunk y: {}", data.center.y));
+
+        ui.checkbox(show_activity, "Task activity");
+        if *show_activity {
+            ui.horizontal(|ui| {
+                ui.colored_label(activity_color(ChunkActivity::QueuedGeneration).unwrap(), "Queued");
+                ui.colored_label(activity_color(ChunkActivity::Generating).unwrap(), "Generating");
+                ui.colored_label(activity_color(ChunkActivity::Meshing).unwrap(), "Meshing");
+                ui.colored_label(Color32::WHITE, "Completed");
+            });
+        }
+    });
+}
+
@@ -1,14 +1,51 @@
-use std::time::Instant;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-use egui::FontDefinitions;
+use egui::{FontDefinitions, LayerId, Color32};
 use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
 use egui_winit_platform::{Platform, PlatformDescriptor};
-use winit::{window::Window, event::*};
+use winit::{window::{Window, WindowId}, event::*, dpi::PhysicalSize};
+use glam::Vec4;
 
+use crate::prelude::*;
 use crate::render::Renderer;
+use crate::render::camera::Camera;
+use crate::render::shadow_map::ShadowBias;
+use crate::assets::loader;
+use super::settings::{Settings, MIN_UI_SCALE, MAX_UI_SCALE};
+use super::config::{Config, CONFIG_FILE};
+use super::player::MAX_HEALTH;
 
 mod debug_window;
-pub use debug_window::{debug_string, debug_display};
+pub use debug_window::{debug_string, debug_display, debug_section_string};
+mod minimap;
+pub use minimap::MinimapData;
+mod biome_overlay;
+pub use biome_overlay::BiomeOverlayData;
+use biome_overlay::BiomeOverlay;
+mod hud;
+pub use hud::HudData;
+mod settings_window;
+use settings_window::TexturePackChoice;
+mod main_menu;
+pub use main_menu::{MainMenu, MenuAction, LoadingProgress};
+mod console;
+use console::{Console, HISTORY_FILE};
+mod slot;
+mod inventory;
+pub use inventory::{InventoryData, InventoryAction};
+mod container;
+pub use container::{ContainerData, ContainerAction, FurnaceProgress};
+mod world_labels;
+pub use world_labels::MAX_LABEL_DISTANCE;
+use world_labels::ScreenLabel;
+
+// config key of the ui scale, which is multiplied by the os scale factor
+pub const UI_SCALE_KEY: &str = "ui_scale";
+// the screen flashes red for this long after the player is damaged
+const DAMAGE_FLASH_TIME: Duration = Duration::from_millis(300);
+// opacity of the flash when it starts, it fades out from there
+const DAMAGE_FLASH_ALPHA: f32 = 0.4;
 
 pub struct MineConeUi {
     start_time: Instant,
@@ -16,58 +53,465 @@ pub struct MineConeUi {
     render_pass: RenderPass,
 
     debug_panel_open: bool,
+    hud_open: bool,
+    hud_data: Option<HudData>,
+    minimap_open: bool,
+    minimap_data: Option<MinimapData>,
+    task_activity_open: bool,
+    biome_overlay_open: bool,
+    biome_overlay: BiomeOverlay,
+    settings_open: bool,
+    settings: Settings,
+    // the ui scale egui is using, the setting is applied to it before each frame
+    ui_scale: f32,
+    // set when the ui scale setting is applied, until the client takes it
+    ui_scale_changed: bool,
+    shadow_bias: ShadowBias,
+    texture_packs: TexturePackChoice,
+    // set when a different texture pack is selected, until the client takes it
+    texture_pack_changed: bool,
+    // rgba color drawn over the world, behind all windows
+    screen_tint: Vec4,
+    // shown instead of everything else until a world is chosen
+    main_menu: Option<MainMenu>,
+    // set when something is chosen in the main menu, until the game takes it
+    menu_action: Option<MenuAction>,
+    // shown instead of the game's windows while the world is loading
+    loading_progress: Option<LoadingProgress>,
+    // chunks around the teleport destination which are done and the total, while the player is teleporting
+    teleport_progress: Option<(usize, usize)>,
+    console_open: bool,
+    console: Console,
+    // set when a command is entered in the console, until the client takes it
+    console_command: Option<String>,
+    inventory_open: bool,
+    inventory_data: Option<InventoryData>,
+    // set when something is done in the inventory, until the client takes it
+    inventory_action: Option<InventoryAction>,
+    // set by the client when the player opens a container, and cleared when its window is closed
+    container_open: bool,
+    container_data: Option<ContainerData>,
+    // set when something is done in the container window, until the client takes it
+    container_action: Option<ContainerAction>,
+    // labels over things in the world, projected each physics update
+    world_labels: Vec<ScreenLabel>,
+    // the name of the targeted block is shown over it, set in the debug window
+    target_label: bool,
+    // none hides the health bar, for game modes without health
+    health: Option<f32>,
+    // time the player was last damaged, for the red flash
+    damage_time: Option<Instant>,
 }
 
 impl MineConeUi {
     pub fn new(window: &Window, renderer: &Renderer) -> Self {
         let size = window.inner_size();
+        // the ui is created before any world is loaded, so it reads its scale itself
+        let ui_scale = Config::load(PathBuf::from(CONFIG_FILE)).get(UI_SCALE_KEY)
+            .and_then(|ui_scale| ui_scale.parse::<f32>().ok())
+            .map_or(1.0, |ui_scale| ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE));
+        let mut settings = Settings::default();
+        settings.interface.ui_scale = ui_scale;
 
         MineConeUi {
             start_time: Instant::now(),
             platform: Platform::new(PlatformDescriptor {
                 physical_width: size.width,
                 physical_height: size.height,
-                scale_factor: window.scale_factor(),
+                scale_factor: window.scale_factor() * ui_scale as f64,
                 font_definitions: FontDefinitions::default(),
                 style: Default::default(),
             }),
             render_pass: RenderPass::new(renderer.device(), renderer.surface_format(), 1),
             debug_panel_open: false,
+            hud_open: true,
+            hud_data: None,
+            minimap_open: false,
+            minimap_data: None,
+            task_activity_open: false,
+            biome_overlay_open: false,
+            biome_overlay: BiomeOverlay::new(),
+            settings_open: false,
+            settings,
+            ui_scale,
+            ui_scale_changed: false,
+            shadow_bias: ShadowBias::default(),
+            texture_packs: TexturePackChoice::default(),
+            texture_pack_changed: false,
+            screen_tint: Vec4::ZERO,
+            main_menu: None,
+            menu_action: None,
+            loading_progress: None,
+            teleport_progress: None,
+            console_open: false,
+            console: Console::with_history_file(PathBuf::from(HISTORY_FILE)),
+            console_command: None,
+            inventory_open: false,
+            inventory_data: None,
+            inventory_action: None,
+            container_open: false,
+            container_data: None,
+            container_action: None,
+            world_labels: Vec::new(),
+            target_label: false,
+            health: None,
+            damage_time: None,
         }
     }
 
-    fn windows(&self) {
+    fn windows(&mut self) {
+        if let Some(main_menu) = &mut self.main_menu {
+            if let Some(action) = main_menu.show(&self.platform.context()) {
+                self.menu_action = Some(action);
+            }
+            return;
+        }
+
+        if let Some(loading_progress) = &self.loading_progress {
+            main_menu::loading_screen(&self.platform.context(), loading_progress);
+            return;
+        }
+
+        if self.screen_tint.w > 0.0 {
+            let context = self.platform.context();
+            let color = (self.screen_tint * 255.0).to_array().map(|component| component as u8);
+            context.layer_painter(LayerId::background()).rect_filled(
+                context.input().screen_rect(),
+                0.0,
+                Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]),
+            );
+        }
+
+        if let Some(damage_time) = self.damage_time {
+            let elapsed = damage_time.elapsed();
+            if elapsed < DAMAGE_FLASH_TIME {
+                let alpha = DAMAGE_FLASH_ALPHA * (1.0 - elapsed.as_secs_f32() / DAMAGE_FLASH_TIME.as_secs_f32());
+                let context = self.platform.context();
+                context.layer_painter(LayerId::background()).rect_filled(
+                    context.input().screen_rect(),
+                    0.0,
+                    Color32::from_rgba_unmultiplied(255, 0, 0, (alpha * 255.0) as u8),
+                );
+            } else {
+                self.damage_time = None;
+            }
+        }
+
+        if let Some((done, total)) = self.teleport_progress {
+            hud::teleport_overlay(&self.platform.context(), done, total);
+        }
+
+        // the hud is hidden while menus are open
+        if self.hud_open && !self.menu_open() {
+            world_labels::world_labels(&self.platform.context(), &self.world_labels);
+
+            if let Some(health) = self.health {
+                hud::health_bar(&self.platform.context(), health, MAX_HEALTH);
+            }
+
+            if let Some(hud_data) = &self.hud_data {
+                hud::hud(&self.platform.context(), hud_data);
+            }
+        }
+
         if self.debug_panel_open {
-            debug_window::debug_window(&self.platform.context());
+            debug_window::debug_window(&self.platform.context(), &mut self.shadow_bias, &mut self.target_label);
+        }
+
+        if self.minimap_open {
+            if let Some(minimap_data) = &self.minimap_data {
+                minimap::minimap_window(&self.platform.context(), minimap_data, &mut self.task_activity_open);
+            }
+        }
+
+        if self.inventory_open {
+            if let Some(inventory_data) = &self.inventory_data {
+                if let Some(action) = inventory::inventory_window(&self.platform.context(), inventory_data) {
+                    self.inventory_action = Some(action);
+                }
+            }
+        }
+
+        if self.container_open {
+            if let Some(container_data) = &self.container_data {
+                if let Some(action) = container::container_window(&self.platform.context(), container_data, &mut self.container_open) {
+                    self.container_action = Some(action);
+                }
+            }
+        }
+
+        if self.biome_overlay_open {
+            self.biome_overlay.window(&self.platform.context());
+        }
+
+        if self.settings_open && settings_window::settings_window(&self.platform.context(), &mut self.settings, &mut self.texture_packs) {
+            self.texture_pack_changed = true;
+        }
+
+        // the console closes once a command is entered
+        if self.console_open {
+            if let Some(command) = self.console.show(&self.platform.context()) {
+                self.console_command = Some(command);
+                self.console_open = false;
+            }
+        }
+    }
+
+    // true if a window is open which the hud would be drawn over
+    fn menu_open(&self) -> bool {
+        self.settings_open || self.inventory_open || self.container_open || self.console_open
+    }
+
+    // anything shown about the last world is cleared
+    pub fn open_main_menu(&mut self, main_menu: MainMenu) {
+        self.main_menu = Some(main_menu);
+        self.hud_data = None;
+        self.health = None;
+        self.damage_time = None;
+        self.minimap_data = None;
+        self.world_labels.clear();
+        self.loading_progress = None;
+        self.console_open = false;
+        self.console_command = None;
+        self.inventory_open = false;
+        self.inventory_data = None;
+        self.inventory_action = None;
+        self.close_container();
+    }
+
+    pub fn close_main_menu(&mut self) {
+        self.main_menu = None;
+        self.menu_action = None;
+    }
+
+    pub fn main_menu_mut(&mut self) -> Option<&mut MainMenu> {
+        self.main_menu.as_mut()
+    }
+
+    // what was chosen in the main menu, if anything was since the last call
+    pub fn take_menu_action(&mut self) -> Option<MenuAction> {
+        self.menu_action.take()
+    }
+
+    // none hides the loading screen
+    pub fn set_loading_progress(&mut self, loading_progress: Option<LoadingProgress>) {
+        self.loading_progress = loading_progress;
+    }
+
+    pub fn set_teleport_progress(&mut self, teleport_progress: Option<(usize, usize)>) {
+        self.teleport_progress = teleport_progress;
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    // the new ui scale, if it was changed since the last call
+    pub fn take_ui_scale_change(&mut self) -> Option<f32> {
+        std::mem::take(&mut self.ui_scale_changed).then_some(self.ui_scale)
+    }
+
+    pub fn shadow_bias(&self) -> ShadowBias {
+        self.shadow_bias
+    }
+
+    // sets the pack shown as selected, without counting as a change
+    pub fn set_texture_pack(&mut self, pack: Option<String>) {
+        self.texture_packs.selected = pack;
+        self.texture_pack_changed = false;
+    }
+
+    // the newly selected texture pack, if it was changed since the last call
+    pub fn take_texture_pack_change(&mut self) -> Option<Option<String>> {
+        if std::mem::take(&mut self.texture_pack_changed) {
+            Some(self.texture_packs.selected.clone())
+        } else {
+            None
         }
     }
 
+    pub fn console_open(&self) -> bool {
+        self.console_open
+    }
+
+    // returns true if the console was open
+    pub fn close_console(&mut self) -> bool {
+        std::mem::replace(&mut self.console_open, false)
+    }
+
+    pub fn console_print(&mut self, line: String) {
+        self.console.print(line);
+    }
+
+    // the command entered in the console, if one was since the last call
+    pub fn take_console_command(&mut self) -> Option<String> {
+        self.console_command.take()
+    }
+
+    pub fn inventory_open(&self) -> bool {
+        self.inventory_open
+    }
+
+    pub fn set_inventory_data(&mut self, data: InventoryData) {
+        self.inventory_data = Some(data);
+    }
+
+    // what was done in the inventory window, if anything was since the last call
+    pub fn take_inventory_action(&mut self) -> Option<InventoryAction> {
+        self.inventory_action.take()
+    }
+
+    // the container is shown once its data is set
+    pub fn open_container(&mut self) {
+        self.container_open = true;
+        self.container_data = None;
+        self.container_action = None;
+    }
+
+    pub fn close_container(&mut self) {
+        self.container_open = false;
+        self.container_data = None;
+        self.container_action = None;
+    }
+
+    // false once the player closes the container's window
+    pub fn container_open(&self) -> bool {
+        self.container_open
+    }
+
+    pub fn set_container_data(&mut self, data: ContainerData) {
+        self.container_data = Some(data);
+    }
+
+    // what was done in the container window, if anything was since the last call
+    pub fn take_container_action(&mut self) -> Option<ContainerAction> {
+        self.container_action.take()
+    }
+
+    pub fn minimap_open(&self) -> bool {
+        self.minimap_open
+    }
+
+    // task activity is shown on the minimap
+    pub fn task_activity_open(&self) -> bool {
+        self.minimap_open && self.task_activity_open
+    }
+
+    pub fn set_minimap_data(&mut self, data: MinimapData) {
+        self.minimap_data = Some(data);
+    }
+
+    pub fn hud_open(&self) -> bool {
+        self.hud_open
+    }
+
+    pub fn set_hud_data(&mut self, data: HudData) {
+        self.hud_data = Some(data);
+    }
+
+    pub fn set_health(&mut self, health: Option<f32>) {
+        self.health = health;
+    }
+
+    // briefly flashes the screen red
+    pub fn flash_damage(&mut self) {
+        self.damage_time = Some(Instant::now());
+    }
+
+    // labels is the position each label is drawn above, and its text
+    pub fn draw_world_labels(&mut self, camera: &Camera, labels: &[(Position, String)]) {
+        let screen_size = self.platform.context().input().screen_rect().size();
+        self.world_labels = world_labels::project_labels(camera, screen_size, labels);
+    }
+
+    pub fn target_label_enabled(&self) -> bool {
+        self.target_label
+    }
+
+    pub fn set_screen_tint(&mut self, tint: Vec4) {
+        self.screen_tint = tint;
+    }
+
+    pub fn biome_overlay_open(&self) -> bool {
+        self.biome_overlay_open
+    }
+
+    pub fn biome_overlay_has_diagram(&self) -> bool {
+        self.biome_overlay.has_diagram()
+    }
+
+    pub fn set_biome_diagram(&mut self, colors: &[[u8; 3]]) {
+        self.biome_overlay.set_diagram(&self.platform.context(), colors);
+    }
+
+    pub fn set_biome_overlay_data(&mut self, data: BiomeOverlayData) {
+        self.biome_overlay.set_data(&self.platform.context(), data);
+    }
+
+    // true if the pointer is over a window or dragging something in one, so clicks shouldn't go to the game
+    pub fn wants_pointer_input(&self) -> bool {
+        self.platform.context().wants_pointer_input()
+    }
+
+    // true while typing in the console or a focused text field, so keys shouldn't also go to the game
+    pub fn wants_input(&self) -> bool {
+        self.console_open || self.platform.context().wants_keyboard_input()
+    }
+
     pub fn handle_event(&mut self, event: &Event<()>) {
-        self.platform.handle_event(event);
+        handle_platform_event(&mut self.platform, self.ui_scale, event);
     }
 
     pub fn input(&mut self, event: &WindowEvent) {
+        // the game's windows can't be opened from menus
+        if self.main_menu.is_some() || self.loading_progress.is_some() {
+            return;
+        }
+
         if let WindowEvent::KeyboardInput {
             input: KeyboardInput {
                 state: ElementState::Pressed,
-                virtual_keycode: Some(VirtualKeyCode::F3),
+                virtual_keycode: Some(keycode),
                 ..
             },
             ..
         } = event {
-            self.debug_panel_open = !self.debug_panel_open;
+            match keycode {
+                VirtualKeyCode::F1 => self.hud_open = !self.hud_open,
+                VirtualKeyCode::F3 => self.debug_panel_open = !self.debug_panel_open,
+                VirtualKeyCode::F4 => self.minimap_open = !self.minimap_open,
+                VirtualKeyCode::F5 => self.biome_overlay_open = !self.biome_overlay_open,
+                // letters typed into a text field are only typed, they don't also toggle windows
+                // the slash is typed for the player, since commands start with it
+                VirtualKeyCode::Slash if !self.wants_input() => {
+                    self.console_open = true;
+                    self.console.open("/");
+                },
+                VirtualKeyCode::E if !self.wants_input() => self.inventory_open = !self.inventory_open,
+                VirtualKeyCode::F6 => {
+                    self.settings_open = !self.settings_open;
+                    // packs may have been added or removed since the window was last open
+                    if self.settings_open {
+                        self.texture_packs.available = loader().available_packs();
+                    }
+                },
+                _ => (),
+            }
         }
     }
 
     pub fn frame_update(&mut self, window: &Window, renderer: &Renderer) {
         self.platform.update_time(self.start_time.elapsed().as_secs_f64());
 
-        let size = window.inner_size();
-        let screen_descriptor = ScreenDescriptor {
-            physical_width: size.width,
-            physical_height: size.height,
-            scale_factor: window.scale_factor() as f32,
-        };
+        // egui can change its scale between frames, so the slider changes it right away
+        let ui_scale = self.settings.interface.ui_scale;
+        if ui_scale != self.ui_scale {
+            self.ui_scale = ui_scale;
+            self.ui_scale_changed = true;
+            set_platform_scale(&mut self.platform, window.id(), window.scale_factor(), self.ui_scale, window.inner_size());
+        }
+
+        let screen_descriptor = screen_descriptor(window.inner_size(), window.scale_factor(), self.ui_scale);
 
         self.platform.begin_frame();
 
@@ -100,4 +544,221 @@ impl MineConeUi {
 
         self.render_pass.remove_textures(tdelta).unwrap();
     }
-}
\ No newline at end of file
+}
+
+// egui only sees the os scale factor multiplied by the ui scale, so pointer positions are converted with both
+fn handle_platform_event(platform: &mut Platform, ui_scale: f32, event: &Event<()>) {
+    if let Event::WindowEvent {
+        window_id,
+        event: WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size },
+    } = event {
+        set_platform_scale(platform, *window_id, *scale_factor, ui_scale, **new_inner_size);
+        return;
+    }
+
+    platform.handle_event(event);
+}
+
+// egui's platform has no way to set its scale factor other than telling it the scale factor changed
+fn set_platform_scale(platform: &mut Platform, window_id: WindowId, os_scale_factor: f64, ui_scale: f32, mut size: PhysicalSize<u32>) {
+    let event: Event<()> = Event::WindowEvent {
+        window_id,
+        event: WindowEvent::ScaleFactorChanged {
+            scale_factor: os_scale_factor * ui_scale as f64,
+            new_inner_size: &mut size,
+        },
+    };
+    platform.handle_event(&event);
+}
+
+// egui is painted at the same scale the platform converts input with
+fn screen_descriptor(size: PhysicalSize<u32>, os_scale_factor: f64, ui_scale: f32) -> ScreenDescriptor {
+    ScreenDescriptor {
+        physical_width: size.width,
+        physical_height: size.height,
+        scale_factor: os_scale_factor as f32 * ui_scale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Context, RawInput, Pos2, Rect, Shape, epaint::ClippedShape};
+    use egui_winit_platform::{Platform, PlatformDescriptor};
+    use glam::Vec3;
+    use winit::{window::WindowId, event::*, dpi::{PhysicalSize, PhysicalPosition}};
+
+    use crate::prelude::*;
+    use crate::render::shadow_map::ShadowBias;
+
+    fn input_with_pointer_at(pos: Pos2) -> RawInput {
+        RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, [1280.0, 720.0].into())),
+            events: vec![egui::Event::PointerMoved(pos)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn open_debug_window_captures_pointer() {
+        let context = Context::default();
+        let mut shadow_bias = ShadowBias::default();
+        let mut target_label = false;
+        let _ = context.run(input_with_pointer_at(Pos2::new(1270.0, 710.0)), |context| super::debug_window::debug_window(context, &mut shadow_bias, &mut target_label));
+        assert!(!context.wants_pointer_input());
+
+        let window_center = context.used_rect().center();
+        let _ = context.run(input_with_pointer_at(window_center), |context| super::debug_window::debug_window(context, &mut shadow_bias, &mut target_label));
+        assert!(context.wants_pointer_input());
+    }
+
+    // a 1920x1080 window on a display with an os scale factor of 1.5
+    const WINDOW_SIZE: PhysicalSize<u32> = PhysicalSize::new(1920, 1080);
+    const OS_SCALE_FACTOR: f64 = 1.5;
+
+    fn scaled_platform(ui_scale: f32) -> Platform {
+        let mut platform = Platform::new(PlatformDescriptor {
+            physical_width: WINDOW_SIZE.width,
+            physical_height: WINDOW_SIZE.height,
+            scale_factor: OS_SCALE_FACTOR,
+            font_definitions: Default::default(),
+            style: Default::default(),
+        });
+        let mut size = WINDOW_SIZE;
+        window_event(&mut platform, ui_scale, WindowEvent::ScaleFactorChanged {
+            scale_factor: OS_SCALE_FACTOR,
+            new_inner_size: &mut size,
+        });
+        platform
+    }
+
+    fn window_event(platform: &mut Platform, ui_scale: f32, event: WindowEvent) {
+        let event = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        };
+        super::handle_platform_event(platform, ui_scale, &event);
+    }
+
+    fn cursor_moved(platform: &mut Platform, ui_scale: f32, position: PhysicalPosition<f64>) {
+        #[allow(deprecated)]
+        window_event(platform, ui_scale, WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position,
+            modifiers: Default::default(),
+        });
+    }
+
+    fn mouse_input(platform: &mut Platform, ui_scale: f32, state: ElementState) {
+        #[allow(deprecated)]
+        window_event(platform, ui_scale, WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button: MouseButton::Left,
+            modifiers: Default::default(),
+        });
+    }
+
+    #[test]
+    fn pointer_follows_ui_scale() {
+        for ui_scale in [super::MIN_UI_SCALE, 1.0, super::MAX_UI_SCALE] {
+            let mut platform = scaled_platform(ui_scale);
+            let scale_factor = OS_SCALE_FACTOR as f32 * ui_scale;
+
+            let mut clicked = false;
+            let mut button_rect = Rect::NOTHING;
+            let frame = |platform: &mut Platform, button_rect: &mut Rect, clicked: &mut bool| {
+                platform.begin_frame();
+                egui::CentralPanel::default().show(&platform.context(), |ui| {
+                    let response = ui.button("Play");
+                    *button_rect = response.rect;
+                    *clicked |= response.clicked();
+                });
+                platform.end_frame(None);
+                platform.context().pixels_per_point()
+            };
+
+            assert_eq!(frame(&mut platform, &mut button_rect, &mut clicked), scale_factor);
+            assert_eq!(super::screen_descriptor(WINDOW_SIZE, OS_SCALE_FACTOR, ui_scale).scale_factor, scale_factor);
+
+            let center = button_rect.center();
+            cursor_moved(&mut platform, ui_scale, PhysicalPosition::new((center.x * scale_factor) as f64, (center.y * scale_factor) as f64));
+            mouse_input(&mut platform, ui_scale, ElementState::Pressed);
+            frame(&mut platform, &mut button_rect, &mut clicked);
+            let pointer = platform.context().input().pointer.hover_pos().unwrap();
+            assert!((pointer - center).length() < 0.01, "pointer at {:?} instead of {:?} at ui scale {}", pointer, center, ui_scale);
+
+            mouse_input(&mut platform, ui_scale, ElementState::Released);
+            frame(&mut platform, &mut button_rect, &mut clicked);
+            assert!(clicked, "button not clicked at ui scale {}", ui_scale);
+        }
+    }
+
+    #[test]
+    fn hud_follows_ui_scale() {
+        let hud_data = super::HudData {
+            block: BlockPos::new(12, 64, -30),
+            facing: Vec3::NEG_Z,
+            biome: "Plains".to_owned(),
+        };
+        // physical size of the hud and health bar at each scale
+        let sizes = |ui_scale: f32| {
+            let mut platform = scaled_platform(ui_scale);
+            // anchored areas are only placed right once their size is known from the frame before
+            let mut output = None;
+            for _ in 0..2 {
+                platform.begin_frame();
+                super::hud::hud(&platform.context(), &hud_data);
+                super::hud::health_bar(&platform.context(), 10.0, 20.0);
+                output = Some(platform.end_frame(None));
+            }
+            let output = output.unwrap();
+            let context = platform.context();
+            let scale_factor = context.pixels_per_point();
+
+            // the hud stays anchored in the top right corner of the window
+            let hud_rect = context.used_rect();
+            assert!((hud_rect.right() * scale_factor - WINDOW_SIZE.width as f32).abs() < 8.0 * scale_factor + 1.0, "hud at {:?} at scale {}", hud_rect, scale_factor);
+
+            // the first rectangle painted is the background of the health bar, centered at the bottom of the window
+            let health_bar = output.shapes.iter()
+                .find_map(|ClippedShape(_, shape)| match shape {
+                    Shape::Rect(rect) => Some(rect.rect),
+                    _ => None,
+                })
+                .unwrap();
+            assert!((health_bar.center().x * scale_factor - WINDOW_SIZE.width as f32 / 2.0).abs() < 1.0);
+
+            (hud_rect.size() * scale_factor, health_bar.size() * scale_factor)
+        };
+
+        let (hud_size, health_bar_size) = sizes(1.0);
+        for ui_scale in [super::MIN_UI_SCALE, super::MAX_UI_SCALE] {
+            let (scaled_hud_size, scaled_health_bar_size) = sizes(ui_scale);
+            assert!((scaled_health_bar_size - health_bar_size * ui_scale).length() < 0.01, "health bar not scaled at ui scale {}", ui_scale);
+            // text is laid out in whole pixels, so the hud is only roughly scaled
+            let ratio = scaled_hud_size.x / hud_size.x;
+            assert!((ratio / ui_scale - 1.0).abs() < 0.1, "hud scaled by {} at ui scale {}", ratio, ui_scale);
+        }
+    }
+
+    #[test]
+    fn focused_text_field_captures_keyboard() {
+        let context = Context::default();
+        let mut text = String::new();
+        let mut frame = |context: &Context, focus: bool| {
+            egui::CentralPanel::default().show(context, |ui| {
+                let response = ui.text_edit_singleline(&mut text);
+                if focus {
+                    response.request_focus();
+                }
+            });
+        };
+
+        let _ = context.run(input_with_pointer_at(Pos2::ZERO), |context| frame(context, false));
+        assert!(!context.wants_keyboard_input());
+
+        // clicked while a movement key is held, the client releases the key once this is true
+        let _ = context.run(input_with_pointer_at(Pos2::ZERO), |context| frame(context, true));
+        assert!(context.wants_keyboard_input());
+    }
+}
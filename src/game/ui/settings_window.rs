@@ -0,0 +1,104 @@
+use egui::{Window, Context, Slider, ComboBox};
+
+use winit::event::VirtualKeyCode;
+
+use crate::game::settings::{Settings, MIN_UI_SCALE, MAX_UI_SCALE};
+
+const SHADOW_RESOLUTIONS: [u32; 3] = [1024, 2048, 4096];
+
+// keys which can be bound to picking blocks
+const PICK_BLOCK_KEYS: [VirtualKeyCode; 5] = [
+    VirtualKeyCode::Q,
+    VirtualKeyCode::E,
+    VirtualKeyCode::R,
+    VirtualKeyCode::F,
+    VirtualKeyCode::G,
+];
+
+// keys which can be bound to sprinting
+const SPRINT_KEYS: [VirtualKeyCode; 6] = [
+    VirtualKeyCode::LControl,
+    VirtualKeyCode::RControl,
+    VirtualKeyCode::Tab,
+    VirtualKeyCode::Z,
+    VirtualKeyCode::X,
+    VirtualKeyCode::C,
+];
+
+// texture packs which can be chosen, and the one that is chosen, none is the default textures
+#[derive(Debug, Default)]
+pub struct TexturePackChoice {
+    pub available: Vec<String>,
+    pub selected: Option<String>,
+}
+
+// returns true if a different texture pack was selected
+pub fn settings_window(context: &Context, settings: &mut Settings, texture_packs: &mut TexturePackChoice) -> bool {
+    let old_pack = texture_packs.selected.clone();
+
+    Window::new("Settings").resizable(false).show(context, |ui| {
+        ui.heading("Movement");
+        ui.checkbox(&mut settings.sprint.double_tap_sprint, "Double tap forward to sprint");
+        ui.checkbox(&mut settings.sprint.toggle_sprint, "Toggle sprint");
+        ComboBox::from_label("Sprint key")
+            .selected_text(format!("{:?}", settings.sprint.sprint_key))
+            .show_ui(ui, |ui| {
+                for key in SPRINT_KEYS {
+                    ui.selectable_value(&mut settings.sprint.sprint_key, key, format!("{:?}", key));
+                }
+            });
+
+        ui.heading("Interface");
+        ui.add(Slider::new(&mut settings.interface.ui_scale, MIN_UI_SCALE..=MAX_UI_SCALE).text("UI scale"));
+
+        ui.heading("Graphics");
+        ui.add(Slider::new(&mut settings.graphics.ao_strength, 0.0..=1.0).text("Ambient occlusion strength"));
+        ui.checkbox(&mut settings.graphics.face_shading, "Face shading");
+        ui.checkbox(&mut settings.graphics.smooth_lighting, "Smooth lighting");
+        ui.checkbox(&mut settings.graphics.blob_shadows, "Entity shadows");
+        ui.checkbox(&mut settings.graphics.sun_shadows, "Sun shadows");
+        ui.add_enabled_ui(settings.graphics.sun_shadows, |ui| {
+            ComboBox::from_label("Shadow resolution")
+                .selected_text(settings.graphics.shadow_resolution.to_string())
+                .show_ui(ui, |ui| {
+                    for resolution in SHADOW_RESOLUTIONS {
+                        ui.selectable_value(&mut settings.graphics.shadow_resolution, resolution, resolution.to_string());
+                    }
+                });
+        });
+        ui.add(Slider::new(&mut settings.graphics.camera_smoothing_rate, 1.0..=60.0).text("Camera smoothing rate"));
+        ui.add(Slider::new(&mut settings.graphics.full_detail_distance, 32.0..=512.0).text("Full detail distance"));
+        ui.checkbox(&mut settings.graphics.water_reflections, "Water reflections");
+        ui.add(Slider::new(&mut settings.graphics.mesh_uploads_per_update, 1..=256).text("Chunk mesh uploads per update"));
+        ui.checkbox(&mut settings.graphics.vsync, "Vsync");
+        ComboBox::from_label("Texture pack")
+            .selected_text(texture_packs.selected.as_deref().unwrap_or("Default"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut texture_packs.selected, None, "Default");
+                for pack in texture_packs.available.iter() {
+                    ui.selectable_value(&mut texture_packs.selected, Some(pack.clone()), pack);
+                }
+            });
+
+        ui.heading("Interaction");
+        ui.add(Slider::new(&mut settings.interaction.survival_reach, 1.0..=10.0).text("Reach"));
+        ui.add(Slider::new(&mut settings.interaction.fly_reach, 1.0..=50.0).text("Reach while flying"));
+        ComboBox::from_label("Pick block key")
+            .selected_text(key_name(settings.interaction.pick_block_key))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut settings.interaction.pick_block_key, None, key_name(None));
+                for key in PICK_BLOCK_KEYS {
+                    ui.selectable_value(&mut settings.interaction.pick_block_key, Some(key), key_name(Some(key)));
+                }
+            });
+    });
+
+    texture_packs.selected != old_pack
+}
+
+fn key_name(key: Option<VirtualKeyCode>) -> String {
+    match key {
+        Some(key) => format!("{:?}", key),
+        None => String::from("Middle mouse only"),
+    }
+}
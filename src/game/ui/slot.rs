@@ -0,0 +1,50 @@
+use egui::{Button, Response, RichText, Ui, Vec2};
+
+use crate::game::block::{BlockType, BlockTrait};
+
+const SLOT_SIZE: Vec2 = Vec2::new(72.0, 40.0);
+
+// what a slot holds, the count is none when blocks are infinite
+pub type SlotContents = Option<(BlockType, Option<u32>)>;
+
+fn slot_text(contents: SlotContents) -> String {
+    match contents {
+        Some((block_type, Some(count))) => format!("{}\n{}", block_type.display_name(), count),
+        Some((block_type, None)) => block_type.display_name(),
+        None => String::new(),
+    }
+}
+
+// texture index and hardness are only useful when working on the game, so they are only shown in debug builds
+fn slot_tooltip(ui: &mut Ui, block_type: BlockType, count: Option<u32>) {
+    ui.label(RichText::new(block_type.display_name()).strong());
+    ui.label(format!("id: {} ({})", block_type.name(), block_type as u8));
+    match count {
+        Some(count) => ui.label(format!("count: {}", count)),
+        None => ui.label("count: infinite"),
+    };
+
+    if cfg!(debug_assertions) {
+        let block = block_type.new_block();
+        match block.texture_index() {
+            Some(texture_index) => ui.label(format!("texture index: {}", texture_index)),
+            None => ui.label("texture index: none"),
+        };
+        ui.label(format!("hardness: {}", block.hardness()));
+    }
+}
+
+// a button showing what is in a slot of the hotbar, inventory or a container, with a tooltip about the block in it
+// the selected slot is highlighted
+pub fn slot_widget(ui: &mut Ui, contents: SlotContents, selected: bool) -> Response {
+    let mut button = Button::new(slot_text(contents));
+    if selected {
+        button = button.fill(ui.visuals().selection.bg_fill);
+    }
+
+    let response = ui.add_sized(SLOT_SIZE, button);
+    match contents {
+        Some((block_type, count)) => response.on_hover_ui(|ui| slot_tooltip(ui, block_type, count)),
+        None => response,
+    }
+}
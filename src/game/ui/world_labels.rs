@@ -0,0 +1,95 @@
+use egui::{Align2, Color32, Context, FontId, LayerId, Pos2, Rect, Vec2};
+
+use crate::prelude::*;
+use crate::render::camera::Camera;
+
+// labels further than this from the camera aren't shown
+pub const MAX_LABEL_DISTANCE: f32 = 48.0;
+// labels start fading out at this distance, and are invisible at MAX_LABEL_DISTANCE
+const FADE_START_DISTANCE: f32 = 32.0;
+const FONT_SIZE: f32 = 14.0;
+// space between the text and the edge of its background
+const PADDING: f32 = 2.0;
+
+// a label over something in the world, after it is projected onto the screen
+pub struct ScreenLabel {
+    // the bottom center of the label
+    position: Pos2,
+    text: String,
+    alpha: f32,
+}
+
+// fully visible up to the fade start distance, then fades out linearly
+fn label_alpha(distance: f32) -> f32 {
+    (1.0 - (distance - FADE_START_DISTANCE) / (MAX_LABEL_DISTANCE - FADE_START_DISTANCE)).clamp(0.0, 1.0)
+}
+
+// labels behind the camera, off the screen, or too far away are left out
+// they are sorted furthest first, so closer labels are drawn on top
+pub fn project_labels(camera: &Camera, screen_size: Vec2, labels: &[(Position, String)]) -> Vec<ScreenLabel> {
+    let camera_position = camera.get_position();
+
+    let mut projected = labels.iter()
+        .filter_map(|(position, text)| {
+            let distance = position.distance(camera_position.0);
+            if distance >= MAX_LABEL_DISTANCE {
+                return None;
+            }
+
+            let screen_position = camera.world_to_screen(*position, screen_size)?;
+            Some((distance, ScreenLabel {
+                position: screen_position,
+                text: text.clone(),
+                alpha: label_alpha(distance),
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    projected.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    projected.into_iter().map(|(_, label)| label).collect()
+}
+
+// drawn behind all windows
+pub fn world_labels(context: &Context, labels: &[ScreenLabel]) {
+    let painter = context.layer_painter(LayerId::background());
+
+    for label in labels.iter() {
+        let text_color = Color32::from_white_alpha((label.alpha * 255.0) as u8);
+        let background_color = Color32::from_black_alpha((label.alpha * 128.0) as u8);
+
+        let galley = painter.layout_no_wrap(label.text.clone(), FontId::proportional(FONT_SIZE), text_color);
+        let text_rect = Align2::CENTER_BOTTOM.anchor_rect(Rect::from_min_size(label.position, galley.size()));
+
+        painter.rect_filled(text_rect.expand(PADDING), PADDING, background_color);
+        painter.galley(text_rect.min, galley);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_fade_with_distance() {
+        assert_eq!(label_alpha(0.0), 1.0);
+        assert_eq!(label_alpha(FADE_START_DISTANCE), 1.0);
+        assert!((label_alpha(0.5 * (FADE_START_DISTANCE + MAX_LABEL_DISTANCE)) - 0.5).abs() < 0.001);
+        assert_eq!(label_alpha(MAX_LABEL_DISTANCE), 0.0);
+    }
+
+    #[test]
+    fn far_labels_are_hidden_and_drawn_first() {
+        let camera = Camera::new(glam::Vec3::ZERO, glam::Vec3::X, 16.0 / 9.0);
+        let labels = [
+            (Position::new(5.0, 0.0, 0.0), String::from("near")),
+            (Position::new(40.0, 0.0, 0.0), String::from("far")),
+            (Position::new(-5.0, 0.0, 0.0), String::from("behind")),
+            (Position::new(MAX_LABEL_DISTANCE + 1.0, 0.0, 0.0), String::from("too far")),
+        ];
+
+        let projected = project_labels(&camera, Vec2::new(1280.0, 720.0), &labels);
+        let texts = projected.iter().map(|label| label.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["far", "near"]);
+        assert!(projected[0].alpha < projected[1].alpha);
+    }
+}
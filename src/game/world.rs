@@ -1,28 +1,63 @@
 use std::{
-	fs::{File, OpenOptions},
-	path::Path,
+	fmt, mem,
+	cell::Cell,
+	path::{Path, PathBuf},
 	sync::{Arc, Weak},
+	sync::atomic::{AtomicBool, AtomicU64, Ordering},
+	hash::{Hash, Hasher},
+	time::{Duration, Instant},
 };
 
-use rustc_hash::FxHashMap;
-use glam::{UVec3, IVec3};
-use anyhow::Result;
-use parking_lot::RwLock;
+use rustc_hash::{FxHashMap, FxHasher};
+use glam::{UVec3, IVec2, IVec3};
+use anyhow::{Result, Context};
+use parking_lot::{RwLock, Mutex};
 use glam::Vec3;
 use rustc_hash::FxHashSet;
+use smallvec::SmallVec;
 
 use super::{
-	chunk::{Chunk, LoadedChunk, ChunkData, VisitedBlockMap},
-	entity::Entity,
-	block::{BlockFaceMesh, BlockFace, Block, BlockTrait},
-	worldgen::WorldGenerator,
-	player::{Player, PlayerId},
-	parallel::{Task, run_task, pull_completed_task},
+	chunk::{Chunk, LoadedChunk, ChunkData, VisitedBlockMap, CHUNK_BLOCK_BYTES},
+	entity::{Entity, EntityId, Item, MAX_PUSH_PER_TICK, push_out},
+	block_entity::BlockEntity,
+	block::{BlockFaceMesh, BlockFace, Block, BlockType, BlockTrait},
+	worldgen::{WorldGenerator, GeneratorType, GeneratorOptions, BiomeNoiseData, SurfaceBiome, UndergroundBiome},
+	player::{Player, PlayerId, PlayerPosition, PlayerState, RenderDistance, DamageOutcome, fall_damage, DEFAULT_SPAWN_POINT},
+	parallel::{Task, run_task, run_background_task, pull_completed_task, pull_failed_task},
+	block_stats::BlockStats,
 };
 use crate::prelude::*;
-use super::render_zone::{RENDER_ZONE_SIZE, UpdatedRenderZones};
+use crate::math::Aabb;
+use super::render_zone::UpdatedChunks;
+use super::tick::DEFAULT_TICK_RATE;
+use super::block_update::{BlockUpdateQueue, NeighborUpdateQueue, NeighborUpdate};
+use super::region::{RegionCache, DEFAULT_OPEN_REGIONS};
+use super::world_state::{WorldState, Weather};
+use super::mesh_validation::{MeshValidation, validate_chunk_mesh};
 use crate::vec3_map;
 
+// a chunk read back from its region or the cache, with the entities that were saved with it
+pub type SavedChunk = (LoadedChunk, Vec<(EntityId, Box<dyn Entity>)>);
+
+// the block a raycast hit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+	pub block: BlockPos,
+	// face of the block the ray entered through
+	pub face: BlockFace,
+	// distance along the ray to where it entered the block
+	pub distance: f32,
+	// the box of the block the ray hit, in world space
+	pub selection: Aabb,
+}
+
+impl RaycastHit {
+	// the block on the other side of the face that was hit, where a new block would be placed
+	pub fn adjacent_block(&self) -> BlockPos {
+		self.block + self.face.block_pos_offset()
+	}
+}
+
 #[derive(Debug)]
 pub struct ChunkMeshFaceData {
 	min_chunk: ChunkPos,
@@ -40,121 +75,476 @@ impl ChunkMeshFaceData {
 	}
 }
 
+// mesh face tasks for the chunks around a newly loaded box which are inside the loaded zone,
+// their faces pointing into the box were meshed while the box was missing
+fn seam_mesh_face_tasks(min_chunk: ChunkPos, max_chunk: ChunkPos, zone_min: ChunkPos, zone_max: ChunkPos) -> Vec<ChunkMeshFaceData> {
+	let mut out = Vec::new();
+
+	for axis in [Axis::X, Axis::Y, Axis::Z] {
+		if min_chunk[axis] > zone_min[axis] {
+			let mut seam_min = min_chunk;
+			let mut seam_max = max_chunk;
+			seam_min[axis] = min_chunk[axis] - 1;
+			seam_max[axis] = min_chunk[axis];
+
+			out.push(ChunkMeshFaceData {
+				min_chunk: seam_min,
+				max_chunk: seam_max,
+				face: BlockFace::from_axis(axis, true),
+			});
+		}
+
+		if max_chunk[axis] < zone_max[axis] {
+			let mut seam_min = min_chunk;
+			let mut seam_max = max_chunk;
+			seam_min[axis] = max_chunk[axis];
+			seam_max[axis] = max_chunk[axis] + 1;
+
+			out.push(ChunkMeshFaceData {
+				min_chunk: seam_min,
+				max_chunk: seam_max,
+				face: BlockFace::from_axis(axis, false),
+			});
+		}
+	}
+
+	out
+}
+
 #[derive(Debug)]
 struct ChunkLoadJob {
-	min_chunk: ChunkPos,
-	max_chunk: ChunkPos,
+	chunks: ChunkBox,
 	remaining_chunks: u64,
-	// data to run the mesh facing task after the chunk is done loading in
-	// TODO: handle when there is more than 1 face to mesh
-	mesh_face_task: Option<ChunkMeshFaceData>,
+	// mesh face tasks to run after the chunks are done loading in, one for each side of the job's box
+	// where there are other loaded chunks whose faces need to be remeshed
+	mesh_face_tasks: Vec<ChunkMeshFaceData>,
+	// when the job was started, used to measure chunk load latency
+	requested: Instant,
+}
+
+// removes every job the predicate returns true for and returns them, the other jobs keep their order
+fn remove_jobs(jobs: &mut Vec<ChunkLoadJob>, mut predicate: impl FnMut(&mut ChunkLoadJob) -> bool) -> Vec<ChunkLoadJob> {
+	let mut removed = Vec::new();
+	let mut i = 0;
+	while i < jobs.len() {
+		if predicate(&mut jobs[i]) {
+			removed.push(jobs.remove(i));
+		} else {
+			i += 1;
+		}
+	}
+	removed
+}
+
+// what a console command did to a chunk to recover it from a bad mesh or bad contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkRepair {
+	Remesh,
+	Regenerate,
+}
+
+// a repaired chunk waiting for its mesh tasks, it is finished once they have all completed
+#[derive(Debug)]
+struct ChunkRepairJob {
+	chunk: ChunkPos,
+	repair: ChunkRepair,
+	remaining_tasks: Vec<Task>,
 }
 
-impl ChunkLoadJob {
-	fn contains_chunk(&self, chunk: ChunkPos) -> bool {
-		chunk.x >= self.min_chunk.x
-			&& chunk.y >= self.min_chunk.y
-			&& chunk.z >= self.min_chunk.z
-			&& chunk.x < self.max_chunk.x
-			&& chunk.y < self.max_chunk.y
-			&& chunk.z < self.max_chunk.z
+// why a change to the world could not be made, so commands can say what went wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldError {
+	ChunkNotLoaded {
+		chunk: ChunkPos,
+	},
+	PlayerNotFound {
+		player: PlayerId,
+	},
+}
+
+impl fmt::Display for WorldError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::ChunkNotLoaded { chunk } => write!(f, "chunk {:?} is not loaded", chunk.0),
+			Self::PlayerNotFound { player } => write!(f, "player {:?} is not in the world", player),
+		}
 	}
 }
 
+impl std::error::Error for WorldError {}
+
+// state of a chunk as seen from outside the world, used by debug views
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkState {
+	Loaded,
+	// part of a load job, but not generated yet
+	Queued,
+	// unloaded, but kept in memory since it couldn't be saved
+	Cached,
+	Unloaded,
+}
+
+// estimated memory used by the world's chunks, shown in the debug window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorldMemoryStats {
+	pub loaded_chunks: usize,
+	pub block_bytes: u64,
+	// cpu side meshes of the loaded chunks
+	pub mesh_bytes: u64,
+	pub cached_chunks: usize,
+	pub cached_bytes: u64,
+}
+
+// number of entities in each state, shown in the debug window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntityCount {
+	// in a loaded chunk, so they are ticked
+	pub active: usize,
+	// in the world, but their chunk isn't loaded, so they aren't ticked
+	pub frozen: usize,
+	// kept with their unloaded chunk in the chunk cache until it is loaded again
+	pub pending_save: usize,
+}
+
 // max size of world in chunks
 // 16,384 meters in each x and y direction
 // 2,048 meters in z direction
 pub const WORLD_MAX_SIZE: UVec3 = UVec3::new(512, 64, 512);
 
+// each player's state is saved in a file in this directory, inside of the world's directory
+const PLAYERS_DIRECTORY: &str = "players";
+
+// scheduled block updates past this run on later ticks, so large floods don't stall a tick
+const MAX_BLOCK_UPDATES_PER_TICK: usize = 1024;
+
+// neighbor updates past this run on later ticks
+const MAX_NEIGHBOR_UPDATES_PER_TICK: usize = 4096;
+
+// changed blocks are only taken by a client polling completed tasks, so without one changes past this aren't remeshed
+const MAX_CHANGED_BLOCKS: usize = 16384;
+
+thread_local! {
+	// depth of the neighbor update whose hook is running on this thread, 0 when no hook is running
+	static NEIGHBOR_UPDATE_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+// vertex ambient occlusion with smooth lighting depends on blocks up to this many blocks away in the same layer
+const OCCLUSION_LAYER_REACH: i32 = 2;
+
+// chunks within this distance of a teleport's destination chunk are generated before the player is moved there
+const TELEPORT_CORE_RADIUS: i32 = 1;
+
+// the chunks which must be generated before a player can be moved to destination
+fn teleport_core(destination: Position) -> ChunkBox {
+	let chunk = destination.as_chunk_pos();
+	ChunkBox::new(chunk - ChunkPos::splat(TELEPORT_CORE_RADIUS), chunk + ChunkPos::splat(TELEPORT_CORE_RADIUS + 1))
+}
+
+// horizontal distance in chunks around spawn which is pregenerated when a world is created,
+// it is used like the horizontal render distance, so the same area is pregenerated as a player at spawn would load
+pub const DEFAULT_PREGEN_RADIUS: i32 = 8;
+// 128 by 128 columns of chunks, larger areas would take hours to generate
+pub const MAX_PREGEN_RADIUS: i32 = 64;
+
+// chunks around spawn being generated in the background, see start_pregen
+#[derive(Debug, Clone, Copy)]
+struct PregenJob {
+	total: usize,
+	done: usize,
+	started: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PregenProgress {
+	pub done: usize,
+	pub total: usize,
+	// none until the first chunk is done
+	pub time_left: Option<Duration>,
+}
+
+// a teleport waiting for the chunks around its destination to be generated
+#[derive(Debug, Clone, Copy)]
+struct PendingTeleport {
+	destination: Position,
+	// the render zone around the destination, which is loaded when the teleport starts
+	zone: ChunkBox,
+}
+
+// chunks which have blocks in the same layer as the block, within OCCLUSION_LAYER_REACH of the block
+fn occlusion_layer_chunks(block: BlockPos, face: BlockFace) -> SmallVec<[ChunkPos; 4]> {
+	let in_layer = IVec3::ONE - face.block_pos_offset().abs();
+	let min_chunk = (block - BlockPos(in_layer * OCCLUSION_LAYER_REACH)).as_chunk_pos();
+	let max_chunk = (block + BlockPos(in_layer * OCCLUSION_LAYER_REACH)).as_chunk_pos();
+
+	ChunkPos::iter_range(min_chunk, max_chunk + ChunkPos::splat(1)).collect()
+}
+
 pub struct World {
 	self_weak: Weak<Self>,
 	players: RwLock<FxHashMap<PlayerId, Player>>,
-	entities: RwLock<Vec<Box<dyn Entity>>>,
+	// positions are read and changed much more often than the rest of the player, so they aren't behind the players lock
+	// a player is in both maps or neither, they are added to players first and removed from it last
+	player_positions: FxDashMap<PlayerId, PlayerPosition>,
+	entities: RwLock<FxHashMap<EntityId, Box<dyn Entity>>>,
+	// ids of the entities in each chunk, so entities near a position can be found without checking every entity
+	// always locked after entities
+	entity_chunks: FxDashMap<ChunkPos, SmallVec<[EntityId; 4]>>,
+	// loaded chunks which have block entities to tick, so ticking doesn't have to look at every chunk
+	ticking_chunks: Mutex<FxHashSet<ChunkPos>>,
 	pub chunks: FxDashMap<ChunkPos, LoadedChunk>,
+	// unloaded chunks which couldn't be written to their region, kept until save_all can write them
 	cached_chunks: RwLock<FxHashMap<ChunkPos, ChunkData>>,
 	chunk_load_jobs: RwLock<Vec<ChunkLoadJob>>,
 	chunk_unload_jobs: RwLock<Vec<ChunkLoadJob>>,
+	// players are moved once the core of their destination is generated, see teleport_player
+	pending_teleports: Mutex<FxHashMap<PlayerId, PendingTeleport>>,
+	pregen: Mutex<Option<PregenJob>>,
+	// also holds the world's seed
 	pub(super) world_generator: WorldGenerator,
-	// backing file of the world
-	file: File,
+	// number of the last tick that was run
+	current_tick: AtomicU64,
+	// length of a tick in microseconds
+	tick_time: AtomicU64,
+	// time of day and weather, advanced each tick and read by the client every physics update
+	state: Mutex<WorldState>,
+	// if ambient occlusion is averaged between adjacent vertices when meshing
+	smooth_lighting: AtomicBool,
+	// total number of block raycasts done, shown in the debug window
+	raycast_count: AtomicU64,
+	// checks run on chunk meshes after they are updated, and the total number of violations found
+	mesh_validation: Mutex<MeshValidation>,
+	mesh_violation_count: AtomicU64,
+	block_updates: Mutex<BlockUpdateQueue>,
+	neighbor_updates: Mutex<NeighborUpdateQueue>,
+	// blocks which were changed since the last time completed tasks were polled, and need to be remeshed
+	changed_blocks: Mutex<Vec<BlockPos>>,
+	// the oldest block edit the client hasn't taken yet, and when it was made, to measure how long edits take to be shown
+	pending_edit: Mutex<Option<(BlockPos, Instant)>>,
+	// time each chunk load job took to finish, since the last time they were taken
+	chunk_load_latencies: Mutex<Vec<Duration>>,
+	// chunks being remeshed or regenerated, and the ones finished since they were last taken
+	repair_jobs: Mutex<Vec<ChunkRepairJob>>,
+	finished_repairs: Mutex<Vec<(ChunkPos, ChunkRepair)>>,
+	// results of Task::Analyze which haven't been taken yet, none for an analysis which failed
+	finished_analyses: Mutex<Vec<Option<BlockStats>>>,
+	// chunks saved in the world's directory
+	regions: RegionCache,
+	directory: PathBuf,
 }
 
 impl World {
-	pub fn load_from_file<T: AsRef<Path>>(file_name: T) -> Result<Arc<Self>> {
-		let file = OpenOptions::new()
-			.read(true)
-			.write(true)
-			.open(file_name)?;
+	// the world is saved in directory, which is created if it doesn't exist
+	pub fn open<T: AsRef<Path>>(directory: T, seed: u32, generator_type: GeneratorType, generator_options: GeneratorOptions) -> Result<Arc<Self>> {
+		let directory = directory.as_ref().to_owned();
+		let regions = RegionCache::new(&directory, DEFAULT_OPEN_REGIONS)?;
+		let state = WorldState::load(&directory).context("could not load world state")?;
 
 		Ok(Arc::new_cyclic(|weak| Self {
 			self_weak: weak.clone(),
 			players: RwLock::new(FxHashMap::default()),
-			entities: RwLock::new(Vec::new()),
+			player_positions: FxDashMap::default(),
+			entities: RwLock::new(FxHashMap::default()),
+			entity_chunks: FxDashMap::default(),
+			ticking_chunks: Mutex::new(FxHashSet::default()),
 			chunks: FxDashMap::default(),
 			cached_chunks: RwLock::new(FxHashMap::default()),
 			chunk_load_jobs: RwLock::new(Vec::new()),
 			chunk_unload_jobs: RwLock::new(Vec::new()),
-			world_generator: WorldGenerator::new(0),
-			file,
+			pending_teleports: Mutex::new(FxHashMap::default()),
+			pregen: Mutex::new(None),
+			world_generator: WorldGenerator::new(seed, generator_type, generator_options),
+			current_tick: AtomicU64::new(0),
+			tick_time: AtomicU64::new(1_000_000 / DEFAULT_TICK_RATE as u64),
+			state: Mutex::new(state),
+			smooth_lighting: AtomicBool::new(false),
+			raycast_count: AtomicU64::new(0),
+			mesh_validation: Mutex::new(MeshValidation::Off),
+			mesh_violation_count: AtomicU64::new(0),
+			block_updates: Mutex::new(BlockUpdateQueue::new()),
+			neighbor_updates: Mutex::new(NeighborUpdateQueue::new()),
+			changed_blocks: Mutex::new(Vec::new()),
+			pending_edit: Mutex::new(None),
+			chunk_load_latencies: Mutex::new(Vec::new()),
+			repair_jobs: Mutex::new(Vec::new()),
+			finished_repairs: Mutex::new(Vec::new()),
+			finished_analyses: Mutex::new(Vec::new()),
+			regions,
+			directory,
 		}))
 	}
 
-	// TEMP
+	// a world with seed 0 saved in the temp directory
+	// each test world gets its own directory, so tests running in parallel don't load each other's saves
+	#[cfg(test)]
 	pub fn new_test() -> Result<Arc<Self>> {
-		let file = OpenOptions::new()
-			.read(true)
-			.write(true)
-			.open("test-world")?;
+		static NEXT_TEST_WORLD: AtomicU64 = AtomicU64::new(0);
 
-		let out = Arc::new_cyclic(|weak| Self {
-			self_weak: weak.clone(),
-			players: RwLock::new(FxHashMap::default()),
-			entities: RwLock::new(Vec::new()),
-			chunks: FxDashMap::default(),
-			cached_chunks: RwLock::new(FxHashMap::default()),
-			chunk_load_jobs: RwLock::new(Vec::new()),
-			chunk_unload_jobs: RwLock::new(Vec::new()),
-			world_generator: WorldGenerator::new(0),
-			file,
+		let id = NEXT_TEST_WORLD.fetch_add(1, Ordering::Relaxed);
+		let directory = std::env::temp_dir().join(format!("minecone-test-world-{}-{}", std::process::id(), id));
+		// left over from an earlier run with the same process id
+		let _ = std::fs::remove_dir_all(&directory);
+		Self::open(directory, 0, GeneratorType::Normal, GeneratorOptions::default())
+	}
+
+	// writes the world state, players, loaded chunks which changed, and unloaded chunks which haven't been saved yet
+	// to the world's directory
+	pub fn save_all(&self) -> Result<()> {
+		self.save(false)
+	}
+
+	// like save_all, but used after a panic, when the panicking thread may still hold the entities,
+	// then the chunks are saved without them instead of waiting forever
+	pub fn save_after_crash(&self) -> Result<()> {
+		self.save(true)
+	}
+
+	fn save(&self, after_crash: bool) -> Result<()> {
+		self.world_state().save(&self.directory).context("could not save world state")?;
+		for player in self.players.read().values() {
+			if let Some(position) = self.player_position(player.id()) {
+				self.save_player(player, position)?;
+			}
+		}
+		self.save_loaded_chunks(after_crash);
+		self.save_cached_chunks();
+		self.regions.sync_all()
+	}
+
+	// writes loaded chunks whose blocks changed or which have entities in them to their regions
+	// normal saves wait for the entities, since workers loading or unloading chunks can hold them for a moment,
+	// and chunks saved without their entities would lose them once the world is closed
+	fn save_loaded_chunks(&self, after_crash: bool) {
+		// the chunk map is always locked after entities
+		let entities = if after_crash {
+			self.entities.try_read()
+		} else {
+			Some(self.entities.read())
+		};
+		if entities.is_none() {
+			warn!("entities are locked, saving chunks without them");
+		}
+
+		let mut saved_chunks = 0;
+		for loaded_chunk in self.chunks.iter() {
+			let chunk_pos = *loaded_chunk.key();
+			let chunk_entities = match (&entities, self.entity_chunks.get(&chunk_pos)) {
+				(Some(entities), Some(ids)) => ids.iter()
+					.filter_map(|id| entities.get(id))
+					.map(|entity| &**entity)
+					.collect(),
+				_ => Vec::new(),
+			};
+			if !loaded_chunk.chunk.take_modified() && chunk_entities.is_empty() {
+				continue;
+			}
+
+			// one chunk which can't be written shouldn't stop the rest from being saved
+			let bytes = ChunkData::encode_parts(&loaded_chunk.chunk, chunk_entities.into_iter());
+			match self.regions.write_chunk(chunk_pos, &bytes) {
+				Ok(()) => saved_chunks += 1,
+				Err(error) => {
+					// still saved when the chunk is unloaded or the world is saved again
+					loaded_chunk.chunk.mark_modified();
+					warn!("could not save chunk {:?}: {:#}", chunk_pos.0, error);
+				},
+			}
+		}
+
+		info!("saved {} chunks", saved_chunks);
+	}
+
+	// tries again to write the unloaded chunks which couldn't be written when they were unloaded
+	fn save_cached_chunks(&self) {
+		self.cached_chunks.write().retain(|chunk_pos, chunk_data| match self.regions.write_chunk(*chunk_pos, &chunk_data.encode()) {
+			Ok(()) => false,
+			Err(error) => {
+				warn!("could not save unloaded chunk {:?}: {:#}", chunk_pos.0, error);
+				true
+			},
 		});
+	}
+
+	// characters other than letters, numbers, - and _ are replaced, so the name can't point outside of the players directory
+	fn player_file(&self, name: &str) -> PathBuf {
+		let file_name = name.chars()
+			.map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+			.collect::<String>();
+		self.directory.join(PLAYERS_DIRECTORY).join(file_name).with_extension("cfg")
+	}
+
+	fn save_player(&self, player: &Player, position: Position) -> Result<()> {
+		let file = self.player_file(player.name());
+		let state = PlayerState {
+			position,
+			..player.state()
+		};
+		state.save(&file).with_context(|| format!("could not save player {}", player.name()))
+	}
 
-		Ok(out)
+	// drops every chunk and player, must be called once the workers are shut down for the world to be freed,
+	// since chunks hold a reference to the world
+	pub fn close(&self) {
+		self.chunks.clear();
+		self.cached_chunks.write().clear();
+		self.chunk_load_jobs.write().clear();
+		self.chunk_unload_jobs.write().clear();
+		self.repair_jobs.lock().clear();
+		self.finished_analyses.lock().clear();
+		self.pending_teleports.lock().clear();
+		*self.pregen.lock() = None;
+		self.player_positions.clear();
+		self.players.write().clear();
 	}
 
-	// TODO: refresh meshes of adjacent chunks when loading is finished
 	// TODO: handle unloading of chunks before they finish loading
 	// loads all chunks between min_chunk and max_chunk not including max_chunk,
 	// or incraments the load count if they are already loaded
-	pub fn load_chunks(&self, min_chunk: ChunkPos, max_chunk: ChunkPos, mesh_face_task: Option<ChunkMeshFaceData>) {
+	pub fn load_chunks(&self, min_chunk: ChunkPos, max_chunk: ChunkPos, mesh_face_tasks: Vec<ChunkMeshFaceData>) {
 		self.chunk_load_jobs.write().push(ChunkLoadJob {
-			min_chunk,
-			max_chunk,
-			remaining_chunks: ((max_chunk.x - min_chunk.x) * (max_chunk.y - min_chunk.y) * (max_chunk.z - min_chunk.z))
-				.try_into().unwrap(),
-			mesh_face_task,
+			chunks: ChunkBox::new(min_chunk, max_chunk),
+			remaining_chunks: ChunkBox::new(min_chunk, max_chunk).volume() as u64,
+			mesh_face_tasks,
+			requested: Instant::now(),
 		});
 
-		for x in min_chunk.x..max_chunk.x {
-			for y in min_chunk.y..max_chunk.y {
-				for z in min_chunk.z..max_chunk.z {
-					let position = ChunkPos::new(x, y, z);
+		for position in ChunkPos::iter_range(min_chunk, max_chunk) {
+			run_task(Task::GenerateChunk(position));
+		}
+	}
 
-					run_task(Task::GenerateChunk(position));
-				}
+	// mesh face tasks for the loaded chunks next to a newly generated chunk,
+	// whose borders facing it were meshed while it wasn't loaded, and are missing their faces
+	pub fn unmeshed_border_tasks(&self, chunk: ChunkPos) -> Vec<Task> {
+		let mut out = Vec::new();
+
+		for face in BlockFace::iter() {
+			let neighbor = chunk + ChunkPos(face.block_pos_offset().0);
+			let border_face = face.opposite();
+			let unmeshed = self.chunks.get(&neighbor)
+				.is_some_and(|neighbor| neighbor.chunk.has_unmeshed_border(border_face));
+
+			if unmeshed {
+				out.push(Task::ChunkMeshFace {
+					min_chunk: neighbor,
+					max_chunk: neighbor + ChunkPos::splat(1),
+					face: border_face,
+				});
 			}
 		}
+
+		out
 	}
 
 	// decraments the load counter of all chunks between min and max chunk, not including max
 	// and unloads them if the count reaches 0
 	// TODO: refresh meshes of adjacent chunks when unloading is finished
 	// TODO: handle unloading before loading is finished
-	pub fn unload_chunks(&self, min_chunk: ChunkPos, max_chunk: ChunkPos, mesh_face_task: Option<ChunkMeshFaceData>) {
-		self.chunk_load_jobs.write().push(ChunkLoadJob {
-			min_chunk,
-			max_chunk,
+	pub fn unload_chunks(&self, min_chunk: ChunkPos, max_chunk: ChunkPos, mesh_face_tasks: Vec<ChunkMeshFaceData>) {
+		self.chunk_unload_jobs.write().push(ChunkLoadJob {
+			chunks: ChunkBox::new(min_chunk, max_chunk),
 			remaining_chunks: 1,
-			mesh_face_task,
+			mesh_face_tasks,
+			requested: Instant::now(),
 		});
 
 		run_task(Task::UnloadChunks {
@@ -163,46 +553,217 @@ impl World {
 		});
 	}
 
-	// performs mesh updates on the passed in block as well as all adjacent blocks
+	// performs mesh updates on every layer whose mesh can change when the block changes
 	// NOTE: this is not multithreaded, it blocks the current thread until done
-	// FIXME: this doesn't update everything it needs to with ambient occlusion on chunk boundaries
-	pub fn mesh_update_adjacent(&self, block: BlockPos, updated_render_zones: &mut UpdatedRenderZones) {
-		let block_chunk_local = block.as_chunk_local();
+	pub fn mesh_update_adjacent(&self, block: BlockPos, updated_chunks: &mut UpdatedChunks) {
 		let mut visit_map = VisitedBlockMap::new();
+		let mut updated_meshes = SmallVec::<[ChunkPos; 8]>::new();
 
+		// the block's own faces, only the layers through the block in its own chunk have them
 		if let Some(chunk) = self.chunks.get(&block.as_chunk_pos()) {
-			chunk.chunk.mesh_update_inner(BlockFace::XPos, block_chunk_local.x as usize, &mut visit_map);
-			chunk.chunk.mesh_update_inner(BlockFace::XNeg, block_chunk_local.x as usize, &mut visit_map);
-			chunk.chunk.mesh_update_inner(BlockFace::YPos, block_chunk_local.y as usize, &mut visit_map);
-			chunk.chunk.mesh_update_inner(BlockFace::YNeg, block_chunk_local.y as usize, &mut visit_map);
-			chunk.chunk.mesh_update_inner(BlockFace::ZPos, block_chunk_local.z as usize, &mut visit_map);
-			chunk.chunk.mesh_update_inner(BlockFace::ZNeg, block_chunk_local.z as usize, &mut visit_map);
-			updated_render_zones.mark_block(block);
+			for face in BlockFace::iter() {
+				chunk.chunk.mesh_update_inner(face, block.as_chunk_local().get_face_component(face) as usize, &mut visit_map);
+			}
+			updated_chunks.mark_block(block);
+			updated_meshes.push(block.as_chunk_pos());
 		}
 
+		// faces of the adjacent blocks pointing towards the block, which may have become visible or hidden
+		// the block also changes the ambient occlusion of faces near these ones in the same layer, which can be in other chunks
 		for face in BlockFace::iter() {
 			// subtract to get opposite as normal offest
-			let offset_block = block - face.block_pos_offset();
-			if let Some(chunk) = self.chunks.get(&offset_block.as_chunk_pos()) {
-				updated_render_zones.mark_block(offset_block);
-				chunk.chunk.mesh_update_inner(
-					face,
-					offset_block.as_chunk_local().get_face_component(face) as usize,
-					&mut visit_map
-				);
+			let layer_block = block - face.block_pos_offset();
+			let layer = layer_block.as_chunk_local().get_face_component(face) as usize;
+
+			for chunk_pos in occlusion_layer_chunks(layer_block, face) {
+				if let Some(chunk) = self.chunks.get(&chunk_pos) {
+					chunk.chunk.mesh_update_inner(face, layer, &mut visit_map);
+					updated_chunks.mark_chunk(chunk_pos);
+					updated_meshes.push(chunk_pos);
+				}
+			}
+		}
+
+		// liquid covered by the same liquid is a full block, so the side faces of liquid below the block can change too
+		// when it is in the same chunk, those layers were already updated with the block's own faces
+		let below = block + BlockFace::YNeg.block_pos_offset();
+		if below.as_chunk_pos() != block.as_chunk_pos() && self.is_liquid_at(below) {
+			if let Some(chunk) = self.chunks.get(&below.as_chunk_pos()) {
+				for face in [BlockFace::XPos, BlockFace::XNeg, BlockFace::ZPos, BlockFace::ZNeg] {
+					chunk.chunk.mesh_update_inner(face, below.as_chunk_local().get_face_component(face) as usize, &mut visit_map);
+				}
+				updated_chunks.mark_block(below);
+				updated_meshes.push(below.as_chunk_pos());
+			}
+		}
+
+		updated_meshes.sort_by_key(|chunk| chunk.to_array());
+		updated_meshes.dedup();
+		for chunk_pos in updated_meshes {
+			self.validate_chunk_mesh(chunk_pos);
+		}
+	}
+
+	// remeshes the blocks changed by set_block
+	fn mesh_update_changed_blocks(&self, updated_chunks: &mut UpdatedChunks) {
+		let changed_blocks = mem::take(&mut *self.changed_blocks.lock());
+		for block in changed_blocks {
+			self.mesh_update_adjacent(block, updated_chunks);
+		}
+	}
+
+	pub fn smooth_lighting(&self) -> bool {
+		self.smooth_lighting.load(Ordering::Relaxed)
+	}
+
+	pub fn mesh_validation(&self) -> MeshValidation {
+		*self.mesh_validation.lock()
+	}
+
+	// only meshes updated after this are checked
+	pub fn set_mesh_validation(&self, mesh_validation: MeshValidation) {
+		*self.mesh_validation.lock() = mesh_validation;
+	}
+
+	// total number of mesh violations found since the world was opened
+	pub fn mesh_violation_count(&self) -> u64 {
+		self.mesh_violation_count.load(Ordering::Relaxed)
+	}
+
+	// called after the chunk's mesh is updated, logs every violation found by the mesh validation
+	pub fn validate_chunk_mesh(&self, chunk_pos: ChunkPos) {
+		let mesh_validation = self.mesh_validation();
+		if mesh_validation == MeshValidation::Off {
+			return;
+		}
+
+		let violations = match self.chunks.get(&chunk_pos) {
+			Some(chunk) => validate_chunk_mesh(&chunk.chunk, mesh_validation),
+			None => return,
+		};
+		for violation in violations.iter() {
+			warn!("mesh violation in chunk {:?} on {:?} face: {:?}", violation.chunk, violation.face, violation.violation);
+		}
+		self.mesh_violation_count.fetch_add(violations.len() as u64, Ordering::Relaxed);
+	}
+
+	// smooth lighting is applied when meshing, so every loaded chunk is remeshed when it changes
+	pub fn set_smooth_lighting(&self, smooth_lighting: bool) {
+		if self.smooth_lighting.swap(smooth_lighting, Ordering::Relaxed) != smooth_lighting {
+			for chunk in self.chunks.iter() {
+				run_task(Task::ChunkMesh(*chunk.key()));
 			}
 		}
 	}
 
 	pub fn chunk_mesh_update(&self, min_chunk: ChunkPos, max_chunk: ChunkPos) {
-		for x in min_chunk.x..max_chunk.x {
-			for y in min_chunk.y..max_chunk.y {
-				for z in min_chunk.z..max_chunk.z {
-					let chunk_pos = ChunkPos::new(x, y, z);
-					run_task(Task::ChunkMesh(chunk_pos));
-				}
+		for chunk_pos in ChunkPos::iter_range(min_chunk, max_chunk) {
+			run_task(Task::ChunkMesh(chunk_pos));
+		}
+	}
+
+	// meshes the whole chunk, and the layers of its loaded neighbors which face it
+	fn chunk_remesh_tasks(&self, chunk: ChunkPos) -> Vec<Task> {
+		let mut out = vec![Task::ChunkMesh(chunk)];
+
+		for face in BlockFace::iter() {
+			let neighbor = chunk + ChunkPos(face.block_pos_offset().0);
+			if self.chunks.contains_key(&neighbor) {
+				out.push(Task::ChunkMeshFace {
+					min_chunk: neighbor,
+					max_chunk: neighbor + ChunkPos::splat(1),
+					face: face.opposite(),
+				});
 			}
 		}
+
+		out
+	}
+
+	fn start_repair(&self, chunk: ChunkPos, repair: ChunkRepair) {
+		let tasks = self.chunk_remesh_tasks(chunk);
+		self.repair_jobs.lock().push(ChunkRepairJob {
+			chunk,
+			repair,
+			remaining_tasks: tasks.clone(),
+		});
+
+		for task in tasks {
+			run_task(task);
+		}
+	}
+
+	// remeshes the chunk and its neighbors' layers facing it, for when its mesh has gone wrong
+	// take_finished_repairs returns it once the mesh tasks have completed
+	pub fn remesh_chunk(&self, chunk: ChunkPos) -> Result<(), WorldError> {
+		if !self.chunks.contains_key(&chunk) {
+			return Err(WorldError::ChunkNotLoaded { chunk });
+		}
+
+		self.start_repair(chunk, ChunkRepair::Remesh);
+		Ok(())
+	}
+
+	// throws away every block and block entity in the chunk and generates it again, then remeshes it like remesh_chunk
+	// the chunk stays loaded the whole time, so its load count is kept
+	pub fn regenerate_chunk(&self, chunk: ChunkPos) -> Result<(), WorldError> {
+		let not_loaded = WorldError::ChunkNotLoaded { chunk };
+		if !self.chunks.contains_key(&chunk) {
+			return Err(not_loaded);
+		}
+
+		let world = self.self_weak.upgrade().ok_or(not_loaded)?;
+		// generated before the chunk map is locked, so the other chunks in its shard can be used meanwhile
+		let generated_chunk = self.world_generator.generate_chunk(world, chunk).chunk;
+		// marked as modified, so the saved copy which is being thrown away is replaced the next time the chunk is saved
+		generated_chunk.mark_modified();
+		self.chunks.get_mut(&chunk).ok_or(not_loaded)?.chunk = generated_chunk;
+		// the blocks they were scheduled for are gone
+		self.block_updates.lock().remove_chunk(chunk);
+
+		self.start_repair(chunk, ChunkRepair::Regenerate);
+		Ok(())
+	}
+
+	// every repair job waiting on the task stops waiting on it, and the jobs left with nothing to wait on are finished
+	fn repair_task_completed(&self, task: &Task) {
+		let mut repair_jobs = self.repair_jobs.lock();
+		if repair_jobs.is_empty() {
+			return;
+		}
+
+		let mut finished_repairs = self.finished_repairs.lock();
+		repair_jobs.retain_mut(|job| {
+			if let Some(i) = job.remaining_tasks.iter().position(|remaining_task| remaining_task == task) {
+				job.remaining_tasks.swap_remove(i);
+			}
+
+			if job.remaining_tasks.is_empty() {
+				finished_repairs.push((job.chunk, job.repair));
+				false
+			} else {
+				true
+			}
+		});
+	}
+
+	// chunks whose remesh or regeneration has finished since the last call
+	pub fn take_finished_repairs(&self) -> Vec<(ChunkPos, ChunkRepair)> {
+		mem::take(&mut *self.finished_repairs.lock())
+	}
+
+	// counts the blocks of each type in the loaded chunks in area, or in every loaded chunk if there is no area
+	// this is done in the background, take_finished_analyses returns the counts once they are done
+	pub fn analyze_blocks(&self, area: Option<ChunkBox>) {
+		run_background_task(Task::Analyze {
+			area,
+			stats: None,
+		});
+	}
+
+	// the stats are none if the analysis failed
+	pub fn take_finished_analyses(&self) -> Vec<Option<BlockStats>> {
+		mem::take(&mut *self.finished_analyses.lock())
 	}
 
 	#[inline]
@@ -225,22 +786,247 @@ impl World {
 			.chunk.get_block_mut(block.as_chunk_local())))
 	}
 
-	// sets the block at BlockPos, returns bool on success
-	pub fn set_block(&self, block_pos: BlockPos, block: Block) -> bool {
-		let (chunk_pos, block_pos) = block_pos.as_chunk_block_pos();
+	// returns false if the block is not loaded
+	pub fn is_liquid_at(&self, block: BlockPos) -> bool {
+		self.with_block(block, |block| block.is_liquid()).unwrap_or(false)
+	}
+
+	// if the block can be stood on, returns false if the block is not loaded
+	pub fn is_solid_at(&self, block: BlockPos) -> bool {
+		self.with_block(block, |block| !block.is_air() && !block.is_liquid()).unwrap_or(false)
+	}
+
+	// height of the liquid's surface in the block, 0 if the block has no liquid or is not loaded
+	pub fn liquid_height_at(&self, block: BlockPos) -> f32 {
+		self.with_block(block, |block| {
+			if block.is_liquid() {
+				block.model_height()
+			} else {
+				0.0
+			}
+		}).unwrap_or(0.0)
+	}
+
+	pub fn raycast_count(&self) -> u64 {
+		self.raycast_count.load(Ordering::Relaxed)
+	}
+
+	// returns a copy of the block, or None if it is not loaded
+	pub fn block_at(&self, block: BlockPos) -> Option<Block> {
+		self.with_block(block, Block::clone)
+	}
+
+	// returns the type of the block, or None if it is not loaded
+	pub fn block_type_at(&self, block: BlockPos) -> Option<BlockType> {
+		self.with_block(block, |block| block.block_type())
+	}
+
+	// sets the block at BlockPos, fails if its chunk isn't loaded
+	// the block is remeshed the next time completed tasks are polled,
+	// and its neighbors are told it changed on the next tick
+	pub fn set_block(&self, block_pos: BlockPos, block: Block) -> Result<(), WorldError> {
+		let (chunk_pos, local_block_pos) = block_pos.as_chunk_block_pos();
+
+		let placed_block = block.clone();
+		let old_block = match self.chunks.get(&chunk_pos) {
+			Some(chunk) => mem::replace(&mut *chunk.chunk.get_block_mut(local_block_pos), block),
+			None => return Err(WorldError::ChunkNotLoaded { chunk: chunk_pos }),
+		};
+
+		// an opaque block replaced with another opaque block, surrounded by opaque blocks, has no visible faces before or after,
+		// and doesn't change the ambient occlusion of anything
+		let hidden = !old_block.is_translucent()
+			&& !placed_block.is_translucent()
+			&& BlockFace::iter().all(|face| {
+				self.with_block(block_pos + face.block_pos_offset(), |block| !block.is_translucent()).unwrap_or(false)
+			});
+		if !hidden {
+			let mut changed_blocks = self.changed_blocks.lock();
+			if changed_blocks.len() < MAX_CHANGED_BLOCKS {
+				changed_blocks.push(block_pos);
+			}
+			self.pending_edit.lock().get_or_insert((block_pos, Instant::now()));
+		}
 
+		// the old block's data goes with it, and whatever it held is dropped where it was
 		if let Some(chunk) = self.chunks.get(&chunk_pos) {
-			chunk.chunk.set_block(block_pos, block);
-			true
-		} else {
-			false
+			let old_block_entity = chunk.chunk.remove_block_entity(local_block_pos);
+			let ticking = match placed_block.new_block_entity() {
+				Some(block_entity) => {
+					let ticking = block_entity.is_ticking();
+					chunk.chunk.set_block_entity(local_block_pos, block_entity);
+					ticking
+				},
+				None => false,
+			};
+			drop(chunk);
+			if ticking {
+				self.track_ticking_chunk(chunk_pos);
+			}
+
+			let drop_position = block_pos.as_position() + Position::new(0.5, 0.5, 0.5);
+			for (block_type, count) in old_block_entity.iter().flat_map(BlockEntity::drops) {
+				self.add_entity(Box::new(Item::new(drop_position, block_type, count)));
+			}
+		}
+
+		// the chunk is no longer locked here, so the hooks can change other blocks
+		old_block.on_break(self, block_pos);
+		placed_block.on_place(self, block_pos);
+
+		let depth = NEIGHBOR_UPDATE_DEPTH.with(Cell::get) + 1;
+		let mut neighbor_updates = self.neighbor_updates.lock();
+		for face in BlockFace::iter() {
+			let queued = neighbor_updates.push(NeighborUpdate {
+				block: block_pos + face.block_pos_offset(),
+				changed: block_pos,
+				depth,
+			});
+			if !queued {
+				warn!("neighbor updates from changing {:?} were dropped, too many neighbor changed hooks caused each other", block_pos);
+				break;
+			}
+		}
+
+		Ok(())
+	}
+
+	// calls the function on the block entity at the block, none if there isn't one or its chunk isn't loaded
+	pub fn with_block_entity_mut<T, F>(&self, block_pos: BlockPos, f: F) -> Option<T>
+		where F: FnOnce(&mut BlockEntity) -> T {
+		let (chunk_pos, local_block_pos) = block_pos.as_chunk_block_pos();
+		self.chunks.get(&chunk_pos)?.chunk.with_block_entity_mut(local_block_pos, f)
+	}
+
+	// the chunk's entities are taken out of the world and written to its region with it
+	// chunks which didn't change and have no block entities or entities are dropped,
+	// they are read from their region or generated again when they are loaded
+	// if the chunk can't be written it is kept in memory, until it is loaded again or save_all writes it
+	pub(super) fn save_unloaded_chunk(&self, chunk_pos: ChunkPos, chunk: Chunk) {
+		let entities = self.take_chunk_entities(chunk_pos);
+		if !chunk.take_modified() && !chunk.has_block_entities() && entities.is_empty() {
+			return;
+		}
+
+		let chunk_data = ChunkData::new(chunk, entities);
+		if let Err(error) = self.regions.write_chunk(chunk_pos, &chunk_data.encode()) {
+			warn!("could not save unloaded chunk {:?}, keeping it in memory: {:#}", chunk_pos.0, error);
+			self.cached_chunks.write().insert(chunk_pos, chunk_data);
+		}
+	}
+
+	// the region files the world's chunks are saved in
+	pub fn regions(&self) -> &RegionCache {
+		&self.regions
+	}
+
+	// the chunk saved in the world's regions, none if it isn't saved or can't be read, so it is generated instead
+	pub fn load_saved_chunk(&self, chunk_pos: ChunkPos) -> Option<SavedChunk> {
+		let world = self.self_weak.upgrade()?;
+		let bytes = match self.regions.read_chunk(chunk_pos) {
+			Ok(bytes) => bytes?,
+			Err(error) => {
+				warn!("could not read chunk {:?}, generating it instead: {:#}", chunk_pos, error);
+				return None;
+			},
+		};
+
+		match ChunkData::decode(world, chunk_pos, &bytes) {
+			Ok(chunk_data) => Some(chunk_data.into_parts()),
+			Err(error) => {
+				warn!("could not load chunk {:?}, generating it instead: {:#}", chunk_pos, error);
+				None
+			},
+		}
+	}
+
+	// generates the chunk and saves it to its region without loading it
+	// loaded, cached and saved chunks are skipped, so nothing is replaced with freshly generated terrain,
+	// and pregenerating an area again after quitting part way through only generates what is missing
+	// the chunk can be loaded, changed and saved while it is generated, so it is only written if it still isn't saved
+	pub(super) fn pregenerate_chunk(&self, chunk_pos: ChunkPos) {
+		if self.chunks.contains_key(&chunk_pos) || self.cached_chunks.read().contains_key(&chunk_pos) {
+			return;
+		}
+		match self.regions.contains_chunk(chunk_pos) {
+			Ok(false) => (),
+			Ok(true) => return,
+			Err(error) => {
+				warn!("could not check if chunk {:?} is saved, it won't be pregenerated: {:#}", chunk_pos, error);
+				return;
+			},
+		}
+
+		let world = match self.self_weak.upgrade() {
+			Some(world) => world,
+			None => return,
+		};
+		let chunk = self.world_generator.generate_chunk(world, chunk_pos).chunk;
+		// each chunk is written whole, so quitting part way through leaves every chunk either saved or not
+		if let Err(error) = self.regions.write_new_chunk(chunk_pos, &ChunkData::new(chunk, Vec::new()).encode()) {
+			warn!("could not save pregenerated chunk {:?}: {:#}", chunk_pos, error);
+		}
+	}
+
+	// removes the chunk from the cache, so it can be loaded again
+	// its entities have to be given to restore_entities once the chunk is back in the world,
+	// that can't be done here since the chunk map may be locked, and it is always locked after entities
+	pub(super) fn take_cached_chunk(&self, chunk_pos: ChunkPos) -> Option<SavedChunk> {
+		Some(self.cached_chunks.write().remove(&chunk_pos)?.into_parts())
+	}
+
+	pub fn neighbor_update_count(&self) -> usize {
+		self.neighbor_updates.lock().len()
+	}
+
+	// neighbor updates dropped this session for being too deep, see MAX_NEIGHBOR_UPDATE_DEPTH
+	pub fn dropped_neighbor_update_count(&self) -> u64 {
+		self.neighbor_updates.lock().dropped_count()
+	}
+
+	// runs the neighbor changed hooks of blocks next to blocks that changed,
+	// including changes made by the hooks, until there are none left or the per tick limit is reached
+	fn run_neighbor_updates(&self) {
+		for _ in 0..MAX_NEIGHBOR_UPDATES_PER_TICK {
+			// the queue can't be locked while the hook runs, since it may change blocks
+			let update = match self.neighbor_updates.lock().pop() {
+				Some(update) => update,
+				None => break,
+			};
+
+			if let Some(block) = self.block_at(update.block) {
+				NEIGHBOR_UPDATE_DEPTH.with(|depth| depth.set(update.depth));
+				block.on_neighbor_changed(self, update.block, update.changed);
+				NEIGHBOR_UPDATE_DEPTH.with(|depth| depth.set(0));
+			}
+		}
+	}
+
+	// runs the block's scheduled_update after the given number of ticks
+	pub fn schedule_update(&self, block: BlockPos, delay_ticks: u64) {
+		self.block_updates.lock().schedule(block, self.current_tick() + delay_ticks);
+	}
+
+	pub fn scheduled_update_count(&self) -> usize {
+		self.block_updates.lock().len()
+	}
+
+	fn run_scheduled_updates(&self, tick_number: u64) {
+		let due_updates = self.block_updates.lock().pop_due(tick_number, MAX_BLOCK_UPDATES_PER_TICK);
+
+		for block_pos in due_updates {
+			if let Some(block) = self.block_at(block_pos) {
+				block.scheduled_update(self, block_pos);
+			}
 		}
 	}
 
 	// casts a ray starting at ray_start up to a length of max_length
-	// if a block other than air is found, the coordinates are returned, otherwise None is returned
+	// if the ray hits one of the selection boxes of a block other than air, the hit is returned, otherwise None is returned
 	// if the ray ever intersects with an empty chunk, None is returned
-	pub fn block_raycast(&self, ray_start: Position, ray: Vec3, max_length: f32) -> Option<BlockPos> {
+	pub fn block_raycast(&self, ray_start: Position, ray: Vec3, max_length: f32) -> Option<RaycastHit> {
+		self.raycast_count.fetch_add(1, Ordering::Relaxed);
+
 		let ray = ray.normalize();
 		let block_start_pos = ray_start.as_block_pos();
 		let mut block_pos = block_start_pos;
@@ -275,12 +1061,30 @@ impl World {
 
 		let mut incrament_axis = |next_intercept_time: &mut Vec3, axis: Axis| {
 			block_pos[axis] += direction[axis];
-			if (block_pos - block_start_pos).length() > max_length {
+			if (block_pos - block_start_pos).length_squared() as f32 > max_length * max_length {
 				return Some(None);
 			}
 
-			if !self.with_block(block_pos, |b| b.is_air())? {
-				return Some(Some(block_pos));
+			let selection_aabbs = self.with_block(block_pos, |b| (!b.is_air()).then(|| b.selection_aabbs()))?;
+
+			// the ray can pass through the empty parts of blocks which aren't full cubes, so only the nearest of the block's boxes it hits counts
+			let hit = selection_aabbs.into_iter().flatten()
+				.map(|aabb| Aabb::new(aabb.position + block_pos.as_position().0, aabb.size))
+				.filter_map(|aabb| {
+					// the ray is normalized, so the time it enters the box is the distance
+					let (distance, hit_axis) = aabb.ray_intersection(ray_start.0, ray)?;
+					Some(RaycastHit {
+						block: block_pos,
+						// the ray enters through the face pointing back towards where it came from
+						face: BlockFace::from_axis(hit_axis, direction[hit_axis] < 0),
+						distance,
+						selection: aabb,
+					})
+				})
+				.min_by(|a, b| a.distance.total_cmp(&b.distance));
+
+			if hit.is_some() {
+				return Some(hit);
 			}
 
 			next_intercept_time[axis] += intercept_time_interval[axis];
@@ -302,158 +1106,724 @@ impl World {
 		}
 	}
 
-	// called by the client to force the world to recieve task completion notices
-	// returns true if the mesh should be updated by the client
-	pub fn poll_completed_tasks(&self, updated_render_zones: &mut UpdatedRenderZones) {
-		while let Some(task) = pull_completed_task() {
-			match task {
-				Task::ChunkMesh(chunk) => {
-					updated_render_zones.mark_chunk(chunk);
-				},
-				Task::ChunkMeshFace { min_chunk, max_chunk, .. } => {
-					updated_render_zones.mark_chunk_zone(min_chunk, max_chunk);
-				},
-				Task::GenerateChunk(chunk) => {
-					let mut load_jobs = self.chunk_load_jobs.write();
-
-					let mut drain_iter = load_jobs.drain_filter(|job| {
-						// find out if the chunk is part of this job
-						if job.contains_chunk(chunk) {
-							job.remaining_chunks -= 1;
-							// remove the job if there are no more remaining chunks to temove
-							job.remaining_chunks == 0
-						} else {
-							false
-						}
-					});
-
-					if let Some(finished_job) = drain_iter.next() {
-						self.chunk_mesh_update(finished_job.min_chunk, finished_job.max_chunk);
-						if let Some(mesh_face_task) = finished_job.mesh_face_task {
-							run_task(mesh_face_task.into_task());
-						}
-					}
-				},
-				Task::UnloadChunks { min_chunk, max_chunk } => {
-					// recreate mesh because chunks have been removed, but we don't actually have to generate their meshes
-					updated_render_zones.mark_chunk_zone(min_chunk, max_chunk);
+	pub fn biome_noise_at(&self, block: BlockPos) -> BiomeNoiseData {
+		self.world_generator.biome_noise_at(block)
+	}
 
-					let mut unload_jobs = self.chunk_unload_jobs.write();
+	pub fn surface_biome_at(&self, block: BlockPos) -> &'static SurfaceBiome {
+		self.world_generator.surface_biome_at(block)
+	}
 
-					let mut drain_iter = unload_jobs.drain_filter(|job| {
-						// find out if the chunk is part of this job
-						job.min_chunk == min_chunk && job.max_chunk == max_chunk
-					});
+	pub fn underground_biome_at(&self, block: BlockPos) -> UndergroundBiome {
+		self.world_generator.underground_biome_at(block)
+	}
 
-					if let Some(finished_job) = drain_iter.next() {
-						if let Some(mesh_face_task) = finished_job.mesh_face_task {
-							run_task(mesh_face_task.into_task());
-						}
-					}
-				}
-			}
-		}
+	// y coordinate of the top solid block of the naturally generated terrain at x and z
+	// this does not require the chunk to be generated, so it won't reflect changes made to the world
+	pub fn surface_height(&self, x: i32, z: i32) -> i32 {
+		self.world_generator.surface_column(x, z).height
 	}
-}
 
-impl World {
-	pub fn connect(&self) -> PlayerId {
-		let player = Player::new();
+	pub fn surface_biome(&self, x: i32, z: i32) -> &'static SurfaceBiome {
+		self.world_generator.surface_column(x, z).biome
+	}
+
+	// a generator for the same terrain as this world's, which doesn't share its caches
+	// used by long running queries, so they don't keep the world alive
+	pub fn new_generator(&self) -> WorldGenerator {
+		WorldGenerator::new(self.world_generator.seed(), self.world_generator.generator_type(), self.world_generator.options())
+	}
+
+	// colors of the heat / humidity biome diagram, see SurfaceBiomeMap::diagram_colors
+	pub fn biome_diagram_colors(&self) -> Vec<[u8; 3]> {
+		self.world_generator.surface_biome_map().diagram_colors()
+	}
+
+	// how long each chunk load job that finished since the last call took, from being requested to its chunks being generated
+	pub fn take_chunk_load_latencies(&self) -> Vec<Duration> {
+		mem::take(&mut *self.chunk_load_latencies.lock())
+	}
+
+	// the oldest visible block edit since the last call, and when set_block made it
+	pub fn take_pending_edit(&self) -> Option<(BlockPos, Instant)> {
+		self.pending_edit.lock().take()
+	}
+
+	// called by the client to force the world to recieve task completion notices
+	// returns true if the mesh should be updated by the client
+	pub fn poll_completed_tasks(&self, updated_chunks: &mut UpdatedChunks) {
+		self.mesh_update_changed_blocks(updated_chunks);
+
+		while let Some(task) = pull_completed_task() {
+			self.task_completed(task, updated_chunks);
+		}
+
+		// failed tasks count as completed so jobs waiting on them can still finish,
+		// a failed chunk generation will just leave the chunk missing
+		while let Some(task) = pull_failed_task() {
+			self.task_completed(task, updated_chunks);
+		}
+
+		self.finish_teleports();
+	}
+
+	fn task_completed(&self, task: Task, updated_chunks: &mut UpdatedChunks) {
+		self.repair_task_completed(&task);
+
+		match task {
+			Task::ChunkMesh(chunk) => {
+				updated_chunks.mark_chunk(chunk);
+			},
+			Task::ChunkMeshFace { min_chunk, max_chunk, .. } => {
+				updated_chunks.mark_chunk_zone(min_chunk, max_chunk);
+			},
+			Task::GenerateChunk(chunk) => {
+				for task in self.unmeshed_border_tasks(chunk) {
+					run_task(task);
+				}
+
+				// overlapping jobs can finish on the same chunk, so every finished job is taken before running their follow ups
+				let finished_jobs = remove_jobs(&mut self.chunk_load_jobs.write(), |job| {
+					// find out if the chunk is part of this job
+					if job.chunks.contains(chunk) {
+						job.remaining_chunks -= 1;
+						// remove the job if there are no more remaining chunks to load
+						job.remaining_chunks == 0
+					} else {
+						false
+					}
+				});
+
+				for finished_job in finished_jobs {
+					self.chunk_load_latencies.lock().push(finished_job.requested.elapsed());
+					self.chunk_mesh_update(finished_job.chunks.min, finished_job.chunks.max);
+					for mesh_face_task in finished_job.mesh_face_tasks.iter() {
+						run_task(mesh_face_task.into_task());
+					}
+				}
+			},
+			Task::PregenerateChunk(_) => {
+				let mut pregen = self.pregen.lock();
+				if let Some(job) = &mut *pregen {
+					job.done += 1;
+					if job.done >= job.total {
+						info!("pregenerated {} chunks in {:.1?}", job.total, job.started.elapsed());
+						*pregen = None;
+					}
+				}
+			},
+			Task::UnloadChunks { min_chunk, max_chunk } => {
+				// recreate mesh because chunks have been removed, but we don't actually have to generate their meshes
+				updated_chunks.mark_chunk_zone(min_chunk, max_chunk);
+
+				// each unload task belongs to one job, if the same box is unloaded twice the other job waits for its own task
+				let finished_job = {
+					let mut unload_jobs = self.chunk_unload_jobs.write();
+					unload_jobs.iter()
+						.position(|job| job.chunks == ChunkBox::new(min_chunk, max_chunk))
+						.map(|i| unload_jobs.remove(i))
+				};
+
+				if let Some(finished_job) = finished_job {
+					for mesh_face_task in finished_job.mesh_face_tasks.iter() {
+						run_task(mesh_face_task.into_task());
+					}
+				}
+			},
+			Task::Analyze { stats, .. } => {
+				// a failed analysis has no stats, it is still returned so whoever asked for it gets an answer
+				self.finished_analyses.lock().push(stats.map(|stats| *stats));
+			},
+			#[cfg(test)]
+			Task::TestPanic(_) => (),
+		}
+	}
+}
+
+impl World {
+	// the player's saved state is loaded using their name, and chunks are loaded around their saved position
+	// players who haven't been in the world before start with the default state
+	pub fn connect(&self, name: &str) -> (PlayerId, PlayerState) {
+		let state = match PlayerState::load(&self.player_file(name)) {
+			Ok(state) => state.unwrap_or_default(),
+			Err(error) => {
+				warn!("could not load player {}, they will start at spawn: {:#}", name, error);
+				PlayerState::default()
+			},
+		};
+		let player = Player::with_state(name.to_owned(), state.clone());
+
+		let render_zone = self.render_zone(state.position.as_chunk_pos(), player.render_distance());
+		self.load_chunks(render_zone.min, render_zone.max, Vec::new());
 
-		let min_load_chunk = player.chunk_position() - player.render_distance();
-		let max_load_chunk = player.chunk_position() + player.render_distance();
-		self.load_chunks(min_load_chunk, max_load_chunk, None);
+		let id = player.id();
+		self.add_player(player, state.position);
+		(id, state)
+	}
 
+	// the player's chunks must already be loaded around position
+	fn add_player(&self, player: Player, position: Position) {
 		let id = player.id();
+		let render_box = self.render_zone(position.as_chunk_pos(), player.render_distance());
+		let player_position = PlayerPosition::new(&player, position, render_box);
 		self.players.write().insert(id, player);
-		id
+		self.player_positions.insert(id, player_position);
 	}
 
-	// TODO: allow changing from more than 1 chunk at at a time
-	// TODO: when going along diaganols, sometimes chunks are loaded and immediately unloaded
+	// saves the player's state and removes them from the world
+	// their chunks stay loaded until the world is closed
+	pub fn disconnect(&self, player_id: PlayerId) -> Result<()> {
+		let position = match self.player_positions.remove(&player_id) {
+			Some((_, position)) => position.get(),
+			None => return Ok(()),
+		};
+		let player = self.players.write().remove(&player_id);
+		match player {
+			Some(player) => self.save_player(&player, position),
+			None => Ok(()),
+		}
+	}
+
+	// doesn't lock the player map, so it is cheap to call often from any thread
+	pub fn player_position(&self, player_id: PlayerId) -> Option<Position> {
+		Some(self.player_positions.get(&player_id)?.get())
+	}
+
+	// everything which would be saved for the player right now
+	pub fn player_state(&self, player_id: PlayerId) -> Option<PlayerState> {
+		let position = self.player_position(player_id)?;
+		Some(PlayerState {
+			position,
+			..self.players.read().get(&player_id)?.state()
+		})
+	}
+
+	// the player's state is saved when the world is saved, and when they disconnect
+	// the position in the state isn't used, players are moved with update_player_position
+	pub fn update_player_state<F: FnOnce(&mut PlayerState)>(&self, player_id: PlayerId, f: F) {
+		if let Some(player) = self.players.read().get(&player_id) {
+			player.update_state(f);
+		}
+	}
+
+	pub fn player_health(&self, player_id: PlayerId) -> Option<f32> {
+		Some(self.players.read().get(&player_id)?.health())
+	}
+
+	// damage is ignored in creative, players who die are moved to their spawn point
+	pub fn damage_player(&self, player_id: PlayerId, amount: f32) -> Option<DamageOutcome> {
+		let outcome = self.players.read().get(&player_id)?.damage(amount)?;
+		if let DamageOutcome::Died { respawn_point } = outcome {
+			if let Err(error) = self.update_player_position(player_id, respawn_point) {
+				warn!("could not respawn player: {}", error);
+			}
+		}
+		Some(outcome)
+	}
+
+	// impact_speed is how fast the player was falling when they hit the ground, in blocks / second
+	pub fn player_landed(&self, player_id: PlayerId, impact_speed: f32) -> Option<DamageOutcome> {
+		self.damage_player(player_id, fall_damage(impact_speed))
+	}
+
+	// goes through every chunk, so this shouldn't be called often
+	pub fn memory_stats(&self) -> WorldMemoryStats {
+		let mut stats = WorldMemoryStats::default();
+
+		for chunk in self.chunks.iter() {
+			stats.loaded_chunks += 1;
+			stats.block_bytes += CHUNK_BLOCK_BYTES;
+			stats.mesh_bytes += chunk.chunk.mesh_bytes();
+		}
+
+		let cached_chunks = self.cached_chunks.read();
+		stats.cached_chunks = cached_chunks.len();
+		stats.cached_bytes = cached_chunks.values().map(ChunkData::memory_bytes).sum();
+
+		stats
+	}
+
+	// number of chunks within the player's render distance which are loaded, and the total number of chunks in it
+	pub fn player_load_progress(&self, player_id: PlayerId) -> Option<(usize, usize)> {
+		let player_position = self.player_positions.get(&player_id)?;
+		let render_zone = self.render_zone(player_position.get().as_chunk_pos(), player_position.render_distance());
+		drop(player_position);
+
+		let loaded = render_zone.iter()
+			.filter(|chunk| self.chunks.contains_key(chunk))
+			.count();
+
+		Some((loaded, render_zone.volume()))
+	}
+
+	// the chunks loaded for a player in chunk
+	// the surface is found at the middle of the chunk's column, so the zone only changes when the player moves to another chunk
+	fn render_zone(&self, chunk: ChunkPos, render_distance: RenderDistance) -> ChunkBox {
+		let column = BlockPos::from(chunk) + BlockPos::new(CHUNK_SIZE as i32 / 2, 0, CHUNK_SIZE as i32 / 2);
+		let surface_height = self.surface_height(column.x, column.z);
+		let surface_chunk = BlockPos::new(column.x, surface_height, column.z).as_chunk_pos();
+		render_distance.zone_around(chunk, surface_chunk.y)
+	}
+
+	// moving any distance works, including teleporting, moving more than 1 chunk just loads and unloads larger regions
 	// TEMP: returns true if mesh has changed
-	pub fn set_player_position(&self, player_id: PlayerId, position: Position) -> Option<bool> {
-		let mut players = self.players.write();
-		let player = players.get_mut(&player_id)?;
+	// only the player's position is locked, so this never waits on the player map or blocks readers of other players
+	pub fn update_player_position(&self, player_id: PlayerId, position: Position) -> Result<bool, WorldError> {
+		let player = self.player_positions.get(&player_id)
+			.ok_or(WorldError::PlayerNotFound { player: player_id })?;
+		// held until the end so concurrent moves of the same player are applied in order
+		let mut player_position = player.lock();
 
 		let chunk_position = position.as_chunk_pos();
+		let old_chunk_position = player_position.as_chunk_pos();
 
-		let render_zone_corner = player.chunk_position() - player.render_distance();
-		let render_zone_length = 2 * player.render_distance();
+		// most updates don't change which chunk the player is in, so nothing needs to be loaded
+		if chunk_position == old_chunk_position {
+			*player_position = position;
+			return Ok(false);
+		}
 
-		let load_axis = |axis: Axis| {
-			if chunk_position[axis] == player.chunk_position()[axis] {
-				return;
-			}
+		let old_zone = self.render_zone(old_chunk_position, player.render_distance());
+		let new_zone = self.render_zone(chunk_position, player.render_distance());
 
-			let axis_vec = ChunkPos(IVec3::axis(axis));
+		for unloaded in old_zone.difference(new_zone) {
+			self.unload_chunks(unloaded.min, unloaded.max, Vec::new());
+		}
 
-			let pos_min_chunk = render_zone_corner + render_zone_length.axis_only(axis);
-			let pos_max_chunk = render_zone_corner + render_zone_length + axis_vec;
+		for loaded in new_zone.difference(old_zone) {
+			let mesh_face_tasks = seam_mesh_face_tasks(loaded.min, loaded.max, new_zone.min, new_zone.max);
+			self.load_chunks(loaded.min, loaded.max, mesh_face_tasks);
+		}
 
-			let neg_min_chunk = render_zone_corner - axis_vec;
-			let neg_max_chunk = render_zone_corner + render_zone_length.all_but_axis(axis);
+		player.set_render_box(new_zone);
+		*player_position = position;
+		Ok(true)
+	}
 
-			if chunk_position[axis] == player.chunk_position()[axis] + 1 {
-				let neg_min_chunk = neg_min_chunk + axis_vec;
-				let neg_max_chunk = neg_max_chunk + axis_vec;
+	// false once no player needs the chunk, because it is outside of every player's render box and teleport destination,
+	// so it doesn't have to be meshed
+	// while nobody is in the world there are no render boxes to go by, so every chunk is needed
+	pub fn chunk_needed(&self, chunk: ChunkPos) -> bool {
+		if self.player_positions.is_empty() {
+			return true;
+		}
 
-				self.unload_chunks(neg_min_chunk, neg_max_chunk, None);
+		// the player map isn't locked while the teleports are, finish_teleports locks them the other way around
+		let in_render_box = self.player_positions.iter().any(|player| player.render_box().contains(chunk));
+		in_render_box || self.pending_teleports.lock().values().any(|teleport| teleport.zone.contains(chunk))
+	}
 
-				let load_face_job = ChunkMeshFaceData {
-					face: BlockFace::from_axis(axis, true),
-					min_chunk: pos_min_chunk - axis_vec,
-					max_chunk: pos_max_chunk - axis_vec,
-				};
+	// loads the render zone around destination, and moves the player there once the chunks right around it are generated,
+	// so they are never put in chunks which don't exist yet, the chunks around where they were are unloaded then
+	// teleporting again before then replaces the pending teleport
+	pub fn teleport_player(&self, player_id: PlayerId, destination: Position) -> Result<(), WorldError> {
+		let render_distance = self.player_positions.get(&player_id)
+			.ok_or(WorldError::PlayerNotFound { player: player_id })?
+			.render_distance();
+
+		let zone = self.render_zone(destination.as_chunk_pos(), render_distance);
+		self.load_chunks(zone.min, zone.max, Vec::new());
+
+		let replaced = self.pending_teleports.lock().insert(player_id, PendingTeleport {
+			destination,
+			zone,
+		});
+		if let Some(replaced) = replaced {
+			self.unload_chunks(replaced.zone.min, replaced.zone.max, Vec::new());
+		}
+		Ok(())
+	}
+
+	// number of chunks around the destination of the player's teleport which are done, and the total number of them
+	// none if the player isn't teleporting
+	pub fn teleport_progress(&self, player_id: PlayerId) -> Option<(usize, usize)> {
+		let teleport = *self.pending_teleports.lock().get(&player_id)?;
+		Some(self.teleport_core_progress(teleport.destination))
+	}
+
+	// generates the chunks within radius of spawn in the background and saves them, so loading them later is fast
+	// background tasks only run when there are no other tasks, so this never slows down loading chunks around players
+	// returns the number of chunks queued, or none if chunks are already being pregenerated
+	pub fn start_pregen(&self, radius: i32) -> Option<usize> {
+		let mut pregen = self.pregen.lock();
+		if pregen.is_some() {
+			return None;
+		}
+
+		let spawn = DEFAULT_SPAWN_POINT.as_chunk_pos();
+		let render_distance = RenderDistance {
+			horizontal: radius,
+			..RenderDistance::default()
+		};
+		// nearest columns first, so the area around spawn is done first
+		let mut chunks = self.render_zone(spawn, render_distance).iter().collect::<Vec<_>>();
+		chunks.sort_by_key(|chunk| (chunk.x - spawn.x).abs().max((chunk.z - spawn.z).abs()));
+
+		if chunks.is_empty() {
+			return Some(0);
+		}
+		for chunk in chunks.iter() {
+			run_background_task(Task::PregenerateChunk(*chunk));
+		}
+		*pregen = Some(PregenJob {
+			total: chunks.len(),
+			done: 0,
+			started: Instant::now(),
+		});
+		Some(chunks.len())
+	}
+
+	// none if nothing is being pregenerated
+	pub fn pregen_progress(&self) -> Option<PregenProgress> {
+		let job = (*self.pregen.lock())?;
+		// the rest of the chunks are assumed to take as long as the ones done so far
+		let time_left = (job.done > 0)
+			.then(|| job.started.elapsed().mul_f64((job.total - job.done) as f64 / job.done as f64));
+
+		Some(PregenProgress {
+			done: job.done,
+			total: job.total,
+			time_left,
+		})
+	}
+
+	// chunks which failed to generate count as done, since no job is loading them anymore
+	fn teleport_core_progress(&self, destination: Position) -> (usize, usize) {
+		let core = teleport_core(destination);
+		let load_jobs = self.chunk_load_jobs.read();
 
-				self.load_chunks(pos_min_chunk, pos_max_chunk, Some(load_face_job));
-			} else if chunk_position[axis] == player.chunk_position()[axis] - 1 {
-				let pos_min_chunk = pos_min_chunk - axis_vec;
-				let pos_max_chunk = pos_max_chunk - axis_vec;
+		let done = core.iter()
+			.filter(|chunk| self.chunks.contains_key(chunk) || !load_jobs.iter().any(|job| job.chunks.contains(*chunk)))
+			.count();
+		(done, core.volume())
+	}
 
-				self.unload_chunks(pos_min_chunk, pos_max_chunk, None);
+	// moves the players whose teleport destination is ready
+	fn finish_teleports(&self) {
+		let mut pending_teleports = self.pending_teleports.lock();
 
-				let load_face_job = ChunkMeshFaceData {
-					face: BlockFace::from_axis(axis, false),
-					min_chunk: neg_min_chunk + axis_vec,
-					max_chunk: neg_max_chunk + axis_vec,
+		pending_teleports.retain(|player_id, teleport| {
+			let (done, total) = self.teleport_core_progress(teleport.destination);
+			if done < total {
+				return true;
+			}
+
+			match self.player_positions.get(player_id) {
+				Some(player) => {
+					let mut player_position = player.lock();
+					let old_zone = self.render_zone(player_position.as_chunk_pos(), player.render_distance());
+					self.unload_chunks(old_zone.min, old_zone.max, Vec::new());
+					player.set_render_box(teleport.zone);
+					*player_position = teleport.destination;
+				},
+				// the player left while teleporting
+				None => self.unload_chunks(teleport.zone.min, teleport.zone.max, Vec::new()),
+			}
+			false
+		});
+	}
+
+	// returns the state of every chunk column within radius of center, at the same height as center
+	// all locks are released before returning, so the result can be held onto while rendering
+	pub fn chunk_states_around(&self, center: ChunkPos, radius: i32) -> Vec<(IVec2, ChunkState)> {
+		let job_boxes = self.chunk_load_jobs.read().iter()
+			.map(|job| job.chunks)
+			.collect::<Vec<_>>();
+		let cached_chunks = self.cached_chunks.read();
+
+		let mut out = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+
+		for x in center.x - radius..=center.x + radius {
+			for z in center.z - radius..=center.z + radius {
+				let chunk = ChunkPos::new(x, center.y, z);
+
+				let state = if self.chunks.contains_key(&chunk) {
+					ChunkState::Loaded
+				} else if job_boxes.iter().any(|job_box| job_box.contains(chunk)) {
+					ChunkState::Queued
+				} else if cached_chunks.contains_key(&chunk) {
+					ChunkState::Cached
+				} else {
+					ChunkState::Unloaded
 				};
 
-				self.load_chunks(neg_min_chunk, neg_max_chunk, Some(load_face_job));
-			} else {
-				todo!("moved to far for current player moving code");
+				out.push((IVec2::new(x, z), state));
 			}
+		}
+
+		out
+	}
+
+	#[instrument(level = "debug", skip(self))]
+	pub fn chunk_mesh(&self, chunk: ChunkPos) -> Vec<BlockFaceMesh> {
+		self.chunks.get(&chunk)
+			.and_then(|chunk| chunk.chunk.get_chunk_mesh())
+			.unwrap_or_default()
+	}
+}
+
+impl World {
+	pub fn add_entity(&self, entity: Box<dyn Entity>) -> EntityId {
+		let id = EntityId::new();
+		let mut entities = self.entities.write();
+
+		self.entity_chunks.entry(entity.position().as_chunk_pos()).or_default().push(id);
+		entities.insert(id, entity);
+		id
+	}
+
+	pub fn remove_entity(&self, id: EntityId) -> Option<Box<dyn Entity>> {
+		let mut entities = self.entities.write();
+		let entity = entities.remove(&id)?;
+
+		self.remove_from_entity_chunk(id, entity.position().as_chunk_pos());
+		Some(entity)
+	}
+
+	// removes every entity in the chunk from the world
+	fn take_chunk_entities(&self, chunk: ChunkPos) -> Vec<(EntityId, Box<dyn Entity>)> {
+		let mut entities = self.entities.write();
+		let ids = match self.entity_chunks.remove(&chunk) {
+			Some((_, ids)) => ids,
+			None => return Vec::new(),
 		};
 
-		load_axis(Axis::X);
-		load_axis(Axis::Y);
-		load_axis(Axis::Z);
+		ids.into_iter()
+			.filter_map(|id| Some((id, entities.remove(&id)?)))
+			.collect()
+	}
 
-		let out = chunk_position != player.chunk_position();
+	// adds entities which were taken out of the world back, with the same ids
+	pub(super) fn restore_entities(&self, restored: Vec<(EntityId, Box<dyn Entity>)>) {
+		if restored.is_empty() {
+			return;
+		}
 
-		player.position = position;
-		Some(out)
+		let mut entities = self.entities.write();
+		for (id, entity) in restored {
+			self.entity_chunks.entry(entity.position().as_chunk_pos()).or_default().push(id);
+			entities.insert(id, entity);
+		}
 	}
 
-	pub fn render_zone_mesh(&self, render_zone: ChunkPos) -> Vec<BlockFaceMesh> {
-		let render_zone_end = render_zone + ChunkPos::splat(RENDER_ZONE_SIZE);
+	pub fn entity_count(&self) -> EntityCount {
+		let entities = self.entities.read();
+		let active = entities.values()
+			.filter(|entity| self.chunks.contains_key(&entity.position().as_chunk_pos()))
+			.count();
 
-		let mut out = Vec::new();
+		EntityCount {
+			active,
+			frozen: entities.len() - active,
+			pending_save: self.cached_chunks.read().values().map(ChunkData::entity_count).sum(),
+		}
+	}
+
+	// the chunk the entity left is marked as modified, so a saved copy which still has the entity is replaced
+	fn remove_from_entity_chunk(&self, id: EntityId, chunk: ChunkPos) {
+		let mut chunk_entities = self.entity_chunks.get_mut(&chunk)
+			.expect("entity was not in the bucket of its chunk");
+		chunk_entities.retain(|entity| *entity != id);
+		let now_empty = chunk_entities.is_empty();
+		drop(chunk_entities);
+
+		if now_empty {
+			self.entity_chunks.remove(&chunk);
+		}
+		if let Some(loaded_chunk) = self.chunks.get(&chunk) {
+			loaded_chunk.chunk.mark_modified();
+		}
+	}
+
+	// advances the world by 1 tick, this is called at a fixed rate seperate from the client's frames
+	pub fn tick(&self, tick_number: u64) {
+		self.current_tick.store(tick_number, Ordering::Relaxed);
+		self.state.lock().advance();
+
+		self.run_scheduled_updates(tick_number);
+		self.run_neighbor_updates();
+		self.tick_block_entities(tick_number);
+		self.tick_entities();
+	}
 
-		for x in render_zone.x..render_zone_end.x {
-			for y in render_zone.y..render_zone_end.y {
-				for z in render_zone.z..render_zone_end.z {
-					let chunk_pos = ChunkPos::new(x, y, z);
+	// called when a chunk with ticking block entities is loaded, or one is placed in a loaded chunk
+	pub(super) fn track_ticking_chunk(&self, chunk_pos: ChunkPos) {
+		self.ticking_chunks.lock().insert(chunk_pos);
+	}
+
+	// chunks which are unloaded or have nothing left to tick stop being tracked, their block entities catch up when they are loaded again
+	fn tick_block_entities(&self, tick_number: u64) {
+		let ticking_chunks = self.ticking_chunks.lock().iter().copied().collect::<Vec<_>>();
+		for chunk_pos in ticking_chunks {
+			if let Some(chunk) = self.chunks.get(&chunk_pos) {
+				chunk.chunk.tick_block_entities(tick_number);
+			}
+		}
+
+		// checked with the set locked, so a chunk loaded on another thread since it was ticked isn't dropped
+		self.ticking_chunks.lock().retain(|chunk_pos| {
+			self.chunks.get(chunk_pos).is_some_and(|chunk| chunk.chunk.has_ticking_block_entities())
+		});
+	}
+
+	pub fn current_tick(&self) -> u64 {
+		self.current_tick.load(Ordering::Relaxed)
+	}
 
-					if let Some(chunk) = self.chunks.get(&chunk_pos) {
-						if let Some(mesh) = chunk.chunk.get_chunk_mesh() {
-							out.extend(mesh);
-						}
+	pub fn world_state(&self) -> WorldState {
+		*self.state.lock()
+	}
+
+	pub fn set_time_of_day(&self, time_of_day: u64) {
+		self.state.lock().set_time_of_day(time_of_day);
+	}
+
+	pub fn set_weather(&self, weather: Weather) {
+		self.state.lock().weather = weather;
+	}
+
+	pub fn tick_time(&self) -> Duration {
+		Duration::from_micros(self.tick_time.load(Ordering::Relaxed))
+	}
+
+	pub fn set_tick_time(&self, tick_time: Duration) {
+		self.tick_time.store(tick_time.as_micros() as u64, Ordering::Relaxed);
+	}
+
+	// seed for random events in a chunk during a tick, so they happen the same way every time the world is simulated
+	pub fn random_tick_seed(&self, tick_number: u64, chunk: ChunkPos) -> u64 {
+		let mut hasher = FxHasher::default();
+		(self.world_generator.seed(), tick_number, chunk).hash(&mut hasher);
+		hasher.finish()
+	}
+
+	// entities only move on ticks, by the tick time, so how they move doesn't depend on the frame rate
+	// entities in chunks which aren't loaded are frozen, so they don't fall through terrain that isn't there
+	fn tick_entities(&self) {
+		let delta = self.tick_time();
+		let mut entities = self.entities.write();
+		let mut despawned = Vec::new();
+
+		for (id, entity) in entities.iter_mut() {
+			let old_chunk = entity.position().as_chunk_pos();
+			if !self.chunks.contains_key(&old_chunk) {
+				continue;
+			}
+
+			entity.tick(self, delta);
+			let new_chunk = entity.position().as_chunk_pos();
+
+			if new_chunk != old_chunk {
+				self.remove_from_entity_chunk(*id, old_chunk);
+				self.entity_chunks.entry(new_chunk).or_default().push(*id);
+			}
+			if entity.should_despawn() {
+				despawned.push(*id);
+			}
+		}
+
+		self.push_entities_apart(&mut entities);
+
+		for id in despawned {
+			let entity = entities.remove(&id).unwrap();
+			self.remove_from_entity_chunk(id, entity.position().as_chunk_pos());
+		}
+	}
+
+	// pushes overlapping entities away from each other
+	// every push is found before any entity moves, so the order entities are visited in doesn't matter
+	fn push_entities_apart(&self, entities: &mut FxHashMap<EntityId, Box<dyn Entity>>) {
+		let mut pushes = FxHashMap::<EntityId, Vec3>::default();
+
+		for (id, entity) in entities.iter() {
+			let chunk = entity.position().as_chunk_pos();
+			let bounding_box = match entity.bounding_box() {
+				Some(bounding_box) if self.chunks.contains_key(&chunk) => bounding_box,
+				_ => continue,
+			};
+
+			// entities are much smaller than chunks, so anything overlapping this one is in its chunk or one next to it
+			for other_chunk in ChunkPos::iter_range(chunk - ChunkPos::splat(1), chunk + ChunkPos::splat(2)) {
+				if !self.chunks.contains_key(&other_chunk) {
+					continue;
+				}
+				let chunk_entities = match self.entity_chunks.get(&other_chunk) {
+					Some(chunk_entities) => chunk_entities,
+					None => continue,
+				};
+
+				// each pair is only checked from the entity with the lower id
+				for other_id in chunk_entities.iter().filter(|other_id| **other_id > *id) {
+					let other = &entities[other_id];
+					let offset = match other.bounding_box().and_then(|other_box| push_out(bounding_box, other_box, [*id, *other_id])) {
+						Some(offset) => offset,
+						None => continue,
+					};
+
+					// pushable entities split the distance, or one moves all of it if the other can't be pushed
+					match (entity.pushable(), other.pushable()) {
+						(true, true) => {
+							*pushes.entry(*id).or_default() -= offset / 2.0;
+							*pushes.entry(*other_id).or_default() += offset / 2.0;
+						},
+						(true, false) => *pushes.entry(*id).or_default() -= offset,
+						(false, true) => *pushes.entry(*other_id).or_default() += offset,
+						(false, false) => (),
 					}
 				}
 			}
 		}
 
+		for (id, push) in pushes {
+			let entity = entities.get_mut(&id).unwrap();
+			let old_chunk = entity.position().as_chunk_pos();
+			entity.push(self, push.clamp_length_max(MAX_PUSH_PER_TICK));
+
+			let new_chunk = entity.position().as_chunk_pos();
+			if new_chunk != old_chunk {
+				self.remove_from_entity_chunk(id, old_chunk);
+				self.entity_chunks.entry(new_chunk).or_default().push(id);
+			}
+		}
+	}
+
+	// where the entity is drawn and its label, none if there is no entity with the id or it has no label
+	// alpha is the fraction of the way from the last tick to the next one, see Entity::render_position
+	pub fn entity_label(&self, id: EntityId, alpha: f32) -> Option<(Position, String)> {
+		let entities = self.entities.read();
+		let entity = entities.get(&id)?;
+		Some((entity.render_position(alpha), entity.label()?))
+	}
+
+	// none if there is no entity with the id
+	pub fn entity_position(&self, id: EntityId) -> Option<Position> {
+		self.entities.read().get(&id).map(|entity| entity.position())
+	}
+
+	// where the entity is drawn, none if there is no entity with the id
+	pub fn entity_render_position(&self, id: EntityId, alpha: f32) -> Option<Position> {
+		self.entities.read().get(&id).map(|entity| entity.render_position(alpha))
+	}
+
+	// returns all entities whose position is inside of the bounding box,
+	// only the entities in chunks overlapping the bounding box are checked
+	pub fn entities_in_aabb(&self, aabb: Aabb) -> Vec<EntityId> {
+		let entities = self.entities.read();
+
+		let min_chunk = Position(aabb.position).as_chunk_pos();
+		let max_chunk = Position(aabb.end()).as_chunk_pos();
+
+		let mut out = Vec::new();
+		for chunk in ChunkPos::iter_range(min_chunk, max_chunk + ChunkPos::splat(1)) {
+			let chunk_entities = match self.entity_chunks.get(&chunk) {
+				Some(chunk_entities) => chunk_entities,
+				None => continue,
+			};
+
+			out.extend(chunk_entities.iter()
+				.filter(|id| aabb.contains_point(entities[*id].position().0)));
+		}
+
 		out
 	}
 }
@@ -464,12 +1834,1022 @@ mod tests {
 
 	use test::Bencher;
 	use super::*;
+	use crate::game::player::{GameMode, MAX_HEALTH};
+	use crate::game::tick::TickClock;
+	use crate::game::block::{Stone, StoneSlab, Dirt, Air, Water, Sand, Furnace, BlockVertex, SOURCE_LIQUID_LEVEL};
+	use crate::game::block_entity::{SMELT_TICKS, FURNACE_OUTPUT_SLOT};
+	use crate::game::chunk::{CHUNK_SIZE, MAX_BLOCK_ENTITY_CATCH_UP_TICKS};
+	use crate::game::worldgen::DeterministicRng;
 
 	#[bench]
 	fn mesh_generation_benchmark(b: &mut Bencher) {
 		b.iter(|| {
 			let world = World::new_test().unwrap();
-			world.connect();
+			world.connect("test");
+		})
+	}
+
+	#[bench]
+	fn update_player_position_same_chunk_benchmark(b: &mut Bencher) {
+		let world = World::new_test().unwrap();
+		let (player, _) = world.connect("test");
+
+		let mut offset = 0.0;
+		b.iter(|| {
+			// stays within the starting chunk
+			offset = (offset + 0.1) % 1.0;
+			world.update_player_position(player, Position::new(offset, offset, offset))
 		})
 	}
+
+	#[test]
+	fn memory_stats_count_chunks_and_meshes() {
+		let world = World::new_test().unwrap();
+		for (chunk, block) in [(ChunkPos::new(0, 0, 0), Stone::new().into()), (ChunkPos::new(0, 1, 0), Air::new().into())] {
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, block)));
+		}
+
+		let unmeshed = world.memory_stats();
+		assert_eq!(unmeshed.loaded_chunks, 2);
+		assert_eq!(unmeshed.block_bytes, 2 * CHUNK_BLOCK_BYTES);
+		assert_eq!(unmeshed.cached_chunks, 0);
+
+		// the stone's top face borders the air chunk
+		world.chunks.get(&ChunkPos::new(0, 0, 0)).unwrap().chunk.chunk_mesh_update();
+		let meshed = world.memory_stats();
+		assert!(meshed.mesh_bytes > unmeshed.mesh_bytes);
+
+		world.close();
+	}
+
+	#[test]
+	fn raycast_hits_top_face() {
+		let world = World::new_test().unwrap();
+		for (chunk, block) in [(ChunkPos::new(0, 0, 0), Stone::new().into()), (ChunkPos::new(0, 1, 0), Air::new().into())] {
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, block)));
+		}
+
+		let top = CHUNK_SIZE as f32;
+		let hit = world.block_raycast(Position::new(5.5, top + 3.5, 5.5), -Vec3::Y, 15.0).unwrap();
+		assert_eq!(hit.block, BlockPos::new(5, CHUNK_SIZE as i32 - 1, 5));
+		assert_eq!(hit.face, BlockFace::YPos);
+		assert!((hit.distance - 3.5).abs() < 0.001);
+		assert_eq!(hit.adjacent_block(), BlockPos::new(5, CHUNK_SIZE as i32, 5));
+
+		// out of reach
+		assert!(world.block_raycast(Position::new(5.5, top + 3.5, 5.5), -Vec3::Y, 2.0).is_none());
+	}
+
+	#[test]
+	fn raycast_passes_over_bottom_slab() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::new(world.clone(), chunk, |block| {
+			match (block.x, block.y, block.z) {
+				(5, 5, 5) => StoneSlab::new().into(),
+				(6, 5, 5) => Stone::new().into(),
+				_ => Air::new().into(),
+			}
+		})));
+
+		// over the top half of the slab into the block behind it
+		let hit = world.block_raycast(Position::new(3.5, 5.75, 5.5), Vec3::X, 10.0).unwrap();
+		assert_eq!(hit.block, BlockPos::new(6, 5, 5));
+		assert_eq!(hit.face, BlockFace::XNeg);
+		assert!((hit.distance - 2.5).abs() < 0.001);
+
+		// into the bottom half of the slab
+		let hit = world.block_raycast(Position::new(3.5, 5.25, 5.5), Vec3::X, 10.0).unwrap();
+		assert_eq!(hit.block, BlockPos::new(5, 5, 5));
+		assert_eq!(hit.selection, Aabb::new(Vec3::new(5.0, 5.0, 5.0), Vec3::new(1.0, 0.5, 1.0)));
+
+		// down onto the top of the slab, which is half way down its block
+		let hit = world.block_raycast(Position::new(5.5, 7.5, 5.5), -Vec3::Y, 10.0).unwrap();
+		assert_eq!(hit.block, BlockPos::new(5, 5, 5));
+		assert_eq!(hit.face, BlockFace::YPos);
+		assert!((hit.distance - 2.0).abs() < 0.001);
+		assert_eq!(hit.adjacent_block(), BlockPos::new(5, 6, 5));
+	}
+
+	// a source in the middle of a 5x5 basin spreads out to fill it, and dries up when the source is removed
+	#[test]
+	fn water_floods_basin() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::new(world.clone(), chunk, |block| {
+			let wall = (block.x == 0 || block.x == 6 || block.z == 0 || block.z == 6)
+				&& block.x <= 6 && block.z <= 6 && block.y <= 2;
+
+			if block.y == 0 || wall {
+				Stone::new().into()
+			} else {
+				Air::new().into()
+			}
+		})));
+
+		let source = BlockPos::new(3, 1, 3);
+		world.set_block(source, Water::new().into()).unwrap();
+
+		let mut tick = 0;
+		let mut run_ticks = |count| {
+			for _ in 0..count {
+				tick += 1;
+				world.tick(tick);
+			}
+		};
+		run_ticks(200);
+		assert_eq!(world.scheduled_update_count(), 0);
+
+		for x in 1..=5 {
+			for z in 1..=5 {
+				let expected_level = SOURCE_LIQUID_LEVEL - ((x - 3i32).abs() + (z - 3i32).abs()) as u8;
+				let block = world.block_at(BlockPos::new(x, 1, z)).unwrap();
+				assert_eq!(block.block_type(), BlockType::Water);
+				assert_eq!(block.liquid_level(), Some(expected_level), "at {}, {}", x, z);
+
+				assert!(world.block_at(BlockPos::new(x, 2, z)).unwrap().is_air());
+			}
+		}
+		// the walls kept it in
+		assert!(world.block_at(BlockPos::new(7, 1, 3)).unwrap().is_air());
+
+		world.set_block(source, Air::new().into()).unwrap();
+		run_ticks(200);
+		assert_eq!(world.scheduled_update_count(), 0);
+
+		for x in 1..=5 {
+			for z in 1..=5 {
+				assert!(world.block_at(BlockPos::new(x, 1, z)).unwrap().is_air(), "at {}, {}", x, z);
+			}
+		}
+	}
+
+	// removing the block under a stack of sand makes the whole stack fall until it lands
+	#[test]
+	fn sand_falls_when_support_removed() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::new(world.clone(), chunk, |block| {
+			if block.y == 0 {
+				Stone::new().into()
+			} else if block.x == 2 && block.z == 2 && block.y == 5 {
+				Dirt::new().into()
+			} else if block.x == 2 && block.z == 2 && (6..=8).contains(&block.y) {
+				Sand::new().into()
+			} else {
+				Air::new().into()
+			}
+		})));
+
+		let mut tick = 0;
+		let mut run_ticks = |count| {
+			for _ in 0..count {
+				tick += 1;
+				world.tick(tick);
+			}
+		};
+		// supported sand stays where it is
+		run_ticks(20);
+		assert_eq!(world.block_type_at(BlockPos::new(2, 6, 2)), Some(BlockType::Sand));
+
+		world.set_block(BlockPos::new(2, 5, 2), Air::new().into()).unwrap();
+		run_ticks(100);
+		assert_eq!(world.scheduled_update_count(), 0);
+		assert_eq!(world.neighbor_update_count(), 0);
+
+		for y in 1..=10 {
+			let expected = if y <= 3 { BlockType::Sand } else { BlockType::Air };
+			assert_eq!(world.block_type_at(BlockPos::new(2, y, 2)), Some(expected), "at y = {}", y);
+		}
+	}
+
+	// meshes of every loaded chunk, as bytes so they can be compared
+	fn all_chunk_meshes(world: &World) -> FxHashMap<ChunkPos, Vec<u8>> {
+		world.chunks.iter().map(|chunk| {
+			let vertices: Vec<BlockVertex> = chunk.chunk.get_chunk_mesh().unwrap().iter().flat_map(|face| face.0).collect();
+			(*chunk.key(), bytemuck::cast_slice(&vertices).to_vec())
+		}).collect()
+	}
+
+	// random edits updated with mesh_update_adjacent should mesh the same as remeshing every chunk from scratch
+	fn check_mesh_updates_match_full_remesh(smooth_lighting: bool) {
+		let world = World::new_test().unwrap();
+		world.smooth_lighting.store(smooth_lighting, Ordering::Relaxed);
+
+		let mut rng = DeterministicRng::for_test(98765);
+		let mut next = move |max: u32| rng.range(0..max as i32) as u32;
+		let random_block = |next: &mut dyn FnMut(u32) -> u32| -> Block {
+			match next(4) {
+				0 => Stone::new().into(),
+				1 => Dirt::new().into(),
+				_ => Air::new().into(),
+			}
+		};
+
+		for x in 0..2 {
+			for y in 0..2 {
+				for z in 0..2 {
+					let chunk = ChunkPos::new(x, y, z);
+					let loaded_chunk = LoadedChunk::new(Chunk::new(world.clone(), chunk, |_| random_block(&mut next)));
+					world.chunks.insert(chunk, loaded_chunk);
+				}
+			}
+		}
+		for chunk in world.chunks.iter() {
+			chunk.chunk.chunk_mesh_update();
+		}
+
+		// edits near chunk borders are the most likely to be missed
+		let coordinates = [0, 1, 2, 29, 30, 31, 32, 33, 34, 61, 62, 63];
+		let mut updated_chunks = UpdatedChunks::new();
+		for _ in 0..60 {
+			let mut coordinate = || coordinates[next(coordinates.len() as u32) as usize];
+			let block = BlockPos::new(coordinate(), coordinate(), coordinate());
+			world.set_block(block, random_block(&mut next)).unwrap();
+			world.mesh_update_changed_blocks(&mut updated_chunks);
+		}
+
+		let updated_meshes = all_chunk_meshes(&world);
+		for chunk in world.chunks.iter() {
+			chunk.chunk.chunk_mesh_update();
+		}
+		let full_meshes = all_chunk_meshes(&world);
+
+		for (chunk, mesh) in full_meshes.iter() {
+			assert!(updated_meshes[chunk] == *mesh, "mesh of chunk {:?} doesn't match", chunk);
+		}
+	}
+
+	#[test]
+	fn mesh_updates_match_full_remesh() {
+		check_mesh_updates_match_full_remesh(false);
+	}
+
+	#[test]
+	fn mesh_updates_match_full_remesh_smooth_lighting() {
+		check_mesh_updates_match_full_remesh(true);
+	}
+
+	#[test]
+	fn interior_edit_remeshes_only_its_chunk() {
+		let world = World::new_test().unwrap();
+		for x in 0..2 {
+			for z in 0..2 {
+				let chunk = ChunkPos::new(x, 0, z);
+				world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+			}
+		}
+		for chunk in world.chunks.iter() {
+			chunk.chunk.chunk_mesh_update();
+		}
+		let old_meshes = all_chunk_meshes(&world);
+
+		let block = BlockPos::new(16, 16, 16);
+		world.set_block(block, Stone::new().into()).unwrap();
+		assert_eq!(world.take_pending_edit().map(|(edit, _)| edit), Some(block));
+		let mut updated_chunks = UpdatedChunks::new();
+		world.mesh_update_changed_blocks(&mut updated_chunks);
+		assert_eq!(updated_chunks.take_nearest(ChunkPos::new(0, 0, 0), 8), vec![ChunkPos::new(0, 0, 0)]);
+
+		let new_meshes = all_chunk_meshes(&world);
+		for (chunk, mesh) in new_meshes.iter() {
+			let changed = old_meshes[chunk] != *mesh;
+			assert_eq!(changed, *chunk == ChunkPos::new(0, 0, 0), "mesh of chunk {:?}", chunk);
+		}
+		world.close();
+	}
+
+	#[test]
+	fn changed_blocks_are_capped_without_a_client() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+
+		// nothing polls completed tasks, so nothing takes the changed blocks
+		let block = BlockPos::new(4, 4, 4);
+		for i in 0..MAX_CHANGED_BLOCKS + 10 {
+			let placed: Block = if i % 2 == 0 { Stone::new().into() } else { Air::new().into() };
+			world.set_block(block, placed).unwrap();
+		}
+		assert_eq!(world.changed_blocks.lock().len(), MAX_CHANGED_BLOCKS);
+		world.close();
+	}
+
+	#[test]
+	fn diagonal_move_meshes_both_seams() {
+		let world = World::new_test().unwrap();
+		// added directly so connecting doesn't load the whole render zone
+		// the surface isn't included, so the zone is the same height in both chunk columns
+		let render_distance = RenderDistance {
+			include_surface: false,
+			..RenderDistance::default()
+		};
+		let player = Player::with_render_distance(render_distance);
+		let player_id = player.id();
+		world.add_player(player, Position::new(0.0, 0.0, 0.0));
+
+		let chunk_size = CHUNK_SIZE as f32;
+		world.update_player_position(player_id, Position::new(chunk_size + 1.0, 1.0, chunk_size + 1.0)).unwrap();
+
+		let load_jobs = world.chunk_load_jobs.read();
+		let load_boxes = load_jobs.iter()
+			.filter(|job| !job.mesh_face_tasks.is_empty())
+			.collect::<Vec<_>>();
+
+		// the new slab on x and the new slab on z, including the corner between them
+		let height = render_distance.up + render_distance.down;
+		let slab_volume = height * 2 * render_distance.horizontal;
+		let loaded_volume: usize = load_boxes.iter().map(|job| job.chunks.volume()).sum();
+		assert_eq!(loaded_volume, (2 * slab_volume - height) as usize);
+
+		let mesh_face_tasks = load_boxes.iter().flat_map(|job| job.mesh_face_tasks.iter()).collect::<Vec<_>>();
+		// the already loaded chunks on the old edge of the render zone, on both axis
+		assert!(mesh_face_tasks.iter().any(|task| {
+			task.face == BlockFace::XPos && task.min_chunk.x == render_distance.horizontal - 1 && task.max_chunk.x == render_distance.horizontal
+		}));
+		assert!(mesh_face_tasks.iter().any(|task| {
+			task.face == BlockFace::ZPos && task.min_chunk.z == render_distance.horizontal - 1 && task.max_chunk.z == render_distance.horizontal
+		}));
+	}
+
+	fn test_load_job(min_chunk: ChunkPos, max_chunk: ChunkPos, remaining_chunks: u64) -> ChunkLoadJob {
+		ChunkLoadJob {
+			chunks: ChunkBox::new(min_chunk, max_chunk),
+			remaining_chunks,
+			mesh_face_tasks: Vec::new(),
+			requested: Instant::now(),
+		}
+	}
+
+	#[test]
+	fn overlapping_load_jobs_finish_on_the_same_chunk() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunk_load_jobs.write().extend([
+			test_load_job(chunk, ChunkPos::new(1, 1, 1), 1),
+			// the other chunk in this job has already loaded
+			test_load_job(chunk, ChunkPos::new(2, 1, 1), 1),
+			test_load_job(chunk, ChunkPos::new(2, 1, 1), 2),
+			test_load_job(ChunkPos::new(5, 0, 0), ChunkPos::new(6, 1, 1), 1),
+		]);
+
+		let mut updated_chunks = UpdatedChunks::new();
+		world.task_completed(Task::GenerateChunk(chunk), &mut updated_chunks);
+
+		// both finished jobs are followed up, not just the first
+		assert_eq!(world.take_chunk_load_latencies().len(), 2);
+		let remaining = world.chunk_load_jobs.read().iter()
+			.map(|job| (job.chunks, job.remaining_chunks))
+			.collect::<Vec<_>>();
+		assert_eq!(remaining, vec![
+			(ChunkBox::new(chunk, ChunkPos::new(2, 1, 1)), 1),
+			(ChunkBox::new(ChunkPos::new(5, 0, 0), ChunkPos::new(6, 1, 1)), 1),
+		]);
+
+		world.close();
+	}
+
+	#[test]
+	fn unload_jobs_finish_once_per_unload() {
+		let world = World::new_test().unwrap();
+		let (min_chunk, max_chunk) = (ChunkPos::new(0, 0, 0), ChunkPos::new(1, 1, 1));
+		world.unload_chunks(min_chunk, max_chunk, Vec::new());
+		world.unload_chunks(min_chunk, max_chunk, Vec::new());
+		assert!(world.chunk_load_jobs.read().is_empty());
+		assert_eq!(world.chunk_unload_jobs.read().len(), 2);
+
+		let mut updated_chunks = UpdatedChunks::new();
+		world.task_completed(Task::UnloadChunks { min_chunk, max_chunk }, &mut updated_chunks);
+		assert_eq!(world.chunk_unload_jobs.read().len(), 1);
+		world.task_completed(Task::UnloadChunks { min_chunk, max_chunk }, &mut updated_chunks);
+		assert!(world.chunk_unload_jobs.read().is_empty());
+
+		world.close();
+	}
+
+	#[test]
+	fn regenerated_chunks_stay_loaded_and_finish_once_meshed() {
+		let world = World::new_test().unwrap();
+		let (chunk, neighbor) = (ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0));
+		for position in [chunk, neighbor] {
+			let loaded_chunk = world.world_generator.generate_chunk(world.clone(), position);
+			loaded_chunk.inc_load_count();
+			world.chunks.insert(position, loaded_chunk);
+		}
+		let block = BlockPos::new(3, 3, 3);
+		let generated_type = world.block_type_at(block);
+		world.set_block(block, Furnace::new().into()).unwrap();
+		// the furnace is saved, and has to be replaced by the regenerated chunk
+		world.save_all().unwrap();
+		world.schedule_update(block, 5);
+		world.schedule_update(neighbor.as_block_pos() + block, 5);
+
+		assert_eq!(world.regenerate_chunk(ChunkPos::new(0, 5, 0)), Err(WorldError::ChunkNotLoaded { chunk: ChunkPos::new(0, 5, 0) }));
+		world.regenerate_chunk(chunk).unwrap();
+		assert_eq!(world.block_type_at(block), generated_type);
+		assert_eq!(world.with_block_entity_mut(block, |_| ()), None);
+		assert_eq!(world.chunks.get(&chunk).unwrap().get_load_count(), 1);
+		// only the update in the neighbor is left
+		assert_eq!(world.block_updates.lock().len(), 1);
+
+		// it is finished once the chunk and the layer of its neighbor facing it are meshed
+		let mut updated_chunks = UpdatedChunks::new();
+		world.task_completed(Task::ChunkMesh(chunk), &mut updated_chunks);
+		assert!(world.take_finished_repairs().is_empty());
+		world.task_completed(Task::ChunkMeshFace {
+			min_chunk: neighbor,
+			max_chunk: neighbor + ChunkPos::splat(1),
+			face: BlockFace::XNeg,
+		}, &mut updated_chunks);
+		assert_eq!(world.take_finished_repairs(), vec![(chunk, ChunkRepair::Regenerate)]);
+
+		// remeshing waits for the same tasks
+		world.remesh_chunk(neighbor).unwrap();
+		world.task_completed(Task::ChunkMesh(neighbor), &mut updated_chunks);
+		world.task_completed(Task::ChunkMeshFace {
+			min_chunk: chunk,
+			max_chunk: chunk + ChunkPos::splat(1),
+			face: BlockFace::XPos,
+		}, &mut updated_chunks);
+		assert_eq!(world.take_finished_repairs(), vec![(neighbor, ChunkRepair::Remesh)]);
+
+		// the regenerated chunk is what is saved, not the one it replaced
+		world.save_all().unwrap();
+		world.close();
+		let world = World::open(&world.directory, 0, GeneratorType::Normal, GeneratorOptions::default()).unwrap();
+		let (saved_chunk, _) = world.load_saved_chunk(chunk).unwrap();
+		assert_eq!(Some(saved_chunk.chunk.get_block(block.as_chunk_block_pos().1).block_type()), generated_type);
+		assert!(!saved_chunk.chunk.has_block_entities());
+		drop(saved_chunk);
+		world.close();
+	}
+
+	#[test]
+	fn failed_analysis_is_still_returned() {
+		let world = World::new_test().unwrap();
+		let mut updated_chunks = UpdatedChunks::new();
+		// a failed task is completed as it was queued, without stats
+		world.task_completed(Task::Analyze { area: None, stats: None }, &mut updated_chunks);
+		assert_eq!(world.take_finished_analyses(), vec![None]);
+		world.close();
+	}
+
+	#[test]
+	fn furnaces_tick_while_loaded_and_catch_up() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+		let furnace = BlockPos::new(1, 1, 1);
+		world.set_block(furnace, Furnace::new().into()).unwrap();
+		world.with_block_entity_mut(furnace, |entity| {
+			assert!(entity.insert(BlockType::RockyDirt, 10));
+			assert!(entity.insert(BlockType::Lava, 1));
+		});
+		let smelted = || world.with_block_entity_mut(furnace, |entity| entity.slots()[FURNACE_OUTPUT_SLOT]).unwrap();
+
+		let smelt_ticks = SMELT_TICKS as u64;
+		for tick in 1..=smelt_ticks {
+			world.tick(tick);
+		}
+		assert_eq!(smelted(), Some((BlockType::Stone, 1)));
+
+		// unloaded chunks aren't ticked, and stop being tracked
+		let (_, loaded_chunk) = world.chunks.remove(&chunk).unwrap();
+		world.tick(smelt_ticks + 1);
+		assert!(world.ticking_chunks.lock().is_empty());
+
+		// the time the chunk was unloaded for is caught up when it is loaded again
+		world.chunks.insert(chunk, loaded_chunk);
+		world.track_ticking_chunk(chunk);
+		world.tick(3 * smelt_ticks);
+		assert_eq!(smelted(), Some((BlockType::Stone, 3)));
+
+		// but only up to the limit
+		world.tick(1000 * smelt_ticks);
+		let caught_up = 3 + MAX_BLOCK_ENTITY_CATCH_UP_TICKS / smelt_ticks;
+		assert_eq!(smelted(), Some((BlockType::Stone, caught_up as u32)));
+
+		world.close();
+	}
+
+	#[test]
+	fn furnaces_catch_up_after_being_saved() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+		let furnace = BlockPos::new(1, 1, 1);
+		world.set_block(furnace, Furnace::new().into()).unwrap();
+		world.with_block_entity_mut(furnace, |entity| {
+			assert!(entity.insert(BlockType::RockyDirt, 10));
+			assert!(entity.insert(BlockType::Lava, 1));
+		});
+		let smelted = || world.with_block_entity_mut(furnace, |entity| entity.slots()[FURNACE_OUTPUT_SLOT]).unwrap();
+
+		let smelt_ticks = SMELT_TICKS as u64;
+		for tick in 1..=smelt_ticks {
+			world.tick(tick);
+		}
+		assert_eq!(smelted(), Some((BlockType::Stone, 1)));
+
+		let (_, loaded_chunk) = world.chunks.remove(&chunk).unwrap();
+		world.save_unloaded_chunk(chunk, loaded_chunk.chunk);
+		world.tick(smelt_ticks + 1);
+
+		// the chunk remembers when it was last ticked, so the time it was saved for is caught up, up to the limit
+		let (loaded_chunk, _) = world.load_saved_chunk(chunk).unwrap();
+		world.chunks.insert(chunk, loaded_chunk);
+		world.track_ticking_chunk(chunk);
+		world.tick(1000 * smelt_ticks);
+		let caught_up = 1 + MAX_BLOCK_ENTITY_CATCH_UP_TICKS / smelt_ticks;
+		assert_eq!(smelted(), Some((BlockType::Stone, caught_up as u32)));
+
+		world.close();
+	}
+
+	#[test]
+	fn teleports_wait_for_the_destination_to_generate() {
+		let world = World::new_test().unwrap();
+		// added directly so the starting render zone isn't loaded
+		let player = Player::with_render_distance(RenderDistance::default());
+		let player_id = player.id();
+		let start = Position::new(1.0, 2.0, 3.0);
+		world.add_player(player, start);
+
+		let destination = Position::new(5000.0, 40.0, -3000.0);
+		world.teleport_player(player_id, destination).unwrap();
+		let core = teleport_core(destination);
+		world.finish_teleports();
+		assert_eq!(world.player_position(player_id), Some(start));
+		assert_eq!(world.teleport_progress(player_id), Some((0, core.volume())));
+
+		let mut core_chunks = core.iter().collect::<Vec<_>>();
+		let last = core_chunks.pop().unwrap();
+		for chunk in core_chunks {
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+		}
+		world.finish_teleports();
+		assert_eq!(world.player_position(player_id), Some(start));
+		assert_eq!(world.teleport_progress(player_id), Some((core.volume() - 1, core.volume())));
+
+		world.chunks.insert(last, LoadedChunk::new(Chunk::filled(world.clone(), last, Air::new().into())));
+		world.finish_teleports();
+		assert_eq!(world.player_position(player_id), Some(destination));
+		assert_eq!(world.teleport_progress(player_id), None);
+
+		let missing = PlayerId::new();
+		assert_eq!(world.teleport_player(missing, destination), Err(WorldError::PlayerNotFound { player: missing }));
+
+		world.close();
+	}
+
+	#[test]
+	fn pregenerated_chunks_are_saved_and_loaded() {
+		let directory = std::env::temp_dir().join(format!("minecone-pregen-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&directory);
+		let world = World::open(&directory, 0, GeneratorType::Normal, GeneratorOptions::default()).unwrap();
+		let chunk = BlockPos::new(16, world.surface_height(16, 16), 16).as_chunk_pos();
+
+		world.pregenerate_chunk(chunk);
+		assert!(!world.chunks.contains_key(&chunk));
+		assert!(world.regions.contains_chunk(chunk).unwrap());
+
+		// the saved chunk is the same as generating it again
+		let (saved, entities) = world.load_saved_chunk(chunk).unwrap();
+		assert!(entities.is_empty());
+		let generated = world.world_generator.generate_chunk(world.clone(), chunk);
+		for x in 0..CHUNK_SIZE as i32 {
+			for y in 0..CHUNK_SIZE as i32 {
+				for z in 0..CHUNK_SIZE as i32 {
+					let local = BlockPos::new(x, y, z);
+					assert_eq!(saved.chunk.get_block(local).block_type(), generated.chunk.get_block(local).block_type());
+				}
+			}
+		}
+		drop((saved, generated));
+
+		assert_eq!(world.start_pregen(0), Some(0));
+		assert_eq!(world.pregen_progress(), None);
+		let total = world.start_pregen(1).unwrap();
+		assert!(total > 0);
+		assert_eq!(world.start_pregen(4), None);
+		assert_eq!(world.pregen_progress(), Some(PregenProgress { done: 0, total, time_left: None }));
+
+		let mut updated_chunks = UpdatedChunks::new();
+		world.task_completed(Task::PregenerateChunk(chunk), &mut updated_chunks);
+		let progress = world.pregen_progress().unwrap();
+		assert_eq!(progress.done, 1);
+		assert!(progress.time_left.is_some());
+
+		for _ in 1..total {
+			world.task_completed(Task::PregenerateChunk(chunk), &mut updated_chunks);
+		}
+		assert_eq!(world.pregen_progress(), None);
+
+		world.close();
+		std::fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn render_zone_keeps_surface_loaded_when_flying() {
+		let world = World::new_test().unwrap();
+		let surface_chunk = BlockPos::new(16, world.surface_height(16, 16), 16).as_chunk_pos();
+
+		let high_above = ChunkPos::new(0, surface_chunk.y + 20, 0);
+		let zone = world.render_zone(high_above, RenderDistance::default());
+		assert!(zone.contains(surface_chunk));
+		assert!(zone.contains(high_above));
+	}
+
+	#[test]
+	fn failed_changes_say_why() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(3, 0, -2);
+		let block = chunk.as_block_pos() + BlockPos::new(1, 2, 3);
+
+		assert_eq!(world.set_block(block, Stone::new().into()), Err(WorldError::ChunkNotLoaded { chunk }));
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+		assert_eq!(world.set_block(block, Stone::new().into()), Ok(()));
+		assert_eq!(world.block_type_at(block), Some(BlockType::Stone));
+
+		let player = PlayerId::new();
+		assert_eq!(world.update_player_position(player, Position::new(0.0, 0.0, 0.0)), Err(WorldError::PlayerNotFound { player }));
+
+		world.close();
+	}
+
+	#[test]
+	fn player_state_round_trip() {
+		let directory = std::env::temp_dir().join(format!("minecone-player-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&directory);
+		let world = World::open(&directory, 0, GeneratorType::Flat, GeneratorOptions::default()).unwrap();
+
+		// a new player starts at spawn with the default state
+		let (player_id, state) = world.connect("tester");
+		assert_eq!(state, PlayerState::default());
+
+		world.update_player_position(player_id, Position::new(100.5, 20.0, -40.25)).unwrap();
+		world.update_player_state(player_id, |state| {
+			state.set_forward(Vec3::new(1.0, -0.5, 1.0));
+			state.game_mode = GameMode::Survival;
+			state.hotbar = vec![Some(BlockType::Sand), None, Some(BlockType::Stone)];
+			state.hotbar_counts = vec![12, 0, 3];
+			state.selected_slot = 2;
+			state.spawn_point = Position::new(8.0, 3.0, 8.0);
+			state.health = 7.5;
+		});
+		let expected = world.player_state(player_id).unwrap();
+		world.disconnect(player_id).unwrap();
+
+		// a different world object for the same directory, like when the game is started again
+		let world = World::open(&directory, 0, GeneratorType::Flat, GeneratorOptions::default()).unwrap();
+		let (player_id, state) = world.connect("tester");
+		assert_eq!(state, expected);
+		assert_eq!(state.position, Position::new(100.5, 20.0, -40.25));
+		// chunks are loaded around the saved position
+		assert_eq!(world.player_position(player_id), Some(state.position));
+
+		// other players are separate
+		assert_eq!(world.connect("someone else").1, PlayerState::default());
+
+		std::fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn generator_options_are_kept_when_reopened() {
+		use crate::game::world_list::{create_world, list_worlds};
+
+		let worlds_directory = std::env::temp_dir().join(format!("minecone-generator-options-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&worlds_directory);
+		let options = GeneratorOptions {
+			terrain_scale: 0.08,
+			biome_scale: 0.005,
+			cave_scale: 0.03,
+			cave_threshold: 0.4,
+			underground_biome_scale: 0.01,
+		};
+		assert_ne!(options, GeneratorOptions::default());
+		create_world(&worlds_directory, "options", 5, GeneratorType::Normal, options).unwrap();
+
+		// opened the same way the game opens worlds from the world list
+		let entry = list_worlds(&worlds_directory).remove(0);
+		let world = World::open(&entry.directory, entry.meta.seed, entry.meta.generator, entry.meta.generator_options).unwrap();
+		assert_eq!(world.world_generator.options(), options);
+		assert_eq!(world.new_generator().options(), options);
+
+		std::fs::remove_dir_all(&worlds_directory).unwrap();
+	}
+
+	#[test]
+	fn world_state_is_saved_with_world() {
+		let directory = std::env::temp_dir().join(format!("minecone-world-state-world-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&directory);
+		let world = World::open(&directory, 0, GeneratorType::Flat, GeneratorOptions::default()).unwrap();
+
+		world.set_time_of_day(1000);
+		world.set_weather(Weather::Rain);
+		world.tick(1);
+		world.save_all().unwrap();
+		world.close();
+
+		let world = World::open(&directory, 0, GeneratorType::Flat, GeneratorOptions::default()).unwrap();
+		assert_eq!(world.world_state().time_of_day(), 1001);
+		assert_eq!(world.world_state().weather, Weather::Rain);
+
+		std::fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn changed_chunks_are_saved_with_world() {
+		let directory = std::env::temp_dir().join(format!("minecone-chunk-save-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&directory);
+		let world = World::open(&directory, 0, GeneratorType::Flat, GeneratorOptions::default()).unwrap();
+		let (changed, unchanged) = (ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0));
+		for chunk in [changed, unchanged] {
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+		}
+
+		world.set_block(BlockPos::new(3, 4, 5), Stone::new().into()).unwrap();
+		world.add_entity(Box::new(Item::new(Position::new(8.5, 2.0, 8.5), BlockType::Sand, 2)));
+		world.save_all().unwrap();
+		assert!(!world.regions.contains_chunk(unchanged).unwrap());
+		world.close();
+
+		let world = World::open(&directory, 0, GeneratorType::Flat, GeneratorOptions::default()).unwrap();
+		let (loaded_chunk, entities) = world.load_saved_chunk(changed).unwrap();
+		assert_eq!(loaded_chunk.chunk.get_block(BlockPos::new(3, 4, 5)).block_type(), BlockType::Stone);
+		assert_eq!(entities.len(), 1);
+		drop(loaded_chunk);
+		world.close();
+
+		std::fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn entities_which_left_a_chunk_are_not_saved_with_it() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(0, 0, 0);
+		world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+		let item = world.add_entity(Box::new(Item::new(Position::new(8.5, 2.0, 8.5), BlockType::Sand, 2)));
+
+		// the chunk is saved with the item, without any of its blocks changing
+		world.save_all().unwrap();
+		let (saved_chunk, entities) = world.load_saved_chunk(chunk).unwrap();
+		assert_eq!(entities.len(), 1);
+		drop(saved_chunk);
+
+		// the item is picked up, then the chunk is unloaded and loaded again
+		world.remove_entity(item).unwrap();
+		let (_, loaded_chunk) = world.chunks.remove(&chunk).unwrap();
+		world.save_unloaded_chunk(chunk, loaded_chunk.chunk);
+		let (saved_chunk, entities) = world.load_saved_chunk(chunk).unwrap();
+		assert!(entities.is_empty());
+		drop(saved_chunk);
+		world.close();
+	}
+
+	#[test]
+	fn dying_respawns_at_spawn_point() {
+		let world = World::new_test().unwrap();
+		let spawn_point = Position::new(8.0, 3.0, 8.0);
+		let player = Player::new();
+		player.update_state(|state| {
+			state.spawn_point = spawn_point;
+			state.game_mode = GameMode::Survival;
+		});
+		let player_id = player.id();
+		world.add_player(player, Position::new(0.0, 0.0, 0.0));
+		world.update_player_position(player_id, Position::new(0.0, 1.0, 0.0)).unwrap();
+
+		// landing gently doesn't hurt
+		assert_eq!(world.player_landed(player_id, 5.0), None);
+
+		let outcome = world.damage_player(player_id, 5.0);
+		assert_eq!(outcome, Some(DamageOutcome::Hurt { health: MAX_HEALTH - 5.0 }));
+		assert_eq!(world.player_health(player_id), Some(MAX_HEALTH - 5.0));
+
+		assert_eq!(world.player_landed(player_id, 60.0), Some(DamageOutcome::Died { respawn_point: spawn_point }));
+		assert_eq!(world.player_position(player_id), Some(spawn_point));
+		assert_eq!(world.player_health(player_id), Some(MAX_HEALTH));
+
+		// creative players can't be hurt
+		world.update_player_state(player_id, |state| state.game_mode = GameMode::Creative);
+		assert_eq!(world.damage_player(player_id, 100.0), None);
+		assert_eq!(world.player_health(player_id), Some(MAX_HEALTH));
+	}
+
+	#[test]
+	fn player_positions_under_contention() {
+		const MOVES: usize = 2000;
+
+		let world = World::new_test().unwrap();
+		let player = Player::new();
+		let player_id = player.id();
+		world.add_player(player, Position::new(0.0, 0.0, 0.0));
+
+		let done = Arc::new(AtomicBool::new(false));
+		let readers = (0..4).map(|_| {
+			let world = world.clone();
+			let done = done.clone();
+			std::thread::spawn(move || loop {
+				// every position written has all 3 coordinates the same
+				let position = world.player_position(player_id).unwrap();
+				assert!(position.x == position.y && position.y == position.z, "{:?}", position);
+				if done.load(Ordering::Acquire) {
+					break;
+				}
+			})
+		}).collect::<Vec<_>>();
+
+		// changes the rest of the player at the same time as it moves
+		let metadata_writer = {
+			let world = world.clone();
+			let done = done.clone();
+			std::thread::spawn(move || {
+				while !done.load(Ordering::Acquire) {
+					world.update_player_state(player_id, |state| state.selected_slot = (state.selected_slot + 1) % 9);
+					assert!(world.player_load_progress(player_id).is_some());
+				}
+			})
+		};
+
+		let mut last_position = Position::new(0.0, 0.0, 0.0);
+		for i in 0..MOVES {
+			// crosses into the next chunk and back now and then, so chunk loading happens while holding the position
+			let offset = if i % 100 < 50 { (i % 10) as f32 } else { CHUNK_SIZE as f32 + (i % 10) as f32 };
+			last_position = Position::new(offset, offset, offset);
+			world.update_player_position(player_id, last_position).unwrap();
+		}
+		done.store(true, Ordering::Release);
+
+		for reader in readers {
+			reader.join().unwrap();
+		}
+		metadata_writer.join().unwrap();
+
+		assert_eq!(world.player_position(player_id), Some(last_position));
+		assert_eq!(world.player_state(player_id).unwrap().position, last_position);
+	}
+
+	struct MovingEntity {
+		position: Position,
+		velocity: Vec3,
+	}
+
+	impl Entity for MovingEntity {
+		fn position(&self) -> Position {
+			self.position
+		}
+
+		fn tick(&mut self, _world: &World, delta: Duration) {
+			self.position.0 += self.velocity * delta.as_secs_f32();
+		}
+	}
+
+	#[test]
+	fn entities_in_aabb_matches_all_entities() {
+		let world = World::new_test().unwrap();
+		// entities only move in loaded chunks, these are around where they start
+		for chunk in ChunkPos::iter_range(ChunkPos::splat(-4), ChunkPos::splat(4)) {
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, Air::new().into())));
+		}
+
+		// random numbers in -1.0..1.0
+		let mut rng = DeterministicRng::for_test(54321);
+		let mut next = || rng.next_f64() as f32 * 2.0 - 1.0;
+
+		for _ in 0..500 {
+			world.add_entity(Box::new(MovingEntity {
+				position: Position::new(100.0 * next(), 100.0 * next(), 100.0 * next()),
+				velocity: Vec3::new(20.0 * next(), 20.0 * next(), 20.0 * next()),
+			}));
+		}
+
+		let queries = [
+			Aabb::new(Vec3::splat(-10.0), Vec3::splat(20.0)),
+			Aabb::new(Vec3::new(-100.0, -5.0, 30.0), Vec3::new(200.0, 10.0, 45.5)),
+			Aabb::new(Vec3::new(3.5, -70.25, -90.0), Vec3::new(60.0, 80.0, 7.0)),
+			Aabb::new(Vec3::splat(-200.0), Vec3::splat(400.0)),
+		];
+
+		let check_queries = |world: &World| {
+			for aabb in queries {
+				let mut found = world.entities_in_aabb(aabb);
+				found.sort();
+
+				let mut expected = world.entities.read().iter()
+					.filter(|(_, entity)| aabb.contains_point(entity.position().0))
+					.map(|(id, _)| *id)
+					.collect::<Vec<_>>();
+				expected.sort();
+
+				assert_eq!(found, expected);
+			}
+		};
+
+		check_queries(&world);
+
+		// entities move between chunks, and the buckets must follow them
+		world.set_tick_time(Duration::from_millis(500));
+		for tick in 0..10 {
+			world.tick(tick);
+			check_queries(&world);
+		}
+
+		// every entity is in the bucket of its chunk exactly once
+		let bucket_count: usize = world.entity_chunks.iter().map(|chunk_entities| chunk_entities.len()).sum();
+		assert_eq!(bucket_count, 500);
+		world.close();
+	}
+
+	// the tick an item dropped from 40 blocks up lands on the ground, with frames every frame_time
+	fn item_landing_tick(frame_time: Duration) -> u64 {
+		let world = World::new_test().unwrap();
+		for chunk in ChunkPos::iter_range(ChunkPos::new(0, -1, 0), ChunkPos::new(1, 3, 1)) {
+			let block = if chunk.y < 0 { Stone::new().into() } else { Air::new().into() };
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, block)));
+		}
+
+		let start = Instant::now();
+		let mut clock = TickClock::new(DEFAULT_TICK_RATE, start);
+		world.set_tick_time(clock.tick_time());
+		let item = world.add_entity(Box::new(Item::new(Position::new(8.5, 40.0, 8.5), BlockType::Sand, 1)));
+
+		let mut last_drawn_y = 40.0;
+		for frame in 1..10_000 {
+			for tick in clock.update(start + frame_time * frame) {
+				world.tick(tick);
+				if world.entity_position(item).unwrap().y == 0.0 {
+					world.close();
+					return tick;
+				}
+			}
+
+			// between ticks the item is drawn part of the way to where it is, so it never jumps back up
+			let drawn_y = world.entity_render_position(item, clock.alpha()).unwrap().y;
+			assert!(drawn_y <= last_drawn_y, "item drawn at {} after {}", drawn_y, last_drawn_y);
+			last_drawn_y = drawn_y;
+		}
+		panic!("item never landed");
+	}
+
+	#[test]
+	fn item_falls_the_same_at_any_frame_rate() {
+		let at_30_fps = item_landing_tick(Duration::from_micros(33_333));
+		let at_144_fps = item_landing_tick(Duration::from_micros(6_944));
+		assert_eq!(at_30_fps, at_144_fps);
+		assert!(at_30_fps > 5, "item landed after {} ticks", at_30_fps);
+	}
+
+	#[test]
+	fn stacked_items_spread_out_and_come_to_rest() {
+		let world = World::new_test().unwrap();
+		for chunk in ChunkPos::iter_range(ChunkPos::new(0, -1, 0), ChunkPos::new(1, 1, 1)) {
+			let block = if chunk.y < 0 { Stone::new().into() } else { Air::new().into() };
+			world.chunks.insert(chunk, LoadedChunk::new(Chunk::filled(world.clone(), chunk, block)));
+		}
+		world.set_tick_time(Duration::from_millis(50));
+
+		let items = (0..10)
+			.map(|_| world.add_entity(Box::new(Item::new(Position::new(8.5, 0.0, 8.5), BlockType::Sand, 1))))
+			.collect::<Vec<_>>();
+		let positions = |world: &World| items.iter()
+			.map(|item| world.entity_position(*item).unwrap())
+			.collect::<Vec<_>>();
+
+		// the stack is pushed apart a little each tick, instead of all at once
+		world.tick(0);
+		assert!(positions(&world).iter().all(|position| position.distance(Vec3::new(8.5, 0.0, 8.5)) <= MAX_PUSH_PER_TICK + 0.0001));
+
+		for tick in 1..200 {
+			world.tick(tick);
+		}
+		let spread = positions(&world);
+		world.tick(200);
+		assert_eq!(positions(&world), spread);
+
+		let entities = world.entities.read();
+		for (i, first) in items.iter().enumerate() {
+			let first_box = entities[first].bounding_box().unwrap();
+			for second in &items[i + 1..] {
+				assert_eq!(push_out(first_box, entities[second].bounding_box().unwrap(), [*first, *second]), None);
+			}
+		}
+		drop(entities);
+
+		// still on top of the same block
+		for position in spread {
+			assert_eq!(position.as_block_pos(), BlockPos::new(8, 0, 8), "item moved to {:?}", position);
+		}
+		world.close();
+	}
+
+	#[test]
+	fn random_tick_seed_is_deterministic() {
+		let world = World::new_test().unwrap();
+		let chunk = ChunkPos::new(3, -1, 8);
+
+		assert_eq!(world.random_tick_seed(100, chunk), world.random_tick_seed(100, chunk));
+		assert_ne!(world.random_tick_seed(100, chunk), world.random_tick_seed(101, chunk));
+		assert_ne!(world.random_tick_seed(100, chunk), world.random_tick_seed(100, ChunkPos::new(3, -1, 9)));
+	}
 }
@@ -0,0 +1,279 @@
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context};
+use rustc_hash::FxHasher;
+
+use crate::prelude::*;
+use super::config::Config;
+use super::worldgen::{GeneratorType, GeneratorOptions};
+
+// each world is a directory inside this one, relative to the working directory
+pub const WORLDS_DIRECTORY: &str = "worlds";
+// metadata file inside each world directory, directories without one are not worlds
+pub(super) const META_FILE: &str = "world.cfg";
+// saved in the metadata, older worlds are loaded with the generator defaults of the version they were saved with
+// 0 is worlds saved before the version was, 1 added generator options
+pub const WORLD_FORMAT_VERSION: u32 = 1;
+
+// what is shown about a world in the world list, and what is needed to create its generator
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldMeta {
+	pub name: String,
+	pub seed: u32,
+	pub generator: GeneratorType,
+	pub generator_options: GeneratorOptions,
+	// seconds since the unix epoch, 0 if the world was never played
+	pub last_played: u64,
+}
+
+impl WorldMeta {
+	fn load(directory: &Path) -> Result<Self> {
+		let file = directory.join(META_FILE);
+		if !file.is_file() {
+			return Err(anyhow!("{} is missing", file.display()));
+		}
+		let config = Config::load(file);
+
+		let name = config.get("name").ok_or_else(|| anyhow!("world has no name"))?;
+		let seed = config.get("seed").ok_or_else(|| anyhow!("world has no seed"))?
+			.parse().context("invalid seed")?;
+		let generator = config.get("generator")
+			.and_then(GeneratorType::from_name)
+			.ok_or_else(|| anyhow!("invalid generator type"))?;
+		let last_played = config.get("last_played").and_then(|time| time.parse().ok()).unwrap_or(0);
+
+		let format_version = match config.get("format_version") {
+			Some(version) => version.parse().context("invalid format version")?,
+			None => 0,
+		};
+		// options missing from the file are what that version of the game would have used
+		let defaults = GeneratorOptions::defaults_for_version(format_version);
+		let option = |key: &str, default: f64| -> Result<f64> {
+			match config.get(key) {
+				Some(value) => value.parse().with_context(|| format!("invalid {}", key)),
+				None => Ok(default),
+			}
+		};
+		let generator_options = GeneratorOptions {
+			terrain_scale: option("terrain_scale", defaults.terrain_scale)?,
+			biome_scale: option("biome_scale", defaults.biome_scale)?,
+			cave_scale: option("cave_scale", defaults.cave_scale)?,
+			cave_threshold: option("cave_threshold", defaults.cave_threshold)?,
+			underground_biome_scale: option("underground_biome_scale", defaults.underground_biome_scale)?,
+		};
+
+		Ok(WorldMeta {
+			name: name.to_owned(),
+			seed,
+			generator,
+			generator_options,
+			last_played,
+		})
+	}
+
+	fn save(&self, directory: &Path) -> Result<()> {
+		let mut config = Config::load(directory.join(META_FILE));
+		config.set("name", Some(&self.name));
+		config.set("seed", Some(&self.seed.to_string()));
+		config.set("generator", Some(self.generator.name()));
+		config.set("last_played", Some(&self.last_played.to_string()));
+		config.set("format_version", Some(&WORLD_FORMAT_VERSION.to_string()));
+
+		let options = &self.generator_options;
+		config.set("terrain_scale", Some(&options.terrain_scale.to_string()));
+		config.set("biome_scale", Some(&options.biome_scale.to_string()));
+		config.set("cave_scale", Some(&options.cave_scale.to_string()));
+		config.set("cave_threshold", Some(&options.cave_threshold.to_string()));
+		config.set("underground_biome_scale", Some(&options.underground_biome_scale.to_string()));
+		config.save()
+	}
+}
+
+// a world found in the worlds directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldEntry {
+	pub directory: PathBuf,
+	pub meta: WorldMeta,
+}
+
+impl WorldEntry {
+	// the world saved in directory, fails if its metadata can't be read
+	pub fn load(directory: &Path) -> Result<Self> {
+		Ok(WorldEntry {
+			directory: directory.to_owned(),
+			meta: WorldMeta::load(directory)?,
+		})
+	}
+
+	// sets the last played time to now
+	pub fn mark_played(&mut self) -> Result<()> {
+		self.meta.last_played = unix_time();
+		self.meta.save(&self.directory)
+	}
+}
+
+fn unix_time() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+// every world in the worlds directory, most recently played first
+// worlds whose metadata can't be read are skipped
+pub fn list_worlds(worlds_directory: &Path) -> Vec<WorldEntry> {
+	let entries = match fs::read_dir(worlds_directory) {
+		Ok(entries) => entries,
+		Err(_) => return Vec::new(),
+	};
+
+	let mut worlds = entries
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.path().is_dir())
+		.filter_map(|entry| {
+			let directory = entry.path();
+			match WorldEntry::load(&directory) {
+				Ok(world) => Some(world),
+				Err(error) => {
+					warn!("skipping world {}: {:#}", directory.display(), error);
+					None
+				},
+			}
+		})
+		.collect::<Vec<_>>();
+
+	worlds.sort_by(|a, b| b.meta.last_played.cmp(&a.meta.last_played).then_with(|| a.meta.name.cmp(&b.meta.name)));
+	worlds
+}
+
+// the directory is named after the world, with a number added if that name is taken
+pub fn create_world(
+	worlds_directory: &Path,
+	name: &str,
+	seed: u32,
+	generator: GeneratorType,
+	generator_options: GeneratorOptions,
+) -> Result<WorldEntry> {
+	let name = name.trim();
+	if name.is_empty() {
+		return Err(anyhow!("world name is empty"));
+	}
+	fs::create_dir_all(worlds_directory)?;
+
+	let base_name = directory_name(name);
+	let mut directory = worlds_directory.join(&base_name);
+	let mut number = 2;
+	while directory.exists() {
+		directory = worlds_directory.join(format!("{}-{}", base_name, number));
+		number += 1;
+	}
+	fs::create_dir(&directory)?;
+
+	let meta = WorldMeta {
+		name: name.to_owned(),
+		seed,
+		generator,
+		generator_options,
+		last_played: 0,
+	};
+	meta.save(&directory)?;
+
+	Ok(WorldEntry {
+		directory,
+		meta,
+	})
+}
+
+// removes the world's directory and everything saved in it
+pub fn delete_world(world: &WorldEntry) -> Result<()> {
+	fs::remove_dir_all(&world.directory)
+		.with_context(|| format!("could not delete {}", world.directory.display()))
+}
+
+// characters which aren't safe in file names on every platform are replaced
+fn directory_name(name: &str) -> String {
+	let directory_name = name.chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+		.collect::<String>();
+
+	if directory_name.chars().all(|c| c == '_') {
+		String::from("world")
+	} else {
+		directory_name
+	}
+}
+
+// a number is used as the seed directly, other text is hashed, and an empty seed is random
+pub fn seed_from_text(text: &str) -> u32 {
+	let text = text.trim();
+	if text.is_empty() {
+		return SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.subsec_nanos() ^ time.as_secs() as u32);
+	}
+
+	text.parse().unwrap_or_else(|_| {
+		let mut hasher = FxHasher::default();
+		text.hash(&mut hasher);
+		hasher.finish() as u32
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn create_list_and_delete_worlds() {
+		let worlds_directory = std::env::temp_dir().join(format!("minecone-world-list-test-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&worlds_directory);
+		assert!(list_worlds(&worlds_directory).is_empty());
+
+		let mut first = create_world(&worlds_directory, " My World! ", 42, GeneratorType::Normal, GeneratorOptions::default()).unwrap();
+		let second = create_world(&worlds_directory, "My World?", 7, GeneratorType::Flat, GeneratorOptions::default()).unwrap();
+		assert_eq!(first.directory, worlds_directory.join("My_World_"));
+		assert_eq!(second.directory, worlds_directory.join("My_World_-2"));
+		assert_eq!(first.meta.name, "My World!");
+		assert!(create_world(&worlds_directory, "  ", 0, GeneratorType::Normal, GeneratorOptions::default()).is_err());
+
+		// directories without metadata aren't worlds
+		fs::create_dir(worlds_directory.join("not a world")).unwrap();
+
+		// most recently played is first
+		first.mark_played().unwrap();
+		let worlds = list_worlds(&worlds_directory);
+		assert_eq!(worlds, vec![first.clone(), second.clone()]);
+
+		delete_world(&first).unwrap();
+		assert_eq!(list_worlds(&worlds_directory), vec![second]);
+
+		fs::remove_dir_all(&worlds_directory).unwrap();
+	}
+
+	#[test]
+	fn worlds_without_options_use_defaults_of_their_version() {
+		let directory = std::env::temp_dir().join(format!("minecone-world-meta-test-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&directory);
+		fs::create_dir_all(&directory).unwrap();
+
+		// saved before options or a format version were
+		fs::write(directory.join(META_FILE), "name = Old World\nseed = 3\ngenerator = normal\n").unwrap();
+		let meta = WorldMeta::load(&directory).unwrap();
+		assert_eq!(meta.generator_options, GeneratorOptions::defaults_for_version(0));
+
+		// once saved, the options are in the file, so they don't depend on the defaults any more
+		meta.save(&directory).unwrap();
+		let config = Config::load(directory.join(META_FILE));
+		assert_eq!(config.get("format_version"), Some(WORLD_FORMAT_VERSION.to_string().as_str()));
+		assert_eq!(config.get("cave_threshold"), Some(meta.generator_options.cave_threshold.to_string().as_str()));
+		assert_eq!(WorldMeta::load(&directory).unwrap(), meta);
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn seeds_from_text() {
+		assert_eq!(seed_from_text("12345"), 12345);
+		assert_eq!(seed_from_text(" 7 "), 7);
+		assert_eq!(seed_from_text("glacier"), seed_from_text("glacier"));
+		assert_ne!(seed_from_text("glacier"), seed_from_text("desert"));
+	}
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use image::RgbImage;
+
+use super::map_export::{surface_map, MapProgress};
+use super::player::DEFAULT_SPAWN_POINT;
+use super::worldgen::{WorldGenerator, GeneratorType, GeneratorOptions};
+
+// the preview is 2 * PREVIEW_RADIUS + 1 columns wide, centered on the spawn point
+pub const PREVIEW_RADIUS: i32 = 64;
+// the seed and generator the preview is made with
+type PreviewSettings = (u32, GeneratorType, GeneratorOptions);
+
+// the settings have to stay the same for this long before a preview is made, so typing doesn't start one per key
+const PREVIEW_DELAY: Duration = Duration::from_millis(300);
+
+// a preview being made on its own thread
+struct PreviewJob {
+	settings: PreviewSettings,
+	progress: Arc<MapProgress>,
+	thread: JoinHandle<Option<RgbImage>>,
+}
+
+impl PreviewJob {
+	fn start(settings: PreviewSettings) -> Self {
+		let (seed, generator_type, options) = settings;
+		let progress = Arc::new(MapProgress::default());
+		let thread_progress = progress.clone();
+		let thread = thread::Builder::new()
+			.name(String::from("world preview"))
+			.spawn(move || {
+				let generator = WorldGenerator::new(seed, generator_type, options);
+				let spawn_point = DEFAULT_SPAWN_POINT.as_block_pos();
+				surface_map(&generator, spawn_point.x, spawn_point.z, PREVIEW_RADIUS, &thread_progress)
+			})
+			.expect("could not start world preview thread");
+
+		PreviewJob {
+			settings,
+			progress,
+			thread,
+		}
+	}
+
+	// the thread stops after the row it is on, and its result is never used
+	fn cancel(self) {
+		self.progress.cancelled.store(true, Ordering::Relaxed);
+	}
+}
+
+// a surface map of a world which hasn't been created yet, remade whenever its seed or generator changes
+pub struct WorldPreview {
+	// the seed and generator the preview should show, and when they were last changed
+	wanted: Option<PreviewSettings>,
+	changed_time: Instant,
+	job: Option<PreviewJob>,
+	// the seed and generator of the last finished preview
+	shown: Option<PreviewSettings>,
+}
+
+impl WorldPreview {
+	pub fn new() -> Self {
+		WorldPreview {
+			wanted: None,
+			changed_time: Instant::now(),
+			job: None,
+			shown: None,
+		}
+	}
+
+	// true if the last finished preview is for what was last asked for
+	pub fn is_current(&self) -> bool {
+		self.wanted.is_some() && self.wanted == self.shown
+	}
+
+	// called every frame with the seed in the dialog, or none if there isn't one to preview
+	// returns the preview when it is finished
+	pub fn update(&mut self, seed: Option<u32>, generator_type: GeneratorType, options: GeneratorOptions, now: Instant) -> Option<RgbImage> {
+		let wanted = seed.map(|seed| (seed, generator_type, options));
+		if wanted != self.wanted {
+			self.wanted = wanted;
+			self.changed_time = now;
+			if let Some(job) = self.job.take() {
+				job.cancel();
+			}
+		}
+
+		if let Some(job) = &self.job {
+			if !job.thread.is_finished() {
+				return None;
+			}
+
+			let job = self.job.take().unwrap();
+			let image = job.thread.join().ok().flatten()?;
+			self.shown = Some(job.settings);
+			return Some(image);
+		}
+
+		if let Some(settings) = self.wanted {
+			if !self.is_current() && now >= self.changed_time + PREVIEW_DELAY {
+				self.job = Some(PreviewJob::start(settings));
+			}
+		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// updates the preview until it returns an image, or panics if it takes too long
+	fn wait_for_preview(preview: &mut WorldPreview, seed: u32, start: Instant) -> RgbImage {
+		for i in 0..2000 {
+			if let Some(image) = preview.update(Some(seed), GeneratorType::Normal, GeneratorOptions::default(), start + PREVIEW_DELAY + Duration::from_millis(i)) {
+				return image;
+			}
+			thread::sleep(Duration::from_millis(5));
+		}
+		panic!("preview for seed {} was never finished", seed);
+	}
+
+	#[test]
+	fn preview_waits_for_the_seed_to_settle() {
+		let mut preview = WorldPreview::new();
+		let start = Instant::now();
+
+		// typing a seed keeps pushing the preview back
+		for (i, seed) in [1, 12, 123].into_iter().enumerate() {
+			assert!(preview.update(Some(seed), GeneratorType::Normal, GeneratorOptions::default(), start + Duration::from_millis(100 * i as u64)).is_none());
+			assert!(preview.job.is_none());
+		}
+
+		let image = wait_for_preview(&mut preview, 123, start);
+		let size = 2 * PREVIEW_RADIUS as u32 + 1;
+		assert_eq!(image.dimensions(), (size, size));
+		assert!(preview.is_current());
+
+		// nothing is made again for the same seed
+		assert!(preview.update(Some(123), GeneratorType::Normal, GeneratorOptions::default(), start + Duration::from_secs(60)).is_none());
+		assert!(preview.job.is_none());
+	}
+
+	#[test]
+	fn changing_the_seed_cancels_the_preview() {
+		let mut preview = WorldPreview::new();
+		let start = Instant::now();
+
+		preview.update(Some(5), GeneratorType::Normal, GeneratorOptions::default(), start);
+		preview.update(Some(5), GeneratorType::Normal, GeneratorOptions::default(), start + PREVIEW_DELAY);
+		let progress = preview.job.as_ref().unwrap().progress.clone();
+
+		preview.update(Some(6), GeneratorType::Normal, GeneratorOptions::default(), start + PREVIEW_DELAY);
+		assert!(progress.cancelled.load(Ordering::Relaxed));
+		assert!(!preview.is_current());
+
+		wait_for_preview(&mut preview, 6, start + PREVIEW_DELAY);
+		assert_eq!(preview.shown, Some((6, GeneratorType::Normal, GeneratorOptions::default())));
+	}
+}
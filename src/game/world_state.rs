@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+use crate::prelude::*;
+use super::config::Config;
+use super::world_list::META_FILE;
+
+// length of a full day and night, 20 minutes at the default tick rate
+pub const DAY_LENGTH: u64 = 24000;
+// the sun is at its default direction at time 0, and on the other side of the world half a day later
+pub const DAY_TIME: u64 = 0;
+pub const NIGHT_TIME: u64 = DAY_LENGTH / 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weather {
+	Clear,
+	Rain,
+}
+
+impl Weather {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Clear => "clear",
+			Self::Rain => "rain",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"clear" => Some(Self::Clear),
+			"rain" => Some(Self::Rain),
+			_ => None,
+		}
+	}
+}
+
+// time and weather shared by everyone in the world, saved with the world's metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldState {
+	// ticks since the start of the current day, always less than DAY_LENGTH
+	time_of_day: u64,
+	pub weather: Weather,
+}
+
+impl Default for WorldState {
+	fn default() -> Self {
+		WorldState {
+			time_of_day: DAY_TIME,
+			weather: Weather::Clear,
+		}
+	}
+}
+
+impl WorldState {
+	pub fn time_of_day(&self) -> u64 {
+		self.time_of_day
+	}
+
+	// times past the end of the day wrap around to the following days
+	pub fn set_time_of_day(&mut self, time_of_day: u64) {
+		self.time_of_day = time_of_day % DAY_LENGTH;
+	}
+
+	// goes from 0 to 1 over a day
+	pub fn day_fraction(&self) -> f32 {
+		self.time_of_day as f32 / DAY_LENGTH as f32
+	}
+
+	// called once per world tick
+	pub fn advance(&mut self) {
+		self.set_time_of_day(self.time_of_day + 1);
+	}
+
+	// worlds saved before there was a world state start at the default state
+	pub fn load(directory: &Path) -> Result<Self> {
+		let config = Config::load(directory.join(META_FILE));
+
+		let time_of_day = match config.get("time_of_day") {
+			Some(time) => time.parse().context("invalid time of day")?,
+			None => DAY_TIME,
+		};
+		let weather = match config.get("weather") {
+			Some(weather) => Weather::from_name(weather).ok_or_else(|| anyhow!("invalid weather {}", weather))?,
+			None => Weather::Clear,
+		};
+
+		let mut state = WorldState {
+			time_of_day: 0,
+			weather,
+		};
+		state.set_time_of_day(time_of_day);
+		Ok(state)
+	}
+
+	// the rest of the world's metadata is kept, the directory is created if it doesn't exist
+	pub fn save(&self, directory: &Path) -> Result<()> {
+		fs::create_dir_all(directory)?;
+
+		let mut config = Config::load(directory.join(META_FILE));
+		config.set("time_of_day", Some(&self.time_of_day.to_string()));
+		config.set("weather", Some(self.weather.name()));
+		config.save()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn world_state_round_trip() {
+		let directory = std::env::temp_dir().join(format!("minecone-world-state-test-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&directory);
+
+		// nothing saved yet
+		assert_eq!(WorldState::load(&directory).unwrap(), WorldState::default());
+
+		let mut state = WorldState::default();
+		state.set_time_of_day(DAY_LENGTH + 500);
+		state.weather = Weather::Rain;
+		assert_eq!(state.time_of_day(), 500);
+
+		// other metadata in the file is kept
+		let mut config = Config::load(directory.join(META_FILE));
+		config.set("name", Some("test world"));
+		fs::create_dir_all(&directory).unwrap();
+		config.save().unwrap();
+
+		state.save(&directory).unwrap();
+		assert_eq!(WorldState::load(&directory).unwrap(), state);
+		assert_eq!(Config::load(directory.join(META_FILE)).get("name"), Some("test world"));
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn time_wraps_at_end_of_day() {
+		let mut state = WorldState::default();
+		state.set_time_of_day(DAY_LENGTH - 1);
+		state.advance();
+		assert_eq!(state.time_of_day(), 0);
+		assert_eq!(state.day_fraction(), 0.0);
+	}
+}
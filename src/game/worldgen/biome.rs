@@ -116,13 +116,52 @@ pub enum SeaBiome {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UndergroundBiome {
+	// no caves
 	SolidGround,
 	Caverns,
+	// caverns with grass on the floor
 	LushCaverns,
+	// caverns filled with water below the water table
 	UndergroundLake,
+	// lush caverns which are also filled with water below the water table
 	FloodedCaverns,
 }
 
+impl UndergroundBiome {
+	// noise is the underground biome noise, which is roughly between -1 and 1
+	pub fn from_noise(noise: f64) -> Self {
+		match noise {
+			noise if noise < -0.2 => Self::SolidGround,
+			noise if noise < 0.0 => Self::Caverns,
+			noise if noise < 0.15 => Self::LushCaverns,
+			noise if noise < 0.3 => Self::UndergroundLake,
+			_ => Self::FloodedCaverns,
+		}
+	}
+
+	pub fn has_caves(&self) -> bool {
+		*self != Self::SolidGround
+	}
+
+	pub fn has_lush_floor(&self) -> bool {
+		matches!(self, Self::LushCaverns | Self::FloodedCaverns)
+	}
+
+	pub fn is_flooded(&self) -> bool {
+		matches!(self, Self::UndergroundLake | Self::FloodedCaverns)
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::SolidGround => "solid ground",
+			Self::Caverns => "caverns",
+			Self::LushCaverns => "lush caverns",
+			Self::UndergroundLake => "underground lake",
+			Self::FloodedCaverns => "flooded caverns",
+		}
+	}
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnderworldBiome {
 }
@@ -0,0 +1,121 @@
+use std::ops::Range;
+
+use array_init::array_init;
+
+use crate::prelude::*;
+use crate::game::block::{Block, Air, Grass, Water};
+use super::biome::UndergroundBiome;
+use super::rng::DeterministicRng;
+
+// caves are never carved closer to the surface than this, so the surface and the layers under it stay whole
+pub const CAVE_MIN_DEPTH: i32 = 8;
+// noise is sampled every this many blocks and interpolated between
+const SAMPLE_STEP: i32 = 4;
+const GRID_SIZE: usize = CHUNK_SIZE / SAMPLE_STEP as usize + 1;
+// water tables are the same height over squares this many blocks wide
+// this is a multiple of the chunk size, so each chunk only has 1 water table
+const WATER_TABLE_CELL_SIZE: i32 = 128;
+const WATER_TABLE_HEIGHTS: Range<i32> = -96..-16;
+const WATER_TABLE_SALT: u64 = 1;
+
+// noise sampled on a grid over a chunk and interpolated between, 3d noise for every block would be too slow
+pub struct NoiseGrid {
+	values: [[[f64; GRID_SIZE]; GRID_SIZE]; GRID_SIZE],
+}
+
+impl NoiseGrid {
+	// sample is given the position of each grid point
+	pub fn new<F: FnMut(BlockPos) -> f64>(chunk_block: BlockPos, mut sample: F) -> Self {
+		NoiseGrid {
+			values: array_init(|x| array_init(|y| array_init(|z| {
+				sample(chunk_block + BlockPos::new(x as i32, y as i32, z as i32) * SAMPLE_STEP)
+			}))),
+		}
+	}
+
+	// interpolated values are never above this
+	pub fn max(&self) -> f64 {
+		self.values.iter().flatten().flatten().copied().fold(f64::NEG_INFINITY, f64::max)
+	}
+
+	// local coordinates go from 0 to CHUNK_SIZE, inclusive, so the blocks around a chunk's edge can be checked
+	pub fn get(&self, local: BlockPos) -> f64 {
+		let cell = local.0.map(|elem| (elem / SAMPLE_STEP).min(GRID_SIZE as i32 - 2));
+		let t = (local.0 - cell * SAMPLE_STEP).as_dvec3() / SAMPLE_STEP as f64;
+		let [x, y, z] = cell.to_array().map(|elem| elem as usize);
+
+		let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+		let along_z = |x: usize, y: usize| lerp(self.values[x][y][z], self.values[x][y][z + 1], t.z);
+		let along_y = |x: usize| lerp(along_z(x, y), along_z(x, y + 1), t.y);
+		lerp(along_y(x), along_y(x + 1), t.x)
+	}
+}
+
+// height of the water table flooded caves are filled up to
+pub fn water_table(seed: u32, chunk_block: BlockPos) -> i32 {
+	let cell_x = chunk_block.x.div_euclid(WATER_TABLE_CELL_SIZE);
+	let cell_z = chunk_block.z.div_euclid(WATER_TABLE_CELL_SIZE);
+	DeterministicRng::for_column(seed, WATER_TABLE_SALT, cell_x, cell_z).range(WATER_TABLE_HEIGHTS)
+}
+
+// carves caves into a chunk, and fills them based on the underground biome
+pub struct ChunkCaves {
+	pub carve_noise: NoiseGrid,
+	pub biome_noise: NoiseGrid,
+	pub water_table: i32,
+	// blocks where the carve noise is above this are carved out
+	pub threshold: f64,
+}
+
+impl ChunkCaves {
+	// true if no block in the chunk is carved
+	pub fn is_empty(&self) -> bool {
+		self.carve_noise.max() <= self.threshold
+	}
+
+	pub fn biome_at(&self, local: BlockPos) -> UndergroundBiome {
+		UndergroundBiome::from_noise(self.biome_noise.get(local))
+	}
+
+	// depth is how far the block is below the top of its column
+	fn is_carved(&self, local: BlockPos, depth: i32) -> bool {
+		depth >= CAVE_MIN_DEPTH
+			&& self.carve_noise.get(local) > self.threshold
+			&& self.biome_at(local).has_caves()
+	}
+
+	// generated_block is what the block would be without caves
+	pub fn block_at(&self, local: BlockPos, block_y: i32, depth: i32, generated_block: Block) -> Block {
+		let biome = self.biome_at(local);
+
+		if self.is_carved(local, depth) {
+			if biome.is_flooded() && block_y <= self.water_table {
+				Water::new().into()
+			} else {
+				Air::new().into()
+			}
+		} else if biome.has_lush_floor() && self.is_carved(local + BlockPos::new(0, 1, 0), depth - 1) {
+			Grass::new().into()
+		} else {
+			generated_block
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn noise_grid_interpolates_between_samples() {
+		let grid = NoiseGrid::new(BlockPos::new(32, -64, 0), |block| (block.x + 2 * block.y + 3 * block.z) as f64);
+
+		// a linear function is interpolated exactly
+		for local in [BlockPos::new(0, 0, 0), BlockPos::new(5, 17, 31), BlockPos::new(32, 32, 32), BlockPos::new(3, 30, 9)] {
+			let block = local + BlockPos::new(32, -64, 0);
+			assert!((grid.get(local) - (block.x + 2 * block.y + 3 * block.z) as f64).abs() < 1e-9, "at {:?}", local);
+		}
+		// the largest value is at the far corner of the grid, x = 64, y = -32, z = 32, so 64 - 2 * 32 + 3 * 32
+		assert_eq!(grid.max(), 96.0);
+	}
+}
@@ -0,0 +1,86 @@
+use glam::IVec2;
+use smallvec::SmallVec;
+
+use crate::game::block::Block;
+use super::rng::DeterministicRng;
+
+// decorations in a column can cover columns up to this far away on the x and z axis,
+// so generating a chunk has to check columns this far outside of it
+pub const MAX_DECORATION_REACH: i32 = 1;
+
+// columns next to a scattered decoration which the rest of its clump can be on
+const SCATTER_OFFSETS: [IVec2; 4] = [
+	IVec2::new(1, 0),
+	IVec2::new(-1, 0),
+	IVec2::new(0, 1),
+	IVec2::new(0, -1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationPlacement {
+	// a single block on top of the column
+	SurfaceOnly,
+	// a clump of 1 to 3 blocks, each on top of its own column, so none of them float over a drop
+	Scatter,
+}
+
+// a block placed on top of the surface in a biome
+#[derive(Debug)]
+pub struct Decoration {
+	pub block: Block,
+	// chance of the decoration starting in any column
+	pub frequency: f32,
+	pub placement: DecorationPlacement,
+}
+
+impl Decoration {
+	// the columns covered by the decoration if it starts in column, or none if it doesn't
+	// rng must only depend on the column, so every chunk the decoration is in places the same blocks
+	pub fn columns(&self, rng: &mut DeterministicRng, column: IVec2) -> SmallVec<[IVec2; 3]> {
+		let mut out = SmallVec::new();
+		if !rng.chance(self.frequency as f64) {
+			return out;
+		}
+
+		out.push(column);
+		if self.placement == DecorationPlacement::Scatter {
+			let mut offsets = SCATTER_OFFSETS;
+			rng.shuffle(&mut offsets);
+			let extra_blocks = rng.range(0..3) as usize;
+			out.extend(offsets[..extra_blocks].iter().map(|offset| column + *offset));
+		}
+		out
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::block::Stone;
+
+	#[test]
+	fn decorations_start_at_their_frequency() {
+		let decoration = Decoration {
+			block: Stone::new().into(),
+			frequency: 0.05,
+			placement: DecorationPlacement::Scatter,
+		};
+
+		let mut started = 0;
+		for x in 0..200 {
+			for z in 0..200 {
+				let column = IVec2::new(x, z);
+				let columns = decoration.columns(&mut DeterministicRng::for_column(0, 0, x, z), column);
+				if !columns.is_empty() {
+					started += 1;
+					assert_eq!(columns[0], column);
+					assert!(columns.len() <= 3);
+					assert!(columns.iter().all(|covered| (*covered - column).abs().max_element() <= MAX_DECORATION_REACH));
+				}
+			}
+		}
+
+		// 2000 expected, the standard deviation is about 44
+		assert!((1800..2200).contains(&started), "{} decorations started", started);
+	}
+}
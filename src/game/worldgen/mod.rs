@@ -1,30 +1,123 @@
 use std::sync::Arc;
 
 use noise::{Seedable, NoiseFn, OpenSimplex};
-use parking_lot::RwLock;
+use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
-use glam::{IVec2, Vec3Swizzles};
+use glam::IVec2;
 use statrs::function::erf::erf;
+use array_init::array_init;
 
 use crate::prelude::*;
-use biome::{SurfaceBiome, BiomeNoiseData};
-use surface_biome::SurfaceBiomeMap;
-use super::chunk::{Chunk, LoadedChunk};
+pub use biome::{BiomeNoiseData, UndergroundBiome};
+pub use surface_biome::{SurfaceBiome, SurfaceBiomeMap, BIOME_MAP_SIZE, MAX_LAYER_JITTER};
+pub use rng::DeterministicRng;
+pub use decoration::{Decoration, DecorationPlacement};
+use decoration::MAX_DECORATION_REACH;
+use super::chunk::{Chunk, LoadedChunk, CHUNK_SIZE};
 use super::world::World;
 use super::block::*;
+use caves::{ChunkCaves, NoiseGrid, water_table};
 
 mod biome;
 mod surface_biome;
+mod rng;
+mod caves;
+mod decoration;
 
-type Cache2D = FxHashMap<IVec2, f64>;
 type Cache3D = FxHashMap<BlockPos, f64>;
 
-#[derive(Debug, Default)]
-struct NoiseCache {
-	height_noise: Cache2D,
-	biome_height_noise: Cache2D,
-	biome_heat_noise: Cache2D,
-	biome_humidity_noise: Cache2D,
+// maximum number of columns kept in the surface column cache before it is cleared
+const COLUMN_CACHE_SIZE: usize = 4096;
+
+// salt for the DeterministicRng deciding where decorations go
+const DECORATION_SALT: u64 = 1;
+
+// layer boundaries change over a few blocks, so they undulate instead of looking like random noise
+const LAYER_JITTER_SCALE: f64 = 0.15;
+
+// which terrain a world generates, chosen when the world is created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorType {
+	// hills and biomes from noise
+	Normal,
+	// every column has its surface at y = 0, biomes still change its blocks
+	Flat,
+}
+
+impl GeneratorType {
+	pub const ALL: [GeneratorType; 2] = [GeneratorType::Normal, GeneratorType::Flat];
+
+	// name stored in world metadata
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Normal => "normal",
+			Self::Flat => "flat",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|generator| generator.name() == name)
+	}
+}
+
+// tunable parameters of the generator, saved with each world so it always generates the same terrain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeneratorOptions {
+	// how quickly the terrain height changes, larger is hillier
+	pub terrain_scale: f64,
+	// larger makes smaller surface biomes
+	pub biome_scale: f64,
+	// larger makes smaller, more twisty caves
+	pub cave_scale: f64,
+	// blocks where the cave noise is above this are carved out, smaller makes more caves
+	pub cave_threshold: f64,
+	// larger makes smaller underground biomes
+	pub underground_biome_scale: f64,
+}
+
+// defaults of worlds saved with each format version, indexed by the version
+// worlds saved before options were stored (version 0) use the first entry
+// a new entry is added whenever the defaults change, old entries must never be changed
+const VERSIONED_DEFAULTS: [GeneratorOptions; 2] = [
+	GeneratorOptions {
+		terrain_scale: 0.05,
+		biome_scale: 0.002,
+		cave_scale: 0.04,
+		cave_threshold: 0.25,
+		underground_biome_scale: 0.004,
+	},
+	GeneratorOptions {
+		terrain_scale: 0.05,
+		biome_scale: 0.002,
+		cave_scale: 0.04,
+		cave_threshold: 0.25,
+		underground_biome_scale: 0.004,
+	},
+];
+
+impl GeneratorOptions {
+	// worlds saved by newer versions which are missing options use the newest defaults known
+	pub fn defaults_for_version(format_version: u32) -> Self {
+		VERSIONED_DEFAULTS.get(format_version as usize)
+			.copied()
+			.unwrap_or(VERSIONED_DEFAULTS[VERSIONED_DEFAULTS.len() - 1])
+	}
+}
+
+impl Default for GeneratorOptions {
+	fn default() -> Self {
+		VERSIONED_DEFAULTS[VERSIONED_DEFAULTS.len() - 1]
+	}
+}
+
+// the surface of a single column of blocks
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceColumn {
+	// y coordinate of the top solid block of the terrain, decorations may be on top of it
+	pub height: i32,
+	pub biome: &'static SurfaceBiome,
+	// how far the biome's layer boundaries are moved in this column, see SurfaceBiome::layer_bottoms
+	pub layer_jitter: i32,
 }
 
 struct CachedNoise2D {
@@ -50,9 +143,9 @@ impl CachedNoise2D {
 		}
 	}
 
-	fn get_block_pos(&self, block: BlockPos, cache: &mut Cache2D) -> f64 {
-		*cache.entry(block.0.xz()).or_insert_with(||
-			(self.amplitude_fn)(self.noise.get([block.x as f64 * self.scale, block.z as f64 * self.scale])))
+	// 2d noise only depends on the x and z coordinate of the block
+	fn get_block_pos(&self, block: BlockPos) -> f64 {
+		(self.amplitude_fn)(self.noise.get([block.x as f64 * self.scale, block.z as f64 * self.scale]))
 	}
 }
 
@@ -85,16 +178,25 @@ impl CachedNoise3D {
 	}
 }
 
+// chunks are generated on any worker in any order, so every chunk must only depend on the seed and its position
+// anything random uses DeterministicRng, and nothing generated may depend on which chunks were generated before it
 pub struct WorldGenerator {
 	height_noise: CachedNoise2D,
-	biome_height_noise: CachedNoise2D,
 	biome_heat_noise: CachedNoise2D,
 	biome_humidity_noise: CachedNoise2D,
+	cave_noise: CachedNoise3D,
+	underground_biome_noise: CachedNoise3D,
+	layer_jitter_noise: CachedNoise2D,
 	surface_biome_map: SurfaceBiomeMap,
+	// columns recently queried through surface_column
+	column_cache: Mutex<FxHashMap<IVec2, SurfaceColumn>>,
+	generator_type: GeneratorType,
+	options: GeneratorOptions,
+	seed: u32,
 }
 
 impl WorldGenerator {
-	pub fn new(seed: u32) -> Self {
+	pub fn new(seed: u32, generator_type: GeneratorType, options: GeneratorOptions) -> Self {
 		// TODO: this doesn't make it completely uniform, could be better
 		let biome_make_uniform = |value: f64| {
 			// the varience of opensimplex is about this
@@ -105,24 +207,30 @@ impl WorldGenerator {
 		};
 
 		WorldGenerator {
-			height_noise: CachedNoise2D::new(seed, 0.05),
-			biome_height_noise: CachedNoise2D::new(seed + 1, 0.002),
-			biome_heat_noise: CachedNoise2D::new_amplitude_scaled(seed + 2, 0.002, biome_make_uniform),
-			biome_humidity_noise: CachedNoise2D::new_amplitude_scaled(seed + 3, 0.002, biome_make_uniform),
+			height_noise: CachedNoise2D::new(seed, options.terrain_scale),
+			biome_heat_noise: CachedNoise2D::new_amplitude_scaled(seed + 2, options.biome_scale, biome_make_uniform),
+			biome_humidity_noise: CachedNoise2D::new_amplitude_scaled(seed + 3, options.biome_scale, biome_make_uniform),
+			cave_noise: CachedNoise3D::new(seed.wrapping_add(4), options.cave_scale),
+			underground_biome_noise: CachedNoise3D::new(seed.wrapping_add(5), options.underground_biome_scale),
+			layer_jitter_noise: CachedNoise2D::new(seed.wrapping_add(6), LAYER_JITTER_SCALE),
 			surface_biome_map: SurfaceBiomeMap::new(),
+			column_cache: Mutex::new(FxHashMap::default()),
+			generator_type,
+			options,
+			seed,
 		}
 	}
 
-	fn get_height_noise(&self, block: BlockPos, amplitude: f64, cache: &mut NoiseCache) -> i32 {
-		(amplitude * self.height_noise.get_block_pos(block, &mut cache.height_noise)) as i32
+	fn get_height_noise(&self, block: BlockPos, amplitude: f64) -> i32 {
+		(amplitude * self.height_noise.get_block_pos(block)) as i32
 	}
 
-	fn get_biome_height_noise(&self, block: BlockPos, cache: &mut NoiseCache) -> i32 {
-		let noise = 6.0 * self.biome_height_noise.get_block_pos(block, &mut cache.biome_height_noise);
-		(noise * noise * noise) as i32
+	fn get_layer_jitter(&self, block: BlockPos) -> i32 {
+		let noise = 8.0 * self.layer_jitter_noise.get_block_pos(block);
+		(noise.round() as i32).clamp(-MAX_LAYER_JITTER, MAX_LAYER_JITTER)
 	}
 
-	fn get_biome_noise(&self, block: BlockPos, cache: &mut NoiseCache) -> BiomeNoiseData {
+	fn get_biome_noise(&self, block: BlockPos) -> BiomeNoiseData {
 		// TODO: this doesn't make it completely uniform, could be better
 		let make_uniform = |value: f64| {
 			// the varience of opensimplex is about this
@@ -131,25 +239,439 @@ impl WorldGenerator {
 			(25.0 + 25.0 * uniform).clamp(0.0, 49.0) as u8
 			/*(25.0 + 55.0 * value).clamp(0.0, 49.0) as u8*/
 		};
-		let heat = self.biome_heat_noise.get_block_pos(block, &mut cache.biome_heat_noise) as u8;
-		let humidity = self.biome_humidity_noise.get_block_pos(block, &mut cache.biome_humidity_noise) as u8;
+		let heat = self.biome_heat_noise.get_block_pos(block) as u8;
+		let humidity = self.biome_humidity_noise.get_block_pos(block) as u8;
 		BiomeNoiseData {
 			heat,
 			humidity,
 		}
 	}
 
+	pub fn biome_noise_at(&self, block: BlockPos) -> BiomeNoiseData {
+		self.get_biome_noise(block)
+	}
+
+	pub fn surface_biome_at(&self, block: BlockPos) -> &'static SurfaceBiome {
+		self.surface_biome_map.get_biome(self.biome_noise_at(block))
+	}
+
+	// computes the surface of the column containing block, this is what generate_chunk uses for each column
+	fn compute_surface_column(&self, block: BlockPos) -> SurfaceColumn {
+		let biome = self.surface_biome_at(block);
+		let (height, layer_jitter) = match self.generator_type {
+			GeneratorType::Normal => (self.get_height_noise(block, biome.height_amplitude), self.get_layer_jitter(block)),
+			GeneratorType::Flat => (0, 0),
+		};
+
+		SurfaceColumn {
+			height,
+			biome,
+			layer_jitter,
+		}
+	}
+
+	// returns the surface of the column at x and z without generating any chunks
+	pub fn surface_column(&self, x: i32, z: i32) -> SurfaceColumn {
+		let column = IVec2::new(x, z);
+
+		if let Some(surface_column) = self.column_cache.lock().get(&column) {
+			return *surface_column;
+		}
+
+		// don't hold the lock while computing noise
+		let surface_column = self.compute_surface_column(BlockPos::new(x, 0, z));
+
+		let mut column_cache = self.column_cache.lock();
+		if column_cache.len() >= COLUMN_CACHE_SIZE {
+			column_cache.clear();
+		}
+		column_cache.insert(column, surface_column);
+
+		surface_column
+	}
+
+	// same as surface_column, but without the cache, for queries over areas much larger than it
+	pub fn surface_column_uncached(&self, x: i32, z: i32) -> SurfaceColumn {
+		self.compute_surface_column(BlockPos::new(x, 0, z))
+	}
+
+	// the underground biome is used wherever there would be caves, even if there are none at the block
+	pub fn underground_biome_at(&self, block: BlockPos) -> UndergroundBiome {
+		UndergroundBiome::from_noise(self.underground_biome_noise.get_block_pos(block, &mut Cache3D::default()))
+	}
+
+	// flat worlds have no caves
+	fn chunk_caves(&self, chunk_block: BlockPos) -> Option<ChunkCaves> {
+		if self.generator_type == GeneratorType::Flat {
+			return None;
+		}
+
+		let mut cave_cache = Cache3D::default();
+		let mut biome_cache = Cache3D::default();
+		Some(ChunkCaves {
+			carve_noise: NoiseGrid::new(chunk_block, |block| self.cave_noise.get_block_pos(block, &mut cave_cache)),
+			biome_noise: NoiseGrid::new(chunk_block, |block| self.underground_biome_noise.get_block_pos(block, &mut biome_cache)),
+			water_table: water_table(self.seed, chunk_block),
+			threshold: self.options.cave_threshold,
+		})
+	}
+
+	pub fn seed(&self) -> u32 {
+		self.seed
+	}
+
+	pub fn generator_type(&self) -> GeneratorType {
+		self.generator_type
+	}
+
+	pub fn options(&self) -> GeneratorOptions {
+		self.options
+	}
+
+	pub fn surface_biome_map(&self) -> &SurfaceBiomeMap {
+		&self.surface_biome_map
+	}
+
+	// the decoration on top of each column in the chunk, keyed by the column's local x and z
+	// decorations starting in columns next to the chunk are included, so clumps cross chunk borders without
+	// the chunks needing to know about each other
+	fn chunk_decorations(&self, chunk_block: BlockPos) -> FxHashMap<IVec2, Block> {
+		let mut out = FxHashMap::default();
+		let chunk_size = CHUNK_SIZE as i32;
+
+		for x in -MAX_DECORATION_REACH..chunk_size + MAX_DECORATION_REACH {
+			for z in -MAX_DECORATION_REACH..chunk_size + MAX_DECORATION_REACH {
+				let column = IVec2::new(chunk_block.x + x, chunk_block.z + z);
+				let biome = self.surface_biome_at(BlockPos::new(column.x, 0, column.y));
+				let mut rng = DeterministicRng::for_column(self.seed, DECORATION_SALT, column.x, column.y);
+
+				for decoration in biome.decorations.iter() {
+					for covered in decoration.columns(&mut rng, column) {
+						let local = covered - IVec2::new(chunk_block.x, chunk_block.z);
+						if local.cmpge(IVec2::ZERO).all() && local.cmplt(IVec2::splat(chunk_size)).all() {
+							out.entry(local).or_insert_with(|| decoration.block.clone());
+						}
+					}
+				}
+			}
+		}
+
+		out
+	}
+
+	#[instrument(level = "debug", skip(self, world))]
 	pub fn generate_chunk(&self, world: Arc<World>, position: ChunkPos) -> LoadedChunk {
-		let mut cache = NoiseCache::default();
-		LoadedChunk::new(Chunk::new(world, position, |block| {
-			let biome_height = self.get_biome_height_noise(block, &mut cache);
-			let biome_noise = self.get_biome_noise(block, &mut cache);
+		let chunk_block = position.as_block_pos();
 
-			let biome = self.surface_biome_map.get_biome(biome_noise);
+		// the surface of every column in the chunk
+		let columns: [[SurfaceColumn; CHUNK_SIZE]; CHUNK_SIZE] = array_init(|x| array_init(|z| {
+			self.compute_surface_column(chunk_block + BlockPos::new(x as i32, 0, z as i32))
+		}));
 
-			let height = self.get_height_noise(block, biome.height_amplitude, &mut cache);
+		let min_y = chunk_block.y;
+		let max_y = chunk_block.y + CHUNK_SIZE as i32 - 1;
 
-			biome.get_block_at_depth(block.y - height)
+		// chunk is entirely above the terrain and its decorations
+		let max_height = columns.iter().flatten().map(|column| column.height).max().unwrap();
+		if min_y > max_height + 1 {
+			return LoadedChunk::new(Chunk::filled(world, position, Air::new().into()));
+		}
+
+		// decorations are 1 block above the surface, so only chunks with that block in some column have any
+		let has_decoration_layer = columns.iter().flatten().any(|column| (min_y..=max_y).contains(&(column.height + 1)));
+		let decorations = if has_decoration_layer {
+			self.chunk_decorations(chunk_block)
+		} else {
+			FxHashMap::default()
+		};
+
+		// caves are carved after the surface is generated
+		let caves = self.chunk_caves(chunk_block).filter(|caves| !caves.is_empty());
+
+		// chunk is entirely below the surface layers, so it is all filler if every biome has the same filler
+		// the layers are as thick as they can be with jitter, so this doesn't depend on each column's jitter
+		let first_biome = columns[0][0].biome;
+		let below_layers = columns.iter().flatten()
+			.all(|column| max_y <= column.height - column.biome.max_layer_thickness()
+				&& column.biome.filler.block_type() == first_biome.filler.block_type());
+		if below_layers && caves.is_none() {
+			return LoadedChunk::new(Chunk::filled(world, position, first_biome.filler.clone()));
+		}
+
+		LoadedChunk::new(Chunk::new(world, position, |block| {
+			let local = block - chunk_block;
+			let SurfaceColumn { height, biome, layer_jitter } = columns[local.x as usize][local.z as usize];
+			if block.y == height + 1 {
+				if let Some(decoration) = decorations.get(&IVec2::new(local.x, local.z)) {
+					return decoration.clone();
+				}
+			}
+			let surface_block = biome.get_block_at_depth(block.y - height, layer_jitter);
+
+			match &caves {
+				Some(caves) => caves.block_at(local, block.y, height - block.y, surface_block),
+				None => surface_block,
+			}
 		}))
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	extern crate test;
+
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::thread;
+
+	use rustc_hash::FxHashSet;
+	use test::Bencher;
+	use super::*;
+
+	#[bench]
+	fn tall_column_generation_benchmark(b: &mut Bencher) {
+		let world = World::new_test().unwrap();
+		let generator = WorldGenerator::new(0, GeneratorType::Normal, GeneratorOptions::default());
+
+		b.iter(|| {
+			for y in -8..8 {
+				generator.generate_chunk(world.clone(), ChunkPos::new(0, y, 0));
+			}
+		})
+	}
+
+	#[test]
+	fn surface_column_matches_generated_chunks() {
+		let world = World::new_test().unwrap();
+		let generator = WorldGenerator::new(0, GeneratorType::Normal, GeneratorOptions::default());
+
+		for (x, z) in [(0, 0), (17, -40), (-300, 95), (1000, 1000), (-64, -1)] {
+			let surface = generator.surface_column(x, z);
+			let top_block = BlockPos::new(x, surface.height, z);
+			let above_block = top_block + BlockPos::new(0, 1, 0);
+
+			let is_air = |block: BlockPos| {
+				let (chunk_pos, local) = block.as_chunk_block_pos();
+				let chunk = generator.generate_chunk(world.clone(), chunk_pos);
+				let is_air = chunk.chunk.get_block(local).is_air();
+				is_air
+			};
+
+			assert!(!is_air(top_block), "surface block at {:?} is air", top_block);
+			assert!(is_air(above_block), "block above surface at {:?} is not air", above_block);
+
+			// cached value must match as well
+			let cached = generator.surface_column(x, z);
+			assert_eq!(cached.height, surface.height);
+			assert_eq!(cached.biome.name, surface.biome.name);
+		}
+	}
+
+	#[test]
+	fn layer_jitter_is_bounded_and_deterministic() {
+		let generator = WorldGenerator::new(1234, GeneratorType::Normal, GeneratorOptions::default());
+		let same_seed = WorldGenerator::new(1234, GeneratorType::Normal, GeneratorOptions::default());
+		let other_seed = WorldGenerator::new(99, GeneratorType::Normal, GeneratorOptions::default());
+
+		let mut seen = FxHashMap::default();
+		let mut differs_from_other_seed = false;
+		for x in -64..64 {
+			for z in -64..64 {
+				let column = generator.surface_column_uncached(x, z);
+				assert!(column.layer_jitter.abs() <= MAX_LAYER_JITTER, "jitter {} at {} {}", column.layer_jitter, x, z);
+				assert_eq!(column.layer_jitter, same_seed.surface_column_uncached(x, z).layer_jitter);
+				differs_from_other_seed |= column.layer_jitter != other_seed.surface_column_uncached(x, z).layer_jitter;
+				*seen.entry(column.layer_jitter).or_insert(0) += 1;
+			}
+		}
+		assert!(differs_from_other_seed);
+		// every jitter is used, so the strata aren't flat
+		assert_eq!(seen.len(), 2 * MAX_LAYER_JITTER as usize + 1, "jitters used: {:?}", seen);
+
+		// the top block stays the same, every layer is at least 1 block thick,
+		// and the filler starts within MAX_LAYER_JITTER of where it would without jitter
+		let biome = generator.surface_column(0, 0).biome;
+		let filler = biome.filler.block_type();
+		let mut top_bottoms = FxHashSet::default();
+		let mut last_thicknesses = FxHashSet::default();
+		for jitter in -MAX_LAYER_JITTER..=MAX_LAYER_JITTER {
+			assert_eq!(biome.get_block_at_depth(0, jitter).block_type(), biome.get_block_at_depth(0, 0).block_type());
+
+			let bottoms = biome.layer_bottoms(jitter).collect::<Vec<_>>();
+			let mut layer_top = 0;
+			for bottom in bottoms.iter() {
+				assert!(*bottom < layer_top, "empty layer with jitter {}: {:?}", jitter, bottoms);
+				layer_top = *bottom;
+			}
+
+			let filler_depth = -bottoms[bottoms.len() - 1];
+			assert!((filler_depth - biome.total_layer_thickness()).abs() <= MAX_LAYER_JITTER);
+			assert!(filler_depth <= biome.max_layer_thickness());
+			assert_eq!(biome.get_block_at_depth(-filler_depth, jitter).block_type(), filler);
+			assert_eq!(biome.get_block_at_depth(-biome.max_layer_thickness(), jitter).block_type(), filler);
+
+			top_bottoms.insert(bottoms[0]);
+			last_thicknesses.insert(bottoms[bottoms.len() - 2] - bottoms[bottoms.len() - 1]);
+		}
+		// each boundary moves on its own, so the top one moves too, and the bottom layer isn't always as thick
+		assert!(top_bottoms.len() > 1);
+		assert!(last_thicknesses.len() > 1);
+		// jitter out of bounds is clamped
+		assert_eq!(
+			biome.get_block_at_depth(-biome.max_layer_thickness() + 1, 100).block_type(),
+			biome.get_block_at_depth(-biome.max_layer_thickness() + 1, MAX_LAYER_JITTER).block_type(),
+		);
+	}
+
+	#[test]
+	fn decorations_match_their_frequency() {
+		let world = World::new_test().unwrap();
+		let generator = WorldGenerator::new(0, GeneratorType::Flat, GeneratorOptions::default());
+
+		let mut decorated = 0;
+		let mut expected = 0.0;
+		for chunk_x in 0..16 {
+			for chunk_z in 0..16 {
+				let chunk = generator.generate_chunk(world.clone(), ChunkPos::new(chunk_x, 0, chunk_z)).chunk;
+				for x in 0..CHUNK_SIZE as i32 {
+					for z in 0..CHUNK_SIZE as i32 {
+						// flat worlds have their surface at y = 0
+						if !chunk.get_block(BlockPos::new(x, 1, z)).is_air() {
+							decorated += 1;
+						}
+
+						let biome = generator.surface_column(chunk_x * CHUNK_SIZE as i32 + x, chunk_z * CHUNK_SIZE as i32 + z).biome;
+						expected += biome.decorations.iter()
+							.map(|decoration| decoration.frequency as f64 * match decoration.placement {
+								DecorationPlacement::SurfaceOnly => 1.0,
+								// 1 to 3 blocks
+								DecorationPlacement::Scatter => 2.0,
+							})
+							.sum::<f64>();
+					}
+				}
+			}
+		}
+
+		assert!(expected > 100.0, "only {} decorations expected", expected);
+		assert!((decorated as f64 - expected).abs() < 0.2 * expected, "{} decorations, expected {}", decorated, expected);
+	}
+
+	#[test]
+	fn decorations_are_on_the_surface() {
+		let world = World::new_test().unwrap();
+		let generator = WorldGenerator::new(0, GeneratorType::Normal, GeneratorOptions::default());
+		let mut chunks = FxHashMap::default();
+		let mut block_at = |block: BlockPos| {
+			let (chunk_pos, local) = block.as_chunk_block_pos();
+			let chunk = chunks.entry(chunk_pos).or_insert_with(|| generator.generate_chunk(world.clone(), chunk_pos).chunk);
+			chunk.get_block(local).block_type()
+		};
+
+		let mut decorated = 0;
+		for x in 0..96 {
+			for z in 0..96 {
+				let height = generator.surface_column(x, z).height;
+				assert_ne!(block_at(BlockPos::new(x, height, z)), BlockType::Air);
+				if block_at(BlockPos::new(x, height + 1, z)) != BlockType::Air {
+					decorated += 1;
+				}
+				// decorations are never stacked or floating
+				assert_eq!(block_at(BlockPos::new(x, height + 2, z)), BlockType::Air, "block above decoration at {} {}", x, z);
+			}
+		}
+
+		assert!(decorated > 0);
+	}
+
+	// the type and liquid level of every block in each chunk, generated on the given number of threads in the order given
+	fn generate_blocks(chunks: &[ChunkPos], threads: usize) -> FxHashMap<ChunkPos, Vec<(BlockType, Option<u8>)>> {
+		let world = World::new_test().unwrap();
+		let generator = WorldGenerator::new(1234, GeneratorType::Normal, GeneratorOptions::default());
+		let next_chunk = AtomicUsize::new(0);
+		let out = Mutex::new(FxHashMap::default());
+
+		thread::scope(|scope| {
+			for _ in 0..threads {
+				scope.spawn(|| {
+					while let Some(&chunk_pos) = chunks.get(next_chunk.fetch_add(1, Ordering::Relaxed)) {
+						let chunk = generator.generate_chunk(world.clone(), chunk_pos).chunk;
+						let mut blocks = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+						for x in 0..CHUNK_SIZE as i32 {
+							for y in 0..CHUNK_SIZE as i32 {
+								for z in 0..CHUNK_SIZE as i32 {
+									let block = chunk.get_block(BlockPos::new(x, y, z));
+									blocks.push((block.block_type(), block.liquid_level()));
+								}
+							}
+						}
+						out.lock().insert(chunk_pos, blocks);
+					}
+				});
+			}
+		});
+
+		out.into_inner()
+	}
+
+	#[test]
+	fn generation_is_independent_of_threads_and_order() {
+		let mut chunks = Vec::new();
+		for x in -2..2 {
+			for y in -2..2 {
+				for z in -2..2 {
+					chunks.push(ChunkPos::new(x, y, z));
+				}
+			}
+		}
+
+		let in_order = generate_blocks(&chunks, 1);
+		DeterministicRng::for_chunk(0, 0, ChunkPos::new(0, 0, 0)).shuffle(&mut chunks);
+		let shuffled = generate_blocks(&chunks, 8);
+
+		assert_eq!(in_order.len(), chunks.len());
+		for chunk in chunks.iter() {
+			assert!(in_order[chunk] == shuffled[chunk], "chunk {:?} generated differently", chunk);
+		}
+	}
+
+	// deep chunks have caves in them, filled based on their biome, and nothing is carved near the surface
+	#[test]
+	fn caves_are_carved_underground() {
+		let world = World::new_test().unwrap();
+		let generator = WorldGenerator::new(1234, GeneratorType::Normal, GeneratorOptions::default());
+
+		let mut biomes = Vec::new();
+		let mut carved_blocks = 0;
+		for x in 0..4 {
+			for (y, z) in (-3..0).flat_map(|y| (0..4).map(move |z| (y, z))) {
+				let chunk_pos = ChunkPos::new(x, y, z);
+				let chunk = generator.generate_chunk(world.clone(), chunk_pos).chunk;
+				let chunk_block = chunk_pos.as_block_pos();
+
+				for local_x in 0..CHUNK_SIZE as i32 {
+					for local_z in 0..CHUNK_SIZE as i32 {
+						let surface = generator.surface_column(chunk_block.x + local_x, chunk_block.z + local_z);
+						for local_y in 0..CHUNK_SIZE as i32 {
+							// blocks above the surface are air anyway
+							let depth = surface.height - (chunk_block.y + local_y);
+							if depth < 0 {
+								continue;
+							}
+
+							let block = chunk.get_block(BlockPos::new(local_x, local_y, local_z));
+							if block.is_air() || block.is_liquid() {
+								assert!(depth >= caves::CAVE_MIN_DEPTH, "cave at depth {}", depth);
+								carved_blocks += 1;
+							}
+						}
+					}
+				}
+
+				biomes.push(generator.underground_biome_at(chunk_block));
+			}
+		}
+
+		assert!(carved_blocks > 0);
+		assert!(biomes.iter().any(|biome| biome.has_caves()));
+	}
+}
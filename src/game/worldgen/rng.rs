@@ -0,0 +1,115 @@
+// nothing in worldgen is randomized yet, features added later use this
+#![allow(dead_code)]
+
+use std::ops::Range;
+
+use crate::prelude::*;
+
+// the splitmix64 finalizer, spreads every bit of the input over the whole output
+fn mix(value: u64) -> u64 {
+	let mut value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+	value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+	value ^ (value >> 31)
+}
+
+// random numbers for worldgen, which only depend on the seed and the position they are used at
+// every randomized feature must use this instead of anything shared between chunks,
+// so the world generates the same no matter which thread generates which chunk first
+#[derive(Debug, Clone)]
+pub struct DeterministicRng {
+	state: u64,
+}
+
+impl DeterministicRng {
+	// salt is different for each feature, so features at the same position aren't correlated
+	fn from_parts(seed: u32, salt: u64, coordinates: [i32; 3]) -> Self {
+		let mut state = mix(seed as u64 ^ mix(salt));
+		for coordinate in coordinates {
+			state = mix(state ^ coordinate as u32 as u64);
+		}
+
+		DeterministicRng {
+			state,
+		}
+	}
+
+	pub fn for_chunk(seed: u32, salt: u64, chunk: ChunkPos) -> Self {
+		Self::from_parts(seed, salt, chunk.to_array())
+	}
+
+	pub fn for_block(seed: u32, salt: u64, block: BlockPos) -> Self {
+		Self::from_parts(seed, salt, block.to_array())
+	}
+
+	// for tests which only need some fixed random numbers
+	#[cfg(test)]
+	pub fn for_test(seed: u32) -> Self {
+		Self::from_parts(seed, 0, [0; 3])
+	}
+
+	// for features placed per column, which don't depend on y
+	pub fn for_column(seed: u32, salt: u64, x: i32, z: i32) -> Self {
+		Self::from_parts(seed, salt, [x, 0, z])
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		mix(self.state)
+	}
+
+	// uniform in [0, 1)
+	pub fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	// panics if the range is empty
+	pub fn range(&mut self, range: Range<i32>) -> i32 {
+		assert!(!range.is_empty(), "empty range passed to DeterministicRng::range");
+		let size = (range.end as i64 - range.start as i64) as u64;
+		(range.start as i64 + (self.next_u64() % size) as i64) as i32
+	}
+
+	// true with the given probability
+	pub fn chance(&mut self, probability: f64) -> bool {
+		self.next_f64() < probability
+	}
+
+	pub fn shuffle<T>(&mut self, items: &mut [T]) {
+		for i in (1..items.len()).rev() {
+			let j = self.range(0..i as i32 + 1) as usize;
+			items.swap(i, j);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn same_inputs_give_same_numbers() {
+		let numbers = |seed, salt, chunk| {
+			let mut rng = DeterministicRng::for_chunk(seed, salt, chunk);
+			[rng.next_u64(), rng.next_u64(), rng.next_u64()]
+		};
+		let chunk = ChunkPos::new(4, -2, 9);
+
+		assert_eq!(numbers(7, 1, chunk), numbers(7, 1, chunk));
+		assert_ne!(numbers(7, 1, chunk), numbers(8, 1, chunk));
+		assert_ne!(numbers(7, 1, chunk), numbers(7, 2, chunk));
+		assert_ne!(numbers(7, 1, chunk), numbers(7, 1, ChunkPos::new(4, -2, 10)));
+		// negative coordinates aren't confused with positive ones
+		assert_ne!(numbers(7, 1, ChunkPos::new(1, 0, 0)), numbers(7, 1, ChunkPos::new(-1, 0, 0)));
+	}
+
+	#[test]
+	fn numbers_stay_in_range() {
+		let mut rng = DeterministicRng::for_column(0, 0, -5, 12);
+		for _ in 0..1000 {
+			assert!((-3..4).contains(&rng.range(-3..4)));
+			assert!((0.0..1.0).contains(&rng.next_f64()));
+		}
+		assert_eq!(rng.range(i32::MIN..i32::MIN + 1), i32::MIN);
+	}
+}
@@ -1,11 +1,14 @@
-use std::lazy::SyncLazy;
-
 use array_init::array_init;
+use once_cell::sync::Lazy;
 
 use crate::prelude::*;
 use crate::game::block::*;
 
 use super::biome::BiomeNoiseData;
+use super::decoration::{Decoration, DecorationPlacement};
+
+// each layer boundary is moved up or down by at most this many blocks in each column
+pub const MAX_LAYER_JITTER: i32 = 2;
 
 #[derive(Debug)]
 pub struct SurfaceLayer {
@@ -22,30 +25,69 @@ pub struct SurfaceBiome {
 	pub layers: Vec<SurfaceLayer>,
 	// this is the filler block down to the bottom of the surface layer
 	pub filler: Block,
+	// placed on top of the surface, in order, a decoration doesn't replace an earlier one in the same column
+	pub decorations: Vec<Decoration>,
 	pub heat_point: u8,
 	pub humidity_point: u8,
+	// color used to show this biome in debug views
+	pub map_color: [u8; 3],
+	// color of the fog and sky while the player is in this biome
+	pub fog_color: [u8; 3],
 }
 
 impl SurfaceBiome {
-	pub fn get_block_at_depth(&self, depth: i32) -> Block {
+	// total thickness of all the layers without any jitter
+	pub fn total_layer_thickness(&self) -> i32 {
+		self.layers.iter()
+			.map(|layer| layer.thickness as i32)
+			.sum()
+	}
+
+	// blocks deeper than this are always filler, whatever the column's jitter is
+	pub fn max_layer_thickness(&self) -> i32 {
+		self.total_layer_thickness() + MAX_LAYER_JITTER
+	}
+
+	// depth of the bottom of each layer in a column with this jitter, the last one is where the filler starts
+	// each boundary is moved by its own offset, so strata aren't flat lines and the layers don't all move together
+	// every layer is still at least 1 block thick, and no boundary moves more than MAX_LAYER_JITTER from where it would be
+	pub(super) fn layer_bottoms(&self, jitter: i32) -> impl Iterator<Item = i32> + '_ {
+		let jitter = jitter.clamp(-MAX_LAYER_JITTER, MAX_LAYER_JITTER);
+		let mut unjittered_bottom = 0;
+		let mut bottom = 0;
+
+		self.layers.iter().enumerate().map(move |(i, layer)| {
+			unjittered_bottom -= layer.thickness as i32;
+			// keeping the layer 1 block thick only pushes the boundary to just below the one above it,
+			// which is never more than MAX_LAYER_JITTER past this boundary's unjittered depth
+			bottom = (unjittered_bottom - boundary_offset(jitter, i)).min(bottom - 1);
+			bottom
+		})
+	}
+
+	// the top block is always the top layer, only the boundaries below it are moved by the jitter
+	pub fn get_block_at_depth(&self, depth: i32, jitter: i32) -> Block {
 		if depth > 0 {
 			return Air::new().into();
 		}
 
-		let mut current_block_depth: i32 = 0;
-		for layer in self.layers.iter() {
-			let thickness: i32 = layer.thickness.try_into().unwrap();
-			current_block_depth -= thickness;
-			if depth > current_block_depth {
-				return layer.block.clone();
-			}
-		}
-
-		self.filler.clone()
+		self.layers.iter()
+			.zip(self.layer_bottoms(jitter))
+			.find(|(_, bottom)| depth > *bottom)
+			.map_or_else(|| self.filler.clone(), |(layer, _)| layer.block.clone())
 	}
 }
 
-static BIOMES: SyncLazy<[SurfaceBiome; 3]> = SyncLazy::new(|| [
+// how far the boundary below the layer at index is moved down, from -MAX_LAYER_JITTER to MAX_LAYER_JITTER
+// the first boundary is moved by the column's jitter, later ones are scrambled from it with their index,
+// so each boundary moves differently while still following the smooth jitter noise
+fn boundary_offset(jitter: i32, index: usize) -> i32 {
+	let span = 2 * MAX_LAYER_JITTER + 1;
+	let multiplier = index as i32 % 4 + 1;
+	((jitter + MAX_LAYER_JITTER) * multiplier + index as i32).rem_euclid(span) - MAX_LAYER_JITTER
+}
+
+static BIOMES: Lazy<[SurfaceBiome; 3]> = Lazy::new(|| [
 	SurfaceBiome {
 		name: "grasslands".to_owned(),
 		height_amplitude: 4.0,
@@ -64,8 +106,18 @@ static BIOMES: SyncLazy<[SurfaceBiome; 3]> = SyncLazy::new(|| [
 			},
 		],
 		filler: Stone::new().into(),
+		decorations: vec![
+			// small boulders
+			Decoration {
+				block: Stone::new().into(),
+				frequency: 0.004,
+				placement: DecorationPlacement::Scatter,
+			},
+		],
 		heat_point: 28,
 		humidity_point: 18,
+		map_color: [110, 180, 60],
+		fog_color: [170, 205, 235],
 	},
 	SurfaceBiome {
 		name: "lush grasslands".to_owned(),
@@ -85,8 +137,18 @@ static BIOMES: SyncLazy<[SurfaceBiome; 3]> = SyncLazy::new(|| [
 			},
 		],
 		filler: Stone::new().into(),
+		decorations: vec![
+			// tufts of grass on the bare dirt
+			Decoration {
+				block: Grass::new().into(),
+				frequency: 0.03,
+				placement: DecorationPlacement::SurfaceOnly,
+			},
+		],
 		heat_point: 28,
 		humidity_point: 25,
+		map_color: [40, 140, 50],
+		fog_color: [165, 215, 210],
 	},
 	SurfaceBiome {
 		name: "coniferous forest".to_owned(),
@@ -106,8 +168,11 @@ static BIOMES: SyncLazy<[SurfaceBiome; 3]> = SyncLazy::new(|| [
 			},
 		],
 		filler: Stone::new().into(),
+		decorations: Vec::new(),
 		heat_point: 13,
 		humidity_point: 35,
+		map_color: [30, 90, 80],
+		fog_color: [140, 160, 175],
 	},
 ]);
 
@@ -147,19 +212,11 @@ impl SurfaceBiomeMap {
 		self.map[noise.heat as usize][noise.humidity as usize]
 	}
 
-	pub fn print_diagram(&self) {
-		let mut out_str = String::from("");
-		for heat in 0..50 {
-			for humidity in 0..50 {
-				out_str.push_str(&self.map[heat][humidity].name[0..1]);
-			}
-			out_str.push('\n');
-		}
-		println!("{}", out_str);
-		println!();
-		println!();
-		println!();
-		println!();
-		println!();
+	// returns the color of each cell of the biome map, with heat increasing by row and humidity by column
+	pub fn diagram_colors(&self) -> Vec<[u8; 3]> {
+		self.map.iter()
+			.flatten()
+			.map(|biome| biome.map_color)
+			.collect()
 	}
 }
@@ -0,0 +1,14 @@
+#![cfg_attr(test, feature(test))]
+
+// the game's world, generation and meshing, used by the game and by tools which work on saved worlds
+// the renderer and everything else that needs a window is only built with the render feature
+
+#[macro_use]
+extern crate tracing;
+
+pub mod game;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod math;
+mod assets;
+mod prelude;
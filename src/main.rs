@@ -1,24 +1,50 @@
-#![feature(once_cell)]
-#![feature(drain_filter)]
-#![feature(test)]
-
-#[macro_use]
-extern crate log;
+use std::path::PathBuf;
 
 use winit::{
-	event_loop::EventLoop,
+	event_loop::{EventLoop, ControlFlow},
 	window::WindowBuilder,
 	dpi::PhysicalSize,
 };
+use tracing_subscriber::{prelude::*, EnvFilter, fmt::format::FmtSpan};
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
+
+use minecone2::game;
+
+// sets up logging, honoring RUST_LOG
+// if --trace-chrome is passed, a chrome://tracing json file is also written for this session
+fn init_tracing() -> Option<FlushGuard> {
+	let fmt_layer = tracing_subscriber::fmt::layer()
+		.with_thread_names(true)
+		.with_span_events(FmtSpan::CLOSE);
+
+	let registry = tracing_subscriber::registry()
+		.with(EnvFilter::from_default_env())
+		.with(fmt_layer);
 
-mod game;
-mod render;
-mod math;
-mod assets;
-mod prelude;
+	if std::env::args().any(|arg| arg == "--trace-chrome") {
+		let (chrome_layer, guard) = ChromeLayerBuilder::new()
+			.include_args(true)
+			.build();
+		registry.with(chrome_layer).init();
+		Some(guard)
+	} else {
+		registry.init();
+		None
+	}
+}
+
+// the camera path file passed with --benchmark <path-file>
+fn benchmark_path() -> Option<PathBuf> {
+	let args = std::env::args().collect::<Vec<_>>();
+	args.windows(2)
+		.find(|args| args[0] == "--benchmark")
+		.map(|args| PathBuf::from(&args[1]))
+}
 
 fn main() {
-    pretty_env_logger::init();
+	// the chrome trace is only written out when the guard is dropped
+	let mut trace_guard = init_tracing();
+	game::install_panic_hook();
 
     let event_loop = EventLoop::new();
 	let window = WindowBuilder::new()
@@ -27,9 +53,14 @@ fn main() {
 		.build(&event_loop)
 		.unwrap();
 
-    let mut game = game::Game::new(60, window);
+    let mut game = game::Game::new(60, game::DEFAULT_TICK_RATE, window, benchmark_path());
 
     event_loop.run(move |event, _, control_flow| {
 		*control_flow = game.event_update(event);
+
+		if *control_flow == ControlFlow::Exit {
+			game.exit();
+			trace_guard.take();
+		}
 	});
 }
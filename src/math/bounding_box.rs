@@ -0,0 +1,107 @@
+use glam::Vec3;
+
+use crate::math::Plane;
+use crate::game::types::Axis;
+
+/// Axis aligned bounding boss
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub position: Vec3,
+    pub size: Vec3,
+}
+
+impl Aabb {
+    pub fn new(position: Vec3, size: Vec3) -> Self {
+        Aabb {
+            position,
+            size,
+        }
+    }
+
+    pub fn end(&self) -> Vec3 {
+        self.position + self.size
+    }
+
+    /// Returns true if the point is inside of or on the edge of the bounding box
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        point.cmpge(self.position).all() && point.cmple(self.end()).all()
+    }
+
+    /// Returns true if any part of the boundinf box lies inside of the plane (on the side that the normal is pointong to)
+    pub fn inside_of_plane(&self, plane: Plane) -> bool {
+        let rel_pos = self.position - plane.position;
+
+        // find which corner of the bounding box to use
+        // it will be the corner that the plane's normal vector is pointing towards
+        // use vector functions to do this because if vec3a is used it will use simd and be very fast
+        let mask = plane.normal.cmpge(Vec3::ZERO);
+        let corner_offset = Vec3::select(mask, self.size, Vec3::ZERO);
+        let corner = rel_pos + corner_offset;
+
+        corner.dot(plane.normal) >= 0.0
+    }
+    /// Returns the distance along the ray to where it enters the bounding box, and the axis of the face it enters through
+    /// A ray starting inside of the bounding box enters it at a distance of 0, and None is returned if the ray misses it
+    pub fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<(f32, Axis)> {
+        let mut enter = (f32::NEG_INFINITY, Axis::X);
+        let mut exit = f32::INFINITY;
+
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let start = self.position[axis];
+            let end = self.end()[axis];
+
+            if direction[axis] == 0.0 {
+                // the ray is parallel to this axis' faces, so it never crosses them
+                if origin[axis] < start || origin[axis] > end {
+                    return None;
+                }
+                continue;
+            }
+
+            let start_time = (start - origin[axis]) / direction[axis];
+            let end_time = (end - origin[axis]) / direction[axis];
+            let (near, far) = if start_time < end_time {
+                (start_time, end_time)
+            } else {
+                (end_time, start_time)
+            };
+
+            if near > enter.0 {
+                enter = (near, axis);
+            }
+            exit = exit.min(far);
+        }
+
+        if enter.0 > exit || exit < 0.0 {
+            None
+        } else {
+            Some((enter.0.max(0.0), enter.1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_intersection_finds_entered_face() {
+        let aabb = Aabb::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 0.5, 1.0));
+
+        let (distance, axis) = aabb.ray_intersection(Vec3::new(0.0, 0.25, 0.5), Vec3::X).unwrap();
+        assert!((distance - 1.0).abs() < 0.0001);
+        assert_eq!(axis, Axis::X);
+
+        let (distance, axis) = aabb.ray_intersection(Vec3::new(1.5, 2.0, 0.5), Vec3::NEG_Y).unwrap();
+        assert!((distance - 1.5).abs() < 0.0001);
+        assert_eq!(axis, Axis::Y);
+
+        // passes over the top, and points away from the box
+        assert!(aabb.ray_intersection(Vec3::new(0.0, 0.75, 0.5), Vec3::X).is_none());
+        assert!(aabb.ray_intersection(Vec3::new(0.0, 0.25, 0.5), Vec3::NEG_X).is_none());
+
+        // starts inside
+        let (distance, _) = aabb.ray_intersection(Vec3::new(1.5, 0.25, 0.5), Vec3::new(1.0, 1.0, 0.0).normalize()).unwrap();
+        assert_eq!(distance, 0.0);
+    }
+}
@@ -1,2 +1,4 @@
 mod plane;
-pub use plane::Plane;
\ No newline at end of file
+pub use plane::Plane;
+mod bounding_box;
+pub use bounding_box::Aabb;
@@ -5,7 +5,9 @@ pub use anyhow::Result;
 use rustc_hash::FxHasher;
 use dashmap::DashMap;
 
-pub use crate::game::{CHUNK_SIZE, types::*, debug_string, debug_display};
+pub use crate::game::{CHUNK_SIZE, types::*};
+#[cfg(feature = "render")]
+pub use crate::game::{debug_string, debug_display, debug_section_string};
 
 pub type FxDashMap<K, V> = DashMap<K, V, BuildHasherDefault<FxHasher>>;
 
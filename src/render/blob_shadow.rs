@@ -0,0 +1,109 @@
+use std::mem;
+
+use glam::Vec3;
+
+use super::Aabb;
+use super::model::Vertex;
+
+// radius of the shadow of an entity standing on the ground
+const SHADOW_RADIUS: f32 = 0.5;
+// opacity at the center of the shadow of an entity standing on the ground
+const SHADOW_ALPHA: f32 = 0.5;
+// entities higher than this above the ground have no shadow
+pub const MAX_SHADOW_HEIGHT: f32 = 4.0;
+// the shadow shrinks to this fraction of its size as the entity reaches the max height
+const MIN_SHADOW_SCALE: f32 = 0.5;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowVertex {
+	position: [f32; 3],
+	// position in the quad, from -1 to 1 on each axis
+	offset: [f32; 2],
+	alpha: f32,
+}
+
+impl ShadowVertex {
+	const ATTRIBS: [wgpu::VertexAttribute; 3] =
+		wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32];
+}
+
+impl Vertex for ShadowVertex {
+	fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+		wgpu::VertexBufferLayout {
+			array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+			step_mode: wgpu::VertexStepMode::Vertex,
+			attributes: &Self::ATTRIBS,
+		}
+	}
+}
+
+// a dark circle on the ground under an entity, used instead of real shadows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlobShadow {
+	// center of the circle, on the ground
+	pub center: Vec3,
+	pub radius: f32,
+	pub alpha: f32,
+}
+
+impl BlobShadow {
+	// ground_height is the height of the top of the ground under the entity
+	// returns none if the entity is too far above the ground to have a shadow
+	pub fn new(entity_position: Vec3, ground_height: f32) -> Option<Self> {
+		let height = entity_position.y - ground_height;
+		if !(0.0..MAX_SHADOW_HEIGHT).contains(&height) {
+			return None;
+		}
+
+		// higher entities have smaller and fainter shadows
+		let fraction = height / MAX_SHADOW_HEIGHT;
+		Some(BlobShadow {
+			center: Vec3::new(entity_position.x, ground_height, entity_position.z),
+			radius: SHADOW_RADIUS * (1.0 - fraction * (1.0 - MIN_SHADOW_SCALE)),
+			alpha: SHADOW_ALPHA * (1.0 - fraction),
+		})
+	}
+
+	pub fn bounding_box(&self) -> Aabb {
+		let corner = self.center - Vec3::new(self.radius, 0.0, self.radius);
+		Aabb::new(corner, Vec3::new(2.0 * self.radius, 0.0, 2.0 * self.radius))
+	}
+
+	// 2 triangles facing up
+	pub fn vertices(&self) -> [ShadowVertex; 6] {
+		let vertex = |x: f32, z: f32| ShadowVertex {
+			position: (self.center + Vec3::new(x, 0.0, z) * self.radius).to_array(),
+			offset: [x, z],
+			alpha: self.alpha,
+		};
+
+		[
+			vertex(-1.0, -1.0),
+			vertex(-1.0, 1.0),
+			vertex(1.0, 1.0),
+			vertex(-1.0, -1.0),
+			vertex(1.0, 1.0),
+			vertex(1.0, -1.0),
+		]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn shadow_fades_with_height() {
+		let ground = BlobShadow::new(Vec3::new(2.5, 10.0, -3.5), 10.0).unwrap();
+		assert_eq!(ground.center, Vec3::new(2.5, 10.0, -3.5));
+		assert_eq!((ground.radius, ground.alpha), (SHADOW_RADIUS, SHADOW_ALPHA));
+
+		let high = BlobShadow::new(Vec3::new(2.5, 12.0, -3.5), 10.0).unwrap();
+		assert_eq!(high.center, ground.center);
+		assert!(high.radius < ground.radius && high.alpha < ground.alpha);
+
+		assert_eq!(BlobShadow::new(Vec3::new(0.0, 10.0 + MAX_SHADOW_HEIGHT, 0.0), 10.0), None);
+		assert_eq!(BlobShadow::new(Vec3::new(0.0, 9.0, 0.0), 10.0), None);
+	}
+}
@@ -0,0 +1,287 @@
+// size of each page of a buffer pool in bytes, allocations larger than this get their own page
+pub const PAGE_SIZE: u64 = 16 * 1024 * 1024;
+
+// a range of elements within a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+	pub start: u32,
+	pub len: u32,
+}
+
+impl Allocation {
+	fn end(&self) -> u32 {
+		self.start + self.len
+	}
+}
+
+// first fit allocator of element ranges in a buffer
+#[derive(Debug)]
+pub struct RangeAllocator {
+	capacity: u32,
+	// sorted by start, adjacent free ranges are always merged
+	free_ranges: Vec<Allocation>,
+}
+
+impl RangeAllocator {
+	pub fn new(capacity: u32) -> Self {
+		RangeAllocator {
+			capacity,
+			free_ranges: vec![Allocation {
+				start: 0,
+				len: capacity,
+			}],
+		}
+	}
+
+	pub fn capacity(&self) -> u32 {
+		self.capacity
+	}
+
+	pub fn free_space(&self) -> u32 {
+		self.free_ranges.iter().map(|range| range.len).sum()
+	}
+
+	pub fn largest_free_range(&self) -> u32 {
+		self.free_ranges.iter().map(|range| range.len).max().unwrap_or(0)
+	}
+
+	pub fn alloc(&mut self, len: u32) -> Option<Allocation> {
+		let index = self.free_ranges.iter().position(|range| range.len >= len)?;
+		let range = &mut self.free_ranges[index];
+
+		let out = Allocation {
+			start: range.start,
+			len,
+		};
+
+		range.start += len;
+		range.len -= len;
+		if range.len == 0 {
+			self.free_ranges.remove(index);
+		}
+
+		Some(out)
+	}
+
+	pub fn free(&mut self, allocation: Allocation) {
+		if allocation.len == 0 {
+			return;
+		}
+
+		let index = self.free_ranges.partition_point(|range| range.start < allocation.start);
+		self.free_ranges.insert(index, allocation);
+
+		// merge with the next range, then the previous range
+		if index + 1 < self.free_ranges.len() && self.free_ranges[index].end() == self.free_ranges[index + 1].start {
+			self.free_ranges[index].len += self.free_ranges[index + 1].len;
+			self.free_ranges.remove(index + 1);
+		}
+		if index > 0 && self.free_ranges[index - 1].end() == self.free_ranges[index].start {
+			self.free_ranges[index - 1].len += self.free_ranges[index].len;
+			self.free_ranges.remove(index);
+		}
+	}
+}
+
+// a range of elements within one page of a pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolAllocation {
+	pub page: usize,
+	pub range: Allocation,
+}
+
+// bookkeeping for a pool of fixed size pages, seperate from the gpu buffers so it can be tested without a device
+#[derive(Debug)]
+pub struct PageAllocator {
+	// number of elements in a page
+	page_capacity: u32,
+	pages: Vec<RangeAllocator>,
+}
+
+impl PageAllocator {
+	pub fn new(page_capacity: u32) -> Self {
+		PageAllocator {
+			page_capacity,
+			pages: Vec::new(),
+		}
+	}
+
+	// returns the allocation, and the capacity of the new page if one had to be added
+	pub fn alloc(&mut self, len: u32) -> (PoolAllocation, Option<u32>) {
+		for (page, allocator) in self.pages.iter_mut().enumerate() {
+			if let Some(range) = allocator.alloc(len) {
+				return (PoolAllocation { page, range }, None);
+			}
+		}
+
+		let capacity = self.page_capacity.max(len);
+		let mut allocator = RangeAllocator::new(capacity);
+		let range = allocator.alloc(len).unwrap();
+		self.pages.push(allocator);
+
+		(PoolAllocation { page: self.pages.len() - 1, range }, Some(capacity))
+	}
+
+	pub fn free(&mut self, allocation: PoolAllocation) {
+		self.pages[allocation.page].free(allocation.range);
+	}
+
+	pub fn page_count(&self) -> usize {
+		self.pages.len()
+	}
+
+	// number of elements in all pages
+	pub fn capacity(&self) -> u64 {
+		self.pages.iter().map(|page| page.capacity() as u64).sum()
+	}
+
+	pub fn stats(&self) -> PoolStats {
+		let capacity = self.capacity();
+		let free_space: u64 = self.pages.iter().map(|page| page.free_space() as u64).sum();
+		let largest_free_ranges: u64 = self.pages.iter().map(|page| page.largest_free_range() as u64).sum();
+
+		PoolStats {
+			pages: self.page_count(),
+			used_fraction: if capacity == 0 { 0.0 } else { 1.0 - free_space as f64 / capacity as f64 },
+			// free space that isn't part of the largest free range in its page is considered fragmented
+			fragmentation: if free_space == 0 { 0.0 } else { 1.0 - largest_free_ranges as f64 / free_space as f64 },
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+	pub pages: usize,
+	pub used_fraction: f64,
+	pub fragmentation: f64,
+}
+
+// gpu buffers of a single usage which many meshes are sub allocated from
+#[derive(Debug)]
+pub struct BufferPool {
+	label: &'static str,
+	usage: wgpu::BufferUsages,
+	element_size: u64,
+	allocator: PageAllocator,
+	pages: Vec<wgpu::Buffer>,
+}
+
+impl BufferPool {
+	pub fn new(label: &'static str, element_size: u64, usage: wgpu::BufferUsages) -> Self {
+		BufferPool {
+			label,
+			// copy dst is needed to write to the buffer
+			usage: usage | wgpu::BufferUsages::COPY_DST,
+			element_size,
+			allocator: PageAllocator::new((PAGE_SIZE / element_size) as u32),
+			pages: Vec::new(),
+		}
+	}
+
+	// allocates space for the data and writes it, adding a page if no page has space
+	pub fn alloc_write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[u8]) -> PoolAllocation {
+		let len = (data.len() as u64 / self.element_size) as u32;
+
+		let (allocation, new_page_capacity) = self.allocator.alloc(len);
+		if let Some(capacity) = new_page_capacity {
+			debug!("adding page {} to {}", self.pages.len(), self.label);
+
+			self.pages.push(device.create_buffer(&wgpu::BufferDescriptor {
+				label: Some(&format!("{} page {}", self.label, self.pages.len())),
+				size: capacity as u64 * self.element_size,
+				usage: self.usage,
+				mapped_at_creation: false,
+			}));
+		}
+
+		queue.write_buffer(&self.pages[allocation.page], allocation.range.start as u64 * self.element_size, data);
+		allocation
+	}
+
+	pub fn free(&mut self, allocation: PoolAllocation) {
+		self.allocator.free(allocation);
+	}
+
+	pub fn page(&self, page: usize) -> &wgpu::Buffer {
+		&self.pages[page]
+	}
+
+	pub fn stats(&self) -> PoolStats {
+		self.allocator.stats()
+	}
+
+	pub fn size_bytes(&self) -> u64 {
+		self.allocator.capacity() * self.element_size
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::worldgen::DeterministicRng;
+
+	#[test]
+	fn range_allocator_reuses_freed_ranges() {
+		let mut allocator = RangeAllocator::new(100);
+
+		let a = allocator.alloc(30).unwrap();
+		let b = allocator.alloc(30).unwrap();
+		let c = allocator.alloc(30).unwrap();
+		assert_eq!((a.start, b.start, c.start), (0, 30, 60));
+		assert!(allocator.alloc(20).is_none());
+
+		// freeing 2 adjacent ranges merges them
+		allocator.free(a);
+		allocator.free(b);
+		assert_eq!(allocator.alloc(50).unwrap(), Allocation { start: 0, len: 50 });
+
+		allocator.free(c);
+		assert_eq!(allocator.alloc(40).unwrap(), Allocation { start: 50, len: 40 });
+	}
+
+	#[test]
+	fn oversized_allocation_gets_own_page() {
+		let mut allocator = PageAllocator::new(100);
+
+		let (small, new_page) = allocator.alloc(10);
+		assert_eq!((small.page, new_page), (0, Some(100)));
+
+		let (large, new_page) = allocator.alloc(250);
+		assert_eq!((large.page, new_page), (1, Some(250)));
+
+		let (_, new_page) = allocator.alloc(90);
+		assert_eq!(new_page, None);
+	}
+
+	// repeatedly loads and unloads zones of varying mesh sizes, the pool should stop growing once it has enough pages
+	#[test]
+	fn pool_size_stabilizes() {
+		let mut allocator = PageAllocator::new(10_000);
+		let mut loaded_zones: Vec<Vec<PoolAllocation>> = Vec::new();
+		// random mesh sizes
+		let mut rng = DeterministicRng::for_test(12345);
+		let mut next_size = || rng.range(1..501) as u32;
+
+		let mut page_counts = Vec::new();
+		for round in 0..2000 {
+			// keep about 32 zones loaded, like a player moving through the world
+			while loaded_zones.len() < 32 {
+				let zone = (0..16).map(|_| allocator.alloc(next_size()).0).collect();
+				loaded_zones.push(zone);
+			}
+
+			let unloaded = loaded_zones.remove((round * 7) % loaded_zones.len());
+			for allocation in unloaded {
+				allocator.free(allocation);
+			}
+
+			page_counts.push(allocator.page_count());
+		}
+
+		// after warming up, no more pages are needed
+		let warmed_up = page_counts[1000];
+		assert!(page_counts[1000..].iter().all(|count| *count == warmed_up), "page counts: {:?}", page_counts);
+		// 32 zones of at most 16 * 500 elements each fit in well under this many pages
+		assert!(warmed_up <= 32 * 16 * 500 / 10_000 + 4);
+	}
+}
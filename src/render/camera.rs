@@ -24,7 +24,7 @@ pub struct Camera {
 	znear: f32,
 	zfar: f32,
 	// used for culling aabb
-	frustum_planes: [Plane; 4],
+	frustum_planes: [Plane; 6],
 }
 
 impl Camera {
@@ -37,7 +37,7 @@ impl Camera {
 			fovy: 45.0,
 			znear: 0.1,
 			zfar: 1000.0,
-			frustum_planes: [Plane::default(); 4],
+			frustum_planes: [Plane::default(); 6],
 		};
 
 		out.generate_frustum();
@@ -74,10 +74,49 @@ impl Camera {
 			sideways.cross(forward_far - half_y_side * up).normalize(),
 		);
 		// top
-		self.frustum_planes[2] = Plane::new(
+		self.frustum_planes[3] = Plane::new(
 			self.position,
 			(forward_far + half_y_side * up).cross(sideways).normalize(),
 		);
+
+		let forward = self.forward().normalize();
+		// near
+		self.frustum_planes[4] = Plane::new(
+			self.position + self.znear * forward,
+			forward,
+		);
+		// far
+		self.frustum_planes[5] = Plane::new(
+			self.position + self.zfar * forward,
+			-forward,
+		);
+	}
+
+	pub fn znear(&self) -> f32 {
+		self.znear
+	}
+
+	// corners of the part of the view frustum between the near and far distance from the camera
+	// the 4 corners at the near distance come first
+	pub fn frustum_slice_corners(&self, near: f32, far: f32) -> [Vec3; 8] {
+		let forward = self.forward().normalize();
+		let sideways = self.sideways();
+		let up = sideways.cross(forward);
+		// half the size of the frustum 1 unit in front of the camera
+		let half_y_side = (self.fovy * 0.5).tan();
+		let half_x_side = half_y_side * self.aspect_ratio;
+
+		let mut corners = [Vec3::ZERO; 8];
+		for (i, distance) in [near, far].into_iter().enumerate() {
+			let center = self.position + distance * forward;
+			let x = distance * half_x_side * sideways;
+			let y = distance * half_y_side * up;
+			corners[4 * i] = center - x - y;
+			corners[4 * i + 1] = center + x - y;
+			corners[4 * i + 2] = center + x + y;
+			corners[4 * i + 3] = center - x + y;
+		}
+		corners
 	}
 
 	pub fn get_camera_matrix(&self) -> Mat4 {
@@ -91,7 +130,26 @@ impl Camera {
 
 	// gets a camera uniform which can be sent to the gpu
 	pub fn get_camera_uniform(&self) -> CameraUniform {
-		CameraUniform(self.get_camera_matrix().to_cols_array_2d())
+		CameraUniform::from_matrix(self.get_camera_matrix())
+	}
+
+	// where the position is drawn on a screen of the given size, in the same units as the size with the origin at the top left
+	// none if the position is behind the camera or off the screen
+	pub fn world_to_screen(&self, pos: Position, screen_size: egui::Vec2) -> Option<egui::Pos2> {
+		let clip = self.get_camera_matrix() * pos.0.extend(1.0);
+		if clip.w <= 0.0 {
+			return None;
+		}
+
+		let ndc = clip.truncate() / clip.w;
+		if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 || ndc.z > 1.0 {
+			return None;
+		}
+
+		Some(egui::Pos2::new(
+			0.5 * (ndc.x + 1.0) * screen_size.x,
+			0.5 * (1.0 - ndc.y) * screen_size.y,
+		))
 	}
 
 	pub fn get_position(&self) -> Position {
@@ -107,16 +165,109 @@ impl Camera {
 		self.forward().cross(self.up).normalize()
 	}
 
+	// the camera seen in a horizontal mirror at plane_height, used to draw reflections
+	// up stays the same, so the mirrored view is upside down compared to a real reflection,
+	// which doesn't matter since reflections are sampled by projecting with the mirrored camera's matrix
+	pub fn mirrored(&self, plane_height: f32) -> Camera {
+		let mirror = |point: Vec3| Vec3::new(point.x, 2.0 * plane_height - point.y, point.z);
+		let mut mirrored = Camera {
+			position: mirror(self.position),
+			look_at: mirror(self.look_at),
+			frustum_planes: [Plane::default(); 6],
+			..*self
+		};
+		mirrored.generate_frustum();
+		mirrored
+	}
+
 	// returns true if any part of the axis aligned bounding box is vivisble in the camera
 	pub fn bounding_box_visible(&self, aabb: Aabb) -> bool {
+		// the box the camera is inside is always visible, the near plane would cull it if the camera is facing out of it
+		if aabb.contains_point(self.position) {
+			return true;
+		}
+
 		// this might be cleaner with iter reduce, but i'm not sure if that would get as optimized
 		aabb.inside_of_plane(self.frustum_planes[0])
 			&& aabb.inside_of_plane(self.frustum_planes[1])
 			&& aabb.inside_of_plane(self.frustum_planes[2])
 			&& aabb.inside_of_plane(self.frustum_planes[3])
+			&& aabb.inside_of_plane(self.frustum_planes[4])
+			&& aabb.inside_of_plane(self.frustum_planes[5])
 	}
 }
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform([[f32; 4]; 4]);
+
+impl CameraUniform {
+	pub fn from_matrix(matrix: Mat4) -> Self {
+		CameraUniform(matrix.to_cols_array_2d())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// size of a render zone in blocks
+	const ZONE_SIZE: f32 = 64.0;
+
+	#[test]
+	fn zone_containing_camera_is_visible() {
+		let zone = Aabb::new(Vec3::ZERO, Vec3::splat(ZONE_SIZE));
+
+		// just inside the zone's +x face, looking out of the zone, so the whole zone is behind the near plane
+		let position = Vec3::new(ZONE_SIZE - 0.05, 32.0, 32.0);
+		let camera = Camera::new(position, position + Vec3::X, 16.0 / 9.0);
+
+		assert!(camera.bounding_box_visible(zone));
+
+		// the zone in front of the camera is visible and the zone behind the camera's zone is not
+		let front_zone = Aabb::new(Vec3::new(ZONE_SIZE, 0.0, 0.0), Vec3::splat(ZONE_SIZE));
+		let behind_zone = Aabb::new(Vec3::new(-ZONE_SIZE, 0.0, 0.0), Vec3::splat(ZONE_SIZE));
+		assert!(camera.bounding_box_visible(front_zone));
+		assert!(!camera.bounding_box_visible(behind_zone));
+	}
+
+	#[test]
+	fn world_to_screen_projects_in_front_of_camera() {
+		let camera = Camera::new(Vec3::ZERO, Vec3::X, 16.0 / 9.0);
+		let screen_size = egui::Vec2::new(1280.0, 720.0);
+
+		let center = camera.world_to_screen(Position::new(10.0, 0.0, 0.0), screen_size).unwrap();
+		assert!((center - egui::Pos2::new(640.0, 360.0)).length() < 0.01);
+
+		// up is towards the top of the screen, and sideways is to the right
+		let above = camera.world_to_screen(Position::new(10.0, 1.0, 0.0), screen_size).unwrap();
+		assert!(above.y < center.y);
+		let right = camera.world_to_screen(Position(10.0 * Vec3::X + camera.sideways()), screen_size).unwrap();
+		assert!(right.x > center.x);
+
+		// behind the camera, or too far to the side to be on the screen
+		assert_eq!(camera.world_to_screen(Position::new(-10.0, 0.0, 0.0), screen_size), None);
+		assert_eq!(camera.world_to_screen(Position::new(10.0, 0.0, 100.0), screen_size), None);
+	}
+
+	#[test]
+	fn mirrored_camera_sees_the_reflection() {
+		// looking down at water with its surface at y = 10
+		let camera = Camera::new(Vec3::new(0.0, 20.0, 0.0), Vec3::new(10.0, 15.0, 0.0), 16.0 / 9.0);
+		let mirrored = camera.mirrored(10.0);
+		assert_eq!(mirrored.position, Vec3::new(0.0, 0.0, 0.0));
+		assert!(mirrored.forward().y > 0.0);
+
+		// points on the water surface are seen by both cameras
+		let surface = Aabb::new(Vec3::new(19.0, 10.0, -1.0), Vec3::new(2.0, 0.0, 2.0));
+		assert!(camera.bounding_box_visible(surface));
+		assert!(mirrored.bounding_box_visible(surface));
+		let surface_point = Vec3::new(20.0, 10.0, 0.0);
+		let clip = mirrored.get_camera_matrix() * surface_point.extend(1.0);
+		assert!(clip.w > 0.0 && (clip.truncate() / clip.w).abs().max_element() <= 1.0);
+
+		// a hill above the water is reflected in it, and terrain under the water is behind the mirrored camera's view
+		assert!(mirrored.bounding_box_visible(Aabb::new(Vec3::new(40.0, 10.0, -2.0), Vec3::new(4.0, 10.0, 4.0))));
+		assert!(!mirrored.bounding_box_visible(Aabb::new(Vec3::new(-40.0, -30.0, -2.0), Vec3::new(4.0, 4.0, 4.0))));
+	}
+}
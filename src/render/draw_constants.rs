@@ -0,0 +1,97 @@
+use std::mem;
+use std::num::NonZeroU64;
+
+// per draw data of terrain, each draw reads its own slot of the buffer through a dynamic offset
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawConstants {
+	pub alpha: f32,
+	// from DetailLevel::shader_value
+	pub detail: f32,
+}
+
+impl DrawConstants {
+	const SIZE: u64 = mem::size_of::<Self>() as u64;
+}
+
+// uniform buffer holding the draw constants of every terrain draw in a frame
+// push constants would be simpler, but many adapters don't support them
+#[derive(Debug)]
+pub struct DrawConstantsBuffer {
+	buffer: wgpu::Buffer,
+	// number of slots in the buffer
+	capacity: usize,
+	// slots are spaced by the device's dynamic offset alignment
+	stride: u64,
+}
+
+impl DrawConstantsBuffer {
+	const INITIAL_CAPACITY: usize = 64;
+
+	pub fn new(device: &wgpu::Device) -> Self {
+		let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+		let stride = DrawConstants::SIZE.div_ceil(alignment) * alignment;
+		DrawConstantsBuffer {
+			buffer: Self::create_buffer(device, Self::INITIAL_CAPACITY, stride),
+			capacity: Self::INITIAL_CAPACITY,
+			stride,
+		}
+	}
+
+	fn create_buffer(device: &wgpu::Device, capacity: usize, stride: u64) -> wgpu::Buffer {
+		device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("draw constants buffer"),
+			size: capacity as u64 * stride,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		})
+	}
+
+	// layout entry of the binding in the scene bind group
+	pub fn bind_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+		wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Uniform,
+				has_dynamic_offset: true,
+				min_binding_size: NonZeroU64::new(DrawConstants::SIZE),
+			},
+			count: None,
+		}
+	}
+
+	// a single slot is bound, the dynamic offset selects which one
+	pub fn binding(&self) -> wgpu::BindingResource<'_> {
+		wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+			buffer: &self.buffer,
+			offset: 0,
+			size: NonZeroU64::new(DrawConstants::SIZE),
+		})
+	}
+
+	// dynamic offset of the constants at index in the last write
+	pub fn offset(&self, index: usize) -> u32 {
+		(index as u64 * self.stride) as u32
+	}
+
+	// writes the constants to consecutive slots
+	// returns true if the buffer had to be recreated to fit them, then bind groups using the old buffer must be recreated
+	pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, constants: &[DrawConstants]) -> bool {
+		let recreated = constants.len() > self.capacity;
+		if recreated {
+			self.capacity = constants.len().next_power_of_two();
+			self.buffer = Self::create_buffer(device, self.capacity, self.stride);
+		}
+
+		let mut data = vec![0; constants.len() * self.stride as usize];
+		for (slot, constants) in data.chunks_exact_mut(self.stride as usize).zip(constants) {
+			slot[..DrawConstants::SIZE as usize].copy_from_slice(bytemuck::bytes_of(constants));
+		}
+		if !data.is_empty() {
+			queue.write_buffer(&self.buffer, 0, &data);
+		}
+
+		recreated
+	}
+}
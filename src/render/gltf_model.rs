@@ -0,0 +1,198 @@
+use anyhow::{Result, bail, anyhow};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use glam::{Mat3, Mat4, Vec3};
+use gltf::mesh::Mode;
+use gltf::image::Format;
+
+use super::model::{MeshData, ModelData, ModelVertex};
+
+impl ModelData {
+	// every mesh of every node in the default scene becomes a mesh with the node's transform baked into its vertices
+	// animations and skins are ignored, and primitives which aren't triangle lists are skipped
+	pub fn from_gltf(
+		document: &gltf::Document,
+		buffers: &[gltf::buffer::Data],
+		images: &[gltf::image::Data],
+	) -> Result<Self> {
+		let mut materials = document.materials()
+			.map(|material| gltf_material(&material, images))
+			.collect::<Result<Vec<_>>>()?;
+
+		// used by primitives with no material, only added if a primitive needs it
+		let default_material_index = materials.len();
+		let mut uses_default_material = false;
+
+		if document.animations().len() > 0 || document.skins().len() > 0 {
+			warn!("gltf animations and skins are not supported, they will be ignored");
+		}
+
+		let scene = document.default_scene()
+			.or_else(|| document.scenes().next())
+			.ok_or_else(|| anyhow!("gltf file has no scenes"))?;
+
+		// nodes to visit, with the transform of their parent
+		let mut nodes = scene.nodes().map(|node| (node, Mat4::IDENTITY)).collect::<Vec<_>>();
+		let mut meshes = Vec::new();
+		while let Some((node, parent_transform)) = nodes.pop() {
+			let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+			nodes.extend(node.children().map(|child| (child, transform)));
+
+			let mesh = match node.mesh() {
+				Some(mesh) => mesh,
+				None => continue,
+			};
+
+			// normals are transformed by the inverse transpose so they stay perpendicular with non uniform scale
+			let normal_transform = Mat3::from_mat4(transform).inverse().transpose();
+
+			for primitive in mesh.primitives() {
+				let name = format!(
+					"{} primitive {}",
+					mesh.name().or_else(|| node.name()).unwrap_or("unnamed mesh"),
+					primitive.index(),
+				);
+
+				if primitive.mode() != Mode::Triangles {
+					warn!("skipping {}, it uses {:?} instead of triangles", name, primitive.mode());
+					continue;
+				}
+
+				let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+				let positions = match reader.read_positions() {
+					Some(positions) => positions,
+					None => {
+						warn!("skipping {}, it has no positions", name);
+						continue;
+					},
+				};
+
+				let mut vertices = positions
+					.map(|position| ModelVertex {
+						position: transform.transform_point3(Vec3::from(position)).to_array(),
+						tex_coords: [0.0, 0.0],
+						normal: [0.0, 0.0, 0.0],
+					})
+					.collect::<Vec<_>>();
+
+				if let Some(normals) = reader.read_normals() {
+					for (vertex, normal) in vertices.iter_mut().zip(normals) {
+						vertex.normal = (normal_transform * Vec3::from(normal)).normalize_or_zero().to_array();
+					}
+				}
+
+				if let Some(tex_coords) = reader.read_tex_coords(0) {
+					for (vertex, tex_coords) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+						vertex.tex_coords = tex_coords;
+					}
+				}
+
+				// primitives without indices draw their vertices in order
+				let mut indices = match reader.read_indices() {
+					Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+					None => (0..vertices.len() as u32).collect(),
+				};
+
+				// a transform which mirrors the mesh flips its winding order
+				if transform.determinant() < 0.0 {
+					for triangle in indices.chunks_exact_mut(3) {
+						triangle.swap(1, 2);
+					}
+				}
+
+				let material_index = primitive.material().index().unwrap_or_else(|| {
+					uses_default_material = true;
+					default_material_index
+				});
+
+				meshes.push(MeshData::new(name, vertices, indices, material_index));
+			}
+		}
+
+		if uses_default_material {
+			materials.push((String::from("default material"), solid_color_image([1.0; 4])));
+		}
+
+		Ok(ModelData {
+			meshes,
+			materials,
+		})
+	}
+}
+
+// the base color texture of the material, or its base color if it has no texture
+fn gltf_material(material: &gltf::Material, images: &[gltf::image::Data]) -> Result<(String, DynamicImage)> {
+	let name = material.name()
+		.map(String::from)
+		.unwrap_or_else(|| format!("material {}", material.index().unwrap_or_default()));
+
+	let pbr = material.pbr_metallic_roughness();
+	let image = match pbr.base_color_texture() {
+		Some(info) => gltf_image(&images[info.texture().source().index()])
+			.map_err(|error| anyhow!("could not load texture of {}: {}", name, error))?,
+		None => solid_color_image(pbr.base_color_factor()),
+	};
+
+	Ok((name, image))
+}
+
+fn gltf_image(image: &gltf::image::Data) -> Result<DynamicImage> {
+	let pixels = image.pixels.clone();
+	let image = match image.format {
+		Format::R8G8B8A8 => ImageBuffer::from_raw(image.width, image.height, pixels).map(DynamicImage::ImageRgba8),
+		Format::R8G8B8 => ImageBuffer::from_raw(image.width, image.height, pixels).map(DynamicImage::ImageRgb8),
+		Format::R8G8 => ImageBuffer::from_raw(image.width, image.height, pixels).map(DynamicImage::ImageLumaA8),
+		Format::R8 => ImageBuffer::from_raw(image.width, image.height, pixels).map(DynamicImage::ImageLuma8),
+		format => bail!("unsupported image format {:?}", format),
+	};
+
+	image.ok_or_else(|| anyhow!("image data is smaller than its size"))
+}
+
+// a 1 by 1 image of the color
+fn solid_color_image(color: [f32; 4]) -> DynamicImage {
+	let color = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+	DynamicImage::ImageRgba8(ImageBuffer::from_pixel(1, 1, Rgba(color)))
+}
+
+#[cfg(test)]
+mod tests {
+	use image::GenericImageView;
+
+	use super::*;
+	use crate::assets::loader;
+	use crate::render::Aabb;
+
+	#[test]
+	fn load_glb_fixture() {
+		let (document, buffers, images) = loader().load_gltf("models/test-quads.glb").unwrap();
+		// the fixture has an animation, which is ignored instead of failing the load
+		assert_eq!(document.animations().len(), 1);
+		let model = ModelData::from_gltf(&document, &buffers, &images).unwrap();
+
+		assert_eq!(model.materials.len(), 1);
+		assert_eq!(model.materials[0].0, "checker");
+		assert_eq!(model.materials[0].1.dimensions(), (2, 2));
+		assert_eq!(model.materials[0].1.get_pixel(1, 0).0, [0, 255, 0, 255]);
+
+		// the same unit quad is used by 2 nodes, each with its transform baked in
+		assert_eq!(model.meshes.len(), 2);
+		for mesh in model.meshes.iter() {
+			assert_eq!(mesh.vertices.len(), 4);
+			assert_eq!(mesh.indices.len(), 6);
+			assert_eq!(mesh.material_index, 0);
+		}
+
+		let mut bounding_boxes = model.meshes.iter().map(|mesh| mesh.bounding_box.unwrap()).collect::<Vec<_>>();
+		bounding_boxes.sort_by(|a, b| a.position.x.total_cmp(&b.position.x));
+
+		// rotated a quarter turn around y, so the quad faces +x
+		let rotated = bounding_boxes[0];
+		assert!(rotated.position.abs_diff_eq(Vec3::new(0.0, 0.0, -1.0), 1.0e-5));
+		assert!(rotated.size.abs_diff_eq(Vec3::new(0.0, 1.0, 1.0), 1.0e-5));
+		let rotated_mesh = model.meshes.iter().find(|mesh| mesh.bounding_box == Some(rotated)).unwrap();
+		assert!(Vec3::from(rotated_mesh.vertices[0].normal).abs_diff_eq(Vec3::X, 1.0e-5));
+
+		// scaled by 2 then translated by its parent
+		assert_eq!(bounding_boxes[1], Aabb::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(2.0, 2.0, 0.0)));
+	}
+}
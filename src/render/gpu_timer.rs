@@ -0,0 +1,113 @@
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+// measures how long the gpu spends on part of a frame with timestamp queries
+// the result is read back once the gpu is done with it, so measuring never stalls rendering,
+// frames which finish while an earlier result is still being read aren't measured
+pub struct GpuTimer {
+	// the start and end timestamp
+	query_set: wgpu::QuerySet,
+	// the timestamps are resolved straight into a buffer which can be mapped
+	read_buffer: wgpu::Buffer,
+	// nanoseconds per timestamp tick
+	period: f32,
+	// set when the timestamps of this frame are resolved into the read buffer
+	copied: bool,
+	// true from when the read buffer starts being mapped until it is unmapped again
+	reading: bool,
+	// set by the map callback to whether the read buffer was mapped
+	map_result: Arc<Mutex<Option<bool>>>,
+	last_time: Option<Duration>,
+}
+
+const TIMESTAMPS_SIZE: u64 = 2 * mem::size_of::<u64>() as u64;
+
+impl GpuTimer {
+	// returns none if the device doesn't support timestamp queries
+	pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+		if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+			return None;
+		}
+
+		let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+			label: Some("gpu timer query set"),
+			ty: wgpu::QueryType::Timestamp,
+			count: 2,
+		});
+		let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("gpu timer read buffer"),
+			size: TIMESTAMPS_SIZE,
+			usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		Some(GpuTimer {
+			query_set,
+			read_buffer,
+			period: queue.get_timestamp_period(),
+			copied: false,
+			reading: false,
+			map_result: Arc::new(Mutex::new(None)),
+			last_time: None,
+		})
+	}
+
+	pub fn start(&self, encoder: &mut wgpu::CommandEncoder) {
+		encoder.write_timestamp(&self.query_set, 0);
+	}
+
+	pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+		encoder.write_timestamp(&self.query_set, 1);
+		if !self.reading {
+			encoder.resolve_query_set(&self.query_set, 0..2, &self.read_buffer, 0);
+			self.copied = true;
+		}
+	}
+
+	// must be called after the encoder passed to end is submitted
+	pub fn submitted(&mut self) {
+		if !self.copied {
+			return;
+		}
+		self.copied = false;
+		self.reading = true;
+
+		let map_result = self.map_result.clone();
+		self.read_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+			*map_result.lock() = Some(result.is_ok());
+		});
+	}
+
+	// the most recently measured time, checking if a newer one is ready
+	pub fn time(&mut self, device: &wgpu::Device) -> Option<Duration> {
+		device.poll(wgpu::Maintain::Poll);
+		let map_result = self.map_result.lock().take();
+		if let Some(mapped) = map_result {
+			if mapped {
+				{
+					let data = self.read_buffer.slice(..).get_mapped_range();
+					let timestamps: &[u64] = bytemuck::cast_slice(&data);
+					let ticks = timestamps[1].saturating_sub(timestamps[0]);
+					self.last_time = Some(Duration::from_nanos((ticks as f64 * self.period as f64) as u64));
+				}
+				self.read_buffer.unmap();
+			}
+			self.reading = false;
+		}
+		self.last_time
+	}
+}
+
+// wgpu's query sets aren't Debug
+impl fmt::Debug for GpuTimer {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("GpuTimer")
+			.field("reading", &self.reading)
+			.field("last_time", &self.last_time)
+			.finish()
+	}
+}
@@ -0,0 +1,67 @@
+use glam::Mat4;
+use wgpu::util::DeviceExt;
+
+use super::camera::{Camera, CameraUniform};
+use super::model::Mesh;
+
+// the block in the player's hand, drawn over the world in its own pass
+// the camera matrix it is drawn with also moves the mesh into place,
+// so the mesh keeps the block's own coordinates, which its textures are sampled from
+#[derive(Debug)]
+pub struct HeldBlockRenderer {
+	mesh: Option<Mesh>,
+	// from the mesh's coordinates to world space
+	transform: Mat4,
+	camera_buffer: wgpu::Buffer,
+	camera_bind_group: wgpu::BindGroup,
+}
+
+impl HeldBlockRenderer {
+	pub fn new(device: &wgpu::Device, camera_bind_layout: &wgpu::BindGroupLayout) -> Self {
+		let camera_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("held block camera buffer"),
+				contents: bytemuck::cast_slice(&[CameraUniform::from_matrix(Mat4::IDENTITY)]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let camera_bind_group = device.create_bind_group(
+			&wgpu::BindGroupDescriptor {
+				label: Some("held block camera bind group"),
+				layout: camera_bind_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: camera_buffer.as_entire_binding(),
+					},
+				],
+			}
+		);
+
+		HeldBlockRenderer {
+			mesh: None,
+			transform: Mat4::IDENTITY,
+			camera_buffer,
+			camera_bind_group,
+		}
+	}
+
+	// none when nothing is held
+	pub fn set_mesh(&mut self, mesh: Option<Mesh>) {
+		self.mesh = mesh;
+	}
+
+	pub fn set_transform(&mut self, transform: Mat4) {
+		self.transform = transform;
+	}
+
+	// writes the camera matrix for this frame, and returns the mesh and camera bind group to draw it with
+	// returns none if nothing is held
+	pub fn prepare(&self, queue: &wgpu::Queue, camera: &Camera) -> Option<(&Mesh, &wgpu::BindGroup)> {
+		let mesh = self.mesh.as_ref()?;
+		let camera_uniform = CameraUniform::from_matrix(camera.get_camera_matrix() * self.transform);
+		queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+		Some((mesh, &self.camera_bind_group))
+	}
+}
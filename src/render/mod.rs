@@ -1,21 +1,62 @@
-use std::num::NonZeroU32;
+use std::mem;
+use std::sync::Arc;
+use std::borrow::Cow;
+use std::time::Duration;
 
 use egui_wgpu_backend::ScreenDescriptor;
 //use nalgebra::{Point3, Vector3, Scale3, UnitQuaternion, Unit};
-use glam::{Vec3, Mat4};
+use glam::{Vec3, Mat4, Quat};
 use winit::window::Window;
 use wgpu::util::DeviceExt;
 
-use texture::{Texture, DepthTexture};
+use texture::{DepthTexture, SamplerCache, TextureFilter};
 use camera::Camera;
 use model::*;
-use crate::game::{BlockVertex, num_textures};
+use terrain_buffer::{TerrainBuffers, TerrainMesh, DrawIndexedIndirectArgs};
+use render_queue::{RenderQueue, SortedDraws, DetailLevel};
+use blob_shadow::ShadowVertex;
+use particle::{Particle, ParticleConstants};
+use sky::Sky;
+use shadow_map::{ShadowMaps, ShadowBias, CASCADE_COUNT, DEFAULT_SUN_DIRECTION, light_box_visible};
+use buffer_pool::PoolStats;
+use gpu_timer::GpuTimer;
+use held_block::HeldBlockRenderer;
+use reflection::{Reflection, create_placeholder};
+use draw_constants::{DrawConstants, DrawConstantsBuffer};
+use crate::game::BlockVertex;
+use crate::prelude::*;
+#[cfg(debug_assertions)]
+use crate::assets::loader;
 
 pub mod camera;
 pub mod model;
-mod bounding_box;
-pub use bounding_box::Aabb;
+mod gltf_model;
+pub use crate::math::Aabb;
 pub mod texture;
+pub mod terrain_buffer;
+pub mod render_queue;
+pub mod blob_shadow;
+pub mod particle;
+pub mod shadow_map;
+mod sky;
+mod buffer_pool;
+mod gpu_timer;
+mod held_block;
+mod reflection;
+mod draw_constants;
+
+// release builds only use the embedded shader
+const EMBEDDED_SHADER: &str = include_str!("shader.wgsl");
+// the shadow shader is small and rarely changed, so it isn't reloaded
+const SHADOW_SHADER: &str = include_str!("shadow.wgsl");
+const PARTICLE_SHADER: &str = include_str!("particle.wgsl");
+// size of each shadow cascade before the settings are applied
+const DEFAULT_SHADOW_RESOLUTION: u32 = 2048;
+// index of the draw constants the water reflection is drawn with, in the frame's draw constants buffer
+const REFLECTION_CONSTANTS_INDEX: usize = 0;
+// debug builds read the shader from the source tree, so it can be reloaded without recompiling
+#[cfg(debug_assertions)]
+const SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/render/shader.wgsl");
 
 #[derive(Debug)]
 pub struct Renderer {
@@ -23,8 +64,59 @@ pub struct Renderer {
 	device: wgpu::Device,
 	queue: wgpu::Queue,
 	config: wgpu::SurfaceConfiguration,
+	// vsync can only be turned off if the surface supports a present mode which doesn't wait for it
+	supported_present_modes: Vec<wgpu::PresentMode>,
 	render_pipeline: wgpu::RenderPipeline,
+	// draws terrain which is fading in, with blending
+	fade_pipeline: wgpu::RenderPipeline,
+	held_block_pipeline: wgpu::RenderPipeline,
+	held_block: HeldBlockRenderer,
+	// draws opaque terrain into the reflection, skipping what is under the water
+	reflection_pipeline: wgpu::RenderPipeline,
+	// none while water reflections are off
+	reflection: Option<Reflection>,
+	// height of the water surface to reflect, none if there is no water to reflect
+	reflection_plane: Option<f32>,
+	// texture index of water, whose top faces show the reflection
+	water_texture_index: i32,
+	reflection_sampler: wgpu::Sampler,
+	// kept alive for its view
+	_placeholder_reflection_texture: wgpu::Texture,
+	placeholder_reflection_view: wgpu::TextureView,
+	shadow_pipeline: wgpu::RenderPipeline,
+	particle_pipeline: wgpu::RenderPipeline,
+	// the camera direction particles turn to face, written each frame they are drawn
+	particle_constants_buffer: wgpu::Buffer,
+	particle_bind_group: wgpu::BindGroup,
+	// draws terrain depth into the shadow cascades
+	shadow_map_pipeline: wgpu::RenderPipeline,
+	shadow_maps: ShadowMaps,
+	shadow_bind_layout: wgpu::BindGroupLayout,
+	shadows_enabled: bool,
+	shadow_bias: ShadowBias,
+	// how far the sky has turned from its default, the sun and stars turn with it
+	sky_rotation: Quat,
+	sky: Sky,
+	terrain_buffers: TerrainBuffers,
+	// if all terrain can be drawn with a single multi draw indirect call
+	multi_draw_indirect: bool,
+	gpu_timer: Option<GpuTimer>,
 	texture_bind_layout: wgpu::BindGroupLayout,
+	samplers: SamplerCache,
+	// kept to rebuild the render pipeline when the shader is reloaded
+	camera_bind_layout: wgpu::BindGroupLayout,
+	scene_uniform: SceneUniform,
+	ao_strength: f32,
+	face_shading: bool,
+	fog: Fog,
+	scene_buffer: wgpu::Buffer,
+	// alpha and detail of each terrain draw, bound in the scene bind group
+	draw_constants: DrawConstantsBuffer,
+	scene_bind_layout: wgpu::BindGroupLayout,
+	// samples the reflection if there is one
+	scene_bind_group: wgpu::BindGroup,
+	// used while drawing the reflection, which can't sample itself
+	reflection_scene_bind_group: wgpu::BindGroup,
 	depth_texture: DepthTexture,
 	camera: Camera,
 	camera_modified: bool,
@@ -43,6 +135,97 @@ pub struct RenderContext<'a> {
 	device: &'a wgpu::Device,
 	queue: &'a wgpu::Queue,
 	texture_bind_layout: &'a wgpu::BindGroupLayout,
+	samplers: &'a SamplerCache,
+}
+
+impl RenderContext<'_> {
+	// samplers with the same filter are shared
+	pub fn sampler(&self, filter: TextureFilter) -> Arc<wgpu::Sampler> {
+		self.samplers.get(self.device, filter)
+	}
+}
+
+// the terrain shader is used for several pipelines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PipelineKind {
+	Opaque,
+	// blends with what is behind it and doesn't write depth
+	Fading,
+	// the block in the player's hand, drawn in its own pass after the world
+	HeldBlock,
+	// opaque terrain in the water reflection
+	Reflected,
+}
+
+// fog which fades terrain into the fog color between the start and end distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+	pub color: Vec3,
+	pub start: f32,
+	pub end: f32,
+}
+
+impl Fog {
+	// fog used before the client sets any, far enough away that nothing is fogged
+	const NONE: Fog = Fog {
+		color: Vec3::new(0.1, 0.2, 0.3),
+		start: 1.0e9,
+		end: 2.0e9,
+	};
+}
+
+// scene wide parameters the shader applies to terrain, which can be changed without remeshing
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct SceneUniform {
+	// the 4th component is unused, it is only there for alignment
+	fog_color: [f32; 4],
+	ao_strength: f32,
+	fog_start: f32,
+	fog_end: f32,
+	water_texture_index: i32,
+	// the 4th component is unused
+	camera_position: [f32; 4],
+	// the mirrored camera's matrix, which positions on the water surface are projected with to sample the reflection
+	reflection_view_proj: [[f32; 4]; 4],
+	reflection_height: f32,
+	// 1 if a reflection is drawn this frame
+	reflection_strength: f32,
+	// 1 if faces are darkened by their direction
+	face_shading: f32,
+	// uniforms are padded to 16 bytes
+	_padding: f32,
+}
+
+impl SceneUniform {
+	// reflection is the mirrored camera and the height of the water surface, if a reflection is drawn
+	fn new(ao_strength: f32, face_shading: bool, fog: Fog, water_texture_index: i32, camera_position: Vec3, reflection: Option<(&Camera, f32)>) -> Self {
+		SceneUniform {
+			fog_color: fog.color.extend(1.0).to_array(),
+			ao_strength,
+			fog_start: fog.start,
+			fog_end: fog.end,
+			water_texture_index,
+			camera_position: camera_position.extend(1.0).to_array(),
+			reflection_view_proj: reflection.map_or(Mat4::IDENTITY, |(camera, _)| camera.get_camera_matrix()).to_cols_array_2d(),
+			reflection_height: reflection.map_or(0.0, |(_, height)| height),
+			reflection_strength: if reflection.is_some() { 1.0 } else { 0.0 },
+			face_shading: if face_shading { 1.0 } else { 0.0 },
+			_padding: 0.0,
+		}
+	}
+}
+
+// statistics about the last rendered frame, shown in the debug window
+#[derive(Debug, Clone, Copy)]
+pub struct RenderStats {
+	pub draw_calls: usize,
+	pub visible_meshes: usize,
+	// cull groups which were drawn and which were culled by the frustum
+	pub visible_groups: usize,
+	pub culled_groups: usize,
+	// time the gpu spent on the main pass of a recent frame, none if the gpu can't measure it
+	pub gpu_time: Option<Duration>,
 }
 
 impl Renderer {
@@ -61,9 +244,14 @@ impl Renderer {
 			},
 		).await.unwrap();
 
-		let features = wgpu::Features::TEXTURE_BINDING_ARRAY
-			| wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
-			| wgpu::Features::POLYGON_MODE_LINE;
+		// multi draw indirect is used if it is available, otherwise each terrain mesh is drawn seperately
+		let multi_draw_indirect = adapter.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT);
+		let mut features = wgpu::Features::POLYGON_MODE_LINE;
+		if multi_draw_indirect {
+			features |= wgpu::Features::MULTI_DRAW_INDIRECT;
+		}
+		// the gpu time of each frame is measured if it is supported
+		features |= adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
 
 		let (device, queue) = adapter.request_device(
 			&wgpu::DeviceDescriptor {
@@ -85,32 +273,13 @@ impl Renderer {
 			present_mode: wgpu::PresentMode::Fifo,
 		};
 		surface.configure(&device, &config);
+		let supported_present_modes = surface.get_supported_modes(&adapter);
 
-		let texture_bind_group_layout = device.create_bind_group_layout(
-			&wgpu::BindGroupLayoutDescriptor {
-				label: Some("texture bind group layout"),
-				entries: &[
-					wgpu::BindGroupLayoutEntry {
-						binding: 0,
-						visibility: wgpu::ShaderStages::FRAGMENT,
-						ty: wgpu::BindingType::Texture {
-							multisampled: false,
-							view_dimension: wgpu::TextureViewDimension::D2,
-							sample_type: wgpu::TextureSampleType::Float { filterable: true },
-						},
-						count: NonZeroU32::new(num_textures()),
-					},
-					wgpu::BindGroupLayoutEntry {
-						binding: 1,
-						visibility: wgpu::ShaderStages::FRAGMENT,
-						ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-						count: None,
-					},
-				],
-			}
-		);
+		let texture_bind_group_layout = Self::create_texture_bind_layout(&device);
 
 		let depth_texture = DepthTexture::new(&device, &config, "depth texture");
+		let terrain_buffers = TerrainBuffers::new(&device);
+		let gpu_timer = GpuTimer::new(&device, &queue);
 
 		// render pipeline
 		let camera = Camera::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), config.width as f32 / config.height as f32);
@@ -155,18 +324,287 @@ impl Renderer {
 			}
 		);
 
-		let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+		let scene_uniform = SceneUniform::new(1.0, true, Fog::NONE, -1, camera.position, None);
+		let scene_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("scene buffer"),
+				contents: bytemuck::cast_slice(&[scene_uniform]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let scene_bind_group_layout = device.create_bind_group_layout(
+			&wgpu::BindGroupLayoutDescriptor {
+				label: Some("scene bind group layout"),
+				entries: &[
+					wgpu::BindGroupLayoutEntry {
+						binding: 0,
+						visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+						ty: wgpu::BindingType::Buffer {
+							ty: wgpu::BufferBindingType::Uniform,
+							has_dynamic_offset: false,
+							min_binding_size: None,
+						},
+						count: None,
+					},
+					wgpu::BindGroupLayoutEntry {
+						binding: 1,
+						visibility: wgpu::ShaderStages::FRAGMENT,
+						ty: wgpu::BindingType::Texture {
+							multisampled: false,
+							view_dimension: wgpu::TextureViewDimension::D2,
+							sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						},
+						count: None,
+					},
+					wgpu::BindGroupLayoutEntry {
+						binding: 2,
+						visibility: wgpu::ShaderStages::FRAGMENT,
+						ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+						count: None,
+					},
+					DrawConstantsBuffer::bind_layout_entry(3),
+				],
+			}
+		);
+		let draw_constants = DrawConstantsBuffer::new(&device);
+
+		let reflection_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+			label: Some("reflection sampler"),
+			address_mode_u: wgpu::AddressMode::ClampToEdge,
+			address_mode_v: wgpu::AddressMode::ClampToEdge,
+			address_mode_w: wgpu::AddressMode::ClampToEdge,
+			mag_filter: wgpu::FilterMode::Linear,
+			min_filter: wgpu::FilterMode::Linear,
+			..Default::default()
+		});
+		let (placeholder_reflection_texture, placeholder_reflection_view) = create_placeholder(&device, &queue);
+		// reflections start off, so both scene bind groups sample the placeholder
+		let scene_bind_group = Self::create_scene_bind_group(&device, &scene_bind_group_layout, &scene_buffer, &draw_constants, &placeholder_reflection_view, &reflection_sampler);
+		let reflection_scene_bind_group = Self::create_scene_bind_group(&device, &scene_bind_group_layout, &scene_buffer, &draw_constants, &placeholder_reflection_view, &reflection_sampler);
+
+		let shadow_bind_layout = ShadowMaps::create_bind_layout(&device);
+		let shadow_maps = ShadowMaps::new(&device, DEFAULT_SHADOW_RESOLUTION, &camera_bind_group_layout, &shadow_bind_layout);
+
+		let sky = Sky::new(&device, config.format);
+		let shadow_map_pipeline = ShadowMaps::build_pipeline(&device, &camera_bind_group_layout);
+
+		let bind_layouts = [&texture_bind_group_layout, &camera_bind_group_layout, &scene_bind_group_layout, &shadow_bind_layout];
+
+		// if the shader on disk can't be used, fall back to the embedded shader so the game still starts
+		#[cfg(debug_assertions)]
+		let (render_pipeline, fade_pipeline, held_block_pipeline, reflection_pipeline) = Self::load_shader_source()
+			.and_then(|source| Self::build_pipelines(
+				&device,
+				config.format,
+				&source,
+				&bind_layouts,
+			))
+			.unwrap_or_else(|error| {
+				warn!("could not use shader from {}, using embedded shader: {:#}", SHADER_PATH, error);
+				Self::build_pipelines(
+					&device,
+					config.format,
+					EMBEDDED_SHADER,
+					&bind_layouts,
+				).expect("embedded shader is invalid")
+			});
+		#[cfg(not(debug_assertions))]
+		let (render_pipeline, fade_pipeline, held_block_pipeline, reflection_pipeline) = Self::build_pipelines(
+			&device,
+			config.format,
+			EMBEDDED_SHADER,
+			&bind_layouts,
+		).expect("embedded shader is invalid");
+		let shadow_pipeline = Self::build_shadow_pipeline(&device, config.format, &camera_bind_group_layout);
+		let particle_bind_layout = ParticleConstants::create_bind_layout(&device);
+		let particle_constants_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("particle constants buffer"),
+			size: mem::size_of::<ParticleConstants>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+		let particle_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("particle bind group"),
+			layout: &particle_bind_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: particle_constants_buffer.as_entire_binding(),
+				},
+			],
+		});
+		let particle_pipeline = Self::build_particle_pipeline(&device, config.format, &camera_bind_group_layout, &particle_bind_layout);
+		let held_block = HeldBlockRenderer::new(&device, &camera_bind_group_layout);
+
+		Self {
+			surface,
+			device,
+			queue,
+			config,
+			supported_present_modes,
+			render_pipeline,
+			fade_pipeline,
+			held_block_pipeline,
+			held_block,
+			reflection_pipeline,
+			reflection: None,
+			reflection_plane: None,
+			water_texture_index: -1,
+			reflection_sampler,
+			_placeholder_reflection_texture: placeholder_reflection_texture,
+			placeholder_reflection_view,
+			shadow_pipeline,
+			particle_pipeline,
+			particle_constants_buffer,
+			particle_bind_group,
+			shadow_map_pipeline,
+			shadow_maps,
+			shadow_bind_layout,
+			shadows_enabled: true,
+			shadow_bias: ShadowBias::default(),
+			sky_rotation: Quat::IDENTITY,
+			sky,
+			terrain_buffers,
+			multi_draw_indirect,
+			gpu_timer,
+			texture_bind_layout: texture_bind_group_layout,
+			samplers: SamplerCache::default(),
+			camera_bind_layout: camera_bind_group_layout,
+			scene_uniform,
+			ao_strength: 1.0,
+			face_shading: true,
+			fog: Fog::NONE,
+			scene_buffer,
+			draw_constants,
+			scene_bind_layout: scene_bind_group_layout,
+			scene_bind_group,
+			reflection_scene_bind_group,
+			depth_texture,
+			camera,
+			camera_modified: false,
+			camera_buffer,
+			camera_bind_group,
+			surface_texture: None,
+			surface_texture_view: None,
+			size,
+		}
+	}
+
+	fn create_texture_bind_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+		device.create_bind_group_layout(
+			&wgpu::BindGroupLayoutDescriptor {
+				label: Some("texture bind group layout"),
+				entries: &[
+					wgpu::BindGroupLayoutEntry {
+						binding: 0,
+						visibility: wgpu::ShaderStages::FRAGMENT,
+						ty: wgpu::BindingType::Texture {
+							multisampled: false,
+							// block textures are layers of a single texture, so any number of them can be bound
+							view_dimension: wgpu::TextureViewDimension::D2Array,
+							sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						},
+						count: None,
+					},
+					wgpu::BindGroupLayoutEntry {
+						binding: 1,
+						visibility: wgpu::ShaderStages::FRAGMENT,
+						ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+						count: None,
+					},
+				],
+			}
+		)
+	}
+
+	#[cfg(debug_assertions)]
+	fn load_shader_source() -> Result<String> {
+		// the path is absolute, so it is not relative to the resource folder
+		Ok(String::from_utf8(loader().load_bytes(SHADER_PATH)?)?)
+	}
+
+	fn create_scene_bind_group(
+		device: &wgpu::Device,
+		layout: &wgpu::BindGroupLayout,
+		scene_buffer: &wgpu::Buffer,
+		draw_constants: &DrawConstantsBuffer,
+		reflection_view: &wgpu::TextureView,
+		reflection_sampler: &wgpu::Sampler,
+	) -> wgpu::BindGroup {
+		device.create_bind_group(
+			&wgpu::BindGroupDescriptor {
+				label: Some("scene bind group"),
+				layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: scene_buffer.as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::TextureView(reflection_view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: wgpu::BindingResource::Sampler(reflection_sampler),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: draw_constants.binding(),
+					},
+				],
+			}
+		)
+	}
+
+	// builds the opaque terrain pipeline, the fade in pipeline, the held block pipeline, and the reflection pipeline from the same shader
+	fn build_pipelines(
+		device: &wgpu::Device,
+		surface_format: wgpu::TextureFormat,
+		shader_source: &str,
+		bind_layouts: &[&wgpu::BindGroupLayout],
+	) -> Result<(wgpu::RenderPipeline, wgpu::RenderPipeline, wgpu::RenderPipeline, wgpu::RenderPipeline)> {
+		Ok((
+			Self::build_pipeline(device, surface_format, shader_source, bind_layouts, PipelineKind::Opaque)?,
+			Self::build_pipeline(device, surface_format, shader_source, bind_layouts, PipelineKind::Fading)?,
+			Self::build_pipeline(device, surface_format, shader_source, bind_layouts, PipelineKind::HeldBlock)?,
+			Self::build_pipeline(device, surface_format, shader_source, bind_layouts, PipelineKind::Reflected)?,
+		))
+	}
+
+	// compiles the shader and creates the render pipeline, returning the validation error if either fails
+	fn build_pipeline(
+		device: &wgpu::Device,
+		surface_format: wgpu::TextureFormat,
+		shader_source: &str,
+		// texture, camera, scene, and shadow bind layouts, in bind group order
+		bind_layouts: &[&wgpu::BindGroupLayout],
+		kind: PipelineKind,
+	) -> Result<wgpu::RenderPipeline> {
+		let fading = kind == PipelineKind::Fading;
+
+		// without an error scope wgpu panics on invalid shaders
+		device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("shader.wgsl"),
+			source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(shader_source)),
+		});
+
 		let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
 			label: Some("render pipeline layout"),
-			bind_group_layouts: &[
-				&texture_bind_group_layout,
-				&camera_bind_group_layout,
-			],
+			bind_group_layouts: bind_layouts,
 			push_constant_ranges: &[],
 		});
 
 		let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-			label: Some("render pipeline"),
+			label: Some(match kind {
+				PipelineKind::Opaque => "render pipeline",
+				PipelineKind::Fading => "fade render pipeline",
+				PipelineKind::HeldBlock => "held block render pipeline",
+				PipelineKind::Reflected => "reflection render pipeline",
+			}),
 			layout: Some(&render_pipeline_layout),
 			vertex: wgpu::VertexState {
 				module: &shader,
@@ -177,10 +615,14 @@ impl Renderer {
 			},
 			fragment: Some(wgpu::FragmentState {
 				module: &shader,
-				entry_point: "fs_main",
+				entry_point: match kind {
+					PipelineKind::HeldBlock => "fs_held_block",
+					PipelineKind::Reflected => "fs_reflected",
+					PipelineKind::Opaque | PipelineKind::Fading => "fs_main",
+				},
 				targets: &[Some(wgpu::ColorTargetState {
-					format: config.format,
-					blend: Some(wgpu::BlendState::REPLACE),
+					format: surface_format,
+					blend: Some(if fading { wgpu::BlendState::ALPHA_BLENDING } else { wgpu::BlendState::REPLACE }),
 					write_mask: wgpu::ColorWrites::ALL,
 				})],
 			}),
@@ -198,7 +640,7 @@ impl Renderer {
 			},
 			depth_stencil: Some(wgpu::DepthStencilState {
 				format: DepthTexture::DEPTH_FORMAT,
-				depth_write_enabled: true,
+				depth_write_enabled: !fading,
 				depth_compare: wgpu::CompareFunction::Less,
 				stencil: wgpu::StencilState::default(),
 				bias: wgpu::DepthBiasState::default(),
@@ -211,29 +653,183 @@ impl Renderer {
 			multiview: None,
 		});
 
-		Self {
-			surface,
-			device,
-			queue,
-			config,
-			render_pipeline,
-			texture_bind_layout: texture_bind_group_layout,
-			depth_texture,
-			camera,
-			camera_modified: false,
-			camera_buffer,
-			camera_bind_group,
-			surface_texture: None,
-			surface_texture_view: None,
-			size,
+		match pollster::block_on(device.pop_error_scope()) {
+			Some(error) => Err(anyhow::anyhow!("{}", error)),
+			None => Ok(render_pipeline),
 		}
 	}
 
+	// shadows are blended over terrain without writing depth, and are biased towards the camera so they don't z fight with the ground
+	fn build_shadow_pipeline(
+		device: &wgpu::Device,
+		surface_format: wgpu::TextureFormat,
+		camera_bind_layout: &wgpu::BindGroupLayout,
+	) -> wgpu::RenderPipeline {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("shadow.wgsl"),
+			source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADOW_SHADER)),
+		});
+
+		let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("shadow pipeline layout"),
+			bind_group_layouts: &[camera_bind_layout],
+			push_constant_ranges: &[],
+		});
+
+		device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("shadow pipeline"),
+			layout: Some(&layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[
+					ShadowVertex::desc(),
+				],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_main",
+				targets: &[Some(wgpu::ColorTargetState {
+					format: surface_format,
+					blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				cull_mode: None,
+				polygon_mode: wgpu::PolygonMode::Fill,
+				unclipped_depth: false,
+				conservative: false,
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: DepthTexture::DEPTH_FORMAT,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::LessEqual,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState {
+					constant: -2,
+					slope_scale: -1.0,
+					clamp: 0.0,
+				},
+			}),
+			multisample: wgpu::MultisampleState {
+				count: 1,
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+		})
+	}
+
+	// particles are blended over everything else without writing depth, and are seen from both sides
+	fn build_particle_pipeline(
+		device: &wgpu::Device,
+		surface_format: wgpu::TextureFormat,
+		camera_bind_layout: &wgpu::BindGroupLayout,
+		particle_bind_layout: &wgpu::BindGroupLayout,
+	) -> wgpu::RenderPipeline {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("particle.wgsl"),
+			source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(PARTICLE_SHADER)),
+		});
+
+		let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("particle pipeline layout"),
+			bind_group_layouts: &[camera_bind_layout, particle_bind_layout],
+			push_constant_ranges: &[],
+		});
+
+		device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("particle pipeline"),
+			layout: Some(&layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[
+					Particle::desc(),
+				],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_main",
+				targets: &[Some(wgpu::ColorTargetState {
+					format: surface_format,
+					blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				cull_mode: None,
+				polygon_mode: wgpu::PolygonMode::Fill,
+				unclipped_depth: false,
+				conservative: false,
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: DepthTexture::DEPTH_FORMAT,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::LessEqual,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample: wgpu::MultisampleState {
+				count: 1,
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+		})
+	}
+
+	// rereads the shader from disk and swaps in a new render pipeline, keeping the old one if the new shader is invalid
+	// this must not be called while a frame is being recorded, frames which were already submitted
+	// keep using the old pipeline, since wgpu keeps it alive until they finish
+	#[cfg(debug_assertions)]
+	pub fn reload_shader(&mut self) -> Result<()> {
+		assert!(self.surface_texture.is_none(), "cannot reload shader during a render pass");
+
+		let source = Self::load_shader_source()?;
+		(self.render_pipeline, self.fade_pipeline, self.held_block_pipeline, self.reflection_pipeline) = Self::build_pipelines(
+			&self.device,
+			self.config.format,
+			&source,
+			&[&self.texture_bind_layout, &self.camera_bind_layout, &self.scene_bind_layout, &self.shadow_bind_layout],
+		)?;
+
+		info!("reloaded shader from {}", SHADER_PATH);
+		Ok(())
+	}
+
+	// indices are relative to the first vertex of the mesh
+	pub fn alloc_terrain_mesh(&mut self, vertices: &[BlockVertex], indices: &[u32], bounding_box: Aabb) -> TerrainMesh {
+		self.terrain_buffers.alloc_mesh(&self.device, &self.queue, vertices, indices, bounding_box)
+	}
+
+	pub fn free_terrain_mesh(&mut self, mesh: TerrainMesh) {
+		self.terrain_buffers.free_mesh(mesh);
+	}
+
+	// stats of the terrain vertex pool and index pool
+	pub fn terrain_pool_stats(&self) -> (PoolStats, PoolStats) {
+		self.terrain_buffers.stats()
+	}
+
+	// gpu memory allocated for terrain, in bytes
+	pub fn terrain_memory_size(&self) -> u64 {
+		self.terrain_buffers.size_bytes()
+	}
+
 	pub fn context(&self) -> RenderContext {
 		RenderContext {
 			device: &self.device,
 			queue: &self.queue,
 			texture_bind_layout: &self.texture_bind_layout,
+			samplers: &self.samplers,
 		}
 	}
 
@@ -259,6 +855,132 @@ impl Renderer {
 			self.config.height = new_size.height;
 			self.surface.configure(&self.device, &self.config);
 			self.depth_texture = DepthTexture::new(&self.device, &self.config, "depth texture");
+			if self.reflection.is_some() {
+				self.set_water_reflections(false);
+				self.set_water_reflections(true);
+			}
+		}
+	}
+
+	// without vsync frames are shown as soon as they are rendered, if the surface supports it
+	pub fn set_vsync(&mut self, vsync: bool) {
+		let present_mode = if vsync {
+			wgpu::PresentMode::Fifo
+		} else {
+			[wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox].into_iter()
+				.find(|mode| self.supported_present_modes.contains(mode))
+				.unwrap_or(wgpu::PresentMode::Fifo)
+		};
+
+		if present_mode != self.config.present_mode {
+			self.config.present_mode = present_mode;
+			self.surface.configure(&self.device, &self.config);
+		}
+	}
+
+	// the reflection textures only exist while reflections are on
+	pub fn set_water_reflections(&mut self, enabled: bool) {
+		if enabled == self.reflection.is_some() {
+			return;
+		}
+
+		self.reflection = enabled.then(|| Reflection::new(&self.device, &self.config, &self.camera_bind_layout));
+		self.recreate_scene_bind_groups();
+		self.update_scene_uniform();
+	}
+
+	// called when the reflection texture or the draw constants buffer is replaced
+	fn recreate_scene_bind_groups(&mut self) {
+		let reflection_view = self.reflection.as_ref().map_or(&self.placeholder_reflection_view, Reflection::color_view);
+		self.scene_bind_group = Self::create_scene_bind_group(
+			&self.device,
+			&self.scene_bind_layout,
+			&self.scene_buffer,
+			&self.draw_constants,
+			reflection_view,
+			&self.reflection_sampler,
+		);
+		self.reflection_scene_bind_group = Self::create_scene_bind_group(
+			&self.device,
+			&self.scene_bind_layout,
+			&self.scene_buffer,
+			&self.draw_constants,
+			&self.placeholder_reflection_view,
+			&self.reflection_sampler,
+		);
+	}
+
+	// height of the surface of the water to reflect, none if there is no water
+	pub fn set_reflection_plane(&mut self, height: Option<f32>) {
+		self.reflection_plane = height;
+	}
+
+	pub fn set_water_texture_index(&mut self, texture_index: i32) {
+		self.water_texture_index = texture_index;
+		self.update_scene_uniform();
+	}
+
+	// the camera mirrored about the water surface and the surface's height, if a reflection should be drawn
+	// the water can only be seen from above
+	fn reflection_camera(&self) -> Option<(Camera, f32)> {
+		self.reflection.as_ref()?;
+		let height = self.reflection_plane.filter(|height| self.camera.position.y > *height)?;
+		Some((self.camera.mirrored(height), height))
+	}
+
+	// 0 turns off ambient occlusion and 1 is full strength
+	pub fn set_ao_strength(&mut self, ao_strength: f32) {
+		self.ao_strength = ao_strength;
+		self.update_scene_uniform();
+	}
+
+	// darkens faces by the direction they face, so terrain has depth even where nothing else lights it
+	pub fn set_face_shading(&mut self, face_shading: bool) {
+		self.face_shading = face_shading;
+		self.update_scene_uniform();
+	}
+
+	// the background is cleared to the fog color as well
+	pub fn set_fog(&mut self, fog: Fog) {
+		self.fog = fog;
+		self.update_scene_uniform();
+	}
+
+	// the shadow maps are recreated if the resolution changes
+	pub fn set_shadows(&mut self, enabled: bool, resolution: u32) {
+		self.shadows_enabled = enabled;
+		if resolution != self.shadow_maps.resolution() {
+			self.shadow_maps = ShadowMaps::new(&self.device, resolution, &self.camera_bind_layout, &self.shadow_bind_layout);
+		}
+	}
+
+	pub fn set_shadow_bias(&mut self, shadow_bias: ShadowBias) {
+		self.shadow_bias = shadow_bias;
+	}
+
+	// the sun starts at DEFAULT_SUN_DIRECTION, and the stars and the moon turn with it
+	pub fn set_sky_rotation(&mut self, sky_rotation: Quat) {
+		self.sky_rotation = sky_rotation;
+	}
+
+	// pointing towards the sun
+	pub fn sun_direction(&self) -> Vec3 {
+		self.sky_rotation * DEFAULT_SUN_DIRECTION.normalize()
+	}
+
+	fn update_scene_uniform(&mut self) {
+		let reflection_camera = self.reflection_camera();
+		let scene_uniform = SceneUniform::new(
+			self.ao_strength,
+			self.face_shading,
+			self.fog,
+			self.water_texture_index,
+			self.camera.position,
+			reflection_camera.as_ref().map(|(camera, height)| (camera, *height)),
+		);
+		if scene_uniform != self.scene_uniform {
+			self.scene_uniform = scene_uniform;
+			self.queue.write_buffer(&self.scene_buffer, 0, bytemuck::cast_slice(&[scene_uniform]));
 		}
 	}
 
@@ -303,24 +1025,215 @@ impl Renderer {
 		self.surface_texture_view.as_ref()
 	}
 
-	pub fn render(&mut self, models: &[(&Mesh, &Material)]) {
+	pub fn camera(&self) -> &Camera {
+		&self.camera
+	}
+
+	// the mesh of the block in the player's hand, none if nothing is held
+	pub fn set_held_block(&mut self, mesh: Option<Mesh>) {
+		self.held_block.set_mesh(mesh);
+	}
+
+	// moves the held block from its mesh's coordinates into world space, in front of the camera
+	pub fn set_held_block_transform(&mut self, transform: Mat4) {
+		self.held_block.set_transform(transform);
+	}
+
+	// draws the terrain depth from the sun into each cascade, returning the number of draw calls
+	// each cascade culls the terrain against its own bounds, since terrain outside the view can still cast shadows into it
+	fn render_shadow_maps(&self, encoder: &mut wgpu::CommandEncoder, render_queue: &RenderQueue, cascades: &[Mat4; CASCADE_COUNT]) -> usize {
+		let mut draw_calls = 0;
+		for (i, cascade) in cascades.iter().enumerate() {
+			let draws = render_queue.sorted_draws(|aabb| light_box_visible(*cascade, aabb));
+
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("shadow map render pass"),
+				color_attachments: &[],
+				depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+					view: self.shadow_maps.layer_view(i),
+					depth_ops: Some(wgpu::Operations {
+						load: wgpu::LoadOp::Clear(1.0),
+						store: true,
+					}),
+					stencil_ops: None,
+				}),
+			});
+
+			render_pass.set_pipeline(&self.shadow_map_pipeline);
+			render_pass.set_bind_group(0, self.shadow_maps.cascade_bind_group(i), &[]);
+
+			let opaque = draws.terrain_opaque.iter().map(|(page, args)| (*page, args));
+			let translucent = draws.terrain_translucent.iter().map(|(_, _, page, args)| (*page, args));
+			let mut bound_page = None;
+			for (page, draw) in opaque.chain(translucent) {
+				if bound_page != Some(page) {
+					render_pass.set_vertex_buffer(0, self.terrain_buffers.vertex_buffer(page).slice(..));
+					render_pass.set_index_buffer(self.terrain_buffers.index_buffer(page).slice(..), wgpu::IndexFormat::Uint32);
+					bound_page = Some(page);
+				}
+				render_pass.draw_indexed(
+					draw.first_index..draw.first_index + draw.index_count,
+					draw.base_vertex,
+					0..1,
+				);
+				draw_calls += 1;
+			}
+		}
+		draw_calls
+	}
+
+	// draws the opaque terrain above the water from the mirrored camera into the reflection, returning the number of draw calls
+	// the reflection is small and blurred by ripples, so everything in it is drawn with reduced detail
+	fn render_reflection(
+		&self,
+		encoder: &mut wgpu::CommandEncoder,
+		render_queue: &RenderQueue,
+		terrain_material: &Material,
+		reflection: &Reflection,
+		mirrored_camera: &Camera,
+	) -> usize {
+		let draws = render_queue.sorted_draws(|aabb| mirrored_camera.bounding_box_visible(aabb));
+		let camera_bind_group = reflection.prepare(&self.queue, mirrored_camera);
+
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("reflection render pass"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: reflection.color_view(),
+				resolve_target: None,
+				ops: wgpu::Operations {
+					// the sky isn't drawn into the reflection, the water reflects the fog color instead
+					load: wgpu::LoadOp::Clear(wgpu::Color {
+						r: self.fog.color.x as f64,
+						g: self.fog.color.y as f64,
+						b: self.fog.color.z as f64,
+						a: 1.0,
+					}),
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+				view: reflection.depth_view(),
+				depth_ops: Some(wgpu::Operations {
+					load: wgpu::LoadOp::Clear(1.0),
+					store: true,
+				}),
+				stencil_ops: None,
+			}),
+		});
+
+		render_pass.set_pipeline(&self.reflection_pipeline);
+		render_pass.set_bind_group(2, &self.reflection_scene_bind_group, &[self.draw_constants.offset(REFLECTION_CONSTANTS_INDEX)]);
+		render_pass.set_bind_group(3, self.shadow_maps.bind_group(), &[]);
+
+		let mut bound_page = None;
+		for (page, draw) in draws.terrain_opaque.iter() {
+			if bound_page != Some(*page) {
+				render_pass.bind_terrain_page(&self.terrain_buffers, *page, terrain_material, camera_bind_group);
+				bound_page = Some(*page);
+			}
+			render_pass.draw_indexed(
+				draw.first_index..draw.first_index + draw.index_count,
+				draw.base_vertex,
+				0..1,
+			);
+		}
+		draws.terrain_opaque.len()
+	}
+
+	// draws the queued items in pass order: the sky, opaque terrain, then translucent terrain, blob shadows and particles blended over it
+	// the held block is drawn over all of them
+	pub fn render(&mut self, render_queue: &RenderQueue, terrain_material: &Material) -> RenderStats {
+		// the camera moves every frame, and the water only shows the reflection while it is drawn
+		self.update_scene_uniform();
+
+		let SortedDraws {
+			terrain_opaque: draws,
+			terrain_page_runs: page_runs,
+			terrain_translucent: fading_draws,
+			blob_shadows,
+			particles,
+			visible_groups,
+			culled_groups,
+		} = render_queue.sorted_draws(|aabb| self.camera.bounding_box_visible(aabb));
+
+		let use_indirect = self.multi_draw_indirect && !draws.is_empty();
+
+		// the reflection's constants come first, then those of each opaque page run, then those of each fading draw
+		let opaque_constants = page_runs.iter().map(|(_, detail, _)| DrawConstants { alpha: 1.0, detail: detail.shader_value() });
+		let fading_constants = fading_draws.iter().map(|(alpha, detail, _, _)| DrawConstants { alpha: *alpha, detail: detail.shader_value() });
+		let draw_constants = std::iter::once(DrawConstants { alpha: 1.0, detail: DetailLevel::Reduced.shader_value() })
+			.chain(opaque_constants)
+			.chain(fading_constants)
+			.collect::<Vec<_>>();
+		if self.draw_constants.write(&self.device, &self.queue, &draw_constants) {
+			self.recreate_scene_bind_groups();
+		}
+		let first_fading_constants = REFLECTION_CONSTANTS_INDEX + 1 + page_runs.len();
+		if use_indirect {
+			let draw_args = draws.iter().map(|(_, args)| *args).collect::<Vec<_>>();
+			self.terrain_buffers.write_indirect(&self.device, &self.queue, &draw_args);
+		}
+
+		// there are only a few shadows, so their vertices are uploaded again each frame
+		let shadow_vertices = blob_shadows.iter().flat_map(|shadow| shadow.vertices()).collect::<Vec<_>>();
+		let shadow_buffer = (!shadow_vertices.is_empty()).then(|| self.device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("shadow vertex buffer"),
+				contents: bytemuck::cast_slice(&shadow_vertices),
+				usage: wgpu::BufferUsages::VERTEX,
+			}
+		));
+
+		// particles are simulated on the cpu, so they are uploaded again each frame too
+		if !particles.is_empty() {
+			self.queue.write_buffer(&self.particle_constants_buffer, 0, bytemuck::bytes_of(&ParticleConstants::new(self.camera.forward())));
+		}
+		let particle_buffer = (!particles.is_empty()).then(|| self.device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("particle instance buffer"),
+				contents: bytemuck::cast_slice(&particles),
+				usage: wgpu::BufferUsages::VERTEX,
+			}
+		));
+
 		let view = self.output_texture_view().expect("render pass has not been started");
 
 		let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
 			label: Some("render encoder"),
 		});
 
+		let sun_direction = self.sun_direction();
+		self.sky.update(&self.queue, &self.camera, sun_direction, self.sky_rotation);
+		let cascades = self.shadow_maps.update(&self.queue, &self.camera, sun_direction, self.shadow_bias, self.shadows_enabled);
+		let shadow_draw_calls = if self.shadows_enabled {
+			self.render_shadow_maps(&mut encoder, render_queue, &cascades)
+		} else {
+			0
+		};
+
+		let reflection_draw_calls = match (&self.reflection, self.reflection_camera()) {
+			(Some(reflection), Some((mirrored_camera, _))) => {
+				self.render_reflection(&mut encoder, render_queue, terrain_material, reflection, &mirrored_camera)
+			},
+			_ => 0,
+		};
+
+		if let Some(gpu_timer) = &self.gpu_timer {
+			gpu_timer.start(&mut encoder);
+		}
+
 		{
 			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
 				label: Some("render pass"),
 				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-					view: &view,
+					view,
 					resolve_target: None,
 					ops: wgpu::Operations {
+						// terrain fades into the fog color, so the sky must be the same color
 						load: wgpu::LoadOp::Clear(wgpu::Color {
-							r: 0.1,
-							g: 0.2,
-							b: 0.3,
+							r: self.fog.color.x as f64,
+							g: self.fog.color.y as f64,
+							b: self.fog.color.z as f64,
 							a: 1.0,
 						}),
 						store: true,
@@ -336,18 +1249,110 @@ impl Renderer {
 				}),
 			});
 
+			self.sky.draw(&mut render_pass);
+
 			render_pass.set_pipeline(&self.render_pipeline);
+			// both terrain pipelines use the same layout, so this stays bound for the fading pipeline too
+			render_pass.set_bind_group(3, self.shadow_maps.bind_group(), &[]);
+
+			for (i, (page, _, run)) in page_runs.iter().enumerate() {
+				render_pass.bind_terrain_page(&self.terrain_buffers, *page, terrain_material, &self.camera_bind_group);
+				render_pass.set_bind_group(2, &self.scene_bind_group, &[self.draw_constants.offset(REFLECTION_CONSTANTS_INDEX + 1 + i)]);
 
-			for (mesh, material) in models.iter() {
-				if let Some(aabb) = mesh.bounding_box {
-					if !self.camera.bounding_box_visible(aabb) {
-						continue;
+				if use_indirect {
+					render_pass.multi_draw_indexed_indirect(
+						self.terrain_buffers.indirect_buffer(),
+						(run.start * mem::size_of::<DrawIndexedIndirectArgs>()) as u64,
+						run.len() as u32,
+					);
+				} else {
+					for (_, draw) in draws[run.clone()].iter() {
+						render_pass.draw_indexed(
+							draw.first_index..draw.first_index + draw.index_count,
+							draw.base_vertex,
+							0..1,
+						);
 					}
 				}
-				render_pass.draw_mesh(mesh, material, &self.camera_bind_group);
 			}
+
+			// drawn after all opaque terrain so it can be blended over it
+			render_pass.set_pipeline(&self.fade_pipeline);
+			for (i, (_, _, page, draw)) in fading_draws.iter().enumerate() {
+				render_pass.bind_terrain_page(&self.terrain_buffers, *page, terrain_material, &self.camera_bind_group);
+				render_pass.set_bind_group(2, &self.scene_bind_group, &[self.draw_constants.offset(first_fading_constants + i)]);
+				render_pass.draw_indexed(
+					draw.first_index..draw.first_index + draw.index_count,
+					draw.base_vertex,
+					0..1,
+				);
+			}
+
+			// drawn last since the shadow pipeline doesn't share the terrain pipeline layout
+			if let Some(shadow_buffer) = &shadow_buffer {
+				render_pass.set_pipeline(&self.shadow_pipeline);
+				render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+				render_pass.set_vertex_buffer(0, shadow_buffer.slice(..));
+				render_pass.draw(0..shadow_vertices.len() as u32, 0..1);
+			}
+
+			if let Some(particle_buffer) = &particle_buffer {
+				render_pass.set_pipeline(&self.particle_pipeline);
+				render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+				render_pass.set_bind_group(1, &self.particle_bind_group, &[]);
+				render_pass.set_vertex_buffer(0, particle_buffer.slice(..));
+				render_pass.draw(0..6, 0..particles.len() as u32);
+			}
+		}
+
+		// the depth is cleared so the held block is never hidden by a wall it is pushed into
+		let held_block = self.held_block.prepare(&self.queue, &self.camera);
+		if let Some((mesh, camera_bind_group)) = held_block {
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("held block render pass"),
+				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+					view,
+					resolve_target: None,
+					ops: wgpu::Operations {
+						load: wgpu::LoadOp::Load,
+						store: true,
+					}
+				})],
+				depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+					view: &self.depth_texture.view,
+					depth_ops: Some(wgpu::Operations {
+						load: wgpu::LoadOp::Clear(1.0),
+						store: true,
+					}),
+					stencil_ops: None,
+				}),
+			});
+
+			render_pass.set_pipeline(&self.held_block_pipeline);
+			// the held block shader doesn't read the draw constants, but the layout still needs an offset
+			render_pass.set_bind_group(2, &self.scene_bind_group, &[self.draw_constants.offset(REFLECTION_CONSTANTS_INDEX)]);
+			render_pass.set_bind_group(3, self.shadow_maps.bind_group(), &[]);
+			render_pass.draw_mesh(mesh, terrain_material, camera_bind_group);
+		}
+
+		if let Some(gpu_timer) = &mut self.gpu_timer {
+			gpu_timer.end(&mut encoder);
 		}
 
 		self.queue.submit(std::iter::once(encoder.finish()));
+
+		let gpu_time = self.gpu_timer.as_mut().and_then(|gpu_timer| {
+			gpu_timer.submitted();
+			gpu_timer.time(&self.device)
+		});
+
+		let opaque_draw_calls = if use_indirect { page_runs.len() } else { draws.len() };
+		RenderStats {
+			draw_calls: opaque_draw_calls + fading_draws.len() + shadow_buffer.is_some() as usize + particle_buffer.is_some() as usize + held_block.is_some() as usize + shadow_draw_calls + reflection_draw_calls,
+			visible_meshes: draws.len() + fading_draws.len(),
+			visible_groups,
+			culled_groups,
+			gpu_time,
+		}
 	}
 }
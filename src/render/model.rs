@@ -1,12 +1,12 @@
-use std::{mem, path::Path};
+use std::{mem, path::Path, sync::Arc};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use image::DynamicImage;
 use wgpu::util::DeviceExt;
 //use nalgebra::{Vector3, Scale3, Matrix4, UnitQuaternion};
 use glam::{Vec3, Mat4, Quat};
 
-use super::{RenderContext, texture::Texture, Aabb};
+use super::{RenderContext, texture::{Texture, TextureFilter}, terrain_buffer::{TerrainBuffers, TerrainPage}, Aabb};
 use crate::assets::loader;
 
 pub trait Vertex: bytemuck::Pod + bytemuck::Zeroable {
@@ -81,6 +81,17 @@ impl Mesh {
 		}
 	}
 
+	pub fn from_data(mesh_data: &MeshData, context: RenderContext) -> Self {
+		Self::new(
+			&mesh_data.name,
+			&mesh_data.vertices,
+			&mesh_data.indices,
+			mesh_data.material_index,
+			mesh_data.bounding_box,
+			context,
+		)
+	}
+
 	pub fn triangle_count(&self) -> u32 {
 		self.num_elements / 3
 	}
@@ -89,8 +100,9 @@ impl Mesh {
 #[derive(Debug)]
 pub struct Material {
 	name: String,
-	diffuse_textures: Vec<Texture>,
-	diffuse_sampler: wgpu::Sampler,
+	diffuse_texture: Texture,
+	layer_count: u32,
+	diffuse_sampler: Arc<wgpu::Sampler>,
 	bind_group: wgpu::BindGroup,
 }
 
@@ -99,100 +111,149 @@ impl Material {
 	pub fn load_from_file<T: AsRef<Path>>(
 		file_name: T,
 		name: String,
+		sampler: Arc<wgpu::Sampler>,
 		context: RenderContext,
 	) -> Result<Self> {
-		let diffuse_texture = Texture::from_file(file_name, &format!("{} diffuse texture", name), context)?;
-
-		let diffuse_sampler = context.device.create_sampler(
-			&wgpu::SamplerDescriptor {
-				address_mode_u: wgpu::AddressMode::ClampToEdge,
-				address_mode_v: wgpu::AddressMode::ClampToEdge,
-				address_mode_w: wgpu::AddressMode::ClampToEdge,
-				// TODO: make adjustable
-				mag_filter: wgpu::FilterMode::Nearest,
-				min_filter: wgpu::FilterMode::Nearest,
-				mipmap_filter: wgpu::FilterMode::Nearest,
-				..Default::default()
-			}
-		);
+		let image = loader().load_image(file_name)?;
+		Self::array_from_images(&[image], name, sampler, context)
+	}
 
-		let bind_group = context.device.create_bind_group(
-			&wgpu::BindGroupDescriptor {
-				label: Some(&format!("{} bind group", name)),
-				layout: context.texture_bind_layout,
-				entries: &[
-					wgpu::BindGroupEntry {
-						binding: 0,
-						resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-					},
-					wgpu::BindGroupEntry {
-						binding: 1,
-						resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
-					},
-				],
-			}
-		);
+	// each image is a layer of the texture array, which is indexed by the texture index of the vertex
+	pub fn array_from_images(
+		images: &[DynamicImage],
+		name: String,
+		sampler: Arc<wgpu::Sampler>,
+		context: RenderContext,
+	) -> Result<Self> {
+		let diffuse_texture = Texture::array_from_images(images, &format!("{} diffuse texture", name), context)?;
+		let bind_group = Self::create_bind_group(&name, &diffuse_texture, &sampler, context);
 
 		Ok(Self {
 			name,
-			diffuse_textures: vec![diffuse_texture],
-			diffuse_sampler,
+			diffuse_texture,
+			layer_count: images.len() as u32,
+			diffuse_sampler: sampler,
 			bind_group,
 		})
 	}
 
-	pub fn array_from_images(
-		images: &[DynamicImage],
-		name: String,
+	fn create_bind_group(
+		name: &str,
+		diffuse_texture: &Texture,
+		diffuse_sampler: &wgpu::Sampler,
 		context: RenderContext,
-	) -> Self {
-		let mut diffuse_textures = Vec::with_capacity(images.len());
-		for image in images.iter() {
-			diffuse_textures.push(Texture::from_image(image, &format!("{} diffuse texture", name), context));
-		}
-
-		let mut texture_views = Vec::with_capacity(images.len());
-		for texture in diffuse_textures.iter() {
-			texture_views.push(&texture.view);
-		}
-
-		let diffuse_sampler = context.device.create_sampler(
-			&wgpu::SamplerDescriptor {
-				address_mode_u: wgpu::AddressMode::ClampToEdge,
-				address_mode_v: wgpu::AddressMode::ClampToEdge,
-				address_mode_w: wgpu::AddressMode::ClampToEdge,
-				// TODO: make adjustable
-				mag_filter: wgpu::FilterMode::Nearest,
-				min_filter: wgpu::FilterMode::Nearest,
-				mipmap_filter: wgpu::FilterMode::Nearest,
-				..Default::default()
-			}
-		);
-
-		let bind_group = context.device.create_bind_group(
+	) -> wgpu::BindGroup {
+		context.device.create_bind_group(
 			&wgpu::BindGroupDescriptor {
 				label: Some(&format!("{} bind group", name)),
 				layout: context.texture_bind_layout,
 				entries: &[
 					wgpu::BindGroupEntry {
 						binding: 0,
-						resource: wgpu::BindingResource::TextureViewArray(&texture_views),
+						resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
 					},
 					wgpu::BindGroupEntry {
 						binding: 1,
-						resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
+						resource: wgpu::BindingResource::Sampler(diffuse_sampler),
 					},
 				],
 			}
-		);
+		)
+	}
 
-		Self {
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn layer_count(&self) -> u32 {
+		self.layer_count
+	}
+
+	// replaces the texture array with new images, which may have a different size or number of layers
+	// meshes using this material don't need to be rebuilt, as long as their texture indices are still valid
+	pub fn replace_images(&mut self, images: &[DynamicImage], context: RenderContext) -> Result<()> {
+		self.diffuse_texture = Texture::array_from_images(images, &format!("{} diffuse texture", self.name), context)?;
+		self.layer_count = images.len() as u32;
+		self.rebuild_bind_group(context);
+		Ok(())
+	}
+
+	pub fn set_sampler(&mut self, sampler: Arc<wgpu::Sampler>, context: RenderContext) {
+		self.diffuse_sampler = sampler;
+		self.rebuild_bind_group(context);
+	}
+
+	// must be called after the texture or sampler changes, for the change to be used when drawing
+	pub fn rebuild_bind_group(&mut self, context: RenderContext) {
+		self.bind_group = Self::create_bind_group(&self.name, &self.diffuse_texture, &self.diffuse_sampler, context);
+	}
+}
+
+// a mesh which has been loaded but not yet uploaded to the gpu
+#[derive(Debug)]
+pub struct MeshData {
+	pub name: String,
+	pub vertices: Vec<ModelVertex>,
+	pub indices: Vec<u32>,
+	pub material_index: usize,
+	// none if the mesh has no vertices
+	pub bounding_box: Option<Aabb>,
+}
+
+impl MeshData {
+	// computes the bounding box from the vertices
+	pub fn new(name: String, vertices: Vec<ModelVertex>, indices: Vec<u32>, material_index: usize) -> Self {
+		let bounding_box = vertices.iter()
+			.map(|vertex| Vec3::from(vertex.position))
+			.fold(None, |bounds: Option<(Vec3, Vec3)>, position| match bounds {
+				Some((min, max)) => Some((min.min(position), max.max(position))),
+				None => Some((position, position)),
+			})
+			.map(|(min, max)| Aabb::new(min, max - min));
+
+		MeshData {
 			name,
-			diffuse_textures,
-			diffuse_sampler,
-			bind_group,
+			vertices,
+			indices,
+			material_index,
+			bounding_box,
 		}
 	}
+
+	// the obj must be loaded with a single index, so every attribute has the same index as its position
+	// missing texture coordinates and normals are left as 0
+	pub fn from_obj(obj_model: tobj::Model) -> Self {
+		let mesh = obj_model.mesh;
+
+		let vertices = (0..mesh.positions.len() / 3)
+			.map(|i| ModelVertex {
+				position: [
+					mesh.positions[i * 3],
+					mesh.positions[i * 3 + 1],
+					mesh.positions[i * 3 + 2],
+				],
+				tex_coords: [
+					mesh.texcoords.get(i * 2).copied().unwrap_or(0.0),
+					mesh.texcoords.get(i * 2 + 1).copied().unwrap_or(0.0),
+				],
+				normal: [
+					mesh.normals.get(i * 3).copied().unwrap_or(0.0),
+					mesh.normals.get(i * 3 + 1).copied().unwrap_or(0.0),
+					mesh.normals.get(i * 3 + 2).copied().unwrap_or(0.0),
+				],
+			})
+			.collect::<Vec<_>>();
+
+		MeshData::new(obj_model.name, vertices, mesh.indices, mesh.material_id.unwrap_or(0))
+	}
+}
+
+// a model which has been loaded but not yet uploaded to the gpu
+#[derive(Debug)]
+pub struct ModelData {
+	pub meshes: Vec<MeshData>,
+	// the name and diffuse texture of each material, indexed by the material index of the meshes
+	pub materials: Vec<(String, DynamicImage)>,
 }
 
 #[derive(Debug)]
@@ -202,7 +263,13 @@ pub struct Model {
 }
 
 impl Model {
-	/*pub fn load_from_file<T: AsRef<Path>>(
+	// the file is only loaded the first time, later loads of it share the same model
+	pub fn load_cached<T: AsRef<Path>>(file_name: T, context: RenderContext) -> Result<Arc<Self>> {
+		loader().load_model_cached(file_name, context)
+	}
+
+	// textures of the obj's materials are relative to the resource folder
+	pub fn load_from_file<T: AsRef<Path>>(
 		file_name: T,
 		context: RenderContext,
 	) -> Result<Self> {
@@ -212,44 +279,61 @@ impl Model {
 
 		let mut materials = Vec::with_capacity(obj_materials.len());
 		for mat in obj_materials.into_iter() {
-			materials.push(Material::load_from_file(&mat.diffuse_texture, mat.diffuse_texture.clone(), context)?);
+			materials.push(Material::load_from_file(&mat.diffuse_texture, mat.diffuse_texture.clone(), context.sampler(TextureFilter::Linear), context)?);
 		}
 
 		let mut meshes = Vec::with_capacity(obj_meshes.len());
 		for mesh in obj_meshes.into_iter() {
-			let vertices = (0..mesh.mesh.positions.len() / 3)
-				.map(|i| ModelVertex {
-					position: [
-						mesh.mesh.positions[i * 3],
-						mesh.mesh.positions[i * 3 + 1],
-						mesh.mesh.positions[i * 3 + 2],
-					],
-					tex_coords: [
-						mesh.mesh.texcoords[i * 2],
-						mesh.mesh.texcoords[i * 2 + 1],
-					],
-					normal: [
-						mesh.mesh.normals[i * 3],
-						mesh.mesh.normals[i * 3 + 1],
-						mesh.mesh.normals[i * 3 + 2],
-					],
-				})
-				.collect::<Vec<_>>();
-
-			meshes.push(Mesh::new(
-				&mesh.name,
-				&vertices,
-				&mesh.mesh.indices,
-				mesh.mesh.material_id.unwrap_or(0),
-				context
-			));
+			let mesh_data = MeshData::from_obj(mesh);
+			if mesh_data.material_index >= materials.len() {
+				bail!(
+					"mesh {} in {} uses material {}, but there are only {} materials",
+					mesh_data.name,
+					file_name.display(),
+					mesh_data.material_index,
+					materials.len(),
+				);
+			}
+
+			meshes.push(Mesh::from_data(&mesh_data, context));
 		}
 
 		Ok(Model {
 			meshes,
 			materials,
 		})
-	}*/
+	}
+
+	// meshes are baked into a single model, with the node transforms of the file applied to their vertices
+	// animations and skins are ignored
+	pub fn load_gltf<T: AsRef<Path>>(
+		file_name: T,
+		context: RenderContext,
+	) -> Result<Self> {
+		let (document, buffers, images) = loader().load_gltf(file_name)?;
+		Self::from_data(&ModelData::from_gltf(&document, &buffers, &images)?, context)
+	}
+
+	pub fn from_data(model_data: &ModelData, context: RenderContext) -> Result<Self> {
+		let mut materials = Vec::with_capacity(model_data.materials.len());
+		for (name, image) in model_data.materials.iter() {
+			materials.push(Material::array_from_images(
+				std::slice::from_ref(image),
+				name.clone(),
+				context.sampler(TextureFilter::Linear),
+				context,
+			)?);
+		}
+
+		let meshes = model_data.meshes.iter()
+			.map(|mesh_data| Mesh::from_data(mesh_data, context))
+			.collect();
+
+		Ok(Model {
+			meshes,
+			materials,
+		})
+	}
 
 	pub fn new(
 		name: &str,
@@ -367,6 +451,15 @@ pub trait DrawModel<'a> {
 		model: &'a ModelInstance,
 		camera_bind_group: &'a wgpu::BindGroup,
 	);
+
+	// binds the shared terrain buffers of the page, after which terrain meshes in that page can be drawn with their draw args
+	fn bind_terrain_page(
+		&mut self,
+		terrain_buffers: &'a TerrainBuffers,
+		page: TerrainPage,
+		material: &'a Material,
+		camera_bind_group: &'a wgpu::BindGroup,
+	);
 }
 
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
@@ -403,4 +496,41 @@ where
 			self.draw_indexed(0..mesh.num_elements, 0, 0..model_instance.num_instances());
 		}
 	}
+
+	fn bind_terrain_page(
+		&mut self,
+		terrain_buffers: &'b TerrainBuffers,
+		page: TerrainPage,
+		material: &'b Material,
+		camera_bind_group: &'b wgpu::BindGroup,
+	) {
+		self.set_vertex_buffer(0, terrain_buffers.vertex_buffer(page).slice(..));
+		self.set_index_buffer(terrain_buffers.index_buffer(page).slice(..), wgpu::IndexFormat::Uint32);
+		self.set_bind_group(0, &material.bind_group, &[]);
+		self.set_bind_group(1, camera_bind_group, &[]);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn load_obj_fixture() {
+		let (obj_meshes, obj_materials) = loader().load_obj("models/test-cube.obj").unwrap();
+		assert_eq!(obj_meshes.len(), 1);
+		// texture paths are kept relative to the resource folder, so they can be loaded with the asset loader
+		assert_eq!(obj_materials.len(), 1);
+		assert_eq!(obj_materials[0].diffuse_texture, "textures/stone.png");
+		assert!(loader().load_image(&obj_materials[0].diffuse_texture).is_ok());
+
+		let mesh = MeshData::from_obj(obj_meshes.into_iter().next().unwrap());
+		assert_eq!(mesh.name, "test_cube");
+		// each quad face is split into 2 triangles, and corners are duplicated per face because their normals differ
+		assert_eq!(mesh.vertices.len(), 24);
+		assert_eq!(mesh.indices.len(), 36);
+		assert!(mesh.indices.iter().all(|index| (*index as usize) < mesh.vertices.len()));
+		assert_eq!(mesh.material_index, 0);
+		assert_eq!(mesh.bounding_box, Some(Aabb::new(Vec3::new(-0.5, 0.0, -0.25), Vec3::new(1.0, 2.0, 0.5))));
+	}
 }
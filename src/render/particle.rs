@@ -0,0 +1,104 @@
+use std::mem;
+
+use glam::{Vec3, Vec4};
+
+use super::Aabb;
+use super::model::Vertex;
+
+// a camera facing quad which stays upright, drawn once per instance
+// the quad turns around its vertical axis to face the camera, so it can be stretched into a streak
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Particle {
+	// center of the quad
+	position: [f32; 3],
+	// half of the width and height of the quad
+	half_size: [f32; 2],
+	// rgba, the alpha fades out towards the sides of the quad
+	color: [f32; 4],
+}
+
+impl Particle {
+	const ATTRIBS: [wgpu::VertexAttribute; 3] =
+		wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x4];
+
+	pub fn new(position: Vec3, width: f32, height: f32, color: Vec4) -> Self {
+		Particle {
+			position: position.to_array(),
+			half_size: [width / 2.0, height / 2.0],
+			color: color.to_array(),
+		}
+	}
+
+	// the quad can face any direction around its vertical axis, so the box contains all of them
+	pub fn bounding_box(&self) -> Aabb {
+		let [half_width, half_height] = self.half_size;
+		let half_extent = Vec3::new(half_width, half_height, half_width);
+		Aabb::new(Vec3::from(self.position) - half_extent, 2.0 * half_extent)
+	}
+}
+
+impl Vertex for Particle {
+	fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+		wgpu::VertexBufferLayout {
+			array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+			step_mode: wgpu::VertexStepMode::Instance,
+			attributes: &Self::ATTRIBS,
+		}
+	}
+}
+
+// values which are the same for every particle in a frame
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ParticleConstants {
+	// horizontal direction to the right of the camera, the w component is unused
+	pub right: [f32; 4],
+}
+
+impl ParticleConstants {
+	// a camera looking straight up or down has no horizontal forward direction, so it keeps facing along x
+	pub fn new(camera_forward: Vec3) -> Self {
+		let right = camera_forward.cross(Vec3::Y).try_normalize().unwrap_or(Vec3::X);
+		ParticleConstants {
+			right: right.extend(0.0).to_array(),
+		}
+	}
+
+	// the constants are written to a uniform buffer each frame, bound in group 1 of the particle pipeline
+	pub fn create_bind_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+		device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("particle bind group layout"),
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::VERTEX,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+			],
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn particle_faces_the_camera() {
+		let constants = ParticleConstants::new(Vec3::new(0.0, -0.5, 1.0));
+		let right = Vec4::from(constants.right).truncate();
+		assert!(right.abs_diff_eq(Vec3::new(-1.0, 0.0, 0.0), 0.0001));
+		assert_eq!(ParticleConstants::new(Vec3::NEG_Y).right, [1.0, 0.0, 0.0, 0.0]);
+
+		let particle = Particle::new(Vec3::new(1.0, 2.0, 3.0), 0.1, 1.0, Vec4::ONE);
+		let bounding_box = particle.bounding_box();
+		assert!(bounding_box.position.abs_diff_eq(Vec3::new(0.95, 1.5, 2.95), 0.0001));
+		assert!(bounding_box.size.abs_diff_eq(Vec3::new(0.1, 1.0, 0.1), 0.0001));
+	}
+}
@@ -0,0 +1,59 @@
+// Particles, upright quads turned to face the camera, one instance per particle
+
+struct CameraUniform {
+	view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct ParticleConstants {
+	// horizontal direction to the right of the camera
+	right: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> constants: ParticleConstants;
+
+struct InstanceInput {
+	@location(0) position: vec3<f32>,
+	@location(1) half_size: vec2<f32>,
+	@location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	// -1 to 1 across the quad
+	@location(0) offset: vec2<f32>,
+	@location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, instance: InstanceInput) -> VertexOutput {
+	// 2 triangles, so no vertex buffer is needed for the quad
+	var corners = array<vec2<f32>, 6>(
+		vec2<f32>(-1.0, -1.0),
+		vec2<f32>(1.0, -1.0),
+		vec2<f32>(1.0, 1.0),
+		vec2<f32>(-1.0, -1.0),
+		vec2<f32>(1.0, 1.0),
+		vec2<f32>(-1.0, 1.0),
+	);
+	let offset = corners[vertex_index];
+	let position = instance.position
+		+ constants.right.xyz * offset.x * instance.half_size.x
+		+ vec3<f32>(0.0, offset.y * instance.half_size.y, 0.0);
+
+	var vertex_out: VertexOutput;
+	vertex_out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+	vertex_out.offset = offset;
+	vertex_out.color = instance.color;
+	return vertex_out;
+}
+
+@fragment
+fn fs_main(fragment_in: VertexOutput) -> @location(0) vec4<f32> {
+	// soft sides and ends, so thin streaks don't look like solid lines
+	let fade = (1.0 - abs(fragment_in.offset.x)) * (1.0 - fragment_in.offset.y * fragment_in.offset.y);
+	return vec4<f32>(fragment_in.color.rgb, fragment_in.color.a * fade);
+}
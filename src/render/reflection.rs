@@ -0,0 +1,114 @@
+use std::mem;
+
+use wgpu::util::DeviceExt;
+
+use super::camera::{Camera, CameraUniform};
+use super::texture::DepthTexture;
+
+// reflections are drawn at this fraction of the screen's width and height
+const RESOLUTION_DIVISOR: u32 = 2;
+
+// the terrain drawn from a camera mirrored about a water surface, sampled by water faces to reflect it
+// this only exists while reflections are turned on, so they cost nothing when they are off
+#[derive(Debug)]
+pub struct Reflection {
+	// kept alive for its view
+	_color_texture: wgpu::Texture,
+	color_view: wgpu::TextureView,
+	depth_texture: DepthTexture,
+	camera_buffer: wgpu::Buffer,
+	camera_bind_group: wgpu::BindGroup,
+}
+
+impl Reflection {
+	// config is the surface's configuration, the reflection must be made again when it changes
+	pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, camera_bind_layout: &wgpu::BindGroupLayout) -> Self {
+		let config = wgpu::SurfaceConfiguration {
+			width: (config.width / RESOLUTION_DIVISOR).max(1),
+			height: (config.height / RESOLUTION_DIVISOR).max(1),
+			..config.clone()
+		};
+
+		let color_texture = device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("reflection texture"),
+			size: wgpu::Extent3d {
+				width: config.width,
+				height: config.height,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			// the same format as the surface, so the terrain pipeline can draw into it
+			format: config.format,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+		});
+		let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+		let depth_texture = DepthTexture::new(device, &config, "reflection depth texture");
+
+		let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("reflection camera buffer"),
+			size: mem::size_of::<CameraUniform>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let camera_bind_group = device.create_bind_group(
+			&wgpu::BindGroupDescriptor {
+				label: Some("reflection camera bind group"),
+				layout: camera_bind_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: camera_buffer.as_entire_binding(),
+					},
+				],
+			}
+		);
+
+		Reflection {
+			_color_texture: color_texture,
+			color_view,
+			depth_texture,
+			camera_buffer,
+			camera_bind_group,
+		}
+	}
+
+	pub fn color_view(&self) -> &wgpu::TextureView {
+		&self.color_view
+	}
+
+	pub fn depth_view(&self) -> &wgpu::TextureView {
+		&self.depth_texture.view
+	}
+
+	// writes the mirrored camera's matrix for this frame, and returns the camera bind group to draw the reflection with
+	pub fn prepare(&self, queue: &wgpu::Queue, mirrored_camera: &Camera) -> &wgpu::BindGroup {
+		queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[mirrored_camera.get_camera_uniform()]));
+		&self.camera_bind_group
+	}
+}
+
+// sampled by water when there is no reflection to sample, its alpha of 0 means nothing is reflected
+pub fn create_placeholder(device: &wgpu::Device, queue: &wgpu::Queue) -> (wgpu::Texture, wgpu::TextureView) {
+	let texture = device.create_texture_with_data(
+		queue,
+		&wgpu::TextureDescriptor {
+			label: Some("placeholder reflection texture"),
+			size: wgpu::Extent3d {
+				width: 1,
+				height: 1,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: wgpu::TextureFormat::Rgba8Unorm,
+			usage: wgpu::TextureUsages::TEXTURE_BINDING,
+		},
+		&[0, 0, 0, 0],
+	);
+	let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+	(texture, view)
+}
@@ -0,0 +1,252 @@
+use std::ops::Range;
+
+use super::Aabb;
+use super::blob_shadow::BlobShadow;
+use super::particle::Particle;
+use super::terrain_buffer::{TerrainMesh, TerrainPage, DrawIndexedIndirectArgs};
+
+// how much work the shader does for a terrain draw, far away terrain can skip detail which is too small to see
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DetailLevel {
+	Full,
+	// textures are sampled from smaller mips and ambient occlusion is skipped
+	Reduced,
+}
+
+impl DetailLevel {
+	// value passed to the shader, 1 for full detail and 0 for reduced detail
+	pub fn shader_value(self) -> f32 {
+		match self {
+			DetailLevel::Full => 1.0,
+			DetailLevel::Reduced => 0.0,
+		}
+	}
+}
+
+// something to draw this frame, the kind of item decides which pass and pipeline it is drawn with
+#[derive(Debug, Clone, Copy)]
+pub enum DrawItem<'a> {
+	TerrainOpaque(&'a TerrainMesh, DetailLevel),
+	// blended over opaque terrain with the given alpha
+	// items with a larger sort key are drawn first, so the key should be the distance from the camera
+	TerrainTranslucent {
+		mesh: &'a TerrainMesh,
+		alpha: f32,
+		sort_key: f32,
+		detail: DetailLevel,
+	},
+	// blended over terrain, after all terrain is drawn
+	BlobShadow(BlobShadow),
+	// blended over everything else in the world
+	Particle(Particle),
+}
+
+impl DrawItem<'_> {
+	fn bounding_box(&self) -> Aabb {
+		match self {
+			DrawItem::TerrainOpaque(mesh, _) => mesh.bounding_box,
+			DrawItem::TerrainTranslucent { mesh, .. } => mesh.bounding_box,
+			DrawItem::BlobShadow(shadow) => shadow.bounding_box(),
+			DrawItem::Particle(particle) => particle.bounding_box(),
+		}
+	}
+}
+
+// everything the client wants drawn in a frame, filled by the client then passed to Renderer::render
+#[derive(Debug, Default)]
+pub struct RenderQueue<'a> {
+	// bounding boxes of cull groups, items in a group whose box isn't visible are skipped without being culled themselves
+	groups: Vec<Aabb>,
+	// index of the cull group of each item
+	items: Vec<(Option<usize>, DrawItem<'a>)>,
+}
+
+impl<'a> RenderQueue<'a> {
+	pub fn new() -> Self {
+		RenderQueue {
+			groups: Vec::new(),
+			items: Vec::new(),
+		}
+	}
+
+	pub fn push(&mut self, item: DrawItem<'a>) {
+		self.items.push((None, item));
+	}
+
+	// all the items must lie inside the bounding box
+	pub fn push_group<I: IntoIterator<Item = DrawItem<'a>>>(&mut self, bounding_box: Aabb, items: I) {
+		let group = self.groups.len();
+		self.groups.push(bounding_box);
+		self.items.extend(items.into_iter().map(|item| (Some(group), item)));
+	}
+
+	// culls the items and puts the visible ones in the order they are drawn
+	pub fn sorted_draws<F: Fn(Aabb) -> bool>(&self, is_visible: F) -> SortedDraws {
+		let group_visible = self.groups.iter().map(|group| is_visible(*group)).collect::<Vec<_>>();
+		let visible_groups = group_visible.iter().filter(|visible| **visible).count();
+
+		let mut terrain_opaque = Vec::new();
+		let mut terrain_translucent = Vec::new();
+		let mut blob_shadows = Vec::new();
+		let mut particles = Vec::new();
+		for (group, item) in self.items.iter() {
+			if let Some(group) = group {
+				if !group_visible[*group] {
+					continue;
+				}
+			}
+			if !is_visible(item.bounding_box()) {
+				continue;
+			}
+
+			match item {
+				DrawItem::TerrainOpaque(mesh, detail) => terrain_opaque.push((*detail, mesh.page(), mesh.draw_args())),
+				DrawItem::TerrainTranslucent { mesh, alpha, sort_key, detail } => {
+					terrain_translucent.push((*sort_key, *alpha, *detail, mesh.page(), mesh.draw_args()));
+				},
+				DrawItem::BlobShadow(shadow) => blob_shadows.push(*shadow),
+				DrawItem::Particle(particle) => particles.push(*particle),
+			}
+		}
+
+		// blended draws are drawn back to front
+		terrain_translucent.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+		// meshes in the same page with the same detail level are drawn together
+		terrain_opaque.sort_by_key(|(detail, page, _)| (*detail, *page));
+		let mut terrain_page_runs: Vec<(TerrainPage, DetailLevel, Range<usize>)> = Vec::new();
+		for (i, (detail, page, _)) in terrain_opaque.iter().enumerate() {
+			match terrain_page_runs.last_mut() {
+				Some((run_page, run_detail, run)) if run_page == page && run_detail == detail => *run = run.start..i + 1,
+				_ => terrain_page_runs.push((*page, *detail, i..i + 1)),
+			}
+		}
+
+		SortedDraws {
+			terrain_opaque: terrain_opaque.into_iter()
+				.map(|(_, page, args)| (page, args))
+				.collect(),
+			terrain_page_runs,
+			terrain_translucent: terrain_translucent.into_iter()
+				.map(|(_, alpha, detail, page, args)| (alpha, detail, page, args))
+				.collect(),
+			blob_shadows,
+			particles,
+			visible_groups,
+			culled_groups: self.groups.len() - visible_groups,
+		}
+	}
+}
+
+// the visible draws of a render queue, grouped by pass in the order the passes are drawn
+#[derive(Debug, Default)]
+pub struct SortedDraws {
+	// sorted by detail level, then by page
+	pub terrain_opaque: Vec<(TerrainPage, DrawIndexedIndirectArgs)>,
+	// ranges of terrain_opaque which are in the same page and have the same detail level
+	pub terrain_page_runs: Vec<(TerrainPage, DetailLevel, Range<usize>)>,
+	// back to front, with the alpha and detail level of each draw
+	pub terrain_translucent: Vec<(f32, DetailLevel, TerrainPage, DrawIndexedIndirectArgs)>,
+	// shadows don't overlap each other much, so they aren't sorted
+	pub blob_shadows: Vec<BlobShadow>,
+	// particles are faint and thin, so they aren't sorted either
+	pub particles: Vec<Particle>,
+	// number of cull groups which were visible and which were culled
+	pub visible_groups: usize,
+	pub culled_groups: usize,
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::RefCell;
+
+	use glam::{Vec3, Vec4};
+
+	use super::*;
+	use super::super::buffer_pool::{Allocation, PoolAllocation};
+
+	// a mesh whose index range starts at first_index, so draws can be told apart
+	fn mesh(page: usize, first_index: u32, x: f32) -> TerrainMesh {
+		let allocation = PoolAllocation {
+			page,
+			range: Allocation { start: first_index, len: 6 },
+		};
+		TerrainMesh::new(allocation, allocation, Aabb::new(Vec3::new(x, 0.0, 0.0), Vec3::ONE))
+	}
+
+	#[test]
+	fn draws_are_culled_and_sorted_by_pass() {
+		let opaque = [mesh(1, 0, 0.0), mesh(0, 10, 1.0), mesh(1, 20, 2.0)];
+		let translucent = [mesh(0, 30, 3.0), mesh(0, 40, 4.0), mesh(0, 50, 5.0)];
+		let culled = mesh(0, 60, 500.0);
+		let hidden_group = [mesh(0, 70, 600.0), mesh(0, 80, 601.0)];
+
+		let mut queue = RenderQueue::new();
+		queue.push_group(Aabb::new(Vec3::ZERO, Vec3::splat(100.0)), [
+			DrawItem::TerrainTranslucent { mesh: &translucent[0], alpha: 0.25, sort_key: 1.0, detail: DetailLevel::Full },
+			DrawItem::TerrainOpaque(&opaque[0], DetailLevel::Full),
+			DrawItem::TerrainOpaque(&opaque[1], DetailLevel::Full),
+			DrawItem::TerrainTranslucent { mesh: &translucent[1], alpha: 0.5, sort_key: 9.0, detail: DetailLevel::Full },
+		]);
+		queue.push(DrawItem::TerrainOpaque(&opaque[2], DetailLevel::Full));
+		queue.push(DrawItem::TerrainTranslucent { mesh: &translucent[2], alpha: 0.75, sort_key: 4.0, detail: DetailLevel::Full });
+		queue.push(DrawItem::TerrainOpaque(&culled, DetailLevel::Full));
+		let shadow = BlobShadow::new(Vec3::new(10.0, 1.0, 0.0), 1.0).unwrap();
+		queue.push(DrawItem::BlobShadow(shadow));
+		queue.push(DrawItem::BlobShadow(BlobShadow::new(Vec3::new(550.0, 1.0, 0.0), 1.0).unwrap()));
+		let particle = Particle::new(Vec3::new(20.0, 5.0, 0.0), 0.05, 0.5, Vec4::ONE);
+		queue.push(DrawItem::Particle(particle));
+		queue.push(DrawItem::Particle(Particle::new(Vec3::new(520.0, 5.0, 0.0), 0.05, 0.5, Vec4::ONE)));
+		queue.push_group(
+			Aabb::new(Vec3::new(600.0, 0.0, 0.0), Vec3::splat(100.0)),
+			hidden_group.iter().map(|mesh| DrawItem::TerrainOpaque(mesh, DetailLevel::Full)),
+		);
+
+		let tested = RefCell::new(Vec::new());
+		let draws = queue.sorted_draws(|aabb| {
+			tested.borrow_mut().push(aabb.position.x);
+			aabb.position.x < 100.0
+		});
+
+		// the items of a group which isn't visible are never tested
+		assert!(!tested.borrow().iter().any(|x| *x > 600.0));
+
+		let opaque_draws = draws.terrain_opaque.iter().map(|(page, args)| (*page, args.first_index)).collect::<Vec<_>>();
+		assert_eq!(opaque_draws, vec![
+			(opaque[1].page(), 10),
+			(opaque[0].page(), 0),
+			(opaque[2].page(), 20),
+		]);
+		assert_eq!(draws.terrain_page_runs, vec![
+			(opaque[1].page(), DetailLevel::Full, 0..1),
+			(opaque[0].page(), DetailLevel::Full, 1..3),
+		]);
+
+		// back to front
+		let translucent_draws = draws.terrain_translucent.iter().map(|(alpha, _, _, args)| (*alpha, args.first_index)).collect::<Vec<_>>();
+		assert_eq!(translucent_draws, vec![(0.5, 40), (0.75, 50), (0.25, 30)]);
+
+		assert_eq!(draws.blob_shadows, vec![shadow]);
+		assert_eq!(draws.particles, vec![particle]);
+		assert_eq!((draws.visible_groups, draws.culled_groups), (1, 1));
+	}
+	#[test]
+	fn page_runs_are_split_by_detail_level() {
+		let meshes = [mesh(0, 0, 0.0), mesh(0, 10, 1.0), mesh(1, 20, 2.0), mesh(0, 30, 3.0)];
+
+		let mut queue = RenderQueue::new();
+		queue.push(DrawItem::TerrainOpaque(&meshes[0], DetailLevel::Reduced));
+		queue.push(DrawItem::TerrainOpaque(&meshes[1], DetailLevel::Full));
+		queue.push(DrawItem::TerrainOpaque(&meshes[2], DetailLevel::Reduced));
+		queue.push(DrawItem::TerrainOpaque(&meshes[3], DetailLevel::Full));
+
+		let draws = queue.sorted_draws(|_| true);
+		let opaque_draws = draws.terrain_opaque.iter().map(|(_, args)| args.first_index).collect::<Vec<_>>();
+		assert_eq!(opaque_draws, vec![10, 30, 0, 20]);
+		assert_eq!(draws.terrain_page_runs, vec![
+			(meshes[1].page(), DetailLevel::Full, 0..2),
+			(meshes[0].page(), DetailLevel::Reduced, 2..3),
+			(meshes[2].page(), DetailLevel::Reduced, 3..4),
+		]);
+	}
+}
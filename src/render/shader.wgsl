@@ -7,10 +7,42 @@ struct CameraUniform {
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+struct SceneUniform {
+	fog_color: vec4<f32>,
+	// 0 turns off ambient occlusion, 1 is full strength
+	ao_strength: f32,
+	// distance from the camera fog starts at, and where it completely covers terrain
+	fog_start: f32,
+	fog_end: f32,
+	// the top faces of blocks with this texture show the reflection
+	water_texture_index: i32,
+	// the 4th component is unused
+	camera_position: vec4<f32>,
+	// projects a position on the water surface to where it is in the reflection, with the mirrored camera
+	reflection_view_proj: mat4x4<f32>,
+	// height of the water surface which is reflected
+	reflection_height: f32,
+	// 1 if a reflection was drawn this frame, 0 if reflections are off
+	reflection_strength: f32,
+	// 1 if faces are darkened by the direction they face, 0 if it is off
+	face_shading: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> scene: SceneUniform;
+@group(2) @binding(1)
+var reflection_texture: texture_2d<f32>;
+@group(2) @binding(2)
+var reflection_sampler: sampler;
+
+// brightness lost per ambient occlusion level at full strength
+let AO_LEVEL_DARKNESS: f32 = 0.2;
+
 struct VertexInput {
 	@location(0) position: vec3<f32>,
 	@location(1) normal: vec3<f32>,
-	@location(2) color: vec3<f32>,
+	// 0 to 3, may be fractional with smooth lighting
+	@location(2) occlusion_level: f32,
 	@location(3) texture_index: i32,
 }
 
@@ -18,8 +50,11 @@ struct VertexOutput {
 	@builtin(position) clip_position: vec4<f32>,
 	@location(0) world_pos: vec3<f32>,
 	@location(1) world_normal: vec3<f32>,
+	// brightness from ambient occlusion
 	@location(2) color: vec3<f32>,
 	@location(3) texture_index: i32,
+	// distance from the camera along the view direction
+	@location(4) view_depth: f32,
 }
 
 @vertex
@@ -28,8 +63,11 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 	vertex_out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
 	vertex_out.world_pos = model.position;
 	vertex_out.world_normal = model.normal;
-	vertex_out.color = model.color;
+	let brightness = 1.0 - AO_LEVEL_DARKNESS * model.occlusion_level * scene.ao_strength;
+	vertex_out.color = vec3<f32>(brightness, brightness, brightness);
 	vertex_out.texture_index = model.texture_index;
+	// w of the clip position is the view space depth with a perspective projection
+	vertex_out.view_depth = vertex_out.clip_position.w;
 	return vertex_out;
 }
 
@@ -37,10 +75,108 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 // Fragment shader
 
 @group(0) @binding(0)
-var block_diffuse_textures: binding_array<texture_2d<f32>>;
+var block_diffuse_textures: texture_2d_array<f32>;
 @group(0) @binding(1)
 var block_diffuse_sampler: sampler;
 
+struct DrawConstants {
+	// opacity of the whole draw, used to fade in new terrain
+	alpha: f32,
+	// 1 for full detail, 0 for far away terrain which skips ambient occlusion and samples smaller mips
+	detail: f32,
+}
+
+// each draw binds its own constants with a dynamic offset
+@group(2) @binding(3)
+var<uniform> draw_constants: DrawConstants;
+
+let CASCADE_COUNT: i32 = 3;
+// brightness of surfaces in shadow or facing away from the sun
+let SHADOW_BRIGHTNESS: f32 = 0.6;
+// brightness of each face direction with face shading, so the sides of terrain can be told apart without any light
+let TOP_FACE_BRIGHTNESS: f32 = 1.0;
+let Z_FACE_BRIGHTNESS: f32 = 0.8;
+let X_FACE_BRIGHTNESS: f32 = 0.6;
+let BOTTOM_FACE_BRIGHTNESS: f32 = 0.5;
+// mip level bias of reduced detail terrain, each level halves the texture resolution
+let REDUCED_DETAIL_LOD_BIAS: f32 = 1.0;
+// how much water reflects when seen from straight above, it reflects more at grazing angles
+let WATER_BASE_REFLECTIVITY: f32 = 0.15;
+// steepness of the ripples on water
+let RIPPLE_SLOPE: f32 = 0.05;
+// how far the reflection is moved per unit of ripple slope, in reflection texture coordinates
+let REFLECTION_DISTORTION: f32 = 0.16;
+
+struct ShadowUniform {
+	cascade_matrices: array<mat4x4<f32>, 3>,
+	// view depth each cascade ends at, the 4th component is unused
+	cascade_splits: vec4<f32>,
+	// pointing towards the sun
+	sun_direction: vec4<f32>,
+	depth_bias: f32,
+	normal_offset: f32,
+	// 1 if shadows are on
+	enabled: f32,
+}
+
+@group(3) @binding(0)
+var<uniform> shadow: ShadowUniform;
+@group(3) @binding(1)
+var shadow_maps: texture_depth_2d_array;
+@group(3) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+// 1 if the point is lit by the sun, 0 if it is in shadow, and in between at the edge of a shadow
+fn sun_visibility(world_pos: vec3<f32>, world_normal: vec3<f32>, view_depth: f32) -> f32 {
+	if (shadow.enabled == 0.0) {
+		return 1.0;
+	}
+
+	var cascade: i32 = 0;
+	loop {
+		if (cascade >= CASCADE_COUNT || view_depth < shadow.cascade_splits[cascade]) {
+			break;
+		}
+		cascade = cascade + 1;
+	}
+	// past the last cascade nothing is shadowed
+	if (cascade >= CASCADE_COUNT) {
+		return 1.0;
+	}
+
+	let sample_pos = world_pos + world_normal * shadow.normal_offset;
+	let light_pos = shadow.cascade_matrices[cascade] * vec4<f32>(sample_pos, 1.0);
+	let uv = light_pos.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+	let depth = light_pos.z - shadow.depth_bias;
+	if (any(uv < vec2<f32>(0.0, 0.0)) || any(uv > vec2<f32>(1.0, 1.0)) || depth > 1.0) {
+		return 1.0;
+	}
+
+	// 3x3 percentage closer filtering
+	let texel_size = 1.0 / vec2<f32>(textureDimensions(shadow_maps));
+	var lit: f32 = 0.0;
+	for (var x: i32 = -1; x <= 1; x = x + 1) {
+		for (var y: i32 = -1; y <= 1; y = y + 1) {
+			let offset = vec2<f32>(f32(x), f32(y)) * texel_size;
+			lit = lit + textureSampleCompareLevel(shadow_maps, shadow_sampler, uv + offset, cascade, depth);
+		}
+	}
+	return lit / 9.0;
+}
+
+// brightness of a face from the direction it faces, 1 if face shading is off
+fn face_brightness(world_normal: vec3<f32>) -> f32 {
+	var brightness: f32 = Z_FACE_BRIGHTNESS;
+	if (world_normal.y > 0.0) {
+		brightness = TOP_FACE_BRIGHTNESS;
+	} else if (world_normal.y < 0.0) {
+		brightness = BOTTOM_FACE_BRIGHTNESS;
+	} else if (world_normal.x != 0.0) {
+		brightness = X_FACE_BRIGHTNESS;
+	}
+	return mix(1.0, brightness, scene.face_shading);
+}
+
 fn wrap_pos(n: f32) -> f32 {
 	if (n >= 0.0) {
 		return n % 1.0;
@@ -49,42 +185,124 @@ fn wrap_pos(n: f32) -> f32 {
 	}
 }
 
-@fragment
-fn fs_main(fragment_in: VertexOutput) -> @location(0) vec4<f32> {
+// position in the texture atlas of the block's texture array layer, the face of the cube the atlas region is from depends on the normal
+fn texture_coords(world_pos: vec3<f32>, world_normal: vec3<f32>) -> vec2<f32> {
 	var offset: vec2<f32>;
 	var sample_pos: vec2<f32>;
 
-	if (fragment_in.world_normal.x > 0.0) {
+	if (world_normal.x > 0.0) {
 		offset.x = 0.5;
 		offset.y = 0.66666;
-		sample_pos.x = 0.25 * wrap_pos(fragment_in.world_pos.z);
-		sample_pos.y = 0.33333 * wrap_pos(fragment_in.world_pos.y);
-	} else if (fragment_in.world_normal.x < 0.0) {
+		sample_pos.x = 0.25 * wrap_pos(world_pos.z);
+		sample_pos.y = 0.33333 * wrap_pos(world_pos.y);
+	} else if (world_normal.x < 0.0) {
 		offset.x = 0.5;
 		offset.y = 0.33333;
-		sample_pos.x = 0.25 * wrap_pos(fragment_in.world_pos.z);
-		sample_pos.y = -0.33333 * wrap_pos(fragment_in.world_pos.y);
-	} else if (fragment_in.world_normal.y > 0.0) {
+		sample_pos.x = 0.25 * wrap_pos(world_pos.z);
+		sample_pos.y = -0.33333 * wrap_pos(world_pos.y);
+	} else if (world_normal.y > 0.0) {
 		offset.x = 0.25;
 		offset.y = 0.33333;
-		sample_pos.x = -0.25 * wrap_pos(fragment_in.world_pos.z);
-		sample_pos.y = 0.33333 * wrap_pos(fragment_in.world_pos.x);
-	} else if (fragment_in.world_normal.y < 0.0) {
+		sample_pos.x = -0.25 * wrap_pos(world_pos.z);
+		sample_pos.y = 0.33333 * wrap_pos(world_pos.x);
+	} else if (world_normal.y < 0.0) {
 		offset.x = 0.5;
 		offset.y = 0.33333;
-		sample_pos.x = 0.25 * wrap_pos(fragment_in.world_pos.z);
-		sample_pos.y = 0.33333 * wrap_pos(fragment_in.world_pos.x);
-	} else if (fragment_in.world_normal.z > 0.0) {
+		sample_pos.x = 0.25 * wrap_pos(world_pos.z);
+		sample_pos.y = 0.33333 * wrap_pos(world_pos.x);
+	} else if (world_normal.z > 0.0) {
 		offset.x = 0.75;
 		offset.y = 0.33333;
-		sample_pos.x = 0.25 * wrap_pos(fragment_in.world_pos.y);
-		sample_pos.y = 0.33333 * wrap_pos(fragment_in.world_pos.x);
+		sample_pos.x = 0.25 * wrap_pos(world_pos.y);
+		sample_pos.y = 0.33333 * wrap_pos(world_pos.x);
 	} else {
 		offset.x = 0.5;
 		offset.y = 0.33333;
-		sample_pos.x = -0.25 * wrap_pos(fragment_in.world_pos.y);
-		sample_pos.y = 0.33333 * wrap_pos(fragment_in.world_pos.x);
+		sample_pos.x = -0.25 * wrap_pos(world_pos.y);
+		sample_pos.y = 0.33333 * wrap_pos(world_pos.x);
+	}
+
+	return offset + sample_pos;
+}
+
+// the reflection seen on the water surface at world_pos, blended over the water's color
+fn water_reflection(world_pos: vec3<f32>, color: vec3<f32>) -> vec3<f32> {
+	// small ripples, so the water doesn't look like a mirror
+	let ripple_normal = normalize(vec3<f32>(
+		RIPPLE_SLOPE * sin(2.1 * world_pos.x + 1.3 * world_pos.z),
+		1.0,
+		RIPPLE_SLOPE * cos(1.7 * world_pos.z - 1.1 * world_pos.x),
+	));
+
+	let reflection_pos = scene.reflection_view_proj * vec4<f32>(world_pos, 1.0);
+	let uv = reflection_pos.xy / reflection_pos.w * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+	let distorted_uv = clamp(uv + ripple_normal.xz * REFLECTION_DISTORTION, vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 1.0));
+	// this is only reached by water fragments, so the mip level can't come from derivatives
+	let reflection = textureSampleLevel(reflection_texture, reflection_sampler, distorted_uv, 0.0);
+
+	// schlick's approximation
+	let view_direction = normalize(scene.camera_position.xyz - world_pos);
+	let facing = clamp(dot(view_direction, ripple_normal), 0.0, 1.0);
+	let fresnel = WATER_BASE_REFLECTIVITY + (1.0 - WATER_BASE_REFLECTIVITY) * pow(1.0 - facing, 5.0);
+	return mix(color, reflection.rgb, fresnel * reflection.a * scene.reflection_strength);
+}
+
+// lit and fogged terrain color, water shows the reflection if show_reflection is true
+fn terrain_color(fragment_in: VertexOutput, show_reflection: bool) -> vec4<f32> {
+	let uv = texture_coords(fragment_in.world_pos, fragment_in.world_normal);
+
+	var sunlight: f32 = 0.0;
+	if (dot(fragment_in.world_normal, shadow.sun_direction.xyz) > 0.0) {
+		sunlight = sun_visibility(fragment_in.world_pos, fragment_in.world_normal, fragment_in.view_depth);
+	}
+	let brightness = mix(SHADOW_BRIGHTNESS, 1.0, sunlight) * face_brightness(fragment_in.world_normal);
+
+	// mixed instead of branched on, so every fragment takes the same path
+	let ao_color = mix(vec3<f32>(1.0, 1.0, 1.0), fragment_in.color, draw_constants.detail);
+	let lod_bias = mix(REDUCED_DETAIL_LOD_BIAS, 0.0, draw_constants.detail);
+	let color = vec4<f32>(ao_color * brightness, 1.0) * textureSampleBias(block_diffuse_textures, block_diffuse_sampler, uv, fragment_in.texture_index, lod_bias);
+
+	var lit_color = color.rgb;
+	let is_water_surface = fragment_in.texture_index == scene.water_texture_index && fragment_in.world_normal.y > 0.0;
+	if (show_reflection && scene.reflection_strength > 0.0 && is_water_surface) {
+		lit_color = water_reflection(fragment_in.world_pos, lit_color);
+	}
+
+	let fog_amount = clamp((fragment_in.view_depth - scene.fog_start) / (scene.fog_end - scene.fog_start), 0.0, 1.0);
+	let fogged_color = mix(lit_color, scene.fog_color.rgb, fog_amount);
+	return vec4<f32>(fogged_color, color.a * draw_constants.alpha);
+}
+
+@fragment
+fn fs_main(fragment_in: VertexOutput) -> @location(0) vec4<f32> {
+	return terrain_color(fragment_in, true);
+}
+
+// draws terrain into the reflection, terrain below the water surface can't be seen in it
+// the reflection doesn't show reflections itself
+@fragment
+fn fs_reflected(fragment_in: VertexOutput) -> @location(0) vec4<f32> {
+	if (fragment_in.world_pos.y < scene.reflection_height) {
+		discard;
+	}
+	return terrain_color(fragment_in, false);
+}
+
+// the held block is drawn in its own space instead of the world's, so it isn't shadowed or fogged
+// its faces get fixed brightnesses instead, so its shape can still be seen
+@fragment
+fn fs_held_block(fragment_in: VertexOutput) -> @location(0) vec4<f32> {
+	let uv = texture_coords(fragment_in.world_pos, fragment_in.world_normal);
+	let normal = abs(fragment_in.world_normal);
+	var brightness: f32 = 0.8;
+	if (fragment_in.world_normal.y > 0.0) {
+		brightness = 1.0;
+	} else if (fragment_in.world_normal.y < 0.0) {
+		brightness = 0.5;
+	} else if (normal.z > normal.x) {
+		brightness = 0.65;
 	}
 
-	return vec4<f32>(fragment_in.color, 1.0) * textureSample(block_diffuse_textures[fragment_in.texture_index], block_diffuse_sampler, offset + sample_pos);
+	let color = textureSample(block_diffuse_textures, block_diffuse_sampler, uv, fragment_in.texture_index);
+	return vec4<f32>(color.rgb * brightness, 1.0);
 }
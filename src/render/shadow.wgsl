@@ -0,0 +1,37 @@
+// Blob shadows, dark circles drawn on the ground under entities
+
+struct CameraUniform {
+	view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+	@location(0) position: vec3<f32>,
+	// -1 to 1 across the quad, the circle is where its length is less than 1
+	@location(1) offset: vec2<f32>,
+	@location(2) alpha: f32,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) offset: vec2<f32>,
+	@location(1) alpha: f32,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+	var vertex_out: VertexOutput;
+	vertex_out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+	vertex_out.offset = model.offset;
+	vertex_out.alpha = model.alpha;
+	return vertex_out;
+}
+
+@fragment
+fn fs_main(fragment_in: VertexOutput) -> @location(0) vec4<f32> {
+	// darkest in the center, fading out to the edge of the circle
+	let falloff = clamp(1.0 - length(fragment_in.offset), 0.0, 1.0);
+	return vec4<f32>(0.0, 0.0, 0.0, fragment_in.alpha * falloff);
+}
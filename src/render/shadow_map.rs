@@ -0,0 +1,417 @@
+use std::borrow::Cow;
+use std::mem;
+
+use glam::{Mat4, Vec3};
+
+use super::Aabb;
+use super::camera::{Camera, CameraUniform};
+use super::model::Vertex;
+use super::texture::DepthTexture;
+use crate::game::BlockVertex;
+
+// the view frustum is split into this many cascades, each with its own layer of the shadow map
+pub const CASCADE_COUNT: usize = 3;
+// terrain further than this from the camera is not shadowed
+const SHADOW_DISTANCE: f32 = 160.0;
+// 0 splits the shadow distance evenly between cascades, 1 splits it logarithmically
+const SPLIT_LAMBDA: f32 = 0.75;
+// how far towards the sun from a cascade terrain is still drawn into the shadow map, so it can shadow the cascade
+const CASTER_DISTANCE: f32 = 128.0;
+// direction of the sun before the sky is rotated, the sun doesn't move until there is a day night cycle
+pub const DEFAULT_SUN_DIRECTION: Vec3 = Vec3::new(0.3, 0.85, 0.45);
+
+const SHADOW_MAP_SHADER: &str = include_str!("shadow_map.wgsl");
+
+// offsets applied when sampling the shadow map, to stop surfaces from shadowing themselves
+// too much bias makes shadows detach from whatever casts them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowBias {
+	// subtracted from a fragment's depth in the shadow map
+	pub depth_bias: f32,
+	// distance in blocks the sample point is moved out along the surface normal
+	pub normal_offset: f32,
+}
+
+impl Default for ShadowBias {
+	fn default() -> Self {
+		ShadowBias {
+			depth_bias: 0.0005,
+			normal_offset: 0.05,
+		}
+	}
+}
+
+// distance from the camera at which each cascade ends
+// closer cascades are smaller, so shadows close to the camera get more detail
+pub fn cascade_splits(near: f32, far: f32) -> [f32; CASCADE_COUNT] {
+	let mut splits = [0.0; CASCADE_COUNT];
+	for (i, split) in splits.iter_mut().enumerate() {
+		let fraction = (i + 1) as f32 / CASCADE_COUNT as f32;
+		let logarithmic = near * (far / near).powf(fraction);
+		let uniform = near + (far - near) * fraction;
+		*split = SPLIT_LAMBDA * logarithmic + (1.0 - SPLIT_LAMBDA) * uniform;
+	}
+	splits
+}
+
+// orthographic view projection from the sun which covers the frustum slice with the given corners
+// it only depends on the size of the slice and not its rotation, and moves in whole shadow map texels,
+// so shadow edges don't shimmer as the camera moves
+pub fn cascade_matrix(corners: &[Vec3; 8], sun_direction: Vec3, resolution: u32) -> Mat4 {
+	let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+	let radius = corners.iter()
+		.map(|corner| corner.distance(center))
+		.fold(0.0, f32::max)
+		.ceil();
+
+	let sun_direction = sun_direction.normalize();
+	let up = if sun_direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+	let light_view = Mat4::look_at_rh(Vec3::ZERO, -sun_direction, up);
+
+	let texel_size = 2.0 * radius / resolution as f32;
+	let light_center = light_view.transform_point3(center);
+	let x = (light_center.x / texel_size).floor() * texel_size;
+	let y = (light_center.y / texel_size).floor() * texel_size;
+
+	// the light looks down -z, so terrain towards the sun has a larger z
+	let projection = Mat4::orthographic_rh(
+		x - radius,
+		x + radius,
+		y - radius,
+		y + radius,
+		-(light_center.z + radius + CASTER_DISTANCE),
+		-(light_center.z - radius),
+	);
+
+	projection * light_view
+}
+
+// if any part of the bounding box could be in the shadow map of the cascade
+pub fn light_box_visible(cascade_matrix: Mat4, aabb: Aabb) -> bool {
+	let mut min = Vec3::splat(f32::INFINITY);
+	let mut max = Vec3::splat(f32::NEG_INFINITY);
+	for i in 0..8 {
+		let corner = aabb.position + aabb.size * Vec3::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32);
+		let projected = cascade_matrix.transform_point3(corner);
+		min = min.min(projected);
+		max = max.max(projected);
+	}
+
+	max.x >= -1.0 && min.x <= 1.0
+		&& max.y >= -1.0 && min.y <= 1.0
+		&& max.z >= 0.0 && min.z <= 1.0
+}
+
+// shadow parameters used by the terrain shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+	cascade_matrices: [[[f32; 4]; 4]; CASCADE_COUNT],
+	// distance from the camera each cascade ends at, the 4th component is unused
+	cascade_splits: [f32; 4],
+	// pointing towards the sun, the 4th component is unused
+	sun_direction: [f32; 4],
+	depth_bias: f32,
+	normal_offset: f32,
+	// 1 if shadows are on, everything is lit by the sun when they are off
+	enabled: f32,
+	_padding: f32,
+}
+
+// one depth texture layer per cascade, rendered from the sun before the main pass
+#[derive(Debug)]
+pub struct ShadowMaps {
+	// kept alive for its views
+	_texture: wgpu::Texture,
+	// used as the depth attachment when rendering each cascade
+	layer_views: Vec<wgpu::TextureView>,
+	resolution: u32,
+	// view projection of each cascade, bound like a camera when rendering the cascade
+	cascade_buffers: Vec<wgpu::Buffer>,
+	cascade_bind_groups: Vec<wgpu::BindGroup>,
+	uniform_buffer: wgpu::Buffer,
+	// the shadow maps and shadow uniform, used by the terrain shader
+	bind_group: wgpu::BindGroup,
+}
+
+impl ShadowMaps {
+	pub fn new(
+		device: &wgpu::Device,
+		resolution: u32,
+		camera_bind_layout: &wgpu::BindGroupLayout,
+		shadow_bind_layout: &wgpu::BindGroupLayout,
+	) -> Self {
+		let texture = device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("shadow map"),
+			size: wgpu::Extent3d {
+				width: resolution,
+				height: resolution,
+				depth_or_array_layers: CASCADE_COUNT as u32,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: DepthTexture::DEPTH_FORMAT,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+		});
+
+		let layer_views = (0..CASCADE_COUNT as u32)
+			.map(|layer| texture.create_view(&wgpu::TextureViewDescriptor {
+				label: Some("shadow map cascade view"),
+				dimension: Some(wgpu::TextureViewDimension::D2),
+				base_array_layer: layer,
+				array_layer_count: std::num::NonZeroU32::new(1),
+				..Default::default()
+			}))
+			.collect::<Vec<_>>();
+		let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+			label: Some("shadow map array view"),
+			dimension: Some(wgpu::TextureViewDimension::D2Array),
+			..Default::default()
+		});
+
+		// linear filtering compares against 4 texels, which smooths shadow edges on top of the filtering in the shader
+		let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+			label: Some("shadow map sampler"),
+			address_mode_u: wgpu::AddressMode::ClampToEdge,
+			address_mode_v: wgpu::AddressMode::ClampToEdge,
+			address_mode_w: wgpu::AddressMode::ClampToEdge,
+			mag_filter: wgpu::FilterMode::Linear,
+			min_filter: wgpu::FilterMode::Linear,
+			mipmap_filter: wgpu::FilterMode::Nearest,
+			compare: Some(wgpu::CompareFunction::LessEqual),
+			..Default::default()
+		});
+
+		let cascade_buffers = (0..CASCADE_COUNT)
+			.map(|_| device.create_buffer(&wgpu::BufferDescriptor {
+				label: Some("shadow cascade buffer"),
+				size: mem::size_of::<CameraUniform>() as u64,
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+				mapped_at_creation: false,
+			}))
+			.collect::<Vec<_>>();
+		let cascade_bind_groups = cascade_buffers.iter()
+			.map(|buffer| device.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("shadow cascade bind group"),
+				layout: camera_bind_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: buffer.as_entire_binding(),
+					},
+				],
+			}))
+			.collect::<Vec<_>>();
+
+		let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("shadow uniform buffer"),
+			size: mem::size_of::<ShadowUniform>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("shadow bind group"),
+			layout: shadow_bind_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: uniform_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(&array_view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::Sampler(&sampler),
+				},
+			],
+		});
+
+		ShadowMaps {
+			_texture: texture,
+			layer_views,
+			resolution,
+			cascade_buffers,
+			cascade_bind_groups,
+			uniform_buffer,
+			bind_group,
+		}
+	}
+
+	pub fn create_bind_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+		device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("shadow bind group layout"),
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						multisampled: false,
+						view_dimension: wgpu::TextureViewDimension::D2Array,
+						sample_type: wgpu::TextureSampleType::Depth,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+					count: None,
+				},
+			],
+		})
+	}
+
+	// terrain is drawn into each cascade with only its depth
+	// the hardware slope bias handles surfaces at a steep angle to the sun, which the uniform bias can't
+	pub fn build_pipeline(device: &wgpu::Device, camera_bind_layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("shadow_map.wgsl"),
+			source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADOW_MAP_SHADER)),
+		});
+
+		let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("shadow map pipeline layout"),
+			bind_group_layouts: &[camera_bind_layout],
+			push_constant_ranges: &[],
+		});
+
+		device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("shadow map pipeline"),
+			layout: Some(&layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[
+					BlockVertex::desc(),
+				],
+			},
+			fragment: None,
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				// the light can see the back of faces the camera culls
+				cull_mode: None,
+				polygon_mode: wgpu::PolygonMode::Fill,
+				unclipped_depth: false,
+				conservative: false,
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: DepthTexture::DEPTH_FORMAT,
+				depth_write_enabled: true,
+				depth_compare: wgpu::CompareFunction::Less,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState {
+					constant: 2,
+					slope_scale: 2.0,
+					clamp: 0.0,
+				},
+			}),
+			multisample: wgpu::MultisampleState {
+				count: 1,
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+		})
+	}
+
+	pub fn resolution(&self) -> u32 {
+		self.resolution
+	}
+
+	pub fn layer_view(&self, cascade: usize) -> &wgpu::TextureView {
+		&self.layer_views[cascade]
+	}
+
+	pub fn cascade_bind_group(&self, cascade: usize) -> &wgpu::BindGroup {
+		&self.cascade_bind_groups[cascade]
+	}
+
+	pub fn bind_group(&self) -> &wgpu::BindGroup {
+		&self.bind_group
+	}
+
+	// fits the cascades to the camera and uploads them, returning the view projection of each cascade
+	// when shadows are off the cascades are still returned, but the shader ignores them
+	pub fn update(
+		&self,
+		queue: &wgpu::Queue,
+		camera: &Camera,
+		sun_direction: Vec3,
+		bias: ShadowBias,
+		enabled: bool,
+	) -> [Mat4; CASCADE_COUNT] {
+		let splits = cascade_splits(camera.znear(), SHADOW_DISTANCE);
+		let mut matrices = [Mat4::IDENTITY; CASCADE_COUNT];
+		let mut near = camera.znear();
+		for (i, far) in splits.iter().enumerate() {
+			matrices[i] = cascade_matrix(&camera.frustum_slice_corners(near, *far), sun_direction, self.resolution);
+			queue.write_buffer(&self.cascade_buffers[i], 0, bytemuck::cast_slice(&matrices[i].to_cols_array_2d()));
+			near = *far;
+		}
+
+		let mut cascade_splits = [0.0; 4];
+		cascade_splits[..CASCADE_COUNT].copy_from_slice(&splits);
+		let uniform = ShadowUniform {
+			cascade_matrices: matrices.map(|matrix| matrix.to_cols_array_2d()),
+			cascade_splits,
+			sun_direction: sun_direction.normalize().extend(0.0).to_array(),
+			depth_bias: bias.depth_bias,
+			normal_offset: bias.normal_offset,
+			enabled: enabled as u32 as f32,
+			_padding: 0.0,
+		};
+		queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+
+		matrices
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cascade_splits_increase() {
+		let splits = cascade_splits(0.1, SHADOW_DISTANCE);
+		assert!(splits[0] > 0.1);
+		assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+		assert!((splits[CASCADE_COUNT - 1] - SHADOW_DISTANCE).abs() < 0.001);
+	}
+
+	#[test]
+	fn cascade_covers_frustum_slice() {
+		let position = Vec3::new(100.5, 70.0, -30.25);
+		let camera = Camera::new(position, position + Vec3::new(1.0, -0.3, 0.5).normalize(), 16.0 / 9.0);
+		let corners = camera.frustum_slice_corners(5.0, 40.0);
+		let matrix = cascade_matrix(&corners, DEFAULT_SUN_DIRECTION, 2048);
+
+		for corner in corners {
+			let projected = matrix.transform_point3(corner);
+			assert!(projected.x.abs() <= 1.0 && projected.y.abs() <= 1.0, "{:?}", projected);
+			assert!((0.0..=1.0).contains(&projected.z), "{:?}", projected);
+		}
+
+		// terrain between the slice and the sun is drawn into the cascade, terrain beyond it is not
+		let center = corners.iter().sum::<Vec3>() / 8.0;
+		let block = |position: Vec3| Aabb::new(position, Vec3::ONE);
+		assert!(light_box_visible(matrix, block(center + DEFAULT_SUN_DIRECTION.normalize() * 100.0)));
+		assert!(!light_box_visible(matrix, block(center + DEFAULT_SUN_DIRECTION.normalize() * 300.0)));
+		assert!(!light_box_visible(matrix, block(center + Vec3::new(500.0, 0.0, 0.0))));
+	}
+}
@@ -0,0 +1,17 @@
+// Depth only terrain shader, used to render each shadow cascade from the sun
+
+struct CascadeUniform {
+	view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> cascade: CascadeUniform;
+
+struct VertexInput {
+	@location(0) position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> @builtin(position) vec4<f32> {
+	return cascade.view_proj * vec4<f32>(model.position, 1.0);
+}
@@ -0,0 +1,177 @@
+use std::borrow::Cow;
+use std::mem;
+
+use glam::{Mat4, Quat, Vec3};
+
+use super::camera::Camera;
+use super::texture::DepthTexture;
+
+const SKY_SHADER: &str = include_str!("sky.wgsl");
+
+// camera and sun parameters used by the sky shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+	// the 4th component of each vector is unused
+	forward: [f32; 4],
+	right: [f32; 4],
+	up: [f32; 4],
+	sun_direction: [f32; 4],
+	world_to_sky: [[f32; 4]; 4],
+}
+
+// rays through the center, right edge, and top edge of the screen, relative to the center ray
+// the rays are 1 unit in front of the camera
+fn view_rays(camera: &Camera) -> [Vec3; 3] {
+	let corners = camera.frustum_slice_corners(1.0, 1.0);
+	let center = 0.5 * (corners[0] + corners[2]);
+	[
+		center - camera.position,
+		0.5 * (corners[1] - corners[0]),
+		0.5 * (corners[3] - corners[0]),
+	]
+}
+
+// stars and the moon, drawn over the clear color before anything else in the main pass
+// the stars are fixed to the sky, which turns with the sun
+#[derive(Debug)]
+pub struct Sky {
+	pipeline: wgpu::RenderPipeline,
+	uniform_buffer: wgpu::Buffer,
+	bind_group: wgpu::BindGroup,
+}
+
+impl Sky {
+	pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
+		let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("sky uniform buffer"),
+			size: mem::size_of::<SkyUniform>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let bind_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("sky bind group layout"),
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+			],
+		});
+
+		let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("sky bind group"),
+			layout: &bind_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: uniform_buffer.as_entire_binding(),
+				},
+			],
+		});
+
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("sky.wgsl"),
+			source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SKY_SHADER)),
+		});
+
+		let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("sky pipeline layout"),
+			bind_group_layouts: &[&bind_layout],
+			push_constant_ranges: &[],
+		});
+
+		let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("sky pipeline"),
+			layout: Some(&layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_main",
+				targets: &[Some(wgpu::ColorTargetState {
+					format: surface_format,
+					blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				cull_mode: None,
+				polygon_mode: wgpu::PolygonMode::Fill,
+				unclipped_depth: false,
+				conservative: false,
+			},
+			// everything drawn after the sky covers it
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: DepthTexture::DEPTH_FORMAT,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::Always,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample: wgpu::MultisampleState {
+				count: 1,
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+		});
+
+		Sky {
+			pipeline,
+			uniform_buffer,
+			bind_group,
+		}
+	}
+
+	// sky_rotation is how far the sky has turned, the sun and stars are turned by it
+	pub fn update(&self, queue: &wgpu::Queue, camera: &Camera, sun_direction: Vec3, sky_rotation: Quat) {
+		let [forward, right, up] = view_rays(camera);
+		let uniform = SkyUniform {
+			forward: forward.extend(0.0).to_array(),
+			right: right.extend(0.0).to_array(),
+			up: up.extend(0.0).to_array(),
+			sun_direction: sun_direction.normalize().extend(0.0).to_array(),
+			world_to_sky: Mat4::from_quat(sky_rotation.inverse()).to_cols_array_2d(),
+		};
+		queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+	}
+
+	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		render_pass.set_pipeline(&self.pipeline);
+		render_pass.set_bind_group(0, &self.bind_group, &[]);
+		render_pass.draw(0..3, 0..1);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn view_rays_match_frustum() {
+		let position = Vec3::new(4.0, 70.0, -12.0);
+		let camera = Camera::new(position, position + Vec3::new(0.5, 0.2, -1.0).normalize(), 16.0 / 9.0);
+		let [forward, right, up] = view_rays(&camera);
+
+		assert!((forward.length() - 1.0).abs() < 0.001);
+		assert!(forward.dot(right).abs() < 0.001 && forward.dot(up).abs() < 0.001);
+
+		// the top right corner of the screen points at the top right corner of the frustum
+		let far_corner = camera.frustum_slice_corners(1.0, 50.0)[6] - position;
+		assert!((forward + right + up).normalize().distance(far_corner.normalize()) < 0.001);
+	}
+}
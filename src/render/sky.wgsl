@@ -0,0 +1,80 @@
+// Sky shader, draws stars and the moon over the clear color before terrain is drawn
+
+struct SkyUniform {
+	// direction through the center of the screen, and the offset to the right and top edges of the screen
+	// 1 unit in front of the camera
+	forward: vec4<f32>,
+	right: vec4<f32>,
+	up: vec4<f32>,
+	// pointing towards the sun
+	sun_direction: vec4<f32>,
+	// rotates a direction in the world into the sky's own space, so stars turn with the sun
+	world_to_sky: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> sky: SkyUniform;
+
+// stars are placed in a grid of cells this many cells across the radius of the sky
+let STAR_GRID_SIZE: f32 = 80.0;
+// fraction of cells which have a star
+let STAR_CHANCE: f32 = 0.25;
+// radius of a star in cells
+let STAR_RADIUS: f32 = 0.08;
+// cosine of the angular radius of the moon
+let MOON_SIZE: f32 = 0.9993;
+let MOON_COLOR: vec3<f32> = vec3<f32>(0.85, 0.87, 0.92);
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	// -1 to 1 across the screen
+	@location(0) screen_pos: vec2<f32>,
+}
+
+// a single triangle which covers the screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	let pos = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+
+	var vertex_out: VertexOutput;
+	vertex_out.clip_position = vec4<f32>(pos, 1.0, 1.0);
+	vertex_out.screen_pos = pos;
+	return vertex_out;
+}
+
+fn hash(cell: vec3<f32>, seed: f32) -> f32 {
+	return fract(sin(dot(cell, vec3<f32>(12.9898, 78.233, 37.719)) + seed) * 43758.5453);
+}
+
+// brightness of the star at the direction, 0 if there is none
+fn star(sky_direction: vec3<f32>) -> f32 {
+	let pos = sky_direction * STAR_GRID_SIZE;
+	let cell = floor(pos);
+	if (hash(cell, 0.0) > STAR_CHANCE) {
+		return 0.0;
+	}
+
+	// the star is somewhere in its cell, far enough from the edges that it isn't cut off
+	let star_pos = cell + 0.5 + 0.8 * (vec3<f32>(hash(cell, 1.0), hash(cell, 2.0), hash(cell, 3.0)) - 0.5);
+	let brightness = 0.4 + 0.6 * hash(cell, 4.0);
+	return brightness * clamp(1.0 - distance(pos, star_pos) / STAR_RADIUS, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(fragment_in: VertexOutput) -> @location(0) vec4<f32> {
+	let direction = normalize(sky.forward.xyz + fragment_in.screen_pos.x * sky.right.xyz + fragment_in.screen_pos.y * sky.up.xyz);
+
+	// the night sky fades in as the sun sets, and out as it rises
+	let night = smoothstep(0.1, -0.1, sky.sun_direction.y);
+	// stars and the moon fade out close to the horizon
+	let above_horizon = smoothstep(-0.02, 0.1, direction.y);
+
+	let moon_direction = -sky.sun_direction.xyz;
+	let moon = smoothstep(MOON_SIZE - 0.0001, MOON_SIZE + 0.0001, dot(direction, moon_direction));
+	if (moon > 0.0) {
+		return vec4<f32>(MOON_COLOR, moon * night * above_horizon);
+	}
+
+	let sky_direction = (sky.world_to_sky * vec4<f32>(direction, 0.0)).xyz;
+	return vec4<f32>(1.0, 1.0, 1.0, star(sky_direction) * night * above_horizon);
+}
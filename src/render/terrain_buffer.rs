@@ -0,0 +1,168 @@
+use std::mem;
+
+use super::Aabb;
+use super::buffer_pool::{BufferPool, PoolAllocation, PoolStats};
+use crate::game::BlockVertex;
+
+const INITIAL_INDIRECT_CAPACITY: u32 = 1024;
+
+// arguments of an indexed indirect draw, layed out as the gpu expects them
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndexedIndirectArgs {
+	pub index_count: u32,
+	pub instance_count: u32,
+	pub first_index: u32,
+	pub base_vertex: i32,
+	pub first_instance: u32,
+}
+
+// a terrain mesh stored in the shared terrain buffers
+// it must be freed with TerrainBuffers::free_mesh
+#[derive(Debug)]
+pub struct TerrainMesh {
+	vertices: PoolAllocation,
+	indices: PoolAllocation,
+	pub bounding_box: Aabb,
+}
+
+// the vertex and index pages a terrain mesh is stored in, meshes in the same pages can be drawn with 1 bind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TerrainPage {
+	vertex_page: usize,
+	index_page: usize,
+}
+
+impl TerrainMesh {
+	pub(super) fn new(vertices: PoolAllocation, indices: PoolAllocation, bounding_box: Aabb) -> Self {
+		TerrainMesh {
+			vertices,
+			indices,
+			bounding_box,
+		}
+	}
+
+	// draw arguments relative to the start of the mesh's pages
+	pub fn draw_args(&self) -> DrawIndexedIndirectArgs {
+		DrawIndexedIndirectArgs {
+			index_count: self.indices.range.len,
+			instance_count: 1,
+			first_index: self.indices.range.start,
+			base_vertex: self.vertices.range.start as i32,
+			first_instance: 0,
+		}
+	}
+
+	pub fn page(&self) -> TerrainPage {
+		TerrainPage {
+			vertex_page: self.vertices.page,
+			index_page: self.indices.page,
+		}
+	}
+
+	pub fn triangle_count(&self) -> u32 {
+		self.indices.range.len / 3
+	}
+
+	pub fn vertex_count(&self) -> u32 {
+		self.vertices.range.len
+	}
+
+	// bytes of the vertex and index buffers used by this mesh
+	pub fn byte_size(&self) -> u64 {
+		self.vertices.range.len as u64 * mem::size_of::<BlockVertex>() as u64
+			+ self.indices.range.len as u64 * mem::size_of::<u32>() as u64
+	}
+}
+
+// pools of vertex and index buffers shared by all terrain meshes, so a single bind covers many terrain draws
+#[derive(Debug)]
+pub struct TerrainBuffers {
+	vertices: BufferPool,
+	indices: BufferPool,
+	indirect_buffer: wgpu::Buffer,
+	indirect_capacity: u32,
+}
+
+impl TerrainBuffers {
+	pub fn new(device: &wgpu::Device) -> Self {
+		TerrainBuffers {
+			vertices: BufferPool::new(
+				"terrain vertex pool",
+				mem::size_of::<BlockVertex>() as u64,
+				wgpu::BufferUsages::VERTEX,
+			),
+			indices: BufferPool::new(
+				"terrain index pool",
+				mem::size_of::<u32>() as u64,
+				wgpu::BufferUsages::INDEX,
+			),
+			indirect_buffer: Self::create_indirect_buffer(device, INITIAL_INDIRECT_CAPACITY),
+			indirect_capacity: INITIAL_INDIRECT_CAPACITY,
+		}
+	}
+
+	fn create_indirect_buffer(device: &wgpu::Device, capacity: u32) -> wgpu::Buffer {
+		device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("terrain indirect buffer"),
+			size: capacity as u64 * mem::size_of::<DrawIndexedIndirectArgs>() as u64,
+			usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		})
+	}
+
+	// indices are relative to the start of this mesh's vertices
+	pub fn alloc_mesh(
+		&mut self,
+		device: &wgpu::Device,
+		queue: &wgpu::Queue,
+		vertices: &[BlockVertex],
+		indices: &[u32],
+		bounding_box: Aabb,
+	) -> TerrainMesh {
+		TerrainMesh::new(
+			self.vertices.alloc_write(device, queue, bytemuck::cast_slice(vertices)),
+			self.indices.alloc_write(device, queue, bytemuck::cast_slice(indices)),
+			bounding_box,
+		)
+	}
+
+	pub fn free_mesh(&mut self, mesh: TerrainMesh) {
+		self.vertices.free(mesh.vertices);
+		self.indices.free(mesh.indices);
+	}
+
+	pub fn vertex_buffer(&self, page: TerrainPage) -> &wgpu::Buffer {
+		self.vertices.page(page.vertex_page)
+	}
+
+	pub fn index_buffer(&self, page: TerrainPage) -> &wgpu::Buffer {
+		self.indices.page(page.index_page)
+	}
+
+	// stats of the vertex pool and index pool
+	pub fn stats(&self) -> (PoolStats, PoolStats) {
+		(self.vertices.stats(), self.indices.stats())
+	}
+
+	// bytes of gpu memory used by all terrain buffers, including free space in the pools
+	pub fn size_bytes(&self) -> u64 {
+		self.vertices.size_bytes()
+			+ self.indices.size_bytes()
+			+ self.indirect_capacity as u64 * mem::size_of::<DrawIndexedIndirectArgs>() as u64
+	}
+
+	pub fn indirect_buffer(&self) -> &wgpu::Buffer {
+		&self.indirect_buffer
+	}
+
+	// writes the draws to the start of the indirect buffer
+	pub fn write_indirect(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, draws: &[DrawIndexedIndirectArgs]) {
+		if draws.len() as u32 > self.indirect_capacity {
+			self.indirect_capacity = (draws.len() as u32).next_power_of_two();
+			self.indirect_buffer = Self::create_indirect_buffer(device, self.indirect_capacity);
+		}
+
+		queue.write_buffer(&self.indirect_buffer, 0, bytemuck::cast_slice(draws));
+	}
+}
@@ -1,7 +1,9 @@
-use std::{num::NonZeroU32, path::Path};
+use std::{num::NonZeroU32, path::Path, sync::Arc};
 
 use image::{DynamicImage, GenericImageView};
 use anyhow::*;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
 
 use crate::assets::loader;
 use super::RenderContext;
@@ -88,6 +90,126 @@ impl Texture {
 			view,
 		}
 	}
+
+	// creates a 2d array texture with each image as a layer, all images must be the same size
+	pub fn array_from_images(
+		images: &[DynamicImage],
+		label: &str,
+		context: RenderContext,
+	) -> Result<Self> {
+		let dimensions = images.first()
+			.ok_or_else(|| anyhow!("cannot create texture array {} with no images", label))?
+			.dimensions();
+
+		let texture_size = wgpu::Extent3d {
+			width: dimensions.0,
+			height: dimensions.1,
+			depth_or_array_layers: images.len() as u32,
+		};
+
+		let texture = context.device.create_texture(
+			&wgpu::TextureDescriptor {
+				label: Some(label),
+				size: texture_size,
+				mip_level_count: 1,
+				sample_count: 1,
+				dimension: wgpu::TextureDimension::D2,
+				format: wgpu::TextureFormat::Rgba8UnormSrgb,
+				usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+			}
+		);
+
+		for (layer, image) in images.iter().enumerate() {
+			if image.dimensions() != dimensions {
+				bail!(
+					"texture {} of texture array {} is {:?}, but all textures must be {:?}",
+					layer,
+					label,
+					image.dimensions(),
+					dimensions,
+				);
+			}
+
+			let rgba = image.to_rgba8();
+
+			context.queue.write_texture(
+				wgpu::ImageCopyTexture {
+					texture: &texture,
+					mip_level: 0,
+					origin: wgpu::Origin3d {
+						x: 0,
+						y: 0,
+						z: layer as u32,
+					},
+					aspect: wgpu::TextureAspect::All,
+				},
+				&rgba,
+				wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: NonZeroU32::new(4 * dimensions.0),
+					rows_per_image: NonZeroU32::new(dimensions.1),
+				},
+				wgpu::Extent3d {
+					depth_or_array_layers: 1,
+					..texture_size
+				},
+			);
+		}
+
+		// a texture with 1 layer would get a 2d view by default
+		let view = texture.create_view(&wgpu::TextureViewDescriptor {
+			dimension: Some(wgpu::TextureViewDimension::D2Array),
+			..Default::default()
+		});
+
+		Ok(Self {
+			texture,
+			view,
+		})
+	}
+}
+
+// how a texture is filtered when it is sampled between texels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+	// keeps pixel art textures sharp
+	Nearest,
+	Linear,
+}
+
+impl TextureFilter {
+	fn filter_mode(&self) -> wgpu::FilterMode {
+		match self {
+			TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+			TextureFilter::Linear => wgpu::FilterMode::Linear,
+		}
+	}
+}
+
+// samplers are shared between all textures which are filtered the same way
+#[derive(Debug, Default)]
+pub struct SamplerCache {
+	samplers: Mutex<FxHashMap<TextureFilter, Arc<wgpu::Sampler>>>,
+}
+
+impl SamplerCache {
+	// creates the sampler the first time the filter is used
+	pub fn get(&self, device: &wgpu::Device, filter: TextureFilter) -> Arc<wgpu::Sampler> {
+		self.samplers.lock().entry(filter).or_insert_with(|| {
+			let filter_mode = filter.filter_mode();
+
+			Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
+				label: Some(&format!("{:?} sampler", filter)),
+				address_mode_u: wgpu::AddressMode::ClampToEdge,
+				address_mode_v: wgpu::AddressMode::ClampToEdge,
+				address_mode_w: wgpu::AddressMode::ClampToEdge,
+				mag_filter: filter_mode,
+				min_filter: filter_mode,
+				mipmap_filter: filter_mode,
+				..Default::default()
+			}))
+		}).clone()
+	}
 }
 
 #[derive(Debug)]
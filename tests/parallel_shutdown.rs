@@ -0,0 +1,28 @@
+// the task queues and workers are global, so shutting them down runs in its own process
+// where it can't throw away the tasks of tests running next to it
+
+use std::sync::Arc;
+
+use minecone2::game::types::ChunkPos;
+use minecone2::game::parallel::{self, Task};
+use minecone2::game::world::World;
+use minecone2::game::worldgen::{GeneratorType, GeneratorOptions};
+
+#[test]
+fn shutdown_releases_world() {
+	let directory = std::env::temp_dir().join(format!("minecone-shutdown-test-{}", std::process::id()));
+	let world = World::open(&directory, 0, GeneratorType::Normal, GeneratorOptions::default()).unwrap();
+	parallel::init(world.clone(), 2);
+	assert_eq!(parallel::worker_count(), 2);
+
+	parallel::run_task(Task::GenerateChunk(ChunkPos::new(100, 0, 100)));
+	parallel::shutdown();
+	assert_eq!(parallel::worker_count(), 0);
+
+	// generated chunks also reference the world
+	world.close();
+	assert_eq!(Arc::strong_count(&world), 1);
+
+	drop(world);
+	let _ = std::fs::remove_dir_all(directory);
+}
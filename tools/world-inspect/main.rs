@@ -0,0 +1,91 @@
+// prints statistics about the chunks saved in a world, without opening a window
+// usage: world-inspect <world directory>
+
+use std::cmp::Reverse;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use minecone2::game::world::World;
+use minecone2::game::world_list::WorldEntry;
+use minecone2::game::block::BlockType;
+use minecone2::game::types::BlockPos;
+use minecone2::game::CHUNK_SIZE;
+
+// totals over every saved chunk
+#[derive(Debug, Default)]
+struct ChunkStats {
+	chunks: usize,
+	// chunks whose data could not be read
+	unreadable_chunks: usize,
+	block_entities: usize,
+	entities: usize,
+	// indexed by block type
+	block_counts: Vec<u64>,
+}
+
+fn chunk_stats(world: &World) -> Result<ChunkStats> {
+	let mut stats = ChunkStats {
+		block_counts: vec![0; BlockType::ALL.len()],
+		..ChunkStats::default()
+	};
+
+	for chunk_pos in world.regions().saved_chunks()? {
+		let (chunk, entities) = match world.load_saved_chunk(chunk_pos) {
+			Some(chunk) => chunk,
+			None => {
+				stats.unreadable_chunks += 1;
+				continue;
+			},
+		};
+
+		stats.chunks += 1;
+		stats.block_entities += chunk.chunk.block_entity_count();
+		stats.entities += entities.len();
+		for x in 0..CHUNK_SIZE as i32 {
+			for y in 0..CHUNK_SIZE as i32 {
+				for z in 0..CHUNK_SIZE as i32 {
+					let block_type = chunk.chunk.get_block(BlockPos::new(x, y, z)).block_type();
+					stats.block_counts[block_type as usize] += 1;
+				}
+			}
+		}
+	}
+
+	Ok(stats)
+}
+
+fn main() -> Result<()> {
+	let directory = std::env::args().nth(1)
+		.map(PathBuf::from)
+		.ok_or_else(|| anyhow!("usage: world-inspect <world directory>"))?;
+
+	let entry = WorldEntry::load(&directory)?;
+	let meta = &entry.meta;
+	let world = World::open(&entry.directory, meta.seed, meta.generator, meta.generator_options)?;
+	println!("{} (seed {}, {} terrain)", meta.name, meta.seed, meta.generator.name());
+
+	let regions = world.regions().saved_regions()?;
+	let mut region_bytes = 0;
+	for region in regions.iter() {
+		region_bytes += world.regions().region_len(*region)?;
+	}
+	println!("{} region files, {} KiB", regions.len(), region_bytes / 1024);
+
+	let stats = chunk_stats(&world)?;
+	println!("{} saved chunks, {} could not be read", stats.chunks, stats.unreadable_chunks);
+	println!("{} block entities, {} entities", stats.block_entities, stats.entities);
+
+	let total_blocks = stats.block_counts.iter().sum::<u64>().max(1);
+	let mut block_counts = BlockType::ALL.iter()
+		.map(|block_type| (*block_type, stats.block_counts[*block_type as usize]))
+		.filter(|(_, count)| *count > 0)
+		.collect::<Vec<_>>();
+	block_counts.sort_by_key(|(_, count)| Reverse(*count));
+	for (block_type, count) in block_counts {
+		println!("{:>12} {:>12} {:>6.2}%", block_type.name(), count, 100.0 * count as f64 / total_blocks as f64);
+	}
+
+	world.close();
+	Ok(())
+}